@@ -0,0 +1,508 @@
+# Type stubs for the `cel` extension module, shipped in the wheel by maturin as
+# a stub-only package, which lets its submodules have stubs of their own.
+# tests/test_stubs.py checks them against the signatures of the compiled module.
+import datetime
+from collections.abc import Callable, Iterable, Iterator, Mapping, Sequence
+from concurrent.futures import Future
+from typing import IO, Any, Literal, TypeAlias, TypedDict, final, overload
+
+_Mode: TypeAlias = Literal["python", "strict"] | Options
+_EvaluationContext: TypeAlias = Context | dict[str, Any]
+_OnError: TypeAlias = Literal["raise", "return"]
+_Output: TypeAlias = Literal["python", "cel"]
+_LogLevel: TypeAlias = str | int
+
+FEATURES: tuple[str, ...]
+
+class _Completion(TypedDict):
+    text: str
+    kind: Literal["variable", "field", "function", "macro", "keyword"]
+    start: int
+
+class _Token(TypedDict):
+    kind: str
+    text: str
+    start: int
+    end: int
+
+class _CacheInfo(TypedDict):
+    hits: int
+    misses: int
+    size: int
+    capacity: int
+
+class _Stats(TypedDict):
+    compiles: int
+    evaluations: int
+    evaluation_seconds: float
+    cache_hits: int
+    cache_misses: int
+    errors: dict[str, int]
+
+class _FunctionInfo(TypedDict):
+    signature: str | None
+    doc: str | None
+    builtin: bool
+
+class _MappedError(TypedDict, total=False):
+    message: str
+    absorbable: bool
+
+@overload
+def evaluate(
+    src: str,
+    evaluation_context: _EvaluationContext | None = None,
+    safe_navigation: bool | None = None,
+    on_error: Literal["raise"] = "raise",
+    unknowns: Sequence[str] | None = None,
+    output: Literal["python"] = "python",
+    mode: _Mode | None = None,
+    max_result_size: int | None = None,
+) -> Any: ...
+@overload
+def evaluate(
+    src: str,
+    evaluation_context: _EvaluationContext | None = None,
+    safe_navigation: bool | None = None,
+    on_error: Literal["raise"] = "raise",
+    unknowns: Sequence[str] | None = None,
+    *,
+    output: Literal["cel"],
+    mode: _Mode | None = None,
+    max_result_size: int | None = None,
+) -> Value | Unknown: ...
+@overload
+def evaluate(
+    src: str,
+    evaluation_context: _EvaluationContext | None = None,
+    safe_navigation: bool | None = None,
+    *,
+    on_error: Literal["return"],
+    unknowns: Sequence[str] | None = None,
+    output: Literal["cel"],
+    mode: _Mode | None = None,
+    max_result_size: int | None = None,
+) -> Value | Unknown | EvalError: ...
+@overload
+def evaluate(
+    src: str,
+    evaluation_context: _EvaluationContext | None = None,
+    safe_navigation: bool | None = None,
+    on_error: _OnError = "raise",
+    unknowns: Sequence[str] | None = None,
+    output: _Output = "python",
+    mode: _Mode | None = None,
+    max_result_size: int | None = None,
+) -> Any: ...
+def evaluate_predicate(
+    src: str,
+    evaluation_context: _EvaluationContext | None = None,
+    safe_navigation: bool | None = None,
+    unknowns: Sequence[str] | None = None,
+    mode: _Mode | None = None,
+) -> bool: ...
+def plan(expressions: Mapping[str, str | Program] | Iterable[tuple[str, str | Program]]) -> Dataflow: ...
+def compile_many(
+    expressions: Mapping[str, str | Program] | Iterable[tuple[str, str | Program]], optimize: bool = False
+) -> ProgramSet: ...
+def complete(
+    expression: str, cursor: int | None = None, context: _EvaluationContext | None = None
+) -> list[_Completion]: ...
+def tokenize(expression: str) -> list[_Token]: ...
+def parse_lenient(expression: str) -> tuple[Program | None, list[Diagnostic]]: ...
+def diagnose(
+    expression: str, env: _EvaluationContext | None = None, uri: str = ""
+) -> list[dict[str, Any]]: ...
+def register_type(
+    name: str,
+    cls: type,
+    fields: Iterable[str] | None = None,
+    methods: Iterable[str] | None = None,
+) -> None: ...
+def register_global_function(name: str, function: Callable[..., Any]) -> None: ...
+def unregister_global_function(name: str) -> None: ...
+def migrate(
+    exprs: Iterable[str], from_mode: _Mode | None = None, to_mode: _Mode | None = None
+) -> list[Migration]: ...
+def explain(
+    expr: str, evaluation_context: _EvaluationContext | None = None, mode: _Mode | None = None
+) -> Explanation: ...
+def suggest_inputs(expr: str, mode: _Mode | None = None) -> list[dict[str, Any]]: ...
+def configure_logging(
+    level: _LogLevel | None = None,
+    target: str | None = None,
+    json: bool | None = None,
+    levels: Mapping[str, _LogLevel] | None = None,
+    stream: IO[str] | None = None,
+) -> None: ...
+def stats() -> _Stats: ...
+
+@final
+class Context:
+    safe_navigation: bool
+    memoize: bool
+    def __init__(
+        self,
+        variables: dict[str, Any] | None = None,
+        functions: dict[str, Callable[..., Any]] | None = None,
+        safe_navigation: bool = False,
+        output_types: dict[str, str] | None = None,
+        mode: _Mode | None = None,
+        namedtuples_as_maps: bool = False,
+        bytes_keys: Literal["error", "decode"] = "error",
+        objects: Literal["error", "attributes"] = "error",
+        round_trip: bool = False,
+        memoize: bool = False,
+        extensions: Sequence[str] | None = None,
+        clock: datetime.datetime | None = None,
+        seed: int | None = None,
+    ) -> None: ...
+    @property
+    def mode(self) -> Options: ...
+    @mode.setter
+    def mode(self, mode: _Mode) -> None: ...
+    @property
+    def namedtuples_as_maps(self) -> bool: ...
+    @property
+    def extensions(self) -> list[str]: ...
+    @property
+    def clock(self) -> datetime.datetime | None: ...
+    @property
+    def seed(self) -> int | None: ...
+    @property
+    def bytes_keys(self) -> Literal["error", "decode"]: ...
+    @property
+    def objects(self) -> Literal["error", "attributes"]: ...
+    @property
+    def round_trip(self) -> bool: ...
+    def add_function(
+        self, name: str, function: Callable[..., Any], timeout: float | None = None, batch: bool = False
+    ) -> None: ...
+    def add_variable(self, name: str, value: Any) -> None: ...
+    def update_variable(self, name: str, value: Any) -> None: ...
+    def memory_usage(self) -> int: ...
+    def functions_info(self) -> dict[str, _FunctionInfo]: ...
+    def set_error_mapper(
+        self, mapper: Callable[[str, BaseException], str | _MappedError | None] | None
+    ) -> None: ...
+    def set_now(self, now: datetime.datetime | None) -> None: ...
+    def remove_variable(self, name: str) -> None: ...
+    def remove_function(self, name: str) -> None: ...
+    def clear(self) -> None: ...
+    def update(
+        self,
+        variables: dict[str, Any] | None = None,
+        force_variable: bool = False,
+        functions: dict[str, Callable[..., Any]] | None = None,
+    ) -> None: ...
+    def __enter__(self) -> Context: ...
+    def __exit__(self, *_args: object) -> bool: ...
+
+@final
+class Program:
+    def __init__(
+        self, source: str, optimize: bool = False, parallel_threshold: int | None = None, name: str | None = None
+    ) -> None: ...
+    @staticmethod
+    def compile(
+        source: str, optimize: bool = False, parallel_threshold: int | None = None, name: str | None = None
+    ) -> Program: ...
+    @staticmethod
+    def loads(data: bytes) -> Program: ...
+    @property
+    def source(self) -> str: ...
+    @property
+    def name(self) -> str | None: ...
+    @property
+    def optimized(self) -> bool: ...
+    @overload
+    def evaluate(
+        self,
+        evaluation_context: _EvaluationContext | None = None,
+        safe_navigation: bool | None = None,
+        on_error: Literal["raise"] = "raise",
+        unknowns: Sequence[str] | None = None,
+        output: Literal["python"] = "python",
+        mode: _Mode | None = None,
+        cache: bool = False,
+        max_result_size: int | None = None,
+        coverage: Coverage | None = None,
+    ) -> Any: ...
+    @overload
+    def evaluate(
+        self,
+        evaluation_context: _EvaluationContext | None = None,
+        safe_navigation: bool | None = None,
+        on_error: Literal["raise"] = "raise",
+        unknowns: Sequence[str] | None = None,
+        *,
+        output: Literal["cel"],
+        mode: _Mode | None = None,
+        cache: bool = False,
+        max_result_size: int | None = None,
+        coverage: Coverage | None = None,
+    ) -> Value | Unknown: ...
+    @overload
+    def evaluate(
+        self,
+        evaluation_context: _EvaluationContext | None = None,
+        safe_navigation: bool | None = None,
+        *,
+        on_error: Literal["return"],
+        unknowns: Sequence[str] | None = None,
+        output: Literal["cel"],
+        mode: _Mode | None = None,
+        cache: bool = False,
+        max_result_size: int | None = None,
+        coverage: Coverage | None = None,
+    ) -> Value | Unknown | EvalError: ...
+    @overload
+    def evaluate(
+        self,
+        evaluation_context: _EvaluationContext | None = None,
+        safe_navigation: bool | None = None,
+        on_error: _OnError = "raise",
+        unknowns: Sequence[str] | None = None,
+        output: _Output = "python",
+        mode: _Mode | None = None,
+        cache: bool = False,
+        max_result_size: int | None = None,
+        coverage: Coverage | None = None,
+    ) -> Any: ...
+    def cache_info(self) -> _CacheInfo: ...
+    def clear_cache(self) -> None: ...
+    def memory_usage(self) -> int: ...
+    def is_constant(self) -> bool: ...
+    def constant_value(self, mode: _Mode | None = None) -> Any: ...
+    def fingerprint(self) -> str: ...
+    def dumps(self) -> bytes: ...
+
+@final
+class ProgramSet:
+    def __init__(
+        self,
+        expressions: Mapping[str, str | Program] | Iterable[tuple[str, str | Program]],
+        optimize: bool = False,
+    ) -> None: ...
+    @property
+    def names(self) -> list[str]: ...
+    @property
+    def variables(self) -> list[str]: ...
+    @property
+    def functions(self) -> list[str]: ...
+    def evaluate_all(
+        self,
+        evaluation_context: _EvaluationContext | None = None,
+        on_error: _OnError = "raise",
+        output: _Output = "python",
+        mode: _Mode | None = None,
+    ) -> dict[str, Any]: ...
+    def project_context(self, evaluation_context: dict[str, Any]) -> dict[str, Any]: ...
+    def __len__(self) -> int: ...
+    def __contains__(self, name: str) -> bool: ...
+    def __getitem__(self, name: str) -> Program: ...
+
+@final
+class Evaluator:
+    def __init__(self, workers: int | None = None) -> None: ...
+    @property
+    def workers(self) -> int: ...
+    def submit(
+        self,
+        program: Program | str,
+        evaluation_context: _EvaluationContext | None = None,
+        safe_navigation: bool | None = None,
+        on_error: _OnError = "raise",
+        unknowns: Sequence[str] | None = None,
+        output: _Output = "python",
+        mode: _Mode | None = None,
+        max_result_size: int | None = None,
+    ) -> Future[Any]: ...
+    def map(
+        self,
+        program: Program | str,
+        contexts: Iterable[_EvaluationContext | None],
+        safe_navigation: bool | None = None,
+        on_error: _OnError = "raise",
+        unknowns: Sequence[str] | None = None,
+        output: _Output = "python",
+        mode: _Mode | None = None,
+        max_result_size: int | None = None,
+    ) -> list[Any]: ...
+    def shutdown(self, wait: bool = True) -> None: ...
+    def __enter__(self) -> Evaluator: ...
+    def __exit__(self, *_args: object) -> bool: ...
+
+@final
+class Dataflow:
+    @property
+    def order(self) -> list[str]: ...
+    def evaluate(
+        self, evaluation_context: _EvaluationContext | None = None, mode: _Mode | None = None
+    ) -> dict[str, Any]: ...
+
+@final
+class Mapper:
+    def __init__(
+        self,
+        fields: Mapping[str, str | Program] | Iterable[tuple[str, str | Program]],
+        mode: _Mode | None = None,
+    ) -> None: ...
+    @property
+    def fields(self) -> list[str]: ...
+    def apply(self, record: dict[str, Any]) -> dict[str, Any]: ...
+    def apply_many(self, records: Iterable[dict[str, Any]]) -> list[dict[str, Any]]: ...
+
+@final
+class Validator:
+    def __init__(self, rules: Iterable[Mapping[str, str | None]], mode: _Mode | None = None) -> None: ...
+    def validate(self, obj: Any) -> list[Violation]: ...
+    def is_valid(self, obj: Any) -> bool: ...
+
+@final
+class Violation:
+    @property
+    def id(self) -> str: ...
+    @property
+    def message(self) -> str: ...
+    @property
+    def path(self) -> str | None: ...
+    @property
+    def reason(self) -> Literal["invalid", "error"]: ...
+
+@final
+class Diagnostic:
+    @property
+    def message(self) -> str: ...
+    @property
+    def start(self) -> int: ...
+    @property
+    def end(self) -> int: ...
+
+@final
+class EvalError:
+    @property
+    def kind(self) -> Literal["compile", "execution"]: ...
+    @property
+    def message(self) -> str: ...
+    @property
+    def expression(self) -> str: ...
+    @property
+    def position(self) -> int | None: ...
+    @property
+    def name(self) -> str | None: ...
+
+@final
+class Unknown:
+    @property
+    def attributes(self) -> list[str]: ...
+
+@final
+class Value:
+    @property
+    def type(self) -> str: ...
+    def to_python(self) -> Any: ...
+    def __getitem__(self, key: Any) -> Value: ...
+    def __contains__(self, item: Any) -> bool: ...
+    def __len__(self) -> int: ...
+    def __iter__(self) -> Iterator[Value]: ...
+    def __bool__(self) -> bool: ...
+
+@final
+class Duration:
+    def __init__(self, value: str | datetime.timedelta) -> None: ...
+    @property
+    def nanoseconds(self) -> int: ...
+    def total_seconds(self) -> float: ...
+    def to_timedelta(self) -> datetime.timedelta: ...
+    def __lt__(self, other: Duration | datetime.timedelta) -> bool: ...
+    def __le__(self, other: Duration | datetime.timedelta) -> bool: ...
+    def __gt__(self, other: Duration | datetime.timedelta) -> bool: ...
+    def __ge__(self, other: Duration | datetime.timedelta) -> bool: ...
+
+@final
+class CelType:
+    def __init__(self, name: str) -> None: ...
+    @property
+    def name(self) -> str: ...
+    def __hash__(self) -> int: ...
+
+@final
+class Options:
+    def __init__(
+        self,
+        *,
+        numeric_promotion: bool = True,
+        truthiness: bool = True,
+        safe_navigation: bool = False,
+        heterogeneous_equality: bool = True,
+        lenient_timestamps: bool = True,
+        duplicate_map_keys: bool = True,
+        error_absorption: bool = True,
+    ) -> None: ...
+    @staticmethod
+    def python() -> Options: ...
+    @staticmethod
+    def strict() -> Options: ...
+    @property
+    def numeric_promotion(self) -> bool: ...
+    @property
+    def truthiness(self) -> bool: ...
+    @property
+    def safe_navigation(self) -> bool: ...
+    @property
+    def heterogeneous_equality(self) -> bool: ...
+    @property
+    def lenient_timestamps(self) -> bool: ...
+    @property
+    def duplicate_map_keys(self) -> bool: ...
+    @property
+    def error_absorption(self) -> bool: ...
+    def __hash__(self) -> int: ...
+
+@final
+class Migration:
+    @property
+    def original(self) -> str: ...
+    @property
+    def expression(self) -> str: ...
+    @property
+    def changes(self) -> list[str]: ...
+    @property
+    def issues(self) -> list[str]: ...
+    @property
+    def changed(self) -> bool: ...
+    @property
+    def needs_attention(self) -> bool: ...
+
+@final
+class Explanation:
+    @property
+    def expression(self) -> str: ...
+    @property
+    def value(self) -> Any: ...
+    @property
+    def clauses(self) -> list[Explanation]: ...
+    @property
+    def values(self) -> dict[str, Any]: ...
+
+@final
+class Coverage:
+    def __init__(self) -> None: ...
+    @property
+    def expressions(self) -> list[str]: ...
+    @property
+    def node_coverage(self) -> float: ...
+    @property
+    def branch_coverage(self) -> float: ...
+    def missing(self) -> list[tuple[str, str, Literal["never evaluated", "never true", "never false"]]]: ...
+    def report(self) -> str: ...
+
+class ConversionError(ValueError): ...
+class ConversionRangeError(ConversionError, OverflowError): ...
+class ConversionTypeError(ConversionError, TypeError): ...
+class LimitExceeded(ValueError): ...
+class CELWarning(UserWarning): ...
+class CELPromotionWarning(CELWarning): ...
+class CELTruthinessWarning(CELWarning): ...
+class CELDuplicateKeyWarning(CELWarning): ...
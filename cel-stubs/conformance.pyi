@@ -0,0 +1,33 @@
+# Type stubs for `cel.conformance`
+from collections.abc import Iterable
+from typing import Any, Literal, final
+
+from cel import _Mode
+
+@final
+class RemoteService:
+    def __init__(self, url: str, timeout: float = 10.0) -> None: ...
+    @property
+    def url(self) -> str: ...
+    @property
+    def timeout(self) -> float: ...
+    def parse(self, expr: str) -> dict[str, Any]: ...
+    def check(self, expr: str) -> dict[str, Any]: ...
+    def evaluate(self, expr: str, variables: dict[str, Any] | None = None) -> Any: ...
+
+@final
+class Divergence:
+    @property
+    def expression(self) -> str: ...
+    @property
+    def kind(self) -> Literal["compile", "error", "value"]: ...
+    @property
+    def local(self) -> Any: ...
+    @property
+    def remote(self) -> Any: ...
+
+def compare(
+    service: RemoteService,
+    expressions: Iterable[str | tuple[str, dict[str, Any]]],
+    mode: _Mode | None = None,
+) -> list[Divergence]: ...
@@ -0,0 +1,20 @@
+# Type stubs for `cel.fuzz`
+from typing import Any, Literal, final
+
+from cel import _EvaluationContext
+from cel.conformance import RemoteService
+
+@final
+class Divergence:
+    @property
+    def expression(self) -> str: ...
+    @property
+    def kind(self) -> Literal["compile", "error", "value"]: ...
+    @property
+    def results(self) -> dict[Literal["python", "strict", "reference"], Any]: ...
+    @property
+    def options(self) -> list[str]: ...
+
+def compare(
+    expr: str, context: _EvaluationContext | None = None, reference: RemoteService | None = None
+) -> Divergence | None: ...
@@ -0,0 +1,20 @@
+# Type stubs for `cel.sandbox`
+from collections.abc import Callable
+from typing import Any
+
+from cel import LimitExceeded as LimitExceeded
+from cel import _EvaluationContext
+
+def evaluate(
+    expression: str,
+    context: _EvaluationContext | None = None,
+    *,
+    functions: dict[str, Callable[..., Any]] | None = None,
+    max_length: int = 1000,
+    max_depth: int = 50,
+    max_cost: int = 100_000,
+    timeout: float = 1.0,
+    max_result_size: int = 100_000,
+) -> Any: ...
+def time_remaining() -> float | None: ...
+def cost_remaining() -> int | None: ...
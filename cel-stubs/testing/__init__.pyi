@@ -0,0 +1,48 @@
+# Type stubs for `cel.testing`
+import os
+import pathlib
+from collections.abc import Iterable, Mapping
+from typing import Any, final
+
+from cel import _EvaluationContext, _Mode
+
+from . import strategies as strategies
+
+_Case = Mapping[str, Any] | tuple[str, Any] | tuple[str, _EvaluationContext | None, Any]
+
+def assert_evaluates(
+    expr: str, context: _EvaluationContext | None, expected: Any, mode: _Mode | None = None
+) -> None: ...
+def run_cases(
+    cases: Iterable[_Case], context: _EvaluationContext | None = None, mode: _Mode | None = None
+) -> None: ...
+def assert_trace(
+    path: str | os.PathLike[str],
+    expr: str,
+    context: _EvaluationContext | None = None,
+    mode: _Mode | None = None,
+    update: bool = False,
+) -> None: ...
+def mutants(expr: str) -> list[Mutant]: ...
+
+# pytest fixtures, made when pytest first looks them up
+def cel_mode() -> _Mode | None: ...
+def cel_golden(request: Any, cel_mode: _Mode | None = None) -> Golden: ...
+
+@final
+class Golden:
+    def __init__(self, directory: str | os.PathLike[str], name: str, mode: _Mode | None = None) -> None: ...
+    @property
+    def directory(self) -> pathlib.Path: ...
+    @property
+    def name(self) -> str: ...
+    def __call__(self, expr: str, context: _EvaluationContext | None = None, name: str | None = None) -> None: ...
+
+@final
+class Mutant:
+    @property
+    def expression(self) -> str: ...
+    @property
+    def original(self) -> str: ...
+    @property
+    def replacement(self) -> str: ...
@@ -0,0 +1,8 @@
+# Type stubs for `cel.testing.strategies`, which needs Hypothesis
+from collections.abc import Mapping
+from typing import Any
+
+from hypothesis.strategies import SearchStrategy
+
+def contexts(declarations: Mapping[str, Any]) -> SearchStrategy[dict[str, Any]]: ...
+def values(cel_type: str) -> SearchStrategy[Any]: ...
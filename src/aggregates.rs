@@ -0,0 +1,188 @@
+//! `sum()`, `avg()`, `min()` and `max()` of the items of a list or the values
+//! of a map, called either as functions or as methods, e.g. `sum(prices)` or
+//! `prices.sum()`. `count(x, predicate)` is a macro, see [`crate::plan`].
+//!
+//! `min()` and `max()` replace the interpreter's, which only take lists, and
+//! compare ints and uints with doubles by their exact values, as `<` does.
+use crate::arithmetic;
+use crate::types::{is_hidden, name_of};
+use cel_interpreter::objects::Map;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use std::cmp::Ordering;
+use std::mem::discriminant;
+
+/// The items of the list or values of the map a function is called on or with
+fn items(ftx: &FunctionContext) -> Result<Vec<Value>, ExecutionError> {
+    let collection = match (&ftx.this, &ftx.args[..]) {
+        (Some(this), []) => this.clone(),
+        (None, [arg]) => ftx.ptx.resolve(arg)?,
+        (Some(_), args) | (None, args) => {
+            return Err(ExecutionError::invalid_argument_count(1, args.len()))
+        }
+    };
+    match collection {
+        Value::List(items) => Ok(items.to_vec()),
+        Value::Map(map) => Ok(values(&map)),
+        other => Err(ftx.error(format!("expected a list or map, got {}", name_of(&other)))),
+    }
+}
+
+/// The values of a map, without the type of a value of a registered type
+fn values(map: &Map) -> Vec<Value> {
+    let fields = map.map.iter().filter(|(key, _)| !is_hidden(map, key));
+    fields.map(|(_, value)| value.clone()).collect()
+}
+
+fn is_number(value: &Value) -> bool {
+    matches!(value, Value::Int(_) | Value::UInt(_) | Value::Float(_))
+}
+
+/// The name of a value's type in errors
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Duration(_) => "duration",
+        Value::Timestamp(_) => "timestamp",
+        other => name_of(other),
+    }
+}
+
+fn as_double(value: &Value) -> f64 {
+    match value {
+        Value::Int(i) => *i as f64,
+        Value::UInt(u) => *u as f64,
+        Value::Float(f) => *f,
+        _ => unreachable!("only called on numbers"),
+    }
+}
+
+/// Checks that the items are numbers, or durations if `durations`, and
+/// returns whether they are of more than one type, which is only allowed with
+/// numeric promotion
+fn mixed(
+    ftx: &FunctionContext,
+    items: &[Value],
+    durations: bool,
+    promotion: bool,
+    verb: &str,
+) -> Result<bool, ExecutionError> {
+    let Some(first) = items.first() else {
+        return Ok(false);
+    };
+    for item in items {
+        let allowed = is_number(item) || (durations && matches!(item, Value::Duration(_)));
+        if !allowed {
+            return Err(ftx.error(format!("can't {} {}", verb, kind(item))));
+        }
+        if discriminant(item) == discriminant(first) {
+            continue;
+        }
+        if !is_number(first) || !is_number(item) {
+            return Err(ftx.error(format!("can't {} {} and {}", verb, kind(first), kind(item))));
+        }
+        if !promotion {
+            return Err(ftx.error(format!(
+                "can't {} {} and {} without numeric promotion",
+                verb,
+                kind(first),
+                kind(item)
+            )));
+        }
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Returns the sum of numbers or durations, 0 if there are none. Ints and
+/// uints fail on overflow, and a mix of number types is summed as doubles.
+///
+/// # Examples
+/// ```cel
+/// sum(order.items.map(i, i.price * i.quantity)) > 100.0
+/// ```
+pub fn sum(ftx: &FunctionContext, promotion: bool) -> ResolveResult {
+    let items = items(ftx)?;
+    if mixed(ftx, &items, true, promotion, "sum")? {
+        return Ok(Value::Float(items.iter().map(as_double).sum()));
+    }
+    let mut items = items.into_iter();
+    let first = items.next().unwrap_or(Value::Int(0));
+    items.try_fold(first, arithmetic::add)
+}
+
+/// Returns the mean of numbers as a double, failing if there are none.
+///
+/// # Examples
+/// ```cel
+/// scores.avg() >= 3.5
+/// ```
+pub fn avg(ftx: &FunctionContext, promotion: bool) -> ResolveResult {
+    let items = items(ftx)?;
+    if items.is_empty() {
+        return Err(ftx.error("can't average no values"));
+    }
+    mixed(ftx, &items, false, promotion, "average")?;
+    let total: f64 = items.iter().map(as_double).sum();
+    Ok(Value::Float(total / items.len() as f64))
+}
+
+/// The arguments of `min()` or `max()`: the items of a list or the values of a
+/// map, or the arguments themselves if there are several
+fn candidates(ftx: &FunctionContext) -> Result<Vec<Value>, ExecutionError> {
+    match (&ftx.this, &ftx.args[..]) {
+        (None, [arg]) => match ftx.ptx.resolve(arg)? {
+            Value::List(items) => Ok(items.to_vec()),
+            Value::Map(map) => Ok(values(&map)),
+            // A single value other than a list or map is its own minimum and maximum
+            value => Ok(vec![value]),
+        },
+        (None, args) if args.len() > 1 => args.iter().map(|arg| ftx.ptx.resolve(arg)).collect(),
+        _ => items(ftx),
+    }
+}
+
+/// The item that `wanted` orders first, or null if there are none
+fn extreme(ftx: &FunctionContext, wanted: Ordering) -> ResolveResult {
+    let mut items = candidates(ftx)?.into_iter();
+    let Some(mut best) = items.next() else {
+        return Ok(Value::Null);
+    };
+    for item in items {
+        match arithmetic::compare(&item, &best) {
+            Some(ordering) if ordering == wanted => best = item,
+            Some(_) => {}
+            None => return Err(ExecutionError::ValuesNotComparable(best, item)),
+        }
+    }
+    Ok(best)
+}
+
+/// Returns the smallest of the arguments, of the items of a list or of the
+/// values of a map.
+///
+/// # Examples
+/// ```cel
+/// min(latencies) < 100
+/// ```
+pub fn min(ftx: &FunctionContext) -> ResolveResult {
+    extreme(ftx, Ordering::Less)
+}
+
+/// Returns the largest of the arguments, of the items of a list or of the
+/// values of a map.
+///
+/// # Examples
+/// ```cel
+/// max(scores_by_user) == 10
+/// ```
+pub fn max(ftx: &FunctionContext) -> ResolveResult {
+    extreme(ftx, Ordering::Greater)
+}
+
+/// Adds the functions to an environment, in place of the interpreter's `min()`
+/// and `max()`
+pub fn register(environment: &mut cel_interpreter::Context, promotion: bool) {
+    environment.add_function("sum", move |ftx: &FunctionContext| sum(ftx, promotion));
+    environment.add_function("avg", move |ftx: &FunctionContext| avg(ftx, promotion));
+    environment.add_function("min", min);
+    environment.add_function("max", max);
+}
@@ -0,0 +1,411 @@
+//! Int and uint arithmetic that fails on overflow and division by zero, as the
+//! spec requires, where the interpreter would panic or wrap around, and
+//! comparisons of ints and uints with doubles by their exact values, where the
+//! interpreter would round the int to a double first. `in` is taken over too,
+//! to find numeric map keys of any type and to explain what it can't look in.
+//!
+//! Plans apply these directly. For the interpreter, operations that might have
+//! int or uint operands, and every `in`, are rewritten into calls to
+//! [`CHECKED`], which falls back to the usual operation for anything else.
+use crate::bytes;
+use crate::plan;
+use crate::transform::{call, is_call_to, map_children};
+use crate::types;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use cel_parser::{ArithmeticOp, Atom, Expression, RelationOp, UnaryOp};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+/// Internal function that operations on possible ints and uints are rewritten
+/// into, called as `@checked(left, "+", right)` or with any other arithmetic or
+/// relation operator, or as `@checked("-", operand)` for a negation
+pub const CHECKED: &str = "@checked";
+
+/// The error of an operation on ints or uints, named by its overload, e.g. `_+_`
+fn overflow(overload: &str, unsigned: bool) -> ExecutionError {
+    let message = match unsigned {
+        true => "unsigned integer overflow",
+        false => "integer overflow",
+    };
+    ExecutionError::function_error(overload, message)
+}
+
+/// `left + right`, failing on overflow
+pub fn add(left: Value, right: Value) -> ResolveResult {
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => l
+            .checked_add(r)
+            .map(Value::Int)
+            .ok_or_else(|| overflow("_+_", false)),
+        (Value::UInt(l), Value::UInt(r)) => l
+            .checked_add(r)
+            .map(Value::UInt)
+            .ok_or_else(|| overflow("_+_", true)),
+        (left, right) => {
+            types::operator("+", &left, &right).unwrap_or_else(|| bytes::add(left, right))
+        }
+    }
+}
+
+/// `left - right`, failing on overflow
+pub fn sub(left: Value, right: Value) -> ResolveResult {
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => l
+            .checked_sub(r)
+            .map(Value::Int)
+            .ok_or_else(|| overflow("_-_", false)),
+        (Value::UInt(l), Value::UInt(r)) => l
+            .checked_sub(r)
+            .map(Value::UInt)
+            .ok_or_else(|| overflow("_-_", true)),
+        (left, right) => types::operator("-", &left, &right).unwrap_or_else(|| left - right),
+    }
+}
+
+/// `left * right`, failing on overflow
+pub fn mul(left: Value, right: Value) -> ResolveResult {
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => l
+            .checked_mul(r)
+            .map(Value::Int)
+            .ok_or_else(|| overflow("_*_", false)),
+        (Value::UInt(l), Value::UInt(r)) => l
+            .checked_mul(r)
+            .map(Value::UInt)
+            .ok_or_else(|| overflow("_*_", true)),
+        (left, right) => types::operator("*", &left, &right).unwrap_or_else(|| left * right),
+    }
+}
+
+/// `left / right`, failing on overflow and division by zero
+pub fn div(left: Value, right: Value) -> ResolveResult {
+    match (left, right) {
+        (Value::Int(_), Value::Int(0)) | (Value::UInt(_), Value::UInt(0)) => {
+            Err(ExecutionError::function_error("_/_", "division by zero"))
+        }
+        (Value::Int(l), Value::Int(r)) => l
+            .checked_div(r)
+            .map(Value::Int)
+            .ok_or_else(|| overflow("_/_", false)),
+        (Value::UInt(l), Value::UInt(r)) => Ok(Value::UInt(l / r)),
+        (left, right) => types::operator("/", &left, &right).unwrap_or_else(|| left / right),
+    }
+}
+
+/// `left % right`, failing on overflow and division by zero
+pub fn rem(left: Value, right: Value) -> ResolveResult {
+    match (left, right) {
+        (Value::Int(_), Value::Int(0)) | (Value::UInt(_), Value::UInt(0)) => {
+            Err(ExecutionError::function_error("_%_", "modulus by zero"))
+        }
+        (Value::Int(l), Value::Int(r)) => l
+            .checked_rem(r)
+            .map(Value::Int)
+            .ok_or_else(|| overflow("_%_", false)),
+        (Value::UInt(l), Value::UInt(r)) => Ok(Value::UInt(l % r)),
+        (left, right) => types::operator("%", &left, &right).unwrap_or_else(|| left % right),
+    }
+}
+
+/// `-operand`, failing on overflow
+pub fn negate(operand: Value) -> ResolveResult {
+    match operand {
+        Value::Int(i) => i
+            .checked_neg()
+            .map(Value::Int)
+            .ok_or_else(|| overflow("-_", false)),
+        Value::Float(f) => Ok(Value::Float(-f)),
+        value => Err(ExecutionError::UnsupportedUnaryOperator("minus", value)),
+    }
+}
+
+/// Applies the arithmetic operator `op`
+pub fn apply(op: &ArithmeticOp, left: Value, right: Value) -> ResolveResult {
+    match op {
+        ArithmeticOp::Add => add(left, right),
+        ArithmeticOp::Subtract => sub(left, right),
+        ArithmeticOp::Multiply => mul(left, right),
+        ArithmeticOp::Divide => div(left, right),
+        ArithmeticOp::Modulus => rem(left, right),
+    }
+}
+
+/// Orders two values, comparing ints and uints with doubles exactly and bytes
+/// lexicographically
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Int(i), Value::Float(f)) => compare_int(*i, *f),
+        (Value::UInt(u), Value::Float(f)) => compare_uint(*u, *f),
+        (Value::Float(f), Value::Int(i)) => compare_int(*i, *f).map(Ordering::reverse),
+        (Value::Float(f), Value::UInt(u)) => compare_uint(*u, *f).map(Ordering::reverse),
+        (left, right) => bytes::compare(left, right),
+    }
+}
+
+/// Whether two values are equal, comparing ints and uints with doubles exactly,
+/// also as the items of lists and values of maps
+pub fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Int(_) | Value::UInt(_), Value::Float(_))
+        | (Value::Float(_), Value::Int(_) | Value::UInt(_)) => {
+            compare(left, right) == Some(Ordering::Equal)
+        }
+        (Value::List(left), Value::List(right)) => {
+            left.len() == right.len() && left.iter().zip(right.iter()).all(|(l, r)| equals(l, r))
+        }
+        (Value::Map(left), Value::Map(right)) => {
+            left.map.len() == right.map.len()
+                && left.map.iter().all(|(key, value)| {
+                    right.map.get(key).is_some_and(|other| equals(value, other))
+                })
+        }
+        (left, right) => left == right,
+    }
+}
+
+/// Orders an int and a double, which has no order with NaN
+fn compare_int(i: i64, f: f64) -> Option<Ordering> {
+    // -2^63 is exact as a double, 2^63 is the first double past i64::MAX
+    if f.is_nan() {
+        None
+    } else if f >= 9223372036854775808.0 {
+        Some(Ordering::Less)
+    } else if f < -9223372036854775808.0 {
+        Some(Ordering::Greater)
+    } else {
+        Some(
+            i.cmp(&(f.trunc() as i64))
+                .then(0f64.partial_cmp(&f.fract())?),
+        )
+    }
+}
+
+/// Orders a uint and a double, which has no order with NaN
+fn compare_uint(u: u64, f: f64) -> Option<Ordering> {
+    if f.is_nan() {
+        None
+    } else if f >= 18446744073709551616.0 {
+        Some(Ordering::Less)
+    } else if f < 0.0 {
+        Some(Ordering::Greater)
+    } else {
+        Some(
+            u.cmp(&(f.trunc() as u64))
+                .then(0f64.partial_cmp(&f.fract())?),
+        )
+    }
+}
+
+/// Rewrites the operations in `expr` that might have int or uint operands into
+/// calls to [`CHECKED`], returning None if there are none.
+pub fn rewrite(expr: &Expression) -> Option<Expression> {
+    needs_rewrite(expr).then(|| rewrite_all(expr))
+}
+
+fn needs_rewrite(expr: &Expression) -> bool {
+    if is_checked(expr) {
+        return true;
+    }
+    let mut found = false;
+    map_children(expr, |child| {
+        found = found || needs_rewrite(child);
+        child.clone()
+    });
+    found
+}
+
+/// Whether `expr` has arithmetic anywhere in it, which without [`rewrite`] is
+/// unchecked
+pub fn has_arithmetic(expr: &Expression) -> bool {
+    if matches!(
+        expr,
+        Expression::Arithmetic(..) | Expression::Unary(UnaryOp::Minus, _)
+    ) {
+        return true;
+    }
+    let mut found = false;
+    map_children(expr, |child| {
+        found = found || has_arithmetic(child);
+        child.clone()
+    });
+    found
+}
+
+/// Whether `expr` is an operation that might overflow, compare an int or uint
+/// with a double, directly or within lists or maps, or is an `in`
+fn is_checked(expr: &Expression) -> bool {
+    match expr {
+        // A value of a registered type added to a map would otherwise be merged with it
+        Expression::Arithmetic(left, ArithmeticOp::Add, right)
+            if matches!(**left, Expression::Map(_)) || matches!(**right, Expression::Map(_)) =>
+        {
+            true
+        }
+        Expression::Arithmetic(left, _, right) => might_be_integer(left) && might_be_integer(right),
+        Expression::Relation(_, RelationOp::In, _) => true,
+        Expression::Relation(left, RelationOp::Equals | RelationOp::NotEquals, right)
+            if might_be_collection(left) && might_be_collection(right) =>
+        {
+            true
+        }
+        Expression::Relation(left, _, right) => {
+            might_be_integer(left) && might_be_double(right)
+                || might_be_double(left) && might_be_integer(right)
+        }
+        Expression::Unary(UnaryOp::Minus, operand) => might_be_integer(operand),
+        _ => false,
+    }
+}
+
+fn rewrite_all(expr: &Expression) -> Expression {
+    // `has()` relies on the interpreter resolving its argument as a selection
+    if is_call_to(expr, "has") {
+        return expr.clone();
+    }
+    let symbol = |symbol: &str| Expression::Atom(Atom::String(Arc::new(symbol.to_string())));
+    match expr {
+        Expression::Arithmetic(left, op, right) if is_checked(expr) => call(
+            CHECKED,
+            vec![
+                rewrite_all(left),
+                symbol(match op {
+                    ArithmeticOp::Add => "+",
+                    ArithmeticOp::Subtract => "-",
+                    ArithmeticOp::Multiply => "*",
+                    ArithmeticOp::Divide => "/",
+                    ArithmeticOp::Modulus => "%",
+                }),
+                rewrite_all(right),
+            ],
+        ),
+        Expression::Relation(left, op, right) if is_checked(expr) => call(
+            CHECKED,
+            vec![
+                rewrite_all(left),
+                symbol(match op {
+                    RelationOp::LessThan => "<",
+                    RelationOp::LessThanEq => "<=",
+                    RelationOp::GreaterThan => ">",
+                    RelationOp::GreaterThanEq => ">=",
+                    RelationOp::Equals => "==",
+                    RelationOp::NotEquals => "!=",
+                    RelationOp::In => "in",
+                }),
+                rewrite_all(right),
+            ],
+        ),
+        Expression::Unary(UnaryOp::Minus, operand) if is_checked(expr) => {
+            call(CHECKED, vec![symbol("-"), rewrite_all(operand)])
+        }
+        _ => map_children(expr, rewrite_all),
+    }
+}
+
+/// Whether `expr` might evaluate to an int or uint, which is only ruled out for
+/// expressions whose type is known without evaluating them
+fn might_be_integer(expr: &Expression) -> bool {
+    match expr {
+        Expression::Atom(atom) => matches!(atom, Atom::Int(_) | Atom::UInt(_)),
+        Expression::Arithmetic(left, _, right) => might_be_integer(left) && might_be_integer(right),
+        Expression::Unary(UnaryOp::Minus | UnaryOp::DoubleMinus, operand) => {
+            might_be_integer(operand)
+        }
+        // Under truthiness `&&` and `||` are one of their operands
+        Expression::And(left, right)
+        | Expression::Or(left, right)
+        | Expression::Ternary(_, left, right) => might_be_integer(left) || might_be_integer(right),
+        Expression::Relation(..)
+        | Expression::Unary(..)
+        | Expression::List(_)
+        | Expression::Map(_) => false,
+        Expression::FunctionCall(function, _, _) => !matches!(
+            &**function,
+            Expression::Ident(name) if matches!(
+                name.as_str(),
+                "double" | "string" | "bytes" | "bool" | "has" | "type" | "timestamp" | "duration"
+            )
+        ),
+        Expression::Member(..) | Expression::Ident(_) => true,
+    }
+}
+
+/// Whether `expr` might evaluate to a list or map, which is only ruled out for
+/// expressions whose type is known without evaluating them
+fn might_be_collection(expr: &Expression) -> bool {
+    match expr {
+        Expression::List(_) | Expression::Map(_) => true,
+        Expression::Atom(_) | Expression::Relation(..) | Expression::Unary(..) => false,
+        Expression::Arithmetic(left, _, right) => {
+            might_be_collection(left) && might_be_collection(right)
+        }
+        Expression::And(left, right)
+        | Expression::Or(left, right)
+        | Expression::Ternary(_, left, right) => {
+            might_be_collection(left) || might_be_collection(right)
+        }
+        Expression::FunctionCall(..) | Expression::Member(..) | Expression::Ident(_) => true,
+    }
+}
+
+/// Whether `expr` might evaluate to a double, which is only ruled out for
+/// expressions whose type is known without evaluating them
+fn might_be_double(expr: &Expression) -> bool {
+    match expr {
+        Expression::Atom(atom) => matches!(atom, Atom::Float(_)),
+        Expression::Arithmetic(left, _, right) => might_be_double(left) && might_be_double(right),
+        Expression::Unary(UnaryOp::Minus | UnaryOp::DoubleMinus, operand) => {
+            might_be_double(operand)
+        }
+        // Under truthiness `&&` and `||` are one of their operands
+        Expression::And(left, right)
+        | Expression::Or(left, right)
+        | Expression::Ternary(_, left, right) => might_be_double(left) || might_be_double(right),
+        Expression::Relation(..)
+        | Expression::Unary(..)
+        | Expression::List(_)
+        | Expression::Map(_) => false,
+        Expression::FunctionCall(function, _, _) => !matches!(
+            &**function,
+            Expression::Ident(name) if matches!(
+                name.as_str(),
+                "int" | "uint" | "size" | "string" | "bytes" | "bool" | "has" | "type"
+                    | "timestamp" | "duration"
+            )
+        ),
+        Expression::Member(..) | Expression::Ident(_) => true,
+    }
+}
+
+/// Implementation of [`CHECKED`]
+pub fn operation(ftx: &FunctionContext) -> ResolveResult {
+    let symbol = |arg: usize| match ftx.args.get(arg) {
+        Some(Expression::Atom(Atom::String(symbol))) => Ok(symbol.as_str()),
+        _ => Err(ftx.error("expected an operator")),
+    };
+    if ftx.args.len() == 2 {
+        symbol(0)?;
+        return negate(ftx.ptx.resolve(&ftx.args[1])?);
+    }
+    let left = ftx.ptx.resolve(&ftx.args[0])?;
+    let right = ftx.ptx.resolve(&ftx.args[2])?;
+    match symbol(1)? {
+        "+" => add(left, right),
+        "-" => sub(left, right),
+        "*" => mul(left, right),
+        "/" => div(left, right),
+        "%" => rem(left, right),
+        "<" => plan::relation(left, &RelationOp::LessThan, right).map(Value::Bool),
+        "<=" => plan::relation(left, &RelationOp::LessThanEq, right).map(Value::Bool),
+        ">" => plan::relation(left, &RelationOp::GreaterThan, right).map(Value::Bool),
+        ">=" => plan::relation(left, &RelationOp::GreaterThanEq, right).map(Value::Bool),
+        "==" => Ok(Value::Bool(equals(&left, &right))),
+        "!=" => Ok(Value::Bool(!equals(&left, &right))),
+        "in" => plan::relation(left, &RelationOp::In, right).map(Value::Bool),
+        _ => Err(ftx.error("expected an operator")),
+    }
+}
+
+/// Adds [`CHECKED`] to an environment
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function(CHECKED, operation);
+}
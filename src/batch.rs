@@ -0,0 +1,232 @@
+//! Batch functions, added with `Context.add_function(..., batch=True)`, which
+//! take a list of values for each argument and return a list of results.
+//!
+//! Before a comprehension evaluates its body for each item, the calls to batch
+//! functions in it are made once with the arguments for every item, and the body
+//! is evaluated with each call replaced by its result for the item. The names
+//! of the batch functions are kept in the environment as the variable
+//! [`BATCH`], which expressions can't refer to, and they are called through the
+//! internal function of the same name.
+use crate::plan::{is_macro, items};
+use crate::transform::map_children;
+use crate::PythonFunction;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use cel_parser::{Atom, Expression};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Internal variable listing the batch functions, and internal function calling
+/// one with lists of arguments
+pub const BATCH: &str = "@batch";
+
+/// Adds the batch functions to an environment
+pub fn register(
+    environment: &mut crate::Environment,
+    functions: HashMap<String, Arc<PythonFunction>>,
+) {
+    let names: Vec<Value> = functions
+        .keys()
+        .map(|name| Value::from(name.as_str()))
+        .collect();
+    environment.add_variable_from_value(BATCH, Value::List(Arc::new(names)));
+    environment.add_function(BATCH, move |ftx: &FunctionContext| -> ResolveResult {
+        let (name, args) = match ftx.args.split_first() {
+            Some((Expression::Atom(Atom::String(name)), args)) => (name, args),
+            _ => return Err(ftx.error("expected the name of a batch function")),
+        };
+        let function = functions
+            .get(name.as_str())
+            .ok_or_else(|| ftx.error(format!("'{}' isn't a batch function", name)))?;
+        let columns = args
+            .iter()
+            .map(|arg| ftx.ptx.resolve(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let rows = match columns.first() {
+            Some(Value::List(column)) => column.len(),
+            _ => return Err(ftx.error("expected lists of arguments")),
+        };
+        call(function, &columns, rows)
+    });
+}
+
+/// The names of the batch functions of an environment
+pub fn names(environment: &cel_interpreter::Context) -> Vec<String> {
+    match environment.get_variable(BATCH) {
+        Ok(Value::List(names)) => names
+            .iter()
+            .filter_map(|name| match name {
+                Value::String(name) => Some(name.to_string()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Calls a batch function with `columns` of arguments for `rows` calls,
+/// checking it returns a list of a result for each
+fn call(function: &PythonFunction, columns: &[Value], rows: usize) -> ResolveResult {
+    match function.call(columns)? {
+        Value::List(results) if results.len() == rows => Ok(Value::List(results)),
+        Value::List(results) => Err(ExecutionError::function_error(
+            &function.name,
+            format!("returned {} results for {} calls", results.len(), rows),
+        )),
+        result => Err(ExecutionError::function_error(
+            &function.name,
+            format!("expected a list of results, got {}", result.type_of()),
+        )),
+    }
+}
+
+/// Calls a batch function with one set of arguments
+pub fn call_one(function: &PythonFunction, args: &[Value]) -> ResolveResult {
+    let columns: Vec<Value> = args
+        .iter()
+        .map(|arg| Value::List(Arc::new(vec![arg.clone()])))
+        .collect();
+    match call(function, &columns, 1)? {
+        Value::List(results) => Ok(results[0].clone()),
+        _ => unreachable!("checked by call"),
+    }
+}
+
+/// The results of the calls to batch functions in a comprehension's body
+pub struct Prefetched {
+    /// The filter and body with each call replaced by its result
+    filter: Option<Expression>,
+    body: Expression,
+    /// The results of the calls for each item, None for an item whose calls
+    /// failed, which is evaluated as is so that it fails as it would have
+    results: Vec<Option<Vec<Value>>>,
+}
+
+impl Prefetched {
+    /// Sets the results of the calls for the item at `index` in `scope`,
+    /// returning the filter and body to evaluate it with, or None if the item
+    /// should be evaluated as is
+    pub fn bind(
+        &self,
+        scope: &mut cel_interpreter::Context,
+        index: usize,
+    ) -> Option<(Option<&Expression>, &Expression)> {
+        let results = self.results.get(index)?.as_ref()?;
+        for (call, result) in results.iter().enumerate() {
+            scope.add_variable_from_value(result_name(call), result.clone());
+        }
+        Some((self.filter.as_ref(), &self.body))
+    }
+}
+
+/// Makes the calls to batch functions in the `filter` and `body` of a
+/// comprehension over `target`, returning None if there are none
+pub fn prefetch(
+    scope: &mut cel_interpreter::Context,
+    variable: &str,
+    target: &Value,
+    filter: Option<&Expression>,
+    body: &Expression,
+) -> Option<Prefetched> {
+    let names = names(scope);
+    if names.is_empty() {
+        return None;
+    }
+    let mut calls = Vec::new();
+    let filter = filter.map(|filter| replace_calls(filter, &names, &mut calls));
+    let body = replace_calls(body, &names, &mut calls);
+    if calls.is_empty() {
+        return None;
+    }
+
+    // The arguments of every call for each item
+    let arguments: Vec<Option<Vec<Vec<Value>>>> = items(target)
+        .ok()?
+        .map(|item| {
+            scope.add_variable_from_value(variable, item);
+            calls
+                .iter()
+                .map(|(_, args)| args.iter().map(|arg| scope.resolve(arg)).collect())
+                .collect::<Result<_, _>>()
+                .ok()
+        })
+        .collect();
+    let batched: Vec<usize> = (0..arguments.len())
+        .filter(|&index| arguments[index].is_some())
+        .collect();
+    if batched.is_empty() {
+        return None;
+    }
+    let mut results: Vec<Option<Vec<Value>>> = arguments
+        .iter()
+        .map(|arguments| arguments.as_ref().map(|_| Vec::new()))
+        .collect();
+
+    for (call, (name, args)) in calls.iter().enumerate() {
+        let mut columns = Vec::new();
+        for arg in 0..args.len() {
+            let column: Vec<Value> = batched
+                .iter()
+                .filter_map(|&index| Some(arguments[index].as_ref()?[call][arg].clone()))
+                .collect();
+            let column_name = format!("{}.args{}", BATCH, arg);
+            scope.add_variable_from_value(column_name.as_str(), Value::List(Arc::new(column)));
+            columns.push(Expression::Ident(Arc::new(column_name)));
+        }
+        let mut args = vec![Expression::Atom(Atom::String(name.clone()))];
+        args.extend(columns);
+        let batch = Expression::FunctionCall(
+            Box::new(Expression::Ident(Arc::new(BATCH.to_string()))),
+            None,
+            args,
+        );
+        match scope.resolve(&batch) {
+            Ok(Value::List(values)) => {
+                for (&index, value) in batched.iter().zip(values.iter()) {
+                    if let Some(results) = &mut results[index] {
+                        results.push(value.clone());
+                    }
+                }
+            }
+            // Each item makes the call itself, and fails with its own error
+            _ => {
+                for &index in &batched {
+                    results[index] = None;
+                }
+            }
+        }
+    }
+    Some(Prefetched {
+        filter,
+        body,
+        results,
+    })
+}
+
+/// The internal variable the result of the call at `index` is set as
+fn result_name(index: usize) -> String {
+    format!("{}.result{}", BATCH, index)
+}
+
+/// Replaces the calls to the batch functions `names` in `expr` by the variables
+/// of their results, adding the name and arguments of each to `calls`. Calls
+/// without arguments and calls within nested comprehensions, whose variables
+/// aren't set yet, are left as they are.
+fn replace_calls(
+    expr: &Expression,
+    names: &[String],
+    calls: &mut Vec<(Arc<String>, Vec<Expression>)>,
+) -> Expression {
+    match expr {
+        Expression::FunctionCall(function, None, args) if !args.is_empty() => {
+            if let Expression::Ident(name) = &**function {
+                if names.iter().any(|batch| batch == name.as_str()) {
+                    calls.push((name.clone(), args.clone()));
+                    return Expression::Ident(Arc::new(result_name(calls.len() - 1)));
+                }
+            }
+            map_children(expr, |child| replace_calls(child, names, calls))
+        }
+        Expression::FunctionCall(function, Some(_), _) if is_macro(function) => expr.clone(),
+        _ => map_children(expr, |child| replace_calls(child, names, calls)),
+    }
+}
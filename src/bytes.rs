@@ -0,0 +1,175 @@
+//! The bytes operators the interpreter doesn't implement: concatenation with
+//! `+`, ordering with `<`, `<=`, `>` and `>=`, and indexing a single byte with
+//! `b[i]`.
+//!
+//! Plans apply these directly. For the interpreter, operations that might have
+//! bytes operands are rewritten into calls to [`BYTES`], which falls back to the
+//! usual operation for anything else.
+use crate::plan;
+use crate::transform::{call, is_call_to, map_children};
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use cel_parser::{ArithmeticOp, Atom, Expression, Member, RelationOp, UnaryOp};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+/// Internal function that operations on possible bytes are rewritten into, called
+/// as `@bytes(left, "+", right)`, with "<", "<=", ">", ">=" or "[]" for an index
+pub const BYTES: &str = "@bytes";
+
+/// `left + right`, concatenating bytes
+pub fn add(left: Value, right: Value) -> ResolveResult {
+    match (left, right) {
+        (Value::Bytes(left), Value::Bytes(right)) => Ok(Value::Bytes(Arc::new(
+            [left.as_slice(), right.as_slice()].concat(),
+        ))),
+        (left, right) => left + right,
+    }
+}
+
+/// Orders two values, comparing bytes lexicographically
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Bytes(left), Value::Bytes(right)) => Some(left.cmp(right)),
+        (left, right) => left.partial_cmp(right),
+    }
+}
+
+/// The byte at `index` as an int, or an error if there is none
+pub fn index(bytes: &[u8], index: i64) -> ResolveResult {
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| bytes.get(index))
+        .map(|byte| Value::Int(*byte as i64))
+        .ok_or_else(|| {
+            ExecutionError::function_error(
+                "[]",
+                format!("index {} is out of range for {} bytes", index, bytes.len()),
+            )
+        })
+}
+
+/// Rewrites the operations in `expr` that might have bytes operands into calls
+/// to [`BYTES`], returning None if there are none.
+pub fn rewrite(expr: &Expression) -> Option<Expression> {
+    needs_rewrite(expr).then(|| rewrite_all(expr))
+}
+
+fn needs_rewrite(expr: &Expression) -> bool {
+    if operands(expr).is_some() {
+        return true;
+    }
+    let mut found = false;
+    map_children(expr, |child| {
+        found = found || needs_rewrite(child);
+        child.clone()
+    });
+    found
+}
+
+/// The operands and symbol of an operation that might have bytes operands
+fn operands(expr: &Expression) -> Option<(&Expression, &'static str, &Expression)> {
+    let (left, symbol, right) = match expr {
+        Expression::Arithmetic(left, ArithmeticOp::Add, right) => (left, "+", right),
+        Expression::Relation(left, op, right) => {
+            let symbol = match op {
+                RelationOp::LessThan => "<",
+                RelationOp::LessThanEq => "<=",
+                RelationOp::GreaterThan => ">",
+                RelationOp::GreaterThanEq => ">=",
+                RelationOp::Equals | RelationOp::NotEquals | RelationOp::In => return None,
+            };
+            (left, symbol, right)
+        }
+        Expression::Member(target, member) => match &**member {
+            Member::Index(index) if might_be_int(index) => {
+                return might_be_bytes(target).then_some((target, "[]", index));
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    (might_be_bytes(left) && might_be_bytes(right)).then_some((left, symbol, right))
+}
+
+fn rewrite_all(expr: &Expression) -> Expression {
+    // `has()` relies on the interpreter resolving its argument as a selection
+    if is_call_to(expr, "has") {
+        return expr.clone();
+    }
+    match operands(expr) {
+        Some((left, symbol, right)) => call(
+            BYTES,
+            vec![
+                rewrite_all(left),
+                Expression::Atom(Atom::String(Arc::new(symbol.to_string()))),
+                rewrite_all(right),
+            ],
+        ),
+        None => map_children(expr, rewrite_all),
+    }
+}
+
+/// Whether `expr` might evaluate to bytes, which is only ruled out for
+/// expressions whose type is known without evaluating them
+fn might_be_bytes(expr: &Expression) -> bool {
+    match expr {
+        Expression::Atom(atom) => matches!(atom, Atom::Bytes(_)),
+        Expression::Arithmetic(left, ArithmeticOp::Add, right) => {
+            might_be_bytes(left) && might_be_bytes(right)
+        }
+        Expression::Arithmetic(..)
+        | Expression::Relation(..)
+        | Expression::And(..)
+        | Expression::Or(..)
+        | Expression::Unary(..)
+        | Expression::List(_)
+        | Expression::Map(_) => false,
+        Expression::FunctionCall(function, _, _) => !matches!(
+            &**function,
+            Expression::Ident(name) if matches!(
+                name.as_str(),
+                "int" | "uint" | "double" | "string" | "bool" | "size" | "has" | "type"
+            )
+        ),
+        Expression::Ternary(..) | Expression::Member(..) | Expression::Ident(_) => true,
+    }
+}
+
+/// Whether `expr` might evaluate to an int, the only index a byte can be selected by
+fn might_be_int(expr: &Expression) -> bool {
+    match expr {
+        Expression::Atom(atom) => matches!(atom, Atom::Int(_)),
+        Expression::Unary(UnaryOp::Minus | UnaryOp::DoubleMinus, operand) => might_be_int(operand),
+        Expression::Relation(..)
+        | Expression::And(..)
+        | Expression::Or(..)
+        | Expression::Unary(..)
+        | Expression::List(_)
+        | Expression::Map(_) => false,
+        _ => true,
+    }
+}
+
+/// Implementation of [`BYTES`]
+pub fn operation(ftx: &FunctionContext) -> ResolveResult {
+    let left = ftx.ptx.resolve(&ftx.args[0])?;
+    let right = ftx.ptx.resolve(&ftx.args[2])?;
+    let op = match ftx.args.get(1) {
+        Some(Expression::Atom(Atom::String(symbol))) => match symbol.as_str() {
+            "+" => return add(left, right),
+            "[]" => return plan::index_into(left, right),
+            "<" => RelationOp::LessThan,
+            "<=" => RelationOp::LessThanEq,
+            ">" => RelationOp::GreaterThan,
+            ">=" => RelationOp::GreaterThanEq,
+            _ => return Err(ftx.error("expected an operator")),
+        },
+        _ => return Err(ftx.error("expected an operator")),
+    };
+    plan::relation(left, &op, right).map(Value::Bool)
+}
+
+/// Adds [`BYTES`] to an environment
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function(BYTES, operation);
+}
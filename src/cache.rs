@@ -0,0 +1,295 @@
+//! Memoized results of a program, for `Program.evaluate(..., cache=True)`.
+//!
+//! Results are keyed by a fingerprint of the variables the expression refers to,
+//! so contexts that only differ in variables it doesn't use share a result.
+//!
+//! Results that depend on more than the variables aren't cached: those of
+//! expressions that call Python functions or methods of registered types, that
+//! read attributes of Python objects, or that draw random numbers or read the
+//! clock without a seed or frozen clock. The seed and frozen time of a context
+//! are part of the key.
+use crate::datetime;
+use crate::memory;
+use crate::random;
+use crate::stats;
+use crate::transform::map_children;
+use crate::types;
+use crate::{Job, Outcome};
+use cel_interpreter::objects::Key;
+use cel_interpreter::Value;
+use cel_parser::Expression;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+/// Builtins that read the clock, unless it is frozen
+const CLOCK: [&str; 2] = ["now", "today"];
+
+/// The number of results kept, the oldest is dropped to make room for another
+pub const CAPACITY: usize = 1024;
+
+#[derive(Default)]
+pub struct Cache {
+    references: OnceLock<References>,
+    entries: Mutex<Entries>,
+}
+
+/// The names an expression refers to
+#[derive(Default)]
+struct References {
+    variables: BTreeSet<String>,
+    functions: BTreeSet<String>,
+}
+
+#[derive(Default)]
+struct Entries {
+    results: HashMap<Vec<u8>, Outcome>,
+    /// Keys in the order they were added
+    order: VecDeque<Vec<u8>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Cache {
+    /// The key of an evaluation of `expr`, or None if its result can't be cached
+    /// because it could be something else the next time, see above.
+    pub fn key(&self, expr: &Expression, job: &Job) -> Option<Vec<u8>> {
+        let references = self.references.get_or_init(|| {
+            let mut references = References::default();
+            collect_references(expr, &mut references);
+            references
+        });
+        let methods = types::methods();
+        let calls = |names: &[&str]| {
+            names
+                .iter()
+                .any(|name| references.functions.contains(*name))
+        };
+        let is_set = |name: &str| job.environment.get_variable(name).is_ok();
+        if job.objects
+            || references
+                .functions
+                .iter()
+                .any(|name| job.functions.contains(name) || methods.contains(name))
+            || (calls(&random::DRAWS) && !is_set(random::SEED))
+            || (calls(&CLOCK) && !is_set(datetime::NOW))
+        {
+            return None;
+        }
+
+        let mut key = Vec::new();
+        let options = job.options;
+        for flag in [
+            options.numeric_promotion,
+            options.truthiness,
+            options.heterogeneous_equality,
+            options.lenient_timestamps,
+            options.duplicate_map_keys,
+            options.error_absorption,
+            job.safe_navigation,
+        ] {
+            key.push(flag as u8);
+        }
+        match &job.unknowns {
+            Some(unknowns) => {
+                key.push(1);
+                write_len(&mut key, unknowns.len());
+                for attribute in unknowns {
+                    write_bytes(&mut key, attribute.as_bytes());
+                }
+            }
+            None => key.push(0),
+        }
+        // Variables are in order, so a missing one can be marked by its absence
+        let internal = [random::SEED, datetime::NOW];
+        for name in references
+            .variables
+            .iter()
+            .map(String::as_str)
+            .chain(internal)
+        {
+            if let Ok(value) = job.environment.get_variable(name) {
+                write_bytes(&mut key, name.as_bytes());
+                write_value(&mut key, &value);
+            }
+        }
+        Some(key)
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<Outcome> {
+        let mut entries = self.entries.lock().unwrap();
+        let outcome = entries.results.get(key).cloned();
+        match outcome {
+            Some(_) => entries.hits += 1,
+            None => entries.misses += 1,
+        }
+        stats::cache_lookup(outcome.is_some());
+        outcome
+    }
+
+    pub fn insert(&self, key: Vec<u8>, outcome: Outcome) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.results.contains_key(&key) {
+            return;
+        }
+        if entries.results.len() >= CAPACITY {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.results.remove(&oldest);
+            }
+        }
+        entries.order.push_back(key.clone());
+        entries.results.insert(key, outcome);
+    }
+
+    pub fn clear(&self) {
+        *self.entries.lock().unwrap() = Entries::default();
+    }
+
+    /// Approximate number of bytes allocated by the cached results
+    pub fn heap_size(&self) -> usize {
+        let entries = self.entries.lock().unwrap();
+        let outcome_heap = |outcome: &Outcome| match outcome {
+            Outcome::Value(value) => memory::value_heap(value),
+            Outcome::Unknown(attributes) => {
+                attributes.capacity() * std::mem::size_of::<String>()
+                    + attributes.iter().map(memory::string_heap).sum::<usize>()
+            }
+            Outcome::Error(error) => {
+                memory::string_heap(&error.message) + memory::string_heap(&error.expression)
+            }
+        };
+        memory::map_heap(&entries.results, |key, outcome| {
+            key.capacity() + outcome_heap(outcome)
+        }) + entries.order.capacity() * std::mem::size_of::<Vec<u8>>()
+            + entries.order.iter().map(Vec::capacity).sum::<usize>()
+    }
+
+    /// The number of hits, misses and cached results
+    pub fn info(&self) -> (u64, u64, usize) {
+        let entries = self.entries.lock().unwrap();
+        (entries.hits, entries.misses, entries.results.len())
+    }
+}
+
+/// Unlike `Expression::references`, this includes the variables of indexes like `a[i]`
+fn collect_references(expr: &Expression, references: &mut References) {
+    match expr {
+        Expression::Ident(name) => {
+            references.variables.insert(name.to_string());
+        }
+        Expression::FunctionCall(function, _, _) => {
+            if let Expression::Ident(name) = &**function {
+                references.functions.insert(name.to_string());
+            }
+        }
+        _ => {}
+    }
+    map_children(expr, |child| {
+        collect_references(child, references);
+        child.clone()
+    });
+}
+
+fn write_len(key: &mut Vec<u8>, len: usize) {
+    key.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+fn write_bytes(key: &mut Vec<u8>, bytes: &[u8]) {
+    write_len(key, bytes.len());
+    key.extend_from_slice(bytes);
+}
+
+/// Writes a value so that only values of the same type and contents are written
+/// the same, where `==` would also match e.g. `1` and `1.0`, which give
+/// different results.
+fn write_value(key: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => key.push(0),
+        Value::Bool(b) => key.push(if *b { 2 } else { 1 }),
+        Value::Int(v) => {
+            key.push(3);
+            key.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::UInt(v) => {
+            key.push(4);
+            key.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::Float(v) => {
+            key.push(5);
+            key.extend_from_slice(&v.to_bits().to_le_bytes());
+        }
+        Value::String(s) => {
+            key.push(6);
+            write_bytes(key, s.as_bytes());
+        }
+        Value::Bytes(b) => {
+            key.push(7);
+            write_bytes(key, b);
+        }
+        Value::List(items) => {
+            key.push(8);
+            write_len(key, items.len());
+            for item in items.iter() {
+                write_value(key, item);
+            }
+        }
+        Value::Map(map) => {
+            // Entries are written in order of their keys, since maps aren't ordered
+            let mut entries: Vec<Vec<u8>> = map
+                .map
+                .iter()
+                .map(|(k, v)| {
+                    let mut entry = Vec::new();
+                    write_key(&mut entry, k);
+                    write_value(&mut entry, v);
+                    entry
+                })
+                .collect();
+            entries.sort();
+            key.push(9);
+            write_len(key, entries.len());
+            for entry in entries {
+                key.extend_from_slice(&entry);
+            }
+        }
+        Value::Timestamp(ts) => {
+            key.push(10);
+            key.extend_from_slice(&ts.timestamp().to_le_bytes());
+            key.extend_from_slice(&ts.timestamp_subsec_nanos().to_le_bytes());
+            key.extend_from_slice(&ts.offset().local_minus_utc().to_le_bytes());
+        }
+        Value::Duration(d) => {
+            key.push(11);
+            key.extend_from_slice(&d.num_seconds().to_le_bytes());
+            key.extend_from_slice(&d.subsec_nanos().to_le_bytes());
+        }
+        Value::Function(name, target) => {
+            key.push(12);
+            write_bytes(key, name.as_bytes());
+            match target {
+                Some(target) => {
+                    key.push(1);
+                    write_value(key, target);
+                }
+                None => key.push(0),
+            }
+        }
+    }
+}
+
+fn write_key(key: &mut Vec<u8>, k: &Key) {
+    match k {
+        Key::Int(v) => {
+            key.push(0);
+            key.extend_from_slice(&v.to_le_bytes());
+        }
+        Key::Uint(v) => {
+            key.push(1);
+            key.extend_from_slice(&v.to_le_bytes());
+        }
+        Key::Bool(b) => key.push(if *b { 3 } else { 2 }),
+        Key::String(s) => {
+            key.push(4);
+            write_bytes(key, s.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! `cel.complete`, which suggests what can be typed at a position in an
+//! expression, for editors and REPLs.
+//!
+//! Expressions being typed rarely parse, so completion works on the text: the
+//! identifier being typed at the cursor is completed either as a name, or as a
+//! member of the `a.b.c` path before it when it follows a dot.
+use crate::context::Context;
+use crate::functions::BUILTINS;
+use crate::plan::MACROS;
+use cel_interpreter::objects::Key;
+use cel_interpreter::Value;
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+const KEYWORDS: [&str; 4] = ["true", "false", "null", "in"];
+
+/// Candidate completions of the identifier at `cursor` in `expression`, by
+/// default its end, as dicts of the `text` to insert in place of the
+/// identifier from `start` to the cursor and its `kind`: "variable", "field",
+/// "function", "macro" or "keyword".
+///
+/// Variables and the fields of maps they hold are taken from `context`, a
+/// Context or a dict, along with its Python functions.
+#[pyfunction]
+#[pyo3(signature = (expression, cursor=None, context=None))]
+pub fn complete<'py>(
+    py: Python<'py>,
+    expression: &str,
+    cursor: Option<usize>,
+    context: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let chars: Vec<char> = expression.chars().collect();
+    let cursor = cursor.unwrap_or(chars.len());
+    if cursor > chars.len() {
+        return Err(PyIndexError::new_err(format!(
+            "cursor {} is past the end of the expression",
+            cursor
+        )));
+    }
+    let before = &chars[..cursor];
+    if in_string(before) {
+        return Ok(Vec::new());
+    }
+
+    let (borrowed, converted);
+    let context = match context {
+        None => None,
+        Some(context) => match context.extract::<PyRef<Context>>() {
+            Ok(context) => {
+                borrowed = context;
+                Some(&*borrowed)
+            }
+            Err(_) => {
+                let variables = context.downcast::<PyDict>().map_err(|_| {
+                    PyValueError::new_err("context must be a Context object or a dict")
+                })?;
+                let mut context = Context::new(
+                    None, None, false, None, None, false, "error", "error", false, false, None,
+                    None, None,
+                )?;
+                context.update(Some(variables), false, None)?;
+                converted = context;
+                Some(&converted)
+            }
+        },
+    };
+
+    let start = before.len()
+        - before
+            .iter()
+            .rev()
+            .take_while(|c| c.is_alphanumeric() || **c == '_')
+            .count();
+    let prefix: String = before[start..].iter().collect();
+
+    let mut candidates: Vec<(String, &str)> = Vec::new();
+    if start > 0 && before[start - 1] == '.' {
+        let receiver = path(&before[..start - 1])
+            .zip(context)
+            .and_then(|(path, context)| resolve(&path, context));
+        if let Some(Value::Map(map)) = &receiver {
+            let mut fields: Vec<String> = map
+                .map
+                .keys()
+                .filter_map(|key| match key {
+                    Key::String(name) => Some(name.to_string()),
+                    _ => None,
+                })
+                .collect();
+            fields.sort();
+            candidates.extend(fields.into_iter().map(|field| (field, "field")));
+        }
+        for (name, signature, _) in BUILTINS {
+            let applies = match (receiver_of(signature), &receiver) {
+                (Some(_), None) => true,
+                (Some(kind), Some(value)) => accepts(kind, value),
+                // `size` can be called as a method too
+                (None, value) => {
+                    *name == "size"
+                        && value.as_ref().is_none_or(|value| {
+                            matches!(
+                                value,
+                                Value::String(_) | Value::Bytes(_) | Value::List(_) | Value::Map(_)
+                            )
+                        })
+                }
+            };
+            if applies {
+                candidates.push((name.to_string(), kind(name)));
+            }
+        }
+    } else {
+        if let Some(context) = context {
+            let mut variables: Vec<&String> = context.variables.keys().collect();
+            variables.sort();
+            candidates.extend(variables.into_iter().map(|name| (name.clone(), "variable")));
+            let mut functions = context.function_names();
+            functions.extend(
+                context
+                    .extension_functions()
+                    .map(|(name, _, _)| name.to_string()),
+            );
+            functions.sort();
+            candidates.extend(functions.into_iter().map(|name| (name, "function")));
+        }
+        for (name, signature, _) in BUILTINS {
+            if receiver_of(signature).is_none() {
+                candidates.push((name.to_string(), kind(name)));
+            }
+        }
+        candidates.extend(
+            KEYWORDS
+                .iter()
+                .map(|keyword| (keyword.to_string(), "keyword")),
+        );
+    }
+
+    let mut seen = HashSet::new();
+    candidates
+        .into_iter()
+        .filter(|(text, _)| text.starts_with(&prefix) && seen.insert(text.clone()))
+        .map(|(text, kind)| {
+            let candidate = PyDict::new_bound(py);
+            candidate.set_item("text", text)?;
+            candidate.set_item("kind", kind)?;
+            candidate.set_item("start", start)?;
+            Ok(candidate)
+        })
+        .collect()
+}
+
+/// Whether the end of `text` is inside a string literal
+fn in_string(text: &[char]) -> bool {
+    let mut quote = None;
+    let mut escaped = false;
+    for &c in text {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None => {}
+        }
+    }
+    quote.is_some()
+}
+
+/// The names of the `a.b.c` path that `text` ends with
+fn path(text: &[char]) -> Option<Vec<String>> {
+    let length = text
+        .iter()
+        .rev()
+        .take_while(|c| c.is_alphanumeric() || **c == '_' || **c == '.')
+        .count();
+    let path: String = text[text.len() - length..].iter().collect();
+    let names: Vec<String> = path.split('.').map(str::to_string).collect();
+    let valid = names.iter().all(|name| {
+        name.chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+    });
+    valid.then_some(names)
+}
+
+/// The value at `path` in the variables of `context`
+fn resolve(path: &[String], context: &Context) -> Option<Value> {
+    let (variable, fields) = path.split_first()?;
+    let mut value = context.variables.get(variable)?.clone();
+    for field in fields {
+        value = match value {
+            Value::Map(map) => map.map.get(&Key::String(Arc::new(field.clone())))?.clone(),
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// What a builtin is called on according to its signature, e.g. "string" for
+/// `string.contains(substring) -> bool`, or None for a global function
+fn receiver_of(signature: &str) -> Option<&str> {
+    let call = signature.split('(').next()?;
+    call.split_once('.').map(|(receiver, _)| receiver)
+}
+
+/// Whether a method called on `receiver` can be called on `value`
+fn accepts(receiver: &str, value: &Value) -> bool {
+    match receiver {
+        "string" => matches!(value, Value::String(_)),
+        "timestamp" => matches!(value, Value::Timestamp(_)),
+        "list" | "target" => matches!(value, Value::List(_) | Value::Map(_)),
+        _ => false,
+    }
+}
+
+fn kind(name: &str) -> &'static str {
+    match MACROS.contains(&name) || name == "has" {
+        true => "macro",
+        false => "function",
+    }
+}
@@ -0,0 +1,110 @@
+//! The comprehension macros for the interpreter, replacing its own so they
+//! behave as plans do: `map` can take a predicate selecting the items it maps,
+//! `filter` iterates over the keys of a map like the other macros, `existsOne`
+//! is another name for `exists_one`, `count` counts the items its predicate is
+//! true for, `groupBy` and `countBy` group them by a key, `distinct` keeps the
+//! first item with each key, and errors are absorbed by `all` and
+//! `exists` as they are by `&&` and `||`. Calls to batch functions in the body
+//! are made for all the items at once, see [`crate::batch`].
+use crate::batch;
+use crate::functions::this_or_arg;
+use crate::plan::{fold_comprehension, items, Macro};
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use cel_parser::Expression;
+
+/// Evaluates the macro `kind`, called as `target.macro(x, body)`, or as
+/// `target.map(x, predicate, body)`
+fn comprehension(ftx: &FunctionContext, kind: Macro) -> ResolveResult {
+    let (target, args) = match &ftx.this {
+        Some(this) => (this.clone(), &ftx.args[..]),
+        None => match ftx.args.split_first() {
+            Some((target, args)) => (ftx.ptx.resolve(target)?, args),
+            None => return Err(ExecutionError::invalid_argument_count(2, 0)),
+        },
+    };
+    let (variable, filter, body) = match (kind, args) {
+        (_, [Expression::Ident(variable), body]) => (variable, None, body),
+        (Macro::Map, [Expression::Ident(variable), filter, body]) => (variable, Some(filter), body),
+        (_, [_, _]) | (Macro::Map, [_, _, _]) => return Err(ftx.error("expected a variable name")),
+        _ => return Err(ExecutionError::invalid_argument_count(2, args.len())),
+    };
+
+    let mut scope = ftx.ptx.new_inner_scope();
+    let prefetched = batch::prefetch(&mut scope, variable, &target, filter, body);
+    let outcomes = items(&target)?.enumerate().filter_map(|(index, item)| {
+        scope.add_variable_from_value(variable.as_str(), item.clone());
+        let (filter, body) = prefetched
+            .as_ref()
+            .and_then(|prefetched| prefetched.bind(&mut scope, index))
+            .unwrap_or((filter, body));
+        match filter.map(|filter| scope.resolve(filter)) {
+            None | Some(Ok(Value::Bool(true))) => {}
+            Some(Ok(_)) => return None,
+            Some(Err(error)) => return Some((item, Err(error))),
+        }
+        let value = scope.resolve(body);
+        Some((item, value))
+    });
+    fold_comprehension(kind, outcomes)
+}
+
+pub fn map(ftx: &FunctionContext) -> ResolveResult {
+    comprehension(ftx, Macro::Map)
+}
+
+pub fn filter(ftx: &FunctionContext) -> ResolveResult {
+    comprehension(ftx, Macro::Filter)
+}
+
+pub fn all(ftx: &FunctionContext) -> ResolveResult {
+    comprehension(ftx, Macro::All)
+}
+
+pub fn exists(ftx: &FunctionContext) -> ResolveResult {
+    comprehension(ftx, Macro::Exists)
+}
+
+pub fn exists_one(ftx: &FunctionContext) -> ResolveResult {
+    comprehension(ftx, Macro::ExistsOne)
+}
+
+pub fn count(ftx: &FunctionContext) -> ResolveResult {
+    comprehension(ftx, Macro::Count)
+}
+
+pub fn group_by(ftx: &FunctionContext) -> ResolveResult {
+    comprehension(ftx, Macro::GroupBy)
+}
+
+pub fn count_by(ftx: &FunctionContext) -> ResolveResult {
+    comprehension(ftx, Macro::CountBy)
+}
+
+/// Also callable without a variable, as `list.distinct()` or `distinct(list)`,
+/// to keep the first of each equal item
+pub fn distinct(ftx: &FunctionContext) -> ResolveResult {
+    let items_only = match ftx.this {
+        Some(_) => ftx.args.is_empty(),
+        None => ftx.args.len() == 1,
+    };
+    if !items_only {
+        return comprehension(ftx, Macro::Distinct);
+    }
+    let target = this_or_arg(ftx)?;
+    let outcomes = items(&target)?.map(|item| (item.clone(), Ok(item)));
+    fold_comprehension(Macro::Distinct, outcomes)
+}
+
+/// Adds the macros to an environment, in place of the interpreter's
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function("map", map);
+    environment.add_function("filter", filter);
+    environment.add_function("all", all);
+    environment.add_function("exists", exists);
+    environment.add_function("exists_one", exists_one);
+    environment.add_function("existsOne", exists_one);
+    environment.add_function("count", count);
+    environment.add_function("groupBy", group_by);
+    environment.add_function("countBy", count_by);
+    environment.add_function("distinct", distinct);
+}
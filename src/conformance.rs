@@ -0,0 +1,542 @@
+//! `cel.conformance`, a client for a remote CEL conformance service, to compare
+//! the results of this implementation with another one, such as cel-go or
+//! cel-java, before relying on expressions behaving the same in both.
+//!
+//! The service is the `google.api.expr.conformance.v1alpha1.ConformanceService`
+//! the CEL conformance tests run against, reached with the JSON mapping of its
+//! messages over HTTP: each method is a POST of a JSON request to
+//! `{url}/google.api.expr.conformance.v1alpha1.ConformanceService/{method}`, as
+//! served by a Connect handler or a gRPC-JSON transcoding proxy in front of a
+//! gRPC server.
+//!
+//! Values are sent and read as `google.api.expr.v1alpha1.Value` messages, with
+//! durations and timestamps as `google.protobuf.Duration` and `Timestamp`
+//! objects.
+use crate::errors::EvalError;
+use crate::output::OutputTypes;
+use crate::{duration, evaluate_value, memo, repr_of, resolve_mode, timestamps, unknowns};
+use crate::{Converter, Outcome, RustyCelType};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE};
+use base64::Engine;
+use cel_interpreter::objects::{Key, Map};
+use cel_interpreter::Value;
+use chrono::SecondsFormat;
+use pyo3::exceptions::{PyConnectionError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyBytes, PyDict, PyString, PyTuple};
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The path of the service, which its methods are appended to
+const SERVICE: &str = "google.api.expr.conformance.v1alpha1.ConformanceService";
+
+const TYPE_URL_PREFIX: &str = "type.googleapis.com/";
+
+/// A remote CEL implementation, serving the conformance service at `url`
+#[pyclass(frozen, module = "cel.conformance")]
+pub struct RemoteService {
+    #[pyo3(get)]
+    url: String,
+    /// Seconds to wait for each request
+    #[pyo3(get)]
+    timeout: f64,
+}
+
+#[pymethods]
+impl RemoteService {
+    #[new]
+    #[pyo3(signature = (url, timeout=10.0))]
+    fn new(url: String, timeout: f64) -> Self {
+        RemoteService {
+            url: url.trim_end_matches('/').to_string(),
+            timeout,
+        }
+    }
+
+    /// The parsed expression the service returns for `expr`, as a dict.
+    /// Raises a ValueError with the issues the service reported if it fails to
+    /// parse.
+    fn parse(&self, py: Python<'_>, expr: &str) -> PyResult<PyObject> {
+        let parsed = self.parsed(py, expr)?.map_err(|error| error.to_py_err())?;
+        to_py(py, &parsed)
+    }
+
+    /// The checked expression the service returns for `expr`, with the type it
+    /// deduced for each subexpression, as a dict. Raises a ValueError with the
+    /// issues the service reported if it fails to parse or check.
+    fn check(&self, py: Python<'_>, expr: &str) -> PyResult<PyObject> {
+        let parsed = self.parsed(py, expr)?.map_err(|error| error.to_py_err())?;
+        let response = self.call(py, "Check", json!({ "parsedExpr": parsed }))?;
+        match field(&response, "checkedExpr") {
+            Some(checked) => to_py(py, checked),
+            None => Err(EvalError::remote(expr, "compile", issues(&response)).to_py_err()),
+        }
+    }
+
+    /// Evaluates `expr` with the service, binding the items of `variables`.
+    /// Raises a ValueError if the service fails to parse or evaluate it.
+    #[pyo3(signature = (expr, variables=None))]
+    fn evaluate(
+        &self,
+        py: Python<'_>,
+        expr: &str,
+        variables: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyObject> {
+        match self.outcome(py, expr, variables)? {
+            Outcome::Value(value) => RustyCelType(value).try_into_py(py, &OutputTypes::default()),
+            Outcome::Unknown(attributes) => Ok(unknowns::Unknown { attributes }.into_py(py)),
+            Outcome::Error(error) => Err(error.to_py_err()),
+        }
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!("RemoteService({})", repr_of(py, &self.url))
+    }
+}
+
+impl RemoteService {
+    /// Calls `method` of the service with `request`, returning its response
+    fn call(&self, py: Python<'_>, method: &str, request: Json) -> PyResult<Json> {
+        let url = format!("{}/{}/{}", self.url, SERVICE, method);
+        let urllib = py.import_bound("urllib.request")?;
+        let body = PyBytes::new_bound(py, request.to_string().as_bytes());
+        let headers = [("Content-Type", "application/json")].into_py_dict_bound(py);
+        let request = urllib.getattr("Request")?.call1((
+            &url,
+            body,
+            headers,
+            None::<PyObject>,
+            false,
+            "POST",
+        ))?;
+        let timeout = [("timeout", self.timeout)].into_py_dict_bound(py);
+        let response = match urllib.call_method("urlopen", (request,), Some(&timeout)) {
+            Ok(response) => response.call_method0("read")?,
+            Err(error) => {
+                // Errors of the service come with a JSON body giving their reason
+                let http_error = py.import_bound("urllib.error")?.getattr("HTTPError")?;
+                if !error.is_instance_bound(py, &http_error) {
+                    return Err(error);
+                }
+                let body: Vec<u8> = error.value_bound(py).call_method0("read")?.extract()?;
+                let message = serde_json::from_slice::<Json>(&body)
+                    .ok()
+                    .and_then(|body| field(&body, "message")?.as_str().map(str::to_string))
+                    .unwrap_or_else(|| error.value_bound(py).to_string());
+                return Err(PyConnectionError::new_err(format!(
+                    "conformance service failed to {}: {}",
+                    method.to_lowercase(),
+                    message
+                )));
+            }
+        };
+        let body: Vec<u8> = response.extract()?;
+        serde_json::from_slice(&body).map_err(|error| {
+            PyValueError::new_err(format!(
+                "conformance service returned an invalid {} response: {}",
+                method, error
+            ))
+        })
+    }
+
+    /// The parsed expression the service returns for `expr`, or the error it
+    /// failed to parse with
+    fn parsed(&self, py: Python<'_>, expr: &str) -> PyResult<Result<Json, EvalError>> {
+        let response = self.call(py, "Parse", json!({ "celSource": expr }))?;
+        Ok(match field(&response, "parsedExpr") {
+            Some(parsed) => Ok(parsed.clone()),
+            None => Err(EvalError::remote(expr, "compile", issues(&response))),
+        })
+    }
+
+    /// The outcome of evaluating `expr` with the service
+    pub(crate) fn outcome(
+        &self,
+        py: Python<'_>,
+        expr: &str,
+        variables: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Outcome> {
+        let mut bindings = serde_json::Map::new();
+        if let Some(variables) = variables {
+            let mut converter = Converter::default();
+            for (name, value) in variables {
+                let name: String = name.extract()?;
+                let value = converter
+                    .convert(&value)
+                    .map_err(|error| PyValueError::new_err(error.to_string()))?;
+                let value = to_json(&value).map_err(|message| {
+                    PyTypeError::new_err(format!("can't send variable '{}': {}", name, message))
+                })?;
+                bindings.insert(name, json!({ "value": value }));
+            }
+        }
+
+        let parsed = match self.parsed(py, expr)? {
+            Ok(parsed) => parsed,
+            Err(error) => return Ok(Outcome::Error(error)),
+        };
+        let response = self.call(
+            py,
+            "Eval",
+            json!({ "parsedExpr": parsed, "bindings": bindings }),
+        )?;
+        let Some(result) = field(&response, "result") else {
+            return Ok(Outcome::Error(EvalError::remote(
+                expr,
+                "execution",
+                issues(&response),
+            )));
+        };
+        if let Some(value) = field(result, "value") {
+            return Ok(Outcome::Value(from_json(value).map_err(|message| {
+                PyValueError::new_err(format!(
+                    "conformance service returned a value that can't be read: {}",
+                    message
+                ))
+            })?));
+        }
+        if let Some(error) = field(result, "error") {
+            return Ok(Outcome::Error(EvalError::remote(
+                expr,
+                "execution",
+                messages(field(error, "errors")),
+            )));
+        }
+        if let Some(unknown) = field(result, "unknown") {
+            let exprs = field(unknown, "exprs").and_then(Json::as_array);
+            let attributes = exprs
+                .into_iter()
+                .flatten()
+                .map(|id| id.to_string().trim_matches('"').to_string())
+                .collect();
+            return Ok(Outcome::Unknown(attributes));
+        }
+        Err(PyValueError::new_err(
+            "conformance service returned a result with neither a value nor an error",
+        ))
+    }
+}
+
+/// A difference between the outcomes of evaluating an expression locally and
+/// with a remote service
+#[pyclass(frozen, module = "cel.conformance")]
+pub struct Divergence {
+    #[pyo3(get)]
+    expression: String,
+    /// "compile" if only one side failed to compile the expression, "error" if
+    /// only one side failed to evaluate it, or "value" if both returned a value
+    /// and the values differ
+    #[pyo3(get)]
+    kind: &'static str,
+    /// The local result, or an `EvalError`
+    #[pyo3(get)]
+    local: PyObject,
+    /// The remote result, or an `EvalError`
+    #[pyo3(get)]
+    remote: PyObject,
+}
+
+#[pymethods]
+impl Divergence {
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!(
+            "Divergence(expression={}, kind={}, local={}, remote={})",
+            repr_of(py, &self.expression),
+            repr_of(py, self.kind),
+            self.local.bind(py).repr()?,
+            self.remote.bind(py).repr()?
+        ))
+    }
+}
+
+/// Evaluates each of `expressions` both locally and with `service`, returning a
+/// `Divergence` for each expression whose outcomes differ.
+///
+/// An expression is either a string or a `(string, variables)` tuple. Values
+/// are compared exactly, so `1` and `1.0` differ, and errors only by whether
+/// they happened while compiling or evaluating, as implementations word their
+/// messages differently. `mode` is the mode of the local evaluations.
+#[pyfunction(signature = (service, expressions, mode=None))]
+fn compare(
+    py: Python<'_>,
+    service: &RemoteService,
+    expressions: &Bound<'_, PyAny>,
+    mode: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Vec<Divergence>> {
+    let options = resolve_mode(None, mode)?;
+    let mut divergences = Vec::new();
+    for item in expressions.iter()? {
+        let item = item?;
+        let (expression, variables) = match item.downcast::<PyString>() {
+            Ok(expression) => (expression.to_string(), None),
+            Err(_) => match item.downcast::<PyTuple>() {
+                Ok(pair) if pair.len() == 2 => {
+                    (pair.get_item(0)?.extract()?, Some(pair.get_item(1)?))
+                }
+                _ => {
+                    return Err(PyTypeError::new_err(format!(
+                        "expected an expression or an (expression, variables) tuple, got {}",
+                        crate::type_name(&item)
+                    )))
+                }
+            },
+        };
+        let variables = variables
+            .as_ref()
+            .map(|v| v.downcast::<PyDict>())
+            .transpose()?;
+
+        let local = evaluate_value(
+            &expression,
+            variables.map(|v| v.as_any()),
+            None,
+            None,
+            options,
+            None,
+        )?;
+        let remote = service.outcome(py, &expression, variables)?;
+        if let Some(kind) = divergence(&local, &remote) {
+            divergences.push(Divergence {
+                expression,
+                kind,
+                local: outcome_into_py(py, local)?,
+                remote: outcome_into_py(py, remote)?,
+            });
+        }
+    }
+    Ok(divergences)
+}
+
+/// The kind of divergence between two outcomes, if they differ
+pub(crate) fn divergence(local: &Outcome, remote: &Outcome) -> Option<&'static str> {
+    match (local, remote) {
+        (Outcome::Error(local), Outcome::Error(remote)) if local.kind == remote.kind => None,
+        (Outcome::Error(error), _) | (_, Outcome::Error(error)) => match error.kind {
+            "compile" => Some("compile"),
+            _ => Some("error"),
+        },
+        (Outcome::Value(local), Outcome::Value(remote)) => {
+            // The value as it would be sent, e.g. with timestamps in UTC
+            let local = to_json(local)
+                .and_then(|json| from_json(&json))
+                .unwrap_or_else(|_| local.clone());
+            (!memo::same(&local, remote)).then_some("value")
+        }
+        (Outcome::Unknown(_), Outcome::Unknown(_)) => None,
+        _ => Some("value"),
+    }
+}
+
+pub(crate) fn outcome_into_py(py: Python<'_>, outcome: Outcome) -> PyResult<PyObject> {
+    crate::outcome_into_py(
+        py,
+        outcome,
+        true,
+        false,
+        OutputTypes::default(),
+        &Default::default(),
+    )
+}
+
+/// The field `name` of a JSON message, given in camelCase, which parsers also
+/// accept in snake_case
+fn field<'a>(message: &'a Json, name: &str) -> Option<&'a Json> {
+    let object = message.as_object()?;
+    object.get(name).or_else(|| {
+        let snake_case: String = name
+            .chars()
+            .flat_map(|c| match c.is_ascii_uppercase() {
+                true => vec!['_', c.to_ascii_lowercase()],
+                false => vec![c],
+            })
+            .collect();
+        object.get(&snake_case)
+    })
+}
+
+/// The issues of a response, which are `google.rpc.Status` messages
+fn issues(response: &Json) -> String {
+    messages(field(response, "issues"))
+}
+
+fn messages(statuses: Option<&Json>) -> String {
+    let messages: Vec<&str> = statuses
+        .and_then(Json::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|status| field(status, "message")?.as_str())
+        .collect();
+    match messages.is_empty() {
+        true => "the conformance service gave no reason".to_string(),
+        false => messages.join("; "),
+    }
+}
+
+fn to_py(py: Python<'_>, json: &Json) -> PyResult<PyObject> {
+    let loads = py.import_bound("json")?.getattr("loads")?;
+    Ok(loads.call1((json.to_string(),))?.unbind())
+}
+
+/// A CEL value as a `google.api.expr.v1alpha1.Value` message
+fn to_json(value: &Value) -> Result<Json, String> {
+    Ok(match value {
+        Value::Null => json!({ "nullValue": null }),
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Int(i) => json!({ "int64Value": i.to_string() }),
+        Value::UInt(u) => json!({ "uint64Value": u.to_string() }),
+        Value::Float(f) if f.is_nan() => json!({ "doubleValue": "NaN" }),
+        Value::Float(f) if f.is_infinite() => {
+            json!({ "doubleValue": if *f > 0.0 { "Infinity" } else { "-Infinity" } })
+        }
+        Value::Float(f) => json!({ "doubleValue": f }),
+        Value::String(s) => json!({ "stringValue": s.as_str() }),
+        Value::Bytes(b) => json!({ "bytesValue": STANDARD.encode(b.as_slice()) }),
+        Value::List(items) => {
+            let values = items.iter().map(to_json).collect::<Result<Vec<_>, _>>()?;
+            json!({ "listValue": { "values": values } })
+        }
+        Value::Map(map) => {
+            let entries = map
+                .map
+                .iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        Key::Int(i) => json!({ "int64Value": i.to_string() }),
+                        Key::Uint(u) => json!({ "uint64Value": u.to_string() }),
+                        Key::Bool(b) => json!({ "boolValue": b }),
+                        Key::String(s) => json!({ "stringValue": s.as_str() }),
+                    };
+                    Ok(json!({ "key": key, "value": to_json(value)? }))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            json!({ "mapValue": { "entries": entries } })
+        }
+        Value::Duration(d) => {
+            let nanos = d.num_seconds() as i128 * 1_000_000_000 + d.subsec_nanos() as i128;
+            let sign = if nanos < 0 { "-" } else { "" };
+            let seconds = duration::decimal(nanos.unsigned_abs(), 9);
+            object("google.protobuf.Duration", format!("{}{}s", sign, seconds))
+        }
+        Value::Timestamp(ts) => object(
+            "google.protobuf.Timestamp",
+            ts.to_utc().to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        ),
+        Value::Function(name, _) => return Err(format!("function '{}' has no value", name)),
+    })
+}
+
+/// A well known type as the `Any` of an `objectValue`
+fn object(type_name: &str, value: String) -> Json {
+    json!({ "objectValue": { "@type": format!("{}{}", TYPE_URL_PREFIX, type_name), "value": value } })
+}
+
+/// The CEL value of a `google.api.expr.v1alpha1.Value` message
+fn from_json(message: &Json) -> Result<Value, String> {
+    let object = message
+        .as_object()
+        .ok_or_else(|| format!("expected a Value message, got {}", message))?;
+    let Some((kind, content)) = object.iter().next() else {
+        return Err("got a Value message without a value".to_string());
+    };
+    let invalid = || format!("invalid {} {}", kind, content);
+    Ok(match kind.as_str() {
+        "nullValue" | "null_value" => Value::Null,
+        "boolValue" | "bool_value" => Value::Bool(content.as_bool().ok_or_else(invalid)?),
+        "int64Value" | "int64_value" | "enumValue" | "enum_value" => {
+            let number = match field(content, "value") {
+                Some(number) => number,
+                None => content,
+            };
+            Value::Int(integer(number).ok_or_else(invalid)?)
+        }
+        "uint64Value" | "uint64_value" => Value::UInt(integer(content).ok_or_else(invalid)?),
+        "doubleValue" | "double_value" => Value::Float(match content {
+            Json::String(s) => match s.as_str() {
+                "NaN" => f64::NAN,
+                "Infinity" => f64::INFINITY,
+                "-Infinity" => f64::NEG_INFINITY,
+                s => s.parse().map_err(|_| invalid())?,
+            },
+            number => number.as_f64().ok_or_else(invalid)?,
+        }),
+        "stringValue" | "string_value" => {
+            Value::String(Arc::new(content.as_str().ok_or_else(invalid)?.to_string()))
+        }
+        "bytesValue" | "bytes_value" => {
+            let encoded = content.as_str().ok_or_else(invalid)?;
+            let bytes = STANDARD
+                .decode(encoded)
+                .or_else(|_| URL_SAFE.decode(encoded))
+                .map_err(|_| invalid())?;
+            Value::Bytes(Arc::new(bytes))
+        }
+        "listValue" | "list_value" => {
+            let values = field(content, "values").and_then(Json::as_array);
+            let items = values
+                .into_iter()
+                .flatten()
+                .map(from_json)
+                .collect::<Result<Vec<_>, _>>()?;
+            Value::List(Arc::new(items))
+        }
+        "mapValue" | "map_value" => {
+            let entries = field(content, "entries").and_then(Json::as_array);
+            let mut map = HashMap::new();
+            for entry in entries.into_iter().flatten() {
+                let key = match field(entry, "key").map(from_json).transpose()? {
+                    Some(Value::Int(i)) => Key::Int(i),
+                    Some(Value::UInt(u)) => Key::Uint(u),
+                    Some(Value::Bool(b)) => Key::Bool(b),
+                    Some(Value::String(s)) => Key::String(s),
+                    _ => return Err(format!("invalid map key in {}", entry)),
+                };
+                let value = field(entry, "value").ok_or_else(invalid)?;
+                map.insert(key, from_json(value)?);
+            }
+            Value::Map(Map { map: Arc::new(map) })
+        }
+        "objectValue" | "object_value" => {
+            let type_url = field(content, "@type").and_then(Json::as_str);
+            let value = field(content, "value").and_then(Json::as_str);
+            match (
+                type_url.and_then(|url| url.strip_prefix(TYPE_URL_PREFIX)),
+                value,
+            ) {
+                (Some("google.protobuf.Duration"), Some(value)) => {
+                    Value::Duration(duration::parse(value)?)
+                }
+                (Some("google.protobuf.Timestamp"), Some(value)) => {
+                    Value::Timestamp(timestamps::parse(value, false)?)
+                }
+                _ => return Err(format!("unsupported object {}", content)),
+            }
+        }
+        _ => return Err(format!("unsupported value {}", message)),
+    })
+}
+
+/// An integer, which the JSON mapping writes as a string
+fn integer<T: std::str::FromStr + TryFrom<i64> + TryFrom<u64>>(number: &Json) -> Option<T> {
+    match number {
+        Json::String(s) => s.parse().ok(),
+        number => match number.as_i64() {
+            Some(i) => T::try_from(i).ok(),
+            None => T::try_from(number.as_u64()?).ok(),
+        },
+    }
+}
+
+/// Adds the `cel.conformance` module
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    let conformance = PyModule::new_bound(py, "conformance")?;
+    conformance.add_class::<RemoteService>()?;
+    conformance.add_class::<Divergence>()?;
+    conformance.add_function(wrap_pyfunction!(compare, &conformance)?)?;
+    m.add_submodule(&conformance)?;
+    // Lets `import cel.conformance` find the module
+    py.import_bound("sys")?
+        .getattr("modules")?
+        .set_item("cel.conformance", conformance)
+}
@@ -1,78 +1,858 @@
-use cel_interpreter::objects::TryIntoValue;
+use crate::datetime;
+use crate::format;
+use crate::functions::{self, Described};
+use crate::geo;
+use crate::hash;
+use crate::memory;
+use crate::objects::{Found, Objects, Reader};
+use crate::options::Options;
+use crate::originals::{Originals, Recorded};
+use crate::output::OutputTypes;
+use crate::random;
+use crate::strings;
+use crate::toolkit;
+use crate::{build_environment, CelError, Converter, Environment};
 use cel_interpreter::Value;
-use pyo3::exceptions::PyValueError;
+use chrono::{DateTime, FixedOffset};
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyUserWarning, PyValueError};
 use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
 use pyo3::types::{PyDict, PyTuple};
-use std::collections::HashMap;
+use pyo3::{PyTraverseError, PyVisit};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Functions registered with `cel.register_global_function`
+#[derive(Clone)]
+struct GlobalFunctions {
+    functions: BTreeMap<String, Arc<Py<PyAny>>>,
+    /// A count of the changes to the functions, which environments built with
+    /// them are checked against
+    changes: u64,
+}
+
+static GLOBAL_FUNCTIONS: Mutex<GlobalFunctions> = Mutex::new(GlobalFunctions {
+    functions: BTreeMap::new(),
+    changes: 0,
+});
+
+/// The global functions, copied so that no lock is held while calling Python
+fn global_functions() -> GlobalFunctions {
+    GLOBAL_FUNCTIONS.lock().unwrap().clone()
+}
+
+/// Whether any global functions are registered
+pub fn has_global_functions() -> bool {
+    !GLOBAL_FUNCTIONS.lock().unwrap().functions.is_empty()
+}
+
+/// Make a function available to every evaluation, without adding it to a
+/// Context. Functions of the context or dict an expression is evaluated with
+/// take precedence over global functions of the same name, which in turn
+/// replace builtins.
+#[pyfunction]
+pub fn register_global_function(name: String, function: &Bound<'_, PyAny>) -> PyResult<()> {
+    if !function.is_callable() {
+        return Err(PyTypeError::new_err(format!(
+            "function '{}' isn't callable, got {}",
+            name,
+            crate::type_name(function)
+        )));
+    }
+    let mut global = GLOBAL_FUNCTIONS.lock().unwrap();
+    global.changes += 1;
+    global
+        .functions
+        .insert(name, Arc::new(function.clone().unbind()));
+    Ok(())
+}
+
+/// Remove a global function, raising a KeyError if there isn't one
+#[pyfunction]
+pub fn unregister_global_function(name: &str) -> PyResult<()> {
+    let mut global = GLOBAL_FUNCTIONS.lock().unwrap();
+    global
+        .functions
+        .remove(name)
+        .ok_or_else(|| PyKeyError::new_err(name.to_string()))?;
+    global.changes += 1;
+    Ok(())
+}
+
+/// How a function added to a context is called
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FunctionOptions {
+    /// How long a call may take
+    pub timeout: Option<Duration>,
+    /// Whether the function is called with lists of arguments, see
+    /// [`crate::batch`]
+    pub batch: bool,
+}
 
 #[pyo3::pyclass]
 pub struct Context {
     pub variables: HashMap<String, Value>,
-    pub functions: HashMap<String, Py<PyAny>>,
+    /// Shared with the environment, see [`build_environment`]
+    pub functions: HashMap<String, Arc<Py<PyAny>>>,
+    /// How the functions added with options are called
+    function_options: HashMap<String, FunctionOptions>,
+    /// When set, selecting a missing field (or any field of null) evaluates to null
+    #[pyo3(get, set)]
+    pub safe_navigation: bool,
+    /// The Python types results are converted to
+    pub output_types: OutputTypes,
+    /// The options of the mode used by evaluations that don't pass one
+    pub mode: Options,
+    /// When set, named tuples are converted to maps of their fields rather than lists
+    #[pyo3(get)]
+    pub namedtuples_as_maps: bool,
+    /// When set, bytes dictionary keys are decoded as UTF-8 rather than rejected
+    pub decode_bytes_keys: bool,
+    /// The names of the extensions whose functions and types were added
+    #[pyo3(get)]
+    pub extensions: Vec<String>,
+    /// When set, each Python function is called once for each set of arguments
+    /// within an evaluation, and later calls return the first call's result
+    #[pyo3(get, set)]
+    pub memoize: bool,
+    /// Turns exceptions raised by Python functions into CEL errors, see
+    /// [`crate::error_mapper`]
+    error_mapper: Option<Arc<Py<PyAny>>>,
+    /// The time `now()` of the `datetime` extension returns, rather than the
+    /// system clock's
+    clock: Option<DateTime<FixedOffset>>,
+    /// Seeds the numbers `rand()` and `sample()` draw, and salts the hashes of
+    /// `bucket()`
+    #[pyo3(get)]
+    pub seed: Option<u64>,
+    /// The objects of each variable whose attributes are read while evaluating,
+    /// when objects that can't be converted are kept rather than rejected
+    objects: Option<Objects>,
+    /// The objects the lists and maps of each variable were converted from, in
+    /// round-trip mode
+    originals: Option<HashMap<String, Arc<Recorded>>>,
+    /// Whether global functions are available, which they are unless
+    /// evaluating in the sandbox
+    global_functions: bool,
+    /// The environment last built from the variables and functions, and the
+    /// options and the count of changes to the global functions it was built
+    /// for, which is reused until any of them changes
+    environment: Mutex<Option<(Options, u64, Arc<Environment>)>>,
+}
+
+/// An empty context in the Python mode, as `Context()` creates
+impl Default for Context {
+    fn default() -> Self {
+        Context {
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            function_options: HashMap::new(),
+            safe_navigation: false,
+            output_types: OutputTypes::default(),
+            mode: Options::default(),
+            namedtuples_as_maps: false,
+            decode_bytes_keys: false,
+            extensions: Vec::new(),
+            memoize: false,
+            error_mapper: None,
+            clock: None,
+            seed: None,
+            objects: None,
+            originals: None,
+            global_functions: true,
+            environment: Mutex::default(),
+        }
+    }
 }
 
+/// The time a datetime freezes the clock at, a naive one converted as
+/// variables are
+fn fixed_time(now: &Bound<'_, PyAny>) -> PyResult<DateTime<FixedOffset>> {
+    match crate::Converter::default().convert(now) {
+        Ok(Value::Timestamp(now)) => Ok(now),
+        _ => Err(PyTypeError::new_err(format!(
+            "the clock must be a datetime or None, got {}",
+            crate::type_name(now)
+        ))),
+    }
+}
+
+/// The extensions implemented in Rust, which `Context(extensions=[...])` loads
+/// by name rather than from entry points, and their functions
+const BUILT_IN_EXTENSIONS: [(&str, &[Described]); 6] = [
+    (toolkit::NAME, toolkit::FUNCTIONS),
+    (geo::NAME, geo::FUNCTIONS),
+    (hash::NAME, hash::FUNCTIONS),
+    (strings::NAME, strings::FUNCTIONS),
+    (format::NAME, format::FUNCTIONS),
+    (datetime::NAME, datetime::FUNCTIONS),
+];
+
 #[pyo3::pymethods]
 impl Context {
     #[new]
-    pub fn new(variables: Option<&PyDict>, functions: Option<&PyDict>) -> PyResult<Self> {
+    #[pyo3(signature = (variables=None, functions=None, safe_navigation=false, output_types=None, mode=None, namedtuples_as_maps=false, bytes_keys="error", objects="error", round_trip=false, memoize=false, extensions=None, clock=None, seed=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        variables: Option<&Bound<'_, PyDict>>,
+        functions: Option<&Bound<'_, PyDict>>,
+        safe_navigation: bool,
+        output_types: Option<&Bound<'_, PyDict>>,
+        mode: Option<&Bound<'_, PyAny>>,
+        namedtuples_as_maps: bool,
+        bytes_keys: &str,
+        objects: &str,
+        round_trip: bool,
+        memoize: bool,
+        extensions: Option<Vec<String>>,
+        clock: Option<&Bound<'_, PyAny>>,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
         let mut context = Context {
-            variables: HashMap::new(),
-            functions: HashMap::new(),
+            safe_navigation,
+            output_types: match output_types {
+                Some(output_types) => OutputTypes::from_dict(output_types)?,
+                None => OutputTypes::default(),
+            },
+            mode: match mode {
+                Some(mode) => Options::from_mode(mode)?,
+                None => Options::default(),
+            },
+            namedtuples_as_maps,
+            decode_bytes_keys: match bytes_keys {
+                "error" => false,
+                "decode" => true,
+                _ => {
+                    return Err(PyValueError::new_err(
+                        "bytes_keys must be either 'error' or 'decode'",
+                    ))
+                }
+            },
+            objects: match objects {
+                "error" => None,
+                "attributes" => Some(Objects::default()),
+                _ => {
+                    return Err(PyValueError::new_err(
+                        "objects must be either 'error' or 'attributes'",
+                    ))
+                }
+            },
+            originals: round_trip.then(HashMap::new),
+            memoize,
+            clock: clock.map(fixed_time).transpose()?,
+            seed,
+            ..Context::default()
         };
 
+        // Types of extensions are registered before the variables are converted
+        for name in extensions.into_iter().flatten() {
+            // Built-in extensions have their functions added to the environment
+            if !BUILT_IN_EXTENSIONS
+                .iter()
+                .any(|(built_in, _)| *built_in == name)
+            {
+                Python::with_gil(|py| {
+                    let functions = crate::load_extension(py, &name)?;
+                    context.update(None, false, Some(&functions))
+                })?;
+            }
+            context.extensions.push(name);
+        }
+
         if let Some(variables) = variables {
-            //context.variables.extend(variables.clone());
+            // Variables are converted together so they share interned keys
+            let mut converter = context.converter();
             for (k, v) in variables {
                 let key = k
                     .extract::<String>()
                     .map_err(|_| PyValueError::new_err("Variable name must be strings"));
-                key.map(|key| context.add_variable(key, v))??;
+                key.map(|key| context.convert_variable(&mut converter, key, &v))??;
             }
         };
 
         if let Some(functions) = functions {
-            context.update(functions)?;
+            context.update(None, false, Some(functions))?;
         };
 
         Ok(context)
     }
 
-    fn add_function(&mut self, name: String, function: Py<PyAny>) {
-        self.functions.insert(name, function);
+    /// The `cel.Options` of the mode, which can be set to "python", "strict" or
+    /// an `Options`
+    #[getter(mode)]
+    fn get_mode(&self) -> Options {
+        self.mode
     }
 
-    pub fn add_variable(&mut self, name: String, value: &PyAny) -> PyResult<()> {
-        let value = crate::RustyPyType(value).try_into_value().map_err(|e| {
-            pyo3::exceptions::PyValueError::new_err(format!(
-                "Failed to convert variable '{}': {}",
-                name, e
-            ))
-        })?;
-        self.variables.insert(name, value);
+    #[setter(mode)]
+    fn set_mode(&mut self, mode: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.mode = Options::from_mode(mode)?;
+        Ok(())
+    }
+
+    /// How bytes dictionary keys are converted, "error" or "decode"
+    #[getter]
+    fn bytes_keys(&self) -> &'static str {
+        match self.decode_bytes_keys {
+            true => "decode",
+            false => "error",
+        }
+    }
+
+    /// How objects that can't be converted are handled, "error" or "attributes"
+    #[getter(objects)]
+    fn get_objects(&self) -> &'static str {
+        match self.objects {
+            Some(_) => "attributes",
+            None => "error",
+        }
+    }
+
+    /// Whether lists and maps that evaluate to themselves are returned as the
+    /// objects they were converted from
+    #[getter]
+    fn round_trip(&self) -> bool {
+        self.originals.is_some()
+    }
+
+    /// Add a function. With a `timeout` in seconds, each call runs on a thread of
+    /// its own and fails with a TimeoutError if it hasn't returned in time; the
+    /// call itself can't be stopped and carries on in the background.
+    ///
+    /// A `batch` function takes a list of values for each of its arguments and
+    /// returns a list of as many results. Calls to it in the body of a `map`,
+    /// `filter`, `all`, `exists` or `existsOne` are made once for all the items,
+    /// and any other call with lists of one value.
+    #[pyo3(signature = (name, function, timeout=None, batch=false))]
+    fn add_function(
+        &mut self,
+        name: String,
+        function: Py<PyAny>,
+        timeout: Option<f64>,
+        batch: bool,
+    ) -> PyResult<()> {
+        let timeout = timeout
+            .map(|timeout| {
+                Duration::try_from_secs_f64(timeout)
+                    .ok()
+                    .filter(|timeout| !timeout.is_zero())
+                    .ok_or_else(|| {
+                        PyValueError::new_err(format!(
+                            "timeout must be a positive number of seconds, got {}",
+                            timeout
+                        ))
+                    })
+            })
+            .transpose()?;
+        self.insert_function(name.clone(), function);
+        let options = FunctionOptions { timeout, batch };
+        if options != FunctionOptions::default() {
+            self.function_options.insert(name, options);
+        }
+        Ok(())
+    }
+
+    pub fn add_variable(&mut self, name: String, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.convert_variable(&mut self.converter(), name, value)
+    }
+
+    /// Replace the value of an existing variable, raising a KeyError if there
+    /// isn't one.
+    ///
+    /// Only the new value is converted and replaced in the environment, which
+    /// makes this the cheapest way to change a large context between evaluations.
+    pub fn update_variable(&mut self, name: String, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        if !self.variables.contains_key(&name) {
+            return Err(PyKeyError::new_err(name));
+        }
+        self.convert_variable(&mut self.converter(), name, value)
+    }
+
+    /// Approximate number of bytes retained by the context: its converted
+    /// variables and the environment last built from them, whose values share
+    /// their contents with the variables.
+    pub fn memory_usage(&self) -> usize {
+        let variables = memory::map_heap(&self.variables, |name, value| {
+            memory::string_heap(name) + memory::value_heap(value)
+        });
+        let functions = memory::map_heap(&self.functions, |name, _| memory::string_heap(name));
+        let environment = match &*self.environment.lock().unwrap() {
+            Some((_, _, environment)) => match &**environment {
+                cel_interpreter::Context::Root { variables, .. } => {
+                    memory::map_heap(variables, |name, _| memory::string_heap(name))
+                }
+                cel_interpreter::Context::Child { .. } => 0,
+            },
+            None => 0,
+        };
+        std::mem::size_of::<Context>() + variables + functions + environment
+    }
+
+    /// What can be called in expressions evaluated against the context, as a
+    /// dict from each name to a dict of its `signature`, its `doc` and whether
+    /// it is `builtin`, which the functions of built-in extensions are.
+    ///
+    /// The signature and doc of a Python function are taken from the function
+    /// and are None if it has none. Python functions, including global ones,
+    /// replace builtins of the same name.
+    fn functions_info<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let info = PyDict::new_bound(py);
+        let builtins = functions::BUILTINS.iter().chain(self.extension_functions());
+        for (name, signature, doc) in builtins {
+            let entry = PyDict::new_bound(py);
+            entry.set_item("signature", signature)?;
+            entry.set_item("doc", doc)?;
+            entry.set_item("builtin", true)?;
+            info.set_item(name, entry)?;
+        }
+
+        let inspect = py.import_bound("inspect")?;
+        let functions = self.functions_with_globals();
+        let mut names: Vec<&String> = functions.keys().collect();
+        names.sort();
+        for name in names {
+            let function = functions[name].bind(py);
+            // Builtins implemented in C may not have a signature
+            let signature = match inspect.call_method1("signature", (function,)) {
+                Ok(signature) => Some(format!("{}{}", name, signature.str()?)),
+                Err(_) => None,
+            };
+            let entry = PyDict::new_bound(py);
+            entry.set_item("signature", signature)?;
+            entry.set_item("doc", inspect.call_method1("getdoc", (function,))?)?;
+            entry.set_item("builtin", false)?;
+            info.set_item(name, entry)?;
+        }
+        Ok(info)
+    }
+
+    /// Set the function that turns exceptions raised by Python functions into
+    /// CEL errors, or remove it with None.
+    ///
+    /// It is called with the name of the function and the exception, and
+    /// returns None to keep the default error, the message of the error, or a
+    /// dict of an optional `message` and whether the error is `absorbable` by
+    /// `&&`, `||` and the other operators that absorb errors. An error that
+    /// isn't absorbable fails the evaluation even when the other operand
+    /// decides the result.
+    #[pyo3(signature = (mapper))]
+    fn set_error_mapper(&mut self, mapper: Option<&Bound<'_, PyAny>>) -> PyResult<()> {
+        if let Some(mapper) = mapper.filter(|mapper| !mapper.is_callable()) {
+            return Err(PyTypeError::new_err(format!(
+                "error mapper isn't callable, got {}",
+                crate::type_name(mapper)
+            )));
+        }
+        self.error_mapper = mapper.map(|mapper| Arc::new(mapper.clone().unbind()));
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Freeze the time that `now()` and `today()` of the `datetime` extension
+    /// see at a datetime, as `Context(clock=...)` does, or go back to the
+    /// system clock with None.
+    #[pyo3(signature = (now))]
+    fn set_now(&mut self, now: Option<&Bound<'_, PyAny>>) -> PyResult<()> {
+        self.clock = now.map(fixed_time).transpose()?;
+        self.invalidate();
+        Ok(())
+    }
+
+    /// The time the clock is frozen at, or None if it is the system clock
+    #[getter]
+    fn clock(&self) -> Option<DateTime<FixedOffset>> {
+        self.clock
+    }
+
+    /// Remove a variable, raising a KeyError if there isn't one
+    fn remove_variable(&mut self, name: &str) -> PyResult<()> {
+        self.variables
+            .remove(name)
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))?;
+        if let Some(objects) = &self.objects {
+            objects.lock().unwrap().remove(name);
+        }
+        if let Some(originals) = &mut self.originals {
+            originals.remove(name);
+        }
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Remove a function, raising a KeyError if there isn't one
+    fn remove_function(&mut self, name: &str) -> PyResult<()> {
+        self.functions
+            .remove(name)
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))?;
+        self.function_options.remove(name);
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Remove all variables and functions, releasing the converted values and
+    /// the references to the functions straight away
+    fn clear(&mut self) {
+        self.variables = HashMap::new();
+        self.functions = HashMap::new();
+        self.function_options = HashMap::new();
+        if let Some(objects) = &self.objects {
+            *objects.lock().unwrap() = HashMap::new();
+        }
+        if let Some(originals) = &mut self.originals {
+            *originals = HashMap::new();
+        }
+        self.invalidate();
+    }
+
+    /// Lets the garbage collector find reference cycles through functions and
+    /// objects, such as a closure that refers to the context it was added to
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        for function in self.functions.values() {
+            visit.call(&**function)?;
+        }
+        if let Some(error_mapper) = &self.error_mapper {
+            visit.call(&**error_mapper)?;
+        }
+        if let Some(objects) = &self.objects {
+            for object in objects.lock().unwrap().values().flatten() {
+                visit.call(&**object)?;
+            }
+        }
+        for recorded in self.originals.iter().flat_map(HashMap::values) {
+            for object in recorded.objects() {
+                visit.call(object)?;
+            }
+        }
         Ok(())
     }
 
-    pub fn update(&mut self, variables: &PyDict) -> PyResult<()> {
-        for (key, value) in variables {
+    fn __clear__(&mut self) {
+        self.functions.clear();
+        self.error_mapper = None;
+        if let Some(objects) = &self.objects {
+            objects.lock().unwrap().clear();
+        }
+        if let Some(originals) = &mut self.originals {
+            originals.clear();
+        }
+        self.invalidate();
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    /// Leaving a `with` block clears the context
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&mut self, _args: &Bound<'_, PyAny>) -> bool {
+        self.clear();
+        false
+    }
+
+    /// Add variables and functions. Callable values in `variables` are added as
+    /// functions, with a warning for callables that aren't plain functions, such
+    /// as classes or objects with a `__call__` method, as these may be meant as data.
+    ///
+    /// Values in `functions` are always added as functions, and with
+    /// `force_variable=True` values in `variables` are always added as variables.
+    #[pyo3(signature = (variables=None, force_variable=false, functions=None))]
+    pub fn update(
+        &mut self,
+        variables: Option<&Bound<'_, PyDict>>,
+        force_variable: bool,
+        functions: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<()> {
+        let mut converter = self.converter();
+        for (key, value) in variables.into_iter().flatten() {
             // Attempt to extract the key as a String
             let key = key
                 .extract::<String>()
                 .map_err(|_| PyValueError::new_err("Keys must be strings"))?;
 
-            if value.is_callable() {
+            if value.is_callable() && !force_variable {
+                if !value.is_instance(&function_types(value.py())?)? {
+                    PyErr::warn_bound(
+                        value.py(),
+                        &value.py().get_type_bound::<PyUserWarning>(),
+                        &format!(
+                            "'{}' is a callable {} so it was added as a function, pass it in \
+                             `functions` to add it as one, or use force_variable=True to add \
+                             it as a variable",
+                            key,
+                            crate::type_name(&value)
+                        ),
+                        1,
+                    )?;
+                }
                 // Value is a function, add it to the functions hashmap
-                let py_function = value.to_object(value.py());
-                self.functions.insert(key, py_function);
+                self.insert_function(key, value.unbind());
             } else {
                 // Value is a variable, add it to the variables hashmap
-                let value = crate::RustyPyType(value)
-                    .try_into_value()
-                    .map_err(|e| PyValueError::new_err(e.to_string()))?;
+                self.convert_variable(&mut converter, key, &value)?;
+            }
+        }
 
-                self.variables.insert(key, value);
+        for (key, value) in functions.into_iter().flatten() {
+            let key = key
+                .extract::<String>()
+                .map_err(|_| PyValueError::new_err("Keys must be strings"))?;
+            if !value.is_callable() {
+                return Err(PyTypeError::new_err(format!(
+                    "function '{}' isn't callable, got {}",
+                    key,
+                    crate::type_name(&value)
+                )));
             }
+            self.insert_function(key, value.unbind());
         }
 
         Ok(())
     }
 }
+
+impl Context {
+    fn converter(&self) -> Converter {
+        Converter {
+            namedtuples_as_maps: self.namedtuples_as_maps,
+            decode_bytes_keys: self.decode_bytes_keys,
+            ..Converter::default()
+        }
+    }
+
+    fn convert_variable(
+        &mut self,
+        converter: &mut Converter,
+        name: String,
+        value: &Bound<'_, PyAny>,
+    ) -> PyResult<()> {
+        if self.objects.is_some() {
+            converter.objects = Some(Found::Variable {
+                name: name.clone(),
+                objects: Vec::new(),
+            });
+        }
+        if self.originals.is_some() {
+            converter.originals = Some(HashMap::new());
+        }
+        let value = converter
+            .convert(value)
+            .map_err(|e| conversion_error(&name, e))?;
+        if let (Some(originals), Some(found)) = (&mut self.originals, converter.originals.take()) {
+            match found.is_empty() {
+                true => originals.remove(&name),
+                false => {
+                    originals.insert(name.clone(), Arc::new(Recorded::new(value.clone(), found)))
+                }
+            };
+        }
+        if let (Some(objects), Some(Found::Variable { objects: found, .. })) =
+            (&self.objects, converter.objects.take())
+        {
+            let mut objects = objects.lock().unwrap();
+            match found.is_empty() {
+                true => objects.remove(&name),
+                false => objects.insert(name.clone(), found),
+            };
+        }
+        self.set_variable(name, value);
+        Ok(())
+    }
+
+    /// Whether objects that can't be converted are kept, to read their
+    /// attributes while evaluating
+    pub fn holds_objects(&self) -> bool {
+        self.objects.is_some()
+    }
+
+    /// The objects the variables were converted from, in round-trip mode
+    pub fn originals(&self) -> Originals {
+        let recorded = self.originals.iter().flat_map(HashMap::values).cloned();
+        Originals(recorded.collect())
+    }
+
+    /// A copy of the context that variables can be added to without changing this one
+    pub fn fork(&self) -> Context {
+        Context {
+            variables: self.variables.clone(),
+            functions: self.functions.clone(),
+            function_options: self.function_options.clone(),
+            safe_navigation: self.safe_navigation,
+            output_types: self.output_types,
+            mode: self.mode,
+            namedtuples_as_maps: self.namedtuples_as_maps,
+            decode_bytes_keys: self.decode_bytes_keys,
+            objects: self
+                .objects
+                .as_ref()
+                .map(|objects| Arc::new(Mutex::new(objects.lock().unwrap().clone()))),
+            originals: self.originals.clone(),
+            extensions: self.extensions.clone(),
+            memoize: self.memoize,
+            error_mapper: self.error_mapper.clone(),
+            clock: self.clock,
+            seed: self.seed,
+            global_functions: self.global_functions,
+            environment: Mutex::default(),
+        }
+    }
+
+    /// Sets a variable, replacing it in the environment rather than discarding
+    /// the environment, unless an evaluation is still using it
+    pub fn set_variable(&mut self, name: String, value: Value) {
+        let cached = self.environment.get_mut().unwrap();
+        match cached
+            .as_mut()
+            .and_then(|(_, _, environment)| Arc::get_mut(environment))
+        {
+            Some(environment) => environment.add_variable_from_value(name.clone(), value.clone()),
+            None => *cached = None,
+        }
+        self.variables.insert(name, value);
+    }
+
+    /// The environment to evaluate against this context with `options`
+    pub fn environment(&self, options: &Options) -> Arc<Environment> {
+        let changes = match self.global_functions {
+            true => GLOBAL_FUNCTIONS.lock().unwrap().changes,
+            false => 0,
+        };
+        let mut cached = self.environment.lock().unwrap();
+        match &*cached {
+            Some((built_for, built_with, environment))
+                if built_for == options && *built_with == changes =>
+            {
+                environment.clone()
+            }
+            _ => {
+                let objects = self.objects.clone().map(|objects| Reader {
+                    objects,
+                    namedtuples_as_maps: self.namedtuples_as_maps,
+                    decode_bytes_keys: self.decode_bytes_keys,
+                });
+                let mut environment = build_environment(
+                    &self.variables,
+                    &self.functions_with_globals(),
+                    &self.function_options,
+                    self.error_mapper.as_ref(),
+                    objects,
+                    &self.extensions,
+                    options,
+                );
+                if let Some(now) = self.clock {
+                    environment.add_variable_from_value(datetime::NOW, Value::Timestamp(now));
+                }
+                if let Some(seed) = self.seed {
+                    environment.add_variable_from_value(random::SEED, Value::UInt(seed));
+                }
+                let environment = Arc::new(environment);
+                *cached = Some((*options, changes, environment.clone()));
+                environment
+            }
+        }
+    }
+
+    /// Adds or replaces a function, without options
+    fn insert_function(&mut self, name: String, function: Py<PyAny>) {
+        self.function_options.remove(&name);
+        self.functions.insert(name, Arc::new(function));
+        self.invalidate();
+    }
+
+    /// The functions of the context together with the global functions it
+    /// doesn't replace
+    fn functions_with_globals(&self) -> HashMap<String, Arc<Py<PyAny>>> {
+        let mut functions = HashMap::new();
+        if self.global_functions {
+            functions.extend(global_functions().functions);
+        }
+        functions.extend(self.functions.clone());
+        functions
+    }
+
+    /// The names of the Python functions that can be called in expressions
+    /// evaluated against the context, including global functions
+    pub fn function_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.functions.keys().cloned().collect();
+        if self.global_functions && has_global_functions() {
+            let global = global_functions().functions.into_keys();
+            names.extend(global.filter(|name| !self.functions.contains_key(name)));
+        }
+        names
+    }
+
+    /// Whether the extension `name` was loaded
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|extension| extension == name)
+    }
+
+    /// The functions of the built-in extensions that were loaded, with their
+    /// signature and what they do
+    pub fn extension_functions(&self) -> impl Iterator<Item = &'static Described> + '_ {
+        BUILT_IN_EXTENSIONS
+            .iter()
+            .filter(|(name, _)| self.has_extension(name))
+            .flat_map(|(_, functions)| functions.iter())
+    }
+
+    /// The qualified names of the builtins and the functions of the built-in
+    /// extensions that were loaded that are in a namespace, like `geo.point`
+    pub fn namespaced_functions(&self) -> Vec<&'static str> {
+        functions::BUILTINS
+            .iter()
+            .chain(self.extension_functions())
+            .map(|(name, _, _)| *name)
+            .filter(|name| name.contains('.'))
+            .collect()
+    }
+
+    /// Leaves global functions out of the environment, as the sandbox only
+    /// allows the functions passed to it
+    pub fn without_global_functions(&mut self) {
+        self.global_functions = false;
+        self.invalidate();
+    }
+
+    fn invalidate(&mut self) {
+        *self.environment.get_mut().unwrap() = None;
+    }
+}
+
+/// Names the variable that failed to convert, and where within it the failure was
+fn conversion_error(name: &str, error: CelError) -> PyErr {
+    let CelError::ConversionError { path, message } = error;
+    match path.is_empty() {
+        true => PyValueError::new_err(format!(
+            "Failed to convert variable '{}': {}",
+            name, message
+        )),
+        false => PyValueError::new_err(format!(
+            "Failed to convert variable '{}' at context['{}']{}: {}",
+            name, name, path, message
+        )),
+    }
+}
+
+/// The types of callables that are plainly functions rather than objects that
+/// happen to be callable, as a tuple for `isinstance`
+fn function_types(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    static FUNCTION_TYPES: GILOnceCell<PyObject> = GILOnceCell::new();
+    FUNCTION_TYPES
+        .get_or_try_init(py, || {
+            let types = py.import_bound("types")?;
+            let mut function_types = [
+                "FunctionType",
+                "BuiltinFunctionType",
+                "MethodType",
+                "MethodWrapperType",
+                "MethodDescriptorType",
+                "WrapperDescriptorType",
+            ]
+            .into_iter()
+            .map(|name| types.getattr(name))
+            .collect::<PyResult<Vec<_>>>()?;
+            function_types.push(py.import_bound("functools")?.getattr("partial")?);
+            Ok::<_, PyErr>(PyTuple::new_bound(py, function_types).into_any().unbind())
+        })
+        .map(|function_types| function_types.bind(py).clone())
+}
@@ -0,0 +1,136 @@
+//! The conversion functions `int()`, `uint()`, `double()`, `string()`,
+//! `bytes()`, `bool()` and `dyn()`, which replace the interpreter's with the
+//! edge case behaviour of the CEL specification.
+//!
+//! Failures are reported with a [`ConversionFailure`] so that they raise a
+//! `cel.ConversionError` in Python.
+use crate::duration;
+use crate::errors::ConversionFailure::{Encoding, Overload, Range, Syntax};
+use crate::functions::this_or_arg;
+use crate::types::name_of;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use std::sync::Arc;
+
+/// Adds the conversion functions to the environment
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function("int", int);
+    environment.add_function("uint", uint);
+    environment.add_function("double", double);
+    environment.add_function("string", string);
+    environment.add_function("bytes", bytes);
+    environment.add_function("bool", bool);
+    environment.add_function("dyn", dyn_);
+}
+
+fn cannot_convert(ftx: &FunctionContext, value: &Value, to: &str) -> ExecutionError {
+    Overload.error(ftx, format!("cannot convert {} to {}", name_of(value), to))
+}
+
+/// Converts to an int. Doubles are truncated towards zero, and must be within
+/// the range of an int; timestamps give seconds since the Unix epoch.
+///
+/// # Examples
+/// ```cel
+/// int('-42') == -42
+/// int(2.9) == 2 && int(-2.9) == -2
+/// ```
+pub fn int(ftx: &FunctionContext) -> ResolveResult {
+    Ok(match this_or_arg(ftx)? {
+        Value::Int(v) => Value::Int(v),
+        Value::UInt(v) => Value::Int(
+            i64::try_from(v)
+                .map_err(|_| Range.error(ftx, format!("{}u is out of range for int", v)))?,
+        ),
+        // The bounds are -2^63 and 2^63, so NaN and infinities are rejected too
+        Value::Float(v) if v >= i64::MIN as f64 && v < i64::MAX as f64 => Value::Int(v as i64),
+        Value::Float(v) => return Err(Range.error(ftx, format!("{} is out of range for int", v))),
+        Value::String(s) => Value::Int(
+            s.parse()
+                .map_err(|_| Syntax.error(ftx, format!("cannot parse {:?} as int", s)))?,
+        ),
+        Value::Timestamp(ts) => Value::Int(ts.timestamp()),
+        other => return Err(cannot_convert(ftx, &other, "int")),
+    })
+}
+
+/// Converts to a uint. Doubles are truncated towards zero, and must be within
+/// the range of a uint.
+pub fn uint(ftx: &FunctionContext) -> ResolveResult {
+    Ok(match this_or_arg(ftx)? {
+        Value::UInt(v) => Value::UInt(v),
+        Value::Int(v) => Value::UInt(
+            u64::try_from(v)
+                .map_err(|_| Range.error(ftx, format!("{} is out of range for uint", v)))?,
+        ),
+        Value::Float(v) if v > -1.0 && v < u64::MAX as f64 => Value::UInt(v as u64),
+        Value::Float(v) => return Err(Range.error(ftx, format!("{} is out of range for uint", v))),
+        Value::String(s) => Value::UInt(
+            s.parse()
+                .map_err(|_| Syntax.error(ftx, format!("cannot parse {:?} as uint", s)))?,
+        ),
+        other => return Err(cannot_convert(ftx, &other, "uint")),
+    })
+}
+
+/// Converts to a double
+pub fn double(ftx: &FunctionContext) -> ResolveResult {
+    Ok(match this_or_arg(ftx)? {
+        Value::Float(v) => Value::Float(v),
+        Value::Int(v) => Value::Float(v as f64),
+        Value::UInt(v) => Value::Float(v as f64),
+        Value::String(s) => Value::Float(
+            s.parse()
+                .map_err(|_| Syntax.error(ftx, format!("cannot parse {:?} as double", s)))?,
+        ),
+        other => return Err(cannot_convert(ftx, &other, "double")),
+    })
+}
+
+/// Converts a value to a string, formatting durations like `"72h3m0.5s"`.
+///
+/// Bytes must be valid UTF-8.
+pub fn string(ftx: &FunctionContext) -> ResolveResult {
+    Ok(match this_or_arg(ftx)? {
+        Value::String(v) => Value::String(v),
+        Value::Bool(v) => Value::String(v.to_string().into()),
+        Value::Timestamp(t) => Value::String(t.to_rfc3339().into()),
+        Value::Duration(v) => Value::String(duration::format(&v).into()),
+        Value::Int(v) => Value::String(v.to_string().into()),
+        Value::UInt(v) => Value::String(v.to_string().into()),
+        Value::Float(v) => Value::String(v.to_string().into()),
+        Value::Bytes(v) => match std::str::from_utf8(v.as_slice()) {
+            Ok(s) => Value::String(Arc::new(s.to_string())),
+            Err(e) => return Err(Encoding.error(ftx, e)),
+        },
+        other => return Err(cannot_convert(ftx, &other, "string")),
+    })
+}
+
+/// Converts a string to its UTF-8 bytes
+pub fn bytes(ftx: &FunctionContext) -> ResolveResult {
+    Ok(match this_or_arg(ftx)? {
+        Value::Bytes(v) => Value::Bytes(v),
+        Value::String(s) => Value::Bytes(Arc::new(s.as_bytes().to_vec())),
+        other => return Err(cannot_convert(ftx, &other, "bytes")),
+    })
+}
+
+/// Converts to a bool, accepting the same strings as cel-go: `"true"`, `"True"`,
+/// `"TRUE"`, `"t"` and `"1"`, and likewise for false.
+pub fn bool(ftx: &FunctionContext) -> ResolveResult {
+    Ok(match this_or_arg(ftx)? {
+        Value::Bool(v) => Value::Bool(v),
+        Value::String(s) => match s.as_str() {
+            "true" | "True" | "TRUE" | "t" | "1" => Value::Bool(true),
+            "false" | "False" | "FALSE" | "f" | "0" => Value::Bool(false),
+            _ => return Err(Syntax.error(ftx, format!("cannot parse {:?} as bool", s))),
+        },
+        other => return Err(cannot_convert(ftx, &other, "bool")),
+    })
+}
+
+/// Returns its argument unchanged. CEL uses `dyn()` to opt out of type checking,
+/// which this implementation doesn't do, so it only exists for compatibility.
+pub fn dyn_(ftx: &FunctionContext) -> ResolveResult {
+    this_or_arg(ftx)
+}
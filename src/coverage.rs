@@ -0,0 +1,354 @@
+//! `cel.Coverage`, which records the parts of expressions that evaluations
+//! exercised, so that the test suite of a policy can show it covers every
+//! branch.
+//!
+//! An evaluation with coverage runs the expression with each of its nodes
+//! wrapped in a call to [`COVER`], which counts the evaluations of the node
+//! before resolving it, and how often it was true and false. A chain of fields
+//! selected from a variable, like `user.age`, is one node, as are the arguments
+//! of `has()`.
+use crate::explain::is_path;
+use crate::transform::{call, map_children};
+use crate::unparse::unparse;
+use crate::{options, plan, stats, Job, Outcome};
+use cel_interpreter::{FunctionContext, ResolveResult, Value};
+use cel_parser::{Atom, Expression, Member, UnaryOp};
+use pyo3::prelude::*;
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+/// Internal function that nodes are wrapped in while their coverage is recorded
+pub const COVER: &str = "@cover";
+
+/// How often a node was evaluated, and to true and to false
+#[derive(Debug, Clone, Copy, Default)]
+struct Counts {
+    evaluations: u64,
+    true_count: u64,
+    false_count: u64,
+}
+
+thread_local! {
+    /// The counts of the nodes of the expression evaluated with coverage on
+    /// the thread
+    static COUNTS: RefCell<Option<Vec<Counts>>> = const { RefCell::new(None) };
+}
+
+/// A node of an expression, in the order of a depth first walk
+#[derive(Debug)]
+struct Node {
+    expression: String,
+    parent: Option<usize>,
+    /// Whether the node is an operand of `&&`, `||` or `!`, or the condition
+    /// of `?:`
+    condition: bool,
+    counts: Counts,
+}
+
+impl Node {
+    /// Whether the node's outcomes are branches: a condition, or the whole
+    /// expression once it has evaluated to a bool
+    fn is_condition(&self) -> bool {
+        self.condition
+            || (self.parent.is_none() && self.counts.true_count + self.counts.false_count > 0)
+    }
+
+    /// How many of the node's branches were taken
+    fn taken(&self) -> usize {
+        (self.counts.true_count > 0) as usize + (self.counts.false_count > 0) as usize
+    }
+}
+
+/// The nodes of `source`, with the instrumented expression that counts their
+/// evaluations
+#[derive(Debug)]
+struct Instrumented {
+    source: String,
+    expression: Arc<Expression>,
+    nodes: Vec<Node>,
+}
+
+/// Records the parts of expressions exercised by the evaluations it's passed
+/// to, e.g. `program.evaluate(context, coverage=coverage)`, across all of them
+#[pyclass(frozen, module = "cel")]
+pub struct Coverage {
+    expressions: Mutex<Vec<Instrumented>>,
+}
+
+#[pymethods]
+impl Coverage {
+    #[new]
+    fn new() -> Self {
+        Coverage {
+            expressions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The sources of the expressions evaluated with this coverage
+    #[getter]
+    fn expressions(&self) -> Vec<String> {
+        let expressions = self.expressions.lock().unwrap();
+        expressions.iter().map(|e| e.source.clone()).collect()
+    }
+
+    /// The fraction of nodes evaluated at least once, over every expression
+    #[getter]
+    fn node_coverage(&self) -> f64 {
+        let expressions = self.expressions.lock().unwrap();
+        let nodes = expressions.iter().flat_map(|e| &e.nodes);
+        let (covered, total) = nodes.fold((0, 0), |(covered, total), node| {
+            (covered + (node.counts.evaluations > 0) as usize, total + 1)
+        });
+        fraction(covered, total)
+    }
+
+    /// The fraction of branches taken at least once, over every expression:
+    /// each condition being true and being false
+    #[getter]
+    fn branch_coverage(&self) -> f64 {
+        let expressions = self.expressions.lock().unwrap();
+        let conditions = expressions
+            .iter()
+            .flat_map(|e| &e.nodes)
+            .filter(|node| node.is_condition());
+        let (covered, total) = conditions.fold((0, 0), |(covered, total), node| {
+            (covered + node.taken(), total + 2)
+        });
+        fraction(covered, total)
+    }
+
+    /// What wasn't exercised, as `(expression, node, reason)` tuples where the
+    /// reason is "never evaluated", "never true" or "never false". Only the
+    /// outermost of the nodes that were never evaluated is listed.
+    fn missing(&self) -> Vec<(String, String, &'static str)> {
+        let expressions = self.expressions.lock().unwrap();
+        let mut missing = Vec::new();
+        for instrumented in expressions.iter() {
+            for node in &instrumented.nodes {
+                let counts = node.counts;
+                let parent_evaluated = node
+                    .parent
+                    .is_none_or(|parent| instrumented.nodes[parent].counts.evaluations > 0);
+                let mut miss = |reason| {
+                    missing.push((instrumented.source.clone(), node.expression.clone(), reason))
+                };
+                if counts.evaluations == 0 {
+                    if parent_evaluated {
+                        miss("never evaluated");
+                    }
+                } else if node.is_condition() {
+                    if counts.true_count == 0 {
+                        miss("never true");
+                    }
+                    if counts.false_count == 0 {
+                        miss("never false");
+                    }
+                }
+            }
+        }
+        missing
+    }
+
+    /// A report of the coverage of each expression and what it is missing
+    fn report(&self) -> String {
+        let missing = self.missing();
+        let expressions = self.expressions.lock().unwrap();
+        let mut report = String::new();
+        for instrumented in expressions.iter() {
+            let nodes = &instrumented.nodes;
+            let evaluated = nodes.iter().filter(|n| n.counts.evaluations > 0).count();
+            let conditions = nodes.iter().filter(|n| n.is_condition());
+            let branches = conditions.clone().count() * 2;
+            let taken: usize = conditions.map(Node::taken).sum();
+            let _ = writeln!(
+                report,
+                "{}: {}/{} nodes, {}/{} branches",
+                instrumented.source,
+                evaluated,
+                nodes.len(),
+                taken,
+                branches
+            );
+            for (_, node, reason) in missing.iter().filter(|m| m.0 == instrumented.source) {
+                let _ = writeln!(report, "  {}: {}", reason, node);
+            }
+        }
+        report
+    }
+
+    fn __repr__(&self) -> String {
+        let expressions = self.expressions.lock().unwrap().len();
+        format!(
+            "Coverage(expressions={}, node_coverage={:.2}, branch_coverage={:.2})",
+            expressions,
+            self.node_coverage(),
+            self.branch_coverage()
+        )
+    }
+}
+
+fn fraction(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        1.0
+    } else {
+        covered as f64 / total as f64
+    }
+}
+
+impl Coverage {
+    /// Runs `job` for `program`, the parsed `source`, counting the evaluations
+    /// of its nodes
+    pub(crate) fn run(&self, job: Job, source: &str, program: &Expression) -> Outcome {
+        // The expression is checked as written, as the static types of the
+        // instrumented one are unknown
+        if let Err(message) = options::validate(program, &job.options) {
+            stats::failed("rejected");
+            return Outcome::Error(options::rejection(source, message));
+        }
+        let (instrumented, nodes) = {
+            let mut expressions = self.expressions.lock().unwrap();
+            let position = expressions.iter().position(|e| e.source == source);
+            let index = position.unwrap_or_else(|| {
+                expressions.push(instrument(source, program));
+                expressions.len() - 1
+            });
+            let instrumented = &expressions[index];
+            (instrumented.expression.clone(), instrumented.nodes.len())
+        };
+
+        // A function called by the evaluation may evaluate with coverage
+        // itself, whose counts replace these until it is done
+        let outer = COUNTS.with(|counts| counts.replace(Some(vec![Counts::default(); nodes])));
+        let outcome = job.run(source, &instrumented, None);
+        let counts = COUNTS
+            .with(|counts| counts.replace(outer))
+            .unwrap_or_default();
+
+        let mut expressions = self.expressions.lock().unwrap();
+        if let Some(instrumented) = expressions.iter_mut().find(|e| e.source == source) {
+            for (node, counts) in instrumented.nodes.iter_mut().zip(counts) {
+                node.counts.evaluations += counts.evaluations;
+                node.counts.true_count += counts.true_count;
+                node.counts.false_count += counts.false_count;
+            }
+        }
+        outcome
+    }
+}
+
+/// Wraps each node of `program` in a call to [`COVER`] with its number
+fn instrument(source: &str, program: &Expression) -> Instrumented {
+    let mut nodes = Vec::new();
+    let expression = wrap(program, None, false, &mut nodes);
+    Instrumented {
+        source: source.to_string(),
+        expression: Arc::new(expression),
+        nodes,
+    }
+}
+
+fn wrap(
+    expr: &Expression,
+    parent: Option<usize>,
+    condition: bool,
+    nodes: &mut Vec<Node>,
+) -> Expression {
+    let index = nodes.len();
+    nodes.push(Node {
+        expression: unparse(expr),
+        parent,
+        condition,
+        counts: Counts::default(),
+    });
+    let parent = Some(index);
+    let wrapped = match expr {
+        // Paths are kept whole, as unknowns and type names are matched by path
+        _ if is_path(expr) => expr.clone(),
+        Expression::And(left, right) | Expression::Or(left, right) => {
+            let left = wrap(left, parent, true, nodes);
+            let right = wrap(right, parent, true, nodes);
+            match expr {
+                Expression::And(..) => Expression::And(left.into(), right.into()),
+                _ => Expression::Or(left.into(), right.into()),
+            }
+        }
+        Expression::Unary(op @ (UnaryOp::Not | UnaryOp::DoubleNot), operand) => {
+            Expression::Unary(op.clone(), wrap(operand, parent, true, nodes).into())
+        }
+        Expression::Ternary(condition, left, right) => Expression::Ternary(
+            wrap(condition, parent, true, nodes).into(),
+            wrap(left, parent, false, nodes).into(),
+            wrap(right, parent, false, nodes).into(),
+        ),
+        // The target of a construction names a type
+        Expression::Member(target, member) if matches!(&**member, Member::Fields(_)) => {
+            Expression::Member(
+                target.clone(),
+                Box::new(match &**member {
+                    Member::Fields(fields) => Member::Fields(
+                        fields
+                            .iter()
+                            .map(|(name, value)| (name.clone(), wrap(value, parent, false, nodes)))
+                            .collect(),
+                    ),
+                    member => member.clone(),
+                }),
+            )
+        }
+        Expression::FunctionCall(function, None, _) if matches!(&**function, Expression::Ident(name) if name.as_str() == "has") => {
+            expr.clone()
+        }
+        // The first argument of a macro names its variable
+        Expression::FunctionCall(function, Some(target), args) if plan::is_macro(function) => {
+            let target = wrap(target, parent, false, nodes);
+            let mut args = args.iter();
+            let variable = args.next().cloned();
+            Expression::FunctionCall(
+                function.clone(),
+                Some(target.into()),
+                variable
+                    .into_iter()
+                    .chain(
+                        args.map(|arg| wrap(arg, parent, false, nodes))
+                            .collect::<Vec<_>>(),
+                    )
+                    .collect(),
+            )
+        }
+        Expression::FunctionCall(function, target, args) => Expression::FunctionCall(
+            function.clone(),
+            target
+                .as_ref()
+                .map(|target| wrap(target, parent, false, nodes).into()),
+            args.iter()
+                .map(|arg| wrap(arg, parent, false, nodes))
+                .collect(),
+        ),
+        _ => map_children(expr, |child| wrap(child, parent, false, nodes)),
+    };
+    call(
+        COVER,
+        vec![Expression::Atom(Atom::Int(index as i64)), wrapped],
+    )
+}
+
+/// Implementation of [`COVER`], which counts an evaluation of the node
+/// numbered by its first argument and resolves the second
+pub fn cover(ftx: &FunctionContext) -> ResolveResult {
+    let result = ftx.ptx.resolve(&ftx.args[1]);
+    if let Expression::Atom(Atom::Int(index)) = &ftx.args[0] {
+        COUNTS.with(|counts| {
+            let mut counts = counts.borrow_mut();
+            if let Some(counts) = counts.as_mut().and_then(|c| c.get_mut(*index as usize)) {
+                counts.evaluations += 1;
+                match &result {
+                    Ok(Value::Bool(true)) => counts.true_count += 1,
+                    Ok(Value::Bool(false)) => counts.false_count += 1,
+                    _ => {}
+                }
+            }
+        });
+    }
+    result
+}
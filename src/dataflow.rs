@@ -0,0 +1,213 @@
+//! `cel.plan`, which evaluates named expressions that refer to each other's
+//! results in an order where every result is computed before it is used.
+use crate::context::Context;
+use crate::options::Options;
+use crate::plan::is_macro;
+use crate::program::Program;
+use crate::transform::map_children;
+use crate::{originals, outcome_into_py, output_types, repr_of, resolve_mode, Job, Outcome};
+use cel_parser::Expression;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::BTreeSet;
+
+/// Named expressions in the order they are evaluated, created by `cel.plan`.
+///
+/// Each result is added to the context of the evaluation under its name, so
+/// the expressions after it can refer to it like any other variable.
+#[pyclass(frozen, module = "cel")]
+pub struct Dataflow {
+    steps: Vec<(String, Py<Program>)>,
+}
+
+#[pymethods]
+impl Dataflow {
+    /// The names of the expressions in the order they are evaluated
+    #[getter]
+    pub fn order(&self) -> Vec<String> {
+        self.steps.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Evaluate every expression, returning a dict of their results by name in
+    /// the order they were evaluated. The first expression that fails raises
+    /// its error.
+    ///
+    /// The variables and functions of `evaluation_context` are available to
+    /// all of them. A passed in Context isn't changed.
+    #[pyo3(signature = (evaluation_context=None, mode=None))]
+    fn evaluate<'py>(
+        &self,
+        py: Python<'py>,
+        evaluation_context: Option<&Bound<'py, PyAny>>,
+        mode: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let options = resolve_mode(evaluation_context, mode)?;
+        self.run(py, evaluation_context, options)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!("Dataflow({})", repr_of(py, self.order()))
+    }
+}
+
+impl Dataflow {
+    /// [`Dataflow::evaluate`] with the options of a mode that has been resolved
+    pub fn run<'py>(
+        &self,
+        py: Python<'py>,
+        evaluation_context: Option<&Bound<'py, PyAny>>,
+        options: Options,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let output = output_types(evaluation_context);
+        let originals = originals(evaluation_context);
+        let mut context = match evaluation_context {
+            Some(evaluation_context) => {
+                if let Ok(context) = evaluation_context.extract::<PyRef<Context>>() {
+                    context.fork()
+                } else if let Ok(variables) = evaluation_context.downcast::<PyDict>() {
+                    let mut context = Context::new(
+                        None, None, false, None, None, false, "error", "error", false, false, None,
+                        None, None,
+                    )?;
+                    context.update(Some(variables), false, None)?;
+                    context
+                } else {
+                    return Err(PyValueError::new_err(
+                        "evaluation_context must be a Context object or a dict",
+                    ));
+                }
+            }
+            None => Context::new(
+                None, None, false, None, None, false, "error", "error", false, false, None, None,
+                None,
+            )?,
+        };
+
+        let results = PyDict::new_bound(py);
+        for (name, program) in &self.steps {
+            let job = Job::for_context(&context, None, None, options);
+            let outcome = program.get().run(job);
+            if let Outcome::Value(value) = &outcome {
+                context.set_variable(name.clone(), value.clone());
+            }
+            results.set_item(
+                name,
+                outcome_into_py(py, outcome, false, false, output, &originals)?,
+            )?;
+        }
+        Ok(results)
+    }
+}
+
+/// Order named expressions so that each is evaluated after the expressions
+/// whose results it refers to.
+///
+/// `expressions` is a dict or an iterable of `(name, expression)` pairs, where
+/// each expression is the source of one or a `Program`. Raises a ValueError if
+/// a name is used twice or expressions refer to each other in a cycle.
+#[pyfunction]
+pub fn plan(py: Python<'_>, expressions: &Bound<'_, PyAny>) -> PyResult<Dataflow> {
+    let pairs = match expressions.downcast::<PyDict>() {
+        Ok(dict) => dict.items().into_any(),
+        Err(_) => expressions.clone(),
+    };
+    let mut named: Vec<(String, Py<Program>)> = Vec::new();
+    for pair in pairs.iter()? {
+        let (name, expression): (String, Bound<'_, PyAny>) = pair?.extract()?;
+        if named.iter().any(|(existing, _)| *existing == name) {
+            return Err(PyValueError::new_err(format!(
+                "the name '{}' is used by more than one expression",
+                name
+            )));
+        }
+        let program = match expression.downcast::<Program>() {
+            Ok(program) => program.clone().unbind(),
+            Err(_) => Py::new(py, Program::new(expression.extract()?, false, None, None)?)?,
+        };
+        named.push((name, program));
+    }
+
+    // The indexes of the other expressions each one refers to
+    let dependencies: Vec<Vec<usize>> = named
+        .iter()
+        .map(|(name, program)| {
+            let variables = free_variables(program.get().expression());
+            named
+                .iter()
+                .enumerate()
+                .filter(|(_, (other, _))| other != name && variables.contains(other))
+                .map(|(index, _)| index)
+                .collect()
+        })
+        .collect();
+
+    // Repeatedly takes the first expression whose dependencies have all been
+    // taken, which keeps the given order where the dependencies allow it
+    let mut taken = vec![false; named.len()];
+    let mut order = Vec::with_capacity(named.len());
+    while order.len() < named.len() {
+        let next = (0..named.len()).find(|&index| {
+            !taken[index]
+                && dependencies[index]
+                    .iter()
+                    .all(|&dependency| taken[dependency])
+        });
+        match next {
+            Some(index) => {
+                taken[index] = true;
+                order.push(index);
+            }
+            None => {
+                let cycle: Vec<&str> = (0..named.len())
+                    .filter(|&index| !taken[index])
+                    .map(|index| named[index].0.as_str())
+                    .collect();
+                return Err(PyValueError::new_err(format!(
+                    "expressions refer to each other in a cycle: {}",
+                    cycle.join(", ")
+                )));
+            }
+        }
+    }
+
+    let mut named: Vec<Option<(String, Py<Program>)>> = named.into_iter().map(Some).collect();
+    Ok(Dataflow {
+        steps: order
+            .into_iter()
+            .map(|index| named[index].take().unwrap())
+            .collect(),
+    })
+}
+
+/// The variables `expr` refers to, leaving out the variables of macros within it
+pub fn free_variables(expr: &Expression) -> BTreeSet<String> {
+    let mut variables = BTreeSet::new();
+    match expr {
+        Expression::Ident(name) => {
+            variables.insert(name.to_string());
+        }
+        Expression::FunctionCall(function, Some(target), args) if is_macro(function) => {
+            variables.extend(free_variables(target));
+            let mut args = args.iter();
+            let bound = match args.next() {
+                Some(Expression::Ident(name)) => Some(name.to_string()),
+                _ => None,
+            };
+            for arg in args {
+                let mut inner = free_variables(arg);
+                if let Some(bound) = &bound {
+                    inner.remove(bound);
+                }
+                variables.extend(inner);
+            }
+            return variables;
+        }
+        _ => {}
+    }
+    map_children(expr, |child| {
+        variables.extend(free_variables(child));
+        child.clone()
+    });
+    variables
+}
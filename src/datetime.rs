@@ -0,0 +1,218 @@
+//! The built-in `datetime` extension, loaded with
+//! `Context(extensions=["datetime"])`, with helpers for time-window rules:
+//! `now()`, `today()`, `ts.truncate(unit)`, `ts.add(duration)` and
+//! `date_diff(a, b, unit)`.
+//!
+//! `now()` reads the system clock, unless the context's clock is frozen with
+//! `Context(clock=...)` or `Context.set_now`, at a time kept in the environment
+//! as the variable [`NOW`], which expressions can't refer to.
+use crate::duration;
+use crate::functions::Described;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, TimeZone, Timelike, Utc};
+use std::time::SystemTime;
+
+/// The name the extension is loaded by
+pub const NAME: &str = "datetime";
+
+/// Internal variable holding the time `now()` returns when it is frozen
+pub const NOW: &str = "@now";
+
+/// The functions of the extension, with their signature and what they do, for
+/// `Context.functions_info`
+pub const FUNCTIONS: &[Described] = &[
+    (
+        "now",
+        "now() -> timestamp",
+        "The current time, or the time the context froze it at",
+    ),
+    (
+        "today",
+        "today() -> timestamp",
+        "The start of the current day in UTC",
+    ),
+    (
+        "truncate",
+        "timestamp.truncate(unit) -> timestamp",
+        "The timestamp rounded down to the start of its year, month, day, hour, minute or second",
+    ),
+    (
+        "add",
+        "timestamp.add(duration) -> timestamp",
+        "The timestamp after a duration, which may be a string such as '1h30m'",
+    ),
+    (
+        "date_diff",
+        "date_diff(timestamp, timestamp, unit) -> int",
+        "The number of whole years, months, weeks, days, hours, minutes or seconds from the \
+         second timestamp to the first",
+    ),
+];
+
+/// Adds the functions of the extension to an environment
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function("now", now);
+    environment.add_function("today", today);
+    environment.add_function("truncate", truncate);
+    environment.add_function("add", add);
+    environment.add_function("date_diff", date_diff);
+}
+
+/// The arguments of a call, the value it's called on first, checking there are `N`
+fn arguments<const N: usize>(ftx: &FunctionContext) -> Result<[Value; N], ExecutionError> {
+    let this = ftx.this.iter().cloned().map(Ok);
+    let args = ftx.args.iter().map(|arg| ftx.ptx.resolve(arg));
+    let values = this.chain(args).collect::<Result<Vec<_>, _>>()?;
+    let count = values.len();
+    values
+        .try_into()
+        .map_err(|_| ExecutionError::invalid_argument_count(N, count))
+}
+
+fn timestamp(ftx: &FunctionContext, value: Value) -> Result<DateTime<FixedOffset>, ExecutionError> {
+    match value {
+        Value::Timestamp(timestamp) => Ok(timestamp),
+        other => Err(ftx.error(format!("expected a timestamp, got {}", other.type_of()))),
+    }
+}
+
+/// A unit of time, accepted in the singular or plural
+fn unit_name(ftx: &FunctionContext, value: Value) -> Result<String, ExecutionError> {
+    match value {
+        Value::String(unit) => Ok(unit.strip_suffix('s').unwrap_or(&unit).to_string()),
+        other => Err(ftx.error(format!("expected a unit, got {}", other.type_of()))),
+    }
+}
+
+fn overflow(ftx: &FunctionContext) -> ExecutionError {
+    ftx.error("timestamp out of range")
+}
+
+/// The time `now()` returns
+fn current(ftx: &FunctionContext) -> DateTime<FixedOffset> {
+    match ftx.ptx.get_variable(NOW) {
+        Ok(Value::Timestamp(now)) => now,
+        // `Utc::now` needs chrono's `clock` feature, which only `local-timezone` enables
+        _ => DateTime::<Utc>::from(SystemTime::now()).fixed_offset(),
+    }
+}
+
+/// Returns the current time in UTC, or the time the context's clock is frozen
+/// at.
+pub fn now(ftx: &FunctionContext) -> ResolveResult {
+    let [] = arguments(ftx)?;
+    Ok(Value::Timestamp(current(ftx)))
+}
+
+/// Returns the start of the current day in UTC.
+pub fn today(ftx: &FunctionContext) -> ResolveResult {
+    let [] = arguments(ftx)?;
+    let now = current(ftx).with_timezone(&Utc).fixed_offset();
+    truncated(ftx, now, "day").map(Value::Timestamp)
+}
+
+/// Returns the timestamp rounded down to the start of its year, month, day,
+/// hour, minute or second, in its own UTC offset.
+///
+/// # Examples
+/// ```cel
+/// timestamp('2024-05-06T07:08:09Z').truncate('hour') == timestamp('2024-05-06T07:00:00Z')
+/// ```
+pub fn truncate(ftx: &FunctionContext) -> ResolveResult {
+    let [ts, unit] = arguments(ftx)?;
+    let ts = timestamp(ftx, ts)?;
+    truncated(ftx, ts, &unit_name(ftx, unit)?).map(Value::Timestamp)
+}
+
+fn truncated(
+    ftx: &FunctionContext,
+    ts: DateTime<FixedOffset>,
+    unit: &str,
+) -> Result<DateTime<FixedOffset>, ExecutionError> {
+    let (month, day) = match unit {
+        "year" => (1, 1),
+        "month" => (ts.month(), 1),
+        _ => (ts.month(), ts.day()),
+    };
+    let (hour, minute, second) = match unit {
+        "year" | "month" | "day" => (0, 0, 0),
+        "hour" => (ts.hour(), 0, 0),
+        "minute" => (ts.hour(), ts.minute(), 0),
+        "second" => (ts.hour(), ts.minute(), ts.second()),
+        _ => {
+            return Err(ftx.error(format!(
+                "can't truncate to '{}', expected year, month, day, hour, minute or second",
+                unit
+            )))
+        }
+    };
+    ts.offset()
+        .with_ymd_and_hms(ts.year(), month, day, hour, minute, second)
+        .single()
+        .ok_or_else(|| overflow(ftx))
+}
+
+/// Returns the timestamp after a duration, given as a duration or a string.
+///
+/// # Examples
+/// ```cel
+/// timestamp('2024-05-06T07:08:09Z').add('1h') == timestamp('2024-05-06T08:08:09Z')
+/// ```
+pub fn add(ftx: &FunctionContext) -> ResolveResult {
+    let [ts, by] = arguments(ftx)?;
+    let ts = timestamp(ftx, ts)?;
+    let by = match by {
+        Value::Duration(by) => by,
+        Value::String(by) => duration::parse(&by).map_err(|e| ftx.error(e))?,
+        other => return Err(ftx.error(format!("expected a duration, got {}", other.type_of()))),
+    };
+    ts.checked_add_signed(by)
+        .map(Value::Timestamp)
+        .ok_or_else(|| overflow(ftx))
+}
+
+/// Returns the number of whole years, months, weeks, days, hours, minutes or
+/// seconds from the second timestamp to the first, which is negative if the
+/// first is earlier. Years and months are calendar ones, in the UTC offset of
+/// the first timestamp.
+///
+/// # Examples
+/// ```cel
+/// date_diff(now(), user.created, 'days') > 30
+/// ```
+pub fn date_diff(ftx: &FunctionContext) -> ResolveResult {
+    let [a, b, unit] = arguments(ftx)?;
+    let a = timestamp(ftx, a)?;
+    let b = timestamp(ftx, b)?.with_timezone(a.offset());
+    let per = match unit_name(ftx, unit)?.as_str() {
+        "year" => return Ok(Value::Int(months(a, b) / 12)),
+        "month" => return Ok(Value::Int(months(a, b))),
+        "week" => Duration::weeks(1),
+        "day" => Duration::days(1),
+        "hour" => Duration::hours(1),
+        "minute" => Duration::minutes(1),
+        "second" => Duration::seconds(1),
+        unit => {
+            return Err(ftx.error(format!(
+                "unknown unit '{}', expected years, months, weeks, days, hours, minutes or seconds",
+                unit
+            )))
+        }
+    };
+    let seconds = a.signed_duration_since(b).num_seconds();
+    Ok(Value::Int(seconds / per.num_seconds()))
+}
+
+/// The number of whole calendar months from `b` to `a`
+fn months(a: DateTime<FixedOffset>, b: DateTime<FixedOffset>) -> i64 {
+    let whole = (a.year() as i64 - b.year() as i64) * 12 + a.month() as i64 - b.month() as i64;
+    // Where each is within its month, to tell whether the last month is whole
+    let within = |ts: DateTime<FixedOffset>| (ts.day(), ts.time());
+    if whole > 0 && within(a) < within(b) {
+        whole - 1
+    } else if whole < 0 && within(a) > within(b) {
+        whole + 1
+    } else {
+        whole
+    }
+}
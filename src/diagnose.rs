@@ -0,0 +1,220 @@
+//! `cel.diagnose`, which reports the problems in an expression as Language
+//! Server Protocol diagnostics, so a language server can pass them on as is.
+use crate::context::Context;
+use crate::dataflow::free_variables;
+use crate::functions::BUILTINS;
+use crate::namespaces;
+use crate::recover::{recover, MISSING};
+use crate::tokenize::tokens;
+use crate::transform::map_children;
+use crate::types::{is_type_name, methods};
+use cel_parser::Expression;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// LSP `DiagnosticSeverity` values
+const ERROR: u8 = 1;
+const HINT: u8 = 4;
+
+/// A problem found in an expression, with byte ranges into it
+struct Problem {
+    range: Range<usize>,
+    severity: u8,
+    code: &'static str,
+    message: String,
+    related: Vec<(String, Range<usize>)>,
+}
+
+/// The problems in `expression` as LSP `Diagnostic` dicts, in the order they
+/// appear in it.
+///
+/// Syntax errors are always reported, with the code "syntax-error". With an
+/// `env`, a Context or a dict of the variables and functions the expression
+/// will be evaluated with, references to anything else are reported too, as
+/// "undeclared-reference" and "undeclared-function". Positions are zero based
+/// lines and UTF-16 code units, as in LSP, and `uri` is the document used in the
+/// locations of related information.
+#[pyfunction]
+#[pyo3(signature = (expression, env=None, uri=""))]
+pub fn diagnose<'py>(
+    py: Python<'py>,
+    expression: &str,
+    env: Option<&Bound<'py, PyAny>>,
+    uri: &str,
+) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let (parsed, errors) = recover(expression);
+    let mut problems: Vec<Problem> = Vec::new();
+    for error in errors {
+        let text = &expression[error.range.clone()];
+        problems.push(Problem {
+            range: error.range.clone(),
+            severity: ERROR,
+            code: "syntax-error",
+            message: error.message,
+            related: error.related.into_iter().collect(),
+        });
+        let signed_number = text.len() > 1
+            && (text.starts_with('-') || text.starts_with('+'))
+            && text[1..].starts_with(|c: char| c.is_ascii_digit() || c == '.');
+        if signed_number {
+            problems.push(Problem {
+                range: error.range,
+                severity: HINT,
+                code: "signed-literal",
+                message: format!(
+                    "'{}' is read as a signed number, put a space after '{}' to use it as an operator",
+                    text,
+                    &text[..1]
+                ),
+                related: Vec::new(),
+            });
+        }
+    }
+
+    if let (Some(parsed), Some(env)) = (&parsed, env) {
+        let context = match env.extract::<PyRef<Context>>() {
+            Ok(context) => context.fork(),
+            Err(_) => {
+                let variables = env
+                    .downcast::<PyDict>()
+                    .map_err(|_| PyValueError::new_err("env must be a Context object or a dict"))?;
+                let mut context = Context::new(
+                    None, None, false, None, None, false, "error", "error", false, false, None,
+                    None, None,
+                )?;
+                context.update(Some(variables), false, None)?;
+                context
+            }
+        };
+        // Calls like `geo.point(...)` don't refer to a `geo` variable
+        let rewritten = namespaces::rewrite(parsed, &context.namespaced_functions());
+        let parsed = rewritten.as_ref().unwrap_or(parsed);
+        let tokens = tokens(expression);
+        // Identifier tokens named `name`, that are called if `called`
+        let occurrences = |name: &str, called: bool| -> Vec<Range<usize>> {
+            (0..tokens.len())
+                .filter(|&index| {
+                    let (kind, range) = &tokens[index];
+                    let follows = |text: &str| {
+                        tokens
+                            .get(index + 1)
+                            .is_some_and(|(_, next)| &expression[next.clone()] == text)
+                    };
+                    let after_dot = index > 0 && &expression[tokens[index - 1].1.clone()] == ".";
+                    *kind == "identifier"
+                        && &expression[range.clone()] == name
+                        && follows("(") == called
+                        && (called || !after_dot)
+                })
+                .map(|index| tokens[index].1.clone())
+                .collect()
+        };
+
+        for name in free_variables(parsed) {
+            if name == MISSING || context.variables.contains_key(&name) || is_type_name(&name) {
+                continue;
+            }
+            for range in occurrences(&name, false) {
+                problems.push(Problem {
+                    range,
+                    severity: ERROR,
+                    code: "undeclared-reference",
+                    message: format!("undeclared reference to '{}'", name),
+                    related: Vec::new(),
+                });
+            }
+        }
+        #[allow(unused_mut)]
+        let mut declared_functions = context.function_names();
+        #[cfg(feature = "native-extensions")]
+        declared_functions.extend(crate::native::function_names());
+        declared_functions.extend(
+            context
+                .extension_functions()
+                .map(|(name, _, _)| name.to_string()),
+        );
+        for name in called_functions(parsed) {
+            let known = name == MISSING
+                || declared_functions.contains(&name)
+                || BUILTINS.iter().any(|(builtin, _, _)| *builtin == name)
+                || methods().contains(&name);
+            if known {
+                continue;
+            }
+            for range in occurrences(&name, true) {
+                problems.push(Problem {
+                    range,
+                    severity: ERROR,
+                    code: "undeclared-function",
+                    message: format!("undeclared function '{}'", name),
+                    related: Vec::new(),
+                });
+            }
+        }
+    }
+
+    problems.sort_by_key(|problem| (problem.range.start, problem.range.end));
+    problems
+        .into_iter()
+        .map(|problem| {
+            let diagnostic = PyDict::new_bound(py);
+            diagnostic.set_item("range", lsp_range(py, expression, &problem.range)?)?;
+            diagnostic.set_item("severity", problem.severity)?;
+            diagnostic.set_item("code", problem.code)?;
+            diagnostic.set_item("source", "cel")?;
+            diagnostic.set_item("message", problem.message)?;
+            let related = PyList::empty_bound(py);
+            for (message, range) in &problem.related {
+                let location = PyDict::new_bound(py);
+                location.set_item("uri", uri)?;
+                location.set_item("range", lsp_range(py, expression, range)?)?;
+                let information = PyDict::new_bound(py);
+                information.set_item("location", location)?;
+                information.set_item("message", message)?;
+                related.append(information)?;
+            }
+            diagnostic.set_item("relatedInformation", related)?;
+            Ok(diagnostic)
+        })
+        .collect()
+}
+
+/// The names of the functions `expr` calls
+pub fn called_functions(expr: &Expression) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    if let Expression::FunctionCall(function, _, _) = expr {
+        if let Expression::Ident(name) = &**function {
+            names.insert(name.to_string());
+        }
+    }
+    map_children(expr, |child| {
+        names.extend(called_functions(child));
+        child.clone()
+    });
+    names
+}
+
+/// An LSP `Range` of a byte range into `text`
+fn lsp_range<'py>(
+    py: Python<'py>,
+    text: &str,
+    range: &Range<usize>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let lsp = PyDict::new_bound(py);
+    lsp.set_item("start", lsp_position(py, text, range.start)?)?;
+    lsp.set_item("end", lsp_position(py, text, range.end)?)?;
+    Ok(lsp)
+}
+
+/// An LSP `Position` of a byte offset into `text`
+fn lsp_position<'py>(py: Python<'py>, text: &str, offset: usize) -> PyResult<Bound<'py, PyDict>> {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let position = PyDict::new_bound(py);
+    position.set_item("line", before.matches('\n').count())?;
+    position.set_item("character", before[line_start..].encode_utf16().count())?;
+    Ok(position)
+}
@@ -0,0 +1,206 @@
+use crate::functions::this_or_arg;
+use crate::repr_of;
+use cel_interpreter::{FunctionContext, ResolveResult, Value};
+use chrono::Duration as ChronoDuration;
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// Parses a duration string as accepted by Go's `time.ParseDuration`, which is
+/// the format CEL uses: a sign followed by a sequence of decimal numbers, each
+/// with an optional fraction and a unit, e.g. `"-1.5h"` or `"2h45m30.5s"`.
+///
+/// Valid units are `h`, `m`, `s`, `ms`, `us` (or `µs`) and `ns`. Fractions of a
+/// nanosecond are truncated.
+pub fn parse(s: &str) -> Result<ChronoDuration, String> {
+    let invalid = || format!("invalid duration \"{}\"", s);
+
+    let (negative, mut rest) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    if rest == "0" {
+        return Ok(ChronoDuration::zero());
+    }
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut total: u128 = 0;
+    while !rest.is_empty() {
+        let whole_digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let whole = &rest[..whole_digits];
+        rest = &rest[whole_digits..];
+        let fraction = match rest.strip_prefix('.') {
+            Some(after_point) => {
+                let digits = after_point.bytes().take_while(u8::is_ascii_digit).count();
+                rest = &after_point[digits..];
+                &after_point[..digits]
+            }
+            None => "",
+        };
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(invalid());
+        }
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ns" => 1,
+            "us" | "µs" | "μs" => 1_000,
+            "ms" => 1_000_000,
+            "s" => NANOS_PER_SECOND,
+            "m" => 60 * NANOS_PER_SECOND,
+            "h" => 3600 * NANOS_PER_SECOND,
+            "" => return Err(format!("missing unit in duration \"{}\"", s)),
+            unit => return Err(format!("unknown unit \"{}\" in duration \"{}\"", unit, s)),
+        };
+        rest = &rest[unit_len..];
+
+        let whole: u128 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| invalid())?
+        };
+        let mut nanos = whole.checked_mul(unit).ok_or_else(invalid)?;
+        // Digits beyond the 20th can't contribute a whole nanosecond
+        let fraction = &fraction[..fraction.len().min(20)];
+        if !fraction.is_empty() {
+            let scale = 10u128.pow(fraction.len() as u32);
+            let fraction: u128 = fraction.parse().map_err(|_| invalid())?;
+            nanos += fraction * unit / scale;
+        }
+        total = total.checked_add(nanos).ok_or_else(invalid)?;
+    }
+
+    let nanos = match i64::try_from(total) {
+        Ok(nanos) if negative => -nanos,
+        Ok(nanos) => nanos,
+        Err(_) if negative && total == 1 << 63 => i64::MIN,
+        Err(_) => return Err(format!("duration \"{}\" is out of range", s)),
+    };
+    Ok(ChronoDuration::nanoseconds(nanos))
+}
+
+/// Formats a duration like Go's `time.Duration.String`, e.g. `"72h3m0.5s"`.
+///
+/// Durations under a second use a smaller unit so the leading digit is non-zero
+/// (`"1.5ms"`), and zero formats as `"0s"`.
+pub fn format(duration: &ChronoDuration) -> String {
+    let nanos =
+        duration.num_seconds() as i128 * NANOS_PER_SECOND as i128 + duration.subsec_nanos() as i128;
+    let sign = if nanos < 0 { "-" } else { "" };
+    let nanos = nanos.unsigned_abs();
+
+    if nanos == 0 {
+        return "0s".to_string();
+    }
+    if nanos < NANOS_PER_SECOND {
+        let (digits, unit) = match nanos {
+            n if n < 1_000 => (0, "ns"),
+            n if n < 1_000_000 => (3, "µs"),
+            _ => (6, "ms"),
+        };
+        return format!("{}{}{}", sign, decimal(nanos, digits), unit);
+    }
+
+    let seconds = nanos / NANOS_PER_SECOND;
+    let (hours, minutes) = (seconds / 3600, seconds / 60 % 60);
+    let seconds = decimal(nanos % (60 * NANOS_PER_SECOND), 9);
+    match (hours, minutes) {
+        (0, 0) => format!("{}{}s", sign, seconds),
+        (0, _) => format!("{}{}m{}s", sign, minutes, seconds),
+        _ => format!("{}{}h{}m{}s", sign, hours, minutes, seconds),
+    }
+}
+
+/// Formats `value / 10^digits` without trailing zeros in the fraction
+pub(crate) fn decimal(value: u128, digits: u32) -> String {
+    let scale = 10u128.pow(digits);
+    let (whole, fraction) = (value / scale, value % scale);
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:0width$}", fraction, width = digits as usize);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+/// Implementation of `duration()`, which parses a duration string. A duration
+/// is returned unchanged.
+pub fn duration(ftx: &FunctionContext) -> ResolveResult {
+    match this_or_arg(ftx)? {
+        Value::String(s) => parse(&s).map(Value::Duration).map_err(|e| ftx.error(e)),
+        Value::Duration(d) => Ok(Value::Duration(d)),
+        other => Err(ftx.error(format!("cannot convert {} to duration", other.type_of()))),
+    }
+}
+
+/// A CEL duration.
+///
+/// Durations have nanosecond precision, unlike `datetime.timedelta` which is
+/// limited to microseconds, and use CEL's string format: `Duration("1h30m")`
+/// and `str(duration)` round trip just as `duration()` and `string()` do in an
+/// expression.
+#[pyclass(frozen, name = "Duration")]
+#[derive(Clone)]
+pub struct CelDuration {
+    pub duration: ChronoDuration,
+}
+
+#[pymethods]
+impl CelDuration {
+    /// Create a duration from a CEL duration string or a `timedelta`
+    #[new]
+    fn new(value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let duration = if let Ok(s) = value.extract::<String>() {
+            parse(&s).map_err(PyValueError::new_err)?
+        } else if let Ok(duration) = value.extract::<ChronoDuration>() {
+            duration
+        } else {
+            return Err(PyTypeError::new_err(
+                "Duration expects a duration string or a timedelta",
+            ));
+        };
+        Ok(CelDuration { duration })
+    }
+
+    /// The duration in seconds, like `timedelta.total_seconds()`
+    fn total_seconds(&self) -> f64 {
+        self.duration.num_seconds() as f64 + self.duration.subsec_nanos() as f64 / 1e9
+    }
+
+    #[getter]
+    fn nanoseconds(&self) -> i128 {
+        self.duration.num_seconds() as i128 * NANOS_PER_SECOND as i128
+            + self.duration.subsec_nanos() as i128
+    }
+
+    /// Convert to a `timedelta`, truncating to microseconds
+    fn to_timedelta(&self, py: Python<'_>) -> PyObject {
+        self.duration.into_py(py)
+    }
+
+    fn __richcmp__(&self, other: &Bound<'_, PyAny>, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let other = if let Ok(other) = other.downcast::<CelDuration>() {
+            other.get().duration
+        } else if let Ok(other) = other.extract::<ChronoDuration>() {
+            other
+        } else {
+            return Ok(py.NotImplemented());
+        };
+        Ok(op.matches(self.duration.cmp(&other)).into_py(py))
+    }
+
+    fn __str__(&self) -> String {
+        format(&self.duration)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!("Duration({})", repr_of(py, format(&self.duration)))
+    }
+}
@@ -0,0 +1,102 @@
+//! Error mappers, set with `Context.set_error_mapper`, which decide how an
+//! exception raised by a Python function becomes a CEL error.
+//!
+//! A mapper can make an error fatal, so that it isn't absorbed by `&&`, `||`,
+//! the comprehensions or `coalesce`. Rather than teaching each of those about
+//! such errors, the first fatal error is kept by the evaluation running on the
+//! thread, as the budget of a sandboxed evaluation is, and replaces its result.
+use cel_interpreter::ExecutionError;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::cell::RefCell;
+
+thread_local! {
+    static FATAL: RefCell<Option<Option<ExecutionError>>> = const { RefCell::new(None) };
+}
+
+/// Keeps the first fatal error of the evaluation on this thread until it is
+/// dropped. A Python function that evaluates an expression itself gets a scope
+/// of its own.
+pub struct Scope {
+    outer: Option<Option<ExecutionError>>,
+}
+
+impl Scope {
+    pub fn enter() -> Scope {
+        Scope {
+            outer: FATAL.with(|fatal| fatal.replace(Some(None))),
+        }
+    }
+
+    /// The fatal error of the evaluation, if there was one
+    pub fn take(&self) -> Option<ExecutionError> {
+        FATAL.with(|fatal| fatal.borrow_mut().as_mut()?.take())
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let outer = self.outer.take();
+        FATAL.with(|fatal| *fatal.borrow_mut() = outer);
+    }
+}
+
+/// The CEL error for `error`, raised by the Python function `name`, as
+/// `mapper` decides. The mapper is called with the name and the exception and
+/// returns None to keep the default error, a message, or a dict of an optional
+/// `message` and whether the error is `absorbable` (by default it is).
+pub fn map(py: Python<'_>, mapper: &Py<PyAny>, name: &str, error: PyErr) -> ExecutionError {
+    let default = error.to_string();
+    let (message, absorbable) = match mapped(py, mapper, name, error) {
+        Ok((message, absorbable)) => (message.unwrap_or(default), absorbable),
+        Err(failure) => (
+            format!("error mapper failed on {}: {}", default, failure),
+            true,
+        ),
+    };
+    let error = ExecutionError::FunctionError {
+        function: name.to_string(),
+        message,
+    };
+    if !absorbable {
+        FATAL.with(|fatal| {
+            if let Some(fatal @ None) = fatal.borrow_mut().as_mut() {
+                *fatal = Some(error.clone());
+            }
+        });
+    }
+    error
+}
+
+/// The message and absorbability `mapper` returns for `error`
+fn mapped(
+    py: Python<'_>,
+    mapper: &Py<PyAny>,
+    name: &str,
+    error: PyErr,
+) -> PyResult<(Option<String>, bool)> {
+    let result = mapper.call1(py, (name, error.into_value(py)))?;
+    let result = result.bind(py);
+    if result.is_none() {
+        return Ok((None, true));
+    }
+    if let Ok(message) = result.extract::<String>() {
+        return Ok((Some(message), true));
+    }
+    let Ok(result) = result.downcast::<PyDict>() else {
+        return Err(PyTypeError::new_err(format!(
+            "expected None, a str or a dict, got {}",
+            crate::type_name(result)
+        )));
+    };
+    let message = match result.get_item("message")? {
+        Some(message) => message.extract()?,
+        None => None,
+    };
+    let absorbable = match result.get_item("absorbable")? {
+        Some(absorbable) => absorbable.extract()?,
+        None => true,
+    };
+    Ok((message, absorbable))
+}
@@ -0,0 +1,310 @@
+use crate::options;
+use crate::repr_of;
+use cel_interpreter::{ExecutionError, FunctionContext, ParseError};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyOverflowError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyTuple, PyType};
+use std::fmt::Display;
+
+create_exception!(
+    cel,
+    ConversionError,
+    PyValueError,
+    "Raised when a conversion function such as `int()` can't convert its argument."
+);
+
+create_exception!(
+    cel,
+    LimitExceeded,
+    PyValueError,
+    "Raised when an expression, its evaluation or its result exceeds a limit, such as `max_result_size`."
+);
+
+static CONVERSION_RANGE_ERROR: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+static CONVERSION_TYPE_ERROR: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+/// The conversion functions whose failures raise a `ConversionError`
+const CONVERSIONS: [&str; 7] = ["int", "uint", "double", "string", "bytes", "bool", "dyn"];
+
+/// How a conversion function failed, which decides the exception that is raised.
+///
+/// The failure is carried in the error message as a prefix, as the interpreter's
+/// function errors only have a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversionFailure {
+    /// A string that isn't a valid representation, e.g. `int('abc')`
+    Syntax,
+    /// A value outside the range of the target type, e.g. `int(1e20)`
+    Range,
+    /// An argument of a type that can't be converted, e.g. `int([])`
+    Overload,
+    /// Bytes that aren't valid UTF-8 converted to a string
+    Encoding,
+}
+
+impl ConversionFailure {
+    const ALL: [ConversionFailure; 4] = [
+        ConversionFailure::Syntax,
+        ConversionFailure::Range,
+        ConversionFailure::Overload,
+        ConversionFailure::Encoding,
+    ];
+
+    fn prefix(self) -> &'static str {
+        match self {
+            ConversionFailure::Syntax => "invalid syntax",
+            ConversionFailure::Range => "range error",
+            ConversionFailure::Overload => "no such overload",
+            ConversionFailure::Encoding => "invalid UTF-8",
+        }
+    }
+
+    /// An error from the function being executed, e.g. "range error: ..."
+    pub fn error(self, ftx: &FunctionContext, detail: impl Display) -> ExecutionError {
+        ftx.error(format!("{}: {}", self.prefix(), detail))
+    }
+
+    fn of(error: &ExecutionError) -> Option<Self> {
+        match error {
+            ExecutionError::FunctionError { function, message }
+                if CONVERSIONS.contains(&function.as_str()) =>
+            {
+                Self::ALL
+                    .into_iter()
+                    .find(|failure| message.starts_with(failure.prefix()))
+            }
+            _ => None,
+        }
+    }
+
+    fn exception_type(self, py: Python<'_>) -> PyResult<Bound<'_, PyType>> {
+        match self {
+            ConversionFailure::Syntax | ConversionFailure::Encoding => {
+                Ok(py.get_type_bound::<ConversionError>())
+            }
+            ConversionFailure::Range => conversion_range_error(py),
+            ConversionFailure::Overload => conversion_type_error(py),
+        }
+    }
+}
+
+/// `ConversionRangeError`, a subclass of both `ConversionError` and `OverflowError`
+fn conversion_range_error(py: Python<'_>) -> PyResult<Bound<'_, PyType>> {
+    CONVERSION_RANGE_ERROR
+        .get_or_try_init(py, || {
+            subclass(
+                py,
+                "ConversionRangeError",
+                py.get_type_bound::<PyOverflowError>(),
+            )
+        })
+        .map(|ty| ty.bind(py).clone())
+}
+
+/// `ConversionTypeError`, a subclass of both `ConversionError` and `TypeError`
+fn conversion_type_error(py: Python<'_>) -> PyResult<Bound<'_, PyType>> {
+    CONVERSION_TYPE_ERROR
+        .get_or_try_init(py, || {
+            subclass(
+                py,
+                "ConversionTypeError",
+                py.get_type_bound::<PyTypeError>(),
+            )
+        })
+        .map(|ty| ty.bind(py).clone())
+}
+
+/// Creates a subclass of `ConversionError` and a builtin exception, which
+/// `create_exception!` can't as it only supports a single base class
+fn subclass(py: Python<'_>, name: &str, builtin: Bound<'_, PyType>) -> PyResult<Py<PyType>> {
+    let bases = PyTuple::new_bound(py, [py.get_type_bound::<ConversionError>(), builtin]);
+    let namespace = PyDict::new_bound(py);
+    namespace.set_item("__module__", "cel")?;
+    Ok(py
+        .get_type_bound::<PyType>()
+        .call1((name, bases, namespace))?
+        .downcast_into::<PyType>()?
+        .unbind())
+}
+
+/// Adds the exception classes to the module
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("ConversionError", py.get_type_bound::<ConversionError>())?;
+    m.add("LimitExceeded", py.get_type_bound::<LimitExceeded>())?;
+    m.add("ConversionRangeError", conversion_range_error(py)?)?;
+    m.add("ConversionTypeError", conversion_type_error(py)?)?;
+    Ok(())
+}
+
+/// Describes why an expression failed to compile or evaluate.
+///
+/// Returned in place of a result by `evaluate(..., on_error="return")` so that
+/// callers evaluating many records can collect failures rather than stopping
+/// at the first one.
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub struct EvalError {
+    /// Either "compile" or "execution"
+    #[pyo3(get)]
+    pub kind: &'static str,
+    #[pyo3(get)]
+    pub message: String,
+    #[pyo3(get)]
+    pub expression: String,
+    /// Offset into the expression of a syntax error or a repeated map key, None for
+    /// errors raised during execution
+    #[pyo3(get)]
+    pub position: Option<usize>,
+    /// Set when a conversion function failed, to raise a `ConversionError`
+    conversion: Option<ConversionFailure>,
+    /// The name of the `Program` the expression was compiled into, if it has one
+    #[pyo3(get)]
+    pub name: Option<String>,
+}
+
+impl EvalError {
+    pub fn compile(expression: &str, error: &ParseError) -> Self {
+        EvalError {
+            kind: "compile",
+            message: error.to_string(),
+            expression: expression.to_string(),
+            position: error.span.start.as_ref().map(|start| start.absolute),
+            conversion: None,
+            name: None,
+        }
+    }
+
+    /// An expression with a literal the parser can read but not convert, such
+    /// as an int that is out of range
+    pub fn invalid_literal(expression: &str, position: usize, message: String) -> Self {
+        EvalError {
+            kind: "compile",
+            message,
+            expression: expression.to_string(),
+            position: Some(position),
+            conversion: None,
+            name: None,
+        }
+    }
+
+    /// An expression that parsed but was rejected before being evaluated
+    pub fn rejected(expression: &str, message: String) -> Self {
+        EvalError {
+            kind: "compile",
+            message,
+            expression: expression.to_string(),
+            position: None,
+            conversion: None,
+            name: None,
+        }
+    }
+
+    /// An expression that wasn't evaluated as it was to be nested in more than
+    /// `limit` evaluations, through Python functions evaluating expressions
+    pub fn nested(expression: &str, limit: usize) -> Self {
+        EvalError {
+            kind: "execution",
+            message: format!(
+                "evaluations are nested more than {} deep, a Python function may be evaluating \
+                 itself without end",
+                limit
+            ),
+            expression: expression.to_string(),
+            position: None,
+            conversion: None,
+            name: None,
+        }
+    }
+
+    /// An error a remote CEL implementation reported, see `cel.conformance`
+    #[cfg(feature = "conformance")]
+    pub fn remote(expression: &str, kind: &'static str, message: String) -> Self {
+        EvalError {
+            kind,
+            message,
+            expression: expression.to_string(),
+            position: None,
+            conversion: None,
+            name: None,
+        }
+    }
+
+    pub fn execution(expression: &str, error: &ExecutionError) -> Self {
+        let message = match error {
+            // Names the map literal itself, rather than the function it was rewritten into
+            ExecutionError::FunctionError { function, message } if function == options::MAP => {
+                message.clone()
+            }
+            _ => error.to_string(),
+        };
+        EvalError {
+            kind: "execution",
+            message,
+            expression: expression.to_string(),
+            position: None,
+            conversion: ConversionFailure::of(error),
+            name: None,
+        }
+    }
+
+    /// The error pointing at `position` in the expression
+    pub fn at(self, position: Option<usize>) -> Self {
+        EvalError { position, ..self }
+    }
+
+    /// The error of the program named `name`
+    pub fn named(self, name: Option<&str>) -> Self {
+        EvalError {
+            name: name.map(str::to_string),
+            ..self
+        }
+    }
+
+    pub fn to_py_err(&self) -> PyErr {
+        let stage = match self.kind {
+            "compile" => "compile",
+            _ => "evaluate",
+        };
+        let message = match &self.name {
+            Some(name) => format!(
+                "Failed to {} program '{}', expression '{}': {}",
+                stage, name, self.expression, self.message
+            ),
+            None => format!(
+                "Failed to {} expression '{}': {}",
+                stage, self.expression, self.message
+            ),
+        };
+        match self.conversion {
+            Some(failure) => Python::with_gil(|py| match failure.exception_type(py) {
+                Ok(ty) => PyErr::from_type_bound(ty, message),
+                Err(err) => err,
+            }),
+            None => PyValueError::new_err(message),
+        }
+    }
+}
+
+#[pymethods]
+impl EvalError {
+    fn __repr__(&self, py: Python<'_>) -> String {
+        let name = match &self.name {
+            Some(name) => format!(", name={}", repr_of(py, name)),
+            None => String::new(),
+        };
+        format!(
+            "EvalError(kind={}, message={}, expression={}{})",
+            repr_of(py, self.kind),
+            repr_of(py, &self.message),
+            repr_of(py, &self.expression),
+            name
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.message.clone()
+    }
+}
@@ -0,0 +1,240 @@
+use crate::errors::EvalError;
+use crate::program::Program;
+use crate::{
+    check_result_size, originals, outcome_into_py, output_types, parse_on_error, parse_output,
+    resolve_mode,
+};
+use crate::{Job, Outcome};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Evaluates programs on a pool of threads, returning a
+/// `concurrent.futures.Future` for each evaluation.
+///
+/// The arguments of an evaluation are converted on the calling thread, after
+/// which it runs without the GIL, which is only taken again to call Python
+/// functions and to convert the result. Evaluations that don't call Python
+/// functions therefore run in parallel with each other and with Python code.
+#[pyclass(frozen, module = "cel")]
+pub struct Evaluator {
+    pool: rayon::ThreadPool,
+    shut_down: AtomicBool,
+    pending: Arc<Pending>,
+}
+
+/// Counts the evaluations that haven't finished, so that shutting down can wait for them
+#[derive(Default)]
+struct Pending {
+    count: Mutex<usize>,
+    finished: Condvar,
+}
+
+impl Pending {
+    fn start(&self) {
+        *self.count.lock().unwrap() += 1;
+    }
+
+    fn finish(&self) {
+        let mut count = self.count.lock().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.finished.notify_all();
+        }
+    }
+
+    fn wait(&self) {
+        let mut count = self.count.lock().unwrap();
+        while *count > 0 {
+            count = self.finished.wait(count).unwrap();
+        }
+    }
+}
+
+#[pymethods]
+impl Evaluator {
+    /// Without `workers` there is a thread for each CPU
+    #[new]
+    #[pyo3(signature = (workers=None))]
+    fn new(workers: Option<usize>) -> PyResult<Self> {
+        if workers == Some(0) {
+            return Err(PyValueError::new_err("workers must be at least 1"));
+        }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(workers.unwrap_or(0))
+            .thread_name(|index| format!("cel-evaluator-{}", index))
+            // As large as the main thread's usually is, for evaluations nested
+            // by Python functions
+            .stack_size(8 << 20)
+            .build()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok(Evaluator {
+            pool,
+            shut_down: AtomicBool::new(false),
+            pending: Arc::default(),
+        })
+    }
+
+    #[getter]
+    fn workers(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Schedule an evaluation of `program`, a `Program` or the source of one,
+    /// accepting the same options as `Program.evaluate`.
+    ///
+    /// Returns a `concurrent.futures.Future` for its result. Errors in the
+    /// evaluation are raised by the future, or with `on_error="return"` are its
+    /// result, while invalid arguments are raised straight away.
+    #[pyo3(signature = (program, evaluation_context=None, safe_navigation=None, on_error="raise", unknowns=None, output="python", mode=None, max_result_size=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn submit(
+        &self,
+        py: Python<'_>,
+        program: &Bound<'_, PyAny>,
+        evaluation_context: Option<&Bound<'_, PyAny>>,
+        safe_navigation: Option<bool>,
+        on_error: &str,
+        unknowns: Option<Vec<String>>,
+        output: &str,
+        mode: Option<&Bound<'_, PyAny>>,
+        max_result_size: Option<usize>,
+    ) -> PyResult<PyObject> {
+        if self.shut_down.load(Ordering::SeqCst) {
+            return Err(PyRuntimeError::new_err(
+                "cannot submit evaluations after shutdown",
+            ));
+        }
+        let return_errors = parse_on_error(on_error)?;
+        let opaque = parse_output(output)?;
+        let options = resolve_mode(evaluation_context, mode)?;
+        let output = output_types(evaluation_context);
+        let originals = originals(evaluation_context);
+        let program: Result<Py<Program>, EvalError> = match program.downcast::<Program>() {
+            Ok(program) => Ok(program.clone().unbind()),
+            Err(_) => match Program::parse(program.extract()?) {
+                Ok(program) => Ok(Py::new(py, program)?),
+                Err(error) => Err(error),
+            },
+        };
+        let job = Job::new(evaluation_context, safe_navigation, unknowns, options)?;
+
+        let future = py
+            .import_bound("concurrent.futures")?
+            .getattr("Future")?
+            .call0()?
+            .unbind();
+        let result = future.clone_ref(py);
+        let pending = self.pending.clone();
+        pending.start();
+        let task = move || {
+            let running = Python::with_gil(|py| {
+                future
+                    .call_method0(py, "set_running_or_notify_cancel")
+                    .and_then(|running| running.extract::<bool>(py))
+            });
+            // The future was cancelled before the evaluation started
+            if let Ok(true) = running {
+                let outcome = match program {
+                    Ok(program) => program.get().run(job),
+                    Err(error) => Outcome::Error(error),
+                };
+                Python::with_gil(|py| {
+                    let converted = check_result_size(&outcome, max_result_size).and_then(|_| {
+                        outcome_into_py(py, outcome, return_errors, opaque, output, &originals)
+                    });
+                    let set = match converted {
+                        Ok(value) => future.call_method1(py, "set_result", (value,)),
+                        Err(error) => {
+                            future.call_method1(py, "set_exception", (error.into_value(py),))
+                        }
+                    };
+                    if let Err(error) = set {
+                        error.write_unraisable_bound(py, None);
+                    }
+                });
+            }
+            pending.finish();
+        };
+        // A Python function running on one of the threads that submits an
+        // evaluation and waits for it could wait forever for a free thread, so
+        // the evaluation is run on that thread instead
+        match self.pool.current_thread_index() {
+            Some(_) => py.allow_threads(task),
+            None => self.pool.spawn(task),
+        }
+        Ok(result)
+    }
+
+    /// Evaluate `program` against each of `contexts`, returning the results in
+    /// the same order
+    #[pyo3(signature = (program, contexts, safe_navigation=None, on_error="raise", unknowns=None, output="python", mode=None, max_result_size=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn map(
+        &self,
+        py: Python<'_>,
+        program: &Bound<'_, PyAny>,
+        contexts: &Bound<'_, PyAny>,
+        safe_navigation: Option<bool>,
+        on_error: &str,
+        unknowns: Option<Vec<String>>,
+        output: &str,
+        mode: Option<&Bound<'_, PyAny>>,
+        max_result_size: Option<usize>,
+    ) -> PyResult<Py<PyList>> {
+        // A string is compiled once rather than for every context
+        let program = match program.downcast::<Program>() {
+            Ok(program) => program.clone(),
+            Err(_) => Bound::new(py, Program::new(program.extract()?, false, None, None)?)?,
+        };
+        let futures = contexts
+            .iter()?
+            .map(|context| {
+                self.submit(
+                    py,
+                    program.as_any(),
+                    Some(&context?),
+                    safe_navigation,
+                    on_error,
+                    unknowns.clone(),
+                    output,
+                    mode,
+                    max_result_size,
+                )
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let results = futures
+            .iter()
+            .map(|future| future.call_method0(py, "result"))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(PyList::new_bound(py, results).unbind())
+    }
+
+    /// Stop accepting evaluations, by default waiting for those already
+    /// submitted to finish
+    #[pyo3(signature = (wait=true))]
+    fn shutdown(&self, py: Python<'_>, wait: bool) {
+        self.shut_down.store(true, Ordering::SeqCst);
+        // Called by a Python function on one of the threads, the evaluation
+        // running it would never finish
+        if wait && self.pool.current_thread_index().is_none() {
+            py.allow_threads(|| self.pending.wait());
+        }
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&self, py: Python<'_>, _args: &Bound<'_, PyAny>) -> bool {
+        self.shutdown(py, true);
+        false
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Evaluator(workers={})", self.workers())
+    }
+}
@@ -0,0 +1,369 @@
+//! `cel.explain`, which says why a boolean expression, such as an access
+//! policy, evaluated to what it did, e.g. for "access denied because ..."
+//! messages.
+//!
+//! The clauses that decided the result are found by evaluating the operands of
+//! `&&`, `||`, `!` and `?:` on their own: a false `&&` is explained by each of
+//! its false operands, any of which makes it false, and a true one by all of
+//! them, and the other way around for `||`. Other expressions are leaves, shown
+//! with the values of the variables and fields they read.
+use crate::originals::Originals;
+use crate::output::OutputTypes;
+use crate::plan::MACROS;
+use crate::unknowns::is_truthy;
+use crate::unparse::unparse;
+use crate::{compile, execute, originals, output_types, repr_of, resolve_mode, types, Outcome};
+use cel_interpreter::Value;
+use cel_parser::{Expression, Member, UnaryOp};
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Why an expression, or a clause of one, evaluated to `value`
+#[pyclass(frozen, module = "cel")]
+pub struct Explanation {
+    /// The source of the clause
+    #[pyo3(get)]
+    expression: String,
+    /// The value of the clause, or an `EvalError` if it failed
+    #[pyo3(get)]
+    value: PyObject,
+    /// The clauses that decided the value, empty for a leaf
+    #[pyo3(get)]
+    clauses: Vec<Py<Explanation>>,
+    /// The values of the variables and fields a leaf reads, by their path,
+    /// e.g. `{"user.age": 15}`
+    #[pyo3(get)]
+    values: Py<PyDict>,
+}
+
+#[pymethods]
+impl Explanation {
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!(
+            "Explanation(expression={}, value={})",
+            repr_of(py, &self.expression),
+            self.value.bind(py).repr()?
+        ))
+    }
+
+    /// The explanation as indented lines, one for each clause
+    fn __str__(&self, py: Python<'_>) -> PyResult<String> {
+        self.text(py)
+    }
+}
+
+impl Explanation {
+    pub(crate) fn text(&self, py: Python<'_>) -> PyResult<String> {
+        let mut lines = Vec::new();
+        self.lines(py, 0, &mut lines)?;
+        Ok(lines.join("\n"))
+    }
+
+    fn lines(&self, py: Python<'_>, depth: usize, lines: &mut Vec<String>) -> PyResult<()> {
+        let mut line = format!(
+            "{}{} is {}",
+            "  ".repeat(depth),
+            self.expression,
+            self.value.bind(py).str()?
+        );
+        let values = self.values.bind(py);
+        if !values.is_empty() {
+            let values = values
+                .iter()
+                .map(|(path, value)| Ok(format!("{} = {}", path, value.repr()?)))
+                .collect::<PyResult<Vec<_>>>()?;
+            line.push_str(&format!(" ({})", values.join(", ")));
+        }
+        lines.push(line);
+        for clause in &self.clauses {
+            clause.get().lines(py, depth + 1, lines)?;
+        }
+        Ok(())
+    }
+}
+
+/// Evaluates `expr`, which must produce a bool, and explains why it is true or
+/// false, e.g. which conditions of a policy failed, with the values they read.
+#[pyfunction(signature = (expr, evaluation_context=None, mode=None))]
+pub fn explain(
+    py: Python<'_>,
+    expr: &str,
+    evaluation_context: Option<&Bound<'_, PyAny>>,
+    mode: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Explanation> {
+    let program = compile(expr).map_err(|error| error.to_py_err())?;
+    let explainer = Explainer {
+        context: evaluation_context,
+        options: resolve_mode(evaluation_context, mode)?,
+        output: output_types(evaluation_context),
+        originals: originals(evaluation_context),
+    };
+    let outcome = execute(
+        expr,
+        &program,
+        None,
+        evaluation_context,
+        None,
+        None,
+        explainer.options,
+    )?;
+    let got = match &outcome {
+        Outcome::Value(Value::Bool(_)) => None,
+        Outcome::Value(other) => Some(types::name_of(other).to_string()),
+        Outcome::Unknown(attributes) => Some(format!("unknown ({})", attributes.join(", "))),
+        Outcome::Error(error) => return Err(error.to_py_err()),
+    };
+    if let Some(got) = got {
+        return Err(PyTypeError::new_err(format!(
+            "Expected expression '{}' to evaluate to a bool, got {}",
+            expr, got
+        )));
+    }
+    explainer.explain(py, &program, Some(expr), outcome)
+}
+
+struct Explainer<'a, 'py> {
+    context: Option<&'a Bound<'py, PyAny>>,
+    options: crate::options::Options,
+    output: OutputTypes,
+    originals: Originals,
+}
+
+impl Explainer<'_, '_> {
+    fn evaluate(&self, expr: &Expression) -> PyResult<Outcome> {
+        execute(
+            &unparse(expr),
+            expr,
+            None,
+            self.context,
+            None,
+            None,
+            self.options,
+        )
+    }
+
+    /// Explains the `outcome` of `expr`, written as `source` if given
+    fn explain(
+        &self,
+        py: Python<'_>,
+        expr: &Expression,
+        source: Option<&str>,
+        outcome: Outcome,
+    ) -> PyResult<Explanation> {
+        let truthy = matches!(&outcome, Outcome::Value(value) if is_truthy(value));
+        let clauses: Vec<(&Expression, Outcome)> = match expr {
+            Expression::And(..) | Expression::Or(..) => {
+                let and = matches!(expr, Expression::And(..));
+                let mut operands = Vec::new();
+                flatten(expr, and, &mut operands);
+                let outcomes = operands
+                    .into_iter()
+                    .map(|operand| Ok((operand, self.evaluate(operand)?)))
+                    .collect::<PyResult<Vec<_>>>()?;
+                // A false `&&` or a true `||` is decided by any of the operands
+                // that are, the other results by all of them
+                if truthy != and {
+                    let deciding: Vec<_> = outcomes
+                        .iter()
+                        .filter(|(_, outcome)| {
+                            matches!(outcome, Outcome::Value(value) if is_truthy(value) == truthy)
+                        })
+                        .cloned()
+                        .collect();
+                    if deciding.is_empty() {
+                        outcomes
+                    } else {
+                        deciding
+                    }
+                } else {
+                    outcomes
+                }
+            }
+            Expression::Unary(UnaryOp::Not | UnaryOp::DoubleNot, operand) => {
+                vec![(&**operand, self.evaluate(operand)?)]
+            }
+            Expression::Ternary(condition, left, right) => {
+                let decided = self.evaluate(condition)?;
+                let branch = match &decided {
+                    Outcome::Value(value) if is_truthy(value) => left,
+                    Outcome::Value(_) => right,
+                    _ => return self.leaf(py, expr, source, outcome),
+                };
+                vec![(&**condition, decided), (&**branch, self.evaluate(branch)?)]
+            }
+            _ => return self.leaf(py, expr, source, outcome),
+        };
+
+        let clauses = clauses
+            .into_iter()
+            .map(|(clause, outcome)| Py::new(py, self.explain(py, clause, None, outcome)?))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(Explanation {
+            expression: source.map_or_else(|| unparse(expr), str::to_string),
+            value: self.py_value(py, outcome)?,
+            clauses,
+            values: PyDict::new_bound(py).unbind(),
+        })
+    }
+
+    /// A clause that isn't explained further, with the values it reads
+    fn leaf(
+        &self,
+        py: Python<'_>,
+        expr: &Expression,
+        source: Option<&str>,
+        outcome: Outcome,
+    ) -> PyResult<Explanation> {
+        let values = PyDict::new_bound(py);
+        let mut paths = Vec::new();
+        // A leaf that is itself a variable or field shows its value already
+        if !is_path(expr) {
+            read_paths(expr, &mut Vec::new(), &mut paths);
+        }
+        for path in paths {
+            let name = unparse(path);
+            if values.contains(&name)? {
+                continue;
+            }
+            // Paths that fail, such as missing fields, are left out
+            if let Outcome::Value(value) = self.evaluate(path)? {
+                values.set_item(name, self.py_value(py, Outcome::Value(value))?)?;
+            }
+        }
+        Ok(Explanation {
+            expression: source.map_or_else(|| unparse(expr), str::to_string),
+            value: self.py_value(py, outcome)?,
+            clauses: Vec::new(),
+            values: values.unbind(),
+        })
+    }
+
+    fn py_value(&self, py: Python<'_>, outcome: Outcome) -> PyResult<PyObject> {
+        crate::outcome_into_py(py, outcome, true, false, self.output, &self.originals)
+    }
+}
+
+/// The operands of a chain of `&&`, if `and`, or else of `||`
+fn flatten<'a>(expr: &'a Expression, and: bool, operands: &mut Vec<&'a Expression>) {
+    match expr {
+        Expression::And(left, right) if and => {
+            flatten(left, and, operands);
+            flatten(right, and, operands);
+        }
+        Expression::Or(left, right) if !and => {
+            flatten(left, and, operands);
+            flatten(right, and, operands);
+        }
+        _ => operands.push(expr),
+    }
+}
+
+/// The variables and chains of fields selected from them that `expr` reads,
+/// e.g. `user.age`, other than the variables of the macros in `bound`
+pub(crate) fn read_paths<'a>(
+    expr: &'a Expression,
+    bound: &mut Vec<&'a str>,
+    paths: &mut Vec<&'a Expression>,
+) {
+    match expr {
+        Expression::Ident(_) | Expression::Member(..) if is_path(expr) => {
+            if !root(expr).is_some_and(|name| bound.contains(&name)) {
+                paths.push(expr);
+            }
+        }
+        Expression::Member(target, member) => {
+            read_paths(target, bound, paths);
+            match &**member {
+                Member::Index(index) => read_paths(index, bound, paths),
+                Member::Fields(fields) => {
+                    for (_, value) in fields {
+                        read_paths(value, bound, paths);
+                    }
+                }
+                Member::Attribute(_) => {}
+            }
+        }
+        // The field `has` tests may be missing, so only what it's selected from is read
+        Expression::FunctionCall(function, None, args) if matches!(&**function, Expression::Ident(name) if name.as_str() == "has") => {
+            for arg in args {
+                match arg {
+                    Expression::Member(target, _) => read_paths(target, bound, paths),
+                    arg => read_paths(arg, bound, paths),
+                }
+            }
+        }
+        Expression::FunctionCall(function, target, args) => {
+            if let Some(target) = target {
+                read_paths(target, bound, paths);
+            }
+            let variable = match (&**function, args.first()) {
+                (Expression::Ident(name), Some(Expression::Ident(variable)))
+                    if target.is_some() && MACROS.contains(&name.as_str()) =>
+                {
+                    Some(variable.as_str())
+                }
+                _ => None,
+            };
+            match variable {
+                Some(variable) => {
+                    bound.push(variable);
+                    for arg in &args[1..] {
+                        read_paths(arg, bound, paths);
+                    }
+                    bound.pop();
+                }
+                None => {
+                    for arg in args {
+                        read_paths(arg, bound, paths);
+                    }
+                }
+            }
+        }
+        Expression::Arithmetic(left, _, right)
+        | Expression::Relation(left, _, right)
+        | Expression::And(left, right)
+        | Expression::Or(left, right) => {
+            read_paths(left, bound, paths);
+            read_paths(right, bound, paths);
+        }
+        Expression::Ternary(condition, left, right) => {
+            read_paths(condition, bound, paths);
+            read_paths(left, bound, paths);
+            read_paths(right, bound, paths);
+        }
+        Expression::Unary(_, operand) => read_paths(operand, bound, paths),
+        Expression::List(items) => {
+            for item in items {
+                read_paths(item, bound, paths);
+            }
+        }
+        Expression::Map(entries) => {
+            for (key, value) in entries {
+                read_paths(key, bound, paths);
+                read_paths(value, bound, paths);
+            }
+        }
+        Expression::Ident(_) | Expression::Atom(_) => {}
+    }
+}
+
+/// The variable a path starts from
+fn root(expr: &Expression) -> Option<&str> {
+    match expr {
+        Expression::Ident(name) => Some(name),
+        Expression::Member(target, _) => root(target),
+        _ => None,
+    }
+}
+
+/// Whether `expr` is a variable or a chain of fields selected from one
+pub(crate) fn is_path(expr: &Expression) -> bool {
+    match expr {
+        Expression::Ident(_) => true,
+        Expression::Member(target, member) => {
+            matches!(&**member, Member::Attribute(_)) && is_path(target)
+        }
+        _ => false,
+    }
+}
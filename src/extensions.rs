@@ -0,0 +1,84 @@
+//! Extensions that third-party packages contribute functions and types with,
+//! through the `cel.extensions` entry point group, for
+//! `Context(extensions=[...])`.
+//!
+//! An entry point refers to an extension: an object, usually a module, with a
+//! `functions` mapping of names to callables and/or a `types` mapping of names
+//! to the classes to register with `cel.register_type`, either a class or a dict
+//! of the other arguments of `register_type` with the class as `cls`. It can
+//! also refer to a callable that returns an extension when called without
+//! arguments.
+use crate::types::register_type;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyDict, PyMapping, PyType};
+
+/// The entry point group extensions are found in
+const GROUP: &str = "cel.extensions";
+
+/// The functions of the installed extension `name`, after registering its types
+pub fn load<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyDict>> {
+    let found = py.import_bound("importlib.metadata")?.call_method(
+        "entry_points",
+        (),
+        Some(&[("group", GROUP), ("name", name)].into_py_dict_bound(py)),
+    )?;
+    let entry_point = match found.iter()?.next() {
+        Some(entry_point) => entry_point?,
+        None => {
+            return Err(PyValueError::new_err(format!(
+                "no CEL extension '{}' is installed, extensions are found in the '{}' entry \
+                 point group",
+                name, GROUP
+            )))
+        }
+    };
+
+    let mut extension = entry_point.call_method0("load")?;
+    let declares = |extension: &Bound<'py, PyAny>| -> PyResult<bool> {
+        Ok(extension.hasattr("functions")? || extension.hasattr("types")?)
+    };
+    if !declares(&extension)? && extension.is_callable() {
+        extension = extension.call0()?;
+    }
+    if !declares(&extension)? {
+        return Err(PyTypeError::new_err(format!(
+            "CEL extension '{}' has neither functions nor types, got {}",
+            name,
+            crate::type_name(&extension)
+        )));
+    }
+
+    if let Ok(types) = extension.getattr("types") {
+        let types = types.downcast::<PyMapping>()?;
+        for item in types.items()?.iter()? {
+            let (type_name, registered): (String, Bound<'py, PyAny>) = item?.extract()?;
+            match registered.downcast::<PyDict>() {
+                Ok(arguments) => {
+                    let cls = arguments
+                        .get_item("cls")?
+                        .ok_or_else(|| {
+                            PyValueError::new_err(format!(
+                                "type '{}' of CEL extension '{}' has no 'cls'",
+                                type_name, name
+                            ))
+                        })?
+                        .downcast_into::<PyType>()?;
+                    register_type(
+                        py,
+                        &type_name,
+                        &cls,
+                        arguments.get_item("fields")?.as_ref(),
+                        arguments.get_item("methods")?.as_ref(),
+                    )?;
+                }
+                Err(_) => register_type(py, &type_name, registered.downcast()?, None, None)?,
+            }
+        }
+    }
+    let functions = PyDict::new_bound(py);
+    if let Ok(declared) = extension.getattr("functions") {
+        functions.update(declared.downcast()?)?;
+    }
+    Ok(functions)
+}
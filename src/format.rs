@@ -0,0 +1,593 @@
+//! The built-in `format` extension, loaded with `Context(extensions=["format"])`,
+//! for user-facing messages built in CEL: `format.number(x, locale)`,
+//! `format.currency(x, code, locale)` and `format.datetime(ts, pattern, locale)`.
+//!
+//! It is a small subset of ICU's formatting, with the separators, currency
+//! placement and month and weekday names of the languages in [`LOCALES`], which
+//! a locale like `de-CH` or `pt_BR` is matched to by its language. The
+//! functions are in the `format` namespace, see [`crate::namespaces`].
+use crate::functions::Described;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use chrono::{DateTime, Datelike, FixedOffset, Timelike};
+use std::sync::Arc;
+
+/// The name the extension is loaded by, and the namespace of its functions
+pub const NAME: &str = "format";
+
+/// The functions of the extension, with their signature and what they do, for
+/// `Context.functions_info`
+pub const FUNCTIONS: &[Described] = &[
+    (
+        "format.number",
+        "format.number(number, locale='en') -> string",
+        "The number with the digit grouping and decimal separator of a locale",
+    ),
+    (
+        "format.currency",
+        "format.currency(number, code, locale='en') -> string",
+        "An amount of a currency, such as 'USD', as written in a locale",
+    ),
+    (
+        "format.datetime",
+        "format.datetime(timestamp, pattern, locale='en') -> string",
+        "The timestamp in an ICU date pattern such as 'd MMMM yyyy', in a locale",
+    ),
+];
+
+/// Adds the functions of the extension to an environment
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function("format.number", number);
+    environment.add_function("format.currency", currency);
+    environment.add_function("format.datetime", datetime);
+}
+
+/// How a language writes numbers and dates
+struct Locale {
+    language: &'static str,
+    group: &'static str,
+    decimal: &'static str,
+    /// The fewest digits before the last group for the digits to be grouped,
+    /// e.g. 2 for `1234` but `12.345` in Spanish
+    minimum_grouping: usize,
+    /// The patterns of a positive and a negative amount, where `¤` is the
+    /// currency symbol and `#` the number
+    currency: (&'static str, &'static str),
+    months: [&'static str; 12],
+    short_months: [&'static str; 12],
+    /// From Sunday
+    weekdays: [&'static str; 7],
+    short_weekdays: [&'static str; 7],
+    periods: [&'static str; 2],
+}
+
+/// The locales that can be formatted for, by language
+const LOCALES: &[Locale] = &[
+    Locale {
+        language: "en",
+        group: ",",
+        decimal: ".",
+        minimum_grouping: 1,
+        currency: ("¤#", "-¤#"),
+        months: [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ],
+        short_months: [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ],
+        weekdays: [
+            "Sunday",
+            "Monday",
+            "Tuesday",
+            "Wednesday",
+            "Thursday",
+            "Friday",
+            "Saturday",
+        ],
+        short_weekdays: ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"],
+        periods: ["AM", "PM"],
+    },
+    Locale {
+        language: "de",
+        group: ".",
+        decimal: ",",
+        minimum_grouping: 1,
+        currency: ("#\u{a0}¤", "-#\u{a0}¤"),
+        months: [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ],
+        short_months: [
+            "Jan.", "Feb.", "März", "Apr.", "Mai", "Juni", "Juli", "Aug.", "Sept.", "Okt.", "Nov.",
+            "Dez.",
+        ],
+        weekdays: [
+            "Sonntag",
+            "Montag",
+            "Dienstag",
+            "Mittwoch",
+            "Donnerstag",
+            "Freitag",
+            "Samstag",
+        ],
+        short_weekdays: ["So.", "Mo.", "Di.", "Mi.", "Do.", "Fr.", "Sa."],
+        periods: ["AM", "PM"],
+    },
+    Locale {
+        language: "fr",
+        group: "\u{202f}",
+        decimal: ",",
+        minimum_grouping: 1,
+        currency: ("#\u{a0}¤", "-#\u{a0}¤"),
+        months: [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        short_months: [
+            "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.",
+            "nov.", "déc.",
+        ],
+        weekdays: [
+            "dimanche", "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi",
+        ],
+        short_weekdays: ["dim.", "lun.", "mar.", "mer.", "jeu.", "ven.", "sam."],
+        periods: ["AM", "PM"],
+    },
+    Locale {
+        language: "es",
+        group: ".",
+        decimal: ",",
+        minimum_grouping: 2,
+        currency: ("#\u{a0}¤", "-#\u{a0}¤"),
+        months: [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+        short_months: [
+            "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+        ],
+        weekdays: [
+            "domingo",
+            "lunes",
+            "martes",
+            "miércoles",
+            "jueves",
+            "viernes",
+            "sábado",
+        ],
+        short_weekdays: ["dom", "lun", "mar", "mié", "jue", "vie", "sáb"],
+        periods: ["a.\u{a0}m.", "p.\u{a0}m."],
+    },
+    Locale {
+        language: "it",
+        group: ".",
+        decimal: ",",
+        minimum_grouping: 1,
+        currency: ("#\u{a0}¤", "-#\u{a0}¤"),
+        months: [
+            "gennaio",
+            "febbraio",
+            "marzo",
+            "aprile",
+            "maggio",
+            "giugno",
+            "luglio",
+            "agosto",
+            "settembre",
+            "ottobre",
+            "novembre",
+            "dicembre",
+        ],
+        short_months: [
+            "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic",
+        ],
+        weekdays: [
+            "domenica",
+            "lunedì",
+            "martedì",
+            "mercoledì",
+            "giovedì",
+            "venerdì",
+            "sabato",
+        ],
+        short_weekdays: ["dom", "lun", "mar", "mer", "gio", "ven", "sab"],
+        periods: ["AM", "PM"],
+    },
+    Locale {
+        language: "nl",
+        group: ".",
+        decimal: ",",
+        minimum_grouping: 1,
+        currency: ("¤\u{a0}#", "¤\u{a0}-#"),
+        months: [
+            "januari",
+            "februari",
+            "maart",
+            "april",
+            "mei",
+            "juni",
+            "juli",
+            "augustus",
+            "september",
+            "oktober",
+            "november",
+            "december",
+        ],
+        short_months: [
+            "jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+        ],
+        weekdays: [
+            "zondag",
+            "maandag",
+            "dinsdag",
+            "woensdag",
+            "donderdag",
+            "vrijdag",
+            "zaterdag",
+        ],
+        short_weekdays: ["zo", "ma", "di", "wo", "do", "vr", "za"],
+        periods: ["a.m.", "p.m."],
+    },
+    Locale {
+        language: "pt",
+        group: ".",
+        decimal: ",",
+        minimum_grouping: 1,
+        currency: ("¤\u{a0}#", "-¤\u{a0}#"),
+        months: [
+            "janeiro",
+            "fevereiro",
+            "março",
+            "abril",
+            "maio",
+            "junho",
+            "julho",
+            "agosto",
+            "setembro",
+            "outubro",
+            "novembro",
+            "dezembro",
+        ],
+        short_months: [
+            "jan.", "fev.", "mar.", "abr.", "mai.", "jun.", "jul.", "ago.", "set.", "out.", "nov.",
+            "dez.",
+        ],
+        weekdays: [
+            "domingo",
+            "segunda-feira",
+            "terça-feira",
+            "quarta-feira",
+            "quinta-feira",
+            "sexta-feira",
+            "sábado",
+        ],
+        short_weekdays: ["dom.", "seg.", "ter.", "qua.", "qui.", "sex.", "sáb."],
+        periods: ["AM", "PM"],
+    },
+    Locale {
+        language: "ja",
+        group: ",",
+        decimal: ".",
+        minimum_grouping: 1,
+        currency: ("¤#", "-¤#"),
+        months: [
+            "1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月",
+        ],
+        short_months: [
+            "1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月",
+        ],
+        weekdays: [
+            "日曜日",
+            "月曜日",
+            "火曜日",
+            "水曜日",
+            "木曜日",
+            "金曜日",
+            "土曜日",
+        ],
+        short_weekdays: ["日", "月", "火", "水", "木", "金", "土"],
+        periods: ["午前", "午後"],
+    },
+];
+
+/// The symbols of the currencies that have one, and the digits their amounts
+/// have after the decimal separator. Other currencies are written with their
+/// code and two digits.
+const CURRENCIES: &[(&str, &str, usize)] = &[
+    ("USD", "$", 2),
+    ("EUR", "€", 2),
+    ("GBP", "£", 2),
+    ("JPY", "¥", 0),
+    ("CNY", "CN¥", 2),
+    ("KRW", "₩", 0),
+    ("INR", "₹", 2),
+    ("AUD", "A$", 2),
+    ("CAD", "CA$", 2),
+    ("NZD", "NZ$", 2),
+    ("BRL", "R$", 2),
+    ("MXN", "MX$", 2),
+];
+
+/// The most digits a number is written with after the decimal separator
+const MAX_FRACTION_DIGITS: usize = 3;
+
+/// The resolved arguments of a call, checking there are `N`, or `N - 1`
+/// without the locale, which is then English
+fn arguments<const N: usize>(
+    ftx: &FunctionContext,
+) -> Result<([Value; N], &'static Locale), ExecutionError> {
+    let mut values = ftx
+        .args
+        .iter()
+        .map(|arg| ftx.ptx.resolve(arg))
+        .collect::<Result<Vec<_>, _>>()?;
+    let count = values.len();
+    if count + 1 == N {
+        values.push(Value::String(Arc::new("en".to_string())));
+    }
+    let values: [Value; N] = values
+        .try_into()
+        .map_err(|_| ExecutionError::invalid_argument_count(N, count))?;
+    let locale = locale(ftx, &values[N - 1])?;
+    Ok((values, locale))
+}
+
+/// The locale of a tag like `en`, `en-NZ` or `pt_BR`, by its language
+fn locale(ftx: &FunctionContext, tag: &Value) -> Result<&'static Locale, ExecutionError> {
+    let Value::String(tag) = tag else {
+        return Err(ftx.error(format!("expected a locale, got {}", tag.type_of())));
+    };
+    let language = tag.split(['-', '_']).next().unwrap_or_default();
+    LOCALES
+        .iter()
+        .find(|locale| locale.language.eq_ignore_ascii_case(language))
+        .ok_or_else(|| {
+            let languages: Vec<_> = LOCALES.iter().map(|locale| locale.language).collect();
+            ftx.error(format!(
+                "unsupported locale '{}', expected one of the languages {}",
+                tag,
+                languages.join(", ")
+            ))
+        })
+}
+
+/// Returns a number with the digit grouping and decimal separator of a
+/// locale, and at most three digits after it.
+///
+/// # Examples
+/// ```cel
+/// format.number(1234567.891, 'de-DE') == '1.234.567,891'
+/// ```
+pub fn number(ftx: &FunctionContext) -> ResolveResult {
+    let ([x, _], locale) = arguments(ftx)?;
+    let text = match x {
+        Value::Int(x) => written(locale, x < 0, &x.unsigned_abs().to_string(), ""),
+        Value::UInt(x) => written(locale, false, &x.to_string(), ""),
+        Value::Float(x) => decimal(locale, x, MAX_FRACTION_DIGITS, true),
+        other => return Err(ftx.error(format!("expected a number, got {}", other.type_of()))),
+    };
+    Ok(Value::String(Arc::new(text)))
+}
+
+/// Returns an amount of a currency, given by its ISO 4217 code, as written in
+/// a locale, with the digits the currency has after the decimal separator.
+///
+/// # Examples
+/// ```cel
+/// format.currency(order.total, 'EUR', 'fr') == '1 234,50 €'
+/// ```
+pub fn currency(ftx: &FunctionContext) -> ResolveResult {
+    let ([amount, code, _], locale) = arguments(ftx)?;
+    let amount = match amount {
+        Value::Int(amount) => amount as f64,
+        Value::UInt(amount) => amount as f64,
+        Value::Float(amount) => amount,
+        other => return Err(ftx.error(format!("expected an amount, got {}", other.type_of()))),
+    };
+    let code = match code {
+        Value::String(code) if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) => {
+            code.to_ascii_uppercase()
+        }
+        Value::String(code) => {
+            return Err(ftx.error(format!(
+                "expected a currency code such as 'USD', got '{}'",
+                code
+            )))
+        }
+        other => {
+            return Err(ftx.error(format!("expected a currency code, got {}", other.type_of())))
+        }
+    };
+    let (symbol, digits) = CURRENCIES
+        .iter()
+        .find(|(known, _, _)| *known == code)
+        .map(|(_, symbol, digits)| (symbol.to_string(), *digits))
+        .unwrap_or((code, 2));
+    let number = decimal(locale, amount.abs(), digits, false);
+    let negative = amount < 0.0 && number.chars().any(|c| c.is_ascii_digit() && c != '0');
+    let pattern = if negative {
+        locale.currency.1
+    } else {
+        locale.currency.0
+    };
+    let text = pattern.replace('#', &number).replace('¤', &symbol);
+    Ok(Value::String(Arc::new(text)))
+}
+
+/// A double with `digits` after the decimal separator, or at most that many
+/// if `trim`
+fn decimal(locale: &Locale, x: f64, digits: usize, trim: bool) -> String {
+    if x.is_nan() {
+        return "NaN".to_string();
+    }
+    if x.is_infinite() {
+        return if x < 0.0 { "-∞" } else { "∞" }.to_string();
+    }
+    let rounded = format!("{:.*}", digits, x.abs());
+    let (whole, fraction) = rounded.split_once('.').unwrap_or((&rounded, ""));
+    let fraction = if trim {
+        fraction.trim_end_matches('0')
+    } else {
+        fraction
+    };
+    // `-0.0001` rounds to zero, which has no sign
+    let negative = x < 0.0 && rounded.chars().any(|c| c.is_ascii_digit() && c != '0');
+    written(locale, negative, whole, fraction)
+}
+
+/// The digits of a number before and after the decimal separator, as a locale
+/// writes them
+fn written(locale: &Locale, negative: bool, whole: &str, fraction: &str) -> String {
+    let mut text = String::new();
+    if negative {
+        text.push('-');
+    }
+    let grouped = whole.len() >= 3 + locale.minimum_grouping;
+    for (i, digit) in whole.chars().enumerate() {
+        if grouped && i > 0 && (whole.len() - i).is_multiple_of(3) {
+            text.push_str(locale.group);
+        }
+        text.push(digit);
+    }
+    if !fraction.is_empty() {
+        text.push_str(locale.decimal);
+        text.push_str(fraction);
+    }
+    text
+}
+
+/// Returns a timestamp, in its own UTC offset, written in an ICU date pattern
+/// of the letters `y`, `M`, `d`, `E`, `a`, `H`, `h`, `m`, `s` and `S`, with
+/// text in single quotes, in a locale.
+///
+/// # Examples
+/// ```cel
+/// format.datetime(event.at, 'EEEE d MMMM yyyy', 'fr') == 'lundi 6 mai 2024'
+/// ```
+pub fn datetime(ftx: &FunctionContext) -> ResolveResult {
+    let ([ts, pattern, _], locale) = arguments(ftx)?;
+    let Value::Timestamp(ts) = ts else {
+        return Err(ftx.error(format!("expected a timestamp, got {}", ts.type_of())));
+    };
+    let Value::String(pattern) = pattern else {
+        return Err(ftx.error(format!("expected a pattern, got {}", pattern.type_of())));
+    };
+    let mut text = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            // Quoted text, in which `''` is a quote, as it is outside
+            if chars.peek() == Some(&'\'') {
+                chars.next();
+                text.push('\'');
+                continue;
+            }
+            while let Some(c) = chars.next() {
+                match c {
+                    '\'' if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        text.push('\'');
+                    }
+                    '\'' => break,
+                    c => text.push(c),
+                }
+            }
+        } else if c.is_ascii_alphabetic() {
+            let mut width = 1;
+            while chars.peek() == Some(&c) {
+                chars.next();
+                width += 1;
+            }
+            text.push_str(&field(ftx, locale, &ts, c, width)?);
+        } else {
+            text.push(c);
+        }
+    }
+    Ok(Value::String(Arc::new(text)))
+}
+
+/// A field of a date pattern, a letter repeated `width` times
+fn field(
+    ftx: &FunctionContext,
+    locale: &Locale,
+    ts: &DateTime<FixedOffset>,
+    letter: char,
+    width: usize,
+) -> Result<String, ExecutionError> {
+    let padded = |value: u32| format!("{:0width$}", value, width = width);
+    Ok(match letter {
+        'y' if width == 2 => format!("{:02}", ts.year().rem_euclid(100)),
+        'y' => format!("{:0width$}", ts.year(), width = width),
+        'M' | 'L' => match width {
+            1 | 2 => padded(ts.month()),
+            3 => locale.short_months[ts.month0() as usize].to_string(),
+            _ => locale.months[ts.month0() as usize].to_string(),
+        },
+        'd' => padded(ts.day()),
+        'E' => {
+            let weekday = ts.weekday().num_days_from_sunday() as usize;
+            match width {
+                1..=3 => locale.short_weekdays[weekday].to_string(),
+                _ => locale.weekdays[weekday].to_string(),
+            }
+        }
+        'a' => locale.periods[usize::from(ts.hour() >= 12)].to_string(),
+        'H' => padded(ts.hour()),
+        'h' => padded(match ts.hour() % 12 {
+            0 => 12,
+            hour => hour,
+        }),
+        'm' => padded(ts.minute()),
+        's' => padded(ts.second()),
+        'S' => {
+            let nanos = format!("{:09}", ts.nanosecond().min(999_999_999));
+            format!("{:0<width$}", &nanos[..width.min(9)], width = width)
+        }
+        _ => {
+            return Err(ftx.error(format!(
+                "unsupported pattern letter '{}', expected one of y, M, d, E, a, H, h, m, s and S",
+                letter
+            )))
+        }
+    })
+}
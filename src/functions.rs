@@ -0,0 +1,372 @@
+use crate::aggregates;
+use crate::comprehensions;
+use crate::conversions;
+use crate::duration;
+use crate::json;
+use crate::options::Options;
+use crate::random;
+use crate::timestamps;
+use crate::types;
+use crate::unknowns::unknown_attributes;
+use cel_interpreter::objects::Key;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use cel_parser::Expression;
+use std::convert::TryInto;
+
+/// The name of a function, its signature and what it does
+pub type Described = (&'static str, &'static str, &'static str);
+
+/// The functions every environment has, with their signature and what they do,
+/// for `Context.functions_info`
+pub const BUILTINS: &[Described] = &[
+    (
+        "size",
+        "size(value) -> int",
+        "The length of a string, bytes, list or map",
+    ),
+    (
+        "contains",
+        "string.contains(substring) -> bool",
+        "Whether the string contains the substring",
+    ),
+    (
+        "startsWith",
+        "string.startsWith(prefix) -> bool",
+        "Whether the string starts with the prefix",
+    ),
+    (
+        "endsWith",
+        "string.endsWith(suffix) -> bool",
+        "Whether the string ends with the suffix",
+    ),
+    (
+        "matches",
+        "string.matches(regex) -> bool",
+        "Whether the string matches the regular expression",
+    ),
+    (
+        "has",
+        "has(field) -> bool",
+        "Whether a field or variable is present",
+    ),
+    (
+        "get",
+        "target.get(key, default) -> value",
+        "An entry of a map or list, or the default (null) when it is missing",
+    ),
+    (
+        "coalesce",
+        "coalesce(values...) -> value",
+        "The first argument that is neither null nor an error, or null if there is none",
+    ),
+    (
+        "rand",
+        "rand() -> double",
+        "A random double from 0 up to 1, drawn from the context's seed when it has one",
+    ),
+    (
+        "sample",
+        "sample(probability) -> bool",
+        "True with the given probability, drawn as rand() is",
+    ),
+    (
+        "bucket",
+        "bucket(id, n) -> int",
+        "The bucket from 0 to n - 1 an id falls in, the same in every process",
+    ),
+    (
+        "json.decode",
+        "json.decode(string) -> dyn",
+        "The value of a JSON string",
+    ),
+    (
+        "json.encode",
+        "json.encode(value) -> string",
+        "The value as a JSON string",
+    ),
+    (
+        "map",
+        "list.map(x, [predicate,] expression) -> list",
+        "The expression evaluated for each item, or for each the predicate is true for",
+    ),
+    (
+        "filter",
+        "list.filter(x, predicate) -> list",
+        "The items the predicate is true for",
+    ),
+    (
+        "all",
+        "list.all(x, predicate) -> bool",
+        "Whether the predicate is true for every item",
+    ),
+    (
+        "exists",
+        "list.exists(x, predicate) -> bool",
+        "Whether the predicate is true for any item",
+    ),
+    (
+        "exists_one",
+        "list.exists_one(x, predicate) -> bool",
+        "Whether the predicate is true for exactly one item",
+    ),
+    (
+        "existsOne",
+        "list.existsOne(x, predicate) -> bool",
+        "Another name for exists_one",
+    ),
+    (
+        "count",
+        "list.count(x, predicate) -> int",
+        "The number of items the predicate is true for",
+    ),
+    (
+        "groupBy",
+        "list.groupBy(x, key) -> map",
+        "The items grouped in lists by the key they have",
+    ),
+    (
+        "countBy",
+        "list.countBy(x, key) -> map",
+        "The number of items that have each key",
+    ),
+    (
+        "distinct",
+        "list.distinct([x, key]) -> list",
+        "The first item with each key, or of each equal item without one",
+    ),
+    (
+        "max",
+        "max(values...) -> value",
+        "The largest of the arguments, of the items of a list or of the values of a map",
+    ),
+    (
+        "min",
+        "min(values...) -> value",
+        "The smallest of the arguments, of the items of a list or of the values of a map",
+    ),
+    (
+        "sum",
+        "sum(list) -> number",
+        "The sum of the numbers or durations of a list or the values of a map",
+    ),
+    (
+        "avg",
+        "avg(list) -> double",
+        "The mean of the numbers of a list or the values of a map",
+    ),
+    ("int", "int(value) -> int", "Converts a value to an int"),
+    ("uint", "uint(value) -> uint", "Converts a value to a uint"),
+    (
+        "double",
+        "double(value) -> double",
+        "Converts a value to a double",
+    ),
+    (
+        "string",
+        "string(value) -> string",
+        "Converts a value to a string",
+    ),
+    (
+        "bytes",
+        "bytes(value) -> bytes",
+        "Converts a string to bytes",
+    ),
+    ("bool", "bool(value) -> bool", "Converts a value to a bool"),
+    (
+        "dyn",
+        "dyn(value) -> value",
+        "The value, with its type only checked at evaluation",
+    ),
+    ("type", "type(value) -> type", "The type of a value"),
+    (
+        "duration",
+        "duration(string) -> duration",
+        "Parses a duration such as '1h30m'",
+    ),
+    (
+        "timestamp",
+        "timestamp(string) -> timestamp",
+        "Parses an RFC 3339 timestamp",
+    ),
+    (
+        "getFullYear",
+        "timestamp.getFullYear([timezone]) -> int",
+        "The year",
+    ),
+    (
+        "getMonth",
+        "timestamp.getMonth([timezone]) -> int",
+        "The month, from 0",
+    ),
+    (
+        "getDayOfYear",
+        "timestamp.getDayOfYear([timezone]) -> int",
+        "The day of the year, from 0",
+    ),
+    (
+        "getDayOfMonth",
+        "timestamp.getDayOfMonth([timezone]) -> int",
+        "The day of the month, from 0",
+    ),
+    (
+        "getDate",
+        "timestamp.getDate([timezone]) -> int",
+        "The day of the month, from 1",
+    ),
+    (
+        "getDayOfWeek",
+        "timestamp.getDayOfWeek([timezone]) -> int",
+        "The day of the week, from 0 for Sunday",
+    ),
+    (
+        "getHours",
+        "timestamp.getHours([timezone]) -> int",
+        "The hours",
+    ),
+    (
+        "getMinutes",
+        "timestamp.getMinutes([timezone]) -> int",
+        "The minutes",
+    ),
+    (
+        "getSeconds",
+        "timestamp.getSeconds([timezone]) -> int",
+        "The seconds",
+    ),
+    (
+        "getMilliseconds",
+        "timestamp.getMilliseconds([timezone]) -> int",
+        "The milliseconds",
+    ),
+];
+
+/// Adds the functions that extend or replace the interpreter's builtins
+pub fn register(environment: &mut cel_interpreter::Context, options: &Options) {
+    environment.add_function("has", has);
+    environment.add_function("get", get);
+    environment.add_function("coalesce", coalesce);
+    environment.add_function("rand", random::rand);
+    environment.add_function("sample", random::sample);
+    environment.add_function("bucket", random::bucket);
+    environment.add_function("json.decode", json::decode);
+    environment.add_function("json.encode", json::encode);
+    comprehensions::register(environment);
+    aggregates::register(environment, options.numeric_promotion);
+    environment.add_function("duration", duration::duration);
+    conversions::register(environment);
+    if options.lenient_timestamps {
+        environment.add_function("timestamp", timestamps::timestamp);
+    } else {
+        environment.add_function("timestamp", timestamps::strict_timestamp);
+    }
+}
+
+/// The value a function was called on, or else its first argument, so that
+/// both `x.f()` and `f(x)` are accepted.
+pub fn this_or_arg(ftx: &FunctionContext) -> ResolveResult {
+    match &ftx.this {
+        Some(this) => Ok(this.clone()),
+        None => match ftx.args.first() {
+            Some(arg) => ftx.ptx.resolve(arg),
+            None => Err(ExecutionError::invalid_argument_count(1, 0)),
+        },
+    }
+}
+
+/// Returns true if the argument can be resolved.
+///
+/// As well as field selections (`has(user.email)`) this accepts a bare variable
+/// name, so `has(feature_flags)` is false when no such variable was provided
+/// rather than an undeclared reference error.
+pub fn has(ftx: &FunctionContext) -> ResolveResult {
+    let arg = ftx
+        .args
+        .first()
+        .ok_or_else(|| ExecutionError::invalid_argument_count(1, 0))?;
+
+    match ftx.ptx.resolve(arg) {
+        Ok(_) => Ok(Value::Bool(true)),
+        Err(ExecutionError::NoSuchKey(_)) => Ok(Value::Bool(false)),
+        Err(ExecutionError::UndeclaredReference(name)) if matches!(arg, Expression::Ident(ident) if *ident == name) => {
+            Ok(Value::Bool(false))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns the entry of a map or list, or a default value if it is absent.
+///
+/// The default is only evaluated when the entry is missing, and is `null` if not
+/// provided.
+///
+/// # Examples
+/// ```cel
+/// {'a': 1}.get('b', 0) == 0
+/// [1, 2, 3].get(5) == null
+/// ```
+pub fn get(ftx: &FunctionContext) -> ResolveResult {
+    let (target, args) = match &ftx.this {
+        Some(this) => (this.clone(), &ftx.args[..]),
+        None => match ftx.args.split_first() {
+            Some((target, args)) => (ftx.ptx.resolve(target)?, args),
+            None => return Err(ExecutionError::invalid_argument_count(2, 0)),
+        },
+    };
+    let (key, default) = match args {
+        [key] => (key, None),
+        [key, default] => (key, Some(default)),
+        _ => return Err(ExecutionError::invalid_argument_count(2, args.len())),
+    };
+
+    let entry = match (&target, ftx.ptx.resolve(key)?) {
+        (Value::Map(map), key) => {
+            let key: Key = key.try_into().map_err(ExecutionError::UnsupportedKeyType)?;
+            map.get(&key)
+                .filter(|_| !types::is_hidden(map, &key))
+                .cloned()
+        }
+        (Value::List(items), Value::Int(index)) => usize::try_from(index)
+            .ok()
+            .and_then(|index| items.get(index))
+            .cloned(),
+        (Value::List(items), Value::UInt(index)) => usize::try_from(index)
+            .ok()
+            .and_then(|index| items.get(index))
+            .cloned(),
+        (Value::List(_), index) => return Err(ExecutionError::UnsupportedListIndex(index)),
+        (target, _) => {
+            return Err(ftx.error(format!("cannot get an entry of {}", target.type_of())))
+        }
+    };
+
+    match (entry, default) {
+        (Some(value), _) => Ok(value),
+        (None, Some(default)) => ftx.ptx.resolve(default),
+        (None, None) => Ok(Value::Null),
+    }
+}
+
+/// Returns the first argument that is neither null nor an error, or null if
+/// there is none.
+///
+/// The arguments are evaluated in order, and only until one has a value, so
+/// missing fields and variables fall through to the next argument. An unknown
+/// argument is returned, as whether it is null isn't known.
+///
+/// # Examples
+/// ```cel
+/// coalesce(user.nickname, user.name, 'anonymous')
+/// ```
+pub fn coalesce(ftx: &FunctionContext) -> ResolveResult {
+    let this = ftx.this.iter().cloned().map(Ok);
+    let args = ftx.args.iter().map(|arg| ftx.ptx.resolve(arg));
+    for result in this.chain(args) {
+        match result {
+            Ok(Value::Null) => {}
+            Ok(value) => return Ok(value),
+            Err(error) if unknown_attributes(&error).is_some() => return Err(error),
+            Err(_) => {}
+        }
+    }
+    Ok(Value::Null)
+}
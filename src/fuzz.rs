@@ -0,0 +1,151 @@
+//! `cel.fuzz`, differential evaluation of an expression in both modes, and
+//! optionally with a reference implementation, to find the expressions whose
+//! outcome depends on the mode before changing the mode they're evaluated in.
+use crate::conformance::{divergence, outcome_into_py, RemoteService};
+use crate::evaluate_value;
+use crate::options::Options;
+use crate::repr_of;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// The options of the modes, which a divergence can be caused by
+const OPTIONS: [&str; 7] = [
+    "numeric_promotion",
+    "truthiness",
+    "safe_navigation",
+    "heterogeneous_equality",
+    "lenient_timestamps",
+    "duplicate_map_keys",
+    "error_absorption",
+];
+
+/// The option `name` of `options`
+fn option<'a>(options: &'a mut Options, name: &str) -> &'a mut bool {
+    match name {
+        "numeric_promotion" => &mut options.numeric_promotion,
+        "truthiness" => &mut options.truthiness,
+        "safe_navigation" => &mut options.safe_navigation,
+        "heterogeneous_equality" => &mut options.heterogeneous_equality,
+        "lenient_timestamps" => &mut options.lenient_timestamps,
+        "duplicate_map_keys" => &mut options.duplicate_map_keys,
+        "error_absorption" => &mut options.error_absorption,
+        _ => unreachable!("unknown option {}", name),
+    }
+}
+
+/// The order of the kinds of divergence, from the most to the least severe
+const KINDS: [&str; 3] = ["compile", "error", "value"];
+
+/// An expression whose outcome differs between the modes, or from a reference
+/// implementation
+#[pyclass(frozen, module = "cel.fuzz")]
+pub struct Divergence {
+    #[pyo3(get)]
+    expression: String,
+    /// The most severe difference between any two of the outcomes: "compile" if
+    /// only some failed to compile the expression, "error" if only some failed
+    /// to evaluate it, or "value" if the values differ
+    #[pyo3(get)]
+    kind: &'static str,
+    /// The result of each evaluation, or an `EvalError`, by "python", "strict"
+    /// and "reference"
+    #[pyo3(get)]
+    results: Py<PyDict>,
+    /// The options that change the outcome of the Python mode when only they
+    /// are set as in the strict mode
+    #[pyo3(get)]
+    options: Vec<&'static str>,
+}
+
+#[pymethods]
+impl Divergence {
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!(
+            "Divergence(expression={}, kind={}, options={})",
+            repr_of(py, &self.expression),
+            repr_of(py, self.kind),
+            repr_of(py, &self.options)
+        )
+    }
+}
+
+/// Evaluates `expr` with `context` in both the Python and the strict mode, and
+/// with `reference`, a `cel.conformance.RemoteService`, when given. Returns a
+/// `Divergence` if the outcomes differ, otherwise None.
+///
+/// The mode of a `Context` passed as `context` is ignored. A reference
+/// implementation is only sent variables, so `context` has to be a dict of
+/// them.
+#[pyfunction(signature = (expr, context=None, reference=None))]
+fn compare(
+    py: Python<'_>,
+    expr: &str,
+    context: Option<&Bound<'_, PyAny>>,
+    reference: Option<&RemoteService>,
+) -> PyResult<Option<Divergence>> {
+    let evaluate = |options: Options| evaluate_value(expr, context, None, None, options, None);
+    let mut outcomes = vec![
+        ("python", evaluate(Options::PYTHON)?),
+        ("strict", evaluate(Options::STRICT)?),
+    ];
+    if let Some(reference) = reference {
+        let variables = match context {
+            Some(context) => Some(context.downcast::<PyDict>().map_err(|_| {
+                PyTypeError::new_err(
+                    "a reference implementation is only sent variables, pass them as a dict",
+                )
+            })?),
+            None => None,
+        };
+        outcomes.push(("reference", reference.outcome(py, expr, variables)?));
+    }
+
+    let mut kinds = Vec::new();
+    for (i, (_, a)) in outcomes.iter().enumerate() {
+        for (_, b) in &outcomes[i + 1..] {
+            kinds.extend(divergence(a, b));
+        }
+    }
+    let Some(kind) = KINDS.into_iter().find(|kind| kinds.contains(kind)) else {
+        return Ok(None);
+    };
+
+    let python = &outcomes[0].1;
+    let mut options = Vec::new();
+    for name in OPTIONS {
+        let (mut changed, mut strict) = (Options::PYTHON, Options::STRICT);
+        let strict = *option(&mut strict, name);
+        if *option(&mut changed, name) == strict {
+            continue;
+        }
+        *option(&mut changed, name) = strict;
+        if divergence(python, &evaluate(changed)?).is_some() {
+            options.push(name);
+        }
+    }
+
+    let results = PyDict::new_bound(py);
+    for (engine, outcome) in outcomes {
+        results.set_item(engine, outcome_into_py(py, outcome)?)?;
+    }
+    Ok(Some(Divergence {
+        expression: expr.to_string(),
+        kind,
+        results: results.unbind(),
+        options,
+    }))
+}
+
+/// Adds the `cel.fuzz` module
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    let fuzz = PyModule::new_bound(py, "fuzz")?;
+    fuzz.add_class::<Divergence>()?;
+    fuzz.add_function(wrap_pyfunction!(compare, &fuzz)?)?;
+    m.add_submodule(&fuzz)?;
+    // Lets `import cel.fuzz` find the module
+    py.import_bound("sys")?
+        .getattr("modules")?
+        .set_item("cel.fuzz", fuzz)
+}
@@ -0,0 +1,172 @@
+//! The built-in `geo` extension, loaded with `Context(extensions=["geo"])`, for
+//! geofencing rules: `geo.point(lat, lng)`, `geo.distance(p1, p2)` and
+//! `geo.inPolygon(p, polygon)`.
+//!
+//! A point is a map of `lat` and `lng` in degrees, so points can also be passed
+//! in as dicts. The functions are in the `geo` namespace, see
+//! [`crate::namespaces`].
+use crate::functions::Described;
+use cel_interpreter::objects::{Key, Map};
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The name the extension is loaded by, and the namespace of its functions
+pub const NAME: &str = "geo";
+
+/// The functions of the extension, with their signature and what they do, for
+/// `Context.functions_info`
+pub const FUNCTIONS: &[Described] = &[
+    (
+        "geo.point",
+        "geo.point(lat, lng) -> map",
+        "A point at a latitude and longitude in degrees",
+    ),
+    (
+        "geo.distance",
+        "geo.distance(point, point) -> double",
+        "The great-circle distance between two points in meters",
+    ),
+    (
+        "geo.inPolygon",
+        "geo.inPolygon(point, list) -> bool",
+        "Whether a point is inside a polygon given as a list of its vertices",
+    ),
+];
+
+/// The mean radius of the Earth in meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Adds the functions of the extension to an environment
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function("geo.point", point);
+    environment.add_function("geo.distance", distance);
+    environment.add_function("geo.inPolygon", in_polygon);
+}
+
+/// The resolved arguments of a call, checking there are `N`
+fn arguments<const N: usize>(ftx: &FunctionContext) -> Result<[Value; N], ExecutionError> {
+    let args = ftx
+        .args
+        .iter()
+        .map(|arg| ftx.ptx.resolve(arg))
+        .collect::<Result<Vec<_>, _>>()?;
+    let count = args.len();
+    args.try_into()
+        .map_err(|_| ExecutionError::invalid_argument_count(N, count))
+}
+
+/// A number of degrees, which is an int or a double
+fn degrees(ftx: &FunctionContext, value: &Value) -> Result<f64, ExecutionError> {
+    match value {
+        Value::Int(value) => Ok(*value as f64),
+        Value::UInt(value) => Ok(*value as f64),
+        Value::Float(value) => Ok(*value),
+        other => Err(ftx.error(format!(
+            "expected a number of degrees, got {}",
+            other.type_of()
+        ))),
+    }
+}
+
+/// The latitude and longitude of a point
+fn coordinates(ftx: &FunctionContext, value: &Value) -> Result<(f64, f64), ExecutionError> {
+    let Value::Map(map) = value else {
+        return Err(ftx.error(format!(
+            "expected a point, a map of lat and lng, got {}",
+            value.type_of()
+        )));
+    };
+    let field = |name: &str| match map.map.get(&Key::String(Arc::new(name.to_string()))) {
+        Some(value) => degrees(ftx, value),
+        None => Err(ftx.error(format!("point has no {}", name))),
+    };
+    checked(ftx, field("lat")?, field("lng")?)
+}
+
+/// Checks that a latitude and longitude are in range
+fn checked(ftx: &FunctionContext, lat: f64, lng: f64) -> Result<(f64, f64), ExecutionError> {
+    if !(-90.0..=90.0).contains(&lat) {
+        return Err(ftx.error(format!("latitude {} is not between -90 and 90", lat)));
+    }
+    if !(-180.0..=180.0).contains(&lng) {
+        return Err(ftx.error(format!("longitude {} is not between -180 and 180", lng)));
+    }
+    Ok((lat, lng))
+}
+
+/// Returns a point at a latitude and longitude in degrees.
+///
+/// # Examples
+/// ```cel
+/// geo.point(-36.85, 174.76) == {'lat': -36.85, 'lng': 174.76}
+/// ```
+pub fn point(ftx: &FunctionContext) -> ResolveResult {
+    let [lat, lng] = arguments(ftx)?;
+    let (lat, lng) = checked(ftx, degrees(ftx, &lat)?, degrees(ftx, &lng)?)?;
+    let mut fields = HashMap::new();
+    fields.insert(Key::String(Arc::new("lat".to_string())), Value::Float(lat));
+    fields.insert(Key::String(Arc::new("lng".to_string())), Value::Float(lng));
+    Ok(Value::Map(Map {
+        map: Arc::new(fields),
+    }))
+}
+
+/// Returns the great-circle distance between two points in meters, by the
+/// haversine formula on a sphere of the Earth's mean radius.
+///
+/// # Examples
+/// ```cel
+/// geo.distance(geo.point(-36.85, 174.76), geo.point(-41.29, 174.78)) < 500000.0
+/// ```
+pub fn distance(ftx: &FunctionContext) -> ResolveResult {
+    let [a, b] = arguments(ftx)?;
+    let (lat1, lng1) = coordinates(ftx, &a)?;
+    let (lat2, lng2) = coordinates(ftx, &b)?;
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let half_dlat = (lat2 - lat1) / 2.0;
+    let half_dlng = (lng2 - lng1).to_radians() / 2.0;
+    let h = half_dlat.sin().powi(2) + lat1.cos() * lat2.cos() * half_dlng.sin().powi(2);
+    Ok(Value::Float(2.0 * EARTH_RADIUS * h.sqrt().min(1.0).asin()))
+}
+
+/// Returns whether a point is inside a polygon, given as a list of at least
+/// three vertices, by casting a ray along its latitude. Edges are straight
+/// lines between latitudes and longitudes, and polygons can't cross the
+/// antimeridian. A point on an edge may be either inside or outside.
+///
+/// # Examples
+/// ```cel
+/// geo.inPolygon(geo.point(1, 1), [geo.point(0, 0), geo.point(0, 2), geo.point(2, 2), geo.point(2, 0)])
+/// ```
+pub fn in_polygon(ftx: &FunctionContext) -> ResolveResult {
+    let [point, polygon] = arguments(ftx)?;
+    let (lat, lng) = coordinates(ftx, &point)?;
+    let Value::List(vertices) = polygon else {
+        return Err(ftx.error(format!(
+            "expected a polygon, a list of points, got {}",
+            polygon.type_of()
+        )));
+    };
+    if vertices.len() < 3 {
+        return Err(ftx.error(format!(
+            "a polygon needs at least 3 vertices, got {}",
+            vertices.len()
+        )));
+    }
+    let vertices = vertices
+        .iter()
+        .map(|vertex| coordinates(ftx, vertex))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut inside = false;
+    let mut previous = vertices[vertices.len() - 1];
+    for &(lat1, lng1) in &vertices {
+        let (lat2, lng2) = previous;
+        if (lat1 > lat) != (lat2 > lat) && lng < (lng2 - lng1) * (lat - lat1) / (lat2 - lat1) + lng1
+        {
+            inside = !inside;
+        }
+        previous = (lat1, lng1);
+    }
+    Ok(Value::Bool(inside))
+}
@@ -0,0 +1,223 @@
+//! The built-in `hash` extension, loaded with `Context(extensions=["hash"])`,
+//! for signature checks and consistent bucketing: `hash.sha256(data)`,
+//! `hash.md5(data)`, `hash.fnv(data)` and `hash.hmac(key, data)`.
+//!
+//! Data is bytes or a string, which is hashed as UTF-8. Digests are bytes, or a
+//! lower case hex string given `'hex'` as a last argument. The functions are in
+//! the `hash` namespace, see [`crate::namespaces`].
+use crate::functions::Described;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// The name the extension is loaded by, and the namespace of its functions
+pub const NAME: &str = "hash";
+
+/// The functions of the extension, with their signature and what they do, for
+/// `Context.functions_info`
+pub const FUNCTIONS: &[Described] = &[
+    (
+        "hash.sha256",
+        "hash.sha256(data, encoding='bytes') -> bytes | string",
+        "The SHA-256 digest of bytes or a string, as bytes or a hex string",
+    ),
+    (
+        "hash.md5",
+        "hash.md5(data, encoding='bytes') -> bytes | string",
+        "The MD5 digest of bytes or a string, as bytes or a hex string",
+    ),
+    (
+        "hash.fnv",
+        "hash.fnv(data) -> uint",
+        "The 64-bit FNV-1a hash of bytes or a string",
+    ),
+    (
+        "hash.hmac",
+        "hash.hmac(key, data, encoding='bytes') -> bytes | string",
+        "The HMAC-SHA256 of bytes or a string with a key, as bytes or a hex string",
+    ),
+];
+
+/// Adds the functions of the extension to an environment
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function("hash.sha256", sha256);
+    environment.add_function("hash.md5", md5);
+    environment.add_function("hash.fnv", fnv);
+    environment.add_function("hash.hmac", hmac);
+}
+
+/// The `N` pieces of data a function is called with, followed by whether its
+/// digest is wanted in hex, if `encoded`
+fn arguments<const N: usize>(
+    ftx: &FunctionContext,
+    encoded: bool,
+) -> Result<([Vec<u8>; N], bool), ExecutionError> {
+    let count = ftx.args.len();
+    if count != N && !(encoded && count == N + 1) {
+        return Err(ExecutionError::invalid_argument_count(N, count));
+    }
+    let values = ftx
+        .args
+        .iter()
+        .map(|arg| ftx.ptx.resolve(arg))
+        .collect::<Result<Vec<_>, _>>()?;
+    let hex = match values.get(N) {
+        None => false,
+        Some(Value::String(encoding)) => match encoding.as_str() {
+            "bytes" => false,
+            "hex" => true,
+            other => {
+                return Err(ftx.error(format!(
+                    "unknown encoding '{}', expected bytes or hex",
+                    other
+                )))
+            }
+        },
+        Some(other) => {
+            return Err(ftx.error(format!("expected an encoding, got {}", other.type_of())))
+        }
+    };
+    let data = values
+        .into_iter()
+        .take(N)
+        .map(|value| match value {
+            Value::Bytes(bytes) => Ok(bytes.to_vec()),
+            Value::String(string) => Ok(string.as_bytes().to_vec()),
+            other => Err(ftx.error(format!(
+                "expected bytes or a string, got {}",
+                other.type_of()
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((data.try_into().expect("checked the count"), hex))
+}
+
+/// A digest as bytes, or as a lower case hex string if `hex`
+fn digest(bytes: &[u8], hex: bool) -> Value {
+    if !hex {
+        return Value::Bytes(Arc::new(bytes.to_vec()));
+    }
+    let mut encoded = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(encoded, "{:02x}", byte).expect("writing to a string can't fail");
+    }
+    Value::String(Arc::new(encoded))
+}
+
+/// Returns the SHA-256 digest of bytes or a string.
+///
+/// # Examples
+/// ```cel
+/// hash.sha256('abc', 'hex').startsWith('ba7816bf')
+/// ```
+pub fn sha256(ftx: &FunctionContext) -> ResolveResult {
+    let ([data], hex) = arguments(ftx, true)?;
+    Ok(digest(&Sha256::digest(data), hex))
+}
+
+/// Returns the MD5 digest of bytes or a string. MD5 is broken for signatures,
+/// and is only for checksums and bucketing.
+///
+/// # Examples
+/// ```cel
+/// hash.md5(b'abc', 'hex') == '900150983cd24fb0d6963f7d28e17f72'
+/// ```
+pub fn md5(ftx: &FunctionContext) -> ResolveResult {
+    let ([data], hex) = arguments(ftx, true)?;
+    Ok(digest(&md5_digest(&data), hex))
+}
+
+/// Returns the 64-bit FNV-1a hash of bytes or a string.
+///
+/// # Examples
+/// ```cel
+/// hash.fnv(user.id) % 100u < 20u
+/// ```
+pub fn fnv(ftx: &FunctionContext) -> ResolveResult {
+    let ([data], _) = arguments(ftx, false)?;
+    let hash = data.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    Ok(Value::UInt(hash))
+}
+
+/// Returns the HMAC-SHA256 of data with a key, both bytes or a string.
+///
+/// # Examples
+/// ```cel
+/// hash.hmac(secret, request.body, 'hex') == request.headers['x-signature']
+/// ```
+pub fn hmac(ftx: &FunctionContext) -> ResolveResult {
+    let ([key, data], hex) = arguments(ftx, true)?;
+    const BLOCK: usize = 64;
+    let mut block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&Sha256::digest(&key));
+    } else {
+        block[..key.len()].copy_from_slice(&key);
+    }
+    let padded = |pad: u8| block.iter().map(move |byte| byte ^ pad).collect::<Vec<_>>();
+    let inner = Sha256::new()
+        .chain_update(padded(0x36))
+        .chain_update(data)
+        .finalize();
+    let outer = Sha256::new()
+        .chain_update(padded(0x5c))
+        .chain_update(inner)
+        .finalize();
+    Ok(digest(&outer, hex))
+}
+
+/// The MD5 digest of `data`, as in RFC 1321
+fn md5_digest(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    // The integer parts of the sines of 1 to 64 radians, scaled by 2^32
+    let constants: Vec<u32> = (1..=64)
+        .map(|i: i32| (f64::from(i).sin().abs() * 4_294_967_296.0) as u32)
+        .collect();
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    for chunk in message.chunks_exact(64) {
+        let words: Vec<u32> = chunk
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().expect("a word is 4 bytes")))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[i]);
+            (a, d, c) = (d, c, b);
+            b = b.wrapping_add(rotated);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 16];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
@@ -0,0 +1,162 @@
+//! The `json.decode(string)` and `json.encode(value)` builtins, for JSON
+//! encoded fields of event payloads.
+//!
+//! As the JSON is often untrusted, both are limited to [`MAX_LENGTH`] bytes of
+//! JSON nested at most [`MAX_DEPTH`] deep. The functions are in the `json`
+//! namespace, see [`crate::namespaces`].
+use crate::duration;
+use crate::types;
+use cel_interpreter::objects::{Key, Map};
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use chrono::SecondsFormat;
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The longest JSON, in bytes, that is decoded or encoded
+pub const MAX_LENGTH: usize = 1024 * 1024;
+
+/// The deepest that decoded JSON may nest arrays and objects
+pub const MAX_DEPTH: usize = 64;
+
+/// The value of the single argument of a call
+fn argument(ftx: &FunctionContext) -> ResolveResult {
+    match &ftx.args[..] {
+        [arg] => ftx.ptx.resolve(arg),
+        args => Err(ExecutionError::invalid_argument_count(1, args.len())),
+    }
+}
+
+/// Returns the value of a JSON string. Integers are ints, or uints above the
+/// largest int, and other numbers are doubles.
+///
+/// # Examples
+/// ```cel
+/// json.decode(event.payload).user.id == 'u-1'
+/// ```
+pub fn decode(ftx: &FunctionContext) -> ResolveResult {
+    let text = match argument(ftx)? {
+        Value::String(text) => text,
+        Value::Bytes(bytes) => Arc::new(
+            String::from_utf8(bytes.to_vec())
+                .map_err(|_| ftx.error("JSON bytes aren't valid UTF-8"))?,
+        ),
+        other => return Err(ftx.error(format!("can't decode {} as JSON", other.type_of()))),
+    };
+    if text.len() > MAX_LENGTH {
+        return Err(ftx.error(format!(
+            "JSON is {} bytes long, the limit is {}",
+            text.len(),
+            MAX_LENGTH
+        )));
+    }
+    // Checked before parsing, as the parser recurses into nested values
+    if depth(&text) > MAX_DEPTH {
+        return Err(ftx.error(format!("JSON is nested more than {} deep", MAX_DEPTH)));
+    }
+    let json: Json =
+        serde_json::from_str(&text).map_err(|e| ftx.error(format!("invalid JSON: {}", e)))?;
+    Ok(value(json))
+}
+
+/// Returns a value as a JSON string, with the keys of maps sorted.
+///
+/// Values JSON has no type for follow the proto3 JSON mapping, so
+/// `timestamp()` and `duration()` read them back: bytes are base64, timestamps
+/// RFC 3339 in UTC (`"2024-05-06T07:08:09Z"`), durations seconds (`"1.5s"`) and
+/// infinite and NaN doubles `"Infinity"`, `"-Infinity"` and `"NaN"`.
+///
+/// # Examples
+/// ```cel
+/// json.encode({'id': user.id, 'tags': tags})
+/// ```
+pub fn encode(ftx: &FunctionContext) -> ResolveResult {
+    let value = argument(ftx)?;
+    let json = to_json(&value)
+        .map_err(|value| ftx.error(format!("can't encode {} as JSON", value.type_of())))?;
+    let text = json.to_string();
+    if text.len() > MAX_LENGTH {
+        return Err(ftx.error(format!(
+            "encoded JSON is {} bytes long, the limit is {}",
+            text.len(),
+            MAX_LENGTH
+        )));
+    }
+    Ok(Value::String(Arc::new(text)))
+}
+
+/// The JSON of a value, or the part of it that has none. The fields of values
+/// of registered types are encoded without their type.
+fn to_json(value: &Value) -> Result<Json, &Value> {
+    Ok(match value {
+        Value::List(items) => Json::Array(items.iter().map(to_json).collect::<Result<_, _>>()?),
+        Value::Map(map) => {
+            let mut fields = serde_json::Map::new();
+            for (key, field) in map.map.iter() {
+                if !types::is_hidden(map, key) {
+                    fields.insert(key.to_string(), to_json(field)?);
+                }
+            }
+            Json::Object(fields)
+        }
+        Value::Float(f) if f.is_nan() => Json::from("NaN"),
+        Value::Float(f) if f.is_infinite() => {
+            Json::from(if *f > 0.0 { "Infinity" } else { "-Infinity" })
+        }
+        Value::Timestamp(ts) => {
+            Json::from(ts.to_utc().to_rfc3339_opts(SecondsFormat::AutoSi, true))
+        }
+        Value::Duration(d) => {
+            let nanos = d.num_seconds() as i128 * 1_000_000_000 + d.subsec_nanos() as i128;
+            let sign = if nanos < 0 { "-" } else { "" };
+            Json::from(format!(
+                "{}{}s",
+                sign,
+                duration::decimal(nanos.unsigned_abs(), 9)
+            ))
+        }
+        other => other.json().map_err(|_| other)?,
+    })
+}
+
+/// How deep arrays and objects nest in JSON text, not counting brackets in
+/// strings
+fn depth(text: &str) -> usize {
+    let (mut depth, mut deepest) = (0usize, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    for byte in text.bytes() {
+        match byte {
+            _ if escaped => escaped = false,
+            b'\\' if in_string => escaped = true,
+            b'"' => in_string = !in_string,
+            b'[' | b'{' if !in_string => {
+                depth += 1;
+                deepest = deepest.max(depth);
+            }
+            b']' | b'}' if !in_string => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    deepest
+}
+
+fn value(json: Json) -> Value {
+    match json {
+        Json::Null => Value::Null,
+        Json::Bool(b) => Value::Bool(b),
+        Json::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => Value::Int(i),
+            (None, Some(u)) => Value::UInt(u),
+            _ => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Json::String(s) => Value::String(Arc::new(s)),
+        Json::Array(items) => Value::List(Arc::new(items.into_iter().map(value).collect())),
+        Json::Object(fields) => {
+            let map: HashMap<Key, Value> = fields
+                .into_iter()
+                .map(|(key, field)| (Key::String(Arc::new(key)), value(field)))
+                .collect();
+            Value::Map(Map { map: Arc::new(map) })
+        }
+    }
+}
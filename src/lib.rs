@@ -1,30 +1,123 @@
+// pyo3 0.22 macro expansions trip this lint on newer toolchains
+#![allow(clippy::useless_conversion)]
+
+mod aggregates;
+mod arithmetic;
+mod batch;
+mod bytes;
+mod cache;
+mod complete;
+mod comprehensions;
+#[cfg(feature = "conformance")]
+mod conformance;
 mod context;
+mod conversions;
+mod coverage;
+mod dataflow;
+mod datetime;
+mod diagnose;
+mod duration;
+mod error_mapper;
+mod errors;
+#[cfg(feature = "threads")]
+mod evaluator;
+mod explain;
+#[cfg(feature = "extensions")]
+mod extensions;
+mod format;
+mod functions;
+#[cfg(feature = "conformance")]
+mod fuzz;
+mod geo;
+mod hash;
+mod json;
+mod logging;
+mod mapper;
+mod memo;
+mod memory;
+mod migrate;
+#[cfg(feature = "testing")]
+mod mutants;
+mod namespaces;
+#[cfg(feature = "native-extensions")]
+pub mod native;
+mod objects;
+mod options;
+mod originals;
+mod output;
+mod plan;
+mod program;
+mod program_set;
+mod random;
+mod recover;
+mod sandbox;
+mod serialize;
+mod stats;
+#[cfg(feature = "testing")]
+mod strategies;
+mod strings;
+mod suggest;
+#[cfg(feature = "testing")]
+mod testing;
+mod timestamps;
+mod tokenize;
+mod toolkit;
+mod transform;
+mod types;
+mod unknowns;
+mod unparse;
+mod validator;
+mod value;
+mod warnings;
 
 use cel_interpreter::objects::{Key, TryIntoValue};
-use cel_interpreter::{ExecutionError, Program, Value};
-use log::{debug, info, warn};
-use pyo3::exceptions::PyValueError;
+use cel_interpreter::{ExecutionError, Value};
+use log::{debug, warn};
+use pyo3::exceptions::{PyRuntimeError, PyTimeoutError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
 
-use chrono::{DateTime, Duration as ChronoDuration, Offset, TimeZone, Utc};
-use pyo3::types::{PyBytes, PyDateTime, PyDict, PyList, PyNone, PyTuple};
-use pyo3::types::{PyDelta, PyFunction};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, Duration as ChronoDuration};
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{
+    PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyInt, PyList, PyMapping, PyMemoryView,
+    PySequence, PyString, PyTuple,
+};
 
+use std::borrow::Cow;
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
-use std::ops::Deref;
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 struct RustyCelType(Value);
 
-impl IntoPy<PyObject> for RustyCelType {
-    fn into_py(self, py: Python<'_>) -> PyObject {
+impl RustyCelType {
+    /// Convert to the equivalent Python object.
+    ///
+    /// Fails with a TypeError for values that have no Python equivalent, such as a
+    /// method selected without calling it (`[1, 2].size`).
+    fn try_into_py(self, py: Python<'_>, output: &output::OutputTypes) -> PyResult<PyObject> {
+        self.try_into_py_with(py, output, &originals::Originals::default())
+    }
+
+    /// Convert to the equivalent Python object, returning lists and maps that
+    /// were converted from the objects in `originals` as those objects
+    fn try_into_py_with(
+        self,
+        py: Python<'_>,
+        output: &output::OutputTypes,
+        originals: &originals::Originals,
+    ) -> PyResult<PyObject> {
+        if let Some(original) = originals.get(py, &self.0) {
+            return Ok(original);
+        }
         // Just use the native rust type's existing
         // IntoPy implementation
-        match self {
+        Ok(match self {
             // Primitive Types
             RustyCelType(Value::Null) => py.None(),
             RustyCelType(Value::Bool(b)) => b.into_py(py),
@@ -32,26 +125,30 @@ impl IntoPy<PyObject> for RustyCelType {
             RustyCelType(Value::UInt(u64)) => u64.into_py(py),
             RustyCelType(Value::Float(f)) => f.into_py(py),
             RustyCelType(Value::Timestamp(ts)) => {
-                debug!("Converting a fixed offset datetime to python type");
-                ts.into_py(py)
+                debug!(target: logging::CONVERSION, "Converting a fixed offset datetime to python type");
+                output.timestamp(py, ts)
             }
-            RustyCelType(Value::Duration(d)) => d.into_py(py),
+            RustyCelType(Value::Duration(d)) => output.duration(py, d),
             RustyCelType(Value::String(s)) => s.as_ref().to_string().into_py(py),
             RustyCelType(Value::List(val)) => {
                 let list = val
-                    .as_ref()
-                    .into_iter()
-                    .map(|v| RustyCelType(v.clone()).into_py(py))
-                    .collect::<Vec<PyObject>>();
-                list.into_py(py)
+                    .iter()
+                    .map(|v| RustyCelType(v.clone()).try_into_py_with(py, output, originals))
+                    .collect::<PyResult<Vec<PyObject>>>()?;
+                output.list(py, list)
             }
-            RustyCelType(Value::Bytes(val)) => PyBytes::new_bound(py, &val).into_py(py),
+            RustyCelType(Value::Bytes(val)) => output.bytes(py, val.as_slice()),
 
             RustyCelType(Value::Map(val)) => {
                 // Create a PyDict with the converted Python key and values.
                 let python_dict = PyDict::new_bound(py);
+                let registered = types::registered_type(&val);
 
-                val.map.as_ref().into_iter().for_each(|(k, v)| {
+                for (k, v) in output.map_entries(&val) {
+                    if registered.is_some() && matches!(k, Key::String(k) if **k == types::TYPE_KEY)
+                    {
+                        continue;
+                    }
                     // Key is an enum with String, Uint, Int and Bool variants. Value is any RustyCelType
                     let key = match k {
                         Key::String(s) => s.as_ref().into_py(py),
@@ -59,248 +156,1160 @@ impl IntoPy<PyObject> for RustyCelType {
                         Key::Int(i64) => i64.into_py(py),
                         Key::Bool(b) => b.into_py(py),
                     };
-                    let value = RustyCelType(v.clone()).into_py(py);
-                    python_dict
-                        .set_item(key, value)
-                        .expect("Failed to set item in Python dict");
-                });
+                    let value = RustyCelType(v.clone()).try_into_py_with(py, output, originals)?;
+                    python_dict.set_item(key, value)?;
+                }
 
-                python_dict.into()
+                match registered {
+                    Some(registered) => registered.instance(&python_dict)?,
+                    None => output.map(py, python_dict)?,
+                }
             }
 
-            // Turn everything else into a String:
-            nonprimitive => format!("{:?}", nonprimitive).into_py(py),
-        }
+            RustyCelType(Value::Function(name, None)) => types::CelType {
+                name: name.to_string(),
+            }
+            .into_py(py),
+
+            RustyCelType(Value::Function(name, Some(_))) if name.as_str() == objects::OBJECT => {
+                return Err(PyTypeError::new_err(
+                    "Python objects can't be returned from expressions, select their attributes instead",
+                ))
+            }
+
+            RustyCelType(Value::Function(name, Some(_))) => {
+                return Err(PyTypeError::new_err(format!(
+                    "Function '{}' can't be converted to a Python value, did you mean to call it?",
+                    name
+                )))
+            }
+        })
     }
 }
 
 #[derive(Debug)]
-struct RustyPyType<'a>(&'a PyAny);
+struct RustyPyType<'a, 'py>(&'a Bound<'py, PyAny>);
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum CelError {
-    ConversionError(String),
+    /// The object at `path` within the object being converted, e.g.
+    /// `['user']['tags'][3]`, couldn't be converted for the reason in `message`
+    ConversionError { path: String, message: String },
+}
+
+impl CelError {
+    fn conversion(message: impl Into<String>) -> Self {
+        CelError::ConversionError {
+            path: String::new(),
+            message: message.into(),
+        }
+    }
+
+    /// Adds the key or index of an item to the path of an error converting it
+    fn within(self, segment: impl fmt::Display) -> Self {
+        match self {
+            CelError::ConversionError { path, message } => CelError::ConversionError {
+                path: format!("{}{}", segment, path),
+                message,
+            },
+        }
+    }
 }
 
 impl fmt::Display for CelError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            CelError::ConversionError(msg) => write!(f, "Conversion Error: {}", msg),
+            CelError::ConversionError { path, message } if path.is_empty() => {
+                write!(f, "Conversion Error: {}", message)
+            }
+            CelError::ConversionError { path, message } => {
+                write!(f, "Conversion Error at {}: {}", path, message)
+            }
         }
     }
 }
 impl Error for CelError {}
 
+/// A Python error raised while inspecting an object, e.g. by a failing property
+impl From<PyErr> for CelError {
+    fn from(error: PyErr) -> Self {
+        CelError::conversion(error.to_string())
+    }
+}
+
 /// We can't implement TryIntoValue for PyAny, so we implement for our wrapper RustyPyType
-impl TryIntoValue for RustyPyType<'_> {
+impl TryIntoValue for RustyPyType<'_, '_> {
     type Error = CelError;
 
     fn try_into_value(self) -> Result<Value, Self::Error> {
-        let val = match self {
-            RustyPyType(pyobject) => {
-                if pyobject.is_none() {
-                    Ok(Value::Null)
-                } else if let Ok(value) = pyobject.extract::<bool>() {
-                    Ok(Value::Bool(value))
-                } else if let Ok(value) = pyobject.extract::<i64>() {
-                    Ok(Value::Int(value))
-                } else if let Ok(value) = pyobject.extract::<f64>() {
-                    Ok(Value::Float(value))
-                } else if let Ok(value) = pyobject.extract::<DateTime<chrono::FixedOffset>>() {
-                    Ok(Value::Timestamp(value))
-                } else if let Ok(value) = pyobject.extract::<chrono::NaiveDateTime>() {
-                    // Handle naive datetime - assuming the naive datetime is in local time
-                    let local_timezone = chrono::Local;
-                    if let Some(datetime_local) =
-                        local_timezone.from_local_datetime(&value).single()
-                    {
-                        let datetime_fixed: DateTime<chrono::FixedOffset> =
-                            datetime_local.with_timezone(&datetime_local.offset().fix());
-                        Ok(Value::Timestamp(datetime_fixed))
-                    } else {
-                        // Ambiguous or invalid local datetime
-                        Err(CelError::ConversionError(
-                            "Ambiguous or invalid local datetime".to_string(),
+        Converter::default().convert(self.0)
+    }
+}
+
+/// Converts Python objects into CEL values.
+///
+/// Dict keys are interned, so converting many dicts with the same keys (e.g. a list
+/// of records) shares one allocation per distinct key rather than one per entry.
+#[derive(Default)]
+pub struct Converter {
+    keys: HashMap<String, Arc<String>>,
+    /// Convert named tuples to maps of their fields rather than to lists
+    pub namedtuples_as_maps: bool,
+    /// Decode bytes dictionary keys as UTF-8 rather than rejecting them
+    pub decode_bytes_keys: bool,
+    /// Where to keep objects that can't be converted, which are rejected if unset
+    pub objects: Option<objects::Found>,
+    /// The attributes and items leading to the object being converted, within
+    /// the outermost one, tracked while there is somewhere to keep objects
+    path: Vec<Value>,
+    /// When set, the objects lists and maps are converted from, by their address
+    pub originals: Option<HashMap<usize, Py<PyAny>>>,
+}
+
+impl Converter {
+    fn intern(&mut self, key: &str) -> Arc<String> {
+        if let Some(interned) = self.keys.get(key) {
+            return interned.clone();
+        }
+        let interned = Arc::new(key.to_string());
+        self.keys.insert(key.to_string(), interned.clone());
+        interned
+    }
+
+    /// A named tuple and its field names, if named tuples are converted to maps
+    fn namedtuple<'py>(
+        &self,
+        pyobject: &Bound<'py, PyAny>,
+    ) -> Option<(Bound<'py, PyTuple>, Vec<String>)> {
+        if !self.namedtuples_as_maps {
+            return None;
+        }
+        let tuple = pyobject.downcast::<PyTuple>().ok()?;
+        let fields = pyobject.getattr("_fields").ok()?;
+        Some((tuple.clone(), fields.extract().ok()?))
+    }
+
+    pub fn convert(&mut self, pyobject: &Bound<'_, PyAny>) -> Result<Value, CelError> {
+        let value = self.convert_object(pyobject)?;
+        if let (Some(originals), Some(address)) = (&mut self.originals, originals::address(&value))
+        {
+            originals.insert(address, pyobject.clone().unbind());
+        }
+        Ok(value)
+    }
+
+    fn convert_object(&mut self, pyobject: &Bound<'_, PyAny>) -> Result<Value, CelError> {
+        if pyobject.is_none() {
+            Ok(Value::Null)
+        } else if let Ok(value) = pyobject.extract::<bool>() {
+            Ok(Value::Bool(value))
+        } else if let Ok(value) = pyobject.extract::<i64>() {
+            Ok(Value::Int(value))
+        } else if let Ok(value) = pyobject.extract::<f64>() {
+            Ok(Value::Float(value))
+        } else if let Ok(value) = pyobject.extract::<DateTime<chrono::FixedOffset>>() {
+            Ok(Value::Timestamp(value))
+        } else if let Ok(value) = pyobject.extract::<chrono::NaiveDateTime>() {
+            naive_timestamp(value).map(Value::Timestamp)
+        } else if let Ok(value) = pyobject.extract::<ChronoDuration>() {
+            Ok(Value::Duration(value))
+        } else if let Ok(value) = pyobject.downcast::<duration::CelDuration>() {
+            Ok(Value::Duration(value.get().duration))
+        } else if let Ok(value) = pyobject.downcast::<types::CelType>() {
+            Ok(types::type_value(&value.get().name))
+        } else if let Ok(value) = pyobject.extract::<String>() {
+            Ok(Value::String(value.into()))
+        } else if let Ok(value) = pyobject.downcast::<PyList>() {
+            self.convert_items(value.iter())
+        } else if let Some(registered) = types::registered_class(pyobject)? {
+            let mut map: HashMap<Key, Value> = HashMap::with_capacity(registered.fields.len() + 1);
+            map.insert(
+                Key::String(self.intern(types::TYPE_KEY)),
+                types::marker(&registered.name),
+            );
+            for field in &registered.fields {
+                let value = pyobject
+                    .getattr(field.as_str())
+                    .map_err(CelError::from)
+                    .and_then(|value| self.within(Value::String(Arc::new(field.clone())), &value))
+                    .map_err(|e| e.within(format!(".{}", field)))?;
+                map.insert(Key::String(self.intern(field)), value);
+            }
+            Ok(Value::Map(map.into()))
+        } else if let Some((tuple, fields)) = self.namedtuple(pyobject) {
+            let map = fields
+                .into_iter()
+                .zip(tuple.iter())
+                .map(|(field, item)| {
+                    let value = self
+                        .within(Value::String(Arc::new(field.clone())), &item)
+                        .map_err(|e| e.within(format!(".{}", field)))?;
+                    Ok((Key::String(self.intern(&field)), value))
+                })
+                .collect::<Result<HashMap<Key, Value>, CelError>>()?;
+            Ok(Value::Map(map.into()))
+        } else if let Ok(value) = pyobject.downcast::<PyTuple>() {
+            self.convert_items(value.iter())
+        } else if let Ok(value) = pyobject.downcast::<PyDict>() {
+            let mut map: HashMap<Key, Value> = HashMap::with_capacity(value.len());
+            for (py_key, value) in value.iter() {
+                let key = self.convert_key(&py_key)?;
+                let value = self
+                    .within(objects::item(&key), &value)
+                    .map_err(|e| e.within(format!("[{}]", repr(&py_key))))?;
+                map.insert(key, value);
+            }
+            Ok(Value::Map(map.into()))
+        } else if let Ok(value) = pyobject.downcast::<PyBytes>() {
+            Ok(Value::Bytes(value.as_bytes().to_vec().into()))
+        } else if let Ok(value) = pyobject.downcast::<PyByteArray>() {
+            Ok(Value::Bytes(value.to_vec().into()))
+        } else if pyobject.is_instance_of::<PyMemoryView>() {
+            let value = pyobject.call_method0("tobytes")?.extract::<Vec<u8>>()?;
+            Ok(Value::Bytes(value.into()))
+        } else if let Ok(value) = pyobject.downcast::<PyMapping>() {
+            // Any other collections.abc.Mapping, such as a ChainMap or UserDict
+            let mut map: HashMap<Key, Value> = HashMap::new();
+            for item in value.items()?.iter()? {
+                let (py_key, value) = item?.extract::<(Bound<'_, PyAny>, Bound<'_, PyAny>)>()?;
+                let key = self.convert_key(&py_key)?;
+                let value = self
+                    .within(objects::item(&key), &value)
+                    .map_err(|e| e.within(format!("[{}]", repr(&py_key))))?;
+                map.insert(key, value);
+            }
+            Ok(Value::Map(map.into()))
+        } else if pyobject.downcast::<PySequence>().is_ok() {
+            // Any other collections.abc.Sequence, such as a range, deque or array
+            let items = pyobject.iter()?.collect::<PyResult<Vec<_>>>()?;
+            self.convert_items(items.into_iter())
+        } else if let Some(found) = &mut self.objects {
+            Ok(found.keep(pyobject, &self.path))
+        } else {
+            Err(CelError::conversion(format!(
+                "Python objects of type {} can't be converted to CEL values",
+                type_name(pyobject)
+            )))
+        }
+    }
+
+    /// Converts a dictionary key, which CEL restricts to str, int, uint and bool
+    fn convert_key(&mut self, py_key: &Bound<'_, PyAny>) -> Result<Key, CelError> {
+        let restriction = "CEL map keys must be str, int or bool";
+        if let Ok(k) = py_key.downcast::<PyBool>() {
+            Ok(Key::Bool(k.is_true()))
+        } else if let Ok(k) = py_key.downcast::<PyString>() {
+            let k = k
+                .to_cow()
+                .map_err(|e| CelError::conversion(e.to_string()))?;
+            Ok(Key::String(self.intern(&k)))
+        } else if let Ok(k) = py_key.downcast::<PyInt>() {
+            if let Ok(k) = k.extract::<i64>() {
+                Ok(Key::Int(k))
+            } else if let Ok(k) = k.extract::<u64>() {
+                Ok(Key::Uint(k))
+            } else {
+                Err(CelError::conversion(format!(
+                    "int key {} is out of range, CEL map keys must fit in 64 bits",
+                    repr(py_key)
+                )))
+            }
+        } else if py_key.is_none() {
+            Err(CelError::conversion(
+                "None cannot be used as a key in dictionaries",
+            ))
+        } else if py_key.is_instance_of::<PyFloat>() {
+            Err(CelError::conversion(format!(
+                "float key {} cannot be used, {}; convert it to an int or str first",
+                repr(py_key),
+                restriction
+            )))
+        } else if py_key.is_instance(&enum_type(py_key.py())?)? {
+            // Enum members are keyed by their value, like IntEnum and StrEnum members
+            self.convert_key(&py_key.getattr("value")?)
+        } else if let Ok(k) = py_key.downcast::<PyBytes>() {
+            match self.decode_bytes_keys {
+                true => {
+                    let k = std::str::from_utf8(k.as_bytes()).map_err(|e| {
+                        CelError::conversion(format!(
+                            "bytes key {} is not valid UTF-8: {}",
+                            repr(py_key),
+                            e
                         ))
-                    }
-                } else if let Ok(value) = pyobject.extract::<ChronoDuration>() {
-                    Ok(Value::Duration(value))
-                } else if let Ok(value) = pyobject.extract::<String>() {
-                    Ok(Value::String(value.into()))
-                } else if let Ok(value) = pyobject.downcast::<PyList>() {
-                    let list = value
-                        .iter()
-                        .map(|item| RustyPyType(item).try_into_value())
-                        .collect::<Result<Vec<Value>, Self::Error>>();
-                    list.map(|v| Value::List(Arc::new(v)))
-                } else if let Ok(value) = pyobject.downcast::<PyTuple>() {
-                    let list = value
-                        .iter()
-                        .map(|item| RustyPyType(item).try_into_value())
-                        .collect::<Result<Vec<Value>, Self::Error>>();
-                    list.map(|v| Value::List(Arc::new(v)))
-                } else if let Ok(value) = pyobject.downcast::<PyDict>() {
-                    let mut map: HashMap<Key, Value> = HashMap::new();
-                    for (key, value) in value.into_iter() {
-                        let key = if key.is_none() {
-                            return Err(CelError::ConversionError(
-                                "None cannot be used as a key in dictionaries".to_string(),
-                            ));
-                        } else if let Ok(k) = key.extract::<i64>() {
-                            Key::Int(k)
-                        } else if let Ok(k) = key.extract::<u64>() {
-                            Key::Uint(k)
-                        } else if let Ok(k) = key.extract::<bool>() {
-                            Key::Bool(k)
-                        } else if let Ok(k) = key.extract::<String>() {
-                            Key::String(k.into())
-                        } else {
-                            return Err(CelError::ConversionError(
-                                "Failed to convert PyDict key to Key".to_string(),
-                            ));
-                        };
-                        if let Ok(dict_value) = RustyPyType(value).try_into_value() {
-                            map.insert(key, dict_value);
-                        } else {
-                            return Err(CelError::ConversionError(
-                                "Failed to convert PyDict value to Value".to_string(),
-                            ));
-                        }
-                    }
-                    Ok(Value::Map(map.into()))
-                } else if let Ok(value) = pyobject.extract::<Vec<u8>>() {
-                    Ok(Value::Bytes(value.into()))
-                } else {
-                    Err(CelError::ConversionError(format!(
-                        "Failed to convert Python object of type {} to Value",
-                        pyobject
-                            .get_type()
-                            .name()
-                            .map(|ps| ps.to_string())
-                            .unwrap_or("<unknown>".into())
-                    )))
+                    })?;
+                    Ok(Key::String(self.intern(k)))
                 }
+                false => Err(CelError::conversion(format!(
+                    "bytes key {} cannot be used, {}; decode it to a str first, or pass \
+                     bytes_keys=\"decode\" to the Context",
+                    repr(py_key),
+                    restriction
+                ))),
             }
-        };
-        val
+        } else {
+            Err(CelError::conversion(format!(
+                "{} key {} cannot be used, {}",
+                type_name(py_key),
+                repr(py_key),
+                restriction
+            )))
+        }
+    }
+
+    fn convert_items<'py>(
+        &mut self,
+        items: impl Iterator<Item = Bound<'py, PyAny>>,
+    ) -> Result<Value, CelError> {
+        let items = items
+            .enumerate()
+            .map(|(i, item)| {
+                self.within(objects::item(&Key::Int(i as i64)), &item)
+                    .map_err(|e| e.within(format!("[{}]", i)))
+            })
+            .collect::<Result<Vec<Value>, CelError>>()?;
+        Ok(Value::List(Arc::new(items)))
+    }
+
+    /// Converts the item or attribute at `segment` of the object being converted
+    fn within(&mut self, segment: Value, item: &Bound<'_, PyAny>) -> Result<Value, CelError> {
+        if self.objects.is_none() {
+            return self.convert(item);
+        }
+        self.path.push(segment);
+        let value = self.convert(item);
+        self.path.pop();
+        value
+    }
+}
+
+/// A naive datetime as a timestamp, assuming it is in local time
+#[cfg(feature = "local-timezone")]
+fn naive_timestamp(
+    value: chrono::NaiveDateTime,
+) -> Result<DateTime<chrono::FixedOffset>, CelError> {
+    use chrono::{Offset, TimeZone};
+    match chrono::Local.from_local_datetime(&value).single() {
+        Some(local) => Ok(local.with_timezone(&local.offset().fix())),
+        None => Err(CelError::conversion("Ambiguous or invalid local datetime")),
+    }
+}
+
+/// A naive datetime as a timestamp, assuming it is in UTC as there is no local
+/// timezone
+#[cfg(not(feature = "local-timezone"))]
+fn naive_timestamp(
+    value: chrono::NaiveDateTime,
+) -> Result<DateTime<chrono::FixedOffset>, CelError> {
+    Ok(value.and_utc().fixed_offset())
+}
+
+fn enum_type(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    static ENUM: GILOnceCell<PyObject> = GILOnceCell::new();
+    ENUM.get_or_try_init(py, || {
+        Ok::<_, PyErr>(py.import_bound("enum")?.getattr("Enum")?.unbind())
+    })
+    .map(|enum_type| enum_type.bind(py).clone())
+}
+
+fn type_name(object: &Bound<'_, PyAny>) -> String {
+    object
+        .get_type()
+        .name()
+        .map(|name| name.to_string())
+        .unwrap_or("<unknown>".into())
+}
+
+fn repr(object: &Bound<'_, PyAny>) -> String {
+    object
+        .repr()
+        .map(|repr| repr.to_string())
+        .unwrap_or("<unknown>".into())
+}
+
+/// The Python `repr()` of a Rust value, for the `__repr__` of classes
+pub(crate) fn repr_of(py: Python<'_>, value: impl ToPyObject) -> String {
+    repr(value.to_object(py).bind(py))
+}
+
+/// The outcome of evaluating an expression
+#[derive(Clone)]
+enum Outcome {
+    Value(Value),
+    /// The result depends on these attributes, which were marked as unknown
+    Unknown(Vec<String>),
+    Error(errors::EvalError),
+}
+
+/// Evaluate a CEL expression, returning its result converted to Python
+///
+/// `safe_navigation` overrides the setting of a passed in Context for this call.
+///
+/// With `on_error="return"` an `EvalError` is returned instead of raising when the
+/// expression fails to compile or evaluate.
+///
+/// Attributes listed in `unknowns` (e.g. `"request.time"`) are treated as not yet
+/// known; if the result depends on any of them an `Unknown` is returned listing them.
+///
+/// With `output="cel"` the result is returned as an opaque `cel.Value` rather than
+/// being converted to Python.
+///
+/// With `mode="strict"` only what the CEL specification allows is accepted, e.g.
+/// `timestamp()` only parses RFC 3339 strings; a `cel.Options` can be passed to
+/// choose which Python conveniences are allowed. When not given, the mode of the
+/// passed in Context is used.
+///
+/// With `max_result_size` set, a `cel.LimitExceeded` is raised rather than returning
+/// a result with more list items, map entries and bytes of strings and bytes.
+#[pyfunction(signature = (src, evaluation_context=None, safe_navigation=None, on_error="raise", unknowns=None, output="python", mode=None, max_result_size=None))]
+#[allow(clippy::too_many_arguments)]
+fn evaluate(
+    py: Python<'_>,
+    src: String,
+    evaluation_context: Option<&Bound<'_, PyAny>>,
+    safe_navigation: Option<bool>,
+    on_error: &str,
+    unknowns: Option<Vec<String>>,
+    output: &str,
+    mode: Option<&Bound<'_, PyAny>>,
+    max_result_size: Option<usize>,
+) -> PyResult<PyObject> {
+    let return_errors = parse_on_error(on_error)?;
+    let opaque = parse_output(output)?;
+    let options = resolve_mode(evaluation_context, mode)?;
+    let output = output_types(evaluation_context);
+    let outcome = evaluate_value(
+        &src,
+        evaluation_context,
+        safe_navigation,
+        unknowns,
+        options,
+        Some(py),
+    )?;
+    check_result_size(&outcome, max_result_size)?;
+    let originals = originals(evaluation_context);
+    outcome_into_py(py, outcome, return_errors, opaque, output, &originals)
+}
+
+/// The output types of a passed in Context, or the defaults
+fn output_types(evaluation_context: Option<&Bound<'_, PyAny>>) -> output::OutputTypes {
+    evaluation_context
+        .and_then(|context| context.extract::<PyRef<context::Context>>().ok())
+        .map(|context| context.output_types)
+        .unwrap_or_default()
+}
+
+/// The objects a passed in Context in round-trip mode converted its variables from
+fn originals(evaluation_context: Option<&Bound<'_, PyAny>>) -> originals::Originals {
+    evaluation_context
+        .and_then(|context| context.extract::<PyRef<context::Context>>().ok())
+        .map(|context| context.originals())
+        .unwrap_or_default()
+}
+
+/// The options of the mode passed to an evaluation, or else of a passed in Context
+fn resolve_mode(
+    evaluation_context: Option<&Bound<'_, PyAny>>,
+    mode: Option<&Bound<'_, PyAny>>,
+) -> PyResult<options::Options> {
+    match mode {
+        Some(mode) => options::Options::from_mode(mode),
+        None => Ok(evaluation_context
+            .and_then(|context| context.extract::<PyRef<context::Context>>().ok())
+            .map(|context| context.mode)
+            .unwrap_or_default()),
+    }
+}
+
+/// Returns true if errors should be returned rather than raised
+fn parse_on_error(on_error: &str) -> PyResult<bool> {
+    match on_error {
+        "raise" => Ok(false),
+        "return" => Ok(true),
+        _ => Err(PyValueError::new_err(
+            "on_error must be either 'raise' or 'return'",
+        )),
+    }
+}
+
+/// Returns true if results should be returned as opaque CEL values
+fn parse_output(output: &str) -> PyResult<bool> {
+    match output {
+        "python" => Ok(false),
+        "cel" => Ok(true),
+        _ => Err(PyValueError::new_err(
+            "output must be either 'python' or 'cel'",
+        )),
+    }
+}
+
+/// Raises a `LimitExceeded` if the value of `outcome` is larger than `max_result_size`,
+/// before any of it is converted
+fn check_result_size(outcome: &Outcome, max_result_size: Option<usize>) -> PyResult<()> {
+    match (outcome, max_result_size) {
+        (Outcome::Value(value), Some(max)) if !output::within_size(value, max) => Err(
+            errors::LimitExceeded::new_err(format!("result has more than {} items and bytes", max)),
+        ),
+        _ => Ok(()),
     }
 }
 
-/// Evaluate a CEL expression
-/// Returns a String representation of the result
-#[pyfunction(signature = (src, evaluation_context=None))]
-fn evaluate(src: String, evaluation_context: Option<&PyAny>) -> PyResult<RustyCelType> {
-    debug!("Evaluating CEL expression: {}", src);
+fn outcome_into_py(
+    py: Python<'_>,
+    outcome: Outcome,
+    return_errors: bool,
+    opaque: bool,
+    output: output::OutputTypes,
+    originals: &originals::Originals,
+) -> PyResult<PyObject> {
+    match outcome {
+        Outcome::Value(value) if opaque => Ok(value::OpaqueValue::new(value, output).into_py(py)),
+        Outcome::Value(value) => RustyCelType(value).try_into_py_with(py, &output, originals),
+        Outcome::Unknown(attributes) => Ok(unknowns::Unknown { attributes }.into_py(py)),
+        Outcome::Error(error) if return_errors => Ok(error.into_py(py)),
+        Outcome::Error(error) => Err(error.to_py_err()),
+    }
+}
 
-    let program = Program::compile(&src).map_err(|e| {
-        PyValueError::new_err(format!(
-            "Failed to compile expression '{}': {}",
-            src, e
-        ))
+/// Evaluate a CEL expression that must produce a boolean
+/// Raises a TypeError if the result is any other type
+#[pyfunction(signature = (src, evaluation_context=None, safe_navigation=None, unknowns=None, mode=None))]
+fn evaluate_predicate(
+    py: Python<'_>,
+    src: String,
+    evaluation_context: Option<&Bound<'_, PyAny>>,
+    safe_navigation: Option<bool>,
+    unknowns: Option<Vec<String>>,
+    mode: Option<&Bound<'_, PyAny>>,
+) -> PyResult<bool> {
+    let options = resolve_mode(evaluation_context, mode)?;
+    let got = match evaluate_value(
+        &src,
+        evaluation_context,
+        safe_navigation,
+        unknowns,
+        options,
+        Some(py),
+    )? {
+        Outcome::Value(Value::Bool(b)) => return Ok(b),
+        Outcome::Value(other) => types::name_of(&other).to_string(),
+        Outcome::Unknown(attributes) => format!("unknown ({})", attributes.join(", ")),
+        Outcome::Error(error) => return Err(error.to_py_err()),
+    };
+    Err(PyTypeError::new_err(format!(
+        "Expected expression '{}' to evaluate to a bool, got {}",
+        src, got
+    )))
+}
+
+/// Compiles and evaluates `src`. With `warn`, first warns in Python if the
+/// expression relies on a convenience of Python mode, as `cel.evaluate` does;
+/// the helpers that evaluate in several modes to compare them pass None.
+fn evaluate_value(
+    src: &str,
+    evaluation_context: Option<&Bound<'_, PyAny>>,
+    safe_navigation: Option<bool>,
+    unknowns: Option<Vec<String>>,
+    options: options::Options,
+    warn: Option<Python<'_>>,
+) -> PyResult<Outcome> {
+    debug!(target: logging::EVAL, "Evaluating CEL expression: {}", src);
+
+    let program = match compile(src) {
+        Ok(program) => program,
+        Err(error) => return Ok(Outcome::Error(error)),
+    };
+    if let Some(py) = warn {
+        warnings::warn(py, src, &warnings::reliances(&program, &options), &options)?;
+    }
+    execute(
+        src,
+        &program,
+        None,
+        evaluation_context,
+        safe_navigation,
+        unknowns,
+        options,
+    )
+}
+
+/// Parse a CEL expression into the AST that is executed
+fn compile(src: &str) -> Result<cel_parser::Expression, errors::EvalError> {
+    stats::compiled();
+    // The parser panics on literals it can't convert
+    if let Some((range, message)) = recover::first_invalid_literal(src) {
+        stats::failed("compile");
+        return Err(errors::EvalError::invalid_literal(
+            src,
+            range.start,
+            message,
+        ));
+    }
+    let program = cel_parser::parse(src).map_err(|e| {
+        stats::failed("compile");
+        errors::EvalError::compile(src, &e)
     })?;
+    if types::reserved_key(&program) {
+        stats::failed("compile");
+        return Err(errors::EvalError::rejected(
+            src,
+            format!(
+                "'{}' can't be a map key, values of registered types hold their type under it",
+                types::TYPE_KEY
+            ),
+        ));
+    }
+    debug!(target: logging::PARSER, "Compiled program: {:?}", program);
+    Ok(program)
+}
 
-    debug!("Compiled program: {:?}", program);
+/// Execute a compiled expression, `src` is only used in error messages
+///
+/// A `plan` built from `program` is used in place of the interpreter when no
+/// rewrites are applied to the expression for this evaluation.
+fn execute(
+    src: &str,
+    program: &cel_parser::Expression,
+    plan: Option<&plan::Plan>,
+    evaluation_context: Option<&Bound<'_, PyAny>>,
+    safe_navigation: Option<bool>,
+    unknowns: Option<Vec<String>>,
+    options: options::Options,
+) -> PyResult<Outcome> {
+    let job = Job::new(evaluation_context, safe_navigation, unknowns, options)?;
+    Ok(job.run(src, program, plan))
+}
+
+/// The interpreter context expressions are evaluated in
+type Environment = cel_interpreter::Context<'static>;
 
-    debug!("Preparing context");
+/// Builds the environment for evaluating with `options` against `variables` and
+/// Python `functions`, which are called as their `function_options` say, and
+/// whose exceptions are turned into CEL errors by `error_mapper` when there is
+/// one.
+///
+/// The internal functions of every rewrite are added too, so the environment
+/// can be reused by evaluations that rewrite the expression differently.
+fn build_environment(
+    variables: &HashMap<String, Value>,
+    functions: &HashMap<String, Arc<Py<PyAny>>>,
+    function_options: &HashMap<String, context::FunctionOptions>,
+    error_mapper: Option<&Arc<Py<PyAny>>>,
+    objects: Option<objects::Reader>,
+    extensions: &[String],
+    options: &options::Options,
+) -> Environment {
+    debug!(target: logging::EVAL, "Preparing context");
     let mut environment = cel_interpreter::Context::default();
-    let mut ctx = context::Context::new(None, None)?;
-
-    // Custom Rust functions can also be added to the environment...
-    //environment.add_function("add", |a: i64, b: i64| a + b);
-
-    // Process the evaluation context if provided
-    if let Some(evaluation_context) = evaluation_context {
-        // Attempt to extract directly as a Context object
-        if let Ok(py_context_ref) = evaluation_context.extract::<PyRef<context::Context>>() {
-            // Clone variables and functions into our local Context
-            ctx.variables = py_context_ref.variables.clone();
-            ctx.functions = py_context_ref.functions.clone();
-        } else if let Ok(py_dict) = evaluation_context.extract::<&PyDict>() {
-            // User passed in a dict - let's process variables and functions from the dict
-            ctx.update(&py_dict)?;
-        } else {
-            return Err(PyValueError::new_err(
-                "evaluation_context must be a Context object or a dict",
-            ));
-        };
+    functions::register(&mut environment, options);
+    types::register(&mut environment);
+    options::register(&mut environment);
+    bytes::register(&mut environment);
+    arithmetic::register(&mut environment);
+    environment.add_function(unknowns::UNKNOWN, unknowns::unknown);
+    environment.add_function(unknowns::AND, unknowns::and);
+    environment.add_function(unknowns::OR, unknowns::or);
+    environment.add_function(transform::SAFE_SELECT, transform::safe_select);
+    environment.add_function(sandbox::TICK, sandbox::tick);
+    environment.add_function(sandbox::DEADLINE, sandbox::deadline);
+    environment.add_function(coverage::COVER, coverage::cover);
+    #[cfg(feature = "native-extensions")]
+    native::register(&mut environment);
+    for extension in extensions {
+        match extension.as_str() {
+            toolkit::NAME => toolkit::register(&mut environment),
+            geo::NAME => geo::register(&mut environment),
+            hash::NAME => hash::register(&mut environment),
+            strings::NAME => strings::register(&mut environment),
+            format::NAME => format::register(&mut environment),
+            datetime::NAME => datetime::register(&mut environment),
+            _ => {}
+        }
+    }
+    if let Some(reader) = objects {
+        environment.add_function(objects::ATTRIBUTE, {
+            let reader = reader.clone();
+            move |ftx: &cel_interpreter::FunctionContext| reader.attribute(ftx)
+        });
+        environment.add_function(
+            objects::OPERATOR,
+            move |ftx: &cel_interpreter::FunctionContext| reader.operator(ftx),
+        );
+    }
+
+    // Add any variables from the passed in Python context
+    for (name, value) in variables {
+        environment.add_variable_from_value(name.clone(), value.clone());
+    }
+
+    // Add functions. They are shared with the Context rather than cloned, so
+    // that it accounts for every reference to them when the garbage collector
+    // traverses it, and the interpreter clones a function each time it is
+    // called, which for a `Py` would need the GIL
+    let mut batched = HashMap::new();
+    for (name, py_function) in functions {
+        let function = Arc::new(PythonFunction {
+            name: name.clone(),
+            function: py_function.clone(),
+            options: function_options.get(name).copied().unwrap_or_default(),
+            error_mapper: error_mapper.cloned(),
+        });
+        if function.options.batch {
+            batched.insert(name.clone(), function.clone());
+        }
+        environment.add_function(
+            name,
+            move |ftx: &cel_interpreter::FunctionContext| -> cel_interpreter::ResolveResult {
+                // Arguments are resolved in order before the function is called
+                let args = ftx
+                    .args
+                    .iter()
+                    .map(|arg| ftx.ptx.resolve(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                memo::call(&function.name, args, |args| match function.options.batch {
+                    true => batch::call_one(&function, args),
+                    false => function.call(args),
+                })
+            },
+        );
+    }
+    if !batched.is_empty() {
+        batch::register(&mut environment, batched);
+    }
+    environment
+}
+
+/// A Python function added to a context, as the environment calls it
+pub(crate) struct PythonFunction {
+    pub name: String,
+    function: Arc<Py<PyAny>>,
+    pub options: context::FunctionOptions,
+    /// Turns the exceptions it raises into CEL errors, see [`error_mapper`]
+    error_mapper: Option<Arc<Py<PyAny>>>,
+}
 
-        // Add any variables from the passed in Python context
-        for (name, value) in &ctx.variables {
-            environment
-                .add_variable(name.clone(), value.clone())
+impl PythonFunction {
+    /// Calls the function with `args` converted to Python objects, and converts
+    /// its result back
+    pub fn call(&self, args: &[Value]) -> cel_interpreter::ResolveResult {
+        let name = &self.name;
+        Python::with_gil(|py| {
+            // Convert the arguments to PyObjects
+            let mut py_args = Vec::new();
+            for arg_value in args {
+                let py_arg = RustyCelType(arg_value.clone())
+                    .try_into_py(py, &output::OutputTypes::default())
+                    .map_err(|e| ExecutionError::FunctionError {
+                        function: name.clone(),
+                        message: e.to_string(),
+                    })?;
+                py_args.push(py_arg);
+            }
+            let py_args = PyTuple::new_bound(py, py_args);
+
+            // Call the Python function
+            debug!(target: logging::FUNCTIONS, "Calling Python function '{}'", name);
+            let py_result = call_function(py, name, &self.function, py_args, self.options.timeout)
                 .map_err(|e| {
-                    PyValueError::new_err(format!("Failed to add variable '{}': {}", name, e))
+                    debug!(target: logging::FUNCTIONS, "Python function '{}' raised {}", name, e);
+                    match &self.error_mapper {
+                        Some(mapper) => error_mapper::map(py, mapper, name, e),
+                        None => ExecutionError::FunctionError {
+                            function: name.clone(),
+                            message: e.to_string(),
+                        },
+                    }
                 })?;
+            // Convert the PyObject to &Bound<PyAny>
+            let py_result_ref = py_result.bind(py);
+
+            // Convert the result back to Value
+            let value = RustyPyType(py_result_ref).try_into_value().map_err(|e| {
+                ExecutionError::FunctionError {
+                    function: name.clone(),
+                    message: format!("Error calling function '{}': {}", name, e),
+                }
+            })?;
+            Ok(value)
+        })
+    }
+}
+
+/// Calls a Python function, on a thread of its own when it has a `timeout`, so
+/// that the evaluation can carry on without it once the timeout has passed
+fn call_function(
+    py: Python<'_>,
+    name: &str,
+    function: &Arc<Py<PyAny>>,
+    args: Bound<'_, PyTuple>,
+    timeout: Option<Duration>,
+) -> PyResult<PyObject> {
+    let Some(timeout) = timeout else {
+        return function.call1(py, args);
+    };
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let function = function.clone();
+    let args = args.unbind();
+    std::thread::Builder::new()
+        .name(format!("cel function {}", name))
+        .spawn(move || {
+            let result = Python::with_gil(|py| function.call1(py, args.bind(py)));
+            // The evaluation may have stopped waiting for the result
+            let _ = sender.send(result);
+        })
+        .map_err(|e| PyRuntimeError::new_err(format!("failed to start a thread: {}", e)))?;
+    match py.allow_threads(move || receiver.recv_timeout(timeout)) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(PyTimeoutError::new_err(format!(
+            "function '{}' didn't return within {} seconds",
+            name,
+            timeout.as_secs_f64()
+        ))),
+        Err(RecvTimeoutError::Disconnected) => Err(PyRuntimeError::new_err(format!(
+            "function '{}' stopped without returning",
+            name
+        ))),
+    }
+}
+
+/// How deeply evaluations may be nested on a thread, by Python functions that
+/// evaluate expressions themselves. Each takes its share of the thread's stack,
+/// so a function that keeps evaluating itself fails rather than overflowing it.
+const MAX_NESTED_EVALUATIONS: usize = 32;
+
+thread_local! {
+    /// The evaluations running on the thread, each nested in the one before
+    static NESTED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts an evaluation as running on the thread until it is dropped
+struct Nested;
+
+impl Nested {
+    fn enter() -> Option<Nested> {
+        NESTED.with(|nested| {
+            let depth = nested.get();
+            (depth < MAX_NESTED_EVALUATIONS).then(|| {
+                nested.set(depth + 1);
+                Nested
+            })
+        })
+    }
+}
+
+impl Drop for Nested {
+    fn drop(&mut self) {
+        NESTED.with(|nested| nested.set(nested.get() - 1));
+    }
+}
+
+/// What an evaluation needs from its Python arguments, converted so that it can
+/// be run without them, e.g. on another thread.
+#[derive(Clone)]
+struct Job {
+    environment: Arc<Environment>,
+    /// Names of the Python functions in the environment
+    functions: Vec<String>,
+    /// Whether the environment holds Python objects whose attributes are read
+    /// while evaluating
+    objects: bool,
+    safe_navigation: bool,
+    /// Whether Python functions are called once for each set of arguments
+    memoize: bool,
+    /// The functions of loaded extensions in a namespace, that calls like
+    /// `geo.point(...)` are to
+    namespaced: Vec<&'static str>,
+    unknowns: Option<Vec<String>>,
+    options: options::Options,
+}
+
+impl Job {
+    fn new(
+        evaluation_context: Option<&Bound<'_, PyAny>>,
+        safe_navigation: Option<bool>,
+        unknowns: Option<Vec<String>>,
+        options: options::Options,
+    ) -> PyResult<Self> {
+        let job = move |context: &context::Context| {
+            Job::for_context(context, safe_navigation, unknowns, options)
+        };
+
+        // Process the evaluation context if provided
+        let mut ctx = context::Context::default();
+        if let Some(evaluation_context) = evaluation_context {
+            // A Context keeps the environment built from it for the next evaluation
+            if let Ok(py_context_ref) = evaluation_context.extract::<PyRef<context::Context>>() {
+                return Ok(job(&py_context_ref));
+            } else if let Ok(py_dict) = evaluation_context.downcast::<PyDict>() {
+                // User passed in a dict - let's process variables and functions from the dict
+                ctx.update(Some(py_dict), false, None)?;
+            } else {
+                return Err(PyValueError::new_err(
+                    "evaluation_context must be a Context object or a dict",
+                ));
+            };
+        }
+        Ok(job(&ctx))
+    }
+
+    /// An evaluation against an already converted `context`
+    fn for_context(
+        context: &context::Context,
+        safe_navigation: Option<bool>,
+        unknowns: Option<Vec<String>>,
+        options: options::Options,
+    ) -> Self {
+        Job {
+            environment: context.environment(&options),
+            functions: context.function_names(),
+            objects: context.holds_objects(),
+            safe_navigation: safe_navigation
+                .unwrap_or(context.safe_navigation || options.safe_navigation),
+            memoize: context.memoize,
+            namespaced: context.namespaced_functions(),
+            unknowns,
+            options,
+        }
+    }
+
+    /// Runs the evaluation, only taking the GIL to call Python functions
+    fn run(
+        self,
+        src: &str,
+        program: &cel_parser::Expression,
+        plan: Option<&plan::Plan>,
+    ) -> Outcome {
+        let start = Instant::now();
+        let outcome = self.resolve(src, program, plan);
+        stats::evaluated(start.elapsed());
+        outcome
+    }
+
+    fn resolve(
+        self,
+        src: &str,
+        program: &cel_parser::Expression,
+        plan: Option<&plan::Plan>,
+    ) -> Outcome {
+        let Some(_nested) = Nested::enter() else {
+            stats::failed("nesting");
+            return Outcome::Error(errors::EvalError::nested(src, MAX_NESTED_EVALUATIONS));
+        };
+        let options = self.options;
+        if let Err(message) = options::validate(program, &options) {
+            stats::failed("rejected");
+            return Outcome::Error(options::rejection(src, message));
         }
+        let environment = &*self.environment;
+        let _calls = memo::Scope::new(self.memoize);
+        let fatal = error_mapper::Scope::enter();
+        let _generator = random::Scope::enter();
 
-        // Add functions
-        let collected_functions: Vec<(String, Py<PyAny>)> = Python::with_gil(|py| {
-            ctx.functions
+        // Plans evaluate macros themselves, so can't be used if a Python function replaces one
+        let overrides_macro = self
+            .functions
+            .iter()
+            .any(|name| plan::MACROS.contains(&name.as_str()));
+        // Python functions and methods of registered types hold the GIL, and
+        // the evaluation's random numbers are drawn in order on its thread
+        let methods = types::methods();
+        let draws = random::DRAWS.map(String::from);
+        let parallel = plan.is_some_and(|plan| {
+            !self
+                .functions
                 .iter()
-                .map(|(name, py_function)| (name.clone(), py_function.clone_ref(py)))
-                .collect()
+                .chain(&methods)
+                .chain(&draws)
+                .any(|name| plan.calls(name))
         });
 
-        for (name, py_function) in collected_functions.into_iter() {
-            environment.add_function(
-                &name.clone(),
-                move |ftx: &cel_interpreter::FunctionContext| -> cel_interpreter::ResolveResult {
-                    Python::with_gil(|py| {
-                        // Convert arguments from Expression in ftx.args to PyObjects
-                        let mut py_args = Vec::new();
-                        for arg_expr in &ftx.args {
-                            let arg_value = ftx.ptx.resolve(arg_expr)?;
-                            let py_arg = RustyCelType(arg_value).into_py(py);
-                            py_args.push(py_arg);
-                        }
-                        let py_args = PyTuple::new_bound(py, py_args);
-
-                        // Call the Python function
-                        let py_result = py_function.call1(py, py_args).map_err(|e| {
-                            ExecutionError::FunctionError {
-                                function: name.clone(),
-                                message: e.to_string(),
-                            }
-                        })?;
-                        // Convert the PyObject to &PyAny
-                        let py_result_ref = py_result.as_ref(py);
-
-                        // Convert the result back to Value
-                        let value = RustyPyType(py_result_ref).try_into_value().map_err(|e| {
-                            ExecutionError::FunctionError {
-                                function: name.clone(),
-                                message: format!("Error calling function '{}': {}", name, e),
-                            }
-                        })?;
-                        Ok(value)
-                    })
-                },
-            );
+        // Rewrites are applied to a copy so a compiled program can be reused
+        let mut program = Cow::Borrowed(program);
+        if let Some(enforced) = options::enforce(&program, &options) {
+            program = Cow::Owned(enforced);
         }
-    }
-
-    let result = program.execute(&environment);
-    match result {
-        Err(error) => {
-            warn!("An error occurred during execution");
-            warn!("Execution error: {:?}", error);
-            // errors
-            //     .into_iter()
-            //     .for_each(|e| println!("Execution error: {:?}", e));
-            Err(PyValueError::new_err("Execution Error"))
+        if let Some(unknowns) = self.unknowns {
+            program = Cow::Owned(unknowns::mark_unknowns(&program, &unknowns));
+        }
+        if let Some(rewritten) = namespaces::rewrite(&program, &self.namespaced) {
+            program = Cow::Owned(rewritten);
+        }
+        if let Some(constructed) = types::rewrite(&program) {
+            program = Cow::Owned(constructed);
         }
+        if self.objects {
+            program = Cow::Owned(objects::rewrite(&program, self.safe_navigation));
+        }
+        if self.safe_navigation {
+            program = Cow::Owned(transform::safe_navigation(&program));
+        }
+
+        // Only the interpreter's comprehensions call batch functions for all items at once
+        let batches = plan.is_some_and(|plan| {
+            batch::names(environment)
+                .iter()
+                .any(|name| plan.calls(name))
+        });
+
+        let result = match (plan, &program) {
+            (Some(plan), Cow::Borrowed(_)) if !overrides_macro && !batches => {
+                plan.run(environment, parallel, options.error_absorption)
+            }
+            // Plans implement error absorption, checked arithmetic and the bytes
+            // operators themselves
+            _ => {
+                let mut program = program;
+                if options.error_absorption {
+                    if let Some(absorbing) = unknowns::absorb_errors(&program) {
+                        program = Cow::Owned(absorbing);
+                    }
+                }
+                if let Some(indexing) = types::rewrite_indexes(&program) {
+                    program = Cow::Owned(indexing);
+                }
+                if let Some(checked) = arithmetic::rewrite(&program) {
+                    program = Cow::Owned(checked);
+                }
+                if let Some(rewritten) = bytes::rewrite(&program) {
+                    program = Cow::Owned(rewritten);
+                }
+                environment.resolve(&program)
+            }
+        };
+        // An error the error mapper made fatal fails the evaluation even if it was absorbed
+        let result = match fatal.take() {
+            Some(error) => Err(error),
+            None => result,
+        };
+        match result {
+            Err(error) => {
+                if let Some(attributes) = unknowns::unknown_attributes(&error) {
+                    debug!(target: logging::EVAL, "Result depends on unknown attributes: {:?}", attributes);
+                    return Outcome::Unknown(attributes);
+                }
+                warn!(target: logging::EVAL, "An error occurred during execution");
+                warn!(target: logging::EVAL, "Execution error: {:?}", error);
+                stats::failed(stats::error_type(&error));
+                Outcome::Error(errors::EvalError::execution(src, &error))
+            }
 
-        Ok(value) => return Ok(RustyCelType(value)),
+            Ok(value) => Outcome::Value(value),
+        }
     }
 }
 
 /// A Python module implemented in Rust.
 #[pymodule]
-fn cel<'py>(py: Python<'py>, m: &PyModule) -> PyResult<()> {
-    pyo3_log::init();
+fn cel(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    logging::init();
 
     m.add_function(wrap_pyfunction!(evaluate, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate_predicate, m)?)?;
+    m.add_function(wrap_pyfunction!(dataflow::plan, m)?)?;
+    m.add_function(wrap_pyfunction!(program_set::compile_many, m)?)?;
+    m.add_function(wrap_pyfunction!(complete::complete, m)?)?;
+    m.add_function(wrap_pyfunction!(tokenize::tokenize, m)?)?;
+    m.add_function(wrap_pyfunction!(recover::parse_lenient, m)?)?;
+    m.add_function(wrap_pyfunction!(diagnose::diagnose, m)?)?;
+    m.add_function(wrap_pyfunction!(types::register_type, m)?)?;
+    m.add_function(wrap_pyfunction!(context::register_global_function, m)?)?;
+    m.add_function(wrap_pyfunction!(context::unregister_global_function, m)?)?;
+    m.add_function(wrap_pyfunction!(migrate::migrate, m)?)?;
+    m.add_function(wrap_pyfunction!(explain::explain, m)?)?;
+    m.add_function(wrap_pyfunction!(suggest::suggest_inputs, m)?)?;
+    m.add_function(wrap_pyfunction!(logging::configure_logging, m)?)?;
+    m.add_function(wrap_pyfunction!(stats::stats, m)?)?;
 
     m.add_class::<context::Context>()?;
-    Ok(())
+    m.add_class::<program::Program>()?;
+    m.add_class::<program_set::ProgramSet>()?;
+    #[cfg(feature = "threads")]
+    m.add_class::<evaluator::Evaluator>()?;
+    m.add_class::<dataflow::Dataflow>()?;
+    m.add_class::<mapper::Mapper>()?;
+    m.add_class::<validator::Validator>()?;
+    m.add_class::<validator::Violation>()?;
+    m.add_class::<recover::Diagnostic>()?;
+    m.add_class::<errors::EvalError>()?;
+    m.add_class::<unknowns::Unknown>()?;
+    m.add_class::<value::OpaqueValue>()?;
+    m.add_class::<duration::CelDuration>()?;
+    m.add_class::<types::CelType>()?;
+    m.add_class::<options::Options>()?;
+    m.add_class::<migrate::Migration>()?;
+    m.add_class::<explain::Explanation>()?;
+    m.add_class::<coverage::Coverage>()?;
+    errors::register(m)?;
+    warnings::register(m)?;
+    sandbox::register(m)?;
+    register_features(m)
+}
+
+/// The optional features of the crate, and whether the module was built with
+/// each of them
+const FEATURES: [(&str, bool); 6] = [
+    ("threads", cfg!(feature = "threads")),
+    ("local-timezone", cfg!(feature = "local-timezone")),
+    ("extensions", cfg!(feature = "extensions")),
+    ("conformance", cfg!(feature = "conformance")),
+    ("testing", cfg!(feature = "testing")),
+    ("native-extensions", cfg!(feature = "native-extensions")),
+];
+
+/// Adds the submodules of the features the module was built with, and
+/// `cel.FEATURES` listing them
+fn register_features(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    #[cfg(feature = "conformance")]
+    {
+        conformance::register(m)?;
+        fuzz::register(m)?;
+    }
+    #[cfg(not(feature = "conformance"))]
+    {
+        unavailable(m, "conformance", "conformance")?;
+        unavailable(m, "fuzz", "conformance")?;
+    }
+    #[cfg(feature = "testing")]
+    testing::register(m)?;
+    // The pytest plugin entry point still has a module to import
+    #[cfg(not(feature = "testing"))]
+    unavailable(m, "testing", "testing")?;
+
+    let enabled = FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    m.add("FEATURES", PyTuple::new_bound(m.py(), enabled))
+}
+
+/// Adds an empty `cel.{name}` in place of a submodule that `feature` provides
+#[cfg(not(all(feature = "conformance", feature = "testing")))]
+fn unavailable(m: &Bound<'_, PyModule>, name: &str, feature: &str) -> PyResult<()> {
+    let py = m.py();
+    let module = PyModule::new_bound(py, name)?;
+    module.setattr(
+        "__doc__",
+        format!(
+            "Not available, as cel was built without the '{}' feature.",
+            feature
+        ),
+    )?;
+    m.add_submodule(&module)?;
+    py.import_bound("sys")?
+        .getattr("modules")?
+        .set_item(format!("cel.{}", name), module)
+}
+
+/// The functions of the installed extension `name`, see `Context(extensions=...)`
+#[cfg(feature = "extensions")]
+fn load_extension<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyDict>> {
+    extensions::load(py, name)
+}
+
+#[cfg(not(feature = "extensions"))]
+fn load_extension<'py>(_py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyDict>> {
+    Err(PyValueError::new_err(format!(
+        "can't load the extension '{}', as cel was built without the 'extensions' feature",
+        name
+    )))
 }
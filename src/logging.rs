@@ -0,0 +1,178 @@
+//! `cel.configure_logging`, which sets the levels of the Python loggers the
+//! crate logs to, per subsystem, and can format their records as JSON.
+//!
+//! Records are passed to Python's `logging` by `pyo3_log`, which logs a record
+//! with the target `cel::eval` to the logger `cel.eval` and caches the levels
+//! of loggers, so the cache is reset whenever they are changed here. Levels
+//! changed with `logging` directly take effect once `configure_logging` is
+//! next called.
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3_log::ResetHandle;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Target of records about parsing expressions
+pub const PARSER: &str = "cel::parser";
+/// Target of records about converting values between Python and CEL
+pub const CONVERSION: &str = "cel::conversion";
+/// Target of records about calling Python functions from expressions
+pub const FUNCTIONS: &str = "cel::functions";
+/// Target of records about evaluating expressions
+pub const EVAL: &str = "cel::eval";
+
+/// The subsystems that have their own logger under `cel`
+const SUBSYSTEMS: [&str; 4] = ["parser", "conversion", "functions", "eval"];
+
+/// The name of the handler `json=True` adds to the `cel` logger
+const JSON_HANDLER: &str = "cel.json";
+
+static RESET: OnceLock<ResetHandle> = OnceLock::new();
+
+/// Passes the records logged by the crate to Python's `logging`, unless a
+/// crate this one is built into has set up a logger already
+pub fn init() {
+    if let Ok(handle) = pyo3_log::try_init() {
+        let _ = RESET.set(handle);
+    }
+}
+
+/// Formats log records as single line JSON objects
+#[pyclass(frozen, module = "cel")]
+struct JsonFormatter;
+
+#[pymethods]
+impl JsonFormatter {
+    fn format(&self, record: &Bound<'_, PyAny>) -> PyResult<String> {
+        let py = record.py();
+        let fields = PyDict::new_bound(py);
+        fields.set_item("time", record.getattr("created")?)?;
+        fields.set_item("level", record.getattr("levelname")?)?;
+        fields.set_item("logger", record.getattr("name")?)?;
+        fields.set_item("message", record.call_method0("getMessage")?)?;
+        py.import_bound("json")?
+            .call_method1("dumps", (fields,))?
+            .extract()
+    }
+}
+
+/// Configures the loggers of the `cel` module.
+///
+/// `level` sets the level of `target`, which is `"cel"` unless given, or one
+/// of its subsystems: `"cel.parser"`, `"cel.conversion"`, `"cel.functions"`
+/// (calls to Python functions) or `"cel.eval"`. `levels` sets several at once,
+/// e.g. `{"parser": "warning", "eval": "debug"}`. Levels are names like
+/// `"debug"` or numbers, and `"notset"` makes a logger use the level of its
+/// parent again.
+///
+/// `json=True` adds a handler to the `cel` logger that writes its records as
+/// JSON lines to `stream`, or stderr, and stops them propagating to the root
+/// logger. `json=False` removes it.
+#[pyfunction]
+#[pyo3(signature = (level=None, target=None, json=None, levels=None, stream=None))]
+pub fn configure_logging(
+    py: Python<'_>,
+    level: Option<&Bound<'_, PyAny>>,
+    target: Option<&str>,
+    json: Option<bool>,
+    levels: Option<HashMap<String, Bound<'_, PyAny>>>,
+    stream: Option<&Bound<'_, PyAny>>,
+) -> PyResult<()> {
+    if target.is_some() && level.is_none() {
+        return Err(PyValueError::new_err("A target needs a level to set"));
+    }
+    if stream.is_some() && json != Some(true) {
+        return Err(PyValueError::new_err(
+            "A stream is only used with json=True",
+        ));
+    }
+
+    // Everything is checked before any logger is changed
+    let mut updates = Vec::new();
+    if let Some(level) = level {
+        updates.push((logger_name(target.unwrap_or("cel"))?, parse_level(level)?));
+    }
+    for (subsystem, level) in levels.iter().flatten() {
+        updates.push((logger_name(subsystem)?, parse_level(level)?));
+    }
+
+    let logging = py.import_bound("logging")?;
+    for (name, level) in updates {
+        logging
+            .call_method1("getLogger", (name,))?
+            .call_method1("setLevel", (level,))?;
+    }
+    if let Some(json) = json {
+        let logger = logging.call_method1("getLogger", ("cel",))?;
+        // A copy, as the list is changed while removing from it
+        let handlers: Vec<Bound<'_, PyAny>> = logger.getattr("handlers")?.extract()?;
+        for handler in handlers {
+            if handler
+                .getattr("name")?
+                .extract::<Option<String>>()?
+                .as_deref()
+                == Some(JSON_HANDLER)
+            {
+                logger.call_method1("removeHandler", (handler,))?;
+            }
+        }
+        if json {
+            let handler = logging.call_method1("StreamHandler", (stream,))?;
+            handler.setattr("name", JSON_HANDLER)?;
+            handler.call_method1("setFormatter", (Py::new(py, JsonFormatter)?,))?;
+            logger.call_method1("addHandler", (handler,))?;
+        }
+        logger.setattr("propagate", !json)?;
+    }
+    if let Some(handle) = RESET.get() {
+        handle.reset();
+    }
+    Ok(())
+}
+
+/// The logger of `target`, which may leave out the `cel.` prefix of a
+/// subsystem
+fn logger_name(target: &str) -> PyResult<String> {
+    let subsystem = target.strip_prefix("cel.").unwrap_or(target);
+    if target == "cel" {
+        Ok(target.to_string())
+    } else if SUBSYSTEMS.contains(&subsystem) {
+        Ok(format!("cel.{}", subsystem))
+    } else {
+        Err(PyValueError::new_err(format!(
+            "Unknown logging target '{}', expected 'cel' or one of its subsystems: {}",
+            target,
+            SUBSYSTEMS.join(", ")
+        )))
+    }
+}
+
+/// The number of a level given by name or number
+fn parse_level(level: &Bound<'_, PyAny>) -> PyResult<i64> {
+    if let Ok(name) = level.extract::<String>() {
+        return match name.to_lowercase().as_str() {
+            "notset" => Ok(0),
+            "debug" => Ok(10),
+            "info" => Ok(20),
+            "warning" => Ok(30),
+            "error" => Ok(40),
+            "critical" => Ok(50),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown logging level '{}', expected one of debug, info, warning, error, critical or notset",
+                name
+            ))),
+        };
+    }
+    match level.extract::<i64>() {
+        Ok(number) if number >= 0 => Ok(number),
+        Ok(number) => Err(PyValueError::new_err(format!(
+            "Logging levels can't be negative, got {}",
+            number
+        ))),
+        Err(_) => Err(PyTypeError::new_err(format!(
+            "Expected a logging level name or number, got {}",
+            level.get_type().name()?
+        ))),
+    }
+}
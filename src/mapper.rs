@@ -0,0 +1,68 @@
+//! `cel.Mapper`, which adds fields computed by CEL expressions to records.
+use crate::dataflow::{self, Dataflow};
+use crate::options::Options;
+use crate::repr_of;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+/// Adds fields computed by CEL expressions to dict records, e.g.
+/// `Mapper({"adult": "age >= 18"}).apply({"age": 30})`.
+///
+/// The expressions are compiled once, and are evaluated with the fields of the
+/// record as variables. A field may refer to the other computed fields, which
+/// are evaluated in an order where each is computed before it is used, as by
+/// `cel.plan`.
+#[pyclass(frozen, module = "cel")]
+pub struct Mapper {
+    flow: Dataflow,
+    mode: Options,
+}
+
+#[pymethods]
+impl Mapper {
+    #[new]
+    #[pyo3(signature = (fields, mode=None))]
+    fn new(
+        py: Python<'_>,
+        fields: &Bound<'_, PyAny>,
+        mode: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        Ok(Mapper {
+            flow: dataflow::plan(py, fields)?,
+            mode: match mode {
+                Some(mode) => Options::from_mode(mode)?,
+                None => Options::default(),
+            },
+        })
+    }
+
+    /// The names of the computed fields in the order they are evaluated
+    #[getter]
+    fn fields(&self) -> Vec<String> {
+        self.flow.order()
+    }
+
+    /// A copy of `record` with the computed fields added, replacing fields of
+    /// the same name
+    fn apply<'py>(&self, record: &Bound<'py, PyDict>) -> PyResult<Bound<'py, PyDict>> {
+        let enriched = record.copy()?;
+        let computed = self
+            .flow
+            .run(record.py(), Some(record.as_any()), self.mode)?;
+        enriched.update(computed.as_mapping())?;
+        Ok(enriched)
+    }
+
+    /// `apply` to each of `records`, returning a list of the enriched records
+    fn apply_many<'py>(&self, records: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyList>> {
+        let enriched = records
+            .iter()?
+            .map(|record| self.apply(record?.downcast::<PyDict>()?))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(PyList::new_bound(records.py(), enriched))
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!("Mapper({})", repr_of(py, self.fields()))
+    }
+}
@@ -0,0 +1,97 @@
+//! Memoization of Python function calls within an evaluation, for contexts
+//! created with `memoize=True`.
+//!
+//! The environment of a context is shared by its evaluations, so the calls are
+//! remembered by the evaluation running on the thread rather than by the
+//! functions, as the budget of a sandboxed evaluation is.
+use cel_interpreter::objects::Map;
+use cel_interpreter::{ResolveResult, Value};
+use std::cell::RefCell;
+
+/// A call made by the evaluation: the function, its arguments and its result
+type Call = (String, Vec<Value>, Value);
+
+thread_local! {
+    static CALLS: RefCell<Option<Vec<Call>>> = const { RefCell::new(None) };
+}
+
+/// Remembers the calls made on this thread until it is dropped, when `memoize` is
+/// set. A Python function that evaluates an expression itself gets a scope of its
+/// own, and the calls of the outer evaluation are remembered again once it ends.
+pub struct Scope {
+    outer: Option<Vec<Call>>,
+}
+
+impl Scope {
+    pub fn new(memoize: bool) -> Scope {
+        let calls = memoize.then(Vec::new);
+        Scope {
+            outer: CALLS.with(|outer| outer.replace(calls)),
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let outer = self.outer.take();
+        CALLS.with(|calls| *calls.borrow_mut() = outer);
+    }
+}
+
+/// The result of calling `function` with `args`: the result of an earlier call
+/// with the same arguments when the evaluation memoizes calls, otherwise
+/// `call()`'s. Only results are remembered, a call that failed is made again.
+pub fn call(
+    function: &str,
+    args: Vec<Value>,
+    call: impl FnOnce(&[Value]) -> ResolveResult,
+) -> ResolveResult {
+    let remembered = CALLS.with(|calls| {
+        let calls = calls.borrow();
+        let (_, _, result) = calls.as_ref()?.iter().find(|(name, called, _)| {
+            name == function
+                && called.len() == args.len()
+                && called.iter().zip(&args).all(|(a, b)| same(a, b))
+        })?;
+        Some(result.clone())
+    });
+    if let Some(result) = remembered {
+        return Ok(result);
+    }
+    let result = call(&args)?;
+    CALLS.with(|calls| {
+        if let Some(calls) = calls.borrow_mut().as_mut() {
+            calls.push((function.to_string(), args, result.clone()));
+        }
+    });
+    Ok(result)
+}
+
+/// Whether two values are the same value of the same type, so a function would
+/// be passed the same Python objects for them. Unlike `==`, `1`, `1u` and `1.0`
+/// are different values.
+pub(crate) fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::List(a), Value::List(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same(a, b))
+        }
+        (Value::Map(a), Value::Map(b)) => same_map(a, b),
+        (Value::Int(a), Value::Int(b)) => a == b,
+        (Value::UInt(a), Value::UInt(b)) => a == b,
+        (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Bytes(a), Value::Bytes(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Null, Value::Null) => true,
+        (Value::Duration(a), Value::Duration(b)) => a == b,
+        (Value::Timestamp(a), Value::Timestamp(b)) => a == b && a.offset() == b.offset(),
+        _ => false,
+    }
+}
+
+fn same_map(a: &Map, b: &Map) -> bool {
+    a.map.len() == b.map.len()
+        && a.map
+            .iter()
+            .all(|(key, value)| b.map.get(key).is_some_and(|other| same(value, other)))
+}
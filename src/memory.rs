@@ -0,0 +1,121 @@
+//! Approximate sizes of converted values and compiled expressions, for
+//! `Context.memory_usage()` and `Program.memory_usage()`.
+//!
+//! The `*_heap` functions count the allocations a value owns, not the value
+//! itself, so they can be added to the size of whatever holds it. Data shared
+//! between values, such as map keys interned while converting a context, is
+//! counted for each value that refers to it, so estimates err on the high side.
+use cel_interpreter::objects::Key;
+use cel_interpreter::Value;
+use cel_parser::{Atom, Expression, Member};
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::Arc;
+
+/// The reference counts stored alongside the contents of an `Arc`
+const ARC_COUNTS: usize = 2 * size_of::<usize>();
+
+pub fn string_heap(s: &String) -> usize {
+    s.capacity()
+}
+
+pub fn arc_string_heap(s: &Arc<String>) -> usize {
+    ARC_COUNTS + size_of::<String>() + s.capacity()
+}
+
+/// The table of a map, whose entries' own allocations are counted by `entry_heap`
+pub fn map_heap<K, V>(map: &HashMap<K, V>, entry_heap: impl Fn(&K, &V) -> usize) -> usize {
+    // Each bucket also has a control byte
+    map.capacity() * (size_of::<(K, V)>() + 1)
+        + map.iter().map(|(k, v)| entry_heap(k, v)).sum::<usize>()
+}
+
+pub fn value_heap(value: &Value) -> usize {
+    match value {
+        Value::String(s) => arc_string_heap(s),
+        Value::Bytes(b) => ARC_COUNTS + size_of::<Vec<u8>>() + b.capacity(),
+        Value::List(items) => {
+            ARC_COUNTS
+                + size_of::<Vec<Value>>()
+                + items.capacity() * size_of::<Value>()
+                + items.iter().map(value_heap).sum::<usize>()
+        }
+        Value::Map(map) => {
+            ARC_COUNTS
+                + size_of::<HashMap<Key, Value>>()
+                + map_heap(&map.map, |k, v| key_heap(k) + value_heap(v))
+        }
+        Value::Function(name, target) => {
+            arc_string_heap(name)
+                + target
+                    .as_ref()
+                    .map_or(0, |target| size_of::<Value>() + value_heap(target))
+        }
+        Value::Int(_)
+        | Value::UInt(_)
+        | Value::Float(_)
+        | Value::Bool(_)
+        | Value::Null
+        | Value::Duration(_)
+        | Value::Timestamp(_) => 0,
+    }
+}
+
+fn key_heap(key: &Key) -> usize {
+    match key {
+        Key::String(s) => arc_string_heap(s),
+        Key::Int(_) | Key::Uint(_) | Key::Bool(_) => 0,
+    }
+}
+
+fn boxed(expr: &Expression) -> usize {
+    size_of::<Expression>() + expression_heap(expr)
+}
+
+fn expressions_heap(exprs: &Vec<Expression>) -> usize {
+    exprs.capacity() * size_of::<Expression>() + exprs.iter().map(expression_heap).sum::<usize>()
+}
+
+pub fn expression_heap(expr: &Expression) -> usize {
+    match expr {
+        Expression::Arithmetic(left, _, right)
+        | Expression::Relation(left, _, right)
+        | Expression::Or(left, right)
+        | Expression::And(left, right) => boxed(left) + boxed(right),
+        Expression::Ternary(condition, left, right) => {
+            boxed(condition) + boxed(left) + boxed(right)
+        }
+        Expression::Unary(_, operand) => boxed(operand),
+        Expression::Member(target, member) => {
+            boxed(target) + size_of::<Member>() + member_heap(member)
+        }
+        Expression::FunctionCall(function, target, args) => {
+            boxed(function) + target.as_deref().map_or(0, boxed) + expressions_heap(args)
+        }
+        Expression::List(items) => expressions_heap(items),
+        Expression::Map(entries) => {
+            entries.capacity() * size_of::<(Expression, Expression)>()
+                + entries
+                    .iter()
+                    .map(|(k, v)| expression_heap(k) + expression_heap(v))
+                    .sum::<usize>()
+        }
+        Expression::Atom(Atom::String(s)) | Expression::Ident(s) => arc_string_heap(s),
+        Expression::Atom(Atom::Bytes(b)) => ARC_COUNTS + size_of::<Vec<u8>>() + b.capacity(),
+        Expression::Atom(_) => 0,
+    }
+}
+
+pub fn member_heap(member: &Member) -> usize {
+    match member {
+        Member::Attribute(name) => arc_string_heap(name),
+        Member::Index(index) => boxed(index),
+        Member::Fields(fields) => {
+            fields.capacity() * size_of::<(Arc<String>, Expression)>()
+                + fields
+                    .iter()
+                    .map(|(name, value)| arc_string_heap(name) + expression_heap(value))
+                    .sum::<usize>()
+        }
+    }
+}
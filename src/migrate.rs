@@ -0,0 +1,345 @@
+//! `cel.migrate`, which rewrites expressions written for one mode so that they
+//! evaluate the same in another, usually from the Python mode to the strict one.
+//!
+//! What the conveniences the new mode lacks did is written out explicitly where
+//! the types involved are known without evaluating the expression:
+//!
+//! - arithmetic mixing a double with an int or uint converts the other operand
+//!   with `double()`, as numeric promotion did; an operand whose type depends on
+//!   the context is assumed to be a number
+//! - non-bool operands of `&&`, `||`, `!` and `?:` are compared explicitly, e.g.
+//!   `size(name) > 0`, and as `||` returned the first truthy operand, `a || b`
+//!   becomes `size(a) > 0 ? a : b`, or `a ? true : b` when only `b` is no bool
+//! - only the last of the entries of a map literal with the same key is kept
+//! - `timestamp()` of a string literal that isn't RFC 3339 is given it in RFC 3339
+//!
+//! Rewritten expressions are written out again from the parsed expression, see
+//! [`unparse`]; expressions that need no changes are kept as they were.
+use crate::options::{arithmetic_symbol, literal_key, static_type, validate, Options, StaticType};
+use crate::repr_of;
+use crate::timestamps;
+use crate::transform::{call, map_children};
+use crate::unparse::unparse;
+use cel_parser::{Atom, Expression, RelationOp, UnaryOp};
+use chrono::SecondsFormat;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+use std::sync::Arc;
+
+/// An expression migrated from one mode to another
+#[pyclass(frozen, module = "cel")]
+pub struct Migration {
+    /// The expression as it was given
+    #[pyo3(get)]
+    original: String,
+    /// The migrated expression, the original one if nothing had to change
+    #[pyo3(get)]
+    expression: String,
+    /// What was rewritten, in the order the rewrites were made
+    #[pyo3(get)]
+    changes: Vec<String>,
+    /// Why the expression still needs attention, e.g. it still can't be
+    /// evaluated in the new mode
+    #[pyo3(get)]
+    issues: Vec<String>,
+}
+
+#[pymethods]
+impl Migration {
+    /// Whether the expression was rewritten
+    #[getter]
+    fn changed(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    /// Whether the expression has to be migrated by hand
+    #[getter]
+    fn needs_attention(&self) -> bool {
+        !self.issues.is_empty()
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!(
+            "Migration(expression={}, changes={}, issues={})",
+            repr_of(py, &self.expression),
+            repr_of(py, &self.changes),
+            repr_of(py, &self.issues)
+        )
+    }
+}
+
+/// Rewrites each of `exprs`, written for `from_mode`, so that they evaluate the
+/// same in `to_mode`, returning a `Migration` for each, in order.
+///
+/// The modes are "python" and "strict" or `cel.Options`, by default from the
+/// Python mode to the strict one. Expressions that can't be rewritten
+/// completely have `issues`, e.g. arithmetic mixing ints and uints, which no
+/// mode allows.
+#[pyfunction(signature = (exprs, from_mode=None, to_mode=None))]
+pub fn migrate(
+    exprs: &Bound<'_, PyAny>,
+    from_mode: Option<&Bound<'_, PyAny>>,
+    to_mode: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Vec<Migration>> {
+    if exprs.is_instance_of::<PyString>() {
+        return Err(PyTypeError::new_err(
+            "expected an iterable of expressions, got a single str",
+        ));
+    }
+    let from = match from_mode {
+        Some(mode) => Options::from_mode(mode)?,
+        None => Options::PYTHON,
+    };
+    let to = match to_mode {
+        Some(mode) => Options::from_mode(mode)?,
+        None => Options::STRICT,
+    };
+    exprs
+        .iter()?
+        .map(|expr| Ok(migrate_expression(expr?.extract()?, &from, &to)))
+        .collect()
+}
+
+fn migrate_expression(original: String, from: &Options, to: &Options) -> Migration {
+    let mut migration = Migration {
+        expression: original.clone(),
+        original,
+        changes: Vec::new(),
+        issues: Vec::new(),
+    };
+    let parsed = match crate::compile(&migration.original) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            migration
+                .issues
+                .push(format!("doesn't compile: {}", error.message));
+            return migration;
+        }
+    };
+    if let Err(message) = validate(&parsed, from) {
+        migration.issues.push(format!(
+            "doesn't compile in the mode it's migrated from: {}",
+            message
+        ));
+        return migration;
+    }
+
+    let mut migrator = Migrator {
+        from: *from,
+        to: *to,
+        changes: Vec::new(),
+    };
+    let migrated = migrator.rewrite(&parsed);
+    if !migrator.changes.is_empty() {
+        migration.expression = unparse(&migrated);
+        migration.changes = migrator.changes;
+        if crate::compile(&migration.expression).ok().as_ref() != Some(&migrated) {
+            migration.issues.push(format!(
+                "the rewritten expression doesn't parse back the same, it was written as {:?}",
+                migration.expression
+            ));
+        }
+    }
+    if let Err(message) = validate(&migrated, to) {
+        migration.issues.push(message);
+    }
+    migration
+}
+
+struct Migrator {
+    from: Options,
+    to: Options,
+    changes: Vec<String>,
+}
+
+impl Migrator {
+    /// Whether `from` has an option that `to` doesn't
+    fn drops(&self, option: fn(&Options) -> bool) -> bool {
+        option(&self.from) && !option(&self.to)
+    }
+
+    /// The type of `expr` where it's known without evaluating it
+    fn known(&self, expr: &Expression) -> StaticType {
+        static_type(expr, &self.from).unwrap_or(StaticType::Unknown)
+    }
+
+    fn rewrite(&mut self, expr: &Expression) -> Expression {
+        let truthiness = self.drops(|options| options.truthiness);
+        match expr {
+            Expression::Arithmetic(left, op, right)
+                if self.drops(|options| options.numeric_promotion) =>
+            {
+                let (left, right) = (self.rewrite(left), self.rewrite(right));
+                let symbol = arithmetic_symbol(op);
+                let (left, right) = match (self.known(&left), self.known(&right)) {
+                    (StaticType::Double, other) if promoted(other) => {
+                        let right = self.promote(right, symbol);
+                        (left, right)
+                    }
+                    (other, StaticType::Double) if promoted(other) => {
+                        (self.promote(left, symbol), right)
+                    }
+                    _ => (left, right),
+                };
+                Expression::Arithmetic(left.into(), op.clone(), right.into())
+            }
+            Expression::Or(left, right) if truthiness => {
+                let (left, right) = (self.rewrite(left), self.rewrite(right));
+                let (left_type, right_type) = (self.known(&left), self.known(&right));
+                let (condition, value) = match (explicit(&left, left_type), left_type) {
+                    (Some(condition), _) => (condition, left),
+                    // `a || b` is `a ? true : b` when `a` is a bool
+                    (None, StaticType::Bool) if explicit(&right, right_type).is_some() => {
+                        (left, Expression::Atom(Atom::Bool(true)))
+                    }
+                    // Whether `a` is a bool is only known when evaluating
+                    (None, _) => return Expression::Or(left.into(), right.into()),
+                };
+                self.changes.push(format!(
+                    "'||' returned the first truthy operand, rewrote it as {} ? {} : {}",
+                    unparse(&condition),
+                    unparse(&value),
+                    unparse(&right)
+                ));
+                Expression::Ternary(condition.into(), value.into(), right.into())
+            }
+            Expression::And(left, right) if truthiness => {
+                let (left, right) = (self.rewrite(left), self.rewrite(right));
+                let (left_type, right_type) = (self.known(&left), self.known(&right));
+                Expression::And(
+                    self.condition(left, left_type, "&&").into(),
+                    self.condition(right, right_type, "&&").into(),
+                )
+            }
+            Expression::Unary(op @ (UnaryOp::Not | UnaryOp::DoubleNot), operand) if truthiness => {
+                let operand = self.rewrite(operand);
+                let operand_type = self.known(&operand);
+                let operand = self.condition(operand, operand_type, "!");
+                match op {
+                    UnaryOp::DoubleNot if operand_type != StaticType::Bool => operand,
+                    _ => Expression::Unary(op.clone(), operand.into()),
+                }
+            }
+            Expression::Ternary(condition, left, right) if truthiness => {
+                let condition = self.rewrite(condition);
+                let condition_type = self.known(&condition);
+                Expression::Ternary(
+                    self.condition(condition, condition_type, "?:").into(),
+                    self.rewrite(left).into(),
+                    self.rewrite(right).into(),
+                )
+            }
+            Expression::Map(entries) if self.drops(|options| options.duplicate_map_keys) => {
+                let mut kept = Vec::new();
+                for (position, (key, value)) in entries.iter().enumerate() {
+                    let literal = literal_key(key);
+                    let repeated = entries[position + 1..]
+                        .iter()
+                        .position(|(other, _)| literal.is_some() && literal_key(other) == literal);
+                    match repeated {
+                        Some(offset) => self.changes.push(format!(
+                            "removed entry {} of a map, its key {} is repeated by entry {}",
+                            position + 1,
+                            unparse(key),
+                            position + 2 + offset
+                        )),
+                        None => kept.push((self.rewrite(key), self.rewrite(value))),
+                    }
+                }
+                Expression::Map(kept)
+            }
+            Expression::FunctionCall(function, None, args)
+                if self.drops(|options| options.lenient_timestamps)
+                    && matches!(&**function, Expression::Ident(name) if name.as_str() == "timestamp") =>
+            {
+                match &args[..] {
+                    [Expression::Atom(Atom::String(s))] if timestamps::parse(s, false).is_err() => {
+                        match timestamps::parse(s, true) {
+                            Ok(ts) => {
+                                let rfc3339 = ts.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+                                self.changes.push(format!(
+                                    "wrote timestamp {:?} in RFC 3339, as {:?}",
+                                    s.as_str(),
+                                    rfc3339
+                                ));
+                                call("timestamp", vec![string(&rfc3339)])
+                            }
+                            Err(_) => expr.clone(),
+                        }
+                    }
+                    _ => map_children(expr, |child| self.rewrite(child)),
+                }
+            }
+            _ => map_children(expr, |child| self.rewrite(child)),
+        }
+    }
+
+    /// `operand` converted to a double for the arithmetic operator `symbol`,
+    /// written as a double literal if it's an int literal
+    fn promote(&mut self, operand: Expression, symbol: &str) -> Expression {
+        let converted = match operand {
+            Expression::Atom(Atom::Int(i)) if (i as f64) as i64 == i => {
+                Expression::Atom(Atom::Float(i as f64))
+            }
+            Expression::Atom(Atom::UInt(u)) if (u as f64) as u64 == u => {
+                Expression::Atom(Atom::Float(u as f64))
+            }
+            ref operand => call("double", vec![operand.clone()]),
+        };
+        self.changes.push(format!(
+            "converted {} to a double for '{}', as {}",
+            unparse(&operand),
+            symbol,
+            unparse(&converted)
+        ));
+        converted
+    }
+
+    /// `expr` of type `known` as a condition of `operator`, compared explicitly
+    /// unless it's a bool or its type is only known when evaluating
+    fn condition(&mut self, expr: Expression, known: StaticType, operator: &str) -> Expression {
+        let Some(condition) = explicit(&expr, known) else {
+            return expr;
+        };
+        self.changes.push(format!(
+            "compared {} explicitly for '{}', as {}",
+            unparse(&expr),
+            operator,
+            unparse(&condition)
+        ));
+        condition
+    }
+}
+
+/// Whether numeric promotion converted an operand of this type to a double
+fn promoted(known: StaticType) -> bool {
+    matches!(
+        known,
+        StaticType::Int | StaticType::UInt | StaticType::Unknown
+    )
+}
+
+/// The truthiness of `expr` of type `known`, unless it's a bool or its type is
+/// only known when evaluating
+fn explicit(expr: &Expression, known: StaticType) -> Option<Expression> {
+    let compare = |left: Expression, op, right| {
+        Expression::Relation(left.into(), op, Expression::Atom(right).into())
+    };
+    Some(match known {
+        StaticType::Bool | StaticType::Unknown => return None,
+        StaticType::String | StaticType::Bytes | StaticType::List | StaticType::Map => compare(
+            call("size", vec![expr.clone()]),
+            RelationOp::GreaterThan,
+            Atom::Int(0),
+        ),
+        StaticType::Int => compare(expr.clone(), RelationOp::NotEquals, Atom::Int(0)),
+        StaticType::UInt => compare(expr.clone(), RelationOp::NotEquals, Atom::UInt(0)),
+        StaticType::Double => compare(expr.clone(), RelationOp::NotEquals, Atom::Float(0.0)),
+        StaticType::Null => Expression::Atom(Atom::Bool(false)),
+    })
+}
+
+fn string(s: &str) -> Expression {
+    Expression::Atom(Atom::String(Arc::new(s.to_string())))
+}
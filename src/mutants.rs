@@ -0,0 +1,199 @@
+//! `cel.testing.mutants`, which makes small changes to an expression that
+//! alter what it means, for mutation testing: a test suite that still passes
+//! with a mutant in place of the expression doesn't check the behaviour the
+//! mutant changed.
+//!
+//! Each mutant changes one node: a comparison, arithmetic or logical operator
+//! is swapped for a similar one, a literal is nudged, a negation is added or
+//! removed, a condition joined with `&&` or `||` is negated as a whole, the
+//! branches of `?:` are swapped or `all()` becomes `exists()`.
+use crate::compile;
+use crate::plan::is_macro;
+use crate::repr_of;
+use crate::transform::{is_call_to, map_children};
+use crate::unparse::unparse;
+use cel_parser::{ArithmeticOp, Atom, Expression, RelationOp, UnaryOp};
+use pyo3::prelude::*;
+use std::sync::Arc;
+
+/// An expression with one of its nodes changed
+#[pyclass(frozen, module = "cel.testing")]
+pub struct Mutant {
+    /// The source of the mutated expression
+    #[pyo3(get)]
+    expression: String,
+    /// The source of the node that was changed
+    #[pyo3(get)]
+    original: String,
+    /// The source of the node it was changed to
+    #[pyo3(get)]
+    replacement: String,
+}
+
+#[pymethods]
+impl Mutant {
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!(
+            "Mutant(expression={}, original={}, replacement={})",
+            repr_of(py, &self.expression),
+            repr_of(py, &self.original),
+            repr_of(py, &self.replacement)
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.expression.clone()
+    }
+}
+
+/// The mutants of `expr`, each differing from it in one node, in the order
+/// their nodes appear in the expression
+#[pyfunction]
+pub fn mutants(expr: &str) -> PyResult<Vec<Mutant>> {
+    let program = compile(expr).map_err(|error| error.to_py_err())?;
+    let original = unparse(&program);
+    let mut mutants: Vec<Mutant> = Vec::new();
+    for (mutated, node, replacement) in mutate(&program) {
+        let expression = unparse(&mutated);
+        // Different changes can give the same expression
+        if expression == original || mutants.iter().any(|m| m.expression == expression) {
+            continue;
+        }
+        mutants.push(Mutant {
+            expression,
+            original: unparse(&node),
+            replacement: unparse(&replacement),
+        });
+    }
+    Ok(mutants)
+}
+
+/// The mutants of `expr`, with the node each changes and what it became
+fn mutate(expr: &Expression) -> Vec<(Expression, Expression, Expression)> {
+    let mut mutants: Vec<_> = replacements(expr)
+        .into_iter()
+        .map(|replacement| (replacement.clone(), expr.clone(), replacement))
+        .collect();
+    // `has()` takes a field selection rather than a value
+    if is_call_to(expr, "has") {
+        return mutants;
+    }
+    let mut children = Vec::new();
+    map_children(expr, |child| {
+        children.push(child.clone());
+        child.clone()
+    });
+    for (index, child) in children.iter().enumerate() {
+        // The first argument of a macro names its variable
+        if index == 1 && matches!(expr, Expression::FunctionCall(f, Some(_), _) if is_macro(f)) {
+            continue;
+        }
+        for (mutated, node, replacement) in mutate(child) {
+            let mut position = 0;
+            let rebuilt = map_children(expr, |other| {
+                position += 1;
+                if position == index + 1 {
+                    mutated.clone()
+                } else {
+                    other.clone()
+                }
+            });
+            mutants.push((rebuilt, node, replacement));
+        }
+    }
+    mutants
+}
+
+/// The nodes that can replace `expr`
+fn replacements(expr: &Expression) -> Vec<Expression> {
+    match expr {
+        Expression::Relation(left, op, right) => {
+            let ops = match op {
+                RelationOp::Equals => vec![RelationOp::NotEquals],
+                RelationOp::NotEquals => vec![RelationOp::Equals],
+                // Moving the boundary, and negating the comparison
+                RelationOp::LessThan => vec![RelationOp::LessThanEq, RelationOp::GreaterThanEq],
+                RelationOp::LessThanEq => vec![RelationOp::LessThan, RelationOp::GreaterThan],
+                RelationOp::GreaterThan => vec![RelationOp::GreaterThanEq, RelationOp::LessThanEq],
+                RelationOp::GreaterThanEq => vec![RelationOp::GreaterThan, RelationOp::LessThan],
+                RelationOp::In => {
+                    return vec![Expression::Unary(UnaryOp::Not, expr.clone().into())]
+                }
+            };
+            ops.into_iter()
+                .map(|op| Expression::Relation(left.clone(), op, right.clone()))
+                .collect()
+        }
+        Expression::Arithmetic(left, op, right) => {
+            let op = match op {
+                ArithmeticOp::Add => ArithmeticOp::Subtract,
+                ArithmeticOp::Subtract => ArithmeticOp::Add,
+                ArithmeticOp::Multiply => ArithmeticOp::Divide,
+                ArithmeticOp::Divide | ArithmeticOp::Modulus => ArithmeticOp::Multiply,
+            };
+            vec![Expression::Arithmetic(left.clone(), op, right.clone())]
+        }
+        // Swapping the operator, and negating the whole condition
+        Expression::And(left, right) => vec![
+            Expression::Or(left.clone(), right.clone()),
+            Expression::Unary(UnaryOp::Not, expr.clone().into()),
+        ],
+        Expression::Or(left, right) => vec![
+            Expression::And(left.clone(), right.clone()),
+            Expression::Unary(UnaryOp::Not, expr.clone().into()),
+        ],
+        Expression::Unary(UnaryOp::Not | UnaryOp::DoubleNot | UnaryOp::Minus, operand) => {
+            vec![(**operand).clone()]
+        }
+        Expression::Ternary(condition, left, right) => vec![Expression::Ternary(
+            condition.clone(),
+            right.clone(),
+            left.clone(),
+        )],
+        Expression::Atom(atom) => literals(atom).into_iter().map(Expression::Atom).collect(),
+        _ if is_call_to(expr, "has") => {
+            vec![Expression::Unary(UnaryOp::Not, expr.clone().into())]
+        }
+        Expression::FunctionCall(function, target, args) if is_macro(function) => {
+            let name = match &**function {
+                Expression::Ident(name) => name.as_str(),
+                _ => return vec![],
+            };
+            let other = match name {
+                "all" => "exists",
+                "exists" => "all",
+                "exists_one" | "existsOne" => "exists",
+                _ => return vec![],
+            };
+            vec![Expression::FunctionCall(
+                Expression::Ident(Arc::new(other.to_string())).into(),
+                target.clone(),
+                args.clone(),
+            )]
+        }
+        _ => vec![],
+    }
+}
+
+/// The literals `atom` is nudged to
+fn literals(atom: &Atom) -> Vec<Atom> {
+    match atom {
+        Atom::Int(i) => [i.checked_add(1), i.checked_sub(1)]
+            .into_iter()
+            .flatten()
+            .map(Atom::Int)
+            .collect(),
+        Atom::UInt(u) => [u.checked_add(1), u.checked_sub(1)]
+            .into_iter()
+            .flatten()
+            .map(Atom::UInt)
+            .collect(),
+        Atom::Float(f) => vec![Atom::Float(f + 1.0), Atom::Float(f - 1.0)],
+        Atom::String(s) if s.is_empty() => vec![Atom::String(Arc::new("x".to_string()))],
+        Atom::String(_) => vec![Atom::String(Arc::new(String::new()))],
+        Atom::Bytes(b) if b.is_empty() => vec![Atom::Bytes(Arc::new(b"x".to_vec()))],
+        Atom::Bytes(_) => vec![Atom::Bytes(Arc::new(Vec::new()))],
+        Atom::Bool(b) => vec![Atom::Bool(!b)],
+        Atom::Null => vec![],
+    }
+}
@@ -0,0 +1,59 @@
+//! Calls to the functions of built-in extensions in a namespace, like
+//! `geo.point(...)` or `hash.sha256(...)`.
+//!
+//! The parser reads such a call as a method call on a variable named after the
+//! namespace, so the functions are added to the environment under their
+//! qualified names, which expressions can't refer to, and [`rewrite`] turns the
+//! calls into calls to them.
+use crate::transform::map_children;
+use cel_parser::Expression;
+use std::sync::Arc;
+
+/// Rewrites calls like `geo.point(...)` into calls to the function with that
+/// qualified name, if it's one of `functions`, returning None if there are none
+pub fn rewrite(expr: &Expression, functions: &[&str]) -> Option<Expression> {
+    if functions.is_empty() || !calls(expr, functions) {
+        return None;
+    }
+    Some(rewrite_calls(expr, functions))
+}
+
+/// The qualified name of the function of `functions` that `expr` calls, if it
+/// calls one
+fn called(expr: &Expression, functions: &[&str]) -> Option<String> {
+    let Expression::FunctionCall(function, Some(target), _) = expr else {
+        return None;
+    };
+    match (&**function, &**target) {
+        (Expression::Ident(name), Expression::Ident(namespace)) => {
+            let qualified = format!("{}.{}", namespace, name);
+            functions.contains(&qualified.as_str()).then_some(qualified)
+        }
+        _ => None,
+    }
+}
+
+fn calls(expr: &Expression, functions: &[&str]) -> bool {
+    if called(expr, functions).is_some() {
+        return true;
+    }
+    let mut found = false;
+    map_children(expr, |child| {
+        found = found || calls(child, functions);
+        child.clone()
+    });
+    found
+}
+
+fn rewrite_calls(expr: &Expression, functions: &[&str]) -> Expression {
+    match (called(expr, functions), expr) {
+        (Some(qualified), Expression::FunctionCall(_, _, args)) => Expression::FunctionCall(
+            Box::new(Expression::Ident(Arc::new(qualified))),
+            None,
+            args.iter()
+                .map(|arg| rewrite_calls(arg, functions))
+                .collect(),
+        ),
+        _ => map_children(expr, |child| rewrite_calls(child, functions)),
+    }
+}
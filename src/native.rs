@@ -0,0 +1,61 @@
+//! A Rust API for crates that build this module into an extension module of
+//! their own, with functions implemented in Rust that expressions call without
+//! going through Python. Enabled by the `native-extensions` feature.
+//!
+//! Functions are registered before the module is initialized, usually in the
+//! downstream crate's own `#[pymodule]`, which then calls [`init`]. The module
+//! needs a name of its own, as this crate already defines `PyInit_cel`:
+//!
+//! ```ignore
+//! use cel::native::{self, FunctionContext, ResolveResult, Value};
+//!
+//! fn haversine(ftx: &FunctionContext) -> ResolveResult { ... }
+//!
+//! #[pymodule]
+//! fn geo_cel(m: &Bound<'_, PyModule>) -> PyResult<()> {
+//!     native::register_function("haversine", haversine);
+//!     native::init(m)
+//! }
+//! ```
+//!
+//! Python functions of a context replace native functions of the same name,
+//! which in turn replace builtins. Native functions don't hold the GIL, so
+//! compiled programs that call them still run in parallel.
+pub use crate::types::register_type;
+pub use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use pyo3::prelude::*;
+use std::sync::Mutex;
+
+/// A function implemented in Rust, which resolves its own arguments from
+/// `ftx.args`, or takes the value it's called on from `ftx.this`
+pub type NativeFunction = fn(&FunctionContext) -> ResolveResult;
+
+static NATIVE: Mutex<Vec<(String, NativeFunction)>> = Mutex::new(Vec::new());
+
+/// Makes `function` available to every evaluation as `name`, replacing a native
+/// function registered under the same name. Environments already built by a
+/// `Context` keep the functions they were built with.
+pub fn register_function(name: impl Into<String>, function: NativeFunction) {
+    let name = name.into();
+    let mut native = NATIVE.lock().unwrap();
+    native.retain(|(registered, _)| *registered != name);
+    native.push((name, function));
+}
+
+/// The names of the registered native functions
+pub fn function_names() -> Vec<String> {
+    let native = NATIVE.lock().unwrap();
+    native.iter().map(|(name, _)| name.clone()).collect()
+}
+
+/// Adds the native functions to an environment
+pub(crate) fn register(environment: &mut cel_interpreter::Context) {
+    for (name, function) in NATIVE.lock().unwrap().iter() {
+        environment.add_function(name, *function);
+    }
+}
+
+/// Initializes `m` as the `cel` module, for a downstream `#[pymodule]`
+pub fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    crate::cel(m)
+}
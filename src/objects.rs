@@ -0,0 +1,412 @@
+//! Python objects whose attributes are read while evaluating, for contexts
+//! created with `objects="attributes"`.
+//!
+//! An object that can't be converted is kept by the context with the variable
+//! it was found in, and stands in the value as a handle: an [`OBJECT`] function
+//! value whose target lists the variable, the index of the object among the
+//! variable's objects and the path of attributes and items selected from it
+//! since. Attribute selections are rewritten into calls to [`ATTRIBUTE`], which
+//! reads the attribute of a handle with `getattr` and selects from any other
+//! value as usual. What an attribute holds is converted like a variable, except
+//! that objects within it become handles with a longer path, rather than being
+//! kept, so evaluations don't grow the context.
+//!
+//! Operators and indexes are rewritten into calls to [`OPERATOR`], which applies
+//! the Python operator when an operand is a handle, so that objects implementing
+//! `__add__`, `__lt__`, `__contains__` or `__getitem__` can be used with them. The
+//! result is a path segment like any other: the operator and the other operand.
+use crate::transform::{call, is_call_to, map_children, resolve_member};
+use crate::{Converter, RustyCelType};
+use cel_interpreter::objects::Key;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use cel_parser::{ArithmeticOp, Atom, Expression, Member, RelationOp};
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyAttributeError, PyLookupError};
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Name of the function value that handles to objects are
+pub const OBJECT: &str = "@object";
+
+/// Internal function that attribute selections are rewritten into
+pub const ATTRIBUTE: &str = "@attribute";
+
+/// Internal function that operators and indexes are rewritten into
+pub const OPERATOR: &str = "@operator";
+
+/// Variables the operands of an operation are bound to when neither is a handle
+const LEFT: &str = "@left";
+const RIGHT: &str = "@right";
+
+/// The objects of each variable of a context that holds any
+pub type Objects = Arc<Mutex<HashMap<String, Vec<Arc<Py<PyAny>>>>>>;
+
+/// Where a [`Converter`] puts the objects it can't convert
+pub enum Found {
+    /// Kept for the variable being converted
+    Variable {
+        name: String,
+        objects: Vec<Arc<Py<PyAny>>>,
+    },
+    /// Found again from the handle of the attribute being converted
+    Attribute(Vec<Value>),
+}
+
+impl Found {
+    /// The handle of `object` at `path` within the value being converted
+    pub fn keep(&mut self, object: &Bound<'_, PyAny>, path: &[Value]) -> Value {
+        let handle = match self {
+            Found::Variable { name, objects } => {
+                objects.push(Arc::new(object.clone().unbind()));
+                vec![
+                    Value::String(Arc::new(name.clone())),
+                    Value::Int(objects.len() as i64 - 1),
+                ]
+            }
+            Found::Attribute(handle) => handle.iter().chain(path).cloned().collect(),
+        };
+        Value::Function(
+            Arc::new(OBJECT.to_string()),
+            Some(Box::new(Value::List(Arc::new(handle)))),
+        )
+    }
+}
+
+/// The variable, index and path of a handle
+fn handle(value: &Value) -> Option<&[Value]> {
+    match value {
+        Value::Function(name, Some(target)) if name.as_str() == OBJECT => match &**target {
+            Value::List(handle) => Some(handle),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A path segment selecting the item at `key`, rather than an attribute
+pub fn item(key: &Key) -> Value {
+    let key = match key {
+        Key::String(key) => Value::String(key.clone()),
+        Key::Int(key) => Value::Int(*key),
+        Key::Uint(key) => Value::UInt(*key),
+        Key::Bool(key) => Value::Bool(*key),
+    };
+    Value::List(Arc::new(vec![key]))
+}
+
+/// Rewrites attribute selections into calls to [`ATTRIBUTE`], which evaluate to
+/// null for missing attributes when `safe`, except within `has()`, and operators
+/// and indexes into calls to [`OPERATOR`]
+pub fn rewrite(expr: &Expression, safe: bool) -> Expression {
+    let operand = |name: &str| Box::new(Expression::Ident(Arc::new(name.to_string())));
+    let operation = |symbol: &str, left: &Expression, right: &Expression, applied| {
+        call(
+            OPERATOR,
+            vec![
+                Expression::Atom(Atom::String(Arc::new(symbol.to_string()))),
+                rewrite(left, safe),
+                rewrite(right, safe),
+                applied,
+            ],
+        )
+    };
+    match expr {
+        Expression::Arithmetic(left, op, right) => operation(
+            arithmetic_symbol(op),
+            left,
+            right,
+            Expression::Arithmetic(operand(LEFT), op.clone(), operand(RIGHT)),
+        ),
+        Expression::Relation(left, op, right) => operation(
+            relation_symbol(op),
+            left,
+            right,
+            Expression::Relation(operand(LEFT), op.clone(), operand(RIGHT)),
+        ),
+        Expression::Member(target, member) => match &**member {
+            Member::Attribute(name) => call(
+                ATTRIBUTE,
+                vec![
+                    rewrite(target, safe),
+                    Expression::Atom(Atom::String(name.clone())),
+                    Expression::Atom(Atom::Bool(safe)),
+                ],
+            ),
+            Member::Index(index) => operation(
+                "[]",
+                target,
+                index,
+                Expression::Member(operand(LEFT), Member::Index(operand(RIGHT)).into()),
+            ),
+            _ => map_children(expr, |child| rewrite(child, safe)),
+        },
+        _ if is_call_to(expr, "has") => map_children(expr, |child| rewrite(child, false)),
+        _ => map_children(expr, |child| rewrite(child, safe)),
+    }
+}
+
+fn arithmetic_symbol(op: &ArithmeticOp) -> &'static str {
+    match op {
+        ArithmeticOp::Add => "+",
+        ArithmeticOp::Subtract => "-",
+        ArithmeticOp::Multiply => "*",
+        ArithmeticOp::Divide => "/",
+        ArithmeticOp::Modulus => "%",
+    }
+}
+
+fn relation_symbol(op: &RelationOp) -> &'static str {
+    match op {
+        RelationOp::LessThan => "<",
+        RelationOp::LessThanEq => "<=",
+        RelationOp::GreaterThan => ">",
+        RelationOp::GreaterThanEq => ">=",
+        RelationOp::Equals => "==",
+        RelationOp::NotEquals => "!=",
+        RelationOp::In => "in",
+    }
+}
+
+/// Whether an attribute is private, or special like `__class__`, which can't be
+/// read by expressions, as it would lead out of the object to its class, module
+/// and the globals of its methods
+fn is_private(name: &str) -> bool {
+    name.starts_with('_')
+}
+
+/// The objects of a context, and how the values of their attributes are converted
+#[derive(Clone)]
+pub struct Reader {
+    pub objects: Objects,
+    pub namedtuples_as_maps: bool,
+    pub decode_bytes_keys: bool,
+}
+
+impl Reader {
+    /// Implementation of [`ATTRIBUTE`]
+    pub fn attribute(&self, ftx: &FunctionContext) -> ResolveResult {
+        let target = ftx.ptx.resolve(&ftx.args[0])?;
+        let Value::String(name) = ftx.ptx.resolve(&ftx.args[1])? else {
+            return Err(ftx.error("expected the name of an attribute"));
+        };
+        let safe = matches!(ftx.ptx.resolve(&ftx.args[2])?, Value::Bool(true));
+
+        let result = match handle(&target) {
+            Some(handle) => self.read(handle, &name),
+            None if safe && target == Value::Null => return Ok(Value::Null),
+            None => resolve_member(ftx.ptx, target, &Member::Attribute(name)),
+        };
+        match result {
+            Err(ExecutionError::NoSuchKey(_)) if safe => Ok(Value::Null),
+            result => result,
+        }
+    }
+
+    /// Implementation of [`OPERATOR`]
+    pub fn operator(&self, ftx: &FunctionContext) -> ResolveResult {
+        let Value::String(symbol) = ftx.ptx.resolve(&ftx.args[0])? else {
+            return Err(ftx.error("expected an operator"));
+        };
+        let left = ftx.ptx.resolve(&ftx.args[1])?;
+        let right = ftx.ptx.resolve(&ftx.args[2])?;
+        // The container of `in` and of an index has to be an object for Python's
+        // operator to be used, otherwise items are compared and selected as usual
+        let handles = match symbol.as_str() {
+            "in" => (None, handle(&right)),
+            "[]" => (handle(&left), None),
+            _ => (handle(&left), handle(&right)),
+        };
+        let (handle, operand, swapped) = match handles {
+            (Some(handle), _) => (handle, right.clone(), false),
+            (None, Some(handle)) => (handle, left.clone(), true),
+            (None, None) => {
+                let mut scope = ftx.ptx.new_inner_scope();
+                scope.add_variable_from_value(LEFT, left);
+                scope.add_variable_from_value(RIGHT, right);
+                return scope.resolve(&ftx.args[3]);
+            }
+        };
+        let segment = Value::List(Arc::new(vec![
+            Value::String(symbol.clone()),
+            operand,
+            Value::Bool(swapped),
+        ]));
+        let mut path = handle.to_vec();
+        path.push(segment);
+
+        Python::with_gil(|py| {
+            let failed = |message: String| ExecutionError::FunctionError {
+                function: symbol.to_string(),
+                message,
+            };
+            let value = match self.walk(py, &path, &failed) {
+                Ok(value) => value,
+                Err(Walked::Missing(_)) if symbol.as_str() == "[]" => {
+                    let key = match &right {
+                        Value::String(key) => key.to_string(),
+                        Value::Int(key) => key.to_string(),
+                        Value::UInt(key) => key.to_string(),
+                        Value::Bool(key) => key.to_string(),
+                        key => format!("{:?}", key),
+                    };
+                    return Err(ExecutionError::NoSuchKey(Arc::new(key)));
+                }
+                Err(Walked::Missing(e)) => return Err(failed(e.to_string())),
+                Err(Walked::Failed(error)) => return Err(error),
+            };
+            self.convert(&value, path)
+                .map_err(|e| failed(e.to_string()))
+        })
+    }
+
+    /// Reads the attribute `name` of the object a handle refers to
+    fn read(&self, handle: &[Value], name: &str) -> ResolveResult {
+        if is_private(name) {
+            return Err(ExecutionError::NoSuchKey(Arc::new(name.to_string())));
+        }
+        let failed = |message: String| ExecutionError::FunctionError {
+            function: name.to_string(),
+            message,
+        };
+        Python::with_gil(|py| {
+            let object = match self.walk(py, handle, &failed) {
+                Ok(object) => object,
+                Err(Walked::Missing(e)) => return Err(failed(e.to_string())),
+                Err(Walked::Failed(error)) => return Err(error),
+            };
+            let value = match object.getattr(name) {
+                Ok(value) => value,
+                Err(e) if e.is_instance_of::<PyAttributeError>(py) => {
+                    return Err(ExecutionError::NoSuchKey(Arc::new(name.to_string())))
+                }
+                Err(e) => return Err(failed(e.to_string())),
+            };
+
+            let mut path = handle.to_vec();
+            path.push(Value::String(Arc::new(name.to_string())));
+            self.convert(&value, path)
+                .map_err(|e| failed(e.to_string()))
+        })
+    }
+
+    /// Converts what was found at `path`, with objects within it becoming
+    /// handles that extend the path
+    fn convert(
+        &self,
+        value: &Bound<'_, PyAny>,
+        path: Vec<Value>,
+    ) -> Result<Value, crate::CelError> {
+        let mut converter = Converter {
+            namedtuples_as_maps: self.namedtuples_as_maps,
+            decode_bytes_keys: self.decode_bytes_keys,
+            objects: Some(Found::Attribute(path)),
+            ..Converter::default()
+        };
+        converter.convert(value)
+    }
+
+    /// Finds the object a handle refers to again, from its variable through
+    /// the attributes, items and operations of its path
+    fn walk<'py>(
+        &self,
+        py: Python<'py>,
+        path: &[Value],
+        failed: &impl Fn(String) -> ExecutionError,
+    ) -> Result<Bound<'py, PyAny>, Walked> {
+        let invalid = || Walked::Failed(failed("invalid object handle".to_string()));
+        let (Some(Value::String(variable)), Some(Value::Int(index))) = (path.first(), path.get(1))
+        else {
+            return Err(invalid());
+        };
+        let object = self
+            .objects
+            .lock()
+            .unwrap()
+            .get(variable.as_str())
+            .and_then(|objects| objects.get(*index as usize).cloned())
+            .ok_or_else(|| {
+                Walked::Failed(failed(format!(
+                    "the object of '{}' is no longer available",
+                    variable
+                )))
+            })?;
+
+        let mut object = object.bind(py).clone();
+        for segment in &path[2..] {
+            object = match segment {
+                Value::String(attribute) if is_private(attribute) => {
+                    return Err(Walked::Failed(ExecutionError::NoSuchKey(attribute.clone())))
+                }
+                Value::String(attribute) => object.getattr(attribute.as_str()),
+                Value::List(key) if key.len() == 1 => match &key[0] {
+                    Value::String(key) => object.get_item(key.as_str()),
+                    Value::Int(key) => object.get_item(key),
+                    Value::UInt(key) => object.get_item(key),
+                    Value::Bool(key) => object.get_item(key),
+                    _ => return Err(invalid()),
+                },
+                Value::List(operation) => match &operation[..] {
+                    [Value::String(symbol), operand, Value::Bool(swapped)] => {
+                        let operand = match handle(operand) {
+                            Some(operand) => self.walk(py, operand, failed)?,
+                            None => RustyCelType(operand.clone())
+                                .try_into_py(py, &Default::default())
+                                .map_err(|e| Walked::Failed(failed(e.to_string())))?
+                                .into_bound(py),
+                        };
+                        match swapped {
+                            true => apply(symbol, &operand, &object),
+                            false => apply(symbol, &object, &operand),
+                        }
+                    }
+                    _ => return Err(invalid()),
+                },
+                _ => return Err(invalid()),
+            }
+            .map_err(|e| match e.is_instance_of::<PyLookupError>(py) {
+                true => Walked::Missing(e),
+                false => Walked::Failed(failed(e.to_string())),
+            })?;
+        }
+        Ok(object)
+    }
+}
+
+/// Why an object couldn't be found again
+enum Walked {
+    /// An item or attribute is missing
+    Missing(PyErr),
+    Failed(ExecutionError),
+}
+
+/// Applies the Python operator `symbol` to `left` and `right`
+pub(crate) fn apply<'py>(
+    symbol: &str,
+    left: &Bound<'py, PyAny>,
+    right: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let compare = |op| left.rich_compare(right, op);
+    match symbol {
+        "+" => left.add(right),
+        "-" => left.sub(right),
+        "*" => left.mul(right),
+        "/" => left.div(right),
+        "%" => left.rem(right),
+        "<" => compare(CompareOp::Lt),
+        "<=" => compare(CompareOp::Le),
+        ">" => compare(CompareOp::Gt),
+        ">=" => compare(CompareOp::Ge),
+        "==" => compare(CompareOp::Eq),
+        "!=" => compare(CompareOp::Ne),
+        "in" => Ok(right
+            .contains(left)?
+            .into_py(left.py())
+            .into_bound(left.py())),
+        "[]" => left.get_item(right),
+        _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "unknown operator {}",
+            symbol
+        ))),
+    }
+}
@@ -0,0 +1,686 @@
+use crate::arithmetic;
+use crate::errors::EvalError;
+use crate::tokenize::tokens;
+use crate::transform::{call, map_children};
+use crate::unparse::unparse;
+use cel_interpreter::objects::{Key, Map};
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use cel_parser::{ArithmeticOp, Atom, Expression, Member, RelationOp, UnaryOp};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Internal function that checks an operand of a logical operator is a bool
+pub const CHECK_BOOL: &str = "@bool";
+
+/// Internal function that arithmetic is rewritten into when numeric promotion
+/// is disabled
+pub const ARITHMETIC: &str = "@arithmetic";
+
+/// Internal function that `==` and `!=` are rewritten into when heterogeneous
+/// equality is disabled
+pub const EQUALITY: &str = "@equality";
+
+/// Internal function that map literals with keys known only while evaluating are
+/// rewritten into when duplicate map keys are disallowed, called with the
+/// source of the literal followed by each key and its value
+pub const MAP: &str = "@map";
+
+/// How the errors of repeated map keys start
+const DUPLICATE_KEY: &str = "duplicate map key";
+
+/// The Python conveniences an evaluation allows, each of which can be turned
+/// off to follow the CEL specification.
+///
+/// `mode="python"` enables all of them and `mode="strict"` disables all but
+/// heterogeneous equality, which the specification allows, and error
+/// absorption, which it requires; an `Options` can be passed as the mode to mix
+/// the two.
+#[pyclass(frozen, get_all, eq, module = "cel")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    /// Arithmetic may mix int, uint and double, e.g. `1 + 2.5`
+    pub numeric_promotion: bool,
+    /// `&&`, `||`, `!` and `?:` accept non-bool values by their truthiness
+    pub truthiness: bool,
+    /// Selecting a missing field, or any field of null, evaluates to null
+    pub safe_navigation: bool,
+    /// `==` and `!=` compare values of different types, e.g. `1 == 1.0`
+    pub heterogeneous_equality: bool,
+    /// `timestamp()` accepts more than RFC 3339 strings and integer seconds
+    pub lenient_timestamps: bool,
+    /// A map literal may repeat a key, e.g. `{'a': 1, 'a': 2}`, the last entry wins
+    pub duplicate_map_keys: bool,
+    /// An error in one operand of `&&` or `||` is absorbed when the other decides
+    /// the result, e.g. `1 / 0 == 1 || true` is true, as the specification requires
+    pub error_absorption: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::PYTHON
+    }
+}
+
+impl Options {
+    pub const PYTHON: Options = Options {
+        numeric_promotion: true,
+        truthiness: true,
+        safe_navigation: false,
+        heterogeneous_equality: true,
+        lenient_timestamps: true,
+        duplicate_map_keys: true,
+        error_absorption: true,
+    };
+
+    pub const STRICT: Options = Options {
+        numeric_promotion: false,
+        truthiness: false,
+        safe_navigation: false,
+        heterogeneous_equality: true,
+        lenient_timestamps: false,
+        duplicate_map_keys: false,
+        error_absorption: true,
+    };
+
+    /// Reads the `mode` argument: "python", "strict" or an `Options`
+    pub fn from_mode(mode: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(options) = mode.downcast::<Options>() {
+            return Ok(*options.get());
+        }
+        match mode.extract::<&str>() {
+            Ok("python") => Ok(Options::PYTHON),
+            Ok("strict") => Ok(Options::STRICT),
+            Ok(_) => Err(PyValueError::new_err(
+                "mode must be either 'python' or 'strict'",
+            )),
+            Err(_) => Err(PyTypeError::new_err(
+                "mode must be 'python', 'strict' or a cel.Options",
+            )),
+        }
+    }
+
+    /// Whether expressions need checking or rewriting before evaluation
+    fn restricts(&self) -> bool {
+        !self.numeric_promotion
+            || !self.truthiness
+            || !self.heterogeneous_equality
+            || !self.duplicate_map_keys
+    }
+}
+
+#[pymethods]
+impl Options {
+    /// Defaults to Python mode, so only the options given are changed from it
+    #[new]
+    #[pyo3(signature = (*, numeric_promotion=true, truthiness=true, safe_navigation=false, heterogeneous_equality=true, lenient_timestamps=true, duplicate_map_keys=true, error_absorption=true))]
+    fn new(
+        numeric_promotion: bool,
+        truthiness: bool,
+        safe_navigation: bool,
+        heterogeneous_equality: bool,
+        lenient_timestamps: bool,
+        duplicate_map_keys: bool,
+        error_absorption: bool,
+    ) -> Self {
+        Options {
+            numeric_promotion,
+            truthiness,
+            safe_navigation,
+            heterogeneous_equality,
+            lenient_timestamps,
+            duplicate_map_keys,
+            error_absorption,
+        }
+    }
+
+    /// The options of `mode="python"`
+    #[staticmethod]
+    fn python() -> Self {
+        Options::PYTHON
+    }
+
+    /// The options of `mode="strict"`
+    #[staticmethod]
+    fn strict() -> Self {
+        Options::STRICT
+    }
+
+    fn __hash__(&self) -> u64 {
+        [
+            self.numeric_promotion,
+            self.truthiness,
+            self.safe_navigation,
+            self.heterogeneous_equality,
+            self.lenient_timestamps,
+            self.duplicate_map_keys,
+            self.error_absorption,
+        ]
+        .iter()
+        .fold(0, |hash, &flag| hash << 1 | flag as u64)
+    }
+
+    fn __repr__(&self) -> String {
+        let py_bool = |flag: bool| if flag { "True" } else { "False" };
+        format!(
+            "Options(numeric_promotion={}, truthiness={}, safe_navigation={}, heterogeneous_equality={}, lenient_timestamps={}, duplicate_map_keys={}, error_absorption={})",
+            py_bool(self.numeric_promotion),
+            py_bool(self.truthiness),
+            py_bool(self.safe_navigation),
+            py_bool(self.heterogeneous_equality),
+            py_bool(self.lenient_timestamps),
+            py_bool(self.duplicate_map_keys),
+            py_bool(self.error_absorption),
+        )
+    }
+}
+
+/// The type of an expression where it is known without evaluating it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StaticType {
+    Int,
+    UInt,
+    Double,
+    String,
+    Bytes,
+    Bool,
+    Null,
+    List,
+    Map,
+    /// Depends on the context, e.g. a variable
+    Unknown,
+}
+
+impl StaticType {
+    fn name(self) -> &'static str {
+        match self {
+            StaticType::Int => "int",
+            StaticType::UInt => "uint",
+            StaticType::Double => "double",
+            StaticType::String => "string",
+            StaticType::Bytes => "bytes",
+            StaticType::Bool => "bool",
+            StaticType::Null => "null_type",
+            StaticType::List => "list",
+            StaticType::Map => "map",
+            StaticType::Unknown => "dyn",
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            StaticType::Int | StaticType::UInt | StaticType::Double
+        )
+    }
+}
+
+/// Rejects expressions that can't evaluate without the conveniences `options`
+/// disables, before they are evaluated.
+///
+/// Only what can be known without the context is checked: arithmetic mixing
+/// numeric types (`1 + 2.0`), non-bool operands of `&&`, `||`, `!` and `?:`
+/// (`size(x) && y`) and literal keys repeated in a map (`{'a': 1, 'a': 2}`). Whatever depends on the context is checked while
+/// evaluating, see [`enforce`].
+pub fn validate(expr: &Expression, options: &Options) -> Result<(), String> {
+    if options.restricts() {
+        static_type(expr, options)?;
+    }
+    Ok(())
+}
+
+/// The error `src`, parsed into an expression, is rejected with by
+/// [`validate`]. A repeated map key is pointed at by its position.
+pub fn rejection(src: &str, message: String) -> EvalError {
+    let position = match message.starts_with(DUPLICATE_KEY) {
+        true => repeated_key(src),
+        false => None,
+    };
+    EvalError::rejected(src, message).at(position)
+}
+
+/// The offset of the first literal key in `src` that an earlier entry of the
+/// same map literal already has. Found from the tokens, as parsed expressions
+/// don't know where they were in the source.
+fn repeated_key(src: &str) -> Option<usize> {
+    let tokens: Vec<_> = tokens(src)
+        .into_iter()
+        .filter(|(kind, _)| *kind != "comment")
+        .map(|(kind, range)| (kind, &src[range.clone()], range.start))
+        .collect();
+    // The keys of the map literal each open bracket starts, None for other
+    // brackets and for constructions like `Msg{...}`
+    let mut open: Vec<Option<Vec<Key>>> = Vec::new();
+    for (index, (_, text, start)) in tokens.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|i| tokens[i]);
+        match *text {
+            "(" | "[" => open.push(None),
+            "{" => open.push(match previous {
+                Some(("identifier", _, _)) => None,
+                _ => Some(Vec::new()),
+            }),
+            ")" | "]" | "}" => {
+                open.pop();
+            }
+            _ => {
+                let starts_entry = matches!(previous, Some((_, "{" | ",", _)));
+                let is_key = matches!(tokens.get(index + 1), Some((_, ":", _)));
+                let (Some(Some(keys)), true, true) = (open.last_mut(), starts_entry, is_key) else {
+                    continue;
+                };
+                let parsed = cel_parser::parse(text).ok();
+                if let Some(key) = parsed.as_ref().and_then(literal_key) {
+                    if keys.contains(&key) {
+                        return Some(*start);
+                    }
+                    keys.push(key);
+                }
+            }
+        }
+    }
+    None
+}
+
+pub(crate) fn static_type(expr: &Expression, options: &Options) -> Result<StaticType, String> {
+    let static_type = |expr: &Expression| static_type(expr, options);
+    let condition_type = |expr: &Expression, role| condition_type(expr, role, options);
+    Ok(match expr {
+        Expression::Atom(atom) => match atom {
+            Atom::Int(_) => StaticType::Int,
+            Atom::UInt(_) => StaticType::UInt,
+            Atom::Float(_) => StaticType::Double,
+            Atom::String(_) => StaticType::String,
+            Atom::Bytes(_) => StaticType::Bytes,
+            Atom::Bool(_) => StaticType::Bool,
+            Atom::Null => StaticType::Null,
+        },
+        Expression::Ident(_) => StaticType::Unknown,
+        Expression::Arithmetic(left, op, right) => {
+            let (left, right) = (static_type(left)?, static_type(right)?);
+            if !options.numeric_promotion
+                && left.is_numeric()
+                && right.is_numeric()
+                && left != right
+            {
+                return Err(format!(
+                    "'{}' can't be applied to {} and {} without numeric promotion, convert \
+                     one operand with int(), uint() or double()",
+                    arithmetic_symbol(op),
+                    left.name(),
+                    right.name()
+                ));
+            }
+            match (left, right) {
+                (StaticType::Unknown, _) | (_, StaticType::Unknown) => StaticType::Unknown,
+                _ if left == right => left,
+                _ => StaticType::Unknown,
+            }
+        }
+        Expression::Relation(left, _, right) => {
+            static_type(left)?;
+            static_type(right)?;
+            StaticType::Bool
+        }
+        Expression::Ternary(condition, left, right) => {
+            condition_type(condition, "the condition of '?:'")?;
+            match (static_type(left)?, static_type(right)?) {
+                (left, right) if left == right => left,
+                _ => StaticType::Unknown,
+            }
+        }
+        Expression::And(left, right) => {
+            condition_type(left, "each operand of '&&'")?;
+            condition_type(right, "each operand of '&&'")?;
+            StaticType::Bool
+        }
+        Expression::Or(left, right) => {
+            condition_type(left, "each operand of '||'")?;
+            condition_type(right, "each operand of '||'")?;
+            StaticType::Bool
+        }
+        Expression::Unary(UnaryOp::Not | UnaryOp::DoubleNot, operand) => {
+            condition_type(operand, "the operand of '!'")?;
+            StaticType::Bool
+        }
+        Expression::Unary(UnaryOp::Minus | UnaryOp::DoubleMinus, operand) => {
+            match static_type(operand)? {
+                numeric if numeric.is_numeric() => numeric,
+                _ => StaticType::Unknown,
+            }
+        }
+        Expression::Member(target, member) => {
+            static_type(target)?;
+            match &**member {
+                Member::Attribute(_) => {}
+                Member::Index(index) => {
+                    static_type(index)?;
+                }
+                Member::Fields(fields) => {
+                    for (_, value) in fields {
+                        static_type(value)?;
+                    }
+                }
+            }
+            StaticType::Unknown
+        }
+        Expression::FunctionCall(function, target, args) => {
+            if let Some(target) = target {
+                static_type(target)?;
+            }
+            for arg in args {
+                static_type(arg)?;
+            }
+            match &**function {
+                Expression::Ident(name) => match name.as_str() {
+                    "int" => StaticType::Int,
+                    "uint" => StaticType::UInt,
+                    "double" => StaticType::Double,
+                    "string" => StaticType::String,
+                    "bytes" => StaticType::Bytes,
+                    "bool" | "has" | "contains" | "startsWith" | "endsWith" | "matches" => {
+                        StaticType::Bool
+                    }
+                    "size" => StaticType::Int,
+                    _ => StaticType::Unknown,
+                },
+                _ => StaticType::Unknown,
+            }
+        }
+        Expression::List(items) => {
+            for item in items {
+                static_type(item)?;
+            }
+            StaticType::List
+        }
+        Expression::Map(entries) => {
+            let mut keys = HashMap::new();
+            for (position, (key, value)) in entries.iter().enumerate() {
+                static_type(key)?;
+                static_type(value)?;
+                if options.duplicate_map_keys {
+                    continue;
+                }
+                if let Some(key) = literal_key(key) {
+                    if let Some(first) = keys.insert(key.clone(), position) {
+                        return Err(duplicate_key_message(&key, first, position, &unparse(expr)));
+                    }
+                }
+            }
+            StaticType::Map
+        }
+    })
+}
+
+/// Checks an operand that must be a bool unless truthiness is enabled
+fn condition_type(expr: &Expression, role: &str, options: &Options) -> Result<(), String> {
+    match static_type(expr, options)? {
+        StaticType::Bool | StaticType::Unknown => Ok(()),
+        _ if options.truthiness => Ok(()),
+        other => Err(format!(
+            "{} must be a bool without truthiness, got {}; compare explicitly instead, \
+             e.g. `size(x) > 0`",
+            role,
+            other.name()
+        )),
+    }
+}
+
+/// The key of a map entry whose key is a literal
+pub(crate) fn literal_key(expr: &Expression) -> Option<Key> {
+    match expr {
+        Expression::Atom(Atom::Int(v)) => Some(Key::Int(*v)),
+        Expression::Atom(Atom::UInt(v)) => Some(Key::Uint(*v)),
+        Expression::Atom(Atom::Bool(b)) => Some(Key::Bool(*b)),
+        Expression::Atom(Atom::String(s)) => Some(Key::String(s.clone())),
+        _ => None,
+    }
+}
+
+/// Names a repeated key as it would be written in CEL, the entries it is the key
+/// of, counting from 1, and the source of the map literal
+fn duplicate_key_message(key: &Key, first: usize, position: usize, literal: &str) -> String {
+    let key = match key {
+        Key::String(s) => format!("'{}'", s),
+        Key::Uint(v) => format!("{}u", v),
+        other => other.to_string(),
+    };
+    format!(
+        "{} {} in entry {}, already the key of entry {} in {}",
+        DUPLICATE_KEY,
+        key,
+        position + 1,
+        first + 1,
+        literal
+    )
+}
+
+pub(crate) fn arithmetic_symbol(op: &ArithmeticOp) -> &'static str {
+    match op {
+        ArithmeticOp::Add => "+",
+        ArithmeticOp::Subtract => "-",
+        ArithmeticOp::Multiply => "*",
+        ArithmeticOp::Divide => "/",
+        ArithmeticOp::Modulus => "%",
+    }
+}
+
+/// Rewrites `expr` so that what `options` disables is checked while evaluating:
+/// operands of logical operators that might not be bools are wrapped in a call to
+/// [`CHECK_BOOL`], arithmetic or equality that might mix types becomes a call
+/// to [`ARITHMETIC`] or [`EQUALITY`], and maps with keys that aren't literals
+/// become a call to [`MAP`].
+///
+/// Returns None if nothing needs to be checked.
+pub fn enforce(expr: &Expression, options: &Options) -> Option<Expression> {
+    options.restricts().then(|| rewrite(expr, options))
+}
+
+fn rewrite(expr: &Expression, options: &Options) -> Expression {
+    let known = |expr: &Expression| static_type(expr, options).unwrap_or(StaticType::Unknown);
+    let same_known_type = |left: &Expression, right: &Expression| {
+        let left = known(left);
+        left != StaticType::Unknown && left == known(right)
+    };
+    let condition = |expr: &Expression, operator: &str| {
+        let rewritten = rewrite(expr, options);
+        if options.truthiness || known(expr) == StaticType::Bool {
+            rewritten
+        } else {
+            call(CHECK_BOOL, vec![rewritten, string(operator)])
+        }
+    };
+
+    match expr {
+        Expression::And(left, right) => {
+            Expression::And(condition(left, "&&").into(), condition(right, "&&").into())
+        }
+        Expression::Or(left, right) => {
+            Expression::Or(condition(left, "||").into(), condition(right, "||").into())
+        }
+        Expression::Unary(op @ (UnaryOp::Not | UnaryOp::DoubleNot), operand) => {
+            Expression::Unary(op.clone(), condition(operand, "!").into())
+        }
+        Expression::Ternary(cond, left, right) => Expression::Ternary(
+            condition(cond, "?:").into(),
+            rewrite(left, options).into(),
+            rewrite(right, options).into(),
+        ),
+        Expression::Arithmetic(left, op, right)
+            if !options.numeric_promotion && !same_known_type(left, right) =>
+        {
+            call(
+                ARITHMETIC,
+                vec![
+                    rewrite(left, options),
+                    string(arithmetic_symbol(op)),
+                    rewrite(right, options),
+                ],
+            )
+        }
+        Expression::Relation(left, op @ (RelationOp::Equals | RelationOp::NotEquals), right)
+            if !options.heterogeneous_equality =>
+        {
+            let symbol = if *op == RelationOp::Equals {
+                "=="
+            } else {
+                "!="
+            };
+            call(
+                EQUALITY,
+                vec![
+                    rewrite(left, options),
+                    string(symbol),
+                    rewrite(right, options),
+                ],
+            )
+        }
+        Expression::Map(entries)
+            if !options.duplicate_map_keys
+                && entries.iter().any(|(key, _)| literal_key(key).is_none()) =>
+        {
+            let entries = entries
+                .iter()
+                .flat_map(|(key, value)| [rewrite(key, options), rewrite(value, options)]);
+            call(
+                MAP,
+                std::iter::once(string(&unparse(expr)))
+                    .chain(entries)
+                    .collect(),
+            )
+        }
+        _ => map_children(expr, |child| rewrite(child, options)),
+    }
+}
+
+fn string(s: &str) -> Expression {
+    Expression::Atom(Atom::String(Arc::new(s.to_string())))
+}
+
+/// The operator name the interpreter uses in its errors
+fn operator_symbol<'a>(ftx: &'a FunctionContext) -> Result<&'a str, ExecutionError> {
+    match ftx.args.get(1) {
+        Some(Expression::Atom(Atom::String(symbol))) => Ok(symbol.as_str()),
+        _ => Err(ftx.error("expected an operator")),
+    }
+}
+
+fn is_numeric(value: &Value) -> bool {
+    matches!(value, Value::Int(_) | Value::UInt(_) | Value::Float(_))
+}
+
+/// Implementation of [`CHECK_BOOL`], which returns its first argument if it is a bool,
+/// the operand or condition of the operator given as its second
+pub fn check_bool(ftx: &FunctionContext) -> ResolveResult {
+    match ftx.ptx.resolve(&ftx.args[0])? {
+        Value::Bool(b) => Ok(Value::Bool(b)),
+        other => {
+            let role = match operator_symbol(ftx)? {
+                "?:" => "condition",
+                _ => "operand",
+            };
+            Err(ExecutionError::UnexpectedType {
+                got: crate::types::name_of(&other).to_string(),
+                want: format!("bool {} of '{}'", role, operator_symbol(ftx)?),
+            })
+        }
+    }
+}
+
+/// Implementation of [`ARITHMETIC`], which is called as `@arithmetic(left, "+", right)`
+/// and fails if the operands are numbers of different types
+pub fn arithmetic(ftx: &FunctionContext) -> ResolveResult {
+    let left = ftx.ptx.resolve(&ftx.args[0])?;
+    let right = ftx.ptx.resolve(&ftx.args[2])?;
+    let (name, apply): (&'static str, fn(Value, Value) -> ResolveResult) =
+        match operator_symbol(ftx)? {
+            "+" => ("add", arithmetic::add),
+            "-" => ("sub", arithmetic::sub),
+            "*" => ("mul", arithmetic::mul),
+            "/" => ("div", arithmetic::div),
+            "%" => ("rem", arithmetic::rem),
+            _ => return Err(ftx.error("expected an operator")),
+        };
+    if is_numeric(&left)
+        && is_numeric(&right)
+        && std::mem::discriminant(&left) != std::mem::discriminant(&right)
+    {
+        return Err(ExecutionError::UnsupportedBinaryOperator(name, left, right));
+    }
+    apply(left, right)
+}
+
+/// Implementation of [`EQUALITY`], which is called as `@equality(left, "==", right)`
+/// and fails if the operands are of different types. Null can be compared to
+/// anything.
+pub fn equality(ftx: &FunctionContext) -> ResolveResult {
+    let left = ftx.ptx.resolve(&ftx.args[0])?;
+    let right = ftx.ptx.resolve(&ftx.args[2])?;
+    let equals = operator_symbol(ftx)? == "==";
+    let name = if equals { "equals" } else { "not_equals" };
+    Ok(Value::Bool(strict_equals(name, &left, &right)? == equals))
+}
+
+/// Whether two values of the same type are equal, failing for values of
+/// different types, also as the items of lists and values of maps
+fn strict_equals(name: &'static str, left: &Value, right: &Value) -> Result<bool, ExecutionError> {
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => Ok(left == right),
+        (Value::List(l), Value::List(r)) if l.len() == r.len() => {
+            for (l, r) in l.iter().zip(r.iter()) {
+                if !strict_equals(name, l, r)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (Value::Map(l), Value::Map(r)) if l.map.len() == r.map.len() => {
+            for (key, l) in l.map.iter() {
+                match r.map.get(key) {
+                    Some(r) if strict_equals(name, l, r)? => {}
+                    _ => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+        (Value::List(_), Value::List(_)) | (Value::Map(_), Value::Map(_)) => Ok(false),
+        _ if std::mem::discriminant(left) != std::mem::discriminant(right) => Err(
+            ExecutionError::UnsupportedBinaryOperator(name, left.clone(), right.clone()),
+        ),
+        _ => Ok(left == right),
+    }
+}
+
+/// Implementation of [`MAP`], which is called with the source of the literal and
+/// then each key followed by its value, and fails if a key is repeated
+pub fn map(ftx: &FunctionContext) -> ResolveResult {
+    let literal = match ftx.args.first() {
+        Some(Expression::Atom(Atom::String(literal))) => literal,
+        _ => return Err(ftx.error("expected the source of the map literal")),
+    };
+    let mut map = HashMap::new();
+    for (position, entry) in ftx.args[1..].chunks(2).enumerate() {
+        let key: Key = ftx
+            .ptx
+            .resolve(&entry[0])?
+            .try_into()
+            .map_err(ExecutionError::UnsupportedKeyType)?;
+        let value = ftx.ptx.resolve(&entry[1])?;
+        if let Some((first, _)) = map.insert(key.clone(), (position, value)) {
+            return Err(ftx.error(duplicate_key_message(&key, first, position, literal)));
+        }
+    }
+    let map: HashMap<Key, Value> = map
+        .into_iter()
+        .map(|(key, (_, value))| (key, value))
+        .collect();
+    Ok(Value::Map(Map { map: Arc::new(map) }))
+}
+
+/// Adds the internal functions used by [`enforce`]'s rewrites
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function(CHECK_BOOL, check_bool);
+    environment.add_function(ARITHMETIC, arithmetic);
+    environment.add_function(EQUALITY, equality);
+    environment.add_function(MAP, map);
+}
@@ -0,0 +1,58 @@
+//! Round-trip mode, for contexts created with `round_trip=True`: lists and maps
+//! of the context that an expression returns unchanged are returned as the
+//! Python objects they were converted from, rather than as converted copies.
+//!
+//! Evaluation shares the contents of lists and maps rather than copying them, so
+//! a converted list or map is recognised by the address of its contents. The
+//! converted value of each variable is kept along with its objects, so no other
+//! list or map can be given one of those addresses while they are kept.
+use cel_interpreter::Value;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The address of the contents of a list or map, which identifies it
+pub fn address(value: &Value) -> Option<usize> {
+    match value {
+        Value::List(items) => Some(Arc::as_ptr(items) as *const () as usize),
+        Value::Map(map) => Some(Arc::as_ptr(&map.map) as *const () as usize),
+        _ => None,
+    }
+}
+
+/// The objects the lists and maps of a variable were converted from
+pub struct Recorded {
+    /// Keeps the addresses of the lists and maps in use
+    _value: Value,
+    objects: HashMap<usize, Py<PyAny>>,
+}
+
+impl Recorded {
+    pub fn new(value: Value, objects: HashMap<usize, Py<PyAny>>) -> Self {
+        Recorded {
+            _value: value,
+            objects,
+        }
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &Py<PyAny>> {
+        self.objects.values()
+    }
+}
+
+/// The objects of the variables of the context an evaluation is against
+#[derive(Default)]
+pub struct Originals(pub Vec<Arc<Recorded>>);
+
+impl Originals {
+    /// The object `value` was converted from, if it was
+    pub fn get(&self, py: Python<'_>, value: &Value) -> Option<PyObject> {
+        let address = address(value)?;
+        self.0.iter().find_map(|recorded| {
+            recorded
+                .objects
+                .get(&address)
+                .map(|object| object.clone_ref(py))
+        })
+    }
+}
@@ -0,0 +1,195 @@
+use crate::duration::CelDuration;
+use cel_interpreter::objects::{Key, Map};
+use cel_interpreter::Value;
+use chrono::{DateTime, Duration as ChronoDuration, FixedOffset};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyByteArray, PyBytes, PyDict, PyTuple};
+
+/// Python types that CEL values are converted to, set per Context with
+/// `output_types`, e.g. `Context(output_types={"timestamp": "iso"})`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputTypes {
+    timestamp: TimestampOutput,
+    duration: DurationOutput,
+    map: MapOutput,
+    map_order: MapOrder,
+    bytes: BytesOutput,
+    list: ListOutput,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum TimestampOutput {
+    #[default]
+    Datetime,
+    /// An ISO 8601 string
+    Iso,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum DurationOutput {
+    #[default]
+    Timedelta,
+    /// A float number of seconds
+    Seconds,
+    /// A `cel.Duration`, which keeps nanosecond precision
+    Cel,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum MapOutput {
+    #[default]
+    Dict,
+    /// A read only `types.MappingProxyType`
+    MappingProxy,
+}
+
+/// The order of the keys of maps. CEL maps don't keep the order their entries
+/// were written in, so the only stable order is a sorted one.
+#[derive(Debug, Clone, Copy, Default)]
+enum MapOrder {
+    /// Whatever order the map has, which varies between runs
+    #[default]
+    Any,
+    /// Sorted by key, with integer keys before bool keys before string keys
+    Sorted,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum BytesOutput {
+    #[default]
+    Bytes,
+    Bytearray,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum ListOutput {
+    #[default]
+    List,
+    /// A tuple, e.g. for lists that were tuples before being passed to CEL
+    Tuple,
+}
+
+impl OutputTypes {
+    pub fn from_dict(output_types: &Bound<'_, PyDict>) -> PyResult<Self> {
+        let mut result = OutputTypes::default();
+        for (kind, output) in output_types {
+            let kind = kind.extract::<String>()?;
+            let output = output.extract::<String>()?;
+            match (kind.as_str(), output.as_str()) {
+                ("timestamp", "datetime") => result.timestamp = TimestampOutput::Datetime,
+                ("timestamp", "iso") => result.timestamp = TimestampOutput::Iso,
+                ("duration", "timedelta") => result.duration = DurationOutput::Timedelta,
+                ("duration", "seconds") => result.duration = DurationOutput::Seconds,
+                ("duration", "cel") => result.duration = DurationOutput::Cel,
+                ("map", "dict") => result.map = MapOutput::Dict,
+                ("map", "mappingproxy") => result.map = MapOutput::MappingProxy,
+                ("map_order", "any") => result.map_order = MapOrder::Any,
+                ("map_order", "sorted") => result.map_order = MapOrder::Sorted,
+                ("bytes", "bytes") => result.bytes = BytesOutput::Bytes,
+                ("bytes", "bytearray") => result.bytes = BytesOutput::Bytearray,
+                ("list", "list") => result.list = ListOutput::List,
+                ("list", "tuple") => result.list = ListOutput::Tuple,
+                ("timestamp" | "duration" | "map" | "map_order" | "bytes" | "list", _) => {
+                    return Err(PyValueError::new_err(format!(
+                        "Unsupported output type '{}' for {}",
+                        output, kind
+                    )))
+                }
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                        "Unknown output_types key '{}', expected one of timestamp, duration, map, map_order, bytes or list",
+                        kind
+                    )))
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn timestamp(&self, py: Python<'_>, ts: DateTime<FixedOffset>) -> PyObject {
+        match self.timestamp {
+            TimestampOutput::Datetime => ts.into_py(py),
+            TimestampOutput::Iso => ts.to_rfc3339().into_py(py),
+        }
+    }
+
+    pub fn duration(&self, py: Python<'_>, d: ChronoDuration) -> PyObject {
+        match self.duration {
+            DurationOutput::Timedelta => d.into_py(py),
+            DurationOutput::Seconds => {
+                (d.num_seconds() as f64 + d.subsec_nanos() as f64 / 1e9).into_py(py)
+            }
+            DurationOutput::Cel => CelDuration { duration: d }.into_py(py),
+        }
+    }
+
+    pub fn map(&self, py: Python<'_>, dict: Bound<'_, PyDict>) -> PyResult<PyObject> {
+        match self.map {
+            MapOutput::Dict => Ok(dict.into_py(py)),
+            MapOutput::MappingProxy => Ok(py
+                .import_bound("types")?
+                .getattr("MappingProxyType")?
+                .call1((dict,))?
+                .unbind()),
+        }
+    }
+
+    /// The entries of a map in the order they are converted in
+    pub fn map_entries<'a>(&self, map: &'a Map) -> Vec<(&'a Key, &'a Value)> {
+        let mut entries: Vec<_> = map.map.iter().collect();
+        if let MapOrder::Sorted = self.map_order {
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        }
+        entries
+    }
+
+    pub fn list(&self, py: Python<'_>, items: Vec<PyObject>) -> PyObject {
+        match self.list {
+            ListOutput::List => items.into_py(py),
+            ListOutput::Tuple => PyTuple::new_bound(py, items).into_py(py),
+        }
+    }
+
+    pub fn bytes(&self, py: Python<'_>, bytes: &[u8]) -> PyObject {
+        match self.bytes {
+            BytesOutput::Bytes => PyBytes::new_bound(py, bytes).into_py(py),
+            BytesOutput::Bytearray => PyByteArray::new_bound(py, bytes).into_py(py),
+        }
+    }
+}
+
+/// Whether `value` holds at most `max` list items, map entries and bytes of
+/// strings and bytes, counted through all of its nesting. Stops counting as soon
+/// as the limit is passed.
+pub fn within_size(value: &Value, max: usize) -> bool {
+    fn spend(value: &Value, remaining: &mut usize) -> bool {
+        let own = match value {
+            Value::List(items) => items.len(),
+            Value::Map(map) => map.map.len(),
+            Value::String(s) => s.len(),
+            Value::Bytes(b) => b.len(),
+            _ => 0,
+        };
+        if own > *remaining {
+            return false;
+        }
+        *remaining -= own;
+        match value {
+            Value::List(items) => items.iter().all(|item| spend(item, remaining)),
+            Value::Map(map) => map.map.iter().all(|(key, value)| {
+                let key = match key {
+                    Key::String(s) => s.len(),
+                    _ => 0,
+                };
+                key <= *remaining && {
+                    *remaining -= key;
+                    spend(value, remaining)
+                }
+            }),
+            _ => true,
+        }
+    }
+    let mut remaining = max;
+    spend(value, &mut remaining)
+}
@@ -0,0 +1,839 @@
+use crate::arithmetic;
+use crate::bytes;
+use crate::memory;
+use crate::transform::{map_children, resolve_member};
+use crate::types::{self, is_hidden, name_of, registered_type, type_name};
+use crate::unknowns::{combine_failures, is_truthy};
+use cel_interpreter::objects::{Key, ValueType};
+use cel_interpreter::{Context, ExecutionError, ResolveResult, Value};
+use cel_parser::{ArithmeticOp, Expression, Member, RelationOp, UnaryOp};
+#[cfg(feature = "threads")]
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::sync::Arc;
+
+/// An expression flattened into a list of instructions for a small stack machine.
+///
+/// Running a plan avoids the recursive walk over the expression tree that the
+/// interpreter performs for every evaluation, constant sub-expressions are
+/// computed once when the plan is built, and the bodies of the comprehension
+/// macros (`map`, `filter`, `all`, `exists`, `exists_one`) are themselves plans
+/// that read the loop variable from a slot rather than through a new scope.
+/// Other function calls are handed to the interpreter unchanged, so they behave
+/// exactly as they would without a plan.
+#[derive(Debug)]
+pub struct Plan {
+    instructions: Vec<Instruction>,
+    /// Whether any part of the plan is resolved by the interpreter, which then
+    /// needs comprehension variables to be declared in its context.
+    resolves: bool,
+    /// Lists at least this long are mapped, filtered and checked by `all` in parallel
+    pub parallel_threshold: Option<usize>,
+}
+
+/// The values a plan operates on, shared with the bodies of comprehensions so
+/// that evaluating them doesn't allocate for every item.
+#[derive(Default)]
+struct Frame {
+    stack: Vec<Value>,
+    /// Comprehension variables, innermost last
+    locals: Vec<Value>,
+    parallel_threshold: Option<usize>,
+    /// Whether an error in one operand of `&&` and `||` is absorbed by the other
+    absorb_errors: bool,
+}
+
+#[derive(Debug)]
+enum Instruction {
+    Push(Value),
+    Load(Arc<String>),
+    LoadLocal(usize),
+    Arithmetic(ArithmeticOp),
+    Relation(RelationOp),
+    Unary(UnaryOp),
+    /// Pops an index then a target
+    Index,
+    /// Pops a target and selects a field from it
+    Select(Member),
+    List(usize),
+    /// Pops the given number of key and value pairs
+    Map(usize),
+    Jump(usize),
+    /// Pops the condition, jumping if it is falsy
+    JumpIfFalsy(usize),
+    /// `&&` of its operands, which are plans of their own so that either can be
+    /// false when the other fails
+    And(Box<(Plan, Plan)>),
+    /// `||` of its operands, the first truthy one, even if the other fails
+    Or(Box<(Plan, Plan)>),
+    /// Pops a list or map and evaluates the body for each item or key, or only
+    /// for those the predicate of a `map` with one is true for
+    Comprehension(Macro, Arc<String>, Option<Plan>, Plan),
+    /// Resolved by the interpreter
+    Resolve(Expression),
+}
+
+/// Names of the macros a plan evaluates itself rather than through the interpreter
+pub const MACROS: [&str; 10] = [
+    "map",
+    "filter",
+    "all",
+    "exists",
+    "exists_one",
+    "existsOne",
+    "count",
+    "groupBy",
+    "countBy",
+    "distinct",
+];
+
+/// Whether `function` names one of the [`MACROS`]
+pub fn is_macro(function: &Expression) -> bool {
+    matches!(function, Expression::Ident(name) if MACROS.contains(&name.as_str()))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Macro {
+    Map,
+    Filter,
+    All,
+    Exists,
+    ExistsOne,
+    Count,
+    GroupBy,
+    CountBy,
+    Distinct,
+}
+
+impl Macro {
+    fn from_name(name: &str) -> Option<Macro> {
+        match name {
+            "map" => Some(Macro::Map),
+            "filter" => Some(Macro::Filter),
+            "all" => Some(Macro::All),
+            "exists" => Some(Macro::Exists),
+            "exists_one" | "existsOne" => Some(Macro::ExistsOne),
+            "count" => Some(Macro::Count),
+            "groupBy" => Some(Macro::GroupBy),
+            "countBy" => Some(Macro::CountBy),
+            "distinct" => Some(Macro::Distinct),
+            _ => None,
+        }
+    }
+}
+
+/// Returns true if `expr` doesn't reference any variables or functions.
+pub fn is_constant(expr: &Expression) -> bool {
+    match expr {
+        Expression::Atom(_) => true,
+        Expression::Ident(_) | Expression::FunctionCall(..) => false,
+        Expression::Arithmetic(left, _, right)
+        | Expression::Relation(left, _, right)
+        | Expression::Or(left, right)
+        | Expression::And(left, right) => is_constant(left) && is_constant(right),
+        Expression::Ternary(cond, left, right) => {
+            is_constant(cond) && is_constant(left) && is_constant(right)
+        }
+        Expression::Unary(_, operand) => is_constant(operand),
+        Expression::Member(target, member) => {
+            is_constant(target)
+                && match &**member {
+                    Member::Attribute(_) => true,
+                    Member::Index(index) => is_constant(index),
+                    Member::Fields(_) => false,
+                }
+        }
+        Expression::List(items) => items.iter().all(is_constant),
+        Expression::Map(entries) => entries
+            .iter()
+            .all(|(key, value)| is_constant(key) && is_constant(value)),
+    }
+}
+
+impl Plan {
+    pub fn new(expr: &Expression) -> Self {
+        Plan::with_locals(expr, &mut Vec::new())
+    }
+
+    fn with_locals(expr: &Expression, locals: &mut Vec<Arc<String>>) -> Self {
+        let mut plan = Plan {
+            instructions: Vec::new(),
+            resolves: false,
+            parallel_threshold: None,
+        };
+        plan.emit(expr, locals);
+        plan
+    }
+
+    fn emit(&mut self, expr: &Expression, locals: &mut Vec<Arc<String>>) {
+        if is_constant(expr) && !matches!(expr, Expression::Atom(_)) {
+            // Anything that fails is left to fail at evaluation time, as is
+            // arithmetic that isn't checked, which would overflow
+            let mut context = Context::empty();
+            arithmetic::register(&mut context);
+            let checked = arithmetic::rewrite(expr);
+            let folded = match &checked {
+                Some(checked) => Value::resolve(checked, &context).ok(),
+                None if !arithmetic::has_arithmetic(expr) => Value::resolve(expr, &context).ok(),
+                None => None,
+            };
+            if let Some(value) = folded {
+                self.instructions.push(Instruction::Push(value));
+                return;
+            }
+        }
+
+        match expr {
+            Expression::Atom(atom) => self.instructions.push(Instruction::Push(atom.into())),
+            Expression::Ident(name) => match locals.iter().rposition(|local| local == name) {
+                Some(slot) => self.instructions.push(Instruction::LoadLocal(slot)),
+                None => self.instructions.push(Instruction::Load(name.clone())),
+            },
+            Expression::Arithmetic(left, op, right) => {
+                self.emit(left, locals);
+                self.emit(right, locals);
+                self.instructions.push(Instruction::Arithmetic(op.clone()));
+            }
+            Expression::Relation(left, op, right) => {
+                self.emit(left, locals);
+                self.emit(right, locals);
+                self.instructions.push(Instruction::Relation(op.clone()));
+            }
+            Expression::Unary(op, operand) => {
+                self.emit(operand, locals);
+                self.instructions.push(Instruction::Unary(op.clone()));
+            }
+            Expression::Ternary(cond, left, right) => {
+                self.emit(cond, locals);
+                let to_right = self.placeholder();
+                self.emit(left, locals);
+                let to_end = self.placeholder();
+                self.instructions[to_right] = Instruction::JumpIfFalsy(self.instructions.len());
+                self.emit(right, locals);
+                self.instructions[to_end] = Instruction::Jump(self.instructions.len());
+            }
+            Expression::And(left, right) => {
+                let operands = self.operands(left, right, locals);
+                self.instructions.push(Instruction::And(operands));
+            }
+            Expression::Or(left, right) => {
+                let operands = self.operands(left, right, locals);
+                self.instructions.push(Instruction::Or(operands));
+            }
+            Expression::Member(target, member) => {
+                self.emit(target, locals);
+                match &**member {
+                    Member::Index(index) => {
+                        self.emit(index, locals);
+                        self.instructions.push(Instruction::Index);
+                    }
+                    member => self.instructions.push(Instruction::Select(member.clone())),
+                }
+            }
+            Expression::List(items) => {
+                for item in items {
+                    self.emit(item, locals);
+                }
+                self.instructions.push(Instruction::List(items.len()));
+            }
+            Expression::Map(entries) => {
+                for (key, value) in entries {
+                    self.emit(key, locals);
+                    self.emit(value, locals);
+                }
+                self.instructions.push(Instruction::Map(entries.len()));
+            }
+            Expression::FunctionCall(name, Some(target), args) => {
+                let comprehension = match (&**name, &args[..]) {
+                    (Expression::Ident(name), [Expression::Ident(variable), body]) => {
+                        Macro::from_name(name).map(|m| (m, variable, None, body))
+                    }
+                    (Expression::Ident(name), [Expression::Ident(variable), filter, body])
+                        if name.as_str() == "map" =>
+                    {
+                        Some((Macro::Map, variable, Some(filter), body))
+                    }
+                    _ => None,
+                };
+                match comprehension {
+                    Some((kind, variable, filter, body)) => {
+                        self.emit(target, locals);
+                        locals.push(variable.clone());
+                        let filter = filter.map(|filter| Plan::with_locals(filter, locals));
+                        let body = Plan::with_locals(body, locals);
+                        locals.pop();
+                        self.resolves |=
+                            body.resolves || filter.as_ref().is_some_and(|f| f.resolves);
+                        self.instructions.push(Instruction::Comprehension(
+                            kind,
+                            variable.clone(),
+                            filter,
+                            body,
+                        ));
+                    }
+                    None => self.resolve(expr),
+                }
+            }
+            Expression::FunctionCall(..) => self.resolve(expr),
+        }
+    }
+
+    /// The operands of a logical operator as plans of their own
+    fn operands(
+        &mut self,
+        left: &Expression,
+        right: &Expression,
+        locals: &mut Vec<Arc<String>>,
+    ) -> Box<(Plan, Plan)> {
+        let left = Plan::with_locals(left, locals);
+        let right = Plan::with_locals(right, locals);
+        self.resolves |= left.resolves || right.resolves;
+        Box::new((left, right))
+    }
+
+    /// Leaves `expr` to the interpreter, with the rewrites it is given when it
+    /// evaluates the whole expression, so that e.g. `f(1 / 0)` fails rather than
+    /// panics
+    fn resolve(&mut self, expr: &Expression) {
+        self.resolves = true;
+        let expr = types::rewrite_indexes(expr).unwrap_or_else(|| expr.clone());
+        let expr = arithmetic::rewrite(&expr).unwrap_or(expr);
+        let expr = bytes::rewrite(&expr).unwrap_or(expr);
+        self.instructions.push(Instruction::Resolve(expr));
+    }
+
+    /// Reserves a slot for a jump whose destination isn't known yet
+    fn placeholder(&mut self) -> usize {
+        self.instructions.push(Instruction::Jump(0));
+        self.instructions.len() - 1
+    }
+
+    /// Evaluates the plan against `ctx`
+    ///
+    /// Comprehensions are only evaluated in parallel if `parallel` is set. Python
+    /// functions must hold the GIL, which other threads can't take while the caller
+    /// holds it, so it must not be set if the plan [calls](Plan::calls) one.
+    /// `absorb_errors` is the error absorption option of the evaluation.
+    pub fn run(&self, ctx: &Context, parallel: bool, absorb_errors: bool) -> ResolveResult {
+        self.run_on(
+            ctx,
+            &mut Frame {
+                parallel_threshold: self.parallel_threshold.filter(|_| parallel),
+                absorb_errors,
+                ..Frame::default()
+            },
+        )
+    }
+
+    /// Approximate number of bytes allocated by the plan, not counting the plan itself
+    pub fn heap_size(&self) -> usize {
+        self.instructions.capacity() * std::mem::size_of::<Instruction>()
+            + self
+                .instructions
+                .iter()
+                .map(|instruction| match instruction {
+                    Instruction::Push(value) => memory::value_heap(value),
+                    Instruction::Load(name) => memory::arc_string_heap(name),
+                    Instruction::Select(member) => memory::member_heap(member),
+                    Instruction::Comprehension(_, name, filter, body) => {
+                        memory::arc_string_heap(name)
+                            + filter.as_ref().map_or(0, Plan::heap_size)
+                            + body.heap_size()
+                    }
+                    Instruction::And(operands) | Instruction::Or(operands) => {
+                        std::mem::size_of::<(Plan, Plan)>()
+                            + operands.0.heap_size()
+                            + operands.1.heap_size()
+                    }
+                    Instruction::Resolve(expr) => memory::expression_heap(expr),
+                    _ => 0,
+                })
+                .sum::<usize>()
+    }
+
+    /// Returns true if any part of the plan calls the function `name`.
+    pub fn calls(&self, name: &str) -> bool {
+        self.instructions
+            .iter()
+            .any(|instruction| match instruction {
+                Instruction::Resolve(expr) => expression_calls(expr, name),
+                Instruction::Comprehension(_, _, filter, body) => {
+                    filter.as_ref().is_some_and(|filter| filter.calls(name)) || body.calls(name)
+                }
+                Instruction::And(operands) | Instruction::Or(operands) => {
+                    operands.0.calls(name) || operands.1.calls(name)
+                }
+                _ => false,
+            })
+    }
+
+    fn run_on(&self, ctx: &Context, frame: &mut Frame) -> ResolveResult {
+        let mut pc = 0;
+        while let Some(instruction) = self.instructions.get(pc) {
+            pc += 1;
+            match instruction {
+                Instruction::Push(value) => frame.stack.push(value.clone()),
+                Instruction::Load(name) => frame.stack.push(ctx.get_variable(name.as_str())?),
+                Instruction::LoadLocal(slot) => frame.stack.push(frame.locals[*slot].clone()),
+                Instruction::Arithmetic(op) => {
+                    let right = pop(&mut frame.stack);
+                    let left = pop(&mut frame.stack);
+                    frame.stack.push(arithmetic::apply(op, left, right)?);
+                }
+                Instruction::Relation(op) => {
+                    let right = pop(&mut frame.stack);
+                    let left = pop(&mut frame.stack);
+                    frame.stack.push(Value::Bool(relation(left, op, right)?));
+                }
+                Instruction::Unary(op) => {
+                    let operand = pop(&mut frame.stack);
+                    frame.stack.push(unary(op, operand)?);
+                }
+                Instruction::Index => {
+                    let index = pop(&mut frame.stack);
+                    let target = pop(&mut frame.stack);
+                    frame.stack.push(index_into(target, index)?);
+                }
+                Instruction::Select(member) => {
+                    let target = pop(&mut frame.stack);
+                    let field = match (&target, member) {
+                        (Value::Map(map), Member::Attribute(name)) => {
+                            map.map.get(&name.clone().into()).cloned()
+                        }
+                        _ => None,
+                    };
+                    frame.stack.push(match field {
+                        Some(field) => field,
+                        None => resolve_member(ctx, target, member)?,
+                    });
+                }
+                Instruction::List(len) => {
+                    let items = frame.stack.split_off(frame.stack.len() - len);
+                    frame.stack.push(Value::List(Arc::new(items)));
+                }
+                Instruction::Map(len) => {
+                    let entries = frame.stack.split_off(frame.stack.len() - 2 * len);
+                    let mut map = HashMap::with_capacity(*len);
+                    let mut entries = entries.into_iter();
+                    while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+                        let key: Key =
+                            key.try_into().map_err(ExecutionError::UnsupportedKeyType)?;
+                        map.insert(key, value);
+                    }
+                    frame.stack.push(Value::Map(map.into()));
+                }
+                Instruction::Jump(target) => pc = *target,
+                Instruction::JumpIfFalsy(target) => {
+                    if !is_truthy(&pop(&mut frame.stack)) {
+                        pc = *target;
+                    }
+                }
+                Instruction::And(operands) => {
+                    let (left, right) = &**operands;
+                    let result = logical(ctx, frame, left, right, false)?;
+                    frame.stack.push(Value::Bool(is_truthy(&result)));
+                }
+                Instruction::Or(operands) => {
+                    let (left, right) = &**operands;
+                    let result = logical(ctx, frame, left, right, true)?;
+                    frame.stack.push(result);
+                }
+                Instruction::Comprehension(kind, variable, filter, body) => {
+                    let target = pop(&mut frame.stack);
+                    let bodies = (filter.as_ref(), body);
+                    let result = comprehension(ctx, *kind, variable, bodies, target, frame)?;
+                    frame.stack.push(result);
+                }
+                Instruction::Resolve(expr) => frame.stack.push(ctx.resolve(expr)?),
+            }
+        }
+        Ok(pop(&mut frame.stack))
+    }
+}
+
+/// Evaluates `||` if `or`, or else `&&`, to the first operand whose truthiness
+/// decides the result or else the right one. An operand that fails only fails
+/// the result if the other doesn't decide it, or, unless errors are absorbed, if
+/// it is the left one.
+fn logical(ctx: &Context, frame: &mut Frame, left: &Plan, right: &Plan, or: bool) -> ResolveResult {
+    let left = operand(ctx, frame, left);
+    match left {
+        Ok(ref value) if is_truthy(value) == or => return left,
+        Err(_) if !frame.absorb_errors => return left,
+        _ => {}
+    }
+    let right = operand(ctx, frame, right);
+    match (left, right) {
+        (_, Ok(value)) if is_truthy(&value) == or => Ok(value),
+        (Ok(_), right) => right,
+        (left, right) => combine_failures(left, right),
+    }
+}
+
+/// Runs the plan of an operand, leaving the stack as it was if it fails
+fn operand(ctx: &Context, frame: &mut Frame, plan: &Plan) -> ResolveResult {
+    let depth = frame.stack.len();
+    let result = plan.run_on(ctx, frame);
+    frame.stack.truncate(depth);
+    result
+}
+
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().expect("plan stack underflow")
+}
+
+pub(crate) fn relation(left: Value, op: &RelationOp, right: Value) -> Result<bool, ExecutionError> {
+    if let Some(ordered) = types::compare(op, &left, &right) {
+        return ordered;
+    }
+    let ordering = |left: Value, right: Value| {
+        arithmetic::compare(&left, &right).ok_or(ExecutionError::ValuesNotComparable(left, right))
+    };
+    Ok(match op {
+        RelationOp::LessThan => ordering(left, right)? == Ordering::Less,
+        RelationOp::LessThanEq => ordering(left, right)? != Ordering::Greater,
+        RelationOp::GreaterThan => ordering(left, right)? == Ordering::Greater,
+        RelationOp::GreaterThanEq => ordering(left, right)? != Ordering::Less,
+        RelationOp::Equals => arithmetic::equals(&left, &right),
+        RelationOp::NotEquals => !arithmetic::equals(&left, &right),
+        RelationOp::In => contains(left, right)?,
+    })
+}
+
+/// `item in container`: whether a list has an item equal to `item`, a map has
+/// it as a key, or a string has it as a substring
+fn contains(item: Value, container: Value) -> Result<bool, ExecutionError> {
+    let error = |message: String| ExecutionError::function_error("@in", message);
+    match (item, container) {
+        (Value::String(item), Value::String(string)) => Ok(string.contains(&*item)),
+        (item, Value::String(_)) => Err(error(format!(
+            "can't look for {} in a string, only a string can be found in one",
+            name_of(&item)
+        ))),
+        (item, Value::List(items)) => Ok(items.iter().any(|x| arithmetic::equals(&item, x))),
+        (item, Value::Map(map)) if registered_type(&map).is_none() => {
+            Ok(keys(&item).iter().any(|key| map.map.contains_key(key)))
+        }
+        (item, container) => {
+            let hint = match item {
+                Value::String(_) => "; did you mean has(...) to test whether a field is set?",
+                _ => "",
+            };
+            Err(error(format!(
+                "can't look for {} in {}, 'in' needs a list, map or string{}",
+                name_of(&item),
+                type_name(&container),
+                hint
+            )))
+        }
+    }
+}
+
+/// The map keys equal to `value`: a number is the same key whether it is an int,
+/// a uint or a double with no fractional part
+fn keys(value: &Value) -> Vec<Key> {
+    match *value {
+        Value::Int(i) => [Some(Key::Int(i)), u64::try_from(i).ok().map(Key::Uint)]
+            .into_iter()
+            .flatten()
+            .collect(),
+        Value::UInt(u) => [Some(Key::Uint(u)), i64::try_from(u).ok().map(Key::Int)]
+            .into_iter()
+            .flatten()
+            .collect(),
+        Value::Float(f) if f.fract() == 0.0 => {
+            if (-9223372036854775808.0..9223372036854775808.0).contains(&f) {
+                keys(&Value::Int(f as i64))
+            } else if (0.0..18446744073709551616.0).contains(&f) {
+                vec![Key::Uint(f as u64)]
+            } else {
+                Vec::new()
+            }
+        }
+        _ => value.clone().try_into().into_iter().collect(),
+    }
+}
+
+fn unary(op: &UnaryOp, operand: Value) -> ResolveResult {
+    match op {
+        UnaryOp::Not => Ok(Value::Bool(!is_truthy(&operand))),
+        UnaryOp::DoubleNot => Ok(Value::Bool(is_truthy(&operand))),
+        UnaryOp::Minus => arithmetic::negate(operand),
+        UnaryOp::DoubleMinus => match operand {
+            Value::Int(_) | Value::UInt(_) | Value::Float(_) => Ok(operand),
+            value => Err(ExecutionError::UnsupportedUnaryOperator("negate", value)),
+        },
+    }
+}
+
+pub(crate) fn index_into(target: Value, index: Value) -> ResolveResult {
+    match (target, index) {
+        (Value::Bytes(b), Value::Int(index)) => bytes::index(&b, index),
+        (Value::List(items), Value::Int(index)) => {
+            Ok(items.get(index as usize).cloned().unwrap_or(Value::Null))
+        }
+        (Value::String(s), Value::Int(index)) => {
+            Ok(match s.get(index as usize..(index + 1) as usize) {
+                None => Value::Null,
+                Some(s) => Value::String(s.to_string().into()),
+            })
+        }
+        (
+            Value::Map(map),
+            index @ (Value::String(_) | Value::Bool(_) | Value::Int(_) | Value::UInt(_)),
+        ) => {
+            let key: Key = index
+                .try_into()
+                .map_err(ExecutionError::UnsupportedKeyType)?;
+            if is_hidden(&map, &key) {
+                return Ok(Value::Null);
+            }
+            Ok(map.get(&key).cloned().unwrap_or(Value::Null))
+        }
+        (Value::Map(_), index) => Err(ExecutionError::UnsupportedMapIndex(index)),
+        (Value::List(_), index) => Err(ExecutionError::UnsupportedListIndex(index)),
+        (value, index) => Err(ExecutionError::UnsupportedIndex(value, index)),
+    }
+}
+
+fn expression_calls(expr: &Expression, name: &str) -> bool {
+    let mut found = false;
+    map_children(expr, |child| {
+        found = found || expression_calls(child, name);
+        child.clone()
+    });
+    found
+        || matches!(expr, Expression::FunctionCall(function, _, _)
+            if matches!(&**function, Expression::Ident(ident) if ident.as_str() == name))
+}
+
+/// The items a comprehension macro iterates over: the items of a list, or the
+/// keys of a map in sorted order, so that which key `distinct` keeps doesn't
+/// depend on how the map hashes them
+pub(crate) fn items(
+    target: &Value,
+) -> Result<Box<dyn Iterator<Item = Value> + '_>, ExecutionError> {
+    match target {
+        Value::List(items) => Ok(Box::new(items.iter().cloned())),
+        Value::Map(map) => {
+            let mut keys: Vec<&Key> = map.map.keys().filter(|key| !is_hidden(map, key)).collect();
+            keys.sort();
+            Ok(Box::new(keys.into_iter().map(Value::from)))
+        }
+        _ => Err(target.error_expected_type(ValueType::List)),
+    }
+}
+
+/// Evaluates a comprehension macro, whose body is only evaluated for the items
+/// its filter, if it has one, is true for
+fn comprehension(
+    ctx: &Context,
+    kind: Macro,
+    variable: &Arc<String>,
+    (filter, body): (Option<&Plan>, &Plan),
+    target: Value,
+    frame: &mut Frame,
+) -> ResolveResult {
+    let items = items(&target)?;
+    let parallel = match (&target, kind, frame.parallel_threshold) {
+        (
+            Value::List(items),
+            Macro::Map
+            | Macro::Filter
+            | Macro::All
+            | Macro::Count
+            | Macro::GroupBy
+            | Macro::CountBy
+            | Macro::Distinct,
+            Some(threshold),
+        ) => items.len() >= threshold,
+        _ => false,
+    };
+    let resolves = body.resolves || filter.is_some_and(|filter| filter.resolves);
+
+    let slot = frame.locals.len();
+    frame.locals.push(Value::Null);
+    let result = match parallel {
+        #[cfg(feature = "threads")]
+        true => {
+            // Every item is evaluated up front, then folded in order exactly as below so
+            // the result (or error) is the same as evaluating sequentially
+            let outcomes: Vec<Option<(Value, ResolveResult)>> = items
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .map_init(
+                    || Frame {
+                        stack: Vec::new(),
+                        locals: frame.locals.clone(),
+                        parallel_threshold: frame.parallel_threshold,
+                        absorb_errors: frame.absorb_errors,
+                    },
+                    |worker, item| {
+                        let mut evaluate =
+                            |plan| evaluate_item(ctx, variable, plan, worker, slot, &item, None);
+                        match selected(filter.map(&mut evaluate))? {
+                            Err(error) => Some((item, Err(error))),
+                            Ok(()) => {
+                                let value = evaluate(body);
+                                Some((item, value))
+                            }
+                        }
+                    },
+                )
+                .collect();
+            fold_comprehension(kind, outcomes.into_iter().flatten())
+        }
+        // Without threads, lists over the threshold are evaluated in sequence too
+        _ => {
+            // The interpreter only needs to see the variable if it resolves part of the body
+            let mut scope = resolves.then(|| ctx.new_inner_scope());
+            let outcomes = items.filter_map(|item| {
+                let mut evaluate =
+                    |plan| evaluate_item(ctx, variable, plan, frame, slot, &item, scope.as_mut());
+                match selected(filter.map(&mut evaluate))? {
+                    Err(error) => Some((item, Err(error))),
+                    Ok(()) => {
+                        let value = evaluate(body);
+                        Some((item, value))
+                    }
+                }
+            });
+            fold_comprehension(kind, outcomes)
+        }
+    };
+    frame.locals.truncate(slot);
+    result
+}
+
+/// Whether an item is selected by the result of a filter, if there is one:
+/// None if it isn't, or the error the filter failed with
+fn selected(outcome: Option<ResolveResult>) -> Option<Result<(), ExecutionError>> {
+    match outcome {
+        None | Some(Ok(Value::Bool(true))) => Some(Ok(())),
+        Some(Ok(_)) => None,
+        Some(Err(error)) => Some(Err(error)),
+    }
+}
+
+fn evaluate_item(
+    ctx: &Context,
+    variable: &Arc<String>,
+    body: &Plan,
+    frame: &mut Frame,
+    slot: usize,
+    item: &Value,
+    scope: Option<&mut Context>,
+) -> ResolveResult {
+    frame.locals[slot] = item.clone();
+    if !body.resolves {
+        return body.run_on(ctx, frame);
+    }
+    match scope {
+        Some(scope) => {
+            scope.add_variable_from_value(variable.as_str(), item.clone());
+            body.run_on(scope, frame)
+        }
+        None => {
+            let mut scope = ctx.new_inner_scope();
+            scope.add_variable_from_value(variable.as_str(), item.clone());
+            body.run_on(&scope, frame)
+        }
+    }
+}
+
+/// Combines the result of evaluating the body for each item, in order
+///
+/// As with `&&` and `||`, `all` is false if the body is false for any item and
+/// `exists` true if it is true for any, even if it fails for others; only
+/// otherwise is the first error the result.
+pub(crate) fn fold_comprehension(
+    kind: Macro,
+    outcomes: impl Iterator<Item = (Value, ResolveResult)>,
+) -> ResolveResult {
+    let mut results = Vec::new();
+    let mut matched = false;
+    let mut counted = 0;
+    // The items of each group of `groupBy` and `countBy`
+    let mut groups: HashMap<Key, Vec<Value>> = HashMap::new();
+    // The keys `distinct` has kept an item for, in a set if they can be map keys
+    let (mut seen, mut seen_values) = (HashSet::new(), Vec::new());
+    let mut failed = None;
+    for (item, value) in outcomes {
+        let value = match (kind, value) {
+            (Macro::All | Macro::Exists, Err(error)) => {
+                failed.get_or_insert(error);
+                continue;
+            }
+            (_, value) => value?,
+        };
+        match (kind, value) {
+            (Macro::Map, value) => results.push(value),
+            (Macro::Filter, Value::Bool(true)) => results.push(item),
+            (Macro::All, Value::Bool(false)) => return Ok(Value::Bool(false)),
+            (Macro::Exists, Value::Bool(true)) => return Ok(Value::Bool(true)),
+            (Macro::ExistsOne, Value::Bool(true)) if matched => return Ok(Value::Bool(false)),
+            (Macro::ExistsOne, Value::Bool(true)) => matched = true,
+            (Macro::Count, Value::Bool(true)) => counted += 1,
+            (Macro::GroupBy | Macro::CountBy, key) => {
+                groups.entry(group_key(kind, key)?).or_default().push(item)
+            }
+            (Macro::Distinct, key) => {
+                let first = match TryInto::<Key>::try_into(key) {
+                    Ok(key) => seen.insert(key),
+                    Err(key) if seen_values.contains(&key) => false,
+                    Err(key) => {
+                        seen_values.push(key);
+                        true
+                    }
+                };
+                if first {
+                    results.push(item);
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(error) = failed {
+        return Err(error);
+    }
+    Ok(match kind {
+        Macro::Map | Macro::Filter | Macro::Distinct => Value::List(Arc::new(results)),
+        Macro::All => Value::Bool(true),
+        Macro::Exists => Value::Bool(false),
+        Macro::ExistsOne => Value::Bool(matched),
+        Macro::Count => Value::Int(counted),
+        Macro::GroupBy => Value::Map(
+            groups
+                .into_iter()
+                .map(|(key, items)| (key, Value::List(Arc::new(items))))
+                .collect::<HashMap<_, _>>()
+                .into(),
+        ),
+        Macro::CountBy => Value::Map(
+            groups
+                .into_iter()
+                .map(|(key, items)| (key, Value::Int(items.len() as i64)))
+                .collect::<HashMap<_, _>>()
+                .into(),
+        ),
+    })
+}
+
+/// The map key of the group of `groupBy` or `countBy` an item's key puts it in
+fn group_key(kind: Macro, key: Value) -> Result<Key, ExecutionError> {
+    key.try_into().map_err(|key: Value| {
+        let name = match kind {
+            Macro::CountBy => "countBy",
+            _ => "groupBy",
+        };
+        ExecutionError::function_error(
+            name,
+            format!(
+                "can't group by {}, map keys are int, uint, bool or string",
+                name_of(&key)
+            ),
+        )
+    })
+}
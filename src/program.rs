@@ -0,0 +1,334 @@
+use crate::cache::{self, Cache};
+use crate::coverage::Coverage;
+use crate::errors::EvalError;
+use crate::logging;
+use crate::memory;
+use crate::options::Options;
+use crate::originals::Originals;
+use crate::output::OutputTypes;
+use crate::plan::{self, Plan};
+use crate::serialize;
+use crate::warnings::{self, Reliance};
+use crate::{
+    check_result_size, compile, originals, outcome_into_py, output_types, parse_on_error,
+    parse_output, repr_of, resolve_mode, Job, Outcome,
+};
+use log::debug;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyTuple};
+use std::sync::OnceLock;
+
+/// A CEL expression that has been compiled once and can be evaluated many times.
+///
+/// Evaluating a Program operates on the parsed expression directly, so the
+/// source isn't parsed again for every evaluation. An optimized Program is also
+/// flattened into an execution [`Plan`], which is faster to evaluate repeatedly.
+///
+/// Programs can be pickled, so worker processes can be sent compiled programs.
+#[pyclass(frozen, module = "cel")]
+pub struct Program {
+    source: String,
+    expression: cel_parser::Expression,
+    plan: Option<Plan>,
+    cache: Cache,
+    /// The outcome of a constant expression in the default mode, once asked for
+    constant: OnceLock<Outcome>,
+    /// The conveniences of Python mode the expression relies on, found on its
+    /// first evaluation
+    reliances: OnceLock<Vec<Reliance>>,
+    /// A name given to tell programs apart, e.g. the policy the expression is
+    name: Option<String>,
+}
+
+#[pymethods]
+impl Program {
+    /// With `parallel_threshold` set, an optimized program maps, filters and checks
+    /// `all` over lists of at least that many items in parallel, unless the expression
+    /// calls a Python function.
+    ///
+    /// A `name`, such as `"rbac.allow_admin"`, is included in the errors of the
+    /// program and the records it logs, to tell which of many programs failed.
+    #[new]
+    #[pyo3(signature = (source, optimize=false, parallel_threshold=None, name=None))]
+    pub fn new(
+        source: String,
+        optimize: bool,
+        parallel_threshold: Option<usize>,
+        name: Option<String>,
+    ) -> PyResult<Self> {
+        if parallel_threshold.is_some() && !optimize {
+            return Err(PyValueError::new_err(
+                "parallel_threshold requires an optimized program",
+            ));
+        }
+        let mut program =
+            Program::parse(source).map_err(|e| e.named(name.as_deref()).to_py_err())?;
+        program.name = name;
+        program.plan = optimize.then(|| {
+            let mut plan = Plan::new(&program.expression);
+            plan.parallel_threshold = parallel_threshold;
+            plan
+        });
+        Ok(program)
+    }
+
+    /// Compile an expression, equivalent to `Program(source, ...)`
+    #[staticmethod]
+    #[pyo3(signature = (source, optimize=false, parallel_threshold=None, name=None))]
+    fn compile(
+        source: String,
+        optimize: bool,
+        parallel_threshold: Option<usize>,
+        name: Option<String>,
+    ) -> PyResult<Self> {
+        Program::new(source, optimize, parallel_threshold, name)
+    }
+
+    #[getter]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    #[getter]
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    #[getter]
+    fn optimized(&self) -> bool {
+        self.plan.is_some()
+    }
+
+    /// Evaluate the program, accepting the same options as `cel.evaluate`
+    ///
+    /// With `cache=True` results are remembered by the values of the variables
+    /// the expression refers to, and returned again for evaluations with the
+    /// same values. Evaluations that call Python functions aren't cached.
+    ///
+    /// With a `cel.Coverage` passed as `coverage`, the parts of the expression
+    /// the evaluation exercises are recorded in it. Such evaluations aren't
+    /// cached.
+    #[pyo3(signature = (evaluation_context=None, safe_navigation=None, on_error="raise", unknowns=None, output="python", mode=None, cache=false, max_result_size=None, coverage=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn evaluate(
+        &self,
+        py: Python<'_>,
+        evaluation_context: Option<&Bound<'_, PyAny>>,
+        safe_navigation: Option<bool>,
+        on_error: &str,
+        unknowns: Option<Vec<String>>,
+        output: &str,
+        mode: Option<&Bound<'_, PyAny>>,
+        cache: bool,
+        max_result_size: Option<usize>,
+        coverage: Option<&Bound<'_, Coverage>>,
+    ) -> PyResult<PyObject> {
+        let return_errors = parse_on_error(on_error)?;
+        let opaque = parse_output(output)?;
+        let options = resolve_mode(evaluation_context, mode)?;
+        self.warn(py, &options)?;
+        let job = Job::new(evaluation_context, safe_navigation, unknowns, options)?;
+        let outcome = match (coverage, cache) {
+            (Some(coverage), _) => {
+                self.named(coverage.get().run(job, &self.source, &self.expression))
+            }
+            (None, true) => match self.cache.key(&self.expression, &job) {
+                Some(key) => match self.cache.get(&key) {
+                    Some(outcome) => outcome,
+                    None => {
+                        let outcome = self.run(job);
+                        self.cache.insert(key, outcome.clone());
+                        outcome
+                    }
+                },
+                None => self.run(job),
+            },
+            (None, false) => self.run(job),
+        };
+        check_result_size(&outcome, max_result_size)?;
+        let output = output_types(evaluation_context);
+        let originals = originals(evaluation_context);
+        outcome_into_py(py, outcome, return_errors, opaque, output, &originals)
+    }
+
+    /// The `hits` and `misses` of evaluations with `cache=True`, and the number
+    /// of results cached (`size`), at most `capacity`
+    fn cache_info<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let (hits, misses, size) = self.cache.info();
+        let info = PyDict::new_bound(py);
+        info.set_item("hits", hits)?;
+        info.set_item("misses", misses)?;
+        info.set_item("size", size)?;
+        info.set_item("capacity", cache::CAPACITY)?;
+        Ok(info)
+    }
+
+    fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
+    /// Approximate number of bytes retained by the program: its source, parsed
+    /// expression, execution plan and cached results
+    fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Program>()
+            + memory::string_heap(&self.source)
+            + memory::expression_heap(&self.expression)
+            + self.plan.as_ref().map_or(0, Plan::heap_size)
+            + self.cache.heap_size()
+            + match self.constant.get() {
+                Some(Outcome::Value(value)) => memory::value_heap(value),
+                _ => 0,
+            }
+    }
+
+    /// Whether the expression refers to no variables or functions, so that it
+    /// evaluates to the same value every time
+    fn is_constant(&self) -> bool {
+        plan::is_constant(&self.expression)
+    }
+
+    /// The value of a constant expression, computed on first use and then kept,
+    /// or evaluated in `mode` when one is given.
+    ///
+    /// Raises a ValueError if the expression isn't constant, and the error of
+    /// evaluating it if that fails, e.g. for `{'a': 1}.b`.
+    #[pyo3(signature = (mode=None))]
+    fn constant_value(
+        &self,
+        py: Python<'_>,
+        mode: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        if !self.is_constant() {
+            return Err(PyValueError::new_err(
+                "the expression isn't constant, it refers to variables or functions",
+            ));
+        }
+        let outcome = match (mode, self.constant.get()) {
+            (Some(mode), _) => self.run(Job::new(None, None, None, Options::from_mode(mode)?)?),
+            (None, Some(outcome)) => outcome.clone(),
+            (None, None) => {
+                let outcome = self.run(Job::new(None, None, None, Options::default())?);
+                self.constant.get_or_init(|| outcome).clone()
+            }
+        };
+        outcome_into_py(
+            py,
+            outcome,
+            false,
+            false,
+            OutputTypes::default(),
+            &Originals::default(),
+        )
+    }
+
+    /// A stable hash of the parsed expression as a hex string, the same for
+    /// sources that only differ in whitespace or comments, so semantically
+    /// identical expressions can be recognised across processes and deployments
+    fn fingerprint(&self) -> String {
+        serialize::fingerprint(&self.expression)
+    }
+
+    /// Serialize the compiled program to bytes, which `Program.loads` turns
+    /// back into a program without parsing the source again
+    fn dumps<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let parts = serialize::Parts {
+            source: self.source.clone(),
+            name: self.name.clone(),
+            expression: self.expression.clone(),
+            optimize: self.plan.is_some(),
+            parallel_threshold: self.plan.as_ref().and_then(|plan| plan.parallel_threshold),
+        };
+        PyBytes::new_bound(py, &serialize::dump(&parts))
+    }
+
+    /// Load a program serialized by `Program.dumps`
+    #[staticmethod]
+    fn loads(data: &[u8]) -> PyResult<Self> {
+        let parts = serialize::load(data)
+            .map_err(|e| PyValueError::new_err(format!("Failed to load program: {}", e)))?;
+        let plan = parts.optimize.then(|| {
+            let mut plan = Plan::new(&parts.expression);
+            plan.parallel_threshold = parts.parallel_threshold;
+            plan
+        });
+        Ok(Program {
+            source: parts.source,
+            expression: parts.expression,
+            plan,
+            cache: Cache::default(),
+            constant: OnceLock::new(),
+            reliances: OnceLock::new(),
+            name: parts.name,
+        })
+    }
+
+    fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyTuple>> {
+        let loads = py.get_type_bound::<Program>().getattr("loads")?;
+        Ok(PyTuple::new_bound(
+            py,
+            [loads, PyTuple::new_bound(py, [self.dumps(py)]).into_any()],
+        ))
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        match &self.name {
+            Some(name) => format!(
+                "Program({}, name={})",
+                repr_of(py, &self.source),
+                repr_of(py, name)
+            ),
+            None => format!("Program({})", repr_of(py, &self.source)),
+        }
+    }
+}
+
+impl Program {
+    /// Compile an expression without optimizing it
+    pub fn parse(source: String) -> Result<Self, EvalError> {
+        let expression = compile(&source)?;
+        Ok(Program::from_expression(source, expression))
+    }
+
+    /// A program for an expression that has already been parsed from `source`
+    pub(crate) fn from_expression(source: String, expression: cel_parser::Expression) -> Self {
+        Program {
+            source,
+            expression,
+            plan: None,
+            cache: Cache::default(),
+            constant: OnceLock::new(),
+            reliances: OnceLock::new(),
+            name: None,
+        }
+    }
+
+    pub(crate) fn expression(&self) -> &cel_parser::Expression {
+        &self.expression
+    }
+
+    /// Evaluate the program for a job that has already been taken from its arguments
+    pub(crate) fn run(&self, job: Job) -> Outcome {
+        if let Some(name) = &self.name {
+            debug!(target: logging::EVAL, "Evaluating program '{}': {}", name, self.source);
+        }
+        self.named(job.run(&self.source, &self.expression, self.plan.as_ref()))
+    }
+
+    /// Warns if the expression relies on a convenience of Python mode `options`
+    /// enables
+    pub(crate) fn warn(&self, py: Python<'_>, options: &Options) -> PyResult<()> {
+        let reliances = self
+            .reliances
+            .get_or_init(|| warnings::reliances(&self.expression, &Options::PYTHON));
+        warnings::warn(py, &self.source, reliances, options)
+    }
+
+    /// `outcome` with the name of the program on its error
+    fn named(&self, outcome: Outcome) -> Outcome {
+        match (outcome, &self.name) {
+            (Outcome::Error(error), Some(name)) => Outcome::Error(error.named(Some(name))),
+            (outcome, _) => outcome,
+        }
+    }
+}
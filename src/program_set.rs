@@ -0,0 +1,233 @@
+//! `cel.compile_many`, which compiles a bundle of named expressions, such as
+//! the rules of a policy, into a `ProgramSet` that evaluates all of them
+//! against one context.
+//!
+//! A set also knows the paths of the context its programs read, so that
+//! `project_context` can copy just those out of a large context before it is
+//! converted.
+use crate::dataflow::free_variables;
+use crate::diagnose::called_functions;
+use crate::explain::read_paths;
+use crate::plan::MACROS;
+use crate::program::Program;
+use crate::suggest::names;
+use crate::{
+    originals, outcome_into_py, output_types, parse_on_error, parse_output, repr_of, resolve_mode,
+    Job,
+};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Named programs compiled together by `cel.compile_many`, or by creating a
+/// `ProgramSet` from the same arguments.
+///
+/// `evaluate_all` converts the context and builds the environment of the
+/// evaluation once, and evaluates every program in it.
+#[pyclass(frozen, module = "cel")]
+pub struct ProgramSet {
+    programs: Vec<(String, Py<Program>)>,
+    /// The parts of the context the programs read
+    projection: Projection,
+}
+
+/// The part of a value that is read: all of it, or only some of its fields
+enum Projection {
+    All,
+    Fields(BTreeMap<String, Projection>),
+}
+
+impl Projection {
+    /// Adds a path of field names to what is read. A path that a shorter one
+    /// is a prefix of is already read in full.
+    fn insert(&mut self, path: &[&str]) {
+        let Projection::Fields(fields) = self else {
+            return;
+        };
+        match path.split_first() {
+            None => *self = Projection::All,
+            Some((first, rest)) => fields
+                .entry(first.to_string())
+                .or_insert_with(|| Projection::Fields(BTreeMap::new()))
+                .insert(rest),
+        }
+    }
+
+    /// The read part of `value`, sharing what is read in full. Only dicts are
+    /// projected, other values are read in full.
+    fn project<'py>(&self, value: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        match (self, value.downcast::<PyDict>()) {
+            (Projection::Fields(fields), Ok(dict)) => {
+                let projected = PyDict::new_bound(value.py());
+                for (name, projection) in fields {
+                    if let Some(field) = dict.get_item(name)? {
+                        projected.set_item(name, projection.project(&field)?)?;
+                    }
+                }
+                Ok(projected.into_any())
+            }
+            _ => Ok(value.clone()),
+        }
+    }
+}
+
+#[pymethods]
+impl ProgramSet {
+    #[new]
+    #[pyo3(signature = (expressions, optimize=false))]
+    fn new(py: Python<'_>, expressions: &Bound<'_, PyAny>, optimize: bool) -> PyResult<Self> {
+        compile_many(py, expressions, optimize)
+    }
+
+    /// The names of the programs, in the order they were given
+    #[getter]
+    fn names(&self) -> Vec<String> {
+        self.programs.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// The variables any of the programs refers to, in sorted order
+    #[getter]
+    fn variables(&self) -> Vec<String> {
+        let variables = self
+            .programs
+            .iter()
+            .flat_map(|(_, program)| free_variables(program.get().expression()));
+        variables.collect::<BTreeSet<_>>().into_iter().collect()
+    }
+
+    /// The functions any of the programs calls, other than macros, in sorted
+    /// order
+    #[getter]
+    fn functions(&self) -> Vec<String> {
+        let functions = self
+            .programs
+            .iter()
+            .flat_map(|(_, program)| called_functions(program.get().expression()))
+            .filter(|name| name != "has" && !MACROS.contains(&name.as_str()));
+        functions.collect::<BTreeSet<_>>().into_iter().collect()
+    }
+
+    /// Evaluate every program against `evaluation_context`, returning a dict of
+    /// their results by name. Accepts the options of `cel.evaluate`; with
+    /// `on_error="return"` a failing program's result is its `cel.EvalError`,
+    /// otherwise the first program that fails raises its error.
+    #[pyo3(signature = (evaluation_context=None, on_error="raise", output="python", mode=None))]
+    fn evaluate_all<'py>(
+        &self,
+        py: Python<'py>,
+        evaluation_context: Option<&Bound<'py, PyAny>>,
+        on_error: &str,
+        output: &str,
+        mode: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let return_errors = parse_on_error(on_error)?;
+        let opaque = parse_output(output)?;
+        let options = resolve_mode(evaluation_context, mode)?;
+        let job = Job::new(evaluation_context, None, None, options)?;
+        let output = output_types(evaluation_context);
+        let originals = originals(evaluation_context);
+        let results = PyDict::new_bound(py);
+        for (name, program) in &self.programs {
+            let program = program.get();
+            program.warn(py, &options)?;
+            let outcome = program.run(job.clone());
+            results.set_item(
+                name,
+                outcome_into_py(py, outcome, return_errors, opaque, output, &originals)?,
+            )?;
+        }
+        Ok(results)
+    }
+
+    /// A dict of only the parts of `evaluation_context` the programs read: the
+    /// variables they refer to and Python functions they call, and for dicts,
+    /// only the fields selected from them. Evaluating the programs against it
+    /// gives the same results, without converting the rest of a large context.
+    fn project_context<'py>(
+        &self,
+        evaluation_context: &Bound<'py, PyDict>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.projection.project(evaluation_context.as_any())
+    }
+
+    fn __len__(&self) -> usize {
+        self.programs.len()
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.programs.iter().any(|(other, _)| other == name)
+    }
+
+    fn __getitem__(&self, py: Python<'_>, name: &str) -> PyResult<Py<Program>> {
+        self.programs
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, program)| program.clone_ref(py))
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!("ProgramSet({})", repr_of(py, self.names()))
+    }
+}
+
+/// Compile named expressions into a `ProgramSet`.
+///
+/// `expressions` is a dict or an iterable of `(name, expression)` pairs. Each
+/// source is compiled into a `Program` named after it, so its errors say which
+/// rule failed, and a `Program` is used as it is. Raises the error of the first
+/// expression that fails to compile, or a ValueError if a name is used twice.
+#[pyfunction]
+#[pyo3(signature = (expressions, optimize=false))]
+pub fn compile_many(
+    py: Python<'_>,
+    expressions: &Bound<'_, PyAny>,
+    optimize: bool,
+) -> PyResult<ProgramSet> {
+    let pairs = match expressions.downcast::<PyDict>() {
+        Ok(dict) => dict.items().into_any(),
+        Err(_) => expressions.clone(),
+    };
+    let mut programs: Vec<(String, Py<Program>)> = Vec::new();
+    for pair in pairs.iter()? {
+        let (name, expression): (String, Bound<'_, PyAny>) = pair?.extract()?;
+        if programs.iter().any(|(existing, _)| *existing == name) {
+            return Err(PyValueError::new_err(format!(
+                "the name '{}' is used by more than one expression",
+                name
+            )));
+        }
+        let program = match expression.downcast::<Program>() {
+            Ok(program) => program.clone().unbind(),
+            Err(_) => {
+                let program =
+                    Program::new(expression.extract()?, optimize, None, Some(name.clone()))?;
+                Py::new(py, program)?
+            }
+        };
+        programs.push((name, program));
+    }
+    Ok(ProgramSet::from_programs(programs))
+}
+
+impl ProgramSet {
+    fn from_programs(programs: Vec<(String, Py<Program>)>) -> Self {
+        let mut projection = Projection::Fields(BTreeMap::new());
+        for (_, program) in &programs {
+            let expression = program.get().expression();
+            let mut paths = Vec::new();
+            read_paths(expression, &mut Vec::new(), &mut paths);
+            for path in paths {
+                projection.insert(&names(path));
+            }
+            for function in called_functions(expression) {
+                projection.insert(&[&function]);
+            }
+        }
+        ProgramSet {
+            programs,
+            projection,
+        }
+    }
+}
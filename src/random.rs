@@ -0,0 +1,153 @@
+//! `rand()`, `sample(probability)` and `bucket(id, n)`, for percentage rollouts
+//! in feature-flag expressions.
+//!
+//! `bucket` hashes its id with SHA-256, so an id lands in the same bucket in
+//! every process and release. `rand` and `sample` draw from a generator kept by
+//! the evaluation running on the thread, as memoized calls are. It is seeded
+//! with `Context(seed=...)`, kept in the environment as the variable [`SEED`]
+//! that expressions can't refer to, so that every evaluation draws the same
+//! numbers, and otherwise with a random seed.
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+/// Internal variable holding the seed of a context
+pub const SEED: &str = "@seed";
+
+/// The functions that draw from the evaluation's generator, which can't be
+/// called from the threads of a parallel comprehension
+pub const DRAWS: [&str; 2] = ["rand", "sample"];
+
+thread_local! {
+    static GENERATOR: RefCell<Option<Option<u64>>> = const { RefCell::new(None) };
+}
+
+/// Keeps the generator of the evaluation on this thread until it is dropped.
+/// It is seeded on the first draw. A Python function that evaluates an
+/// expression itself gets a scope of its own.
+pub struct Scope {
+    outer: Option<Option<u64>>,
+}
+
+impl Scope {
+    pub fn enter() -> Scope {
+        Scope {
+            outer: GENERATOR.with(|generator| generator.replace(Some(None))),
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let outer = self.outer.take();
+        GENERATOR.with(|generator| *generator.borrow_mut() = outer);
+    }
+}
+
+/// The seed of the context, or a random one
+fn seed(ftx: &FunctionContext) -> u64 {
+    match ftx.ptx.get_variable(SEED) {
+        Ok(Value::UInt(seed)) => seed,
+        _ => RandomState::new().hash_one(std::thread::current().id()),
+    }
+}
+
+/// The next number of the SplitMix64 sequence of `state`
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// The next double in [0, 1) of the evaluation's generator
+fn draw(ftx: &FunctionContext) -> f64 {
+    let next = GENERATOR.with(|generator| match &mut *generator.borrow_mut() {
+        Some(state) => split_mix(state.get_or_insert_with(|| seed(ftx))),
+        // Outside an evaluation there is no generator to keep
+        None => split_mix(&mut seed(ftx)),
+    });
+    (next >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Returns a random double in [0, 1).
+///
+/// # Examples
+/// ```cel
+/// rand() < 0.5
+/// ```
+pub fn rand(ftx: &FunctionContext) -> ResolveResult {
+    if !ftx.args.is_empty() || ftx.this.is_some() {
+        return Err(ExecutionError::invalid_argument_count(0, ftx.args.len()));
+    }
+    Ok(Value::Float(draw(ftx)))
+}
+
+/// Returns true with the given probability.
+///
+/// # Examples
+/// ```cel
+/// sample(0.1)
+/// ```
+pub fn sample(ftx: &FunctionContext) -> ResolveResult {
+    let [probability] = &ftx.args[..] else {
+        return Err(ExecutionError::invalid_argument_count(1, ftx.args.len()));
+    };
+    let probability = match ftx.ptx.resolve(probability)? {
+        Value::Float(probability) => probability,
+        Value::Int(probability) => probability as f64,
+        Value::UInt(probability) => probability as f64,
+        other => return Err(ftx.error(format!("expected a probability, got {}", other.type_of()))),
+    };
+    if !(0.0..=1.0).contains(&probability) {
+        return Err(ftx.error(format!(
+            "probability must be between 0 and 1, got {}",
+            probability
+        )));
+    }
+    Ok(Value::Bool(draw(ftx) < probability))
+}
+
+/// Returns the bucket from 0 to `n - 1` that an id falls in, from a SHA-256
+/// hash of the id salted with the seed of the context. Ints are hashed as
+/// their decimal string, so `bucket(42, n)` and `bucket('42', n)` agree.
+///
+/// # Examples
+/// ```cel
+/// bucket(user.id, 100) < 20
+/// ```
+pub fn bucket(ftx: &FunctionContext) -> ResolveResult {
+    let [id, n] = &ftx.args[..] else {
+        return Err(ExecutionError::invalid_argument_count(2, ftx.args.len()));
+    };
+    let id = match ftx.ptx.resolve(id)? {
+        Value::String(id) => id.as_bytes().to_vec(),
+        Value::Bytes(id) => id.to_vec(),
+        Value::Int(id) => id.to_string().into_bytes(),
+        Value::UInt(id) => id.to_string().into_bytes(),
+        other => return Err(ftx.error(format!("can't bucket {}", other.type_of()))),
+    };
+    let n = match ftx.ptx.resolve(n)? {
+        Value::Int(n) if n > 0 => n as u64,
+        Value::UInt(n) if n > 0 => n,
+        Value::Int(n) => return Err(ftx.error(format!("can't make {} buckets", n))),
+        Value::UInt(n) => return Err(ftx.error(format!("can't make {} buckets", n))),
+        other => {
+            return Err(ftx.error(format!(
+                "expected a number of buckets, got {}",
+                other.type_of()
+            )))
+        }
+    };
+    let mut hasher = Sha256::new();
+    if let Ok(Value::UInt(seed)) = ftx.ptx.get_variable(SEED) {
+        hasher.update(format!("{}:", seed));
+    }
+    hasher.update(&id);
+    let hash = hasher.finalize();
+    let hash = u64::from_be_bytes(hash[..8].try_into().expect("a SHA-256 hash is 32 bytes"));
+    Ok(Value::Int((hash % n) as i64))
+}
@@ -0,0 +1,313 @@
+//! `cel.parse_lenient`, which parses expressions that have syntax errors into
+//! as much of the expression as can be made out, for editors.
+//!
+//! The parser stops at the first error, so errors are repaired in the source one
+//! at a time until it parses: what the parser expected at the end of the source
+//! is inserted, a missing operand is filled in with [`MISSING`] and any other
+//! token the parser couldn't use is deleted.
+use crate::program::Program;
+use crate::repr_of;
+use crate::tokenize::{next_token, tokens};
+use crate::transform::map_children;
+use cel_parser::{parse_bytes, parse_string, Expression, Member, ParseError, ParseSequenceError};
+use pyo3::prelude::*;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Name of the variable that stands in for a missing part of an expression,
+/// which can't be written in CEL so evaluating it always fails
+pub const MISSING: &str = "@error";
+
+/// What is inserted into the source for a missing part, replaced by [`MISSING`]
+/// once the source parses
+const PLACEHOLDER: &str = "__cel_missing__";
+
+/// The terminals of the grammar, as listed in the `expected` of parse errors
+const IDENT_TERMINAL: &str = r##"r#"[_a-zA-Z][_a-zA-Z0-9]*"#"##;
+const OPERAND_TERMINAL: &str = r#""true""#;
+
+/// A syntax error found by `cel.parse_lenient`
+#[pyclass(frozen, module = "cel")]
+pub struct Diagnostic {
+    #[pyo3(get)]
+    message: String,
+    /// Character offset of the start of the error in the expression
+    #[pyo3(get)]
+    start: usize,
+    /// Character offset of the end of the error, the same as its start for
+    /// something missing
+    #[pyo3(get)]
+    end: usize,
+}
+
+#[pymethods]
+impl Diagnostic {
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!(
+            "Diagnostic(message={}, start={}, end={})",
+            repr_of(py, &self.message),
+            self.start,
+            self.end
+        )
+    }
+}
+
+/// Parse an expression even if it has syntax errors, returning a Program of
+/// what could be made out and a list of Diagnostics of every error, in the
+/// order they appear in the expression.
+///
+/// Missing operands and names are filled in with a variable that is never
+/// defined, so the partial program fails if it's evaluated. The program is None
+/// if the expression couldn't be repaired.
+#[pyfunction]
+pub fn parse_lenient(expression: &str) -> (Option<Program>, Vec<Diagnostic>) {
+    let (parsed, errors) = recover(expression);
+    let program = parsed.map(|parsed| Program::from_expression(expression.to_string(), parsed));
+    let diagnostics = errors
+        .into_iter()
+        .map(|error| Diagnostic {
+            message: error.message,
+            start: expression[..error.range.start].chars().count(),
+            end: expression[..error.range.end].chars().count(),
+        })
+        .collect();
+    (program, diagnostics)
+}
+
+/// A syntax error, with byte ranges into the expression
+pub struct SyntaxError {
+    pub message: String,
+    pub range: Range<usize>,
+    /// Another part of the expression the error is due to, such as the bracket
+    /// left open for a missing closing bracket
+    pub related: Option<(String, Range<usize>)>,
+}
+
+/// Parse as much of `expression` as can be made out, along with its syntax
+/// errors in the order they appear in it
+pub fn recover(expression: &str) -> (Option<Expression>, Vec<SyntaxError>) {
+    let mut repair = Repair {
+        text: expression.to_string(),
+        edits: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    // Literals the parser can read but not convert would make it panic
+    let invalid: Vec<(Range<usize>, String)> = tokens(expression)
+        .into_iter()
+        .filter_map(|(kind, range)| {
+            invalid_literal(kind, &expression[range.clone()]).map(|message| (range, message))
+        })
+        .collect();
+    for (range, message) in invalid.into_iter().rev() {
+        repair.replace(range, &format!(" {} ", PLACEHOLDER), message, None);
+    }
+
+    let mut parsed = None;
+    for _ in 0..expression.len() * 2 + 16 {
+        match cel_parser::parse(&repair.text) {
+            Ok(expr) => {
+                parsed = Some(fill_missing(&expr));
+                break;
+            }
+            Err(error) => {
+                if !repair.fix(&error) {
+                    break;
+                }
+            }
+        }
+    }
+    let mut errors = repair.errors;
+    errors.sort_by_key(|error| (error.range.start, error.range.end));
+    (parsed, errors)
+}
+
+/// A change to the source, at a byte offset into the source as it was then
+struct Edit {
+    at: usize,
+    removed: usize,
+    inserted: usize,
+}
+
+/// The source being repaired and the errors repaired so far
+struct Repair {
+    text: String,
+    edits: Vec<Edit>,
+    errors: Vec<SyntaxError>,
+}
+
+impl Repair {
+    /// Repair the error the parser stopped at, returning false if it can't be
+    fn fix(&mut self, error: &ParseError) -> bool {
+        let (Some(start), end) = (&error.span.start, &error.span.end) else {
+            return false;
+        };
+        let start = start.absolute;
+        let end = end.as_ref().map_or(start, |end| end.absolute);
+        let token = &self.text[start..end];
+        let expects = |terminal: &str| error.expected.iter().any(|t| t == terminal);
+
+        if error.msg == "invalid token" {
+            let rest = &self.text[start..];
+            let (_, length) = next_token(rest);
+            let message = match rest.chars().next() {
+                Some('"' | '\'') => "unterminated string".to_string(),
+                Some(c) => format!("unexpected character '{}'", c),
+                None => return false,
+            };
+            self.replace(start..start + length, "", message, None);
+        } else if error.msg == "unrecognized eof" {
+            match innermost_open(&self.text[..start]) {
+                Some((closer, open)) if expects(&format!("\"{}\"", closer)) => {
+                    let related = ("the bracket left open".to_string(), open);
+                    let message = format!("expected '{}'", closer);
+                    self.replace(start..start, closer, message, Some(related))
+                }
+                _ if expects(OPERAND_TERMINAL) => self.insert_missing(start, "an expression"),
+                _ if expects(IDENT_TERMINAL) => self.insert_missing(start, "a name"),
+                _ if expects("\":\"") => {
+                    self.replace(start..start, ":", "expected ':'".into(), None)
+                }
+                _ => return false,
+            }
+        } else if error.msg.starts_with("unrecognized token") && expects(OPERAND_TERMINAL) {
+            self.insert_missing(start, &format!("an expression before '{}'", token));
+        } else if error.msg.starts_with("unrecognized token") && expects(IDENT_TERMINAL) {
+            self.insert_missing(start, &format!("a name before '{}'", token));
+        } else if start < end {
+            let message = format!("unexpected '{}'", token);
+            self.replace(start..end, "", message, None);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn insert_missing(&mut self, at: usize, what: &str) {
+        let placeholder = format!(" {} ", PLACEHOLDER);
+        self.replace(at..at, &placeholder, format!("expected {}", what), None);
+    }
+
+    /// Replace `range` of the source with `text`, recording the error it repairs
+    fn replace(
+        &mut self,
+        range: Range<usize>,
+        text: &str,
+        message: String,
+        related: Option<(String, Range<usize>)>,
+    ) {
+        let original = |range: Range<usize>| self.original(range.start)..self.original(range.end);
+        self.errors.push(SyntaxError {
+            message,
+            range: original(range.clone()),
+            related: related.map(|(message, range)| (message, original(range))),
+        });
+        self.text.replace_range(range.clone(), text);
+        self.edits.push(Edit {
+            at: range.start,
+            removed: range.len(),
+            inserted: text.len(),
+        });
+    }
+
+    /// The offset into the original expression of an offset into the source,
+    /// which is where the insertion was made for offsets into inserted text
+    fn original(&self, mut offset: usize) -> usize {
+        for edit in self.edits.iter().rev() {
+            if offset >= edit.at + edit.inserted {
+                offset = offset - edit.inserted + edit.removed;
+            } else if offset > edit.at {
+                offset = edit.at;
+            }
+        }
+        offset
+    }
+}
+
+/// The closing bracket of the innermost bracket left open in `text`, and where
+/// it was opened
+fn innermost_open(text: &str) -> Option<(&'static str, Range<usize>)> {
+    let mut open = Vec::new();
+    for (kind, range) in tokens(text) {
+        match (kind, &text[range.clone()]) {
+            ("punctuation", "(") => open.push((")", range)),
+            ("punctuation", "[") => open.push(("]", range)),
+            ("punctuation", "{") => open.push(("}", range)),
+            ("punctuation", ")" | "]" | "}") => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    open.pop()
+}
+
+/// Why a literal token can't be converted to a value the way the parser does,
+/// if it can't
+/// The first literal in `expression` that the parser would panic on, with its
+/// byte range and why it is invalid
+pub(crate) fn first_invalid_literal(expression: &str) -> Option<(Range<usize>, String)> {
+    tokens(expression).into_iter().find_map(|(kind, range)| {
+        invalid_literal(kind, &expression[range.clone()]).map(|message| (range, message))
+    })
+}
+
+fn invalid_literal(kind: &str, text: &str) -> Option<String> {
+    let hex = |text: &str| text.replace(['x', 'X', 'u', 'U'], "");
+    let error = match kind {
+        "int" if text.contains(['x', 'X']) => {
+            i64::from_str_radix(&hex(text), 16).err()?.to_string()
+        }
+        "int" => text.parse::<i64>().err()?.to_string(),
+        "uint" if text.contains(['x', 'X']) => {
+            u64::from_str_radix(&hex(text), 16).err()?.to_string()
+        }
+        "uint" => text[..text.len() - 1].parse::<u64>().err()?.to_string(),
+        "string" => describe(parse_string(text).err()?),
+        "bytes" => describe(parse_bytes(&text[2..text.len() - 1]).err()?),
+        _ => return None,
+    };
+    Some(format!("invalid {} literal {}: {}", kind, text, error))
+}
+
+fn describe(error: ParseSequenceError) -> String {
+    match error {
+        ParseSequenceError::InvalidSymbol { symbol, .. } => format!("invalid symbol {}", symbol),
+        ParseSequenceError::InvalidEscape { escape, .. } => format!("invalid escape {}", escape),
+        ParseSequenceError::InvalidUnicode { .. } => "invalid unicode escape".to_string(),
+        ParseSequenceError::MissingOpeningQuote | ParseSequenceError::MissingClosingQuote => {
+            "missing quote".to_string()
+        }
+    }
+}
+
+/// `expr` with the placeholders inserted for missing parts replaced by [`MISSING`]
+fn fill_missing(expr: &Expression) -> Expression {
+    let missing = |name: &Arc<String>| match name.as_str() {
+        PLACEHOLDER => Arc::new(MISSING.to_string()),
+        _ => name.clone(),
+    };
+    match expr {
+        Expression::Ident(name) => Expression::Ident(missing(name)),
+        Expression::Member(target, member) => Expression::Member(
+            fill_missing(target).into(),
+            match &**member {
+                Member::Attribute(name) => Member::Attribute(missing(name)),
+                Member::Index(index) => Member::Index(fill_missing(index).into()),
+                Member::Fields(fields) => Member::Fields(
+                    fields
+                        .iter()
+                        .map(|(name, value)| (missing(name), fill_missing(value)))
+                        .collect(),
+                ),
+            }
+            .into(),
+        ),
+        Expression::FunctionCall(function, target, args) => Expression::FunctionCall(
+            fill_missing(function).into(),
+            target.as_ref().map(|target| fill_missing(target).into()),
+            args.iter().map(fill_missing).collect(),
+        ),
+        _ => map_children(expr, fill_missing),
+    }
+}
@@ -0,0 +1,358 @@
+//! `cel.sandbox.evaluate`, a single entry point for evaluating untrusted
+//! expressions with every limit applied.
+//!
+//! Limits on the expression are checked before it is evaluated. The cost of an
+//! evaluation is the number of iterations of comprehension macros (`map`,
+//! `filter`, `all`, `exists`, `exists_one`, `count`, `groupBy`, `countBy` and
+//! `distinct`), the only way an expression can run for longer than its size, so their bodies
+//! are rewritten into calls to [`TICK`], which charges the budget of the
+//! evaluation running on the thread and fails once it is spent or the deadline
+//! has passed.
+//!
+//! Python functions the host passes in `functions` can read what is left of the
+//! budget with `cel.sandbox.time_remaining()` and `cel.sandbox.cost_remaining()`,
+//! to bound their own work, and their calls are wrapped in [`DEADLINE`], which
+//! fails the evaluation once a call returns after the deadline.
+use crate::context::{has_global_functions, Context};
+use crate::errors::{EvalError, LimitExceeded};
+use crate::transform::{call, map_children};
+use crate::{
+    check_result_size, compile, originals, outcome_into_py, output_types, plan, resolve_mode, Job,
+};
+use cel_interpreter::{FunctionContext, ResolveResult};
+use cel_parser::Expression;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// Internal function that comprehension bodies are wrapped in while sandboxed
+pub const TICK: &str = "@tick";
+
+/// Internal function that calls to the functions passed to the sandbox are
+/// wrapped in
+pub const DEADLINE: &str = "@deadline";
+
+/// The functions sandboxed expressions may call. `matches` is left out as it
+/// compiles a regular expression chosen by the expression on every call.
+const ALLOWED_FUNCTIONS: &[&str] = &[
+    "size",
+    "contains",
+    "startsWith",
+    "endsWith",
+    "has",
+    "get",
+    "coalesce",
+    "rand",
+    "sample",
+    "bucket",
+    "map",
+    "filter",
+    "all",
+    "exists",
+    "exists_one",
+    "existsOne",
+    "count",
+    "groupBy",
+    "countBy",
+    "distinct",
+    "sum",
+    "avg",
+    "max",
+    "min",
+    "int",
+    "uint",
+    "double",
+    "string",
+    "bytes",
+    "bool",
+    "dyn",
+    "type",
+    "duration",
+    "timestamp",
+    "getFullYear",
+    "getMonth",
+    "getDayOfYear",
+    "getDayOfMonth",
+    "getDate",
+    "getDayOfWeek",
+    "getHours",
+    "getMinutes",
+    "getSeconds",
+    "getMilliseconds",
+];
+
+/// What is left of the limits of the sandboxed evaluation running on a thread
+struct Budget {
+    remaining: u64,
+    deadline: Instant,
+    /// Set when a limit is hit, which the error of the evaluation is then due to
+    exceeded: Option<String>,
+}
+
+thread_local! {
+    static BUDGET: RefCell<Option<Budget>> = const { RefCell::new(None) };
+}
+
+/// Evaluate an untrusted CEL expression with limits on its size, the work it
+/// may do and the size of its result. Python functions can't be used, other
+/// than those passed in `functions`.
+///
+/// Raises `cel.sandbox.LimitExceeded`, a `ValueError`, when a limit is hit.
+#[pyfunction]
+#[pyo3(signature = (expression, context=None, *, functions=None, max_length=1000, max_depth=50, max_cost=100_000, timeout=1.0, max_result_size=100_000))]
+#[allow(clippy::too_many_arguments)]
+fn evaluate(
+    py: Python<'_>,
+    expression: String,
+    context: Option<&Bound<'_, PyAny>>,
+    functions: Option<&Bound<'_, PyDict>>,
+    max_length: usize,
+    max_depth: usize,
+    max_cost: u64,
+    timeout: f64,
+    max_result_size: usize,
+) -> PyResult<PyObject> {
+    let length = expression.chars().count();
+    if length > max_length {
+        return Err(LimitExceeded::new_err(format!(
+            "expression is {} characters long, the limit is {}",
+            length, max_length
+        )));
+    }
+    let timeout = Duration::try_from_secs_f64(timeout)
+        .map_err(|_| PyValueError::new_err("timeout must be a positive number of seconds"))?;
+
+    let program = compile(&expression).map_err(|e| e.to_py_err())?;
+    let depth = depth(&program);
+    if depth > max_depth {
+        return Err(LimitExceeded::new_err(format!(
+            "expression is nested {} deep, the limit is {}",
+            depth, max_depth
+        )));
+    }
+    let passed: Vec<String> = match functions {
+        Some(functions) => functions.keys().extract()?,
+        None => Vec::new(),
+    };
+    if let Some(name) = disallowed_function(&program, &passed) {
+        return Err(EvalError::rejected(
+            &expression,
+            format!("function '{}' is not available in the sandbox", name),
+        )
+        .to_py_err());
+    }
+
+    let options = resolve_mode(context, None)?;
+    let in_context = || {
+        PyValueError::new_err(
+            "the sandbox doesn't allow Python functions in the context, pass them in `functions`",
+        )
+    };
+    let job = match functions.is_some() || has_global_functions() {
+        // Only the functions passed are available, not global functions
+        true => {
+            let mut sandboxed = match context {
+                Some(context) => match context.extract::<PyRef<Context>>() {
+                    Ok(context) => context.fork(),
+                    Err(_) => {
+                        let variables = context.downcast::<PyDict>().map_err(|_| {
+                            PyValueError::new_err("context must be a Context object or a dict")
+                        })?;
+                        let mut sandboxed = Context::new(
+                            None, None, false, None, None, false, "error", "error", false, false,
+                            None, None, None,
+                        )?;
+                        sandboxed.update(Some(variables), false, None)?;
+                        sandboxed
+                    }
+                },
+                None => Context::new(
+                    None, None, false, None, None, false, "error", "error", false, false, None,
+                    None, None,
+                )?,
+            };
+            if !sandboxed.functions.is_empty() {
+                return Err(in_context());
+            }
+            sandboxed.without_global_functions();
+            sandboxed.update(None, false, functions)?;
+            Job::for_context(&sandboxed, None, None, options)
+        }
+        false => {
+            let job = Job::new(context, None, None, options)?;
+            if !job.functions.is_empty() {
+                return Err(in_context());
+            }
+            job
+        }
+    };
+    // Attributes of objects are read with `getattr`, which untrusted expressions
+    // aren't given
+    if job.objects {
+        return Err(PyValueError::new_err(
+            "the sandbox doesn't allow contexts with objects=\"attributes\"",
+        ));
+    }
+    let metered = meter(&program, &passed);
+
+    // A function passed in may evaluate a sandboxed expression itself, whose
+    // budget replaces this one until it is done
+    let outer = BUDGET.with(|budget| {
+        budget.replace(Some(Budget {
+            remaining: max_cost,
+            deadline: Instant::now() + timeout,
+            exceeded: None,
+        }))
+    });
+    let outcome = py.allow_threads(|| job.run(&expression, &metered, None));
+    let budget = BUDGET.with(|budget| budget.replace(outer));
+    if let Some(message) = budget.and_then(|budget| budget.exceeded) {
+        return Err(LimitExceeded::new_err(message));
+    }
+
+    check_result_size(&outcome, Some(max_result_size))?;
+    let originals = originals(context);
+    outcome_into_py(py, outcome, false, false, output_types(context), &originals)
+}
+
+/// The seconds left before the deadline of the sandboxed evaluation calling the
+/// function this is called from, or None outside of one
+#[pyfunction]
+fn time_remaining() -> Option<f64> {
+    BUDGET.with(|budget| {
+        let budget = budget.borrow();
+        let budget = budget.as_ref()?;
+        Some(
+            budget
+                .deadline
+                .saturating_duration_since(Instant::now())
+                .as_secs_f64(),
+        )
+    })
+}
+
+/// The iterations left of the cost limit of the sandboxed evaluation calling the
+/// function this is called from, or None outside of one
+#[pyfunction]
+fn cost_remaining() -> Option<u64> {
+    BUDGET.with(|budget| budget.borrow().as_ref().map(|budget| budget.remaining))
+}
+
+/// How deeply `expr` is nested, counting each expression within another
+fn depth(expr: &Expression) -> usize {
+    let mut deepest = 0;
+    map_children(expr, |child| {
+        deepest = deepest.max(depth(child));
+        child.clone()
+    });
+    deepest + 1
+}
+
+/// The first function `expr` calls that the sandbox doesn't allow, other than the
+/// `passed` Python functions
+fn disallowed_function(expr: &Expression, passed: &[String]) -> Option<String> {
+    if let Expression::FunctionCall(function, _, _) = expr {
+        match &**function {
+            Expression::Ident(name) if ALLOWED_FUNCTIONS.contains(&name.as_str()) => {}
+            Expression::Ident(name) if passed.iter().any(|passed| **name == *passed) => {}
+            Expression::Ident(name) => return Some(name.to_string()),
+            _ => {}
+        }
+    }
+    let mut found = None;
+    map_children(expr, |child| {
+        found = found.take().or_else(|| disallowed_function(child, passed));
+        child.clone()
+    });
+    found
+}
+
+/// Wraps the bodies of comprehension macros in calls to [`TICK`], and calls to
+/// the `passed` Python functions in calls to [`DEADLINE`]
+fn meter(expr: &Expression, passed: &[String]) -> Expression {
+    match expr {
+        Expression::FunctionCall(function, Some(target), args) if plan::is_macro(function) => {
+            let mut args = args.iter();
+            let variable = args.next().cloned();
+            Expression::FunctionCall(
+                function.clone(),
+                Some(meter(target, passed).into()),
+                variable
+                    .into_iter()
+                    .chain(args.map(|arg| call(TICK, vec![meter(arg, passed)])))
+                    .collect(),
+            )
+        }
+        Expression::FunctionCall(function, _, _)
+            if matches!(&**function, Expression::Ident(name)
+                if passed.iter().any(|passed| **name == *passed)) =>
+        {
+            call(
+                DEADLINE,
+                vec![map_children(expr, |child| meter(child, passed))],
+            )
+        }
+        _ => map_children(expr, |child| meter(child, passed)),
+    }
+}
+
+/// Implementation of [`TICK`], which charges an iteration to the budget of the
+/// evaluation before resolving its argument
+pub fn tick(ftx: &FunctionContext) -> ResolveResult {
+    let exceeded = BUDGET.with(|budget| {
+        let mut budget = budget.borrow_mut();
+        let budget = budget.as_mut()?;
+        let message = if budget.remaining == 0 {
+            "evaluation exceeded its cost limit"
+        } else if Instant::now() > budget.deadline {
+            "evaluation timed out"
+        } else {
+            budget.remaining -= 1;
+            return None;
+        };
+        budget.exceeded = Some(message.to_string());
+        Some(message)
+    });
+    match exceeded {
+        Some(message) => Err(ftx.error(message)),
+        None => ftx.ptx.resolve(&ftx.args[0]),
+    }
+}
+
+/// Implementation of [`DEADLINE`], which resolves its argument, a call to a Python
+/// function, and fails if the deadline of the evaluation passed during the call,
+/// whether the function returned or raised
+pub fn deadline(ftx: &FunctionContext) -> ResolveResult {
+    let result = ftx.ptx.resolve(&ftx.args[0]);
+    let timed_out = BUDGET.with(|budget| {
+        let mut budget = budget.borrow_mut();
+        let budget = budget.as_mut()?;
+        if Instant::now() <= budget.deadline {
+            return None;
+        }
+        let message = "evaluation timed out";
+        budget.exceeded.get_or_insert_with(|| message.to_string());
+        Some(message)
+    });
+    match timed_out {
+        Some(message) => Err(ftx.error(message)),
+        None => result,
+    }
+}
+
+/// Adds the `cel.sandbox` module
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    let sandbox = PyModule::new_bound(py, "sandbox")?;
+    sandbox.add_function(wrap_pyfunction!(evaluate, &sandbox)?)?;
+    sandbox.add_function(wrap_pyfunction!(time_remaining, &sandbox)?)?;
+    sandbox.add_function(wrap_pyfunction!(cost_remaining, &sandbox)?)?;
+    sandbox.add("LimitExceeded", py.get_type_bound::<LimitExceeded>())?;
+    m.add_submodule(&sandbox)?;
+    // Lets `import cel.sandbox` find the module
+    py.import_bound("sys")?
+        .getattr("modules")?
+        .set_item("cel.sandbox", sandbox)
+}
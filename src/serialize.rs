@@ -0,0 +1,447 @@
+//! A compact binary form of compiled expressions, so that they can be sent to
+//! other processes without parsing their source again.
+//!
+//! Every node is written as a tag byte followed by its fields. Integers are
+//! LEB128 varints, zigzag encoded when signed, and strings and bytes are
+//! prefixed with their length.
+use cel_parser::{ArithmeticOp, Atom, Expression, Member, RelationOp, UnaryOp};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Identifies serialized programs, the last byte is the version of the format.
+/// Version 2 added the name of the program, and programs serialized by version 1
+/// are still loaded.
+const MAGIC: &[u8; 4] = b"CEL\x02";
+
+/// How deeply expressions may be nested in a loaded program, as they are read
+/// and evaluated recursively. It is cel-go's parser limit, well within the
+/// nesting the parser here compiles.
+const MAX_DEPTH: usize = 250;
+
+/// Fingerprints are prefixed with the first version of the format, as the
+/// expressions are written the same way since
+const FINGERPRINT_PREFIX: &[u8; 4] = b"CEL\x01";
+
+/// A compiled program taken apart to be serialized
+pub struct Parts {
+    pub source: String,
+    pub name: Option<String>,
+    pub expression: Expression,
+    pub optimize: bool,
+    pub parallel_threshold: Option<usize>,
+}
+
+pub fn dump(parts: &Parts) -> Vec<u8> {
+    let mut writer = Writer(MAGIC.to_vec());
+    writer.string(&parts.source);
+    match &parts.name {
+        Some(name) => {
+            writer.byte(1);
+            writer.string(name);
+        }
+        None => writer.byte(0),
+    }
+    writer.byte(parts.optimize as u8);
+    match parts.parallel_threshold {
+        Some(threshold) => {
+            writer.byte(1);
+            writer.uint(threshold as u64);
+        }
+        None => writer.byte(0),
+    }
+    writer.expression(&parts.expression);
+    writer.0
+}
+
+/// The SHA-256 of `expr` in this form as hex. It only depends on the parsed
+/// expression, not on the whitespace or comments of its source, and only
+/// changes with the version of the format.
+pub fn fingerprint(expr: &Expression) -> String {
+    let mut writer = Writer(FINGERPRINT_PREFIX.to_vec());
+    writer.expression(expr);
+    Sha256::digest(&writer.0)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn load(bytes: &[u8]) -> Result<Parts, String> {
+    let Some(rest) = bytes.strip_prefix(&MAGIC[..3]) else {
+        return Err("not a serialized program".to_string());
+    };
+    let version = rest.first().copied();
+    if version != MAGIC.last().copied() && version != Some(1) {
+        return Err("serialized by an incompatible version".to_string());
+    }
+    let mut reader = Reader {
+        data: &rest[1..],
+        depth: 0,
+    };
+    let parts = Parts {
+        source: reader.string()?,
+        name: match version == Some(1) {
+            true => None,
+            false => match reader.bool()? {
+                true => Some(reader.string()?),
+                false => None,
+            },
+        },
+        optimize: reader.bool()?,
+        parallel_threshold: match reader.bool()? {
+            true => Some(usize::try_from(reader.uint()?).map_err(|_| "threshold is out of range")?),
+            false => None,
+        },
+        expression: reader.expression()?,
+    };
+    match reader.data.is_empty() {
+        true => Ok(parts),
+        false => Err("unexpected data after the expression".to_string()),
+    }
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn byte(&mut self, byte: u8) {
+        self.0.push(byte);
+    }
+
+    fn uint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn int(&mut self, value: i64) {
+        self.uint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.uint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    fn expressions(&mut self, exprs: &[Expression]) {
+        self.uint(exprs.len() as u64);
+        for expr in exprs {
+            self.expression(expr);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Arithmetic(left, op, right) => {
+                self.byte(0);
+                self.byte(match op {
+                    ArithmeticOp::Add => 0,
+                    ArithmeticOp::Subtract => 1,
+                    ArithmeticOp::Divide => 2,
+                    ArithmeticOp::Multiply => 3,
+                    ArithmeticOp::Modulus => 4,
+                });
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Relation(left, op, right) => {
+                self.byte(1);
+                self.byte(match op {
+                    RelationOp::LessThan => 0,
+                    RelationOp::LessThanEq => 1,
+                    RelationOp::GreaterThan => 2,
+                    RelationOp::GreaterThanEq => 3,
+                    RelationOp::Equals => 4,
+                    RelationOp::NotEquals => 5,
+                    RelationOp::In => 6,
+                });
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Ternary(condition, left, right) => {
+                self.byte(2);
+                self.expression(condition);
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Or(left, right) => {
+                self.byte(3);
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::And(left, right) => {
+                self.byte(4);
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Unary(op, operand) => {
+                self.byte(5);
+                self.byte(match op {
+                    UnaryOp::Not => 0,
+                    UnaryOp::DoubleNot => 1,
+                    UnaryOp::Minus => 2,
+                    UnaryOp::DoubleMinus => 3,
+                });
+                self.expression(operand);
+            }
+            Expression::Member(target, member) => {
+                self.byte(6);
+                self.expression(target);
+                match member.as_ref() {
+                    Member::Attribute(name) => {
+                        self.byte(0);
+                        self.string(name);
+                    }
+                    Member::Index(index) => {
+                        self.byte(1);
+                        self.expression(index);
+                    }
+                    Member::Fields(fields) => {
+                        self.byte(2);
+                        self.uint(fields.len() as u64);
+                        for (name, value) in fields {
+                            self.string(name);
+                            self.expression(value);
+                        }
+                    }
+                }
+            }
+            Expression::FunctionCall(function, target, args) => {
+                self.byte(7);
+                self.expression(function);
+                match target {
+                    Some(target) => {
+                        self.byte(1);
+                        self.expression(target);
+                    }
+                    None => self.byte(0),
+                }
+                self.expressions(args);
+            }
+            Expression::List(items) => {
+                self.byte(8);
+                self.expressions(items);
+            }
+            Expression::Map(entries) => {
+                self.byte(9);
+                self.uint(entries.len() as u64);
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::Atom(atom) => {
+                self.byte(10);
+                match atom {
+                    Atom::Int(v) => {
+                        self.byte(0);
+                        self.int(*v);
+                    }
+                    Atom::UInt(v) => {
+                        self.byte(1);
+                        self.uint(*v);
+                    }
+                    Atom::Float(v) => {
+                        self.byte(2);
+                        self.0.extend_from_slice(&v.to_le_bytes());
+                    }
+                    Atom::String(s) => {
+                        self.byte(3);
+                        self.string(s);
+                    }
+                    Atom::Bytes(b) => {
+                        self.byte(4);
+                        self.bytes(b);
+                    }
+                    Atom::Bool(b) => self.byte(if *b { 6 } else { 5 }),
+                    Atom::Null => self.byte(7),
+                }
+            }
+            Expression::Ident(name) => {
+                self.byte(11);
+                self.string(name);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    /// How many expressions the one being read is nested in
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        if len > self.data.len() {
+            return Err("data is truncated".to_string());
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, String> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(format!("invalid flag {}", other)),
+        }
+    }
+
+    fn uint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("integer is too long".to_string())
+    }
+
+    fn int(&mut self) -> Result<i64, String> {
+        let value = self.uint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        let len = self.uint()?;
+        // Every item takes at least a byte, which also stops a corrupt length
+        // from allocating more than the data could hold
+        match usize::try_from(len) {
+            Ok(len) if len <= self.data.len() => Ok(len),
+            _ => Err("data is truncated".to_string()),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.len()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        String::from_utf8(self.bytes()?).map_err(|_| "invalid UTF-8 in a string".to_string())
+    }
+
+    fn name(&mut self) -> Result<Arc<String>, String> {
+        Ok(Arc::new(self.string()?))
+    }
+
+    fn boxed(&mut self) -> Result<Box<Expression>, String> {
+        Ok(Box::new(self.expression()?))
+    }
+
+    fn expressions(&mut self) -> Result<Vec<Expression>, String> {
+        let len = self.len()?;
+        (0..len).map(|_| self.expression()).collect()
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("expression is nested more than {} deep", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let expression = self.node();
+        self.depth -= 1;
+        expression
+    }
+
+    fn node(&mut self) -> Result<Expression, String> {
+        Ok(match self.byte()? {
+            0 => {
+                let op = match self.byte()? {
+                    0 => ArithmeticOp::Add,
+                    1 => ArithmeticOp::Subtract,
+                    2 => ArithmeticOp::Divide,
+                    3 => ArithmeticOp::Multiply,
+                    4 => ArithmeticOp::Modulus,
+                    other => return Err(format!("unknown arithmetic operator {}", other)),
+                };
+                Expression::Arithmetic(self.boxed()?, op, self.boxed()?)
+            }
+            1 => {
+                let op = match self.byte()? {
+                    0 => RelationOp::LessThan,
+                    1 => RelationOp::LessThanEq,
+                    2 => RelationOp::GreaterThan,
+                    3 => RelationOp::GreaterThanEq,
+                    4 => RelationOp::Equals,
+                    5 => RelationOp::NotEquals,
+                    6 => RelationOp::In,
+                    other => return Err(format!("unknown relation operator {}", other)),
+                };
+                Expression::Relation(self.boxed()?, op, self.boxed()?)
+            }
+            2 => Expression::Ternary(self.boxed()?, self.boxed()?, self.boxed()?),
+            3 => Expression::Or(self.boxed()?, self.boxed()?),
+            4 => Expression::And(self.boxed()?, self.boxed()?),
+            5 => {
+                let op = match self.byte()? {
+                    0 => UnaryOp::Not,
+                    1 => UnaryOp::DoubleNot,
+                    2 => UnaryOp::Minus,
+                    3 => UnaryOp::DoubleMinus,
+                    other => return Err(format!("unknown unary operator {}", other)),
+                };
+                Expression::Unary(op, self.boxed()?)
+            }
+            6 => {
+                let target = self.boxed()?;
+                let member = match self.byte()? {
+                    0 => Member::Attribute(self.name()?),
+                    1 => Member::Index(self.boxed()?),
+                    2 => {
+                        let len = self.len()?;
+                        Member::Fields(
+                            (0..len)
+                                .map(|_| Ok((self.name()?, self.expression()?)))
+                                .collect::<Result<_, String>>()?,
+                        )
+                    }
+                    other => return Err(format!("unknown member {}", other)),
+                };
+                Expression::Member(target, Box::new(member))
+            }
+            7 => {
+                let function = self.boxed()?;
+                let target = match self.bool()? {
+                    true => Some(self.boxed()?),
+                    false => None,
+                };
+                Expression::FunctionCall(function, target, self.expressions()?)
+            }
+            8 => Expression::List(self.expressions()?),
+            9 => {
+                let len = self.len()?;
+                Expression::Map(
+                    (0..len)
+                        .map(|_| Ok((self.expression()?, self.expression()?)))
+                        .collect::<Result<_, String>>()?,
+                )
+            }
+            10 => Expression::Atom(match self.byte()? {
+                0 => Atom::Int(self.int()?),
+                1 => Atom::UInt(self.uint()?),
+                2 => Atom::Float(f64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+                3 => Atom::String(self.name()?),
+                4 => Atom::Bytes(Arc::new(self.bytes()?)),
+                5 => Atom::Bool(false),
+                6 => Atom::Bool(true),
+                7 => Atom::Null,
+                other => return Err(format!("unknown literal {}", other)),
+            }),
+            11 => Expression::Ident(self.name()?),
+            other => return Err(format!("unknown expression {}", other)),
+        })
+    }
+}
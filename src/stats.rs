@@ -0,0 +1,122 @@
+//! `cel.stats()`, process-wide counters of the work the module has done, so
+//! that services can export them, e.g. to Prometheus, without wrapping every
+//! call.
+//!
+//! The counters are atomics updated where the work happens, whichever entry
+//! point it came from, and only ever increase.
+use cel_interpreter::ExecutionError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+static COMPILES: AtomicU64 = AtomicU64::new(0);
+static EVALUATIONS: AtomicU64 = AtomicU64::new(0);
+static EVALUATION_NANOS: AtomicU64 = AtomicU64::new(0);
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// The types errors are counted by: failing to parse, being rejected by the
+/// mode, nesting evaluations too deeply and each kind of execution error
+const ERROR_TYPES: [&str; 20] = [
+    "compile",
+    "rejected",
+    "nesting",
+    "invalid_argument_count",
+    "unsupported_target_type",
+    "not_supported_as_method",
+    "unsupported_key_type",
+    "unexpected_type",
+    "no_such_key",
+    "undeclared_reference",
+    "missing_argument_or_target",
+    "values_not_comparable",
+    "unsupported_unary_operator",
+    "unsupported_binary_operator",
+    "unsupported_map_index",
+    "unsupported_list_index",
+    "unsupported_index",
+    "unsupported_function_call_identifier_type",
+    "unsupported_fields_construction",
+    "function_error",
+];
+
+static ERRORS: [AtomicU64; ERROR_TYPES.len()] = [const { AtomicU64::new(0) }; ERROR_TYPES.len()];
+
+/// Counts an expression being parsed
+pub fn compiled() {
+    COMPILES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts an evaluation, and the time it took
+pub fn evaluated(elapsed: Duration) {
+    EVALUATIONS.fetch_add(1, Ordering::Relaxed);
+    let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+    EVALUATION_NANOS.fetch_add(nanos, Ordering::Relaxed);
+}
+
+/// Counts a lookup in the result cache of a program
+pub fn cache_lookup(hit: bool) {
+    match hit {
+        true => CACHE_HITS.fetch_add(1, Ordering::Relaxed),
+        false => CACHE_MISSES.fetch_add(1, Ordering::Relaxed),
+    };
+}
+
+/// Counts an error of one of the [`ERROR_TYPES`]
+pub fn failed(error_type: &str) {
+    if let Some(index) = ERROR_TYPES.iter().position(|t| *t == error_type) {
+        ERRORS[index].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The error type of an execution error
+pub fn error_type(error: &ExecutionError) -> &'static str {
+    match error {
+        ExecutionError::InvalidArgumentCount { .. } => "invalid_argument_count",
+        ExecutionError::UnsupportedTargetType { .. } => "unsupported_target_type",
+        ExecutionError::NotSupportedAsMethod { .. } => "not_supported_as_method",
+        ExecutionError::UnsupportedKeyType(_) => "unsupported_key_type",
+        ExecutionError::UnexpectedType { .. } => "unexpected_type",
+        ExecutionError::NoSuchKey(_) => "no_such_key",
+        ExecutionError::UndeclaredReference(_) => "undeclared_reference",
+        ExecutionError::MissingArgumentOrTarget => "missing_argument_or_target",
+        ExecutionError::ValuesNotComparable(..) => "values_not_comparable",
+        ExecutionError::UnsupportedUnaryOperator(..) => "unsupported_unary_operator",
+        ExecutionError::UnsupportedBinaryOperator(..) => "unsupported_binary_operator",
+        ExecutionError::UnsupportedMapIndex(_) => "unsupported_map_index",
+        ExecutionError::UnsupportedListIndex(_) => "unsupported_list_index",
+        ExecutionError::UnsupportedIndex(..) => "unsupported_index",
+        ExecutionError::UnsupportedFunctionCallIdentifierType(_) => {
+            "unsupported_function_call_identifier_type"
+        }
+        ExecutionError::UnsupportedFieldsConstruction(_) => "unsupported_fields_construction",
+        ExecutionError::FunctionError { .. } => "function_error",
+    }
+}
+
+/// The counters of the process: `compiles`, `evaluations` (including those
+/// nested in Python functions called by another), `evaluation_seconds` spent
+/// in them, `cache_hits` and `cache_misses` of programs evaluated with
+/// `cache=True`, and `errors` by type, listing only types that occurred
+#[pyfunction]
+pub fn stats(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let stats = PyDict::new_bound(py);
+    stats.set_item("compiles", COMPILES.load(Ordering::Relaxed))?;
+    stats.set_item("evaluations", EVALUATIONS.load(Ordering::Relaxed))?;
+    stats.set_item(
+        "evaluation_seconds",
+        EVALUATION_NANOS.load(Ordering::Relaxed) as f64 / 1e9,
+    )?;
+    stats.set_item("cache_hits", CACHE_HITS.load(Ordering::Relaxed))?;
+    stats.set_item("cache_misses", CACHE_MISSES.load(Ordering::Relaxed))?;
+    let errors = PyDict::new_bound(py);
+    for (error_type, count) in ERROR_TYPES.iter().zip(&ERRORS) {
+        let count = count.load(Ordering::Relaxed);
+        if count > 0 {
+            errors.set_item(error_type, count)?;
+        }
+    }
+    stats.set_item("errors", errors)?;
+    Ok(stats)
+}
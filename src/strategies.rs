@@ -0,0 +1,235 @@
+//! `cel.testing.strategies`, Hypothesis strategies for the values and contexts
+//! expressions are evaluated with, to fuzz expressions across all of the inputs
+//! they may be given.
+//!
+//! Contexts are described by declarations: a dict of variable names to CEL
+//! type names such as `"int"`, `"list(string)"` or `"map(string, dyn)"`, to a
+//! dict declaring the fields of a map in the same way, or to a strategy of
+//! their own. Hypothesis is imported when a strategy is made, so it's only
+//! needed by the tests using them.
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyString};
+
+/// The range CEL durations are limited to, about 10,000 years either way
+const MAX_DURATION_SECONDS: i64 = 315_576_000_000;
+
+/// A strategy for the contexts described by `declarations`, dicts with a value
+/// for each declared variable
+#[pyfunction]
+fn contexts<'py>(declarations: &Bound<'py, PyDict>) -> PyResult<Bound<'py, PyAny>> {
+    let st = declarations.py().import_bound("hypothesis.strategies")?;
+    fields(&st, declarations, "")
+}
+
+/// A strategy for values of the CEL type named `cel_type`, e.g. `"int"` or
+/// `"map(string, list(double))"`
+#[pyfunction]
+fn values<'py>(py: Python<'py>, cel_type: &str) -> PyResult<Bound<'py, PyAny>> {
+    let cel_type = parse(cel_type).map_err(PyValueError::new_err)?;
+    strategy(&py.import_bound("hypothesis.strategies")?, &cel_type)
+}
+
+/// A strategy for dicts with a value for each of `declarations`, the fields of
+/// the map at `path`
+fn fields<'py>(
+    st: &Bound<'py, PyModule>,
+    declarations: &Bound<'py, PyDict>,
+    path: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let strategies = PyDict::new_bound(st.py());
+    for (name, declared) in declarations.iter() {
+        let name: String = name.extract()?;
+        let path = if path.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", path, name)
+        };
+        strategies.set_item(name, declaration(st, &declared, &path)?)?;
+    }
+    st.call_method1("fixed_dictionaries", (strategies,))
+}
+
+/// The strategy for the variable or field at `path` that is `declared`
+fn declaration<'py>(
+    st: &Bound<'py, PyModule>,
+    declared: &Bound<'py, PyAny>,
+    path: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    if let Ok(cel_type) = declared.downcast::<PyString>() {
+        let cel_type = parse(cel_type.to_str()?).map_err(|message| {
+            PyValueError::new_err(format!("{} in the declaration of '{}'", message, path))
+        })?;
+        return strategy(st, &cel_type);
+    }
+    if let Ok(declarations) = declared.downcast::<PyDict>() {
+        return fields(st, declarations, path);
+    }
+    if declared.is_instance(&st.getattr("SearchStrategy")?)? {
+        return Ok(declared.clone());
+    }
+    Err(PyTypeError::new_err(format!(
+        "'{}' must be declared with a type name, a dict of fields or a strategy, got {}",
+        path,
+        declared.repr()?
+    )))
+}
+
+/// A declared CEL type
+enum Type {
+    Int,
+    UInt,
+    Double,
+    Bool,
+    String,
+    Bytes,
+    Null,
+    Timestamp,
+    Duration,
+    Dyn,
+    List(Box<Type>),
+    Map(Box<Type>, Box<Type>),
+}
+
+/// The scalar types a `dyn` value may be
+const DYNAMIC: [Type; 8] = [
+    Type::Int,
+    Type::Double,
+    Type::Bool,
+    Type::String,
+    Type::Bytes,
+    Type::Null,
+    Type::Timestamp,
+    Type::Duration,
+];
+
+/// The type named `cel_type`, or why it isn't one
+fn parse(cel_type: &str) -> Result<Type, String> {
+    let cel_type = cel_type.trim();
+    let (name, parameters) = match cel_type.split_once('(') {
+        Some((name, rest)) => match rest.strip_suffix(')') {
+            Some(parameters) => (name.trim(), split(parameters)),
+            None => return Err(format!("unbalanced parentheses in type '{}'", cel_type)),
+        },
+        None => (cel_type, Vec::new()),
+    };
+    Ok(match (name, parameters.as_slice()) {
+        ("list", []) => Type::List(Box::new(Type::Dyn)),
+        ("list", [item]) => Type::List(Box::new(parse(item)?)),
+        ("map", []) => Type::Map(Box::new(Type::String), Box::new(Type::Dyn)),
+        ("map", [key, value]) => {
+            let key = parse(key)?;
+            if !matches!(key, Type::Int | Type::UInt | Type::Bool | Type::String) {
+                return Err(format!(
+                    "map keys must be int, uint, bool or string in '{}'",
+                    cel_type
+                ));
+            }
+            Type::Map(Box::new(key), Box::new(parse(value)?))
+        }
+        ("list" | "map", _) => {
+            return Err(format!("wrong number of type parameters in '{}'", cel_type))
+        }
+        ("int", []) => Type::Int,
+        ("uint", []) => Type::UInt,
+        ("double", []) => Type::Double,
+        ("bool", []) => Type::Bool,
+        ("string", []) => Type::String,
+        ("bytes", []) => Type::Bytes,
+        ("null_type", []) => Type::Null,
+        ("timestamp" | "google.protobuf.Timestamp", []) => Type::Timestamp,
+        ("duration" | "google.protobuf.Duration", []) => Type::Duration,
+        ("dyn", []) => Type::Dyn,
+        _ => return Err(format!("unknown type '{}'", cel_type)),
+    })
+}
+
+/// The parameters of a type, split at the commas that aren't nested in
+/// another type's parameters
+fn split(parameters: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in parameters.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(&parameters[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !parameters.trim().is_empty() {
+        split.push(&parameters[start..]);
+    }
+    split
+}
+
+/// The strategy for values of `cel_type`
+fn strategy<'py>(st: &Bound<'py, PyModule>, cel_type: &Type) -> PyResult<Bound<'py, PyAny>> {
+    let py = st.py();
+    let kwargs = PyDict::new_bound(py);
+    let function = match cel_type {
+        Type::Int => {
+            kwargs.set_item("min_value", i64::MIN)?;
+            kwargs.set_item("max_value", i64::MAX)?;
+            "integers"
+        }
+        // Python ints are converted to CEL ints, so larger uints can't be given
+        // in a context
+        Type::UInt => {
+            kwargs.set_item("min_value", 0)?;
+            kwargs.set_item("max_value", i64::MAX)?;
+            "integers"
+        }
+        Type::Double => "floats",
+        Type::Bool => "booleans",
+        Type::String => "text",
+        Type::Bytes => "binary",
+        Type::Null => "none",
+        Type::Timestamp => {
+            let utc = py
+                .import_bound("datetime")?
+                .getattr("timezone")?
+                .getattr("utc")?;
+            kwargs.set_item("timezones", st.call_method1("just", (utc,))?)?;
+            "datetimes"
+        }
+        Type::Duration => {
+            let timedelta = py.import_bound("datetime")?.getattr("timedelta")?;
+            kwargs.set_item("min_value", timedelta.call1((0, -MAX_DURATION_SECONDS))?)?;
+            kwargs.set_item("max_value", timedelta.call1((0, MAX_DURATION_SECONDS))?)?;
+            "timedeltas"
+        }
+        // Any scalar, or a list or map of them
+        Type::Dyn => {
+            let scalars = DYNAMIC
+                .iter()
+                .map(|cel_type| strategy(st, cel_type))
+                .collect::<PyResult<Vec<_>>>()?;
+            let scalars = st.call_method1("one_of", (PyList::new_bound(py, scalars),))?;
+            let lists = st.call_method1("lists", (&scalars,))?;
+            let maps = st.call_method1("dictionaries", (st.call_method0("text")?, &scalars))?;
+            return st.call_method1("one_of", (PyList::new_bound(py, [scalars, lists, maps]),));
+        }
+        Type::List(item) => return st.call_method1("lists", (strategy(st, item)?,)),
+        Type::Map(key, value) => {
+            return st.call_method1("dictionaries", (strategy(st, key)?, strategy(st, value)?))
+        }
+    };
+    st.getattr(function)?.call((), Some(&kwargs))
+}
+
+/// Adds the `cel.testing.strategies` module
+pub fn register(testing: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = testing.py();
+    let strategies = PyModule::new_bound(py, "strategies")?;
+    strategies.add_function(wrap_pyfunction!(contexts, &strategies)?)?;
+    strategies.add_function(wrap_pyfunction!(values, &strategies)?)?;
+    testing.add_submodule(&strategies)?;
+    // Lets `import cel.testing.strategies` find the module
+    py.import_bound("sys")?
+        .getattr("modules")?
+        .set_item("cel.testing.strategies", strategies)
+}
@@ -0,0 +1,137 @@
+//! The built-in `strings` extension, loaded with `Context(extensions=["strings"])`,
+//! with fuzzy matching for data-quality rules: `strings.levenshtein(a, b)`,
+//! `strings.similarity(a, b)` and `strings.soundex(s)`.
+//!
+//! The functions are in the `strings` namespace, see [`crate::namespaces`].
+use crate::functions::Described;
+use crate::toolkit;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use std::sync::Arc;
+
+/// The name the extension is loaded by, and the namespace of its functions
+pub const NAME: &str = "strings";
+
+/// The functions of the extension, with their signature and what they do, for
+/// `Context.functions_info`
+pub const FUNCTIONS: &[Described] = &[
+    (
+        "strings.levenshtein",
+        "strings.levenshtein(string, string) -> int",
+        "The number of characters to insert, delete or replace to turn one string into the other",
+    ),
+    (
+        "strings.similarity",
+        "strings.similarity(string, string) -> double",
+        "How similar two strings are, from 0.0 for nothing in common to 1.0 for equal",
+    ),
+    (
+        "strings.soundex",
+        "strings.soundex(string) -> string",
+        "The American Soundex code of a name, the same for names that sound alike",
+    ),
+];
+
+/// Adds the functions of the extension to an environment
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function("strings.levenshtein", levenshtein);
+    environment.add_function("strings.similarity", similarity);
+    environment.add_function("strings.soundex", soundex);
+}
+
+/// The `N` strings a function is called with
+fn strings<const N: usize>(ftx: &FunctionContext) -> Result<[Arc<String>; N], ExecutionError> {
+    if ftx.args.len() != N {
+        return Err(ExecutionError::invalid_argument_count(N, ftx.args.len()));
+    }
+    let strings = ftx
+        .args
+        .iter()
+        .map(|arg| match ftx.ptx.resolve(arg)? {
+            Value::String(string) => Ok(string),
+            other => Err(ftx.error(format!("expected a string, got {}", other.type_of()))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(strings.try_into().expect("checked the count"))
+}
+
+/// Returns the Levenshtein distance between two strings, counted in
+/// characters.
+///
+/// # Examples
+/// ```cel
+/// strings.levenshtein('kitten', 'sitting') == 3
+/// ```
+pub fn levenshtein(ftx: &FunctionContext) -> ResolveResult {
+    let [a, b] = strings(ftx)?;
+    Ok(Value::Int(toolkit::distance(&a, &b) as i64))
+}
+
+/// Returns one minus the Levenshtein distance between two strings over the
+/// length of the longer one, so 1.0 if they are equal, including both empty,
+/// and 0.0 if every character differs.
+///
+/// # Examples
+/// ```cel
+/// strings.similarity(a.name, b.name) > 0.8
+/// ```
+pub fn similarity(ftx: &FunctionContext) -> ResolveResult {
+    let [a, b] = strings(ftx)?;
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return Ok(Value::Float(1.0));
+    }
+    let distance = toolkit::distance(&a, &b);
+    Ok(Value::Float(1.0 - distance as f64 / longest as f64))
+}
+
+/// Returns the American Soundex code of a name: its first letter and three
+/// digits for the consonants that follow, so that names which sound alike,
+/// like Robert and Rupert, have the same code. Characters other than ASCII
+/// letters are skipped, and a string without any has an empty code.
+///
+/// # Examples
+/// ```cel
+/// strings.soundex('Robert') == strings.soundex('Rupert')
+/// ```
+pub fn soundex(ftx: &FunctionContext) -> ResolveResult {
+    let [name] = strings(ftx)?;
+    let mut letters = name
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_uppercase());
+    let Some(first) = letters.next() else {
+        return Ok(Value::String(Arc::new(String::new())));
+    };
+    let mut code = String::from(first);
+    let mut previous = digit(first);
+    for letter in letters {
+        let current = digit(letter);
+        if current.is_some() && current != previous {
+            code.extend(current);
+            if code.len() == 4 {
+                break;
+            }
+        }
+        // H and W don't separate consonants with the same digit, but vowels do
+        if !matches!(letter, 'H' | 'W') {
+            previous = current;
+        }
+    }
+    while code.len() < 4 {
+        code.push('0');
+    }
+    Ok(Value::String(Arc::new(code)))
+}
+
+/// The Soundex digit of an upper case consonant, None for vowels, H, W and Y
+fn digit(letter: char) -> Option<char> {
+    match letter {
+        'B' | 'F' | 'P' | 'V' => Some('1'),
+        'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => Some('2'),
+        'D' | 'T' => Some('3'),
+        'L' => Some('4'),
+        'M' | 'N' => Some('5'),
+        'R' => Some('6'),
+        _ => None,
+    }
+}
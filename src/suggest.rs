@@ -0,0 +1,400 @@
+//! `cel.suggest_inputs`, which finds example variables that make a predicate
+//! true, e.g. to test a policy or document the inputs it expects.
+//!
+//! The predicate is rewritten as a disjunction of conjunctions of comparisons
+//! between a variable (or a field of one) and a literal. A value is picked for
+//! each variable of a conjunction from candidates around the literals it's
+//! compared with, so `age >= 18 && age < 65` gives `18`. Clauses that aren't
+//! such comparisons, like function calls, are left for the check at the end:
+//! every suggestion is evaluated and only those that are true are kept.
+use crate::explain::{is_path, read_paths};
+use crate::output::OutputTypes;
+use crate::{compile, evaluate_value, resolve_mode, Outcome, RustyCelType};
+use cel_interpreter::Value;
+use cel_parser::{Atom, Expression, Member, RelationOp, UnaryOp};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+/// How many conjunctions are tried, as rewriting can multiply them
+const MAX_CONJUNCTIONS: usize = 64;
+
+/// Returns example variables that make `expr` true, as a list of dicts with one
+/// for each distinct way found of satisfying it, e.g.
+/// `[{"user": {"age": 18}}]` for `user.age >= 18`. The list is empty if none
+/// were found, which doesn't mean there are none.
+#[pyfunction(signature = (expr, mode=None))]
+pub fn suggest_inputs<'py>(
+    py: Python<'py>,
+    expr: &str,
+    mode: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyList>> {
+    let program = compile(expr).map_err(|error| error.to_py_err())?;
+    let options = resolve_mode(None, mode)?;
+
+    // Variables a conjunction doesn't constrain get a default of the type
+    // they're compared with elsewhere, so the expression can be evaluated
+    let mut paths = Vec::new();
+    read_paths(&program, &mut Vec::new(), &mut paths);
+    let terms = disjunction(&program, false);
+    let mut defaults: Vec<(&Expression, Value)> = Vec::new();
+    for path in paths {
+        if defaults.iter().any(|(other, _)| *other == path) {
+            continue;
+        }
+        let default = terms
+            .iter()
+            .flatten()
+            .find(|constraint| constraint.path == path)
+            .and_then(|constraint| constraint.test.literals().first().map(default_of));
+        if let Some(default) = default {
+            defaults.push((path, default));
+        }
+    }
+
+    let suggestions = PyList::empty_bound(py);
+    for conjunction in terms.iter().take(MAX_CONJUNCTIONS) {
+        let Some(mut assignment) = assign(conjunction) else {
+            continue;
+        };
+        for (path, default) in &defaults {
+            if !assignment.iter().any(|(other, _)| other == path) {
+                assignment.push((path, default.clone()));
+            }
+        }
+        let variables = variables(py, &assignment)?;
+        if suggestions.contains(&variables)? {
+            continue;
+        }
+        if let Outcome::Value(Value::Bool(true)) =
+            evaluate_value(expr, Some(variables.as_any()), None, None, options, None)?
+        {
+            suggestions.append(variables)?;
+        }
+    }
+    Ok(suggestions)
+}
+
+/// What a constraint requires of the value of a path
+#[derive(Debug, Clone)]
+enum Test {
+    Compare(RelationOp, Value),
+    In(Vec<Value>),
+    NotIn(Vec<Value>),
+    StartsWith(Arc<String>, bool),
+    EndsWith(Arc<String>, bool),
+    Contains(Arc<String>, bool),
+}
+
+impl Test {
+    fn negate(self) -> Test {
+        match self {
+            Test::Compare(op, value) => Test::Compare(
+                match op {
+                    RelationOp::LessThan => RelationOp::GreaterThanEq,
+                    RelationOp::LessThanEq => RelationOp::GreaterThan,
+                    RelationOp::GreaterThan => RelationOp::LessThanEq,
+                    RelationOp::GreaterThanEq => RelationOp::LessThan,
+                    RelationOp::Equals => RelationOp::NotEquals,
+                    RelationOp::NotEquals => RelationOp::Equals,
+                    RelationOp::In => RelationOp::In,
+                },
+                value,
+            ),
+            Test::In(values) => Test::NotIn(values),
+            Test::NotIn(values) => Test::In(values),
+            Test::StartsWith(s, holds) => Test::StartsWith(s, !holds),
+            Test::EndsWith(s, holds) => Test::EndsWith(s, !holds),
+            Test::Contains(s, holds) => Test::Contains(s, !holds),
+        }
+    }
+
+    fn literals(&self) -> Vec<Value> {
+        match self {
+            Test::Compare(_, value) => vec![value.clone()],
+            Test::In(values) | Test::NotIn(values) => values.clone(),
+            Test::StartsWith(s, _) | Test::EndsWith(s, _) | Test::Contains(s, _) => {
+                vec![Value::String(s.clone())]
+            }
+        }
+    }
+
+    fn holds(&self, candidate: &Value) -> bool {
+        match self {
+            Test::Compare(op, value) => {
+                let ordering = candidate.partial_cmp(value);
+                match op {
+                    RelationOp::Equals => candidate == value,
+                    RelationOp::NotEquals => candidate != value,
+                    RelationOp::LessThan => ordering == Some(Ordering::Less),
+                    RelationOp::LessThanEq => {
+                        matches!(ordering, Some(Ordering::Less | Ordering::Equal))
+                    }
+                    RelationOp::GreaterThan => ordering == Some(Ordering::Greater),
+                    RelationOp::GreaterThanEq => {
+                        matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+                    }
+                    RelationOp::In => false,
+                }
+            }
+            Test::In(values) => values.contains(candidate),
+            Test::NotIn(values) => !values.contains(candidate),
+            Test::StartsWith(s, holds) => string(candidate, |c| c.starts_with(s.as_str()), *holds),
+            Test::EndsWith(s, holds) => string(candidate, |c| c.ends_with(s.as_str()), *holds),
+            Test::Contains(s, holds) => string(candidate, |c| c.contains(s.as_str()), *holds),
+        }
+    }
+}
+
+/// Whether `candidate` is a string for which `test` is `holds`
+fn string(candidate: &Value, test: impl Fn(&str) -> bool, holds: bool) -> bool {
+    matches!(candidate, Value::String(c) if test(c) == holds)
+}
+
+/// A test of the value of a variable or field
+#[derive(Debug, Clone)]
+struct Constraint<'a> {
+    path: &'a Expression,
+    test: Test,
+}
+
+/// The conjunctions of constraints of which at least one must hold for `expr`
+/// to be true, or false if `negated`. Clauses that aren't understood don't
+/// constrain anything.
+fn disjunction(expr: &Expression, negated: bool) -> Vec<Vec<Constraint<'_>>> {
+    match expr {
+        Expression::And(left, right) | Expression::Or(left, right) => {
+            let left = disjunction(left, negated);
+            let right = disjunction(right, negated);
+            // `!(a && b)` is `!a || !b`, and `!(a || b)` is `!a && !b`
+            if matches!(expr, Expression::Or(..)) != negated {
+                left.into_iter().chain(right).collect()
+            } else {
+                product(left, right)
+            }
+        }
+        Expression::Unary(UnaryOp::Not, operand) => disjunction(operand, !negated),
+        Expression::Unary(UnaryOp::DoubleNot, operand) => disjunction(operand, negated),
+        Expression::Ternary(condition, left, right) => {
+            let mut terms = product(disjunction(condition, false), disjunction(left, negated));
+            terms.extend(product(
+                disjunction(condition, true),
+                disjunction(right, negated),
+            ));
+            terms
+        }
+        Expression::Atom(Atom::Bool(b)) if *b == negated => Vec::new(),
+        expr => match constraint(expr) {
+            Some(constraint) if negated => vec![vec![Constraint {
+                path: constraint.path,
+                test: constraint.test.negate(),
+            }]],
+            Some(constraint) => vec![vec![constraint]],
+            None => vec![Vec::new()],
+        },
+    }
+}
+
+/// Each conjunction of `left` with each of `right`
+fn product<'a>(
+    left: Vec<Vec<Constraint<'a>>>,
+    right: Vec<Vec<Constraint<'a>>>,
+) -> Vec<Vec<Constraint<'a>>> {
+    let mut terms = Vec::new();
+    for l in &left {
+        for r in &right {
+            if terms.len() == MAX_CONJUNCTIONS {
+                return terms;
+            }
+            terms.push(l.iter().chain(r).cloned().collect());
+        }
+    }
+    terms
+}
+
+/// The constraint a comparison of a path with a literal puts on the path
+fn constraint(expr: &Expression) -> Option<Constraint<'_>> {
+    let (path, test) = match expr {
+        _ if is_path(expr) => (expr, Test::Compare(RelationOp::Equals, Value::Bool(true))),
+        Expression::Relation(left, RelationOp::In, right) if is_path(left) => match &**right {
+            Expression::List(items) => (
+                &**left,
+                Test::In(items.iter().map(literal).collect::<Option<_>>()?),
+            ),
+            _ => return None,
+        },
+        Expression::Relation(left, op, right) if is_path(left) => {
+            (&**left, Test::Compare(op.clone(), literal(right)?))
+        }
+        // `18 <= age` is `age >= 18`
+        Expression::Relation(left, op, right) if is_path(right) => {
+            let op = match op {
+                RelationOp::LessThan => RelationOp::GreaterThan,
+                RelationOp::LessThanEq => RelationOp::GreaterThanEq,
+                RelationOp::GreaterThan => RelationOp::LessThan,
+                RelationOp::GreaterThanEq => RelationOp::LessThanEq,
+                RelationOp::In => return None,
+                op => op.clone(),
+            };
+            (&**right, Test::Compare(op, literal(left)?))
+        }
+        Expression::FunctionCall(function, Some(target), args) if is_path(target) => {
+            let (Expression::Ident(name), [Expression::Atom(Atom::String(s))]) =
+                (&**function, args.as_slice())
+            else {
+                return None;
+            };
+            let test = match name.as_str() {
+                "startsWith" => Test::StartsWith(s.clone(), true),
+                "endsWith" => Test::EndsWith(s.clone(), true),
+                "contains" => Test::Contains(s.clone(), true),
+                _ => return None,
+            };
+            (&**target, test)
+        }
+        _ => return None,
+    };
+    Some(Constraint { path, test })
+}
+
+fn literal(expr: &Expression) -> Option<Value> {
+    match expr {
+        Expression::Atom(atom) => Some(match atom {
+            Atom::Int(i) => Value::Int(*i),
+            Atom::UInt(u) => Value::UInt(*u),
+            Atom::Float(f) => Value::Float(*f),
+            Atom::String(s) => Value::String(s.clone()),
+            Atom::Bool(b) => Value::Bool(*b),
+            Atom::Null => Value::Null,
+            Atom::Bytes(_) => return None,
+        }),
+        _ => None,
+    }
+}
+
+/// A value for each path constrained by `conjunction` that meets all of its
+/// constraints, or None if one wasn't found
+fn assign<'a>(conjunction: &[Constraint<'a>]) -> Option<Vec<(&'a Expression, Value)>> {
+    let mut assignment: Vec<(&Expression, Value)> = Vec::new();
+    for constraint in conjunction {
+        if assignment.iter().any(|(path, _)| *path == constraint.path) {
+            continue;
+        }
+        let tests: Vec<&Test> = conjunction
+            .iter()
+            .filter(|other| other.path == constraint.path)
+            .map(|other| &other.test)
+            .collect();
+        let value = candidates(&tests)
+            .into_iter()
+            .find(|candidate| tests.iter().all(|test| test.holds(candidate)))?;
+        assignment.push((constraint.path, value));
+    }
+    Some(assignment)
+}
+
+/// Values around the literals in `tests`, in the order they're tried: the
+/// literals themselves first, then their neighbours, so the bounds of ranges
+/// are preferred
+fn candidates(tests: &[&Test]) -> Vec<Value> {
+    let mut candidates = Vec::new();
+    let mut neighbours = Vec::new();
+    let (mut prefix, mut infix, mut suffix) = (String::new(), String::new(), String::new());
+    for test in tests {
+        match test {
+            Test::StartsWith(s, true) => prefix.push_str(s),
+            Test::Contains(s, true) => infix.push_str(s),
+            Test::EndsWith(s, true) => suffix.push_str(s),
+            _ => {}
+        }
+        for literal in test.literals() {
+            neighbours.extend(match &literal {
+                Value::Int(i) => vec![
+                    Value::Int(i.saturating_add(1)),
+                    Value::Int(i.saturating_sub(1)),
+                ],
+                Value::UInt(u) => vec![
+                    Value::UInt(u.saturating_add(1)),
+                    Value::UInt(u.saturating_sub(1)),
+                ],
+                Value::Float(f) => vec![Value::Float(f + 1.0), Value::Float(f - 1.0)],
+                Value::String(s) => vec![Value::String(Arc::new(format!("{}a", s)))],
+                Value::Bool(b) => vec![Value::Bool(!b)],
+                _ => Vec::new(),
+            });
+            neighbours.push(default_of(&literal));
+            candidates.push(literal);
+        }
+    }
+    if !(prefix.is_empty() && infix.is_empty() && suffix.is_empty()) {
+        for middle in ["", "a"] {
+            let combined = format!("{}{}{}{}", prefix, infix, middle, suffix);
+            candidates.push(Value::String(Arc::new(combined)));
+        }
+    }
+    candidates.extend(neighbours);
+    candidates
+}
+
+/// The zero value of the type of `value`
+fn default_of(value: &Value) -> Value {
+    match value {
+        Value::Int(_) => Value::Int(0),
+        Value::UInt(_) => Value::UInt(0),
+        Value::Float(_) => Value::Float(0.0),
+        Value::String(_) => Value::String(Arc::new(String::new())),
+        Value::Bool(_) => Value::Bool(false),
+        _ => Value::Null,
+    }
+}
+
+/// The names of a path, e.g. `["user", "age"]` for `user.age`
+pub(crate) fn names(path: &Expression) -> Vec<&str> {
+    match path {
+        Expression::Ident(name) => vec![name.as_str()],
+        Expression::Member(target, member) => {
+            let mut names = names(target);
+            if let Member::Attribute(name) = &**member {
+                names.push(name.as_str());
+            }
+            names
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The variables of an assignment, as nested dicts for fields. A path that
+/// fields are selected from is left out, as the fields decide its value.
+fn variables<'py>(
+    py: Python<'py>,
+    assignment: &[(&Expression, Value)],
+) -> PyResult<Bound<'py, PyDict>> {
+    let variables = PyDict::new_bound(py);
+    let paths: Vec<Vec<&str>> = assignment.iter().map(|(path, _)| names(path)).collect();
+    for (names, (_, value)) in paths.iter().zip(assignment) {
+        let has_fields = paths
+            .iter()
+            .any(|other| other.len() > names.len() && other.starts_with(names));
+        let Some((last, parents)) = names.split_last() else {
+            continue;
+        };
+        if has_fields {
+            continue;
+        }
+        let mut dict = variables.clone();
+        for parent in parents {
+            dict = match dict.get_item(parent)? {
+                Some(child) => child.downcast_into::<PyDict>()?,
+                None => {
+                    let child = PyDict::new_bound(py);
+                    dict.set_item(parent, &child)?;
+                    child
+                }
+            };
+        }
+        let value = RustyCelType(value.clone()).try_into_py(py, &OutputTypes::default())?;
+        dict.set_item(last, value)?;
+    }
+    Ok(variables)
+}
@@ -0,0 +1,407 @@
+//! `cel.testing`, helpers for testing libraries of expressions, such as
+//! policies, the same way across projects.
+//!
+//! `assert_evaluates` and `run_cases` check results, telling `True` from `1`
+//! and `1` from `1.0`, which compare equal in Python but are different CEL
+//! values. `assert_trace` compares the explanation of a predicate (see
+//! `cel.explain`) with a golden file, rewritten instead when `CEL_UPDATE_GOLDEN`
+//! is set. `mutants` makes changed versions of an expression, for checking that
+//! its tests would notice them.
+//!
+//! The module is also a pytest plugin, registered with the `pytest11` entry
+//! point. Its fixtures are made on first use, so that pytest is only imported
+//! by tests.
+use crate::explain::explain;
+use crate::mutants::{self, Mutant};
+use crate::strategies;
+use crate::{evaluate_value, originals, outcome_into_py, output_types, resolve_mode};
+use pyo3::exceptions::{PyAssertionError, PyBaseException, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyTuple, PyType};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// The environment variable that makes golden files be rewritten
+const UPDATE_GOLDEN: &str = "CEL_UPDATE_GOLDEN";
+
+/// The pytest fixtures the module provides
+const FIXTURES: [&str; 2] = ["cel_mode", "cel_golden"];
+
+/// Asserts that `expr` evaluates to `expected`, or raises it if `expected` is
+/// an exception class such as `ValueError`
+#[pyfunction(signature = (expr, context, expected, mode=None))]
+fn assert_evaluates(
+    py: Python<'_>,
+    expr: &str,
+    context: Option<&Bound<'_, PyAny>>,
+    expected: &Bound<'_, PyAny>,
+    mode: Option<&Bound<'_, PyAny>>,
+) -> PyResult<()> {
+    match check(py, expr, context, expected, mode)? {
+        Some(failure) => Err(PyAssertionError::new_err(failure)),
+        None => Ok(()),
+    }
+}
+
+/// Asserts that each of `cases` evaluates as expected, reporting all of those
+/// that don't. A case is a dict with `expression` and `expected` and optionally
+/// `context` and `name`, or a tuple of `(expression, expected)` or
+/// `(expression, context, expected)`. Cases without a context are evaluated with
+/// `context`.
+#[pyfunction(signature = (cases, context=None, mode=None))]
+fn run_cases(
+    py: Python<'_>,
+    cases: &Bound<'_, PyAny>,
+    context: Option<&Bound<'_, PyAny>>,
+    mode: Option<&Bound<'_, PyAny>>,
+) -> PyResult<()> {
+    let mut failures = Vec::new();
+    let mut count = 0;
+    for case in cases.iter()? {
+        let case = case?;
+        count += 1;
+        let (name, expr, case_context, expected) = unpack(&case, count)?;
+        let case_context = case_context.filter(|c| !c.is_none());
+        if let Some(failure) = check(
+            py,
+            &expr,
+            case_context.as_ref().or(context),
+            &expected,
+            mode,
+        )? {
+            failures.push(format!(
+                "  {}: {}",
+                name.unwrap_or_else(|| expr.clone()),
+                failure
+            ));
+        }
+    }
+    if failures.is_empty() {
+        return Ok(());
+    }
+    Err(PyAssertionError::new_err(format!(
+        "{} of {} cases failed:\n{}",
+        failures.len(),
+        count,
+        failures.join("\n")
+    )))
+}
+
+/// The name, expression, context and expected result of the `number`th case
+#[allow(clippy::type_complexity)]
+fn unpack<'py>(
+    case: &Bound<'py, PyAny>,
+    number: usize,
+) -> PyResult<(
+    Option<String>,
+    String,
+    Option<Bound<'py, PyAny>>,
+    Bound<'py, PyAny>,
+)> {
+    if let Ok(case) = case.downcast::<PyDict>() {
+        let required = |key: &str| {
+            case.get_item(key)?
+                .ok_or_else(|| PyTypeError::new_err(format!("case {} has no '{}'", number, key)))
+        };
+        let name = match case.get_item("name")? {
+            Some(name) => Some(name.extract()?),
+            None => None,
+        };
+        return Ok((
+            name,
+            required("expression")?.extract()?,
+            case.get_item("context")?,
+            required("expected")?,
+        ));
+    }
+    if case.is_instance_of::<PyTuple>() || case.is_instance_of::<PyList>() {
+        match case.len()? {
+            2 => return Ok((None, case.get_item(0)?.extract()?, None, case.get_item(1)?)),
+            3 => {
+                return Ok((
+                    None,
+                    case.get_item(0)?.extract()?,
+                    Some(case.get_item(1)?),
+                    case.get_item(2)?,
+                ))
+            }
+            _ => {}
+        }
+    }
+    Err(PyTypeError::new_err(format!(
+        "case {} must be a dict, or a tuple of (expression, expected) or (expression, context, expected), got {}",
+        number,
+        case.repr()?
+    )))
+}
+
+/// Why `expr` doesn't evaluate to `expected`, or None if it does
+fn check(
+    py: Python<'_>,
+    expr: &str,
+    context: Option<&Bound<'_, PyAny>>,
+    expected: &Bound<'_, PyAny>,
+    mode: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Option<String>> {
+    let options = resolve_mode(context, mode)?;
+    let result = evaluate_value(expr, context, None, None, options, None).and_then(|outcome| {
+        outcome_into_py(
+            py,
+            outcome,
+            false,
+            false,
+            output_types(context),
+            &originals(context),
+        )
+    });
+    let error_class = expected
+        .downcast::<PyType>()
+        .ok()
+        .filter(|class| class.is_subclass_of::<PyBaseException>().unwrap_or(false));
+    Ok(match (result, error_class) {
+        (Ok(value), None) if same(value.bind(py), expected)? => None,
+        (Ok(value), None) => Some(format!(
+            "'{}' evaluated to {}, expected {}",
+            expr,
+            value.bind(py).repr()?,
+            expected.repr()?
+        )),
+        (Ok(value), Some(class)) => Some(format!(
+            "expected '{}' to raise {}, it evaluated to {}",
+            expr,
+            class.name()?,
+            value.bind(py).repr()?
+        )),
+        (Err(error), Some(class)) if error.get_type_bound(py).is_subclass(class)? => None,
+        (Err(error), Some(class)) => Some(format!(
+            "expected '{}' to raise {}, it raised {}: {}",
+            expr,
+            class.name()?,
+            error.get_type_bound(py).name()?,
+            error.value_bound(py)
+        )),
+        (Err(error), None) => Some(format!(
+            "'{}' raised {}: {}, expected {}",
+            expr,
+            error.get_type_bound(py).name()?,
+            error.value_bound(py),
+            expected.repr()?
+        )),
+    })
+}
+
+/// Whether `got` equals `expected` and, in lists and dicts too, is the same
+/// kind of value, as `True == 1` and `1 == 1.0` in Python
+fn same(got: &Bound<'_, PyAny>, expected: &Bound<'_, PyAny>) -> PyResult<bool> {
+    if got.is_instance_of::<PyBool>() != expected.is_instance_of::<PyBool>()
+        || got.is_instance_of::<PyFloat>() != expected.is_instance_of::<PyFloat>()
+    {
+        return Ok(false);
+    }
+    if let (Ok(got), Ok(expected)) = (got.downcast::<PyList>(), expected.downcast::<PyList>()) {
+        if got.len() != expected.len() {
+            return Ok(false);
+        }
+        for (got, expected) in got.iter().zip(expected.iter()) {
+            if !same(&got, &expected)? {
+                return Ok(false);
+            }
+        }
+        return Ok(true);
+    }
+    if let (Ok(got), Ok(expected)) = (got.downcast::<PyDict>(), expected.downcast::<PyDict>()) {
+        if got.len() != expected.len() {
+            return Ok(false);
+        }
+        for (key, expected) in expected.iter() {
+            match got.get_item(key)? {
+                Some(got) if same(&got, &expected)? => {}
+                _ => return Ok(false),
+            }
+        }
+        return Ok(true);
+    }
+    got.eq(expected)
+}
+
+/// Asserts that the trace of `expr`, the text of its `cel.explain`
+/// explanation, is the one in the golden file at `path`. The file is written
+/// instead if `update` is true or `CEL_UPDATE_GOLDEN` is set.
+#[pyfunction(signature = (path, expr, context=None, mode=None, update=false))]
+fn assert_trace(
+    py: Python<'_>,
+    path: PathBuf,
+    expr: &str,
+    context: Option<&Bound<'_, PyAny>>,
+    mode: Option<&Bound<'_, PyAny>>,
+    update: bool,
+) -> PyResult<()> {
+    let trace = explain(py, expr, context, mode)?.text(py)? + "\n";
+    if update || std::env::var_os(UPDATE_GOLDEN).is_some_and(|value| !value.is_empty()) {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(&path, trace)?;
+        return Ok(());
+    }
+    let golden = match fs::read_to_string(&path) {
+        Ok(golden) => golden,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            return Err(PyAssertionError::new_err(format!(
+                "golden file {} doesn't exist, set {}=1 to create it",
+                path.display(),
+                UPDATE_GOLDEN
+            )))
+        }
+        Err(error) => return Err(error.into()),
+    };
+    if golden == trace {
+        return Ok(());
+    }
+    let kwargs = PyDict::new_bound(py);
+    kwargs.set_item("fromfile", path.display().to_string())?;
+    kwargs.set_item("tofile", "trace")?;
+    kwargs.set_item("lineterm", "")?;
+    let diff = py.import_bound("difflib")?.getattr("unified_diff")?.call(
+        (
+            golden.lines().collect::<Vec<_>>(),
+            trace.lines().collect::<Vec<_>>(),
+        ),
+        Some(&kwargs),
+    )?;
+    let diff = diff
+        .iter()?
+        .map(|line| line?.extract())
+        .collect::<PyResult<Vec<String>>>()?;
+    Err(PyAssertionError::new_err(format!(
+        "the trace of '{}' differs from {}:\n{}",
+        expr,
+        path.display(),
+        diff.join("\n")
+    )))
+}
+
+/// Compares traces with the golden files of a test, named after the test in
+/// `directory`. This is what the `cel_golden` fixture returns.
+#[pyclass(frozen, module = "cel.testing")]
+struct Golden {
+    #[pyo3(get)]
+    directory: PathBuf,
+    #[pyo3(get)]
+    name: String,
+    mode: Option<PyObject>,
+}
+
+#[pymethods]
+impl Golden {
+    #[new]
+    #[pyo3(signature = (directory, name, mode=None))]
+    fn new(directory: PathBuf, name: &str, mode: Option<PyObject>) -> Self {
+        // Parametrized tests are named like `test_policy[admin-1]`
+        let name = name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || "-_.".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Golden {
+            directory,
+            name,
+            mode,
+        }
+    }
+
+    /// Asserts that the trace of `expr` is the one in the golden file, with
+    /// `name` telling apart several traces of one test
+    #[pyo3(signature = (expr, context=None, name=None))]
+    fn __call__(
+        &self,
+        py: Python<'_>,
+        expr: &str,
+        context: Option<&Bound<'_, PyAny>>,
+        name: Option<&str>,
+    ) -> PyResult<()> {
+        let file = match name {
+            Some(name) => format!("{}-{}.txt", self.name, name),
+            None => format!("{}.txt", self.name),
+        };
+        let mode = self.mode.as_ref().map(|mode| mode.bind(py));
+        assert_trace(py, self.directory.join(file), expr, context, mode, false)
+    }
+}
+
+/// Fixture with the mode the other fixtures evaluate in, the default mode
+/// unless a project overrides it
+#[pyfunction]
+fn cel_mode() -> Option<PyObject> {
+    None
+}
+
+/// Fixture comparing traces with golden files kept in a `golden` directory
+/// next to the test
+#[pyfunction(signature = (request, cel_mode=None))]
+fn cel_golden(request: &Bound<'_, PyAny>, cel_mode: Option<PyObject>) -> PyResult<Golden> {
+    let directory: PathBuf = request.getattr("path")?.getattr("parent")?.extract()?;
+    let name: String = request.getattr("node")?.getattr("name")?.extract()?;
+    Ok(Golden::new(directory.join("golden"), &name, cel_mode))
+}
+
+/// Makes the fixtures when pytest first looks them up
+#[pyfunction]
+#[pyo3(name = "__getattr__")]
+fn module_getattr(py: Python<'_>, name: &str) -> PyResult<PyObject> {
+    let function = match name {
+        "cel_mode" => wrap_pyfunction_bound!(cel_mode, py)?,
+        "cel_golden" => wrap_pyfunction_bound!(cel_golden, py)?,
+        _ => {
+            return Err(pyo3::exceptions::PyAttributeError::new_err(format!(
+                "module 'cel.testing' has no attribute '{}'",
+                name
+            )))
+        }
+    };
+    let fixture = py
+        .import_bound("pytest")?
+        .getattr("fixture")?
+        .call1((function,))?;
+    py.import_bound("cel.testing")?.setattr(name, &fixture)?;
+    Ok(fixture.unbind())
+}
+
+#[pyfunction]
+#[pyo3(name = "__dir__")]
+fn module_dir(py: Python<'_>) -> PyResult<Vec<String>> {
+    let module = py.import_bound("cel.testing")?;
+    let mut names: Vec<String> = module.dict().keys().extract()?;
+    for fixture in FIXTURES {
+        if !names.iter().any(|name| name == fixture) {
+            names.push(fixture.to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// Adds the `cel.testing` module
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    let testing = PyModule::new_bound(py, "testing")?;
+    testing.add_function(wrap_pyfunction!(assert_evaluates, &testing)?)?;
+    testing.add_function(wrap_pyfunction!(run_cases, &testing)?)?;
+    testing.add_function(wrap_pyfunction!(assert_trace, &testing)?)?;
+    testing.add_function(wrap_pyfunction!(module_getattr, &testing)?)?;
+    testing.add_function(wrap_pyfunction!(module_dir, &testing)?)?;
+    testing.add_function(wrap_pyfunction!(mutants::mutants, &testing)?)?;
+    testing.add_class::<Golden>()?;
+    testing.add_class::<Mutant>()?;
+    strategies::register(&testing)?;
+    m.add_submodule(&testing)?;
+    // Lets `import cel.testing` find the module
+    py.import_bound("sys")?
+        .getattr("modules")?
+        .set_item("cel.testing", testing)
+}
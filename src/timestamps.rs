@@ -0,0 +1,157 @@
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+
+/// Formats tried, in order, for a timestamp with a UTC offset. `%#z` accepts
+/// `Z`, `+01`, `+0100` and `+01:00`.
+const OFFSET_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f%#z", "%Y-%m-%dT%H:%M%#z"];
+
+/// Formats tried, in order, for a timestamp without an offset, which is taken to be UTC
+const NAIVE_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"];
+
+/// Parses a timestamp string.
+///
+/// Unless `lenient`, only RFC 3339 is accepted, e.g. `"2024-01-02T03:04:05.5+01:00"`.
+/// Otherwise the date and time may also be separated by a space, fractional
+/// seconds may use a comma, the offset may be written `+0100` or `+01`, and
+/// timestamps without an offset, or with only a date, are taken to be UTC.
+pub fn parse(s: &str, lenient: bool) -> Result<DateTime<FixedOffset>, String> {
+    if s.as_bytes().get(10) == Some(&b'T') {
+        if let Ok(ts) = DateTime::parse_from_rfc3339(s) {
+            return Ok(ts);
+        }
+    }
+    if !lenient {
+        return Err(format!("cannot parse \"{}\" as an RFC 3339 timestamp", s));
+    }
+
+    let mut normalized = s.replace(',', ".");
+    if matches!(normalized.as_bytes().get(10), Some(b' ' | b't')) {
+        normalized.replace_range(10..11, "T");
+    }
+    let normalized = normalized.as_str();
+
+    OFFSET_FORMATS
+        .iter()
+        .find_map(|format| DateTime::parse_from_str(normalized, format).ok())
+        .or_else(|| {
+            NAIVE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(normalized, format).ok())
+                .or_else(|| {
+                    NaiveDate::parse_from_str(normalized, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|date| date.and_hms_opt(0, 0, 0))
+                })
+                .map(|naive| naive.and_utc().fixed_offset())
+        })
+        .ok_or_else(|| format!("cannot parse \"{}\" as a timestamp", s))
+}
+
+/// Converts an integer number of `unit`s since the Unix epoch to a timestamp
+fn from_epoch(value: i64, unit: &str) -> Result<DateTime<Utc>, String> {
+    match unit {
+        "s" => DateTime::from_timestamp(value, 0),
+        "ms" => DateTime::from_timestamp_millis(value),
+        "us" => DateTime::from_timestamp_micros(value),
+        "ns" => Some(DateTime::from_timestamp_nanos(value)),
+        _ => return Err(unknown_unit(unit)),
+    }
+    .ok_or_else(|| format!("timestamp {}{} is out of range", value, unit))
+}
+
+/// Converts a fractional number of `unit`s since the Unix epoch to a timestamp,
+/// rounded to the nearest nanosecond
+fn from_epoch_float(value: f64, unit: &str) -> Result<DateTime<Utc>, String> {
+    let per_second = match unit {
+        "s" => 1.0,
+        "ms" => 1e3,
+        "us" => 1e6,
+        "ns" => 1e9,
+        _ => return Err(unknown_unit(unit)),
+    };
+    let seconds = value / per_second;
+    let whole = seconds.floor();
+    let nanos = ((seconds - whole) * 1e9).round().min(999_999_999.0);
+    // Outside this range `as` would saturate rather than fail
+    if !(i64::MIN as f64..i64::MAX as f64).contains(&whole) {
+        return Err(format!("timestamp {:?}{} is out of range", value, unit));
+    }
+    DateTime::from_timestamp(whole as i64, nanos as u32)
+        .ok_or_else(|| format!("timestamp {:?}{} is out of range", value, unit))
+}
+
+fn unknown_unit(unit: &str) -> String {
+    format!(
+        "unknown unit \"{}\" for timestamp, expected one of s, ms, us or ns",
+        unit
+    )
+}
+
+/// Implementation of `timestamp()` with lenient timestamps.
+///
+/// Strings are parsed leniently (see [`parse`]), and numbers are taken as time
+/// since the Unix epoch, in seconds unless a unit is given.
+///
+/// # Examples
+/// ```cel
+/// timestamp('2024-01-02 03:04:05') == timestamp('2024-01-02T03:04:05Z')
+/// timestamp(1717000000) == timestamp(1717000000000, 'ms')
+/// timestamp(1717000000.5) == timestamp('2024-05-29T16:26:40.5Z')
+/// ```
+pub fn timestamp(ftx: &FunctionContext) -> ResolveResult {
+    convert(ftx, true)
+}
+
+/// Implementation of `timestamp()` as the CEL specification defines it, which
+/// accepts an RFC 3339 string or an integer number of seconds since the Unix epoch.
+pub fn strict_timestamp(ftx: &FunctionContext) -> ResolveResult {
+    convert(ftx, false)
+}
+
+fn convert(ftx: &FunctionContext, lenient: bool) -> ResolveResult {
+    let (value, args) = match &ftx.this {
+        Some(this) => (this.clone(), &ftx.args[..]),
+        None => match ftx.args.split_first() {
+            Some((value, args)) => (ftx.ptx.resolve(value)?, args),
+            None => return Err(ExecutionError::invalid_argument_count(1, 0)),
+        },
+    };
+    let unit = match (args, lenient) {
+        ([], _) => None,
+        ([unit], true) => match ftx.ptx.resolve(unit)? {
+            Value::String(unit) => Some(unit),
+            other => {
+                return Err(ftx.error(format!(
+                    "timestamp unit must be a string, got {}",
+                    other.type_of()
+                )))
+            }
+        },
+        _ => {
+            let expected = if lenient { 2 } else { 1 };
+            return Err(ExecutionError::invalid_argument_count(
+                expected,
+                args.len() + 1,
+            ));
+        }
+    };
+    let unit = unit.as_deref().map_or("s", String::as_str);
+
+    let result = match (value, lenient) {
+        (Value::Timestamp(ts), _) if args.is_empty() => return Ok(Value::Timestamp(ts)),
+        (Value::String(s), _) if args.is_empty() => parse(&s, lenient),
+        (Value::Timestamp(_) | Value::String(_), _) => {
+            return Err(ftx.error("a unit can only be given with a number"))
+        }
+        (Value::Int(i), _) => from_epoch(i, unit).map(|ts| ts.fixed_offset()),
+        (Value::UInt(u), true) => i64::try_from(u)
+            .map_err(|_| format!("timestamp {}{} is out of range", u, unit))
+            .and_then(|i| from_epoch(i, unit))
+            .map(|ts| ts.fixed_offset()),
+        (Value::Float(f), true) => from_epoch_float(f, unit).map(|ts| ts.fixed_offset()),
+        (other, _) => {
+            return Err(ftx.error(format!("cannot convert {} to timestamp", other.type_of())))
+        }
+    };
+    result.map(Value::Timestamp).map_err(|e| ftx.error(e))
+}
@@ -0,0 +1,122 @@
+//! `cel.tokenize`, which splits an expression into the tokens the parser reads,
+//! for syntax highlighting.
+//!
+//! The lexer of the parser isn't public, so its terminals are mirrored here:
+//! like it, the longest match wins and a keyword or operator wins a tie with a
+//! pattern, which is why `x-1` is read as `x` followed by the int `-1`.
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use regex::Regex;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+/// The patterns of the grammar's terminals, in the order ties between them are
+/// broken
+const PATTERNS: &[(&str, &str)] = &[
+    ("comment", r"//[^\n\r]*"),
+    ("int", r"-?[0-9]+"),
+    ("int", r"-?0[xX][0-9a-fA-F]+"),
+    ("uint", r"[0-9]+[uU]"),
+    ("uint", r"-?0[xX][0-9a-fA-F]+[uU]"),
+    ("float", r"[-+]?[0-9]*\.[0-9]+([eE][-+]?[0-9]+)?"),
+    ("float", r"[-+]?[0-9]+[eE][-+]?[0-9]+"),
+    ("string", r#"[rR]?"([^"\\]*(?:\\.[^"\\]*)*)""#),
+    ("string", r#"[rR]?'([^'\\]*(?:\\.[^'\\]*)*)'"#),
+    ("bytes", r#"[bB]"(\\.|[^"\n])*""#),
+    ("bytes", r#"[bB]'(\\.|[^'\n])*'"#),
+    ("identifier", r"[_a-zA-Z][_a-zA-Z0-9]*"),
+];
+
+const KEYWORDS: &[&str] = &["true", "false", "null", "in"];
+
+const OPERATORS: &[&str] = &[
+    "!", "!!", "!=", "%", "&&", "*", "+", "-", "--", "/", "<", "<=", "==", ">", ">=", "?", ":",
+    "||",
+];
+
+const PUNCTUATION: &[&str] = &[".", ",", "(", ")", "[", "]", "{", "}"];
+
+fn patterns() -> &'static [(&'static str, Regex)] {
+    static PATTERNS_CELL: OnceLock<Vec<(&str, Regex)>> = OnceLock::new();
+    PATTERNS_CELL.get_or_init(|| {
+        PATTERNS
+            .iter()
+            .map(|(kind, pattern)| (*kind, Regex::new(&format!("^(?:{})", pattern)).unwrap()))
+            .collect()
+    })
+}
+
+/// The kind and length in bytes of the token `rest` starts with
+pub(crate) fn next_token(rest: &str) -> (&'static str, usize) {
+    let mut longest = ("error", rest.chars().next().map_or(0, char::len_utf8));
+    let mut matched = false;
+    for (kind, terminals) in [
+        ("keyword", KEYWORDS),
+        ("operator", OPERATORS),
+        ("punctuation", PUNCTUATION),
+    ] {
+        for terminal in terminals {
+            if rest.starts_with(terminal) && (!matched || terminal.len() > longest.1) {
+                longest = (kind, terminal.len());
+                matched = true;
+            }
+        }
+    }
+    for (kind, pattern) in patterns() {
+        if let Some(found) = pattern.find(rest) {
+            if found.end() > 0 && (!matched || found.end() > longest.1) {
+                longest = (kind, found.end());
+                matched = true;
+            }
+        }
+    }
+    longest
+}
+
+/// The tokens of `expression` as dicts of their `kind`, `text` and the `start`
+/// and `end` of it as character offsets, leaving out whitespace.
+///
+/// The kinds are "int", "uint", "float", "string", "bytes", "identifier",
+/// "keyword", "operator", "punctuation" and "comment". A character that can't
+/// start a token, such as the quote of an unterminated string, is an "error"
+/// token of its own, so an expression that doesn't parse can still be
+/// highlighted.
+#[pyfunction]
+pub fn tokenize<'py>(py: Python<'py>, expression: &str) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let mut chars = 0;
+    let mut previous = 0;
+    tokens(expression)
+        .into_iter()
+        .map(|(kind, range)| {
+            let text = &expression[range.clone()];
+            chars += expression[previous..range.start].chars().count();
+            let start = chars;
+            chars += text.chars().count();
+            previous = range.end;
+            let token = PyDict::new_bound(py);
+            token.set_item("kind", kind)?;
+            token.set_item("text", text)?;
+            token.set_item("start", start)?;
+            token.set_item("end", chars)?;
+            Ok(token)
+        })
+        .collect()
+}
+
+/// The kinds of the tokens of `expression` and their byte ranges
+pub(crate) fn tokens(expression: &str) -> Vec<(&'static str, Range<usize>)> {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    while offset < expression.len() {
+        let rest = &expression[offset..];
+        let whitespace = rest.len() - rest.trim_start().len();
+        if whitespace > 0 {
+            offset += whitespace;
+            continue;
+        }
+        let (kind, length) = next_token(rest);
+        tokens.push((kind, offset..offset + length));
+        offset += length;
+    }
+    tokens
+}
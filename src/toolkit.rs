@@ -0,0 +1,237 @@
+//! The built-in `toolkit` extension, loaded with `Context(extensions=["toolkit"])`,
+//! with Rust implementations of string helpers that are often added as Python
+//! functions, so that calling them doesn't take the GIL.
+//!
+//! Each function can be called either as a function or as a method of its first
+//! argument, e.g. both `slugify(title)` and `title.slugify()`.
+use crate::functions::Described;
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use std::sync::Arc;
+
+/// The name the extension is loaded by
+pub const NAME: &str = "toolkit";
+
+/// The functions of the extension, with their signature and what they do, for
+/// `Context.functions_info`
+pub const FUNCTIONS: &[Described] = &[
+    (
+        "slugify",
+        "slugify(string) -> string",
+        "The string in lower case, with each run of characters other than letters and digits \
+         replaced by a hyphen",
+    ),
+    (
+        "levenshtein",
+        "levenshtein(string, string) -> int",
+        "The number of characters to insert, delete or replace to turn one string into the other",
+    ),
+    (
+        "globMatch",
+        "globMatch(string, pattern) -> bool",
+        "Whether the string matches a glob pattern of '*', '?' and '[...]'",
+    ),
+    (
+        "splitCsv",
+        "splitCsv(string) -> list",
+        "The fields of a line of comma-separated values, with quoted fields unquoted",
+    ),
+];
+
+/// Adds the functions of the extension to an environment
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function("slugify", slugify);
+    environment.add_function("levenshtein", levenshtein);
+    environment.add_function("globMatch", glob_match);
+    environment.add_function("splitCsv", split_csv);
+}
+
+/// The `N` strings a function is called with, the value it's called on first
+fn strings<const N: usize>(ftx: &FunctionContext) -> Result<[Arc<String>; N], ExecutionError> {
+    let this = ftx.this.iter().cloned().map(Ok);
+    let args = ftx.args.iter().map(|arg| ftx.ptx.resolve(arg));
+    let values = this.chain(args).collect::<Result<Vec<_>, _>>()?;
+    if values.len() != N {
+        return Err(ExecutionError::invalid_argument_count(N, values.len()));
+    }
+    let strings = values
+        .into_iter()
+        .map(|value| match value {
+            Value::String(string) => Ok(string),
+            other => Err(ftx.error(format!("expected a string, got {}", other.type_of()))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(strings.try_into().expect("checked the count"))
+}
+
+/// Returns the string in lower case, with each run of characters other than
+/// letters and digits replaced by a hyphen, and none at either end.
+///
+/// # Examples
+/// ```cel
+/// slugify('Hello, World!') == 'hello-world'
+/// ```
+pub fn slugify(ftx: &FunctionContext) -> ResolveResult {
+    let [text] = strings(ftx)?;
+    let mut slug = String::with_capacity(text.len());
+    let words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty());
+    for word in words {
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.extend(word.chars().flat_map(char::to_lowercase));
+    }
+    Ok(Value::String(Arc::new(slug)))
+}
+
+/// Returns the Levenshtein distance between two strings, counted in
+/// characters.
+///
+/// # Examples
+/// ```cel
+/// levenshtein('kitten', 'sitting') == 3
+/// ```
+pub fn levenshtein(ftx: &FunctionContext) -> ResolveResult {
+    let [a, b] = strings(ftx)?;
+    Ok(Value::Int(distance(&a, &b) as i64))
+}
+
+/// The Levenshtein distance between two strings, counted in characters
+pub fn distance(a: &str, b: &str) -> usize {
+    if a == b {
+        return 0;
+    }
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // The distances from the prefixes of `a` to the previous and current prefix of `b`
+    let mut previous: Vec<usize> = (0..=a.len()).collect();
+    let mut current = vec![0; a.len() + 1];
+    for (j, cb) in b.iter().enumerate() {
+        current[0] = j + 1;
+        for (i, ca) in a.iter().enumerate() {
+            let replace = previous[i] + usize::from(ca != cb);
+            current[i + 1] = replace.min(previous[i + 1] + 1).min(current[i] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[a.len()]
+}
+
+/// Returns whether the string matches a glob pattern, in which `*` matches any
+/// characters, including `/`, `?` matches one character, and `[...]` one of a
+/// set of characters and ranges, or of those not in it with `[!...]`.
+///
+/// # Examples
+/// ```cel
+/// globMatch('reports/2024.csv', 'reports/*.csv')
+/// ```
+pub fn glob_match(ftx: &FunctionContext) -> ResolveResult {
+    let [text, pattern] = strings(ftx)?;
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    Ok(Value::Bool(glob_matches(&text, &pattern)))
+}
+
+fn glob_matches(text: &[char], pattern: &[char]) -> bool {
+    let (mut t, mut p) = (0, 0);
+    // The position after the last `*` seen, and where in the text it stopped
+    // matching, to match one more character with it when the rest fails
+    let mut star = None;
+    while t < text.len() {
+        if pattern.get(p) == Some(&'*') {
+            p += 1;
+            star = Some((p, t));
+            continue;
+        }
+        if p < pattern.len() {
+            let (matched, length) = element(&pattern[p..], text[t]);
+            if matched {
+                p += length;
+                t += 1;
+                continue;
+            }
+        }
+        match star {
+            Some((after, stopped)) => {
+                p = after;
+                t = stopped + 1;
+                star = Some((after, t));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Whether the element of a glob pattern at the start of `pattern` matches `c`,
+/// and its length. A `[` without a closing `]` is a literal.
+fn element(pattern: &[char], c: char) -> (bool, usize) {
+    match pattern[0] {
+        '?' => (true, 1),
+        '[' => set(pattern, c).unwrap_or((c == '[', 1)),
+        literal => (literal == c, 1),
+    }
+}
+
+/// Whether the set at the start of `pattern` matches `c`, and its length, or
+/// None if it isn't closed. A `]` first in the set is one of its characters.
+fn set(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let first = i;
+    let mut matched = false;
+    loop {
+        let start = *pattern.get(i)?;
+        if start == ']' && i > first {
+            return Some((matched != negated, i + 1));
+        }
+        match (pattern.get(i + 1), pattern.get(i + 2)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                matched |= (start..=end).contains(&c);
+                i += 3;
+            }
+            _ => {
+                matched |= start == c;
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Returns the fields of a line of comma-separated values. A field that starts
+/// with a double quote is unquoted, and may contain commas and doubled quotes.
+///
+/// # Examples
+/// ```cel
+/// splitCsv('a,"b, c",""""') == ['a', 'b, c', '"']
+/// ```
+pub fn split_csv(ftx: &FunctionContext) -> ResolveResult {
+    let [line] = strings(ftx)?;
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let (mut quoted, mut start) = (false, true);
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if start => quoted = true,
+            ',' if !quoted => {
+                fields.push(Value::String(Arc::new(std::mem::take(&mut field))));
+                start = true;
+                continue;
+            }
+            c => field.push(c),
+        }
+        start = false;
+    }
+    fields.push(Value::String(Arc::new(field)));
+    Ok(Value::List(Arc::new(fields)))
+}
@@ -0,0 +1,142 @@
+use cel_interpreter::objects::Key;
+use cel_interpreter::{Context, FunctionContext, ResolveResult, Value};
+use cel_parser::{Expression, Member};
+use std::sync::Arc;
+
+/// Name of the internal function that field selections are rewritten into when
+/// safe navigation is enabled. It isn't a valid CEL identifier so it can't clash
+/// with a user supplied function.
+pub const SAFE_SELECT: &str = "@select";
+
+/// Variable the selection target is bound to while a rewritten selection is resolved.
+const SELECT_TARGET: &str = "@target";
+
+/// Rebuilds `expr` with `f` applied to each of its direct children.
+pub fn map_children<F>(expr: &Expression, mut f: F) -> Expression
+where
+    F: FnMut(&Expression) -> Expression,
+{
+    match expr {
+        Expression::Arithmetic(left, op, right) => {
+            Expression::Arithmetic(f(left).into(), op.clone(), f(right).into())
+        }
+        Expression::Relation(left, op, right) => {
+            Expression::Relation(f(left).into(), op.clone(), f(right).into())
+        }
+        Expression::Ternary(cond, left, right) => {
+            Expression::Ternary(f(cond).into(), f(left).into(), f(right).into())
+        }
+        Expression::Or(left, right) => Expression::Or(f(left).into(), f(right).into()),
+        Expression::And(left, right) => Expression::And(f(left).into(), f(right).into()),
+        Expression::Unary(op, operand) => Expression::Unary(op.clone(), f(operand).into()),
+        Expression::Member(target, member) => {
+            Expression::Member(f(target).into(), map_member(member, &mut f).into())
+        }
+        Expression::FunctionCall(name, target, args) => Expression::FunctionCall(
+            name.clone(),
+            target.as_ref().map(|t| f(t).into()),
+            args.iter().map(&mut f).collect(),
+        ),
+        Expression::List(items) => Expression::List(items.iter().map(&mut f).collect()),
+        Expression::Map(entries) => {
+            Expression::Map(entries.iter().map(|(k, v)| (f(k), f(v))).collect())
+        }
+        Expression::Atom(_) | Expression::Ident(_) => expr.clone(),
+    }
+}
+
+fn map_member<F>(member: &Member, f: &mut F) -> Member
+where
+    F: FnMut(&Expression) -> Expression,
+{
+    match member {
+        Member::Attribute(name) => Member::Attribute(name.clone()),
+        Member::Index(index) => Member::Index(f(index).into()),
+        Member::Fields(fields) => Member::Fields(
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), f(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// A call to the global function `name`
+pub fn call(name: &str, args: Vec<Expression>) -> Expression {
+    Expression::FunctionCall(
+        Expression::Ident(Arc::new(name.to_string())).into(),
+        None,
+        args,
+    )
+}
+
+/// Returns true if `expr` is a call to the global function `name`.
+pub fn is_call_to(expr: &Expression, name: &str) -> bool {
+    match expr {
+        Expression::FunctionCall(function, None, _) => {
+            matches!(&**function, Expression::Ident(ident) if ident.as_str() == name)
+        }
+        _ => false,
+    }
+}
+
+/// Rewrites every field selection and index into a call to [`SAFE_SELECT`] so that
+/// selecting a missing map key, or selecting anything from `null`, yields `null`
+/// rather than an error.
+///
+/// Arguments to `has()` are left untouched as it relies on the missing key error.
+pub fn safe_navigation(expr: &Expression) -> Expression {
+    match expr {
+        Expression::Member(target, member) => Expression::FunctionCall(
+            Expression::Ident(Arc::new(SAFE_SELECT.to_string())).into(),
+            None,
+            vec![
+                safe_navigation(target),
+                Expression::Member(
+                    Expression::Ident(Arc::new(SELECT_TARGET.to_string())).into(),
+                    map_member(member, &mut safe_navigation).into(),
+                ),
+            ],
+        ),
+        _ if is_call_to(expr, "has") => expr.clone(),
+        _ => map_children(expr, safe_navigation),
+    }
+}
+
+/// Implementation of [`SAFE_SELECT`].
+///
+/// The first argument is the selection target and the second is the original
+/// selection applied to [`SELECT_TARGET`], which is used whenever the target
+/// isn't null and the selection isn't a missing map field.
+pub fn safe_select(ftx: &FunctionContext) -> ResolveResult {
+    let target = ftx.ptx.resolve(&ftx.args[0])?;
+    let selection = &ftx.args[1];
+
+    if let (Value::Map(map), Expression::Member(_, member)) = (&target, selection) {
+        if let Member::Attribute(name) = &**member {
+            if !map.map.contains_key(&Key::String(name.clone())) {
+                return Ok(Value::Null);
+            }
+        }
+    }
+
+    match target {
+        Value::Null => Ok(Value::Null),
+        target => {
+            let mut scope = ftx.ptx.new_inner_scope();
+            scope.add_variable_from_value(SELECT_TARGET, target);
+            scope.resolve(selection)
+        }
+    }
+}
+
+/// Applies `member` to an already resolved `target` using the interpreter's own
+/// member resolution.
+pub fn resolve_member(ctx: &Context, target: Value, member: &Member) -> ResolveResult {
+    let mut scope = ctx.new_inner_scope();
+    scope.add_variable_from_value(SELECT_TARGET, target);
+    scope.resolve(&Expression::Member(
+        Expression::Ident(Arc::new(SELECT_TARGET.to_string())).into(),
+        member.clone().into(),
+    ))
+}
@@ -0,0 +1,658 @@
+use crate::functions::{this_or_arg, BUILTINS};
+use crate::transform::{call, is_call_to, map_children};
+use crate::{objects, plan, repr_of};
+use cel_interpreter::extractors::This;
+use cel_interpreter::functions;
+use cel_interpreter::objects::{Key, Map};
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use cel_parser::{Atom, Expression, Member, RelationOp};
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple, PyType};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// The types that can be referred to by name in an expression, e.g. `type(x) == int`
+const NAMED: [&str; 10] = [
+    "bool",
+    "bytes",
+    "double",
+    "int",
+    "list",
+    "map",
+    "null_type",
+    "string",
+    "type",
+    "uint",
+];
+
+const TIMESTAMP: &str = "google.protobuf.Timestamp";
+const DURATION: &str = "google.protobuf.Duration";
+
+/// The key that values of registered types hold their type under, which can't
+/// be written as a field name in an expression. It is hidden from their size,
+/// their keys and indexing, and can't be a key of a map literal.
+pub const TYPE_KEY: &str = "@type";
+
+/// Internal function that constructions like `my.Money{cents: 5}` are rewritten into
+pub const NEW: &str = "@new";
+
+/// Internal function that indexes are rewritten into for the interpreter while
+/// types are registered, called as `@index(target, index)`, so that the key
+/// values of registered types hold their type under can't be selected
+pub const INDEX: &str = "@index";
+
+/// A Python class registered with `cel.register_type`
+pub struct Registered {
+    pub name: String,
+    class: Py<PyType>,
+    pub fields: Vec<String>,
+    /// Methods of the class that can be called on values of the type
+    methods: Vec<String>,
+}
+
+static REGISTERED: Mutex<Vec<Arc<Registered>>> = Mutex::new(Vec::new());
+
+/// The registered types, copied so that no lock is held while calling Python
+fn registered() -> Vec<Arc<Registered>> {
+    REGISTERED.lock().unwrap().clone()
+}
+
+/// A type value, as returned by `type()`.
+///
+/// The interpreter has no type values, so they are represented by a function
+/// value without a target, which it never produces itself.
+pub fn type_value(name: &str) -> Value {
+    Value::Function(Arc::new(name.to_string()), None)
+}
+
+/// The CEL name of a value's type, e.g. `"double"` where the interpreter says `"float"`
+pub fn name_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null_type",
+        Value::Bool(_) => "bool",
+        Value::Int(_) => "int",
+        Value::UInt(_) => "uint",
+        Value::Float(_) => "double",
+        Value::String(_) => "string",
+        Value::Bytes(_) => "bytes",
+        Value::List(_) => "list",
+        Value::Map(_) => "map",
+        Value::Timestamp(_) => TIMESTAMP,
+        Value::Duration(_) => DURATION,
+        Value::Function(_, None) => "type",
+        Value::Function(_, Some(_)) => "function",
+    }
+}
+
+/// Whether `name` is a variable that refers to a type, or to the namespace of
+/// qualified type names like `google.protobuf.Timestamp`
+pub fn is_type_name(name: &str) -> bool {
+    NAMED.contains(&name)
+        || name == "google"
+        || registered()
+            .iter()
+            .any(|registered| registered.name.split('.').next() == Some(name))
+}
+
+/// The registered type that `object` is an instance of, if any
+pub fn registered_class(object: &Bound<'_, PyAny>) -> PyResult<Option<Arc<Registered>>> {
+    for registered in registered() {
+        if object.is_instance(registered.class.bind(object.py()))? {
+            return Ok(Some(registered));
+        }
+    }
+    Ok(None)
+}
+
+/// What values of the registered type `name` hold under [`TYPE_KEY`]: a function
+/// value named after the key, which expressions can't produce, so that no map
+/// they build passes for a value of a registered type
+pub fn marker(name: &str) -> Value {
+    Value::Function(
+        Arc::new(TYPE_KEY.to_string()),
+        Some(Box::new(type_value(name))),
+    )
+}
+
+/// The name of the registered type whose [`marker`] a map holds
+fn marked_type(map: &Map) -> Option<&Arc<String>> {
+    match map.map.get(&Key::String(Arc::new(TYPE_KEY.to_string())))? {
+        Value::Function(key, Some(target)) if key.as_str() == TYPE_KEY => match &**target {
+            Value::Function(name, None) => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The registered type of a map converted from an instance of one
+pub fn registered_type(map: &Map) -> Option<Arc<Registered>> {
+    let name = marked_type(map)?;
+    registered()
+        .into_iter()
+        .find(|registered| registered.name == **name)
+}
+
+/// Whether `value` is a value of a registered type
+fn is_registered(value: &Value) -> bool {
+    matches!(value, Value::Map(map) if marked_type(map).is_some())
+}
+
+/// Whether `key` of `map` is the one a value of a registered type holds its type
+/// under, which is hidden from expressions
+pub fn is_hidden(map: &Map, key: &Key) -> bool {
+    matches!(key, Key::String(key) if key.as_str() == TYPE_KEY) && marked_type(map).is_some()
+}
+
+impl Registered {
+    /// An instance of the class, created with the `fields` as keyword arguments
+    pub fn instance(&self, fields: &Bound<'_, PyDict>) -> PyResult<PyObject> {
+        Ok(self
+            .class
+            .bind(fields.py())
+            .call((), Some(fields))?
+            .unbind())
+    }
+}
+
+/// Register a Python class as the CEL type `name`, e.g. `"my.Money"`.
+///
+/// Instances of the class are converted to values of that type, whose `fields`
+/// can be selected, and which are converted back to instances of the class by
+/// calling it with the fields as keyword arguments. `type(x) == my.Money` holds
+/// for them, and `my.Money{cents: 5}` constructs one. The fields of dataclasses
+/// and named tuples are found when not given.
+///
+/// The `methods` of the class named can be called on values of the type, e.g.
+/// `price.formatted()`, on an instance created from the value's fields.
+///
+/// Registering a name again replaces the class it refers to. Contexts convert
+/// their variables when they are created, so register types before that.
+#[pyfunction]
+#[pyo3(signature = (name, cls, fields=None, methods=None))]
+pub fn register_type(
+    py: Python<'_>,
+    name: &str,
+    cls: &Bound<'_, PyType>,
+    fields: Option<&Bound<'_, PyAny>>,
+    methods: Option<&Bound<'_, PyAny>>,
+) -> PyResult<()> {
+    let segments: Vec<&str> = name.split('.').collect();
+    let identifier = |segment: &&str| {
+        segment.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    if !segments.iter().all(identifier) || ["true", "false", "null", "in"].contains(&segments[0]) {
+        return Err(PyValueError::new_err(format!(
+            "'{}' isn't a valid type name, it must be identifiers separated by dots",
+            name
+        )));
+    }
+    if NAMED.contains(&segments[0]) || segments[0] == "google" {
+        return Err(PyValueError::new_err(format!(
+            "'{}' would hide the builtin type '{}'",
+            name, segments[0]
+        )));
+    }
+
+    let fields: Vec<String> = match fields {
+        Some(fields) => fields
+            .iter()?
+            .map(|field| field?.extract())
+            .collect::<PyResult<_>>()?,
+        None => {
+            let dataclasses = py.import_bound("dataclasses")?;
+            if dataclasses
+                .call_method1("is_dataclass", (cls,))?
+                .is_truthy()?
+            {
+                dataclasses
+                    .call_method1("fields", (cls,))?
+                    .iter()?
+                    .map(|field| field?.getattr("name")?.extract())
+                    .collect::<PyResult<_>>()?
+            } else if let Ok(fields) = cls.getattr("_fields") {
+                fields.extract()?
+            } else {
+                return Err(PyValueError::new_err(format!(
+                    "the fields of {} must be given, it isn't a dataclass or named tuple",
+                    cls.name()?
+                )));
+            }
+        }
+    };
+
+    let methods: Vec<String> = match methods {
+        Some(methods) => methods
+            .iter()?
+            .map(|method| method?.extract())
+            .collect::<PyResult<_>>()?,
+        None => Vec::new(),
+    };
+    for method in &methods {
+        if BUILTINS.iter().any(|(builtin, _, _)| builtin == method) {
+            return Err(PyValueError::new_err(format!(
+                "method '{}' would replace the builtin function of the same name",
+                method
+            )));
+        }
+        if !cls.getattr(method.as_str()).is_ok_and(|m| m.is_callable()) {
+            return Err(PyValueError::new_err(format!(
+                "{} has no method '{}'",
+                cls.name()?,
+                method
+            )));
+        }
+    }
+
+    let mut registry = REGISTERED.lock().unwrap();
+    let nested = |outer: &str, inner: &str| inner.starts_with(&format!("{}.", outer));
+    if let Some(other) = registry
+        .iter()
+        .find(|other| nested(&other.name, name) || nested(name, &other.name))
+    {
+        return Err(PyValueError::new_err(format!(
+            "'{}' can't be both a type and a namespace, as '{}' is registered",
+            name, other.name
+        )));
+    }
+    registry.retain(|other| other.name != name);
+    registry.push(Arc::new(Registered {
+        name: name.to_string(),
+        class: cls.clone().unbind(),
+        fields,
+        methods,
+    }));
+    Ok(())
+}
+
+/// Adds `type()` and the type names to the environment, including those of
+/// registered types.
+///
+/// Variables added afterwards, like those of the evaluation context, take
+/// precedence over the type names.
+pub fn register(environment: &mut cel_interpreter::Context) {
+    environment.add_function("type", r#type);
+    environment.add_function("size", size);
+    environment.add_function(NEW, construct);
+    environment.add_function(INDEX, index);
+    for name in NAMED {
+        environment.add_variable_from_value(name, type_value(name));
+    }
+    for registered in registered() {
+        for method in &registered.methods {
+            environment.add_function(method, call_method);
+        }
+    }
+
+    // So that qualified names like `google.protobuf.Timestamp` can be selected
+    let mut names = vec![TIMESTAMP.to_string(), DURATION.to_string()];
+    names.extend(
+        registered()
+            .iter()
+            .map(|registered| registered.name.clone()),
+    );
+    let qualified: Vec<(Vec<&str>, &str)> = names
+        .iter()
+        .map(|name| (name.split('.').collect(), name.as_str()))
+        .collect();
+    let qualified = qualified
+        .iter()
+        .map(|(segments, name)| (&segments[..], *name))
+        .collect();
+    for (name, value) in namespace(qualified) {
+        environment.add_variable_from_value(name, value);
+    }
+}
+
+/// The members of a namespace, given the rest of the segments of each type name
+/// in it: type values, or nested namespaces as maps
+fn namespace(names: Vec<(&[&str], &str)>) -> HashMap<String, Value> {
+    let mut nested: BTreeMap<&str, Vec<(&[&str], &str)>> = BTreeMap::new();
+    let mut members = HashMap::new();
+    for (segments, name) in names {
+        match segments {
+            [last] => {
+                members.insert(last.to_string(), type_value(name));
+            }
+            [first, rest @ ..] => nested.entry(*first).or_default().push((rest, name)),
+            [] => {}
+        }
+    }
+    for (first, names) in nested {
+        members.insert(first.to_string(), Value::from(namespace(names)));
+    }
+    members
+}
+
+/// Implementation of `type()`, which returns the type of its argument.
+///
+/// # Examples
+/// ```cel
+/// type(1) == int
+/// type(type(1)) == type
+/// type(timestamp('2024-01-02T03:04:05Z')) == google.protobuf.Timestamp
+/// ```
+pub fn r#type(ftx: &FunctionContext) -> ResolveResult {
+    Ok(type_value(&type_name(&this_or_arg(ftx)?)))
+}
+
+/// The name of the type of `value`, which for values of registered types is
+/// the name they were registered with
+pub fn type_name(value: &Value) -> String {
+    if let Value::Map(map) = value {
+        if let Some(name) = marked_type(map) {
+            return name.to_string();
+        }
+    }
+    name_of(value).to_string()
+}
+
+/// Implementation of `size()`, which doesn't count the key values of registered
+/// types hold their type under among their fields
+fn size(ftx: &FunctionContext) -> ResolveResult {
+    match this_or_arg(ftx)? {
+        Value::Map(map) if marked_type(&map).is_some() => Ok(Value::Int(map.map.len() as i64 - 1)),
+        value => functions::size(ftx, This(value)).map(Value::Int),
+    }
+}
+
+/// Applies the Python operator `symbol`, e.g. `+` or `<`, when either operand is
+/// a value of a registered type, to instances created from the values, with the
+/// result converted like those of Python functions. Their values are maps only
+/// to hold their fields, so map operators aren't applied to them.
+pub fn operator(symbol: &str, left: &Value, right: &Value) -> Option<ResolveResult> {
+    if !is_registered(left) && !is_registered(right) {
+        return None;
+    }
+    let failed = |message: String| ExecutionError::FunctionError {
+        function: symbol.to_string(),
+        message,
+    };
+    Some(Python::with_gil(|py| {
+        let output = Default::default();
+        let instance = |value: &Value| {
+            crate::RustyCelType(value.clone())
+                .try_into_py(py, &output)
+                .map_err(|e| failed(e.to_string()))
+        };
+        let (left, right) = (instance(left)?, instance(right)?);
+        let result = objects::apply(symbol, left.bind(py), right.bind(py))
+            .map_err(|e| failed(e.to_string()))?;
+        crate::Converter::default()
+            .convert(&result)
+            .map_err(|e| failed(e.to_string()))
+    }))
+}
+
+/// Orders `left` and `right` with a Python operator when either is a value of a
+/// registered type, see [`operator`]
+pub fn compare(
+    op: &RelationOp,
+    left: &Value,
+    right: &Value,
+) -> Option<Result<bool, ExecutionError>> {
+    let symbol = match op {
+        RelationOp::LessThan => "<",
+        RelationOp::LessThanEq => "<=",
+        RelationOp::GreaterThan => ">",
+        RelationOp::GreaterThanEq => ">=",
+        RelationOp::Equals | RelationOp::NotEquals | RelationOp::In => return None,
+    };
+    Some(match operator(symbol, left, right)? {
+        Ok(Value::Bool(ordered)) => Ok(ordered),
+        Ok(value) => Err(ExecutionError::function_error(
+            symbol,
+            format!(
+                "expected a bool from the comparison, got {}",
+                type_name(&value)
+            ),
+        )),
+        Err(error) => Err(error),
+    })
+}
+
+/// The methods of registered types, which are Python methods that take the GIL
+pub fn methods() -> Vec<String> {
+    registered()
+        .iter()
+        .flat_map(|registered| registered.methods.clone())
+        .collect()
+}
+
+/// Calls a method of a registered type on an instance created from the value
+/// it's called on, with the arguments and result converted like those of
+/// Python functions
+fn call_method(ftx: &FunctionContext) -> ResolveResult {
+    let this = match &ftx.this {
+        Some(this) => this.clone(),
+        None => return Err(ftx.error("must be called as a method")),
+    };
+    let registered = match &this {
+        Value::Map(map) => registered_type(map),
+        _ => None,
+    };
+    if !registered.is_some_and(|r| r.methods.contains(&ftx.name)) {
+        return Err(ftx.error(format!("{} has no method {}", type_name(&this), ftx.name)));
+    }
+    let args = ftx
+        .args
+        .iter()
+        .map(|arg| ftx.ptx.resolve(arg))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Python::with_gil(|py| {
+        let failed = |e: PyErr| ftx.error(e.to_string());
+        let output = Default::default();
+        let instance = crate::RustyCelType(this)
+            .try_into_py(py, &output)
+            .map_err(failed)?;
+        let args = args
+            .into_iter()
+            .map(|arg| crate::RustyCelType(arg).try_into_py(py, &output))
+            .collect::<PyResult<Vec<_>>>()
+            .map_err(failed)?;
+        let result = instance
+            .call_method1(py, ftx.name.as_str(), PyTuple::new_bound(py, args))
+            .map_err(failed)?;
+        let value = crate::Converter::default()
+            .convert(result.bind(py))
+            .map_err(|e| ftx.error(e.to_string()))?;
+        Ok(value)
+    })
+}
+
+/// Rewrites constructions like `my.Money{cents: 5}` into calls to [`NEW`],
+/// returning None if there are none.
+pub fn rewrite(expr: &Expression) -> Option<Expression> {
+    constructs(expr).then(|| rewrite_constructions(expr))
+}
+
+fn constructs(expr: &Expression) -> bool {
+    if let Expression::Member(_, member) = expr {
+        if let Member::Fields(_) = &**member {
+            return true;
+        }
+    }
+    let mut found = false;
+    map_children(expr, |child| {
+        found = found || constructs(child);
+        child.clone()
+    });
+    found
+}
+
+fn rewrite_constructions(expr: &Expression) -> Expression {
+    match expr {
+        Expression::Member(target, member) => match &**member {
+            Member::Fields(fields) => call(
+                NEW,
+                vec![
+                    rewrite_constructions(target),
+                    Expression::Map(
+                        fields
+                            .iter()
+                            .map(|(name, value)| {
+                                (
+                                    Expression::Atom(Atom::String(name.clone())),
+                                    rewrite_constructions(value),
+                                )
+                            })
+                            .collect(),
+                    ),
+                ],
+            ),
+            _ => map_children(expr, rewrite_constructions),
+        },
+        _ => map_children(expr, rewrite_constructions),
+    }
+}
+
+/// Implementation of [`NEW`], which constructs a value of a registered type
+/// from a map of its fields
+fn construct(ftx: &FunctionContext) -> ResolveResult {
+    let target = ftx.ptx.resolve(&ftx.args[0])?;
+    let not_registered = |got: String| ExecutionError::UnexpectedType {
+        got,
+        want: "a registered type".to_string(),
+    };
+    let Value::Function(name, None) = &target else {
+        return Err(not_registered(name_of(&target).to_string()));
+    };
+    let Some(registered) = registered().into_iter().find(|r| r.name == **name) else {
+        return Err(not_registered(name.to_string()));
+    };
+    let Value::Map(fields) = ftx.ptx.resolve(&ftx.args[1])? else {
+        return Err(ftx.error("expected the fields of the value"));
+    };
+    let mut value = (*fields.map).clone();
+    for key in value.keys() {
+        let known = matches!(key, Key::String(field) if registered.fields.contains(field));
+        if !known {
+            return Err(ExecutionError::NoSuchKey(Arc::new(format!(
+                "{} has no field {}",
+                name, key
+            ))));
+        }
+    }
+    value.insert(Key::String(Arc::new(TYPE_KEY.to_string())), marker(name));
+    Ok(Value::Map(value.into()))
+}
+
+/// Rewrites the indexes in `expr` that might select [`TYPE_KEY`] into calls to
+/// [`INDEX`], returning None if there are none or no types are registered.
+pub fn rewrite_indexes(expr: &Expression) -> Option<Expression> {
+    (!registered().is_empty() && indexes(expr)).then(|| rewrite_all_indexes(expr))
+}
+
+/// Whether `index` might evaluate to [`TYPE_KEY`]
+fn might_be_type_key(index: &Expression) -> bool {
+    match index {
+        Expression::Atom(Atom::String(key)) => key.as_str() == TYPE_KEY,
+        Expression::Atom(_) | Expression::List(_) | Expression::Map(_) => false,
+        _ => true,
+    }
+}
+
+fn indexes(expr: &Expression) -> bool {
+    if let Expression::Member(_, member) = expr {
+        if matches!(&**member, Member::Index(index) if might_be_type_key(index)) {
+            return true;
+        }
+    }
+    let mut found = false;
+    map_children(expr, |child| {
+        found = found || indexes(child);
+        child.clone()
+    });
+    found
+}
+
+fn rewrite_all_indexes(expr: &Expression) -> Expression {
+    // `has()` relies on the interpreter resolving its argument as a selection
+    if is_call_to(expr, "has") {
+        return expr.clone();
+    }
+    match expr {
+        Expression::Member(target, member) => match &**member {
+            Member::Index(index) if might_be_type_key(index) => call(
+                INDEX,
+                vec![rewrite_all_indexes(target), rewrite_all_indexes(index)],
+            ),
+            _ => map_children(expr, rewrite_all_indexes),
+        },
+        _ => map_children(expr, rewrite_all_indexes),
+    }
+}
+
+/// Implementation of [`INDEX`]
+fn index(ftx: &FunctionContext) -> ResolveResult {
+    let target = ftx.ptx.resolve(&ftx.args[0])?;
+    let index = ftx.ptx.resolve(&ftx.args[1])?;
+    plan::index_into(target, index)
+}
+
+/// Whether a map literal in `expr` has [`TYPE_KEY`] as a key, which only values
+/// of registered types hold
+pub fn reserved_key(expr: &Expression) -> bool {
+    if let Expression::Map(entries) = expr {
+        let reserved = |key: &Expression| matches!(key, Expression::Atom(Atom::String(key)) if key.as_str() == TYPE_KEY);
+        if entries.iter().any(|(key, _)| reserved(key)) {
+            return true;
+        }
+    }
+    let mut found = false;
+    map_children(expr, |child| {
+        found = found || reserved_key(child);
+        child.clone()
+    });
+    found
+}
+
+/// A CEL type, as returned by `type(x)`.
+///
+/// Types compare equal by name and can be passed back into an expression, e.g.
+/// `evaluate("type(x) == t", {"x": 1, "t": CelType("int")})`.
+#[pyclass(frozen, name = "CelType")]
+#[derive(Clone)]
+pub struct CelType {
+    #[pyo3(get)]
+    pub name: String,
+}
+
+#[pymethods]
+impl CelType {
+    #[new]
+    fn new(name: String) -> Self {
+        CelType { name }
+    }
+
+    fn __richcmp__(&self, other: &Bound<'_, PyAny>, op: CompareOp) -> PyObject {
+        let py = other.py();
+        match (other.downcast::<CelType>(), op) {
+            (Ok(other), CompareOp::Eq) => (self.name == other.get().name).into_py(py),
+            (Ok(other), CompareOp::Ne) => (self.name != other.get().name).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.name.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn __str__(&self) -> &str {
+        &self.name
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!("CelType({})", repr_of(py, &self.name))
+    }
+}
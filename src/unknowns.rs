@@ -0,0 +1,200 @@
+use crate::repr_of;
+use crate::transform::{call, map_children};
+use cel_interpreter::{ExecutionError, FunctionContext, ResolveResult, Value};
+use cel_parser::{Atom, Expression, Member};
+use pyo3::prelude::*;
+use std::sync::Arc;
+
+/// Internal function that references to unknown attributes are rewritten into.
+pub const UNKNOWN: &str = "@unknown";
+/// Internal replacements for `&&` and `||` that let a known result absorb
+/// unknowns and errors.
+pub const AND: &str = "@and";
+pub const OR: &str = "@or";
+
+/// Returned by `evaluate` when the result depends on attributes marked as unknown.
+#[pyclass(frozen, get_all)]
+#[derive(Debug, Clone)]
+pub struct Unknown {
+    /// The unknown attributes that prevented a result, in the order they were hit
+    pub attributes: Vec<String>,
+}
+
+#[pymethods]
+impl Unknown {
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!("Unknown(attributes={})", repr_of(py, &self.attributes))
+    }
+}
+
+/// Unknowns travel through the interpreter as a function error so that any
+/// operation on them propagates them, just like an error.
+fn unknown_error(attributes: Vec<String>) -> ExecutionError {
+    ExecutionError::FunctionError {
+        function: UNKNOWN.to_string(),
+        message: attributes.join("\n"),
+    }
+}
+
+/// Returns the unknown attributes carried by an error produced by [`UNKNOWN`].
+pub fn unknown_attributes(error: &ExecutionError) -> Option<Vec<String>> {
+    match error {
+        ExecutionError::FunctionError { function, message } if function == UNKNOWN => {
+            Some(message.split('\n').map(String::from).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Returns the attribute path an expression selects, e.g. `["request", "time"]` for
+/// both `request.time` and `request['time']`.
+fn attribute_path(expr: &Expression) -> Option<Vec<&str>> {
+    match expr {
+        Expression::Ident(name) => Some(vec![name.as_str()]),
+        Expression::Member(target, member) => {
+            let mut path = attribute_path(target)?;
+            match &**member {
+                Member::Attribute(name) => path.push(name.as_str()),
+                Member::Index(index) => match &**index {
+                    Expression::Atom(Atom::String(name)) => path.push(name.as_str()),
+                    _ => return None,
+                },
+                Member::Fields(_) => return None,
+            }
+            Some(path)
+        }
+        _ => None,
+    }
+}
+
+/// Rewrites references to the given attributes (dotted paths like `request.time`)
+/// into calls to [`UNKNOWN`], and the logical operators into [`AND`] and [`OR`].
+pub fn mark_unknowns(expr: &Expression, attributes: &[String]) -> Expression {
+    if let Some(path) = attribute_path(expr) {
+        let dotted = path.join(".");
+        if attributes.contains(&dotted) {
+            return Expression::FunctionCall(
+                Expression::Ident(Arc::new(UNKNOWN.to_string())).into(),
+                None,
+                vec![Expression::Atom(Atom::String(Arc::new(dotted)))],
+            );
+        }
+    }
+
+    let rewrite = |e: &Expression| mark_unknowns(e, attributes);
+    match expr {
+        Expression::And(left, right) => call(AND, vec![rewrite(left), rewrite(right)]),
+        Expression::Or(left, right) => call(OR, vec![rewrite(left), rewrite(right)]),
+        _ => map_children(expr, rewrite),
+    }
+}
+
+/// Rewrites the logical operators in `expr` into [`AND`] and [`OR`], so that an
+/// error in one operand is absorbed when the other decides the result, returning
+/// None if there are none.
+pub fn absorb_errors(expr: &Expression) -> Option<Expression> {
+    has_logical(expr).then(|| rewrite_logical(expr))
+}
+
+fn has_logical(expr: &Expression) -> bool {
+    if matches!(expr, Expression::And(..) | Expression::Or(..)) {
+        return true;
+    }
+    let mut found = false;
+    map_children(expr, |child| {
+        found = found || has_logical(child);
+        child.clone()
+    });
+    found
+}
+
+fn rewrite_logical(expr: &Expression) -> Expression {
+    match expr {
+        Expression::And(left, right) => {
+            call(AND, vec![rewrite_logical(left), rewrite_logical(right)])
+        }
+        Expression::Or(left, right) => {
+            call(OR, vec![rewrite_logical(left), rewrite_logical(right)])
+        }
+        _ => map_children(expr, rewrite_logical),
+    }
+}
+
+/// Implementation of [`UNKNOWN`].
+pub fn unknown(ftx: &FunctionContext) -> ResolveResult {
+    match ftx.args.first() {
+        Some(Expression::Atom(Atom::String(attribute))) => {
+            Err(unknown_error(vec![attribute.to_string()]))
+        }
+        _ => Err(ftx.error("expected an attribute name")),
+    }
+}
+
+/// Truthiness as used by the interpreter's logical operators.
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::List(v) => !v.is_empty(),
+        Value::Map(v) => !v.map.is_empty(),
+        Value::Int(v) => *v != 0,
+        Value::UInt(v) => *v != 0,
+        Value::Float(v) => *v != 0.0,
+        Value::String(v) => !v.is_empty(),
+        Value::Bytes(v) => !v.is_empty(),
+        Value::Bool(v) => *v,
+        Value::Null => false,
+        Value::Duration(v) => v.num_nanoseconds().map(|n| n != 0).unwrap_or(false),
+        Value::Timestamp(v) => v.timestamp_nanos_opt().unwrap_or_default() > 0,
+        Value::Function(_, _) => false,
+    }
+}
+
+/// Combines the failures of both operands of a logical operator. Unknowns take
+/// priority over errors, and the unknowns of both sides are merged.
+pub(crate) fn combine_failures(left: ResolveResult, right: ResolveResult) -> ResolveResult {
+    let left_unknowns = left.as_ref().err().and_then(unknown_attributes);
+    let right_unknowns = right.as_ref().err().and_then(unknown_attributes);
+    match (left_unknowns, right_unknowns) {
+        (Some(mut attributes), Some(more)) => {
+            for attribute in more {
+                if !attributes.contains(&attribute) {
+                    attributes.push(attribute);
+                }
+            }
+            Err(unknown_error(attributes))
+        }
+        (Some(attributes), None) | (None, Some(attributes)) => Err(unknown_error(attributes)),
+        (None, None) => left.and(right),
+    }
+}
+
+/// Implementation of [`AND`]: false if either side is false, even if the other
+/// side is unknown or an error.
+pub fn and(ftx: &FunctionContext) -> ResolveResult {
+    let left = ftx.ptx.resolve(&ftx.args[0]);
+    if matches!(left, Ok(ref v) if !is_truthy(v)) {
+        return Ok(Value::Bool(false));
+    }
+    let right = ftx.ptx.resolve(&ftx.args[1]);
+    if matches!(right, Ok(ref v) if !is_truthy(v)) {
+        return Ok(Value::Bool(false));
+    }
+    combine_failures(left, right).map(|_| Value::Bool(true))
+}
+
+/// Implementation of [`OR`]: the first truthy side, even if the other side is
+/// unknown or an error.
+pub fn or(ftx: &FunctionContext) -> ResolveResult {
+    let left = ftx.ptx.resolve(&ftx.args[0]);
+    if let Ok(ref v) = left {
+        if is_truthy(v) {
+            return left;
+        }
+    }
+    let right = ftx.ptx.resolve(&ftx.args[1]);
+    if let Ok(ref v) = right {
+        if is_truthy(v) {
+            return right;
+        }
+    }
+    combine_failures(left, right)
+}
@@ -0,0 +1,234 @@
+//! Writes parsed expressions back out as CEL source, for expressions that have
+//! been rewritten. The source is written in a canonical form: comments and the
+//! original spacing are lost, and parentheses are only kept where they're needed.
+use cel_parser::{ArithmeticOp, Atom, Expression, Member, RelationOp, UnaryOp};
+use std::fmt::Write;
+
+/// How tightly an expression binds, parenthesized where a tighter one is expected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Ternary,
+    Or,
+    And,
+    Relation,
+    Addition,
+    Multiplication,
+    Unary,
+    Member,
+}
+
+/// The source of `expr`
+pub fn unparse(expr: &Expression) -> String {
+    let mut out = String::new();
+    write(&mut out, expr, Precedence::Ternary);
+    out
+}
+
+fn precedence(expr: &Expression) -> Precedence {
+    match expr {
+        Expression::Ternary(..) => Precedence::Ternary,
+        Expression::Or(..) => Precedence::Or,
+        Expression::And(..) => Precedence::And,
+        Expression::Relation(..) => Precedence::Relation,
+        Expression::Arithmetic(_, ArithmeticOp::Add | ArithmeticOp::Subtract, _) => {
+            Precedence::Addition
+        }
+        Expression::Arithmetic(..) => Precedence::Multiplication,
+        Expression::Unary(..) => Precedence::Unary,
+        // A negative literal is written with a minus, like a negation
+        Expression::Atom(Atom::Int(i)) if *i < 0 => Precedence::Unary,
+        Expression::Atom(Atom::Float(f)) if f.is_sign_negative() => Precedence::Unary,
+        _ => Precedence::Member,
+    }
+}
+
+/// Writes `expr`, in parentheses if it binds less tightly than `at_least`
+fn write(out: &mut String, expr: &Expression, at_least: Precedence) {
+    let parenthesized = precedence(expr) < at_least;
+    if parenthesized {
+        out.push('(');
+    }
+    match expr {
+        Expression::Ternary(condition, left, right) => {
+            write(out, condition, Precedence::Or);
+            out.push_str(" ? ");
+            write(out, left, Precedence::Or);
+            out.push_str(" : ");
+            write(out, right, Precedence::Ternary);
+        }
+        Expression::Or(left, right) => binary(out, left, "||", right, Precedence::Or),
+        Expression::And(left, right) => binary(out, left, "&&", right, Precedence::And),
+        Expression::Relation(left, op, right) => {
+            let symbol = match op {
+                RelationOp::LessThan => "<",
+                RelationOp::LessThanEq => "<=",
+                RelationOp::GreaterThan => ">",
+                RelationOp::GreaterThanEq => ">=",
+                RelationOp::Equals => "==",
+                RelationOp::NotEquals => "!=",
+                RelationOp::In => "in",
+            };
+            binary(out, left, symbol, right, Precedence::Relation)
+        }
+        Expression::Arithmetic(left, op, right) => {
+            let symbol = match op {
+                ArithmeticOp::Add => "+",
+                ArithmeticOp::Subtract => "-",
+                ArithmeticOp::Multiply => "*",
+                ArithmeticOp::Divide => "/",
+                ArithmeticOp::Modulus => "%",
+            };
+            binary(out, left, symbol, right, precedence(expr))
+        }
+        Expression::Unary(op, operand) => {
+            out.push_str(match op {
+                UnaryOp::Not => "!",
+                UnaryOp::DoubleNot => "!!",
+                UnaryOp::Minus => "-",
+                UnaryOp::DoubleMinus => "--",
+            });
+            write(out, operand, Precedence::Member);
+        }
+        Expression::Member(target, member) => {
+            write(out, target, Precedence::Member);
+            match &**member {
+                Member::Attribute(name) => {
+                    out.push('.');
+                    out.push_str(name);
+                }
+                Member::Index(index) => {
+                    out.push('[');
+                    write(out, index, Precedence::Ternary);
+                    out.push(']');
+                }
+                Member::Fields(fields) => {
+                    out.push('{');
+                    for (i, (name, value)) in fields.iter().enumerate() {
+                        if i > 0 {
+                            out.push_str(", ");
+                        }
+                        out.push_str(name);
+                        out.push_str(": ");
+                        write(out, value, Precedence::Ternary);
+                    }
+                    out.push('}');
+                }
+            }
+        }
+        Expression::FunctionCall(function, target, args) => {
+            if let Some(target) = target {
+                write(out, target, Precedence::Member);
+                out.push('.');
+            }
+            write(out, function, Precedence::Member);
+            out.push('(');
+            items(out, args);
+            out.push(')');
+        }
+        Expression::List(list) => {
+            out.push('[');
+            items(out, list);
+            out.push(']');
+        }
+        Expression::Map(entries) => {
+            out.push('{');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write(out, key, Precedence::Ternary);
+                out.push_str(": ");
+                write(out, value, Precedence::Ternary);
+            }
+            out.push('}');
+        }
+        Expression::Atom(atom) => literal(out, atom),
+        Expression::Ident(name) => out.push_str(name),
+    }
+    if parenthesized {
+        out.push(')');
+    }
+}
+
+/// Writes a left associative binary operation
+fn binary(
+    out: &mut String,
+    left: &Expression,
+    symbol: &str,
+    right: &Expression,
+    precedence: Precedence,
+) {
+    write(out, left, precedence);
+    let _ = write!(out, " {} ", symbol);
+    // The right operand is parenthesized at the same precedence, e.g. `a - (b - c)`
+    let tighter = match precedence {
+        Precedence::Or => Precedence::And,
+        Precedence::And => Precedence::Relation,
+        Precedence::Relation => Precedence::Addition,
+        Precedence::Addition => Precedence::Multiplication,
+        _ => Precedence::Unary,
+    };
+    write(out, right, tighter);
+}
+
+fn items(out: &mut String, items: &[Expression]) {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write(out, item, Precedence::Ternary);
+    }
+}
+
+fn literal(out: &mut String, atom: &Atom) {
+    match atom {
+        Atom::Int(i) => {
+            let _ = write!(out, "{}", i);
+        }
+        Atom::UInt(u) => {
+            let _ = write!(out, "{}u", u);
+        }
+        Atom::Float(f) => {
+            let formatted = format!("{:?}", f);
+            out.push_str(&formatted);
+            if !formatted.contains(['.', 'e']) {
+                out.push_str(".0");
+            }
+        }
+        Atom::String(s) => {
+            out.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c if c.is_control() => {
+                        let _ = write!(out, "\\u{:04x}", c as u32);
+                    }
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+        Atom::Bytes(bytes) => {
+            out.push_str("b\"");
+            for &byte in bytes.iter() {
+                match byte {
+                    // Quotes and backslashes are written in hex too, as the parser
+                    // doesn't unescape them in bytes
+                    b' '..=b'~' if byte != b'"' && byte != b'\\' => out.push(byte as char),
+                    _ => {
+                        let _ = write!(out, "\\x{:02x}", byte);
+                    }
+                }
+            }
+            out.push('"');
+        }
+        Atom::Bool(b) => {
+            let _ = write!(out, "{}", b);
+        }
+        Atom::Null => out.push_str("null"),
+    }
+}
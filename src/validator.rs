@@ -0,0 +1,162 @@
+//! `cel.Validator`, which checks objects against rules in the style of
+//! Kubernetes validation rules.
+use crate::context::Context;
+use crate::options::Options;
+use crate::program::Program;
+use crate::{repr_of, types, Job, Outcome};
+use cel_interpreter::Value;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+
+/// A rule that an object failed
+#[pyclass(frozen, module = "cel")]
+pub struct Violation {
+    /// The `id` of the rule, which defaults to its source
+    #[pyo3(get)]
+    id: String,
+    #[pyo3(get)]
+    message: String,
+    /// The `path` of the rule, e.g. "spec.replicas", if it has one
+    #[pyo3(get)]
+    path: Option<String>,
+    /// "invalid" if the rule was false, or "error" if it couldn't be evaluated
+    #[pyo3(get)]
+    reason: &'static str,
+}
+
+#[pymethods]
+impl Violation {
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!(
+            "Violation(id={}, message={}, path={}, reason={})",
+            repr_of(py, &self.id),
+            repr_of(py, &self.message),
+            repr_of(py, &self.path),
+            repr_of(py, self.reason)
+        )
+    }
+}
+
+struct Rule {
+    id: String,
+    program: Program,
+    /// A `str.format` template, rendered with the fields of the object
+    message: Option<String>,
+    path: Option<String>,
+}
+
+/// Checks objects against boolean CEL rules, e.g.
+/// `Validator([{"rule": "self.replicas <= 10", "message": "too many replicas"}])`.
+///
+/// Each rule is a dict with the source of the `rule`, which refers to the
+/// object as `self`, and optionally an `id`, a `message` and the `path` of the
+/// field it checks.
+#[pyclass(frozen, module = "cel")]
+pub struct Validator {
+    rules: Vec<Rule>,
+    mode: Options,
+}
+
+#[pymethods]
+impl Validator {
+    #[new]
+    #[pyo3(signature = (rules, mode=None))]
+    fn new(rules: &Bound<'_, PyAny>, mode: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let rules = rules
+            .iter()?
+            .map(|rule| {
+                let rule = rule?;
+                let rule = rule
+                    .downcast::<PyDict>()
+                    .map_err(|_| PyValueError::new_err("each rule must be a dict"))?;
+                let get = |key: &str| -> PyResult<Option<String>> {
+                    match rule.get_item(key)? {
+                        Some(value) if !value.is_none() => Ok(Some(value.extract()?)),
+                        _ => Ok(None),
+                    }
+                };
+                let source = get("rule")?
+                    .ok_or_else(|| PyValueError::new_err("each rule must have a 'rule'"))?;
+                Ok(Rule {
+                    id: get("id")?.unwrap_or_else(|| source.clone()),
+                    program: Program::new(source, false, None, None)?,
+                    message: get("message")?,
+                    path: get("path")?,
+                })
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(Validator {
+            rules,
+            mode: match mode {
+                Some(mode) => Options::from_mode(mode)?,
+                None => Options::default(),
+            },
+        })
+    }
+
+    /// The violations of the rules `obj` fails, in the order of the rules
+    ///
+    /// A rule that can't be evaluated, e.g. as it selects a missing field, or
+    /// that doesn't evaluate to a bool is a violation with the reason "error"
+    /// and the error as its message.
+    fn validate(&self, obj: &Bound<'_, PyAny>) -> PyResult<Vec<Violation>> {
+        let mut context = Context::new(
+            None, None, false, None, None, false, "error", "error", false, false, None, None, None,
+        )?;
+        context.add_variable("self".to_string(), obj)?;
+
+        let mut violations = Vec::new();
+        for rule in &self.rules {
+            let outcome = rule
+                .program
+                .run(Job::for_context(&context, None, None, self.mode));
+            let (reason, message) = match outcome {
+                Outcome::Value(Value::Bool(true)) => continue,
+                Outcome::Value(Value::Bool(false)) => ("invalid", self.message(rule, obj)?),
+                Outcome::Value(other) => (
+                    "error",
+                    format!(
+                        "expected the rule to evaluate to a bool, got {}",
+                        types::name_of(&other)
+                    ),
+                ),
+                Outcome::Unknown(attributes) => (
+                    "error",
+                    format!("depends on unknown attributes {}", attributes.join(", ")),
+                ),
+                Outcome::Error(error) => ("error", error.message),
+            };
+            violations.push(Violation {
+                id: rule.id.clone(),
+                message,
+                path: rule.path.clone(),
+                reason,
+            });
+        }
+        Ok(violations)
+    }
+
+    /// Whether `obj` passes every rule
+    fn is_valid(&self, obj: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(self.validate(obj)?.is_empty())
+    }
+}
+
+impl Validator {
+    /// The message of a rule `obj` failed, formatted with `self` and, for a dict,
+    /// its items, or "failed rule: <rule>" for a rule without one
+    fn message(&self, rule: &Rule, obj: &Bound<'_, PyAny>) -> PyResult<String> {
+        let Some(message) = &rule.message else {
+            return Ok(format!("failed rule: {}", rule.program.source()));
+        };
+        let fields = match obj.downcast::<PyDict>() {
+            Ok(dict) => dict.copy()?,
+            Err(_) => PyDict::new_bound(obj.py()),
+        };
+        fields.set_item("self", obj)?;
+        PyString::new_bound(obj.py(), message)
+            .call_method1("format_map", (fields,))?
+            .extract()
+    }
+}
@@ -0,0 +1,153 @@
+use crate::output::OutputTypes;
+use crate::types;
+use crate::unknowns::is_truthy;
+use crate::{Converter, RustyCelType};
+use cel_interpreter::objects::Key;
+use cel_interpreter::Value;
+use pyo3::exceptions::{PyIndexError, PyKeyError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyIterator, PyList};
+use std::convert::TryInto;
+
+/// A CEL value that hasn't been converted to Python.
+///
+/// Returned by `evaluate(..., output="cel")` so that callers that only need part
+/// of a large result, or its truthiness, don't pay to convert all of it. Indexing
+/// and iterating return further handles; `to_python()` converts the whole value.
+#[pyclass(frozen, name = "Value")]
+pub struct OpaqueValue {
+    value: Value,
+    /// Used when the value is converted
+    output: OutputTypes,
+}
+
+impl OpaqueValue {
+    pub fn new(value: Value, output: OutputTypes) -> Self {
+        OpaqueValue { value, output }
+    }
+}
+
+#[pymethods]
+impl OpaqueValue {
+    /// Convert the value, and everything it contains, to Python
+    fn to_python(&self, py: Python<'_>) -> PyResult<PyObject> {
+        RustyCelType(self.value.clone()).try_into_py(py, &self.output)
+    }
+
+    /// The CEL type of the value, e.g. "map" or "int"
+    #[getter]
+    fn r#type(&self) -> &'static str {
+        types::name_of(&self.value)
+    }
+
+    fn __getitem__(&self, key: &Bound<'_, PyAny>) -> PyResult<OpaqueValue> {
+        let item = match &self.value {
+            Value::Map(map) => {
+                let key = map_key(key)?;
+                map.get(&key)
+                    .cloned()
+                    .ok_or_else(|| PyKeyError::new_err(key.to_string()))?
+            }
+            Value::List(items) => {
+                let index = key.extract::<isize>()?;
+                let position = if index < 0 {
+                    index + items.len() as isize
+                } else {
+                    index
+                };
+                usize::try_from(position)
+                    .ok()
+                    .and_then(|position| items.get(position))
+                    .cloned()
+                    .ok_or_else(|| PyIndexError::new_err("list index out of range"))?
+            }
+            other => {
+                return Err(PyTypeError::new_err(format!(
+                    "{} value is not subscriptable",
+                    other.type_of()
+                )))
+            }
+        };
+        Ok(OpaqueValue::new(item, self.output))
+    }
+
+    fn __contains__(&self, item: &Bound<'_, PyAny>) -> PyResult<bool> {
+        match &self.value {
+            Value::Map(map) => Ok(map_key(item)
+                .map(|key| map.map.contains_key(&key))
+                .unwrap_or(false)),
+            Value::List(items) => {
+                let item = to_value(item)?;
+                Ok(items.contains(&item))
+            }
+            other => Err(PyTypeError::new_err(format!(
+                "{} value is not a container",
+                other.type_of()
+            ))),
+        }
+    }
+
+    fn __len__(&self) -> PyResult<usize> {
+        match &self.value {
+            Value::List(items) => Ok(items.len()),
+            Value::Map(map) => Ok(map.map.len()),
+            Value::String(s) => Ok(s.chars().count()),
+            Value::Bytes(b) => Ok(b.len()),
+            other => Err(PyTypeError::new_err(format!(
+                "{} value has no length",
+                other.type_of()
+            ))),
+        }
+    }
+
+    /// Iterates over the items of a list, or the keys of a map
+    fn __iter__(&self, py: Python<'_>) -> PyResult<Py<PyIterator>> {
+        let items: Vec<PyObject> = match &self.value {
+            Value::List(items) => items
+                .iter()
+                .map(|item| OpaqueValue::new(item.clone(), self.output).into_py(py))
+                .collect(),
+            Value::Map(map) => self
+                .output
+                .map_entries(map)
+                .into_iter()
+                .map(|(key, _)| RustyCelType(key.into()).try_into_py(py, &self.output))
+                .collect::<PyResult<_>>()?,
+            other => {
+                return Err(PyTypeError::new_err(format!(
+                    "{} value is not iterable",
+                    other.type_of()
+                )))
+            }
+        };
+        Ok(PyList::new_bound(py, items).as_any().iter()?.unbind())
+    }
+
+    fn __bool__(&self) -> bool {
+        is_truthy(&self.value)
+    }
+
+    /// Compares equal to another handle, or to a Python object, holding an equal value
+    fn __eq__(&self, other: &Bound<'_, PyAny>) -> bool {
+        match other.downcast::<OpaqueValue>() {
+            Ok(other) => self.value == other.get().value,
+            Err(_) => to_value(other).is_ok_and(|other| self.value == other),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Value(type={})", types::name_of(&self.value))
+    }
+}
+
+fn to_value(object: &Bound<'_, PyAny>) -> PyResult<Value> {
+    Converter::default()
+        .convert(object)
+        .map_err(|e| PyTypeError::new_err(e.to_string()))
+}
+
+fn map_key(key: &Bound<'_, PyAny>) -> PyResult<Key> {
+    to_value(key)?.try_into().map_err(|key: Value| {
+        PyKeyError::new_err(format!("unsupported key type {}", key.type_of()))
+    })
+}
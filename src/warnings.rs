@@ -0,0 +1,165 @@
+//! Warnings for expressions that rely on the conveniences of Python mode, which
+//! `mode="strict"` rejects, so that an expression that would fail or mean
+//! something different in an implementation following the CEL specification
+//! doesn't go unnoticed.
+//!
+//! Like strict mode, only what is known from the expression itself is found,
+//! e.g. `1 + 2.0` but not `x + 2.0` with a variable holding an int. Python's
+//! warning filters turn the warnings into errors, e.g.
+//! `warnings.simplefilter("error", cel.CELWarning)`.
+use crate::options::{self, Options};
+use cel_parser::Expression;
+use pyo3::create_exception;
+use pyo3::exceptions::PyUserWarning;
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+
+create_exception!(
+    cel,
+    CELWarning,
+    PyUserWarning,
+    "Base class of the warnings about expressions that rely on a convenience of Python mode."
+);
+
+create_exception!(
+    cel,
+    CELPromotionWarning,
+    CELWarning,
+    "Warned when an expression relies on numeric promotion, e.g. `1 + 2.0`."
+);
+
+create_exception!(
+    cel,
+    CELTruthinessWarning,
+    CELWarning,
+    "Warned when an expression relies on the truthiness of a non-bool operand of `&&`, `||`, `!` or `?:`."
+);
+
+create_exception!(
+    cel,
+    CELDuplicateKeyWarning,
+    CELWarning,
+    "Warned when a map literal repeats a key, e.g. `{'a': 1, 'a': 2}`."
+);
+
+/// The conveniences an expression can be seen to rely on before evaluating it
+#[derive(Debug, Clone, Copy)]
+enum Convenience {
+    NumericPromotion,
+    Truthiness,
+    DuplicateMapKeys,
+}
+
+impl Convenience {
+    const ALL: [Convenience; 3] = [
+        Convenience::NumericPromotion,
+        Convenience::Truthiness,
+        Convenience::DuplicateMapKeys,
+    ];
+
+    fn enabled(self, options: &Options) -> bool {
+        match self {
+            Convenience::NumericPromotion => options.numeric_promotion,
+            Convenience::Truthiness => options.truthiness,
+            Convenience::DuplicateMapKeys => options.duplicate_map_keys,
+        }
+    }
+
+    /// Python mode with only this convenience disabled
+    fn disabled(self) -> Options {
+        let mut options = Options::PYTHON;
+        match self {
+            Convenience::NumericPromotion => options.numeric_promotion = false,
+            Convenience::Truthiness => options.truthiness = false,
+            Convenience::DuplicateMapKeys => options.duplicate_map_keys = false,
+        }
+        options
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Convenience::NumericPromotion => "numeric promotion",
+            Convenience::Truthiness => "truthiness",
+            Convenience::DuplicateMapKeys => "duplicate map keys",
+        }
+    }
+
+    fn category(self, py: Python<'_>) -> Bound<'_, PyType> {
+        match self {
+            Convenience::NumericPromotion => py.get_type_bound::<CELPromotionWarning>(),
+            Convenience::Truthiness => py.get_type_bound::<CELTruthinessWarning>(),
+            Convenience::DuplicateMapKeys => py.get_type_bound::<CELDuplicateKeyWarning>(),
+        }
+    }
+}
+
+/// A convenience an expression relies on, with the reason strict mode would
+/// reject it
+#[derive(Debug, Clone)]
+pub struct Reliance {
+    convenience: Convenience,
+    message: String,
+}
+
+/// The conveniences `options` enables that `expr` relies on
+pub fn reliances(expr: &Expression, options: &Options) -> Vec<Reliance> {
+    Convenience::ALL
+        .into_iter()
+        .filter(|convenience| convenience.enabled(options))
+        .filter_map(|convenience| {
+            let message = options::validate(expr, &convenience.disabled()).err()?;
+            Some(Reliance {
+                convenience,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Warns about each of the `reliances` of the expression `source` on a
+/// convenience `options` enables. Fails if a warning filter turned the warning
+/// into an error.
+pub fn warn(
+    py: Python<'_>,
+    source: &str,
+    reliances: &[Reliance],
+    options: &Options,
+) -> PyResult<()> {
+    for reliance in reliances {
+        let convenience = reliance.convenience;
+        if !convenience.enabled(options) {
+            continue;
+        }
+        PyErr::warn_bound(
+            py,
+            &convenience.category(py),
+            &format!(
+                "Expression '{}' relies on {}, which mode=\"strict\" rejects: {}",
+                source,
+                convenience.name(),
+                reliance.message
+            ),
+            1,
+        )?;
+    }
+    Ok(())
+}
+
+/// Adds the warning categories to the module
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("CELWarning", py.get_type_bound::<CELWarning>())?;
+    m.add(
+        "CELPromotionWarning",
+        py.get_type_bound::<CELPromotionWarning>(),
+    )?;
+    m.add(
+        "CELTruthinessWarning",
+        py.get_type_bound::<CELTruthinessWarning>(),
+    )?;
+    m.add(
+        "CELDuplicateKeyWarning",
+        py.get_type_bound::<CELDuplicateKeyWarning>(),
+    )?;
+    Ok(())
+}
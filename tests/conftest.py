@@ -1,5 +1,7 @@
 import pytest
 
+import cel
+
 expressions = [
     "1 + 2",
     "1 > 2",
@@ -38,4 +40,24 @@ expression_context_pairs = [
 # Valid expressions with context fixture
 @pytest.fixture(params=expression_context_pairs)
 def expression_context_result(request):
-    return request.param
\ No newline at end of file
+    return request.param
+
+
+@pytest.fixture
+def evaluate_both_ways():
+    """Evaluates with the interpreter and with a compiled plan, which must agree."""
+    def evaluate(expression, context=None, **kwargs):
+        result = cel.evaluate(expression, context, **kwargs)
+        assert cel.Program(expression, optimize=True).evaluate(context, **kwargs) == result
+        return result
+    return evaluate
+
+
+@pytest.fixture
+def evaluate_all_ways(evaluate_both_ways):
+    """Evaluates as `evaluate_both_ways` does and in strict mode, which must agree too."""
+    def evaluate(expression, context=None):
+        result = evaluate_both_ways(expression, context)
+        assert cel.evaluate(expression, context, mode="strict") == result
+        return result
+    return evaluate
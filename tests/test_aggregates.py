@@ -0,0 +1,87 @@
+from datetime import timedelta
+
+import pytest
+
+import cel
+
+CONTEXT = {
+    "ints": [3, 1, 2],
+    "doubles": [1.5, 2.5],
+    "mixed": [1, 2.5],
+    "latency": {"nz": 80, "au": 120, "us": 190},
+    "empty": [],
+}
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("sum(ints)", 6),
+    ("ints.sum()", 6),
+    ("sum(doubles)", 4.0),
+    ("sum(mixed)", 3.5),
+    ("sum(latency)", 390),
+    ("sum(empty)", 0),
+    ("sum([1u, 2u])", 3),
+    ("avg(ints)", 2.0),
+    ("latency.avg()", 130.0),
+    ("min(ints)", 1),
+    ("max(ints)", 3),
+    ("min(latency)", 80),
+    ("latency.max()", 190),
+    ("max(mixed)", 2.5),
+    ("max(1, 2.5, 2u)", 2.5),
+    ("min(7)", 7),
+    ("min(empty)", None),
+    ("max(['b', 'c', 'a'])", "c"),
+    ("ints.count(x, x >= 2)", 2),
+    ("latency.count(region, latency[region] > 100)", 2),
+])
+def test_aggregates(expression, expected, evaluate_both_ways):
+    result = evaluate_both_ways(expression, CONTEXT)
+    assert result == expected
+    assert type(result) is type(expected)
+
+
+def test_sum_of_durations(evaluate_both_ways):
+    durations = {"durations": [timedelta(seconds=1), timedelta(minutes=1)]}
+    assert evaluate_both_ways("sum(durations)", durations) == timedelta(seconds=61)
+
+
+def test_aggregates_of_comprehensions(evaluate_both_ways):
+    orders = [{"total": 10.0, "refunded": False}, {"total": 25.5, "refunded": True}]
+    expression = "orders.map(o, o.total).sum() > 30.0 && orders.count(o, o.refunded) == 1"
+    assert evaluate_both_ways(expression, {"orders": orders}) is True
+
+
+@pytest.mark.parametrize("expression, message", [
+    ("sum(['a'])", "can't sum string"),
+    ("avg([duration('1s')])", "can't average duration"),
+    ("avg(empty)", "can't average no values"),
+    ("sum(1)", "expected a list or map, got int"),
+    ("sum([9223372036854775807, 1])", "integer overflow"),
+    ("max([1, 'a'])", "can not be compared"),
+    ("sum(ints, ints)", "expected 1, got 2"),
+])
+def test_errors(expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression, CONTEXT)
+
+
+def test_mixed_types_need_numeric_promotion():
+    with pytest.raises(ValueError, match="can't sum int and double without numeric promotion"):
+        cel.evaluate("sum(mixed)", CONTEXT, mode="strict")
+    with pytest.raises(ValueError, match="can't average int and double without numeric promotion"):
+        cel.evaluate("avg(mixed)", CONTEXT, mode="strict")
+    assert cel.evaluate("sum(ints)", CONTEXT, mode="strict") == 6
+    with pytest.raises(ValueError, match="can't sum int and duration"):
+        cel.evaluate("sum([1, duration('1s')])")
+
+
+def test_count_in_parallel():
+    program = cel.Program("items.count(x, x % 2 == 0)", optimize=True, parallel_threshold=10)
+    assert program.evaluate({"items": list(range(100))}) == 50
+
+
+def test_aggregates_in_the_sandbox():
+    assert cel.sandbox.evaluate("sum(ints) + ints.count(x, x > 1)", CONTEXT) == 8
+    with pytest.raises(cel.sandbox.LimitExceeded):
+        cel.sandbox.evaluate("ints.count(x, true)", {"ints": list(range(100))}, max_cost=10)
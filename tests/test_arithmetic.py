@@ -0,0 +1,179 @@
+import pytest
+
+import cel
+
+INT_MAX = 2**63 - 1
+INT_MIN = -(2**63)
+UINT_MAX = 2**64 - 1
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("18446744073709551615u", UINT_MAX),
+    ("0xFFFFFFFFFFFFFFFFu", UINT_MAX),
+    ("9223372036854775807", INT_MAX),
+    ("-9223372036854775808", INT_MIN),
+    ("18446744073709551614u + 1u", UINT_MAX),
+    ("18446744073709551615u - 18446744073709551615u", 0),
+    ("4294967296u * 4294967295u", 2**64 - 2**32),
+    ("18446744073709551615u / 2u", UINT_MAX // 2),
+    ("18446744073709551615u % 10u", UINT_MAX % 10),
+    ("9223372036854775806 + 1", INT_MAX),
+    ("-9223372036854775807 - 1", INT_MIN),
+    ("-9223372036854775808 / 1", INT_MIN),
+    ("-(-9223372036854775807)", INT_MAX),
+    ("-7 / 2", -3),
+    ("-7 % 3", -1),
+])
+def test_boundary_values(expression, expected, evaluate_all_ways):
+    assert evaluate_all_ways(expression) == expected
+
+
+@pytest.mark.parametrize("expression, message", [
+    ("0u - 1u", "unsigned integer overflow"),
+    ("18446744073709551615u + 1u", "unsigned integer overflow"),
+    ("4294967296u * 4294967296u", "unsigned integer overflow"),
+    ("9223372036854775807 + 1", "integer overflow"),
+    ("-9223372036854775808 - 1", "integer overflow"),
+    ("9223372036854775807 * 2", "integer overflow"),
+    ("-9223372036854775808 / -1", "integer overflow"),
+    ("-9223372036854775808 % -1", "integer overflow"),
+    ("-(-9223372036854775808)", "integer overflow"),
+    ("1 / 0", "division by zero"),
+    ("1u / 0u", "division by zero"),
+    ("1 % 0", "modulus by zero"),
+    ("1u % 0u", "modulus by zero"),
+])
+def test_overflow_and_division_by_zero_are_errors(expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression)
+    with pytest.raises(ValueError, match=message):
+        cel.Program(expression, optimize=True).evaluate()
+
+
+@pytest.mark.parametrize("expression", [
+    "(9223372036854775807 || 1) + 1",
+    "(0 || 9223372036854775807) * 2",
+    "(true ? 9223372036854775807 : 1) + 1",
+    "-(false || -9223372036854775808)",
+])
+def test_overflow_of_the_operands_of_logical_operators(expression):
+    with pytest.raises(ValueError, match="integer overflow"):
+        cel.evaluate(expression)
+    with pytest.raises(ValueError, match="integer overflow"):
+        cel.Program(expression, optimize=True).evaluate()
+
+
+def test_overflow_of_context_values():
+    context = {"a": INT_MAX, "b": 1}
+    with pytest.raises(ValueError, match="integer overflow"):
+        cel.evaluate("a + b", context)
+    with pytest.raises(ValueError, match="unsigned integer overflow"):
+        cel.evaluate("uint(b) - uint(b) - 1u", context)
+    with pytest.raises(ValueError, match="integer overflow"):
+        cel.evaluate("[b, 9223372036854775807].map(x, x + b)", context)
+
+
+def test_errors_are_absorbed_by_logical_operators():
+    assert cel.evaluate("true || 0u - 1u > 0u") is True
+    assert cel.evaluate("false && 1 / 0 == 0") is False
+
+
+def test_overflow_with_on_error_return():
+    error = cel.evaluate("0u - 1u", on_error="return")
+    assert isinstance(error, cel.EvalError)
+    assert error.kind == "execution"
+
+
+@pytest.mark.parametrize("literal", [
+    "18446744073709551616u",
+    "0x10000000000000000u",
+    "9223372036854775808",
+    "-9223372036854775809",
+])
+def test_out_of_range_literals_fail_to_compile(literal):
+    with pytest.raises(ValueError, match="invalid u?int literal"):
+        cel.evaluate(literal)
+    with pytest.raises(ValueError, match="invalid u?int literal"):
+        cel.Program(f"1 + {literal}")
+    error = cel.evaluate(f"1 + {literal}", on_error="return")
+    assert error.kind == "compile"
+    assert error.position == 4
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("1u == 1", True),
+    ("1 == 1u", True),
+    ("1u != 2", True),
+    ("-1 == 18446744073709551615u", False),
+    ("-1 < 0u", True),
+    ("0u > -1", True),
+    ("18446744073709551615u > 9223372036854775807", True),
+    ("9223372036854775807 < 18446744073709551615u", True),
+    ("1u <= 1", True),
+    ("2 >= 3u", False),
+    ("1.0 == 1u", True),
+    ("1u < 1.5", True),
+    ("-9223372036854775808 < 0u", True),
+    ("1u in [1, 2]", True),
+    ("2 in [1u, 2u]", True),
+])
+def test_cross_type_numeric_comparisons(expression, expected):
+    assert cel.evaluate(expression) is expected
+    assert cel.Program(expression, optimize=True).evaluate() is expected
+
+
+@pytest.mark.parametrize("expression", ["1u + 1", "1 - 1u", "2u * 2"])
+def test_mixed_arithmetic_is_unsupported(expression):
+    with pytest.raises(ValueError, match="Unsupported binary operator"):
+        cel.evaluate(expression)
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("1 < 2u", True),
+    ("1 < 1.5", True),
+    ("2u > 1.5", True),
+    ("1.5 >= 1", True),
+    ("-1 < 0u", True),
+    ("1 == 1u", True),
+    ("1u == 1.0", True),
+    ("2 != 2.5", True),
+])
+def test_strict_mode_compares_numbers_of_different_types(expression, expected):
+    assert cel.evaluate(expression, mode="strict") is expected
+
+
+def test_strict_mode_compares_variables_of_different_types():
+    context = {"count": 1, "ratio": 1.5}
+    assert cel.evaluate("count < ratio && ratio > count", context, mode="strict") is True
+    assert cel.evaluate("count < 2u && count == 1.0", context, mode="strict") is True
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("9223372036854775807 < 9223372036854775808.0", True),
+    ("9223372036854775807 == 9223372036854775807.0", False),
+    ("-9223372036854775808 == -9223372036854775808.0", True),
+    ("9007199254740993 > 9007199254740992.0", True),
+    ("9007199254740993 == 9007199254740992.0", False),
+    ("18446744073709551615u < 18446744073709551616.0", True),
+    ("18446744073709551615u != 18446744073709551615.0", True),
+    ("-3 == -3.0", True),
+    ("-3 < -2.5", True),
+    ("-3 > -3.5", True),
+    ("0u > -0.5", True),
+    ("0u == -0.0", True),
+    ("1 == double('NaN')", False),
+    ("1u != double('NaN')", True),
+])
+def test_ints_and_doubles_compare_exactly(expression, expected, evaluate_all_ways):
+    assert evaluate_all_ways(expression) is expected
+
+
+def test_variables_compare_exactly(evaluate_all_ways):
+    context = {"big": 2**53 + 1, "rounded": float(2**53)}
+    for expression, expected in [
+        ("big == rounded", False),
+        ("big > rounded", True),
+        ("[big].exists(b, b <= rounded)", False),
+        ("big - 1 == rounded", True),
+    ]:
+        assert evaluate_all_ways(expression, context) is expected
@@ -108,7 +108,6 @@ def test_bytes_equality_via_context():
     assert result
 
 
-@pytest.mark.xfail
 def test_bytes_concatenation_context():
     part1 = b'hello'
     part2 = b'world'
@@ -116,9 +115,67 @@ def test_bytes_concatenation_context():
     assert result == b'hello world'
 
 
+@pytest.mark.parametrize("optimize", [False, True])
+@pytest.mark.parametrize("expression, expected", [
+    ("b'ab' + b'cd'", b'abcd'),
+    ("b'abc' < b'abd'", True),
+    ("b'ab' <= b'a'", False),
+    ("b'b' > b'abc'", True),
+    ("b'' >= b''", True),
+    ("b'abc'[0]", 97),
+    ("data[2]", 99),
+    ("data + b'd' == b'abcd'", True),
+    ("data > b'ab' && data < b'b'", True),
+])
+def test_bytes_operators(expression, expected, optimize):
+    program = cel.Program(expression, optimize=optimize)
+    assert program.evaluate({'data': b'abc'}) == expected
+
+
+@pytest.mark.parametrize("optimize", [False, True])
+@pytest.mark.parametrize("expression, index", [("data[3]", 3), ("data[-1]", -1), ("b''[0]", 0)])
+def test_bytes_indexes_past_the_end_are_errors(expression, index, optimize):
+    program = cel.Program(expression, optimize=optimize)
+    with pytest.raises(ValueError, match=f"index {index} is out of range for"):
+        program.evaluate({'data': b'abc'})
+
+
+def test_bytes_operators_in_strict_mode():
+    assert cel.evaluate("x + b'!'", {'x': b'hi'}, mode="strict") == b'hi!'
+    assert cel.evaluate("x < b'z'", {'x': b'hi'}, mode="strict") is True
+
+
+def test_bytes_cannot_be_added_to_strings():
+    with pytest.raises(ValueError, match="Unsupported binary operator"):
+        cel.evaluate("x + 'a'", {'x': b'a'})
+
+
+def test_string_of_invalid_utf8_bytes():
+    with pytest.raises(cel.ConversionError, match="invalid UTF-8"):
+        cel.evaluate("string(b'\\xff')")
+
+
+def test_bytes_dict_keys_are_rejected_with_a_targeted_error():
+    with pytest.raises(ValueError, match="bytes key b'k' cannot be used"):
+        cel.evaluate("d", {'d': {b'k': 1}})
+
+
 def test_nested_context_expression():
     result = cel.evaluate('resource.name.startsWith("/groups/" + claim.group)', {
         "resource": {"name": "/groups/hardbyte"},
         "claim": {"group": "hardbyte"}
     })
     assert result == True
+
+
+def test_evaluate_predicate():
+    assert cel.evaluate_predicate("age > 21", {'age': 32}) is True
+    assert cel.evaluate_predicate("age > 21", {'age': 18}) is False
+
+
+def test_evaluate_predicate_rejects_non_bool():
+    with pytest.raises(TypeError, match="got int"):
+        cel.evaluate_predicate("size(items)", {'items': [1, 2]})
+
+    with pytest.raises(TypeError, match="got string"):
+        cel.evaluate_predicate("name", {'name': 'alice'})
@@ -0,0 +1,179 @@
+import datetime
+
+import pytest
+
+import cel
+
+
+def test_repeated_contexts_are_cached():
+    program = cel.Program("user.plan == 'pro' && region in regions")
+    context = {"user": {"plan": "pro"}, "region": "eu", "regions": ["eu", "us"]}
+    assert program.evaluate(context, cache=True) is True
+    assert program.evaluate(dict(context), cache=True) is True
+    assert program.cache_info() == {"hits": 1, "misses": 1, "size": 1, "capacity": 1024}
+
+
+def test_unreferenced_variables_share_a_result():
+    program = cel.Program("flag")
+    assert program.evaluate({"flag": True, "request_id": 1}, cache=True) is True
+    assert program.evaluate({"flag": True, "request_id": 2}, cache=True) is True
+    assert program.cache_info()["hits"] == 1
+
+
+def test_caching_is_opt_in():
+    program = cel.Program("x")
+    program.evaluate({"x": 1})
+    assert program.cache_info()["misses"] == 0
+
+
+@pytest.mark.parametrize("first,second", [
+    (1, 1.0),
+    (1, True),
+    (0.0, -0.0),
+    ("1", b"1"),
+    ([1, 2], [2, 1]),
+    ({"a": 1}, {"a": 1.0}),
+    ({"a": 1}, {"b": 1}),
+    (datetime.timedelta(seconds=1), datetime.timedelta(seconds=1, microseconds=1)),
+    (datetime.datetime(2024, 1, 1, tzinfo=datetime.timezone.utc),
+     datetime.datetime(2024, 1, 1, 1, tzinfo=datetime.timezone(datetime.timedelta(hours=1)))),
+])
+def test_values_that_compare_equal_are_distinct(first, second):
+    program = cel.Program("x")
+    assert program.evaluate({"x": first}, cache=True) == first
+    result = program.evaluate({"x": second}, cache=True)
+    assert result == second and type(result) is type(second)
+    assert program.cache_info()["hits"] == 0
+
+
+def test_maps_match_in_any_order():
+    program = cel.Program("m.a + m.b")
+    assert program.evaluate({"m": {"a": 1, "b": 2}}, cache=True) == 3
+    assert program.evaluate({"m": {"b": 2, "a": 1}}, cache=True) == 3
+    assert program.cache_info()["hits"] == 1
+
+
+def test_index_variables_are_part_of_the_key():
+    program = cel.Program("items[i]")
+    assert program.evaluate({"items": [1, 2], "i": 0}, cache=True) == 1
+    assert program.evaluate({"items": [1, 2], "i": 1}, cache=True) == 2
+
+
+def test_missing_variables():
+    program = cel.Program("has(x) ? x : 'none'")
+    assert program.evaluate({}, cache=True) == "none"
+    assert program.evaluate({"x": "set"}, cache=True) == "set"
+    assert program.evaluate({}, cache=True) == "none"
+    assert program.cache_info()["hits"] == 1
+
+
+def test_options_are_part_of_the_key():
+    program = cel.Program("x + 2.0")
+    assert program.evaluate({"x": 1}, cache=True) == 3.0
+    with pytest.raises(ValueError, match="Unsupported binary operator"):
+        program.evaluate({"x": 1}, mode="strict", cache=True)
+    assert program.evaluate({"x": 1}, unknowns=["x"], cache=True).attributes == ["x"]
+
+
+def test_errors_are_cached():
+    program = cel.Program("x.missing")
+    with pytest.raises(ValueError, match="Failed to evaluate"):
+        program.evaluate({"x": {}}, cache=True)
+    error = program.evaluate({"x": {}}, cache=True, on_error="return")
+    assert isinstance(error, cel.EvalError)
+    assert program.cache_info()["hits"] == 1
+
+
+def test_python_functions_are_not_cached():
+    calls = []
+
+    def lookup(key):
+        calls.append(key)
+        return len(calls)
+
+    program = cel.Program("lookup(x)")
+    assert program.evaluate({"x": "a", "lookup": lookup}, cache=True) == 1
+    assert program.evaluate({"x": "a", "lookup": lookup}, cache=True) == 2
+    assert program.cache_info()["size"] == 0
+
+
+def test_output_types_apply_to_cached_results():
+    program = cel.Program("d")
+    context = {"d": datetime.timedelta(seconds=90)}
+    assert program.evaluate(context, cache=True) == datetime.timedelta(seconds=90)
+    seconds = cel.Context(context, output_types={"duration": "seconds"})
+    assert program.evaluate(seconds, cache=True) == 90.0
+    assert program.cache_info()["hits"] == 1
+
+
+def test_capacity():
+    program = cel.Program("x", optimize=True)
+    for x in range(1100):
+        program.evaluate({"x": x}, cache=True)
+    assert program.cache_info()["size"] == 1024
+    program.evaluate({"x": 1099}, cache=True)
+    assert program.cache_info()["hits"] == 1
+
+    program.clear_cache()
+    assert program.cache_info() == {"hits": 0, "misses": 0, "size": 0, "capacity": 1024}
+
+
+def test_random_draws_are_cached_only_with_a_seed():
+    program = cel.Program("rand()")
+    draws = {program.evaluate({}, cache=True) for _ in range(3)}
+    assert len(draws) == 3
+    assert program.cache_info()["size"] == 0
+
+    seeded = [program.evaluate(cel.Context(seed=seed), cache=True) for seed in [1, 1, 2]]
+    assert seeded[0] == seeded[1] != seeded[2]
+    assert program.cache_info()["hits"] == 1
+
+
+def test_seeds_are_part_of_the_key():
+    program = cel.Program("bucket('user-1', 1000)")
+    buckets = [program.evaluate(cel.Context(seed=seed), cache=True) for seed in [1, 2]]
+    assert buckets == [program.evaluate(cel.Context(seed=seed)) for seed in [1, 2]]
+    assert buckets[0] != buckets[1]
+
+
+def test_the_clock_is_read_unless_frozen():
+    program = cel.Program("now()")
+    context = cel.Context(extensions=["datetime"])
+    program.evaluate(context, cache=True)
+    program.evaluate(context, cache=True)
+    assert program.cache_info()["size"] == 0
+
+    utc = datetime.timezone.utc
+    for clock in [datetime.datetime(2024, 5, 6, tzinfo=utc), datetime.datetime(2025, 1, 1, tzinfo=utc)]:
+        frozen = cel.Context(extensions=["datetime"], clock=clock)
+        assert program.evaluate(frozen, cache=True) == clock
+
+
+def test_attributes_of_objects_are_read_again():
+    class User:
+        plan = 1
+
+    user = User()
+    program = cel.Program("user.plan")
+    context = cel.Context({"user": user}, objects="attributes")
+    assert program.evaluate(context, cache=True) == 1
+    user.plan = 2
+    assert program.evaluate(context, cache=True) == 2
+
+
+def test_methods_of_registered_types_are_not_cached():
+    class Counter:
+        calls = 0
+
+        def __init__(self, start):
+            self.start = start
+
+        def next_count(self):
+            Counter.calls += 1
+            return self.start + Counter.calls
+
+    cel.register_type("cache_test.Counter", Counter, fields=["start"], methods=["next_count"])
+    program = cel.Program("counter.next_count()")
+    context = {"counter": Counter(10)}
+    assert program.evaluate(context, cache=True) == 11
+    assert program.evaluate(context, cache=True) == 12
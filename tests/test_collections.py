@@ -0,0 +1,60 @@
+import array
+import collections
+import types
+
+import pytest
+
+import cel
+
+
+@pytest.mark.parametrize("value, expected", [
+    (collections.OrderedDict([("b", 1), ("a", 2)]), {"b": 1, "a": 2}),
+    (collections.UserDict({"a": 1}), {"a": 1}),
+    (types.MappingProxyType({"a": 1}), {"a": 1}),
+    (collections.ChainMap({"a": 1}, {"a": 2, "b": 3}), {"a": 1, "b": 3}),
+    (collections.Counter("aab"), {"a": 2, "b": 1}),
+])
+def test_mappings_convert_to_maps(value, expected):
+    assert cel.evaluate("v", {"v": value}) == expected
+    assert cel.evaluate("type(v) == map", {"v": value})
+
+
+@pytest.mark.parametrize("value, expected", [
+    (range(3), [0, 1, 2]),
+    (range(300, 303), [300, 301, 302]),
+    (collections.deque([1, 2]), [1, 2]),
+    (collections.UserList(["a"]), ["a"]),
+    (array.array("i", [1, 2]), [1, 2]),
+    (array.array("d", [1.5]), [1.5]),
+    (array.array("B", [1, 2]), [1, 2]),
+])
+def test_sequences_convert_to_lists(value, expected):
+    assert cel.evaluate("v", {"v": value}) == expected
+    assert cel.evaluate("type(v) == list", {"v": value})
+
+
+@pytest.mark.parametrize("value", [b"ab", bytearray(b"ab"), memoryview(b"ab")])
+def test_bytes_like_values_convert_to_bytes(value):
+    assert cel.evaluate("v", {"v": value}) == b"ab"
+    assert cel.evaluate("type(v) == bytes", {"v": value})
+
+
+def test_nested_collections():
+    value = collections.ChainMap({"items": range(2)}, {"tags": collections.deque(["x"])})
+    assert cel.evaluate("v.items.map(i, i + 1) + [size(v.tags)]", {"v": value}) == [1, 2, 1]
+
+
+def test_conversion_errors_within_mappings_name_the_path():
+    with pytest.raises(ValueError, match=r"\['a'\]"):
+        cel.evaluate("v", {"v": collections.UserDict({"a": {1}})})
+
+
+def test_mapping_keys_are_restricted_like_dict_keys():
+    with pytest.raises(ValueError, match="float key 1.5"):
+        cel.evaluate("v", {"v": collections.UserDict({1.5: 1})})
+
+
+@pytest.mark.parametrize("value", [{1, 2}, frozenset([1]), iter([1, 2])])
+def test_unordered_collections_and_iterators_are_rejected(value):
+    with pytest.raises(ValueError, match="can't be converted"):
+        cel.evaluate("v", {"v": value})
@@ -0,0 +1,58 @@
+import pytest
+
+import cel
+
+CONTEXT = {
+    "user": {"name": "Ada", "address": {"city": "London", "zip": "N1"}},
+    "items": [1, 2],
+    "uid": 3,
+    "lookup": lambda key: key,
+}
+
+
+def texts(candidates):
+    return [candidate["text"] for candidate in candidates]
+
+
+def test_variables_functions_and_keywords():
+    candidates = cel.complete("u", context=CONTEXT)
+    assert texts(candidates) == ["uid", "user", "uint"]
+    assert [candidate["kind"] for candidate in candidates] == ["variable", "variable", "function"]
+    assert all(candidate["start"] == 0 for candidate in candidates)
+
+    assert {"text": "lookup", "kind": "function", "start": 4} in cel.complete("1 + l", context=CONTEXT)
+    assert {"text": "has", "kind": "macro", "start": 0} in cel.complete("ha")
+    assert texts(cel.complete("nu")) == ["null"]
+
+
+def test_fields_of_maps():
+    assert texts(cel.complete("user.", context=CONTEXT))[:2] == ["address", "name"]
+    candidates = cel.complete("user.address.c", context=CONTEXT)
+    assert candidates == [
+        {"text": "city", "kind": "field", "start": 13},
+        {"text": "count", "kind": "macro", "start": 13},
+        {"text": "countBy", "kind": "macro", "start": 13},
+    ]
+
+
+def test_methods_depend_on_the_value():
+    assert texts(cel.complete("user.name.", context=CONTEXT)) == ["size", "contains", "startsWith", "endsWith", "matches"]
+    assert {"text": "map", "kind": "macro", "start": 6} in cel.complete("items.", context=CONTEXT)
+    assert "contains" not in texts(cel.complete("items.", context=CONTEXT))
+    assert texts(cel.complete("uid.", context=CONTEXT)) == []
+    # Without a value every method is offered
+    assert texts(cel.complete("unknown.ex", context=CONTEXT)) == ["exists", "exists_one", "existsOne"]
+    assert "getHours" in texts(cel.complete("f(x).get"))
+
+
+def test_cursor():
+    assert texts(cel.complete("us + uid", cursor=2, context=CONTEXT)) == ["user"]
+    assert cel.complete("'us", context=CONTEXT) == []
+    with pytest.raises(IndexError):
+        cel.complete("us", cursor=3)
+
+
+def test_context_objects():
+    context = cel.Context({"user": {"name": "Ada"}}, functions={"lookup": len})
+    assert texts(cel.complete("user.n", context=context)) == ["name"]
+    assert texts(cel.complete("lo", context=context)) == ["lookup"]
@@ -0,0 +1,205 @@
+import datetime
+import json
+import threading
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+import pytest
+
+import cel
+import cel.conformance
+
+SERVICE = "/google.api.expr.conformance.v1alpha1.ConformanceService/"
+
+
+class FakeService(BaseHTTPRequestHandler):
+    """A conformance service answering evaluations from `results`, by source.
+
+    Parsed expressions carry their source, so that evaluations can be answered
+    without a CEL implementation.
+    """
+
+    results = {}
+    requests = []
+
+    def do_POST(self):
+        method = self.path.removeprefix(SERVICE)
+        request = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+        self.requests.append((method, request))
+        if method == "Parse":
+            source = request["celSource"]
+            if source.startswith("!"):
+                response = {"issues": [{"code": 3, "message": "Syntax error: unexpected '!'"}]}
+            else:
+                response = {"parsedExpr": {"expr": {"id": "1", "constExpr": {"stringValue": source}}}}
+        elif method == "Check":
+            response = {"checkedExpr": {"typeMap": {"1": {"primitive": "INT64"}}}}
+        else:
+            source = request["parsedExpr"]["expr"]["constExpr"]["stringValue"]
+            response = {"result": self.results[source]}
+        status = 200
+        if response.get("result", {}) is None:
+            status, response = 500, {"code": "internal", "message": "evaluator crashed"}
+        body = json.dumps(response).encode()
+        self.send_response(status)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def service():
+    FakeService.results = {}
+    FakeService.requests = []
+    server = ThreadingHTTPServer(("127.0.0.1", 0), FakeService)
+    thread = threading.Thread(target=server.serve_forever, daemon=True)
+    thread.start()
+    yield cel.conformance.RemoteService(f"http://127.0.0.1:{server.server_port}/")
+    server.shutdown()
+    server.server_close()
+
+
+def test_remote_service():
+    remote = cel.conformance.RemoteService("http://localhost:8080/", timeout=2.5)
+    assert remote.url == "http://localhost:8080"
+    assert remote.timeout == 2.5
+    assert repr(remote) == "RemoteService('http://localhost:8080')"
+
+
+def test_parse_and_check(service):
+    assert service.parse("1 + 1") == {"expr": {"id": "1", "constExpr": {"stringValue": "1 + 1"}}}
+    assert service.check("1 + 1") == {"typeMap": {"1": {"primitive": "INT64"}}}
+    with pytest.raises(ValueError, match="Syntax error: unexpected '!'"):
+        service.parse("!!")
+    assert [method for method, _ in FakeService.requests] == ["Parse", "Parse", "Check", "Parse"]
+
+
+def test_remote_values(service):
+    FakeService.results = {
+        "int": {"value": {"int64Value": "-3"}},
+        "uint": {"value": {"uint64Value": "18446744073709551615"}},
+        "double": {"value": {"doubleValue": "Infinity"}},
+        "bytes": {"value": {"bytesValue": "aGVsbG8="}},
+        "null": {"value": {"nullValue": None}},
+        "list": {"value": {"listValue": {"values": [{"boolValue": True}, {"stringValue": "a"}]}}},
+        "map": {"value": {"mapValue": {"entries": [{"key": {"int64Value": "1"}, "value": {"doubleValue": 0.5}}]}}},
+        "empty": {"value": {"list_value": {}}},
+        "duration": {"value": {"objectValue": {"@type": "type.googleapis.com/google.protobuf.Duration", "value": "-1.5s"}}},
+        "timestamp": {
+            "value": {"objectValue": {"@type": "type.googleapis.com/google.protobuf.Timestamp", "value": "2024-01-02T03:04:05Z"}}
+        },
+    }
+    assert service.evaluate("int") == -3
+    assert service.evaluate("uint") == 2**64 - 1
+    assert service.evaluate("double") == float("inf")
+    assert service.evaluate("bytes") == b"hello"
+    assert service.evaluate("null") is None
+    assert service.evaluate("list") == [True, "a"]
+    assert service.evaluate("map") == {1: 0.5}
+    assert service.evaluate("empty") == []
+    assert service.evaluate("duration") == datetime.timedelta(seconds=-1.5)
+    assert service.evaluate("timestamp") == datetime.datetime(2024, 1, 2, 3, 4, 5, tzinfo=datetime.timezone.utc)
+
+
+def test_remote_errors(service):
+    FakeService.results = {"1 / 0": {"error": {"errors": [{"message": "divide by zero"}]}}}
+    with pytest.raises(ValueError, match="divide by zero"):
+        service.evaluate("1 / 0")
+    with pytest.raises(ValueError, match="Failed to compile expression '!1': Syntax error"):
+        service.evaluate("!1")
+
+
+def test_variables_are_sent_as_bindings(service):
+    FakeService.results = {"x": {"value": {"int64Value": "1"}}}
+    when = datetime.datetime(2024, 1, 2, 4, 4, 5, 500000, tzinfo=datetime.timezone(datetime.timedelta(hours=1)))
+    service.evaluate(
+        "x",
+        {"i": 7, "f": 1.0, "s": "a", "b": b"\xff", "l": [None], "m": {"k": True}, "t": when, "d": datetime.timedelta(milliseconds=-250)},
+    )
+    _, request = FakeService.requests[-1]
+    assert request["bindings"] == {
+        "i": {"value": {"int64Value": "7"}},
+        "f": {"value": {"doubleValue": 1.0}},
+        "s": {"value": {"stringValue": "a"}},
+        "b": {"value": {"bytesValue": "/w=="}},
+        "l": {"value": {"listValue": {"values": [{"nullValue": None}]}}},
+        "m": {"value": {"mapValue": {"entries": [{"key": {"stringValue": "k"}, "value": {"boolValue": True}}]}}},
+        "t": {"value": {"objectValue": {"@type": "type.googleapis.com/google.protobuf.Timestamp", "value": "2024-01-02T03:04:05.500Z"}}},
+        "d": {"value": {"objectValue": {"@type": "type.googleapis.com/google.protobuf.Duration", "value": "-0.25s"}}},
+    }
+
+
+def test_functions_cant_be_sent(service):
+    with pytest.raises(ValueError):
+        service.evaluate("f()", {"f": lambda: 1})
+
+
+def test_compare(service):
+    FakeService.results = {
+        "1 + 1": {"value": {"int64Value": "2"}},
+        "x * 2": {"value": {"int64Value": "4"}},
+        "1 / 2": {"value": {"doubleValue": 0.5}},
+        "1 / 0": {"error": {"errors": [{"message": "division by zero"}]}},
+        "[1, 2].size()": {"error": {"errors": [{"message": "no such overload"}]}},
+        "timestamp('2024-01-02T04:04:05+01:00')": {
+            "value": {"objectValue": {"@type": "type.googleapis.com/google.protobuf.Timestamp", "value": "2024-01-02T03:04:05Z"}}
+        },
+        "1 +": {"value": {"int64Value": "1"}},
+    }
+    divergences = cel.conformance.compare(
+        service,
+        [
+            "1 + 1",
+            ("x * 2", {"x": 2}),
+            "1 / 2",
+            "1 / 0",
+            "[1, 2].size()",
+            "timestamp('2024-01-02T04:04:05+01:00')",
+            "!true",
+            "1 +",
+        ],
+    )
+    assert [(d.expression, d.kind) for d in divergences] == [
+        ("1 / 2", "value"),
+        ("[1, 2].size()", "error"),
+        ("!true", "compile"),
+        ("1 +", "compile"),
+    ]
+    value, error, compile_error, _ = divergences
+    assert (value.local, value.remote) == (0, 0.5)
+    assert error.local == 2
+    assert isinstance(error.remote, cel.EvalError)
+    assert error.remote.message == "no such overload"
+    assert compile_error.local is False
+    assert compile_error.remote.kind == "compile"
+    assert repr(value) == "Divergence(expression='1 / 2', kind='value', local=0, remote=0.5)"
+
+
+def test_compare_values_exactly(service):
+    FakeService.results = {"1": {"value": {"doubleValue": 1.0}}, "1u": {"value": {"int64Value": "1"}}}
+    divergences = cel.conformance.compare(service, ["1", "1u"])
+    assert [(d.local, d.remote) for d in divergences] == [(1, 1.0), (1, 1)]
+
+
+def test_compare_with_mode(service):
+    FakeService.results = {"1 + 1.5": {"value": {"doubleValue": 2.5}}}
+    assert cel.conformance.compare(service, ["1 + 1.5"]) == []
+    [divergence] = cel.conformance.compare(service, ["1 + 1.5"], mode="strict")
+    assert divergence.kind == "compile"
+    assert "without numeric promotion" in divergence.local.message
+
+
+def test_compare_rejects_other_items(service):
+    with pytest.raises(TypeError, match="expected an expression or an \\(expression, variables\\) tuple, got int"):
+        cel.conformance.compare(service, [1])
+
+
+def test_service_errors(service):
+    FakeService.results = {"1": None}
+    with pytest.raises(ConnectionError, match="conformance service failed to eval: evaluator crashed"):
+        service.evaluate("1")
+    with pytest.raises(ConnectionError, match="conformance service failed to eval: evaluator crashed"):
+        cel.conformance.compare(service, ["1"])
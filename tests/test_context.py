@@ -99,4 +99,364 @@ def test_nested_context_none():
         }
     }
 
-    context = cel.Context(variables=context)
\ No newline at end of file
+    context = cel.Context(variables=context)
+
+def test_missing_field_raises_by_default():
+    with pytest.raises(ValueError):
+        cel.evaluate("user.email", {'user': {'name': 'alice'}})
+
+
+def test_safe_navigation_per_call():
+    data = {'user': {'name': 'alice', 'address': None}}
+    assert cel.evaluate("user.email", data, safe_navigation=True) is None
+    assert cel.evaluate("user.address.city", data, safe_navigation=True) is None
+    assert cel.evaluate("user.profile.avatar.url", data, safe_navigation=True) is None
+    assert cel.evaluate("user.name", data, safe_navigation=True) == 'alice'
+
+
+def test_safe_navigation_on_context():
+    context = cel.Context({'user': {'name': 'alice'}}, safe_navigation=True)
+    assert context.safe_navigation
+    assert cel.evaluate("user.email == null", context) == True
+    assert cel.evaluate("user['missing']", context) is None
+
+    with pytest.raises(ValueError):
+        cel.evaluate("user.email", context, safe_navigation=False)
+
+
+def test_safe_navigation_keeps_has_semantics():
+    context = cel.Context({'user': {'name': 'alice'}}, safe_navigation=True)
+    assert cel.evaluate("has(user.email)", context) == False
+    assert cel.evaluate("has(user.name)", context) == True
+
+
+def test_safe_navigation_in_macros_and_indexes():
+    data = {'users': [{'name': 'alice', 'tags': ['admin']}, {'name': 'bob'}]}
+    assert cel.evaluate("users.map(u, u.tags)", data, safe_navigation=True) == [['admin'], None]
+    assert cel.evaluate("users.filter(u, u.tags != null).size()", data, safe_navigation=True) == 1
+    assert cel.evaluate("users[0].tags[0]", data, safe_navigation=True) == 'admin'
+
+
+def test_has_top_level_variable():
+    assert cel.evaluate("has(feature_flags)", {'feature_flags': {'beta': True}}) == True
+    assert cel.evaluate("has(feature_flags)", {}) == False
+    assert cel.evaluate("has(feature_flags) && feature_flags.beta", {}) == False
+
+
+def test_has_top_level_variable_with_context():
+    context = cel.Context({'flag': None})
+    assert cel.evaluate("has(flag)", context) == True
+    assert cel.evaluate("has(other)", context) == False
+
+
+def test_has_on_field_of_undeclared_variable_raises():
+    with pytest.raises(ValueError):
+        cel.evaluate("has(missing.field)", {})
+
+
+def test_list_of_records_context():
+    records = [{'id': i, 'name': f"user-{i}", 'tags': {'admin': i % 2 == 0}} for i in range(100)]
+    context = cel.Context({'records': records})
+    assert cel.evaluate("records.filter(r, r.tags.admin).map(r, r.id)[1]", context) == 2
+    assert cel.evaluate("records[99].name", {'records': records}) == "user-99"
+
+
+def test_str_subclass_keys():
+    class Name(str):
+        pass
+
+    assert cel.evaluate("m.a + m.b", {'m': {Name('a'): 1, 'b': 2}}) == 3
+
+
+def test_reused_context_sees_changes():
+    context = cel.Context({"x": 1})
+    assert cel.evaluate("x", context) == 1
+    assert cel.evaluate("x + 1", context) == 2
+
+    context.add_variable("x", 10)
+    assert cel.evaluate("x", context) == 10
+    context.update({"y": 5})
+    assert cel.evaluate("x + y", context) == 15
+    context.add_function("double", lambda v: v * 2)
+    assert cel.evaluate("double(x)", context) == 20
+    context.update({"double": lambda v: v * 3})
+    assert cel.evaluate("double(x)", context) == 30
+
+
+def test_failed_update_keeps_converted_variables():
+    context = cel.Context({"x": 1})
+    assert cel.evaluate("x", context) == 1
+    with pytest.raises(ValueError):
+        context.update({"x": 2, "bad": object()})
+    assert cel.evaluate("x", context) == 2
+
+
+def test_reused_context_with_different_options():
+    context = cel.Context({"t": "2024-01-02"})
+    assert cel.evaluate("timestamp(t).getFullYear()", context) == 2024
+    with pytest.raises(ValueError, match="RFC 3339"):
+        cel.evaluate("timestamp(t)", context, mode="strict")
+    assert cel.evaluate("timestamp(t).getFullYear()", context) == 2024
+    context.mode = "strict"
+    with pytest.raises(ValueError, match="RFC 3339"):
+        cel.evaluate("timestamp(t)", context)
+
+
+def test_reused_context_with_rewrites():
+    context = cel.Context({"user": {}})
+    assert cel.evaluate("user.name", context, safe_navigation=True) is None
+    assert cel.evaluate("user.name", context, unknowns=["user.name"]).attributes == ["user.name"]
+    assert cel.evaluate("size(user)", context) == 0
+
+
+def test_update_variable():
+    context = cel.Context({f"metric_{i}": i for i in range(1000)})
+    assert cel.evaluate("metric_0 + metric_999", context) == 999
+    for value in range(5):
+        context.update_variable("metric_0", value)
+        assert cel.evaluate("metric_0 + metric_999", context) == 999 + value
+    context.update_variable("metric_0", "now a string")
+    assert cel.evaluate("metric_0", context) == "now a string"
+
+
+def test_update_variable_requires_an_existing_variable():
+    context = cel.Context({"x": 1})
+    with pytest.raises(KeyError, match="y"):
+        context.update_variable("y", 2)
+    with pytest.raises(ValueError, match="Failed to convert variable 'x'"):
+        context.update_variable("x", object())
+    assert cel.evaluate("x", context) == 1
+
+
+def test_update_variable_while_an_evaluation_holds_the_context():
+    seen = []
+    context = cel.Context({"x": 1})
+
+    def observe(value):
+        context.update_variable("x", value + 1)
+        seen.append(value)
+        return value
+
+    context.add_function("observe", observe)
+    assert cel.evaluate("observe(x)", context) == 1
+    assert cel.evaluate("observe(x)", context) == 2
+    assert seen == [1, 2]
+
+
+def test_remove_variable():
+    context = cel.Context({"x": 1, "y": 2})
+    assert cel.evaluate("x + y", context) == 3
+    context.remove_variable("y")
+    assert cel.evaluate("has(y)", context) is False
+    with pytest.raises(KeyError, match="y"):
+        context.remove_variable("y")
+
+
+def test_removing_a_variable_restores_a_type_name():
+    context = cel.Context({"int": 5})
+    assert cel.evaluate("int", context) == 5
+    context.remove_variable("int")
+    assert cel.evaluate("type(1) == int", context) is True
+
+
+def test_remove_function():
+    context = cel.Context({"x": 2}, {"twice": lambda v: v * 2})
+    assert cel.evaluate("twice(x)", context) == 4
+    context.remove_function("twice")
+    with pytest.raises(ValueError, match="twice"):
+        cel.evaluate("twice(x)", context)
+    with pytest.raises(KeyError, match="twice"):
+        context.remove_function("twice")
+
+
+def test_clear_releases_functions():
+    import gc
+    import weakref
+
+    class Function:
+        def __call__(self, value):
+            return value
+
+    function = Function()
+    reference = weakref.ref(function)
+    context = cel.Context({"x": 1}, {"f": function})
+    assert cel.evaluate("f(x)", context) == 1
+    del function
+    gc.collect()
+    assert reference() is not None
+
+    context.clear()
+    assert reference() is None
+    assert cel.evaluate("has(x)", context) is False
+
+
+def test_context_manager():
+    with cel.Context({"x": [1, 2, 3]}) as context:
+        assert isinstance(context, cel.Context)
+        assert cel.evaluate("size(x)", context) == 3
+    assert context.memory_usage() == cel.Context().memory_usage()
+    assert cel.evaluate("has(x)", context) is False
+
+
+def test_reference_cycles_through_functions_are_collected():
+    import gc
+    import weakref
+
+    class Application:
+        pass
+
+    def make_context():
+        application = Application()
+        context = cel.Context({"x": 1})
+        # The function refers to the context, which refers back to the function
+        context.add_function("lookup", lambda: (application, context) and 1)
+        assert cel.evaluate("lookup() + x", context) == 2
+        return weakref.ref(application)
+
+    application = make_context()
+    gc.collect()
+    assert application() is None
+
+
+def test_cycles_through_functions_passed_as_variables_are_collected():
+    import gc
+    import weakref
+
+    class Application:
+        pass
+
+    def make_context():
+        application = Application()
+        context = cel.Context()
+        context.update({"f": lambda: application is not None and context is not None})
+        assert cel.evaluate("f()", context) is True
+        return weakref.ref(application)
+
+    application = make_context()
+    gc.collect()
+    assert application() is None
+
+
+def test_namedtuples_convert_to_lists_by_default():
+    import collections
+    Point = collections.namedtuple("Point", ["x", "y"])
+    assert cel.evaluate("p[1]", {"p": Point(1, 2)}) == 2
+
+
+def test_namedtuples_as_maps():
+    import collections
+    Point = collections.namedtuple("Point", ["x", "y"])
+    context = cel.Context({"p": Point(1, 2), "path": [Point(0, 0), Point(3, 4)], "plain": (5, 6)},
+                          namedtuples_as_maps=True)
+    assert context.namedtuples_as_maps
+    assert cel.evaluate("p.x + p.y", context) == 3
+    assert cel.evaluate("path.map(q, q.y)", context) == [0, 4]
+    assert cel.evaluate("plain[0]", context) == 5
+    assert cel.evaluate("p", context) == {"x": 1, "y": 2}
+
+    context.add_variable("q", Point(7, 8))
+    context.update({"r": Point(9, 10)})
+    assert cel.evaluate("q.x + r.y", context) == 17
+
+
+@pytest.mark.parametrize("variables, message", [
+    ({"user": {"tags": ["a", "b", "c", {1}]}},
+     "variable 'user' at context['user']['tags'][3]: Python objects of type set can't"),
+    ({"x": object()}, "variable 'x': Python objects of type object can't"),
+    ({"d": {"a": {(1, 2): 3}}}, "variable 'd' at context['d']['a']: tuple key (1, 2) cannot be used"),
+    ({"rows": ({1: None}, {2: 1j})}, "variable 'rows' at context['rows'][1][2]: Python objects of type complex"),
+])
+def test_conversion_errors_name_the_path(variables, message):
+    for convert in (cel.Context, lambda variables: cel.Context().update(variables),
+                    lambda variables: cel.evaluate("1", variables)):
+        with pytest.raises(ValueError) as error:
+            convert(variables)
+        assert message in str(error.value)
+
+
+def test_conversion_errors_within_named_tuples():
+    import collections
+    Point = collections.namedtuple("Point", ["x", "y"])
+    with pytest.raises(ValueError, match=r"context\['p'\]\.y: Python objects of type set"):
+        cel.Context({"p": Point(1, {2})}, namedtuples_as_maps=True)
+
+
+def test_conversion_errors_of_function_results_name_the_path():
+    with pytest.raises(ValueError, match=r"Conversion Error at \['a'\]\[0\]: Python objects of type set"):
+        cel.evaluate("f()", {"f": lambda: {"a": [set()]}})
+
+
+def test_dict_keys_keep_their_types():
+    context = cel.Context({"d": {True: "bool", 2: "int", 2 ** 64 - 1: "uint", "k": "str"}})
+    assert cel.evaluate("d[true]", context) == "bool"
+    assert cel.evaluate("d[2]", context) == "int"
+    assert cel.evaluate("d[18446744073709551615u]", context) == "uint"
+    assert cel.evaluate("d['k']", context) == "str"
+
+
+def test_enum_dict_keys_use_their_values():
+    import enum
+
+    class Colour(enum.Enum):
+        RED = "red"
+        BLUE = 2
+
+    context = cel.Context({"d": {Colour.RED: 1, Colour.BLUE: 2}})
+    assert cel.evaluate("d['red'] + d[2]", context) == 3
+
+
+@pytest.mark.parametrize("key, message", [
+    (1.5, "float key 1.5 cannot be used"),
+    (2 ** 64, "int key 18446744073709551616 is out of range"),
+    (None, "None cannot be used as a key"),
+    ((1, 2), "tuple key (1, 2) cannot be used, CEL map keys must be str, int or bool"),
+    (b"k", "bytes key b'k' cannot be used"),
+])
+def test_unsupported_dict_keys(key, message):
+    with pytest.raises(ValueError, match=r"context\['d'\]\['outer'\]: ") as error:
+        cel.Context({"d": {"outer": {key: 1}}})
+    assert message in str(error.value)
+
+
+def test_bytes_keys_can_be_decoded():
+    context = cel.Context({"d": {b"name": 1}}, bytes_keys="decode")
+    assert context.bytes_keys == "decode"
+    assert cel.evaluate("d.name", context) == 1
+    assert cel.Context().bytes_keys == "error"
+    with pytest.raises(ValueError, match="not valid UTF-8"):
+        cel.Context({"d": {b"\xff": 1}}, bytes_keys="decode")
+    with pytest.raises(ValueError, match="bytes_keys must be"):
+        cel.Context(bytes_keys="ignore")
+
+
+def test_functions_info_lists_builtins():
+    info = cel.Context().functions_info()
+    assert info["size"] == {
+        "signature": "size(value) -> int",
+        "doc": "The length of a string, bytes, list or map",
+        "builtin": True,
+    }
+    assert {"map", "filter", "timestamp", "getHours", "matches"} <= set(info)
+    assert all(entry["builtin"] for entry in info.values())
+
+
+def test_functions_info_of_python_functions():
+    def discount(price, rate=0.1):
+        """The price after the discount."""
+        return price * (1 - rate)
+
+    context = cel.Context(functions={"discount": discount, "size": len, "cls": str})
+    info = context.functions_info()
+    assert info["discount"] == {
+        "signature": "discount(price, rate=0.1)",
+        "doc": "The price after the discount.",
+        "builtin": False,
+    }
+    # Python functions replace builtins
+    assert info["size"]["builtin"] is False
+    assert info["size"]["doc"] == len.__doc__
+    assert info["cls"]["signature"] is None
+
+    info = cel.Context(functions={"f": lambda x: x}).functions_info()
+    assert info["f"]["signature"] == "f(x)"
+    assert info["f"]["doc"] is None
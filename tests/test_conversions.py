@@ -0,0 +1,115 @@
+import pytest
+
+import cel
+
+
+@pytest.mark.parametrize("expression,expected", [
+    ("int('-42')", -42),
+    ("int('+7')", 7),
+    ("int(2.9)", 2),
+    ("int(-2.9)", -2),
+    ("int(-9223372036854775808.0)", -(2 ** 63)),
+    ("int(42u)", 42),
+    ("int(timestamp('1970-01-01T00:01:00Z'))", 60),
+    ("uint('42')", 42),
+    ("uint(2.9)", 2),
+    ("uint(-0.5)", 0),
+    ("uint(42)", 42),
+    ("double('1.5')", 1.5),
+    ("double('1e3')", 1000.0),
+    ("double(2)", 2.0),
+    ("double(2u)", 2.0),
+    ("string(true)", "true"),
+    ("string(-1)", "-1"),
+    ("string(1u)", "1"),
+    ("string(2.5)", "2.5"),
+    ("string(b'caf\\xc3\\xa9')", "café"),
+    ("bytes('café')", "café".encode()),
+    ("bytes(b'abc')", b"abc"),
+    ("bool('true')", True),
+    ("bool('True')", True),
+    ("bool('t')", True),
+    ("bool('1')", True),
+    ("bool('FALSE')", False),
+    ("bool('f')", False),
+    ("bool(false)", False),
+    ("dyn([1, 'a'])", [1, "a"]),
+    ("dyn(1) + 1", 2),
+])
+def test_conversions(expression, expected):
+    assert cel.evaluate(expression) == expected
+
+
+@pytest.mark.parametrize("expression", [
+    "int('abc')",
+    "int('1.5')",
+    "int('')",
+    "uint('-1')",
+    "double('one')",
+    "bool('yes')",
+])
+def test_invalid_strings(expression):
+    with pytest.raises(cel.ConversionError, match="invalid syntax"):
+        cel.evaluate(expression)
+
+
+@pytest.mark.parametrize("expression", [
+    "int(9223372036854775808.0)",
+    "int(-1e19)",
+    "int(double('nan'))",
+    "int(double('inf'))",
+    "int(18446744073709551615u)",
+    "uint(-1)",
+    "uint(-1.0)",
+    "uint(18446744073709551616.0)",
+])
+def test_out_of_range(expression):
+    with pytest.raises(cel.ConversionRangeError, match="range error") as exc_info:
+        cel.evaluate(expression)
+    assert isinstance(exc_info.value, OverflowError)
+
+
+@pytest.mark.parametrize("expression", [
+    "int([])",
+    "uint(true)",
+    "double(null)",
+    "string(null)",
+    "bytes(1)",
+    "bool(1)",
+])
+def test_unsupported_types(expression):
+    with pytest.raises(cel.ConversionTypeError, match="no such overload") as exc_info:
+        cel.evaluate(expression)
+    assert isinstance(exc_info.value, TypeError)
+
+
+def test_invalid_utf8():
+    with pytest.raises(cel.ConversionError, match="invalid UTF-8"):
+        cel.evaluate("string(b'\\xff')")
+
+
+def test_conversion_errors_are_value_errors():
+    assert issubclass(cel.ConversionError, ValueError)
+    assert issubclass(cel.ConversionRangeError, cel.ConversionError)
+    assert issubclass(cel.ConversionTypeError, cel.ConversionError)
+    with pytest.raises(ValueError):
+        cel.evaluate("int('abc')")
+
+
+def test_conversion_errors_from_programs_and_predicates():
+    with pytest.raises(cel.ConversionRangeError):
+        cel.Program("int(x)", optimize=True).evaluate({"x": 1e20})
+    with pytest.raises(cel.ConversionError):
+        cel.evaluate_predicate("bool(x)", {"x": "maybe"})
+
+
+def test_returned_conversion_error():
+    result = cel.evaluate("int(x)", {"x": "abc"}, on_error="return")
+    assert isinstance(result, cel.EvalError)
+    assert "invalid syntax" in result.message
+
+
+def test_other_errors_are_plain_value_errors():
+    with pytest.raises(ValueError) as exc_info:
+        cel.evaluate("1 + 'a'")
+    assert not isinstance(exc_info.value, cel.ConversionError)
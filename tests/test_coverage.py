@@ -0,0 +1,98 @@
+import pytest
+
+import cel
+
+POLICY = 'user.age >= 18 && (user.role == "admin" || user.country in ["NZ"])'
+
+
+def test_report():
+    program = cel.Program(POLICY)
+    coverage = cel.Coverage()
+    assert program.evaluate({"user": {"age": 15, "role": "user", "country": "NZ"}}, coverage=coverage) is False
+    assert program.evaluate({"user": {"age": 20, "role": "admin", "country": "NZ"}}, coverage=coverage) is True
+    assert coverage.expressions == [POLICY]
+    assert coverage.report() == "\n".join(
+        [
+            f"{POLICY}: 8/12 nodes, 6/10 branches",
+            '  never false: user.role == "admin" || user.country in ["NZ"]',
+            '  never false: user.role == "admin"',
+            '  never evaluated: user.country in ["NZ"]',
+            "",
+        ]
+    )
+    assert coverage.node_coverage == pytest.approx(8 / 12)
+    assert coverage.branch_coverage == pytest.approx(0.6)
+    assert repr(coverage) == "Coverage(expressions=1, node_coverage=0.67, branch_coverage=0.60)"
+
+
+def test_coverage_accumulates_to_complete():
+    program = cel.Program(POLICY)
+    coverage = cel.Coverage()
+    for age, role, country in [(15, "user", "NZ"), (20, "admin", "NZ"), (20, "user", "NZ"), (20, "user", "AU")]:
+        program.evaluate({"user": {"age": age, "role": role, "country": country}}, coverage=coverage)
+    assert coverage.missing() == []
+    assert coverage.node_coverage == 1.0
+    assert coverage.branch_coverage == 1.0
+
+
+def test_missing():
+    coverage = cel.Coverage()
+    cel.Program("x ? 1 : 2").evaluate({"x": True}, coverage=coverage)
+    cel.Program("!flag").evaluate({"flag": True}, coverage=coverage)
+    assert coverage.missing() == [
+        ("x ? 1 : 2", "x", "never false"),
+        ("x ? 1 : 2", "2", "never evaluated"),
+        ("!flag", "!flag", "never true"),
+        ("!flag", "flag", "never false"),
+    ]
+    assert coverage.expressions == ["x ? 1 : 2", "!flag"]
+
+
+def test_empty_coverage():
+    coverage = cel.Coverage()
+    assert coverage.expressions == []
+    assert coverage.missing() == []
+    assert coverage.report() == ""
+    assert coverage.node_coverage == 1.0
+
+
+@pytest.mark.parametrize(
+    "expression, context, expected",
+    [
+        ("items.exists(i, i > limit) && has(user.email)", {"items": [1, 5], "limit": 3, "user": {"email": "a"}}, True),
+        ("[1, 2].map(x, x * 2).filter(y, y > 2)", None, [4]),
+        ("type(x) == int && google.protobuf.Timestamp == type(timestamp('2024-01-01T00:00:00Z'))", {"x": 1}, True),
+        ('{"a": 1}[k] + size(b"ab")', {"k": "a"}, 3),
+        ("a.missing || true", {"a": {}}, True),
+    ],
+)
+def test_evaluations_are_unchanged(expression, context, expected):
+    coverage = cel.Coverage()
+    assert cel.Program(expression).evaluate(context, coverage=coverage) == expected
+    assert coverage.node_coverage > 0
+
+
+def test_errors_are_still_recorded():
+    coverage = cel.Coverage()
+    with pytest.raises(ValueError):
+        cel.Program("x > 0 && 10 / (x - 1) > 1").evaluate({"x": 1}, coverage=coverage)
+    assert coverage.missing() == [
+        ("x > 0 && 10 / (x - 1) > 1", "x > 0", "never false"),
+        ("x > 0 && 10 / (x - 1) > 1", "10 / (x - 1) > 1", "never true"),
+        ("x > 0 && 10 / (x - 1) > 1", "10 / (x - 1) > 1", "never false"),
+        ("x > 0 && 10 / (x - 1) > 1", "1", "never evaluated"),
+    ]
+
+
+def test_strict_mode_checks_the_expression():
+    coverage = cel.Coverage()
+    with pytest.raises(ValueError, match="without numeric promotion"):
+        cel.Program("1 + 1.5").evaluate(None, coverage=coverage, mode="strict")
+    assert coverage.expressions == []
+
+
+def test_coverage_with_unknowns():
+    coverage = cel.Coverage()
+    result = cel.Program("user.age > 1 || admin").evaluate({"admin": False}, unknowns=["user.age"], coverage=coverage)
+    assert isinstance(result, cel.Unknown)
+    assert ("user.age > 1 || admin", "admin", "never true") in coverage.missing()
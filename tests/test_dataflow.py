@@ -0,0 +1,56 @@
+import pytest
+
+import cel
+
+
+def test_expressions_are_evaluated_after_their_dependencies():
+    flow = cel.plan(
+        {
+            "total": "subtotal + tax",
+            "tax": "subtotal / 10.0",
+            "subtotal": "price * double(quantity)",
+        }
+    )
+    assert flow.order == ["subtotal", "tax", "total"]
+    assert flow.evaluate({"price": 2.0, "quantity": 5}) == {"subtotal": 10.0, "tax": 1.0, "total": 11.0}
+    assert list(flow.evaluate({"price": 2.0, "quantity": 5})) == ["subtotal", "tax", "total"]
+
+
+def test_given_order_is_kept_where_possible():
+    flow = cel.plan([("c", "1"), ("b", "a + 1"), ("a", "2"), ("d", "3")])
+    assert repr(flow) == "Dataflow(['c', 'a', 'b', 'd'])"
+    assert flow.order == ["c", "a", "b", "d"]
+
+
+def test_programs_and_sources():
+    flow = cel.plan([("doubled", cel.Program("x * 2", optimize=True)), ("label", "string(doubled)")])
+    assert flow.evaluate({"x": 21}) == {"doubled": 42, "label": "42"}
+
+
+def test_macro_variables_are_not_dependencies():
+    flow = cel.plan([("counts", "items.map(count, count + 1)"), ("count", "size(counts)")])
+    assert flow.order == ["counts", "count"]
+    assert flow.evaluate({"items": [1, 2]}) == {"counts": [2, 3], "count": 2}
+
+
+def test_results_can_be_used_with_context_objects():
+    context = cel.Context({"x": 1}, functions={"inc": lambda v: v + 1})
+    flow = cel.plan({"y": "inc(x)", "z": "inc(y)"})
+    assert flow.evaluate(context) == {"y": 2, "z": 3}
+    with pytest.raises(ValueError, match="Undeclared reference"):
+        cel.evaluate("y", context)
+
+
+def test_cycles_are_rejected():
+    with pytest.raises(ValueError, match="cycle: a, b"):
+        cel.plan({"a": "b + 1", "b": "a + 1", "c": "1"})
+    with pytest.raises(ValueError, match="more than one expression"):
+        cel.plan([("a", "1"), ("a", "2")])
+
+
+def test_errors_are_raised():
+    flow = cel.plan({"a": "missing + 1", "b": "a"})
+    with pytest.raises(ValueError, match="missing"):
+        flow.evaluate({})
+    with pytest.raises(ValueError, match="Failed to compile"):
+        cel.plan({"a": "1 +"})
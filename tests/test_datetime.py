@@ -0,0 +1,123 @@
+import datetime
+
+import pytest
+
+import cel
+
+UTC = datetime.timezone.utc
+FROZEN = datetime.datetime(2024, 5, 6, 7, 8, 9, tzinfo=UTC)
+
+
+@pytest.fixture
+def context():
+    context = cel.Context(extensions=["datetime"])
+    context.set_now(FROZEN)
+    return context
+
+
+def test_datetime_is_opt_in():
+    with pytest.raises(ValueError, match="Undeclared reference to 'now'"):
+        cel.evaluate("now()")
+
+
+def test_now_reads_the_clock():
+    context = cel.Context(extensions=["datetime"])
+    before = datetime.datetime.now(UTC)
+    now = cel.evaluate("now()", context)
+    assert before <= now <= datetime.datetime.now(UTC)
+
+
+def test_now_can_be_frozen(context):
+    assert cel.evaluate("now()", context) == FROZEN
+    assert cel.evaluate("today()", context) == datetime.datetime(2024, 5, 6, tzinfo=UTC)
+    context.set_now(None)
+    assert cel.evaluate("now()", context) != FROZEN
+
+
+def test_set_now_needs_a_datetime(context):
+    with pytest.raises(TypeError, match="the clock must be a datetime or None, got str"):
+        context.set_now("2024-05-06")
+
+
+@pytest.mark.parametrize(
+    "unit,expected",
+    [
+        ("year", datetime.datetime(2024, 1, 1, tzinfo=UTC)),
+        ("month", datetime.datetime(2024, 5, 1, tzinfo=UTC)),
+        ("day", datetime.datetime(2024, 5, 6, tzinfo=UTC)),
+        ("hour", datetime.datetime(2024, 5, 6, 7, tzinfo=UTC)),
+        ("minutes", datetime.datetime(2024, 5, 6, 7, 8, tzinfo=UTC)),
+        ("second", datetime.datetime(2024, 5, 6, 7, 8, 9, tzinfo=UTC)),
+    ],
+)
+def test_truncate(context, unit, expected):
+    assert cel.evaluate(f"now().truncate('{unit}')", context) == expected
+    assert cel.evaluate(f"truncate(now(), '{unit}')", context) == expected
+
+
+def test_truncate_keeps_the_offset(context):
+    auckland = datetime.timezone(datetime.timedelta(hours=12))
+    context.update({"ts": datetime.datetime(2024, 5, 6, 1, 2, 3, tzinfo=auckland)})
+    assert cel.evaluate("ts.truncate('day')", context) == datetime.datetime(2024, 5, 6, tzinfo=auckland)
+
+
+def test_add(context):
+    later = FROZEN + datetime.timedelta(hours=1, minutes=30)
+    assert cel.evaluate("now().add(duration('1h30m'))", context) == later
+    assert cel.evaluate("now().add('1h30m')", context) == later
+    assert cel.evaluate("now().add('-1h')", context) == FROZEN - datetime.timedelta(hours=1)
+
+
+@pytest.mark.parametrize(
+    "created,unit,expected",
+    [
+        (datetime.datetime(2024, 4, 1, tzinfo=UTC), "days", 35),
+        (datetime.datetime(2024, 5, 6, 8, tzinfo=UTC), "hours", 0),
+        (datetime.datetime(2024, 5, 6, 9, tzinfo=UTC), "hours", -1),
+        (datetime.datetime(2024, 4, 6, 7, 8, 10, tzinfo=UTC), "months", 0),
+        (datetime.datetime(2024, 4, 6, 7, 8, 9, tzinfo=UTC), "months", 1),
+        (datetime.datetime(2023, 5, 6, tzinfo=UTC), "years", 1),
+        (datetime.datetime(2024, 6, 7, tzinfo=UTC), "months", -1),
+        (datetime.datetime(2024, 4, 29, tzinfo=UTC), "weeks", 1),
+    ],
+)
+def test_date_diff(context, created, unit, expected):
+    context.update({"created": created})
+    assert cel.evaluate(f"date_diff(now(), created, '{unit}')", context) == expected
+
+
+@pytest.mark.parametrize(
+    "expression,message",
+    [
+        ("now().truncate('fortnight')", "can't truncate to 'fortnight'"),
+        ("date_diff(now(), now(), 'eons')", "unknown unit 'eon'"),
+        ("date_diff(now(), 1, 'days')", "expected a timestamp, got int"),
+        ("now().add(1)", "expected a duration, got int"),
+        ("now(1)", ""),
+    ],
+)
+def test_invalid_arguments(context, expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression, context)
+
+
+
+def test_clock_is_injected_into_the_context():
+    context = cel.Context(extensions=["datetime"], clock=FROZEN)
+    assert context.clock == FROZEN
+    assert cel.evaluate("now()", context) == FROZEN
+    assert cel.evaluate("date_diff(now(), timestamp('2024-05-01T00:00:00Z'), 'days')", context) == 5
+    context.set_now(None)
+    assert context.clock is None
+    assert cel.Context().clock is None
+
+
+def test_naive_clocks_are_converted_like_variables():
+    naive = datetime.datetime(2024, 5, 6, 7, 8, 9)
+    context = cel.Context({"naive": naive}, extensions=["datetime"], clock=naive)
+    assert cel.evaluate("now() == naive", context) is True
+
+
+def test_clock_must_be_a_datetime():
+    with pytest.raises(TypeError, match="the clock must be a datetime or None, got int"):
+        cel.Context(clock=0)
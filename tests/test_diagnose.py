@@ -0,0 +1,81 @@
+import cel
+
+
+def span(diagnostic):
+    start, end = diagnostic["range"]["start"], diagnostic["range"]["end"]
+    return (start["line"], start["character"], end["line"], end["character"])
+
+
+def test_valid_expression():
+    assert cel.diagnose("a + 1") == []
+    assert cel.diagnose("a + 1", {"a": 1}) == []
+
+
+def test_syntax_errors_are_lsp_diagnostics():
+    [diagnostic] = cel.diagnose("1 +")
+    assert diagnostic == {
+        "range": {"start": {"line": 0, "character": 3}, "end": {"line": 0, "character": 3}},
+        "severity": 1,
+        "code": "syntax-error",
+        "source": "cel",
+        "message": "expected an expression",
+        "relatedInformation": [],
+    }
+
+
+def test_unclosed_bracket_is_related_information():
+    [diagnostic] = cel.diagnose("size(\n  items", uri="file:///rule.cel")
+    assert diagnostic["message"] == "expected ')'"
+    assert span(diagnostic) == (1, 7, 1, 7)
+    assert diagnostic["relatedInformation"] == [
+        {
+            "location": {
+                "uri": "file:///rule.cel",
+                "range": {"start": {"line": 0, "character": 4}, "end": {"line": 0, "character": 5}},
+            },
+            "message": "the bracket left open",
+        }
+    ]
+
+
+def test_signed_literal_hint():
+    diagnostics = cel.diagnose("x-1")
+    assert [(d["code"], d["severity"]) for d in diagnostics] == [
+        ("syntax-error", 1),
+        ("signed-literal", 4),
+    ]
+    assert all(span(d) == (0, 1, 0, 3) for d in diagnostics)
+    assert "put a space after '-'" in diagnostics[1]["message"]
+
+
+def test_undeclared_references_need_an_env():
+    assert cel.diagnose("user.name == missing") == []
+    diagnostics = cel.diagnose("user.name == missing || missing", {"user": {"name": "Ada"}})
+    assert [(d["code"], d["message"], span(d)) for d in diagnostics] == [
+        ("undeclared-reference", "undeclared reference to 'missing'", (0, 13, 0, 20)),
+        ("undeclared-reference", "undeclared reference to 'missing'", (0, 24, 0, 31)),
+    ]
+
+
+def test_macro_variables_are_declared():
+    assert cel.diagnose("items.all(x, x > 0) && has(user.name)", {"items": [], "user": {}}) == []
+
+
+def test_undeclared_functions():
+    diagnostics = cel.diagnose("lookup(a) + a.frobnicate()", {"a": 1})
+    assert [(d["code"], d["message"]) for d in diagnostics] == [
+        ("undeclared-function", "undeclared function 'lookup'"),
+        ("undeclared-function", "undeclared function 'frobnicate'"),
+    ]
+    assert cel.diagnose("lookup(a) + size(a)", {"a": "x", "lookup": len}) == []
+
+
+def test_context_env():
+    context = cel.Context({"a": 1}, functions={"double_it": lambda x: x * 2})
+    assert cel.diagnose("double_it(a)", context) == []
+    assert [d["code"] for d in cel.diagnose("double_it(b)", context)] == ["undeclared-reference"]
+
+
+def test_positions_are_utf16_code_units():
+    [diagnostic] = cel.diagnose("'😀' + b", {})
+    assert span(diagnostic) == (0, 7, 0, 8)
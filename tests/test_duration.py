@@ -0,0 +1,83 @@
+import datetime
+
+import pytest
+
+import cel
+
+
+@pytest.mark.parametrize("text,expected", [
+    ("72h3m0.5s", datetime.timedelta(hours=72, minutes=3, seconds=0.5)),
+    ("1.5h", datetime.timedelta(hours=1, minutes=30)),
+    ("-1.5s", datetime.timedelta(seconds=-1.5)),
+    ("+10m", datetime.timedelta(minutes=10)),
+    ("1h1m1s1ms1us", datetime.timedelta(hours=1, minutes=1, seconds=1, milliseconds=1, microseconds=1)),
+    ("3µs", datetime.timedelta(microseconds=3)),
+    ("3μs", datetime.timedelta(microseconds=3)),
+    (".5s", datetime.timedelta(milliseconds=500)),
+    ("0", datetime.timedelta(0)),
+])
+def test_duration_parsing(text, expected):
+    assert cel.evaluate(f"duration('{text}')") == expected
+
+
+@pytest.mark.parametrize("text,message", [
+    ("", "invalid duration"),
+    ("1", "missing unit"),
+    ("1d", "unknown unit"),
+    ("1s garbage", "unknown unit"),
+    ("-1h-30m", "unknown unit"),
+    ("1e3s", "unknown unit"),
+    ("1000000000h", "out of range"),
+])
+def test_invalid_durations(text, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(f"duration('{text}')")
+
+
+@pytest.mark.parametrize("text,expected", [
+    ("72h3m0.5s", "72h3m0.5s"),
+    ("1h", "1h0m0s"),
+    ("90s", "1m30s"),
+    ("1.5ms", "1.5ms"),
+    ("100ns", "100ns"),
+    ("2.5us", "2.5µs"),
+    ("-1.5s", "-1.5s"),
+    ("-90m", "-1h30m0s"),
+    ("0s", "0s"),
+])
+def test_duration_to_string(text, expected):
+    assert cel.evaluate(f"string(duration('{text}'))") == expected
+
+
+def test_string_of_other_types_unchanged():
+    assert cel.evaluate("string(1) + string(2u) + string(1.5) + string('x') + string(b'y')") == "121.5xy"
+    assert cel.evaluate("string(timestamp('2024-01-01T00:00:00Z'))") == "2024-01-01T00:00:00+00:00"
+    with pytest.raises(ValueError, match="cannot convert list to string"):
+        cel.evaluate("string([1])")
+
+
+def test_duration_class():
+    d = cel.Duration("1h30m0.000000001s")
+    assert str(d) == "1h30m0.000000001s"
+    assert repr(d) == "Duration('1h30m0.000000001s')"
+    assert d.nanoseconds == 5400 * 10**9 + 1
+    assert d.total_seconds() == pytest.approx(5400.000000001)
+    assert d.to_timedelta() == datetime.timedelta(hours=1, minutes=30)
+    assert cel.Duration(datetime.timedelta(seconds=3)) == cel.Duration("3s")
+    assert cel.Duration("3s") == datetime.timedelta(seconds=3)
+    assert cel.Duration("1s") < cel.Duration("2s")
+    with pytest.raises(ValueError, match="unknown unit"):
+        cel.Duration("3 days")
+
+
+def test_duration_class_in_context():
+    d = cel.Duration("1ns")
+    assert cel.evaluate("string(d + d)", {'d': d}) == "2ns"
+    assert cel.evaluate("d == duration('1ns')", {'d': d})
+
+
+def test_duration_cel_output_type():
+    context = cel.Context(output_types={'duration': 'cel'})
+    result = cel.evaluate("duration('1.000000001s')", context)
+    assert isinstance(result, cel.Duration)
+    assert result.nanoseconds == 1_000_000_001
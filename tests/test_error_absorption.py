@@ -0,0 +1,71 @@
+import pytest
+
+import cel
+
+CONTEXT = {"request": {}, "flag": True}
+NO_ABSORPTION = cel.Options(error_absorption=False)
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("false && 1 / 0 == 1", False),
+    ("1 / 0 == 1 && false", False),
+    ("true || 1 / 0 == 1", True),
+    ("1 / 0 == 1 || true", True),
+    ("request.missing && false", False),
+    ("request.missing || flag", True),
+    ("undeclared == 1 && !flag", False),
+    ("(1 / 0 == 1 || true) && (false || 1 / 0 == 1 || true)", True),
+    ("[1, 2].all(x, 1 / 0 == x || x > 0)", True),
+])
+def test_errors_are_absorbed(expression, expected, evaluate_both_ways):
+    assert evaluate_both_ways(expression, CONTEXT) is expected
+    assert evaluate_both_ways(expression, CONTEXT, mode="strict") is expected
+
+
+@pytest.mark.parametrize("expression", [
+    "1 / 0 == 1 && true",
+    "true && 1 / 0 == 1",
+    "1 / 0 == 1 || false",
+    "false || 1 / 0 == 1",
+    "1 / 0 == 1 && 1 / 0 == 2",
+])
+def test_errors_that_decide_the_result_are_raised(expression):
+    with pytest.raises(ValueError, match="division by zero"):
+        cel.evaluate(expression)
+    with pytest.raises(ValueError, match="division by zero"):
+        cel.Program(expression, optimize=True).evaluate()
+
+
+def test_first_error_is_raised():
+    with pytest.raises(ValueError, match="No such key"):
+        cel.evaluate("request.missing || 1 / 0 == 1", CONTEXT)
+    with pytest.raises(ValueError, match="No such key"):
+        cel.Program("request.missing || 1 / 0 == 1", optimize=True).evaluate(CONTEXT)
+
+
+def test_python_mode_keeps_the_deciding_value(evaluate_both_ways):
+    assert evaluate_both_ways("request.missing || 'fallback'", CONTEXT) == "fallback"
+    assert evaluate_both_ways("request.missing && 0", CONTEXT) is False
+
+
+@pytest.mark.parametrize("expression", [
+    "1 / 0 == 1 && false",
+    "1 / 0 == 1 || true",
+    "request.missing || flag",
+])
+def test_absorption_can_be_turned_off(expression, evaluate_both_ways):
+    with pytest.raises(ValueError):
+        cel.evaluate(expression, CONTEXT, mode=NO_ABSORPTION)
+    with pytest.raises(ValueError):
+        cel.Program(expression, optimize=True).evaluate(CONTEXT, mode=NO_ABSORPTION)
+    # Short-circuiting still skips the right operand
+    assert evaluate_both_ways("false && 1 / 0 == 1", CONTEXT, mode=NO_ABSORPTION) is False
+    assert evaluate_both_ways("true || 1 / 0 == 1", CONTEXT, mode=NO_ABSORPTION) is True
+
+
+def test_absorption_is_on_in_both_modes():
+    assert cel.Options.python().error_absorption
+    assert cel.Options.strict().error_absorption
+    context = cel.Context(CONTEXT, mode=NO_ABSORPTION)
+    with pytest.raises(ValueError):
+        cel.evaluate("1 / 0 == 1 || true", context)
@@ -0,0 +1,127 @@
+import pytest
+
+import cel
+
+
+def test_execution_error_message_is_raised():
+    with pytest.raises(ValueError, match="Undeclared reference to 'missing'"):
+        cel.evaluate("missing + 1")
+
+
+def test_return_execution_error():
+    result = cel.evaluate("missing + 1", on_error="return")
+    assert isinstance(result, cel.EvalError)
+    assert result.kind == "execution"
+    assert result.expression == "missing + 1"
+    assert "missing" in result.message
+    assert result.position is None
+    assert repr(result).startswith("EvalError(kind='execution', message=\"Undeclared reference to 'missing'")
+    assert repr(result).endswith("expression='missing + 1')")
+
+
+def test_return_compile_error():
+    result = cel.evaluate("1 +", on_error="return")
+    assert isinstance(result, cel.EvalError)
+    assert result.kind == "compile"
+    assert result.position == 3
+
+
+def test_return_error_from_custom_function():
+    def explode(x):
+        raise RuntimeError("boom")
+
+    result = cel.evaluate("explode(1)", {'explode': explode}, on_error="return")
+    assert isinstance(result, cel.EvalError)
+    assert "boom" in str(result)
+
+
+def test_successful_result_unchanged_when_returning_errors():
+    assert cel.evaluate("1 + 1", on_error="return") == 2
+
+
+def test_batch_evaluation_collects_errors():
+    records = [{'a': 1}, {'a': 'x'}, {'a': 3}]
+    results = [cel.evaluate("a * 2", record, on_error="return") for record in records]
+    assert results[0] == 2
+    assert isinstance(results[1], cel.EvalError)
+    assert results[2] == 6
+
+
+def test_invalid_on_error_option():
+    with pytest.raises(ValueError, match="on_error"):
+        cel.evaluate("1", on_error="ignore")
+
+
+def test_function_value_result_raises_type_error():
+    with pytest.raises(TypeError, match="size"):
+        cel.evaluate("[1, 2].size")
+    with pytest.raises(TypeError, match="size"):
+        cel.evaluate("{'a': [[1].size]}")
+
+
+def test_function_value_passed_to_python_function():
+    with pytest.raises(ValueError, match="size"):
+        cel.evaluate("identity([1].size)", {'identity': lambda x: x})
+
+
+def failing_context():
+    def lookup(key):
+        raise ConnectionError(f"no connection for {key}")
+
+    return cel.Context({"flag": True}, functions={"lookup": lookup})
+
+
+def test_error_mapper_message():
+    context = failing_context()
+    context.set_error_mapper(lambda function, exception: f"{function} is down: {type(exception).__name__}")
+    with pytest.raises(ValueError, match="lookup is down: ConnectionError"):
+        cel.evaluate("lookup('a')", context)
+
+
+def test_error_mapper_default_message():
+    context = failing_context()
+    seen = []
+    context.set_error_mapper(lambda function, exception: seen.append((function, exception)))
+    with pytest.raises(ValueError, match="ConnectionError: no connection for a"):
+        cel.evaluate("lookup('a')", context)
+    [(function, exception)] = seen
+    assert function == "lookup"
+    assert isinstance(exception, ConnectionError)
+
+
+def test_error_mapper_fatal_errors_are_not_absorbed():
+    context = failing_context()
+    assert cel.evaluate("lookup('a') == 1 || flag", context) is True
+    assert cel.evaluate("coalesce(lookup('a'), 1)", context) == 1
+
+    context.set_error_mapper(lambda function, exception: {"absorbable": False})
+    for expression in ["lookup('a') == 1 || flag", "coalesce(lookup('a'), 1)", "[1].exists(x, lookup(x) == 1 || true)"]:
+        with pytest.raises(ValueError, match="no connection"):
+            cel.evaluate(expression, context)
+        with pytest.raises(ValueError, match="no connection"):
+            cel.Program(expression, optimize=True).evaluate(context)
+
+    context.set_error_mapper(lambda function, exception: {"message": "gone", "absorbable": True})
+    assert cel.evaluate("lookup('a') == 1 || flag", context) is True
+
+
+def test_error_mapper_fatal_errors_stay_within_their_evaluation():
+    context = failing_context()
+    context.set_error_mapper(lambda function, exception: {"absorbable": False})
+    cel.evaluate("lookup('a') || true", context, on_error="return")
+    assert cel.evaluate("flag", context) is True
+
+
+def test_error_mapper_removed_and_invalid():
+    context = failing_context()
+    context.set_error_mapper(lambda function, exception: "mapped")
+    context.set_error_mapper(None)
+    with pytest.raises(ValueError, match="ConnectionError"):
+        cel.evaluate("lookup('a')", context)
+
+    with pytest.raises(TypeError, match="isn't callable"):
+        context.set_error_mapper("mapped")
+
+    context.set_error_mapper(lambda function, exception: 3)
+    with pytest.raises(ValueError, match="error mapper failed .*expected None, a str or a dict, got int"):
+        cel.evaluate("lookup('a')", context)
@@ -0,0 +1,118 @@
+import concurrent.futures
+import threading
+
+import pytest
+
+import cel
+
+
+def test_submit_returns_a_future():
+    with cel.Evaluator(workers=2) as evaluator:
+        future = evaluator.submit(cel.Program("age > 21"), {"age": 30})
+        assert isinstance(future, concurrent.futures.Future)
+        assert future.result(timeout=5) is True
+
+
+def test_submit_source():
+    with cel.Evaluator() as evaluator:
+        assert evaluator.submit("1 + x", {"x": 2}).result(timeout=5) == 3
+
+
+def test_workers():
+    evaluator = cel.Evaluator(workers=3)
+    assert evaluator.workers == 3
+    assert repr(evaluator) == "Evaluator(workers=3)"
+    assert cel.Evaluator().workers >= 1
+    with pytest.raises(ValueError, match="at least 1"):
+        cel.Evaluator(workers=0)
+
+
+def test_many_evaluations():
+    program = cel.Program("items.filter(x, x % 3 == 0).size()", optimize=True)
+    with cel.Evaluator(workers=4) as evaluator:
+        futures = [evaluator.submit(program, {"items": list(range(n))}) for n in range(200)]
+        results = [future.result(timeout=5) for future in futures]
+    assert results == [len(range(0, n, 3)) for n in range(200)]
+
+
+def test_map():
+    with cel.Evaluator(workers=2) as evaluator:
+        assert evaluator.map("x * 2", [{"x": x} for x in range(10)]) == [x * 2 for x in range(10)]
+        assert evaluator.map(cel.Program("x"), iter([{"x": "a"}, {"x": "b"}])) == ["a", "b"]
+
+
+def test_evaluation_errors_are_raised_by_the_future():
+    with cel.Evaluator() as evaluator:
+        future = evaluator.submit("x.missing", {"x": {}})
+        with pytest.raises(ValueError, match="No such key"):
+            future.result(timeout=5)
+
+        result = evaluator.submit("x.missing", {"x": {}}, on_error="return").result(timeout=5)
+        assert isinstance(result, cel.EvalError)
+        assert result.kind == "execution"
+
+        with pytest.raises(ValueError, match="Failed to compile"):
+            evaluator.submit("1 +").result(timeout=5)
+
+
+def test_invalid_arguments_are_raised_straight_away():
+    with cel.Evaluator() as evaluator:
+        with pytest.raises(ValueError, match="on_error"):
+            evaluator.submit("1", on_error="ignore")
+        with pytest.raises(ValueError, match="evaluation_context"):
+            evaluator.submit("1", [1])
+
+
+def test_options():
+    context = cel.Context({"user": {}}, mode="strict")
+    with cel.Evaluator() as evaluator:
+        with pytest.raises(ValueError, match="can't be applied"):
+            evaluator.submit("1 + 2.0", context).result(timeout=5)
+        assert evaluator.submit("1 + 2.0", context, mode="python").result(timeout=5) == 3.0
+        assert evaluator.submit("user.name", context, safe_navigation=True).result(timeout=5) is None
+        unknown = evaluator.submit("x > 1", unknowns=["x"]).result(timeout=5)
+        assert unknown.attributes == ["x"]
+        assert isinstance(evaluator.submit("[1]", output="cel").result(timeout=5), cel.Value)
+
+
+def test_python_functions():
+    calls = []
+
+    def record(x):
+        calls.append(threading.get_ident())
+        return x + 1
+
+    with cel.Evaluator(workers=2) as evaluator:
+        assert evaluator.map("record(x)", [{"x": x, "record": record} for x in range(5)]) == [1, 2, 3, 4, 5]
+    assert threading.get_ident() not in calls
+
+
+def test_shutdown():
+    evaluator = cel.Evaluator(workers=1)
+    futures = [evaluator.submit("x", {"x": x}) for x in range(20)]
+    evaluator.shutdown()
+    assert all(future.done() for future in futures)
+    with pytest.raises(RuntimeError, match="after shutdown"):
+        evaluator.submit("1")
+
+
+def test_cancelled_futures_are_not_evaluated():
+    release = threading.Event()
+    calls = []
+
+    def block():
+        release.wait(timeout=5)
+        return 1
+
+    def record():
+        calls.append(1)
+        return 1
+
+    with cel.Evaluator(workers=1) as evaluator:
+        blocking = evaluator.submit("block()", {"block": block})
+        queued = evaluator.submit("record()", {"record": record})
+        assert queued.cancel()
+        release.set()
+        assert blocking.result(timeout=5) == 1
+    assert queued.cancelled()
+    assert calls == []
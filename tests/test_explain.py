@@ -0,0 +1,111 @@
+import pytest
+
+import cel
+
+
+POLICY = "user.age >= 18 && user.country in ['NZ', 'AU'] && !user.banned"
+
+
+def test_false_conjunction_is_explained_by_the_failing_clauses():
+    explanation = cel.explain(POLICY, {"user": {"age": 15, "country": "US", "banned": False}})
+    assert explanation.expression == POLICY
+    assert explanation.value is False
+    assert [clause.expression for clause in explanation.clauses] == [
+        "user.age >= 18",
+        'user.country in ["NZ", "AU"]',
+    ]
+    assert [clause.values for clause in explanation.clauses] == [{"user.age": 15}, {"user.country": "US"}]
+    assert all(clause.value is False and clause.clauses == [] for clause in explanation.clauses)
+
+
+def test_true_conjunction_is_explained_by_every_clause():
+    explanation = cel.explain(POLICY, {"user": {"age": 30, "country": "NZ", "banned": False}})
+    assert explanation.value is True
+    assert [clause.expression for clause in explanation.clauses] == [
+        "user.age >= 18",
+        'user.country in ["NZ", "AU"]',
+        "!user.banned",
+    ]
+    [negated] = explanation.clauses[2].clauses
+    assert negated.expression == "user.banned"
+    assert negated.value is False
+
+
+def test_disjunctions():
+    context = {"role": "viewer", "owner": "alice", "user": "alice"}
+    explanation = cel.explain("role == 'admin' || owner == user", context)
+    assert explanation.value is True
+    [clause] = explanation.clauses
+    assert clause.expression == "owner == user"
+    assert clause.values == {"owner": "alice", "user": "alice"}
+
+    context["user"] = "bob"
+    explanation = cel.explain("role == 'admin' || owner == user", context)
+    assert explanation.value is False
+    assert [clause.expression for clause in explanation.clauses] == ['role == "admin"', "owner == user"]
+
+
+def test_nested_clauses():
+    explanation = cel.explain("a && (b || c > 1)", {"a": True, "b": False, "c": 0})
+    assert [clause.expression for clause in explanation.clauses] == ["b || c > 1"]
+    assert [clause.expression for clause in explanation.clauses[0].clauses] == ["b", "c > 1"]
+
+
+def test_ternary_explains_the_condition_and_chosen_branch():
+    explanation = cel.explain("internal ? level > 1 : level > 5", {"internal": False, "level": 3})
+    assert explanation.value is False
+    assert [(clause.expression, clause.value) for clause in explanation.clauses] == [
+        ("internal", False),
+        ("level > 5", False),
+    ]
+
+
+def test_str():
+    explanation = cel.explain(POLICY, {"user": {"age": 15, "country": "NZ", "banned": True}})
+    assert str(explanation) == "\n".join(
+        [
+            f"{POLICY} is False",
+            "  user.age >= 18 is False (user.age = 15)",
+            "  !user.banned is False",
+            "    user.banned is True",
+        ]
+    )
+    assert repr(explanation.clauses[0]) == "Explanation(expression='user.age >= 18', value=False)"
+
+
+def test_macro_variables_and_missing_fields_are_not_shown():
+    context = {"items": [1, 5], "limit": 3, "user": {}}
+    explanation = cel.explain("items.all(i, i < limit) && has(user.email)", context)
+    assert [clause.values for clause in explanation.clauses] == [
+        {"items": [1, 5], "limit": 3},
+        {"user": {}},
+    ]
+
+
+def test_absorbed_errors_are_shown():
+    explanation = cel.explain("a.missing || b", {"a": {}, "b": True}, mode=cel.Options(error_absorption=True))
+    assert explanation.value is True
+    [clause] = explanation.clauses
+    assert clause.expression == "b"
+
+    explanation = cel.explain("a.missing && b", {"a": {}, "b": False}, mode=cel.Options(error_absorption=True))
+    assert explanation.value is False
+    assert [clause.expression for clause in explanation.clauses] == ["b"]
+
+
+def test_errors_are_raised():
+    with pytest.raises(TypeError, match="to evaluate to a bool, got int"):
+        cel.explain("1 + 1")
+    with pytest.raises(ValueError):
+        cel.explain("1 +")
+    with pytest.raises(ValueError):
+        cel.explain("a || b.missing", {"a": False, "b": {}})
+
+
+def test_context_object():
+    context = cel.Context(variables={"x": 2}, functions={"is_even": lambda n: n % 2 == 0})
+    explanation = cel.explain("is_even(x) && x > 3", context)
+    assert explanation.value is False
+    [clause] = explanation.clauses
+    assert clause.expression == "x > 3"
+    assert clause.values == {"x": 2}
@@ -0,0 +1,88 @@
+import contextlib
+import sys
+
+import pytest
+
+import cel
+
+EXTENSION = '''
+import dataclasses
+
+
+@dataclasses.dataclass
+class Point:
+    x: float
+    y: float
+
+    def norm(self):
+        return (self.x ** 2 + self.y ** 2) ** 0.5
+
+
+def distance(a, b):
+    return ((a["x"] - b["x"]) ** 2 + (a["y"] - b["y"]) ** 2) ** 0.5
+
+
+functions = {"distance": distance}
+types = {"{package}.Point": {"cls": Point, "methods": ["norm"]}}
+
+
+def make_text_extension():
+    class Text:
+        functions = {"shout": str.upper}
+
+    return Text
+
+
+class Empty:
+    pass
+'''
+
+
+@contextlib.contextmanager
+def installed(tmp_path, package, entry_points):
+    """Installs a distribution with the module `package` and the given entry
+    points in the `cel.extensions` group, for the duration of a test."""
+    (tmp_path / f"{package}.py").write_text(EXTENSION.replace("{package}", package))
+    dist_info = tmp_path / f"{package}-1.0.dist-info"
+    dist_info.mkdir()
+    (dist_info / "METADATA").write_text(f"Metadata-Version: 2.1\nName: {package}\nVersion: 1.0\n")
+    lines = "".join(f"{name} = {package}{target}\n" for name, target in entry_points.items())
+    (dist_info / "entry_points.txt").write_text(f"[cel.extensions]\n{lines}")
+    sys.path.insert(0, str(tmp_path))
+    try:
+        yield
+    finally:
+        sys.path.remove(str(tmp_path))
+        sys.modules.pop(package, None)
+
+
+def test_extension_functions_and_types(tmp_path):
+    with installed(tmp_path, "geo_functions", {"acme.geo": ""}):
+        context = cel.Context({"origin": {"x": 0, "y": 0}}, extensions=["acme.geo"])
+        assert context.extensions == ["acme.geo"]
+        assert cel.evaluate("distance(origin, {'x': 3, 'y': 4})", context) == 5.0
+        assert cel.evaluate("geo_functions.Point{x: 6.0, y: 8.0}.norm()", context) == 10.0
+        assert context.functions_info()["distance"]["signature"] == "distance(a, b)"
+
+
+def test_extensions_made_by_a_callable(tmp_path):
+    with installed(tmp_path, "text_functions", {"acme.text": ":make_text_extension"}):
+        context = cel.Context(extensions=["acme.text"])
+        assert cel.evaluate("shout('hi')", context) == "HI"
+
+
+def test_context_functions_replace_extension_functions(tmp_path):
+    with installed(tmp_path, "geo_override", {"acme.geo": ""}):
+        context = cel.Context(functions={"distance": lambda a, b: -1}, extensions=["acme.geo"])
+        assert cel.evaluate("distance({}, {})", context) == -1
+
+
+def test_missing_extension():
+    with pytest.raises(ValueError, match="no CEL extension 'acme.missing' is installed"):
+        cel.Context(extensions=["acme.missing"])
+
+
+def test_extension_without_functions_or_types(tmp_path):
+    with installed(tmp_path, "empty_extension", {"acme.empty": ":Empty"}):
+        with pytest.raises(TypeError, match="CEL extension 'acme.empty' has neither functions nor types"):
+            cel.Context(extensions=["acme.empty"])
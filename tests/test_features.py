@@ -0,0 +1,16 @@
+import cel
+
+
+def test_default_features():
+    assert set(cel.FEATURES) >= {"threads", "local-timezone", "extensions", "conformance", "testing"}
+    assert all(isinstance(feature, str) for feature in cel.FEATURES)
+
+
+def test_feature_submodules():
+    import cel.conformance
+    import cel.fuzz
+    import cel.testing
+
+    assert callable(cel.conformance.compare)
+    assert callable(cel.fuzz.compare)
+    assert callable(cel.testing.assert_evaluates)
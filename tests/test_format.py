@@ -0,0 +1,118 @@
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+import cel
+
+NBSP = "\u00a0"
+NNBSP = "\u202f"
+
+
+@pytest.fixture
+def fmt():
+    return cel.Context(
+        {"at": datetime(2024, 5, 6, 14, 8, 9, 123456, tzinfo=timezone.utc)},
+        extensions=["format"],
+    )
+
+
+def test_format_is_opt_in():
+    with pytest.raises(ValueError, match="Undeclared reference to 'number'"):
+        cel.evaluate("format.number(1, 'en')")
+
+
+@pytest.mark.parametrize(
+    "expression,expected",
+    [
+        ("format.number(1234567, 'en-US')", "1,234,567"),
+        ("format.number(1234567.891, 'de-DE')", "1.234.567,891"),
+        ("format.number(1234567.5, 'fr')", f"1{NNBSP}234{NNBSP}567,5"),
+        ("format.number(1234, 'es')", "1234"),
+        ("format.number(12345, 'es-ES')", "12.345"),
+        ("format.number(-1234.0, 'en')", "-1,234"),
+        ("format.number(0.12345, 'en')", "0.123"),
+        ("format.number(-0.0001, 'en')", "0"),
+        ("format.number(18446744073709551615u, 'en')", "18,446,744,073,709,551,615"),
+        ("format.number(999, 'pt_BR')", "999"),
+        ("format.number(1234.5)", "1,234.5"),
+    ],
+)
+def test_number(fmt, expression, expected):
+    assert cel.evaluate(expression, fmt) == expected
+
+
+@pytest.mark.parametrize(
+    "expression,expected",
+    [
+        ("format.currency(1234.5, 'USD', 'en-US')", "$1,234.50"),
+        ("format.currency(-1234.5, 'USD', 'en')", "-$1,234.50"),
+        ("format.currency(1234.5, 'EUR', 'de')", f"1.234,50{NBSP}€"),
+        ("format.currency(1234.5, 'EUR', 'fr-FR')", f"1{NNBSP}234,50{NBSP}€"),
+        ("format.currency(-1234.5, 'EUR', 'nl')", f"€{NBSP}-1.234,50"),
+        ("format.currency(1234.5, 'BRL', 'pt-BR')", f"R${NBSP}1.234,50"),
+        ("format.currency(1234.5, 'JPY', 'ja')", "¥1,234"),
+        ("format.currency(1235, 'jpy', 'en')", "¥1,235"),
+        ("format.currency(12.3, 'CHF', 'de-CH')", f"12,30{NBSP}CHF"),
+        ("format.currency(-0.001, 'USD', 'en')", "$0.00"),
+        ("format.currency(5, 'GBP')", "£5.00"),
+    ],
+)
+def test_currency(fmt, expression, expected):
+    assert cel.evaluate(expression, fmt) == expected
+
+
+@pytest.mark.parametrize(
+    "pattern,locale,expected",
+    [
+        ("yyyy-MM-dd HH:mm:ss", "en", "2024-05-06 14:08:09"),
+        ("EEEE, MMMM d, y 'at' h:mm a", "en-US", "Monday, May 6, 2024 at 2:08 PM"),
+        ("EEE d MMM yy", "en-GB", "Mon 6 May 24"),
+        ("EEEE d MMMM yyyy", "fr", "lundi 6 mai 2024"),
+        ("EEEE, d. MMMM yyyy", "de", "Montag, 6. Mai 2024"),
+        ("d 'de' MMMM 'de' y", "es", "6 de mayo de 2024"),
+        ("y年M月d日 EEEE", "ja", "2024年5月6日 月曜日"),
+        ("HH:mm:ss.SSS", "en", "14:08:09.123"),
+        ("'o''clock' hh''", "en", "o'clock 02'"),
+    ],
+)
+def test_datetime(fmt, pattern, locale, expected):
+    fmt.update({"pattern": pattern, "locale": locale})
+    assert cel.evaluate("format.datetime(at, pattern, locale)", fmt) == expected
+
+
+def test_datetime_is_in_the_timestamps_offset(fmt):
+    fmt.update({"local": datetime(2024, 5, 6, 23, 30, tzinfo=timezone(timedelta(hours=12)))})
+    assert cel.evaluate("format.datetime(local, 'd MMM HH:mm')", fmt) == "6 May 23:30"
+
+
+@pytest.mark.parametrize(
+    "expression,message",
+    [
+        ("format.number('1', 'en')", "expected a number, got string"),
+        ("format.number(1, 'xx-XX')", "unsupported locale 'xx-XX', expected one of the languages en, de"),
+        ("format.number(1, 2)", "expected a locale, got int"),
+        ("format.currency(1, 'dollars', 'en')", "expected a currency code such as 'USD', got 'dollars'"),
+        ("format.datetime(at, 'yyyy Q', 'en')", "unsupported pattern letter 'Q'"),
+        ("format.datetime('2024', 'yyyy', 'en')", "expected a timestamp, got string"),
+        ("format.number()", "expected 2, got 0"),
+    ],
+)
+def test_errors(fmt, expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression, fmt)
+
+
+def test_message_template(fmt):
+    fmt.update({"order": {"total": 99.5, "currency": "EUR"}, "locale": "de-AT"})
+    message = cel.evaluate(
+        "'Bestellung vom ' + format.datetime(at, 'd. MMMM', locale) + ': '"
+        " + format.currency(order.total, order.currency, locale)",
+        fmt,
+    )
+    assert message == f"Bestellung vom 6. Mai: 99,50{NBSP}€"
+
+
+def test_format_functions_are_described(fmt):
+    info = fmt.functions_info()
+    assert info["format.currency"]["builtin"] is True
+    assert cel.diagnose("format.number(1, 'en') == '1'", fmt) == []
@@ -0,0 +1,134 @@
+import pytest
+
+import cel
+
+
+class Recorder:
+    """A Python function that records the arguments it is called with."""
+
+    def __init__(self, result=None):
+        self.calls = []
+        self.result = result
+
+    def __call__(self, *args):
+        self.calls.append(args)
+        return self.result if self.result is not None else len(self.calls)
+
+
+def evaluate_recording_calls(expression, context, recorder):
+    """Evaluates with the interpreter and with a compiled plan, which must call
+    the recorder the same way."""
+    recorder.calls.clear()
+    result = cel.evaluate(expression, context)
+    interpreted = list(recorder.calls)
+    recorder.calls.clear()
+    assert cel.Program(expression, optimize=True).evaluate(context) == result
+    assert recorder.calls == interpreted
+    return result, interpreted
+
+
+def make_context(result=None, **kwargs):
+    recorder = Recorder(result)
+    context = cel.Context({"ids": [1, 2, 1, 2]}, functions={"f": recorder}, **kwargs)
+    return context, recorder
+
+
+def test_calls_are_made_in_order():
+    calls = []
+
+    def record(name):
+        calls.append(name)
+        return name
+
+    context = cel.Context(functions={"f": record})
+    cel.evaluate("f('a') + f(f('b') + f('c')) + [f('d')][0]", context)
+    assert calls == ["a", "b", "c", "bc", "d"]
+
+
+def test_calls_are_not_memoized_by_default():
+    _, calls = evaluate_recording_calls("ids.map(x, f(x))", *make_context())
+    assert calls == [(1,), (2,), (1,), (2,)]
+
+
+@pytest.mark.parametrize("expression, result, calls", [
+    ("f(1) + f(1) + f(2)", 4, [(1,), (2,)]),
+    ("ids.map(x, f(x))", [1, 2, 1, 2], [(1,), (2,)]),
+    ("[f(1, 'a'), f(1, 'b'), f(1, 'a')]", [1, 2, 1], [(1, "a"), (1, "b")]),
+    ("f([1, {'a': 2}]) == f([1, {'a': 2}])", True, [([1, {"a": 2}],)]),
+    ("f() + f()", 2, [()]),
+])
+def test_memoized_calls(expression, result, calls):
+    assert evaluate_recording_calls(expression, *make_context(memoize=True)) == (result, calls)
+
+
+def test_arguments_of_different_types_are_different_calls():
+    _, calls = evaluate_recording_calls("[f(1), f(1u), f(1.0)]", *make_context(memoize=True))
+    assert calls == [(1,), (1,), (1.0,)]
+    assert [type(arg) for (arg,) in calls] == [int, int, float]
+
+
+def test_functions_are_memoized_separately():
+    recorder = Recorder()
+    context = cel.Context(functions={"f": recorder, "g": recorder}, memoize=True)
+    assert cel.evaluate("[f(1), g(1), f(1)]", context) == [1, 2, 1]
+
+
+def test_results_are_not_kept_between_evaluations():
+    context, _ = make_context(memoize=True)
+    assert cel.evaluate("f(1)", context) == 1
+    assert cel.evaluate("f(1)", context) == 2
+
+
+def test_failed_calls_are_made_again():
+    calls = []
+
+    def flaky(x):
+        calls.append(x)
+        if len(calls) == 1:
+            raise RuntimeError("unavailable")
+        return x
+
+    context = cel.Context(functions={"flaky": flaky}, memoize=True)
+    assert cel.evaluate("flaky(1) == 1 || flaky(1) == 1", context) is True
+    assert calls == [1, 1]
+
+
+def test_memoize_can_be_changed():
+    context, recorder = make_context(memoize=True)
+    assert context.memoize is True
+    context.memoize = False
+    _, calls = evaluate_recording_calls("f(1) + f(1)", context, recorder)
+    assert calls == [(1,), (1,)]
+
+
+def test_nested_evaluations_memoize_separately():
+    recorder = Recorder()
+
+    def nested(x):
+        return cel.evaluate("f(x) + f(x)", cel.Context({"x": x}, functions={"f": recorder}))
+
+    context = cel.Context(functions={"f": recorder, "nested": nested}, memoize=True)
+    # The outer call to f(1) is remembered across the nested evaluation, which
+    # doesn't memoize and calls f twice itself
+    assert cel.evaluate("f(1) + nested(1) + f(1)", context) == 1 + (2 + 3) + 1
+    assert recorder.calls == [(1,), (1,), (1,)]
+
+
+@pytest.mark.parametrize("expression", [
+    "false && f(1) == 1",
+    "true || f(1) == 1",
+    "true ? 1 : f(1)",
+    "[1, 2].all(x, x > 1 && f(x) > 0)",
+    "[1, 2].exists(x, x == 1 || f(x) > 0)",
+])
+def test_functions_are_only_called_when_needed(expression):
+    _, calls = evaluate_recording_calls(expression, *make_context())
+    assert calls == []
+
+
+def test_macros_stop_calling_once_decided():
+    context, recorder = make_context(result=True)
+    _, calls = evaluate_recording_calls("ids.exists(x, f(x))", context, recorder)
+    assert calls == [(1,)]
+    _, calls = evaluate_recording_calls("ids.exists_one(x, f(x))", context, recorder)
+    assert calls == [(1,), (2,)]
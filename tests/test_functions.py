@@ -1,3 +1,10 @@
+import functools
+import math
+import operator
+import threading
+import time
+import warnings
+
 import pytest
 
 import cel
@@ -18,3 +25,264 @@ def test_readme_custom_function_example():
 
 
 
+
+
+class Scaler:
+    def __init__(self, factor):
+        self.factor = factor
+
+    def scale(self, x):
+        return x * self.factor
+
+    def __call__(self, x):
+        return x * self.factor
+
+
+CALLABLES = {
+    "partial": (functools.partial(operator.add, 5), "partial(1)", 6),
+    "method": (Scaler(3).scale, "method(2)", 6),
+    "instance": (Scaler(4), "instance(2)", 8),
+    "builtin": (len, "builtin([1, 2, 3])", 3),
+    "c_function": (math.sqrt, "c_function(16.0)", 4.0),
+    "cls": (str, "cls(12)", "12"),
+}
+
+
+@pytest.mark.parametrize("name", list(CALLABLES))
+def test_callables_that_are_not_plain_functions(name):
+    function, expression, expected = CALLABLES[name]
+    assert cel.evaluate(expression, cel.Context(functions={name: function})) == expected
+
+    context = cel.Context()
+    context.add_function(name, function)
+    assert cel.evaluate(expression, context) == expected
+
+    context = cel.Context()
+    context.update(functions={name: function})
+    assert cel.evaluate(expression, context) == expected
+
+
+@pytest.mark.parametrize("name", ["partial", "method", "builtin", "c_function"])
+def test_plain_functions_in_variables_are_added_without_a_warning(name):
+    function, expression, expected = CALLABLES[name]
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        assert cel.evaluate(expression, {name: function}) == expected
+        context = cel.Context()
+        context.update({name: function})
+        assert cel.evaluate(expression, context) == expected
+
+
+@pytest.mark.parametrize("name", ["instance", "cls"])
+def test_other_callables_in_variables_warn(name):
+    function, expression, expected = CALLABLES[name]
+    with pytest.warns(UserWarning, match=f"'{name}' is a callable"):
+        assert cel.evaluate(expression, {name: function}) == expected
+    context = cel.Context()
+    with pytest.warns(UserWarning, match="force_variable=True"):
+        context.update({name: function})
+    assert cel.evaluate(expression, context) == expected
+
+
+class CallableMap(dict):
+    def __call__(self):
+        return "called"
+
+
+def test_force_variable_adds_callable_objects_as_data():
+    context = cel.Context()
+    with pytest.warns(UserWarning, match="'settings' is a callable CallableMap"):
+        context.update({"settings": CallableMap(debug=True)})
+    assert cel.evaluate("settings()", context) == "called"
+
+    context = cel.Context()
+    context.update({"settings": CallableMap(debug=True), "level": 2}, force_variable=True)
+    assert cel.evaluate("settings.debug && level == 2", context) is True
+
+
+def test_update_with_functions_and_variables():
+    context = cel.Context()
+    context.update({"x": 2}, functions={"double": lambda v: v * 2})
+    assert cel.evaluate("double(x)", context) == 4
+    with pytest.raises(TypeError, match="function 'x' isn't callable, got int"):
+        context.update(functions={"x": 1})
+    with pytest.raises(TypeError, match="isn't callable"):
+        cel.Context(functions={"x": 1})
+
+
+def test_force_variable_still_converts():
+    with pytest.raises(ValueError, match="Failed to convert variable 'f'"):
+        cel.Context().update({"f": lambda: 1}, force_variable=True)
+
+
+def test_map_get():
+    data = {'m': {'a': 1, 'b': None}}
+    assert cel.evaluate("m.get('a')", data) == 1
+    assert cel.evaluate("m.get('missing')", data) is None
+    assert cel.evaluate("m.get('missing', 42)", data) == 42
+    assert cel.evaluate("m.get('b', 42)", data) is None
+    assert cel.evaluate("get(m, 'missing', 'x')", data) == 'x'
+
+
+def test_map_get_default_only_evaluated_when_missing():
+    def fallback():
+        raise RuntimeError("should not be called")
+
+    assert cel.evaluate("m.get('a', fallback())", {'m': {'a': 1}, 'fallback': fallback}) == 1
+
+
+def test_list_get():
+    data = {'items': [10, 20, 30]}
+    assert cel.evaluate("items.get(1)", data) == 20
+    assert cel.evaluate("items.get(5)", data) is None
+    assert cel.evaluate("items.get(5, -1)", data) == -1
+    assert cel.evaluate("items.get(-1, -1)", data) == -1
+
+
+def test_get_on_unsupported_type_raises():
+    with pytest.raises(ValueError):
+        cel.evaluate("'abc'.get(0)")
+
+
+def test_coalesce():
+    data = {'user': {'name': 'Ada', 'nickname': None}}
+    assert cel.evaluate("coalesce(user.nickname, user.name)", data) == 'Ada'
+    assert cel.evaluate("coalesce(user.email, user.nickname, 'anonymous')", data) == 'anonymous'
+    assert cel.evaluate("coalesce(missing, 1 / 0, user.name)", data) == 'Ada'
+    assert cel.evaluate("coalesce(null, user.email)", data) is None
+    assert cel.evaluate("coalesce()") is None
+    assert cel.evaluate("coalesce(0, 1)") == 0
+    assert cel.evaluate("user.nickname.coalesce('x')", data) == 'x'
+
+
+def test_coalesce_only_evaluates_until_a_value():
+    def fallback():
+        raise RuntimeError("should not be called")
+
+    assert cel.evaluate("coalesce(a, fallback())", {'a': 1, 'fallback': fallback}) == 1
+
+
+def test_coalesce_keeps_unknowns():
+    result = cel.evaluate("coalesce(request.user, 'x')", {}, unknowns=["request.user"])
+    assert isinstance(result, cel.Unknown)
+    assert cel.evaluate("coalesce('x', request.user)", {}, unknowns=["request.user"]) == 'x'
+
+
+def test_function_timeout():
+    release = threading.Event()
+
+    def stalled(x):
+        release.wait(5)
+        return x
+
+    context = cel.Context({"flag": True})
+    context.add_function("stalled", stalled, timeout=0.05)
+    context.add_function("quick", lambda x: x + 1, timeout=1)
+    try:
+        start = time.monotonic()
+        with pytest.raises(ValueError, match="TimeoutError: function 'stalled' didn't return within 0.05 seconds"):
+            cel.evaluate("stalled(1)", context)
+        assert time.monotonic() - start < 2
+        assert cel.evaluate("stalled(1) == 1 || flag", context) is True
+        assert cel.evaluate("quick(1)", context) == 2
+    finally:
+        release.set()
+
+
+def test_function_timeout_errors_are_mapped():
+    context = cel.Context()
+    context.add_function("slow", lambda: time.sleep(1), timeout=0.01)
+    context.set_error_mapper(lambda function, exception: f"{function}: {type(exception).__name__}")
+    with pytest.raises(ValueError, match="slow: TimeoutError"):
+        cel.evaluate("slow()", context)
+
+
+def test_function_timeout_is_replaced_and_removed():
+    context = cel.Context()
+    context.add_function("f", lambda: time.sleep(0.2) or 1, timeout=0.01)
+    context.update(functions={"f": lambda: time.sleep(0.05) or 2})
+    assert cel.evaluate("f()", context) == 2
+
+    context.add_function("g", lambda: 1, timeout=0.5)
+    context.remove_function("g")
+    context.add_function("g", lambda: time.sleep(0.05) or 3)
+    assert cel.evaluate("g()", context) == 3
+
+
+@pytest.mark.parametrize("timeout", [0, -1, float("nan"), float("inf")])
+def test_invalid_function_timeouts(timeout):
+    with pytest.raises(ValueError, match="timeout must be a positive number of seconds"):
+        cel.Context().add_function("f", lambda: 1, timeout=timeout)
+
+
+@pytest.fixture
+def batch_context():
+    calls = []
+
+    def scale(values, factors):
+        calls.append((list(values), list(factors)))
+        return [value * factor for value, factor in zip(values, factors)]
+
+    context = cel.Context({"items": [1, 2, 3], "factor": 10})
+    context.add_function("scale", scale, batch=True)
+    return context, calls
+
+
+@pytest.mark.parametrize("optimize", [False, True])
+def test_batch_functions_are_called_once_per_comprehension(batch_context, optimize):
+    context, calls = batch_context
+    program = cel.Program("items.map(x, scale(x, factor) + 1)", optimize=optimize)
+    assert program.evaluate(context) == [11, 21, 31]
+    assert calls == [([1, 2, 3], [10, 10, 10])]
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("items.filter(x, scale(x, 2) > 3)", [2, 3]),
+    ("items.all(x, scale(x, 1) > 0)", True),
+    ("items.exists(x, scale(x, 1) == 2)", True),
+    ("items.existsOne(x, scale(x, 1) == 2)", True),
+    ("items.map(x, x > 1, scale(x, 2))", [4, 6]),
+])
+def test_batch_functions_in_every_comprehension(batch_context, expression, expected):
+    context, calls = batch_context
+    assert cel.evaluate(expression, context) == expected
+    assert len(calls) == 1
+
+
+def test_batch_functions_outside_comprehensions(batch_context):
+    context, calls = batch_context
+    assert cel.evaluate("scale(2, 3)", context) == 6
+    assert calls == [([2], [3])]
+
+
+def test_batch_functions_in_nested_comprehensions(batch_context):
+    context, calls = batch_context
+    assert cel.evaluate("[1, 2].map(x, [1, 2].map(y, scale(x, y)))", context) == [[1, 2], [2, 4]]
+    assert calls == [([1, 1], [1, 2]), ([2, 2], [1, 2])]
+
+
+def test_batch_functions_fall_back_to_calls_per_item(batch_context):
+    context, calls = batch_context
+    context.update({"records": [{"v": 1}, {}, {"v": 3}]})
+    assert cel.evaluate("records.map(r, has(r.v) ? scale(r.v, 2) : 0)", context) == [2, 0, 6]
+    assert calls == [([1, 3], [2, 2])]
+
+    def fails_on_two(values):
+        if 2 in values:
+            raise ValueError("two")
+        return values
+
+    context.add_function("check", fails_on_two, batch=True)
+    assert cel.evaluate("items.map(x, check(x) == x || x == 2)", context) == [True, True, True]
+    with pytest.raises(ValueError, match="two"):
+        cel.evaluate("items.map(x, check(x))", context)
+
+
+def test_batch_functions_must_return_a_result_for_each_call():
+    context = cel.Context({"items": [1, 2]})
+    context.add_function("short", lambda values: [], batch=True)
+    context.add_function("scalar", lambda values: 1, batch=True)
+    with pytest.raises(ValueError, match="returned 0 results for 1 calls"):
+        cel.evaluate("items.map(x, short(x))", context)
+    with pytest.raises(ValueError, match="expected a list of results, got int"):
+        cel.evaluate("scalar(1)", context)
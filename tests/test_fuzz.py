@@ -0,0 +1,106 @@
+import datetime
+import json
+import threading
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+import pytest
+
+import cel
+import cel.conformance
+import cel.fuzz
+
+
+class ReferenceService(BaseHTTPRequestHandler):
+    """A conformance service whose every evaluation returns `result`"""
+
+    result = {"value": {"int64Value": "1"}}
+
+    def do_POST(self):
+        self.rfile.read(int(self.headers["Content-Length"]))
+        if self.path.endswith("/Parse"):
+            response = {"parsedExpr": {"expr": {"id": "1"}}}
+        else:
+            response = {"result": self.result}
+        body = json.dumps(response).encode()
+        self.send_response(200)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def reference():
+    server = ThreadingHTTPServer(("127.0.0.1", 0), ReferenceService)
+    thread = threading.Thread(target=server.serve_forever, daemon=True)
+    thread.start()
+    yield cel.conformance.RemoteService(f"http://127.0.0.1:{server.server_port}")
+    server.shutdown()
+    server.server_close()
+
+
+@pytest.mark.parametrize("expression", ["1 + 1", "1 == 1.0", "x.size() > 0", "true ? 1 : 2", "1 / 0 == 1 || true"])
+def test_expressions_that_agree(expression):
+    assert cel.fuzz.compare(expression, {"x": [1]}) is None
+
+
+@pytest.mark.parametrize(
+    "expression, kind, option",
+    [
+        ("1 + 1.5", "compile", "numeric_promotion"),
+        ("{'a': 1, 'a': 2}", "compile", "duplicate_map_keys"),
+        ("name || 'anonymous'", "compile", "truthiness"),
+        ("timestamp('2024-01-02')", "error", "lenient_timestamps"),
+        ("items.map(x, x * 2.0)", "error", "numeric_promotion"),
+    ],
+)
+def test_mode_sensitive_expressions(expression, kind, option):
+    divergence = cel.fuzz.compare(expression, {"name": "", "items": [1, 2]})
+    assert divergence.expression == expression
+    assert divergence.kind == kind
+    assert divergence.options == [option]
+    assert set(divergence.results) == {"python", "strict"}
+    assert isinstance(divergence.results["strict"], cel.EvalError)
+
+
+def test_results():
+    divergence = cel.fuzz.compare("timestamp('2024-01-02')")
+    assert divergence.results["python"] == datetime.datetime(2024, 1, 2, tzinfo=datetime.timezone.utc)
+    assert "RFC 3339" in divergence.results["strict"].message
+    assert repr(divergence) == (
+        "Divergence(expression=\"timestamp('2024-01-02')\", kind='error', options=['lenient_timestamps'])"
+    )
+
+
+def test_mode_of_context_is_ignored():
+    context = cel.Context({"x": 1}, mode="strict")
+    divergence = cel.fuzz.compare("x + 1.5", context)
+    assert divergence.results["python"] == 2.5
+
+
+def test_reference(reference):
+    assert cel.fuzz.compare("x", {"x": 1}, reference=reference) is None
+
+    divergence = cel.fuzz.compare("x", {"x": 2}, reference=reference)
+    assert divergence.kind == "value"
+    assert divergence.results == {"python": 2, "strict": 2, "reference": 1}
+    assert divergence.options == []
+
+
+def test_reference_errors(reference):
+    ReferenceService.result = {"error": {"errors": [{"message": "no such overload"}]}}
+    try:
+        divergence = cel.fuzz.compare("1 + 1.5", reference=reference)
+    finally:
+        ReferenceService.result = {"value": {"int64Value": "1"}}
+    # The strict mode rejecting the expression outranks the reference failing to evaluate it
+    assert divergence.kind == "compile"
+    assert divergence.results["reference"].message == "no such overload"
+
+
+def test_reference_needs_a_dict(reference):
+    with pytest.raises(TypeError, match="only sent variables"):
+        cel.fuzz.compare("x", cel.Context({"x": 1}), reference=reference)
@@ -0,0 +1,82 @@
+import pytest
+
+import cel
+
+AUCKLAND = {"lat": -36.8485, "lng": 174.7633}
+WELLINGTON = {"lat": -41.2865, "lng": 174.7762}
+SQUARE = [
+    {"lat": 0, "lng": 0},
+    {"lat": 0, "lng": 2},
+    {"lat": 2, "lng": 2},
+    {"lat": 2, "lng": 0},
+]
+
+
+@pytest.fixture
+def geo():
+    return cel.Context(
+        {"auckland": AUCKLAND, "wellington": WELLINGTON, "square": SQUARE},
+        extensions=["geo"],
+    )
+
+
+def test_geo_is_opt_in():
+    with pytest.raises(ValueError, match="Undeclared reference to 'point'"):
+        cel.evaluate("geo.point(1, 2)")
+
+
+def test_point(geo):
+    assert cel.evaluate("geo.point(-36.8485, 174.7633)", geo) == AUCKLAND
+    assert cel.evaluate("geo.point(1, 2)", geo) == {"lat": 1.0, "lng": 2.0}
+
+
+@pytest.mark.parametrize(
+    "expression,message",
+    [
+        ("geo.point(91, 0)", "latitude 91 is not between -90 and 90"),
+        ("geo.point(0, -180.5)", "longitude -180.5 is not between -180 and 180"),
+        ("geo.point('1', 2)", "expected a number of degrees, got string"),
+        ("geo.distance(auckland, 1)", "expected a point, a map of lat and lng, got int"),
+        ("geo.distance(auckland, {'lat': 1})", "point has no lng"),
+        ("geo.inPolygon(auckland, auckland)", "expected a polygon, a list of points, got map"),
+        ("geo.inPolygon(auckland, [auckland, wellington])", "needs at least 3 vertices, got 2"),
+    ],
+)
+def test_invalid_arguments(geo, expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression, geo)
+
+
+def test_distance(geo):
+    distance = cel.evaluate("geo.distance(auckland, wellington)", geo)
+    assert distance == pytest.approx(493_600, rel=0.005)
+    assert cel.evaluate("geo.distance(auckland, auckland)", geo) == 0.0
+    assert cel.evaluate("geo.distance(geo.point(0, 0), geo.point(0, 180))", geo) == pytest.approx(
+        20_015_115, rel=0.001
+    )
+
+
+@pytest.mark.parametrize(
+    "point,inside",
+    [
+        ("geo.point(1, 1)", True),
+        ("geo.point(1.9, 0.1)", True),
+        ("geo.point(3, 1)", False),
+        ("geo.point(1, -1)", False),
+        ("auckland", False),
+    ],
+)
+def test_in_polygon(geo, point, inside):
+    assert cel.evaluate(f"geo.inPolygon({point}, square)", geo) is inside
+
+
+def test_geofencing_in_a_comprehension(geo):
+    geo.update({"devices": [{"id": 1, "at": {"lat": 1, "lng": 1}}, {"id": 2, "at": AUCKLAND}]})
+    expression = "devices.filter(d, geo.inPolygon(d.at, square)).map(d, d.id)"
+    assert cel.evaluate(expression, geo) == [1]
+
+
+def test_geo_functions_are_described(geo):
+    info = geo.functions_info()
+    assert info["geo.distance"]["builtin"] is True
+    assert cel.diagnose("geo.distance(auckland, wellington) < 1000.0", geo) == []
@@ -0,0 +1,80 @@
+import contextlib
+
+import pytest
+
+import cel
+import cel.sandbox
+
+
+@contextlib.contextmanager
+def global_functions(**functions):
+    """Registers global functions for the duration of a test."""
+    for name, function in functions.items():
+        cel.register_global_function(name, function)
+    try:
+        yield
+    finally:
+        for name in functions:
+            cel.unregister_global_function(name)
+
+
+def slug(text):
+    return text.lower().replace(" ", "-")
+
+
+def test_global_functions_are_available_everywhere():
+    with global_functions(slug=slug):
+        assert cel.evaluate("slug('Hello World')") == "hello-world"
+        assert cel.evaluate("slug(title)", {"title": "A B"}) == "a-b"
+        assert cel.evaluate("slug(title)", cel.Context({"title": "C D"})) == "c-d"
+        assert cel.Program("slug('E F')", optimize=True).evaluate() == "e-f"
+        assert cel.evaluate_predicate("slug('G') == 'g'") is True
+        with cel.Evaluator(workers=1) as evaluator:
+            assert evaluator.submit("slug('H I')").result() == "h-i"
+
+
+def test_override_order():
+    context = cel.Context({"title": "A B"}, functions={"slug": lambda text: "context"})
+    with global_functions(slug=slug, size=lambda value: -1):
+        assert cel.evaluate("slug(title)", context) == "context"
+        assert cel.evaluate("slug(title)", {"title": "A B", "slug": lambda text: "dict"}) == "dict"
+        assert cel.evaluate("slug(title)", {"title": "A B"}) == "a-b"
+        # Global functions replace builtins
+        assert cel.evaluate("size([1, 2])") == -1
+    assert cel.evaluate("size([1, 2])") == 2
+
+
+def test_contexts_see_changes_to_global_functions():
+    context = cel.Context({"title": "A B"})
+    with global_functions(slug=slug):
+        assert cel.evaluate("slug(title)", context) == "a-b"
+        cel.register_global_function("slug", lambda text: "replaced")
+        assert cel.evaluate("slug(title)", context) == "replaced"
+    with pytest.raises(ValueError, match="Undeclared reference to 'slug'|slug"):
+        cel.evaluate("slug(title)", context)
+
+
+def test_global_functions_are_described_by_contexts():
+    with global_functions(slug=slug):
+        info = cel.Context().functions_info()
+        assert info["slug"] == {"signature": "slug(text)", "doc": None, "builtin": False}
+        assert {"text": "slug", "kind": "function", "start": 0} in cel.complete("sl", context=cel.Context())
+        assert cel.diagnose("slug('a')", cel.Context()) == []
+
+
+def test_unregistering():
+    with pytest.raises(KeyError):
+        cel.unregister_global_function("missing")
+    with pytest.raises(TypeError, match="isn't callable"):
+        cel.register_global_function("answer", 42)
+
+
+def test_sandbox_leaves_out_global_functions():
+    with global_functions(slug=slug):
+        with pytest.raises(ValueError, match="function 'slug' is not available in the sandbox"):
+            cel.sandbox.evaluate("slug('A')")
+        assert cel.sandbox.evaluate("slug('A')", functions={"slug": slug}) == "a"
+        assert cel.sandbox.evaluate("x + 1", cel.Context({"x": 1})) == 2
+        assert cel.sandbox.evaluate("x + 1", {"x": 1}) == 2
+        with pytest.raises(ValueError, match="doesn't allow Python functions"):
+            cel.sandbox.evaluate("1", {"f": lambda: 1})
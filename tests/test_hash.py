@@ -0,0 +1,86 @@
+import hashlib
+import hmac
+
+import pytest
+
+import cel
+
+
+@pytest.fixture
+def context():
+    return cel.Context({"secret": b"key", "body": '{"amount": 10}'}, extensions=["hash"])
+
+
+def fnv1a(data):
+    result = 0xCBF29CE484222325
+    for byte in data:
+        result = ((result ^ byte) * 0x100000001B3) % 2**64
+    return result
+
+
+def test_hash_is_opt_in():
+    with pytest.raises(ValueError, match="Undeclared reference to 'sha256'"):
+        cel.evaluate("hash.sha256('abc')")
+
+
+@pytest.mark.parametrize("data", ["", "abc", "é" * 100, "a" * 55, "a" * 56, "a" * 64])
+def test_digests(context, data):
+    encoded = data.encode()
+    context.update({"data": data})
+    assert cel.evaluate("hash.sha256(data)", context) == hashlib.sha256(encoded).digest()
+    assert cel.evaluate("hash.md5(data)", context) == hashlib.md5(encoded).digest()
+    assert cel.evaluate("hash.md5(data, 'hex')", context) == hashlib.md5(encoded).hexdigest()
+    assert cel.evaluate("hash.fnv(data)", context) == fnv1a(encoded)
+
+
+def test_strings_are_hashed_as_utf8(context):
+    assert cel.evaluate("hash.sha256('abc') == hash.sha256(b'abc')", context) is True
+    assert (
+        cel.evaluate("hash.sha256('abc', 'hex')", context)
+        == "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    )
+    assert cel.evaluate("hash.sha256('abc', 'bytes')", context) == hashlib.sha256(b"abc").digest()
+
+
+def test_hmac(context):
+    expected = hmac.new(b"key", b'{"amount": 10}', hashlib.sha256)
+    assert cel.evaluate("hash.hmac(secret, body)", context) == expected.digest()
+    assert cel.evaluate("hash.hmac(secret, body, 'hex')", context) == expected.hexdigest()
+    long_key = "k" * 100
+    expected = hmac.new(long_key.encode(), b"data", hashlib.sha256).hexdigest()
+    assert cel.evaluate(f"hash.hmac('{long_key}', 'data', 'hex')", context) == expected
+
+
+def test_signature_check(context):
+    signature = hmac.new(b"key", b'{"amount": 10}', hashlib.sha256).hexdigest()
+    context.update({"signature": signature})
+    assert cel.evaluate("hash.hmac(secret, body, 'hex') == signature", context) is True
+
+
+def test_consistent_bucketing(context):
+    ids = [f"user-{i}" for i in range(1000)]
+    context.update({"ids": ids})
+    buckets = cel.evaluate("ids.map(id, hash.fnv(id) % 10u)", context)
+    assert buckets == [fnv1a(i.encode()) % 10 for i in ids]
+
+
+@pytest.mark.parametrize(
+    "expression,message",
+    [
+        ("hash.sha256(1)", "expected bytes or a string, got int"),
+        ("hash.sha256('a', 'base64')", "unknown encoding 'base64', expected bytes or hex"),
+        ("hash.md5('a', 1)", "expected an encoding, got int"),
+        ("hash.fnv('a', 'hex')", "expected 1, got 2"),
+        ("hash.hmac('key')", "expected 2, got 1"),
+    ],
+)
+def test_errors(context, expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression, context)
+
+
+def test_hash_functions_are_described(context):
+    info = context.functions_info()
+    assert info["hash.hmac"]["builtin"] is True
+    assert "hash.sha256" not in cel.Context().functions_info()
+    assert cel.diagnose("hash.hmac(secret, body, 'hex') == ''", context) == []
@@ -0,0 +1,119 @@
+import json
+
+import pytest
+
+import cel
+
+
+def test_decode():
+    payload = json.dumps({"user": {"id": "u-1", "age": 42}, "tags": ["a", "b"], "score": 0.5})
+    assert cel.evaluate("json.decode(payload).user.id == 'u-1'", {"payload": payload}) is True
+    assert cel.evaluate("json.decode(payload)", {"payload": payload}) == json.loads(payload)
+    assert cel.evaluate("json.decode(payload).user.age + 1", {"payload": payload}) == 43
+
+
+@pytest.mark.parametrize(
+    "text,expected",
+    [
+        ("null", None),
+        ("true", True),
+        ("-3", -3),
+        ("18446744073709551615", 18446744073709551615),
+        ("1e3", 1000.0),
+        ('"\\u00e9"', "é"),
+        ('{"a": [1, {"b": null}]}', {"a": [1, {"b": None}]}),
+    ],
+)
+def test_decode_values(text, expected):
+    assert cel.evaluate("json.decode(text)", {"text": text}) == expected
+
+
+def test_decode_bytes():
+    assert cel.evaluate("json.decode(b'[1, 2]')") == [1, 2]
+
+
+def test_decoded_ints_are_ints():
+    assert cel.evaluate("type(json.decode('1')) == int") is True
+
+
+def test_encode():
+    assert cel.evaluate("json.encode({'b': [1, 2.5, null], 'a': true})") == (
+        '{"a":true,"b":[1,2.5,null]}'
+    )
+    assert cel.evaluate("json.encode('quote \"')") == '"quote \\""'
+    assert cel.evaluate("json.encode(b'hi')") == '"aGk="'
+    assert cel.evaluate("json.decode(json.encode(value)) == value", {"value": {"x": [1, "y"]}})
+
+
+@pytest.mark.parametrize(
+    "expression,expected",
+    [
+        ("timestamp('2024-05-06T07:08:09Z')", '"2024-05-06T07:08:09Z"'),
+        ("timestamp('2024-05-06T09:08:09.5+02:00')", '"2024-05-06T07:08:09.500Z"'),
+        ("duration('1s')", '"1s"'),
+        ("duration('1.5s')", '"1.5s"'),
+        ("duration('-1m0.000001s')", '"-60.000001s"'),
+        ("duration('0s')", '"0s"'),
+        ("double('NaN')", '"NaN"'),
+        ("-double('inf')", '"-Infinity"'),
+    ],
+)
+def test_encode_follows_the_proto3_json_mapping(expression, expected):
+    assert cel.evaluate(f"json.encode({expression})") == expected
+
+
+@pytest.mark.parametrize(
+    "value",
+    ["timestamp('2024-05-06T07:08:09.123456Z')", "timestamp('2024-05-06T09:08:09+02:00')"],
+)
+def test_encoded_timestamps_round_trip(value):
+    assert cel.evaluate(f"timestamp(json.decode(json.encode({value}))) == {value}") is True
+
+
+@pytest.mark.parametrize("value", ["duration('1h2m3.5s')", "duration('-1ns')", "duration('0s')"])
+def test_encoded_durations_round_trip(value):
+    assert cel.evaluate(f"duration(json.decode(json.encode({value}))) == {value}") is True
+
+
+@pytest.mark.parametrize(
+    "expression,message",
+    [
+        ("json.decode('{')", "invalid JSON: EOF while parsing an object"),
+        ("json.decode('[1,]')", "invalid JSON"),
+        ("json.decode(1)", "can't decode int as JSON"),
+        ("json.decode(b'\\xff')", "JSON bytes aren't valid UTF-8"),
+        ("json.encode(int)", "can't encode function as JSON"),
+        ("json.decode('1', '2')", "expected 1, got 2"),
+    ],
+)
+def test_errors(expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression)
+
+
+def test_decode_limits_its_input():
+    too_long = json.dumps(["x" * 1000] * 1100)
+    with pytest.raises(ValueError, match=r"JSON is \d+ bytes long, the limit is 1048576"):
+        cel.evaluate("json.decode(text)", {"text": too_long})
+    too_deep = "[" * 65 + "]" * 65
+    with pytest.raises(ValueError, match="JSON is nested more than 64 deep"):
+        cel.evaluate("json.decode(text)", {"text": too_deep})
+    deep = "[" * 64 + "]" * 64
+    assert cel.evaluate("size(json.decode(text))", {"text": deep}) == 1
+    brackets_in_strings = json.dumps(["[" * 100])
+    assert cel.evaluate("json.decode(text)", {"text": brackets_in_strings}) == ["[" * 100]
+
+
+def test_encode_limits_its_output():
+    with pytest.raises(ValueError, match="encoded JSON is .* bytes long, the limit is 1048576"):
+        cel.evaluate("json.encode(items)", {"items": ["x" * 1000] * 1100})
+
+
+def test_a_json_variable_is_still_a_variable():
+    assert cel.evaluate("json.decode", {"json": {"decode": 1}}) == 1
+
+
+def test_json_functions_are_described():
+    info = cel.Context().functions_info()
+    assert info["json.decode"]["builtin"] is True
+    assert cel.diagnose("json.decode(payload).id == 1", {"payload": "{}"}) == []
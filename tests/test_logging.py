@@ -0,0 +1,139 @@
+import io
+import json
+import logging
+
+import pytest
+
+import cel
+
+SUBSYSTEMS = ["parser", "conversion", "functions", "eval"]
+
+
+@pytest.fixture(autouse=True)
+def reset_logging():
+    yield
+    cel.configure_logging(levels={name: "notset" for name in ["cel", *SUBSYSTEMS]}, json=False)
+
+
+@pytest.fixture
+def records():
+    captured = []
+
+    class Handler(logging.Handler):
+        def emit(self, record):
+            captured.append(record)
+
+    handler = Handler()
+    logger = logging.getLogger("cel")
+    logger.addHandler(handler)
+    yield captured
+    logger.removeHandler(handler)
+
+
+def test_subsystem_loggers(records):
+    cel.configure_logging(level="debug")
+    cel.evaluate("double(x) * 2.0 > 1.0", {"x": 1})
+    assert {"cel.parser", "cel.eval"} <= {record.name for record in records}
+
+
+def test_level_of_a_target(records):
+    cel.configure_logging(level="debug", target="cel.parser")
+    cel.evaluate("1 + 1")
+    assert {record.name for record in records} == {"cel.parser"}
+    assert all(record.levelno == logging.DEBUG for record in records)
+
+
+def test_target_without_the_prefix(records):
+    cel.configure_logging(level=logging.DEBUG, target="eval")
+    cel.evaluate("1 + 1")
+    assert {record.name for record in records} == {"cel.eval"}
+
+
+def test_levels_per_subsystem(records):
+    cel.configure_logging(levels={"parser": "debug", "cel.functions": "debug"})
+    cel.evaluate("f(1)", {"f": lambda x: x + 1})
+    names = {record.name for record in records}
+    assert names == {"cel.parser", "cel.functions"}
+    assert any("Calling Python function 'f'" in record.getMessage() for record in records)
+
+
+def test_errors_of_python_functions(records):
+    def fail(x):
+        raise RuntimeError("boom")
+
+    cel.configure_logging(level="debug", target="functions")
+    with pytest.raises(ValueError):
+        cel.evaluate("fail(1)", {"fail": fail})
+    assert any("raised" in record.getMessage() and "boom" in record.getMessage() for record in records)
+
+
+def test_quieting_evaluation_errors(records):
+    cel.evaluate("1 / 0", on_error="return")
+    assert any(record.levelno == logging.WARNING for record in records)
+
+    records.clear()
+    cel.configure_logging(level="error", target="cel.eval")
+    cel.evaluate("1 / 0", on_error="return")
+    assert records == []
+
+
+def test_notset_restores_the_parent_level(records):
+    cel.configure_logging(level="error", target="eval")
+    cel.configure_logging(level="notset", target="eval")
+    cel.evaluate("1 / 0", on_error="return")
+    assert any(record.name == "cel.eval" for record in records)
+
+
+def test_json_output():
+    stream = io.StringIO()
+    cel.configure_logging(level="debug", target="cel.eval", json=True, stream=stream)
+    cel.evaluate("1 + 1")
+    lines = [json.loads(line) for line in stream.getvalue().splitlines()]
+    assert lines
+    assert {line["logger"] for line in lines} == {"cel.eval"}
+    assert all(line["level"] == "DEBUG" for line in lines)
+    assert any(line["message"] == "Evaluating CEL expression: 1 + 1" for line in lines)
+    assert all(isinstance(line["time"], float) for line in lines)
+    assert logging.getLogger("cel").propagate is False
+
+
+def test_json_replaces_its_handler():
+    first, second = io.StringIO(), io.StringIO()
+    cel.configure_logging(level="debug", target="eval", json=True, stream=first)
+    cel.configure_logging(json=True, stream=second)
+    cel.evaluate("1 + 1")
+    assert first.getvalue() == ""
+    assert second.getvalue()
+
+
+def test_json_off():
+    stream = io.StringIO()
+    cel.configure_logging(level="debug", json=True, stream=stream)
+    cel.configure_logging(json=False)
+    cel.evaluate("1 + 1")
+    assert stream.getvalue() == ""
+    assert logging.getLogger("cel").propagate is True
+    assert logging.getLogger("cel").handlers == []
+
+
+def test_invalid_arguments_change_nothing():
+    with pytest.raises(ValueError, match="Unknown logging target 'cel.planner'"):
+        cel.configure_logging(levels={"parser": "debug", "cel.planner": "debug"})
+    assert logging.getLogger("cel.parser").level == logging.NOTSET
+
+    with pytest.raises(ValueError, match="Unknown logging level 'loud'"):
+        cel.configure_logging(level="loud")
+    with pytest.raises(ValueError, match="negative"):
+        cel.configure_logging(level=-1)
+    with pytest.raises(TypeError):
+        cel.configure_logging(level=1.5)
+    with pytest.raises(ValueError, match="needs a level"):
+        cel.configure_logging(target="eval")
+    with pytest.raises(ValueError, match="only used with json=True"):
+        cel.configure_logging(stream=io.StringIO())
+
+
+def test_program_names_are_logged(records):
+    cel.configure_logging(level="debug", target="eval")
+    cel.Program("1 + 1", name="policies.sum").evaluate()
+    assert any(record.getMessage() == "Evaluating program 'policies.sum': 1 + 1" for record in records)
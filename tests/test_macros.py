@@ -0,0 +1,152 @@
+import pytest
+
+import cel
+
+CONTEXT = {"items": [1, 2, 3], "scores": {"ann": 3, "bob": 1}}
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("items.all(x, x > 0)", True),
+    ("items.all(x, x > 1)", False),
+    ("items.exists(x, x == 2)", True),
+    ("items.exists(x, x == 4)", False),
+    ("items.exists_one(x, x > 2)", True),
+    ("items.exists_one(x, x > 1)", False),
+    ("items.existsOne(x, x > 2)", True),
+    ("items.existsOne(x, x > 1)", False),
+    ("items.map(x, x * 2)", [2, 4, 6]),
+    ("items.map(x, x > 1, x * 10)", [20, 30]),
+    ("items.filter(x, x % 2 == 1)", [1, 3]),
+    ("[].all(x, false)", True),
+    ("[].exists(x, true)", False),
+    ("[].existsOne(x, true)", False),
+    ("[].map(x, true, x)", []),
+    ("items.count(x, x > 1)", 2),
+    ("[].count(x, true)", 0),
+])
+def test_macros_on_lists(expression, expected, evaluate_both_ways):
+    assert evaluate_both_ways(expression, CONTEXT) == expected
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("scores.all(k, size(k) == 3)", True),
+    ("scores.exists(k, scores[k] > 2)", True),
+    ("scores.exists_one(k, scores[k] > 0)", False),
+    ("scores.existsOne(k, k == 'bob')", True),
+    ("scores.map(k, k).size()", 2),
+    ("scores.map(k, scores[k] > 2, k + '!')", ["ann!"]),
+    ("scores.filter(k, scores[k] < 2)", ["bob"]),
+    ("scores.count(k, scores[k] > 0)", 2),
+])
+def test_macros_iterate_over_map_keys(expression, expected, evaluate_both_ways):
+    assert evaluate_both_ways(expression, CONTEXT) == expected
+
+
+@pytest.mark.parametrize("expression, expected", [
+    # A false result decides `all` and a true one `exists`, whatever fails
+    ("[0, 1].all(x, 1 / x == 2)", False),
+    ("[1, 0].all(x, 1 / x == 2)", False),
+    ("[0, 1].exists(x, 1 / x == 1)", True),
+    ("[1, 0].exists(x, 1 / x == 1)", True),
+    # The predicate of `map` selects the items the expression is evaluated for
+    ("[0, 2].map(x, x != 0, 4 / x)", [2]),
+])
+def test_errors_are_absorbed(expression, expected, evaluate_both_ways):
+    assert evaluate_both_ways(expression, CONTEXT) == expected
+
+
+@pytest.mark.parametrize("expression", [
+    "[0, 1].all(x, 1 / x > 0)",
+    "[0, 1].exists(x, 1 / x > 1)",
+    "[0, 1].exists_one(x, 1 / x == 1)",
+    "[1, 0].existsOne(x, 1 / x == 1)",
+    "[0, 1].map(x, 1 / x)",
+    "[0, 1].map(x, 1 / x > 0, x)",
+    "[0, 1].filter(x, 1 / x > 0)",
+    "[0, 1].count(x, 1 / x > 0)",
+])
+def test_errors_propagate_otherwise(expression):
+    with pytest.raises(ValueError, match="division by zero"):
+        cel.evaluate(expression)
+    with pytest.raises(ValueError, match="division by zero"):
+        cel.Program(expression, optimize=True).evaluate()
+
+
+def test_absorbed_errors_in_parallel():
+    program = cel.Program("items.all(x, 1 / x == 2)", optimize=True, parallel_threshold=2)
+    assert program.evaluate({"items": [0, 1, 0]}) is False
+    program = cel.Program("items.map(x, x != 0, 6 / x)", optimize=True, parallel_threshold=2)
+    assert program.evaluate({"items": [0, 1, 2, 3]}) == [6, 3, 2]
+
+
+def test_macros_need_a_variable():
+    with pytest.raises(ValueError, match="expected a variable name"):
+        cel.evaluate("items.map(1, 2)", CONTEXT)
+    with pytest.raises(ValueError, match="Invalid argument count"):
+        cel.evaluate("items.all(x, true, false)", CONTEXT)
+
+
+def test_exists_one_alias_is_a_macro():
+    flow = cel.plan([("one", "items.existsOne(x, x > limit)"), ("x", "limit + 1")])
+    assert flow.order == ["one", "x"]
+    assert cel.sandbox.evaluate("items.existsOne(x, x > 2)", CONTEXT) is True
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("items.groupBy(x, x % 2 == 0)", {False: [1, 3], True: [2]}),
+    ("items.countBy(x, x > 1 ? 'big' : 'small')", {"big": 2, "small": 1}),
+    ("[].groupBy(x, x)", {}),
+    ("scores.groupBy(k, scores[k] > 2)", {True: ["ann"], False: ["bob"]}),
+    ("scores.countBy(k, size(k))", {3: 2}),
+    ("['a', 'b', 'a'].countBy(x, x)['a']", 2),
+])
+def test_grouping(expression, expected, evaluate_both_ways):
+    assert evaluate_both_ways(expression, CONTEXT) == expected
+
+
+def test_groups_keep_the_order_of_their_items(evaluate_both_ways):
+    events = [{"user": user, "n": n} for n, user in enumerate("abacbca")]
+    groups = evaluate_both_ways("events.groupBy(e, e.user)", {"events": events})
+    assert [event["n"] for event in groups["a"]] == [0, 2, 6]
+    program = cel.Program("events.groupBy(e, e.user)", optimize=True, parallel_threshold=2)
+    assert program.evaluate({"events": events}) == groups
+
+
+@pytest.mark.parametrize("expression, message", [
+    ("items.groupBy(x, x * 1.5)", "can't group by double, map keys are int, uint, bool or string"),
+    ("items.countBy(x, [x])", "can't group by list"),
+])
+def test_grouping_needs_map_keys(expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression, CONTEXT)
+    with pytest.raises(ValueError, match=message):
+        cel.Program(expression, optimize=True).evaluate(CONTEXT)
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("[3, 1, 3, 2, 1].distinct()", [3, 1, 2]),
+    ("distinct(['b', 'a', 'b'])", ["b", "a"]),
+    ("[1.5, 2.0, 1.5, [1], [1], {'a': 1}, {'a': 1}].distinct()", [1.5, 2.0, [1], {"a": 1}]),
+    ("[1, 1u, true].distinct()", [1, 1, True]),
+    ("[].distinct()", []),
+    ("items.distinct(x, x % 2)", [1, 2]),
+    ("items.distinct(x, x * 0.0)", [1]),
+    ("{'b': 1, 'a': 2, 'c': 3}.distinct(k, true)", ["a"]),
+    ("{'b': 1, 'a': 2, 'c': 3}.distinct(k, k != 'a')", ["a", "b"]),
+])
+def test_distinct(expression, expected, evaluate_both_ways):
+    assert evaluate_both_ways(expression, CONTEXT) == expected
+
+
+def test_distinct_keeps_the_first_item_with_each_key(evaluate_both_ways):
+    events = [{"user": user, "n": n} for n, user in enumerate("abacbca")]
+    expression = "events.distinct(e, e.user).map(e, e.n)"
+    assert evaluate_both_ways(expression, {"events": events}) == [0, 1, 3]
+    program = cel.Program(expression, optimize=True, parallel_threshold=2)
+    assert program.evaluate({"events": events}) == [0, 1, 3]
+    assert cel.sandbox.evaluate(expression, {"events": events}) == [0, 1, 3]
+
+
+def test_distinct_needs_a_variable_name():
+    with pytest.raises(ValueError, match="expected a variable name"):
+        cel.evaluate("items.distinct(1, 2)", CONTEXT)
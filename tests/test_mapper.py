@@ -0,0 +1,41 @@
+import pytest
+
+import cel
+
+
+def test_apply_adds_computed_fields():
+    mapper = cel.Mapper({"full_name": "first + ' ' + last", "adult": "age >= 18"})
+    record = {"first": "Ada", "last": "Lovelace", "age": 36}
+    assert mapper.apply(record) == {**record, "full_name": "Ada Lovelace", "adult": True}
+    # The record itself is left as it was
+    assert "adult" not in record
+    assert repr(mapper) == "Mapper(['full_name', 'adult'])"
+
+
+def test_apply_many():
+    mapper = cel.Mapper({"adult": "age >= 18"})
+    records = ({"age": age} for age in [10, 20])
+    assert mapper.apply_many(records) == [{"age": 10, "adult": False}, {"age": 20, "adult": True}]
+    assert mapper.apply_many([]) == []
+
+
+def test_fields_can_refer_to_each_other_and_replace_fields():
+    mapper = cel.Mapper({"label": "name + ' (' + string(score) + ')'", "name": "name + '!'", "score": "score * 2"})
+    assert mapper.fields == ["name", "score", "label"]
+    assert mapper.apply({"name": "ada", "score": 5}) == {"name": "ada!", "score": 10, "label": "ada! (10)"}
+
+
+def test_mode():
+    record = {"price": 2, "rate": 1.5}
+    assert cel.Mapper({"total": "price * rate"}).apply(record)["total"] == 3.0
+    with pytest.raises(ValueError, match="Unsupported binary operator"):
+        cel.Mapper({"total": "price * rate"}, mode="strict").apply(record)
+
+
+def test_errors():
+    with pytest.raises(ValueError, match="Failed to compile"):
+        cel.Mapper({"a": "1 +"})
+    with pytest.raises(ValueError, match="missing"):
+        cel.Mapper({"a": "missing"}).apply({})
+    with pytest.raises(TypeError):
+        cel.Mapper({"a": "1"}).apply_many([["not", "a", "dict"]])
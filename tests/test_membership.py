@@ -0,0 +1,62 @@
+import pytest
+
+import cel
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("'a' in {'a': 1}", True),
+    ("'b' in {'a': 1}", False),
+    ("1 in {1: 'a'}", True),
+    ("true in {true: 1}", True),
+    ("[1] in {1: 'a'}", False),
+    ("1 in [1, 2]", True),
+    ("3 in [1, 2]", False),
+    ("[1] in [[1], [2]]", True),
+    ("null in [null]", True),
+    ("'a' in []", False),
+    ("'b' in 'abc'", True),
+    ("'d' in 'abc'", False),
+])
+def test_in(expression, expected, evaluate_both_ways):
+    assert evaluate_both_ways(expression) is expected
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("2u in {2: 'a'}", True),
+    ("2 in {2u: 'a'}", True),
+    ("2.0 in {2: 'a'}", True),
+    ("2.5 in {2: 'a'}", False),
+    ("-1 in {18446744073709551615u: 'a'}", False),
+    ("1.0 in [1u, 2u]", True),
+    ("9007199254740993 in [9007199254740992.0]", False),
+])
+def test_numbers_are_found_whatever_their_type(expression, expected, evaluate_both_ways):
+    assert evaluate_both_ways(expression) is expected
+
+
+def test_in_context_values(evaluate_both_ways):
+    context = {"tags": ["a", "b"], "labels": {"env": "prod"}, "name": "alpha"}
+    assert evaluate_both_ways("'a' in tags && 'env' in labels && 'ph' in name", context) is True
+    assert evaluate_both_ways("'c' in tags || 'team' in labels", context) is False
+
+
+@pytest.mark.parametrize("expression, message", [
+    ("'a' in 5", "can't look for string in int, 'in' needs a list, map or string"),
+    ("1 in 'abc'", "can't look for int in a string"),
+    ("b'a' in b'abc'", "can't look for bytes in bytes"),
+    ("1 in null", "can't look for int in null_type"),
+])
+def test_unsupported_containers_are_explained(expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression)
+    with pytest.raises(ValueError, match=message):
+        cel.Program(expression, optimize=True).evaluate()
+
+
+def test_looking_for_a_field_suggests_has():
+    context = {"request": {"user": None}}
+    with pytest.raises(ValueError, match=r"did you mean has\(\.\.\.\)"):
+        cel.evaluate("'name' in request.user", context)
+    with pytest.raises(ValueError) as error:
+        cel.evaluate("1 in request.user", context)
+    assert "has(" not in str(error.value)
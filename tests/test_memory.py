@@ -0,0 +1,85 @@
+import pytest
+
+import cel
+
+
+def test_context_memory_usage_grows_with_its_values():
+    empty = cel.Context().memory_usage()
+    assert empty > 0
+
+    small = cel.Context({"name": "x"}).memory_usage()
+    large = cel.Context({"name": "x" * 100_000}).memory_usage()
+    assert empty < small < large
+    assert large - small >= 99_999
+
+    nested = cel.Context({"data": {"items": [{"id": i, "tags": ["a", "b"]} for i in range(1000)]}})
+    assert nested.memory_usage() > 1000 * 3 * 16
+
+
+def test_context_memory_usage_follows_changes():
+    context = cel.Context({"x": "a" * 1000})
+    before = context.memory_usage()
+    context.update_variable("x", "a" * 100_000)
+    assert context.memory_usage() > before + 90_000
+    context.update_variable("x", 1)
+    assert context.memory_usage() < before
+
+
+def test_context_memory_usage_counts_the_cached_environment():
+    context = cel.Context({f"v{i}": i for i in range(100)})
+    before = context.memory_usage()
+    cel.evaluate("v1", context)
+    assert context.memory_usage() > before
+
+
+def test_program_memory_usage():
+    small = cel.Program("x").memory_usage()
+    large = cel.Program(" + ".join(f"'{i}'" for i in range(1000))).memory_usage()
+    assert 0 < small < large
+
+
+def test_optimized_program_memory_usage_includes_the_plan():
+    source = "items.filter(x, x > 1).map(x, x * 2)"
+    assert cel.Program(source, optimize=True).memory_usage() > cel.Program(source).memory_usage()
+
+
+def test_program_memory_usage_includes_cached_results():
+    program = cel.Program("x")
+    before = program.memory_usage()
+    program.evaluate({"x": "y" * 10_000}, cache=True)
+    assert program.memory_usage() > before + 10_000
+    program.clear_cache()
+    assert program.memory_usage() < before + 10_000
+
+
+def test_max_result_size():
+    context = {"l": list(range(100))}
+    assert cel.evaluate("l.map(x, x)", context, max_result_size=100) == list(range(100))
+    with pytest.raises(cel.LimitExceeded, match="more than 1000 items and bytes"):
+        cel.evaluate("l.map(x, l.map(y, x * y))", context, max_result_size=1000)
+    with pytest.raises(cel.LimitExceeded, match="more than 5 items and bytes"):
+        cel.evaluate("'abcdef'", max_result_size=5)
+    with pytest.raises(cel.LimitExceeded):
+        cel.evaluate("{'key': 1}", max_result_size=3)
+    assert issubclass(cel.LimitExceeded, ValueError)
+    assert cel.LimitExceeded is cel.sandbox.LimitExceeded
+
+
+def test_max_result_size_is_unlimited_by_default():
+    assert len(cel.evaluate("l.map(x, l.map(y, x * y))", {"l": list(range(300))})) == 300
+
+
+def test_max_result_size_of_programs_and_evaluators():
+    program = cel.Program("l + l")
+    context = {"l": list(range(10))}
+    assert program.evaluate(context, max_result_size=20) == list(range(10)) * 2
+    with pytest.raises(cel.LimitExceeded):
+        program.evaluate(context, max_result_size=19)
+    with pytest.raises(cel.LimitExceeded):
+        program.evaluate(context, cache=True, max_result_size=19)
+
+    with cel.Evaluator(workers=2) as evaluator:
+        with pytest.raises(cel.LimitExceeded):
+            evaluator.submit(program, context, max_result_size=19).result(timeout=5)
+        with pytest.raises(cel.LimitExceeded):
+            evaluator.map(program, [context], max_result_size=19)
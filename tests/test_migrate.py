@@ -0,0 +1,128 @@
+import datetime
+
+import pytest
+
+import cel
+
+
+def migrate(expression, **kwargs):
+    [migration] = cel.migrate([expression], **kwargs)
+    return migration
+
+
+@pytest.mark.parametrize(
+    "expression, migrated",
+    [
+        ("1 + 1.5", "1.0 + 1.5"),
+        ("price * 1.2", "double(price) * 1.2"),
+        ("(count + 1) / 2.0", "double(count + 1) / 2.0"),
+        ("items.map(x, x * 0.5)", "items.map(x, double(x) * 0.5)"),
+        ("price > 5 || 'cheap'", 'price > 5 ? true : "cheap"'),
+        ("'' || 'anonymous'", 'size("") > 0 ? "" : "anonymous"'),
+        ("int(count) && ok", "int(count) != 0 && ok"),
+        ("!double(count)", "!(double(count) != 0.0)"),
+        ("[] ? 1 : 2", "size([]) > 0 ? 1 : 2"),
+        ("size(items) && ok", "size(items) != 0 && ok"),
+        ("!size(items)", "!(size(items) != 0)"),
+        ("items.size() + 0.5", "double(items.size()) + 0.5"),
+        ("{'a': 1, 'b': 2, 'a': 3}", '{"b": 2, "a": 3}'),
+        ("timestamp('2024-01-02')", 'timestamp("2024-01-02T00:00:00Z")'),
+        ("timestamp('2024-01-02 03:04:05+01:00')", 'timestamp("2024-01-02T03:04:05+01:00")'),
+    ],
+)
+def test_rewrites(expression, migrated):
+    variables = {"price": 10, "count": 3, "items": [1, 2], "ok": True}
+    migration = migrate(expression)
+    assert migration.original == expression
+    assert migration.expression == migrated
+    assert migration.changed
+    assert not migration.needs_attention
+    assert cel.evaluate(migrated, variables, mode="strict") == cel.evaluate(expression, variables)
+
+
+def test_unchanged_expressions_are_kept_as_written():
+    migration = migrate("x  >  1 &&   y   // not rewritten")
+    assert migration.expression == "x  >  1 &&   y   // not rewritten"
+    assert not migration.changed
+    assert migration.changes == []
+    assert migration.issues == []
+
+
+@pytest.mark.parametrize("expression", ["double(-1) + 2.5", "double(count) * 2.0", "has(a.b) && a.b.startsWith('x')"])
+def test_conversions_already_made_are_kept(expression):
+    migration = migrate(expression)
+    assert not migration.changed
+    assert migration.issues == []
+
+
+def test_changes():
+    migration = migrate("{'a': 1, 'a': 2}.a + 0.5")
+    assert migration.changes == [
+        "removed entry 1 of a map, its key \"a\" is repeated by entry 2",
+        "converted {\"a\": 2}.a to a double for '+', as double({\"a\": 2}.a)",
+    ]
+
+
+def test_expressions_needing_attention():
+    # Whether `user.name` is a bool is only known when evaluating
+    migration = migrate("user.name || 'anonymous'")
+    assert migration.needs_attention
+    assert "each operand of '||' must be a bool" in migration.issues[0]
+    assert migration.expression == "user.name || 'anonymous'"
+
+    migration = migrate("1 + 1u")
+    assert migration.issues == [
+        "'+' can't be applied to int and uint without numeric promotion, convert one operand with int(), uint() or double()"
+    ]
+
+
+def test_expressions_that_dont_compile():
+    migration = migrate("1 +")
+    assert migration.needs_attention
+    assert migration.issues[0].startswith("doesn't compile: ")
+
+    migration = migrate("1 + 1.5", from_mode="strict", to_mode="python")
+    assert migration.issues[0].startswith("doesn't compile in the mode it's migrated from: ")
+
+
+def test_modes():
+    assert not migrate("1 + 1.5", to_mode="python").changed
+    assert migrate("'' || 'x'", to_mode=cel.Options(numeric_promotion=False)).expression == "'' || 'x'"
+    migration = migrate("1 + 1.5 || x", to_mode=cel.Options(numeric_promotion=False))
+    assert migration.expression == "1.0 + 1.5 || x"
+
+
+def test_literals_are_written_back_exactly():
+    expression = "{'k': 'q\"\\\\ \\n\\t\\u0001 é', 'b': b'\\xff\\x00\"', 'k': [-1, 18446744073709551615u, 1e300, -0.5, null]}"
+    migration = migrate(expression)
+    assert migration.changed
+    assert not migration.needs_attention
+    assert cel.evaluate(migration.expression, mode="strict") == cel.evaluate(expression)
+
+
+def test_parentheses_are_kept_where_needed():
+    migration = migrate("(a - (b - c)) * 1.5 + (x ? 1 : 2) * 2.5 + -(-d).e")
+    assert migration.expression == "double(a - (b - c)) * 1.5 + double(x ? 1 : 2) * 2.5 + double(-(-d).e)"
+
+
+def test_rejects_a_single_string():
+    with pytest.raises(TypeError, match="expected an iterable of expressions"):
+        cel.migrate("1 + 1.5")
+
+
+def test_migrates_each_expression():
+    migrations = cel.migrate(iter(["1 + 1", "1 + 1.5"]))
+    assert [m.changed for m in migrations] == [False, True]
+    assert repr(migrations[1]) == (
+        "Migration(expression='1.0 + 1.5', changes=[\"converted 1 to a double for '+', as 1.0\"], issues=[])"
+    )
+
+
+def test_timestamps_that_cant_be_parsed_are_left():
+    migration = migrate("timestamp('yesterday')")
+    assert not migration.changed
+    with pytest.raises(ValueError):
+        cel.evaluate("timestamp('yesterday')")
+    assert cel.evaluate(migrate("timestamp('2024-01-02')").expression, mode="strict") == datetime.datetime(
+        2024, 1, 2, tzinfo=datetime.timezone.utc
+    )
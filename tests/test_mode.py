@@ -0,0 +1,239 @@
+import pytest
+
+import cel
+
+
+@pytest.mark.parametrize("expression,message", [
+    ("1 + 2.0", "'\\+' can't be applied to int and double"),
+    ("2.5 * 2", "'\\*' can't be applied to double and int"),
+    ("1u - 1", "'-' can't be applied to uint and int"),
+    ("-1 + 2.5", "can't be applied to int and double"),
+    ("{'a': [x + (1 % 2.0)]}", "'%' can't be applied to int and double"),
+    ("1 && true", "each operand of '&&' must be a bool"),
+    ("x || 'yes'", "each operand of '\\|\\|' must be a bool"),
+    ("!1", "the operand of '!' must be a bool"),
+    ("[] ? 1 : 2", "the condition of '\\?:' must be a bool without truthiness, got list"),
+    ("int(x) + double(x)", "can't be applied to int and double"),
+])
+def test_strict_mode_rejects_python_only_constructs(expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression, {"x": 1}, mode="strict")
+
+
+@pytest.mark.parametrize("expression,expected", [
+    ("1 + 2.0", 3.0),
+    ("1 && true", True),
+    ("!0", True),
+    ("[] ? 1 : 2", 2),
+])
+def test_python_mode_accepts_them(expression, expected):
+    assert cel.evaluate(expression) == expected
+
+
+@pytest.mark.parametrize("expression,expected", [
+    ("1 + 2", 3),
+    ("double(1) + 2.5", 3.5),
+    ("'a' + 'b'", "ab"),
+    ("1 == 1.0", True),
+    ("1 < 2.5", True),
+    ("(1 < 2) && !false ? 'yes' : 'no'", "yes"),
+    ("[1, 2.5]", [1, 2.5]),
+])
+def test_strict_mode_accepts_spec_expressions(expression, expected):
+    assert cel.evaluate(expression, mode="strict") == expected
+
+
+def test_strict_rejection_is_a_compile_error():
+    result = cel.evaluate("1 + 2.0", mode="strict", on_error="return")
+    assert isinstance(result, cel.EvalError)
+    assert result.kind == "compile"
+    with pytest.raises(ValueError, match="Failed to compile"):
+        cel.evaluate("1 + 2.0", mode="strict")
+
+
+def test_strict_mode_validates_programs():
+    program = cel.Program("1 + 2.0", optimize=True)
+    assert program.evaluate() == 3.0
+    with pytest.raises(ValueError, match="can't be applied to int and double"):
+        program.evaluate(mode="strict")
+    with pytest.raises(ValueError, match="must be a bool"):
+        cel.evaluate_predicate("1 && true", mode="strict")
+
+
+def test_context_mode():
+    context = cel.Context({"x": 1}, mode="strict")
+    assert context.mode == cel.Options.strict()
+    with pytest.raises(ValueError, match="can't be applied to int and double"):
+        cel.evaluate("x + (1 + 2.0)", context)
+    with pytest.raises(ValueError, match="RFC 3339"):
+        cel.Program("timestamp('2024-01-02')").evaluate(context)
+    with pytest.raises(ValueError, match="must be a bool"):
+        cel.evaluate_predicate("1 && true", context)
+
+
+def test_context_mode_defaults_to_python():
+    context = cel.Context({"x": 1})
+    assert context.mode == cel.Options.python()
+    assert cel.evaluate("x + 2.0", context) == 3.0
+
+
+def test_evaluation_mode_overrides_context_mode():
+    strict = cel.Context({"x": 1}, mode="strict")
+    assert cel.evaluate("x + 2.0", strict, mode="python") == 3.0
+    assert cel.Program("1 && true").evaluate(strict, mode="python") is True
+
+    python = cel.Context({"x": 1})
+    with pytest.raises(ValueError, match="can't be applied"):
+        cel.evaluate("1 + 2.0", python, mode="strict")
+
+
+def test_context_mode_can_be_changed():
+    context = cel.Context()
+    context.mode = "strict"
+    with pytest.raises(ValueError, match="can't be applied"):
+        cel.evaluate("1 + 2.0", context)
+    with pytest.raises(ValueError, match="mode must be"):
+        context.mode = "lenient"
+
+
+def test_invalid_context_mode():
+    with pytest.raises(ValueError, match="mode must be"):
+        cel.Context(mode="lenient")
+
+
+def test_options():
+    assert cel.Options() == cel.Options.python()
+    assert cel.Options(numeric_promotion=False) != cel.Options.python()
+    assert not cel.Options.strict().truthiness
+    assert cel.Options.strict().heterogeneous_equality
+    assert repr(cel.Options.strict()) == (
+        "Options(numeric_promotion=False, truthiness=False, safe_navigation=False, "
+        "heterogeneous_equality=True, lenient_timestamps=False, duplicate_map_keys=False, "
+        "error_absorption=True)"
+    )
+    assert len({cel.Options(), cel.Options.python(), cel.Options.strict()}) == 2
+
+
+def test_options_can_be_mixed():
+    options = cel.Options(truthiness=False)
+    assert cel.evaluate("1 + 2.0", mode=options) == 3.0
+    with pytest.raises(ValueError, match="must be a bool without truthiness"):
+        cel.evaluate("1 && true", mode=options)
+    assert cel.evaluate("timestamp('2024-01-02')", mode=options).year == 2024
+
+
+def test_numeric_promotion_is_enforced_while_evaluating():
+    options = cel.Options(numeric_promotion=False)
+    assert cel.evaluate("x + 2", {"x": 1}, mode=options) == 3
+    with pytest.raises(ValueError, match="Unsupported binary operator"):
+        cel.evaluate("x + 2.0", {"x": 1}, mode=options)
+
+
+def test_truthiness_is_enforced_while_evaluating():
+    options = cel.Options(truthiness=False)
+    assert cel.evaluate("x || false", {"x": True}, mode=options) is True
+    with pytest.raises(ValueError, match="bool operand"):
+        cel.evaluate("x || false", {"x": "yes"}, mode=options)
+
+
+def test_heterogeneous_equality():
+    assert cel.evaluate("x == 'a'", {"x": 1}) is False
+    options = cel.Options(heterogeneous_equality=False)
+    with pytest.raises(ValueError, match="Unsupported binary operator"):
+        cel.evaluate("x == 'a'", {"x": 1}, mode=options)
+    assert cel.evaluate("x != null", {"x": 1}, mode=options) is True
+    assert cel.evaluate("x == 1", {"x": 1}, mode=options) is True
+
+
+@pytest.mark.parametrize("optimize", [False, True])
+@pytest.mark.parametrize("expression, expected", [
+    ("[1] == [1.0]", True),
+    ("{'a': 1} == {'a': 1.0}", True),
+    ("x == [1.0, 2u]", True),
+    ("[[1], {'a': 2}] == [[1.0], {'a': 2u}]", True),
+    ("[9007199254740993] == [9007199254740992.0]", False),
+    ("{'a': 1} != {'a': 1.5}", True),
+])
+def test_heterogeneous_equality_within_lists_and_maps(expression, expected, optimize):
+    program = cel.Program(expression, optimize=optimize)
+    assert program.evaluate({"x": [1, 2]}) is expected
+
+
+@pytest.mark.parametrize("optimize", [False, True])
+@pytest.mark.parametrize("expression", ["[1] == [1.0]", "{'a': 1} != {'a': 1.0}", "x == [1, 2u]"])
+def test_strict_equality_within_lists_and_maps(expression, optimize):
+    options = cel.Options(heterogeneous_equality=False)
+    program = cel.Program(expression, optimize=optimize)
+    with pytest.raises(ValueError, match="Unsupported binary operator"):
+        program.evaluate({"x": [1, 2]}, mode=options)
+    assert cel.evaluate("[1, null] == [1, null] && [[1]] != [[2]] && [1] != [1, 2]", mode=options) is True
+
+
+def test_safe_navigation_option():
+    options = cel.Options(safe_navigation=True)
+    assert cel.evaluate("user.email", {"user": {}}, mode=options) is None
+    assert cel.evaluate("user.email", {"user": {}}, mode=options, safe_navigation=False,
+                        on_error="return").kind == "execution"
+
+
+def test_context_options():
+    context = cel.Context({"x": "yes"}, mode=cel.Options(truthiness=False))
+    assert context.mode == cel.Options(truthiness=False)
+    with pytest.raises(ValueError, match="bool operand"):
+        cel.evaluate("x && true", context)
+    context.mode = cel.Options.python()
+    assert cel.evaluate("x && true", context) is True
+
+
+def test_invalid_mode_type():
+    with pytest.raises(TypeError, match="cel.Options"):
+        cel.evaluate("1", mode=1)
+
+
+def test_duplicate_map_keys_are_allowed_in_python_mode():
+    assert cel.evaluate("{'a': 1, 'a': 2}") == {'a': 2}
+
+
+@pytest.mark.parametrize("expression, key", [
+    ("{'a': 1, 'b': 2, 'a': 3}", "'a' in entry 3, already the key of entry 1"),
+    ("{1: 'x', 1: 'y'}", "1 in entry 2"),
+    ("{2u: 'x', 2u: 'y'}", "2u in entry 2"),
+    ("[{true: 1, true: 2}]", "true in entry 2"),
+])
+def test_duplicate_literal_map_keys_are_rejected_in_strict_mode(expression, key):
+    with pytest.raises(ValueError, match="Failed to compile") as error:
+        cel.evaluate(expression, mode="strict")
+    assert f"duplicate map key {key}" in str(error.value)
+
+
+def test_duplicate_map_keys_from_variables_are_rejected_while_evaluating():
+    options = cel.Options(duplicate_map_keys=False)
+    assert cel.evaluate("{x: 1, y: 2}", {"x": "a", "y": "b"}, mode=options) == {"a": 1, "b": 2}
+    with pytest.raises(ValueError, match="duplicate map key 'a' in entry 2"):
+        cel.evaluate("{x: 1, y: 2}", {"x": "a", "y": "a"}, mode=options)
+    with pytest.raises(ValueError, match="duplicate map key 'a' in entry 2"):
+        cel.evaluate("{'a': 1, x: 2}", {"x": "a"}, mode=options)
+
+
+def test_duplicate_map_keys_are_reported_in_source_terms():
+    options = cel.Options(duplicate_map_keys=False)
+    error = cel.evaluate("{k: 1, 'a': 2}", {"k": "a"}, mode=options, on_error="return")
+    assert error.message == "duplicate map key 'a' in entry 2, already the key of entry 1 in {k: 1, \"a\": 2}"
+    assert "@map" not in str(error)
+
+
+@pytest.mark.parametrize("expression, position", [
+    ("{'a': 1, 'a': 2}", 9),
+    ("{'a': 1, \"a\": 2}", 9),
+    ("x == 'a' ? {'b': 'a', 'a': 1, 'a': 2} : {}", 30),
+    ("[{1: 'x'}, {1: 'y', f(1, 2): 3, 0x1: 'z'}]", 32),
+    ("{'a': {'b': 1, 'b': 2}, 'c': 3}", 15),
+])
+def test_duplicate_map_keys_have_a_position(expression, position):
+    error = cel.evaluate(expression, mode="strict", on_error="return")
+    assert error.kind == "compile"
+    assert error.position == position
+
+
+def test_distinct_key_types_are_not_duplicates():
+    assert cel.evaluate("size({1: 'a', 1u: 'b', true: 'c'})", mode="strict") == 3
@@ -0,0 +1,78 @@
+import pytest
+
+import cel
+from cel.testing import Mutant, mutants, run_cases
+
+
+def test_mutants():
+    found = mutants("user.age >= 18 && !user.banned")
+    assert [(m.expression, m.original, m.replacement) for m in found] == [
+        ("user.age >= 18 || !user.banned", "user.age >= 18 && !user.banned", "user.age >= 18 || !user.banned"),
+        ("!(user.age >= 18 && !user.banned)", "user.age >= 18 && !user.banned", "!(user.age >= 18 && !user.banned)"),
+        ("user.age > 18 && !user.banned", "user.age >= 18", "user.age > 18"),
+        ("user.age < 18 && !user.banned", "user.age >= 18", "user.age < 18"),
+        ("user.age >= 19 && !user.banned", "18", "19"),
+        ("user.age >= 17 && !user.banned", "18", "17"),
+        ("user.age >= 18 && user.banned", "!user.banned", "user.banned"),
+    ]
+    assert isinstance(found[0], Mutant)
+    assert str(found[2]) == "user.age > 18 && !user.banned"
+    assert repr(found[4]) == "Mutant(expression='user.age >= 19 && !user.banned', original='18', replacement='19')"
+
+
+@pytest.mark.parametrize(
+    "expression, expected",
+    [
+        ("a + b * c", ["a - b * c", "a + b / c"]),
+        ("a % 2 == 0", ["a % 2 != 0", "a * 2 == 0", "a % 3 == 0", "a % 1 == 0", "a % 2 == 1", "a % 2 == -1"]),
+        ("x in ['a', '']", ["!(x in [\"a\", \"\"])", "x in [\"\", \"\"]", "x in [\"a\", \"x\"]"]),
+        ("has(user.email) ? 1u : 0.5", ["has(user.email) ? 0.5 : 1u", "!has(user.email) ? 1u : 0.5", "has(user.email) ? 2u : 0.5", "has(user.email) ? 0u : 0.5", "has(user.email) ? 1u : 1.5", "has(user.email) ? 1u : -0.5"]),
+        ("items.all(i, i.ok)", ["items.exists(i, i.ok)"]),
+        ("items.exists_one(i, !i.ok)", ["items.exists(i, !i.ok)", "items.exists_one(i, i.ok)"]),
+        ("-x == null", ["-x != null", "x == null"]),
+        ("a > 1 && b == 'x'", ["a > 1 || b == \"x\"", "!(a > 1 && b == \"x\")", "a >= 1 && b == \"x\"", "a <= 1 && b == \"x\"", "a > 2 && b == \"x\"", "a > 0 && b == \"x\"", "a > 1 && b != \"x\"", "a > 1 && b == \"\""]),
+    ],
+)
+def test_mutations(expression, expected):
+    assert [m.expression for m in mutants(expression)] == expected
+
+
+def test_mutants_differ_from_the_expression_and_each_other():
+    # Swapping the branches of `?:` when they're the same changes nothing
+    assert [m.expression for m in mutants("c ? a : a")] == []
+    assert mutants("name") == []
+
+
+def test_mutants_are_valid_expressions():
+    for mutant in mutants('size(user.name) > 3 && user.roles.exists(r, r in ["admin", "owner"]) || 2.0 * x < 10'):
+        cel.Program(mutant.expression)
+
+
+def test_invalid_expression():
+    with pytest.raises(ValueError):
+        mutants("1 +")
+
+
+def test_mutation_testing_a_suite():
+    policy = "user.age >= 18 && !user.banned"
+    cases = [
+        ({"user": {"age": 18, "banned": False}}, True),
+        ({"user": {"age": 17, "banned": False}}, False),
+        ({"user": {"age": 30, "banned": True}}, False),
+    ]
+
+    def survivors(cases):
+        surviving = []
+        for mutant in mutants(policy):
+            try:
+                run_cases([(mutant.expression, context, expected) for context, expected in cases])
+            except AssertionError:
+                continue
+            surviving.append(mutant.expression)
+        return surviving
+
+    run_cases([(policy, context, expected) for context, expected in cases])
+    assert survivors(cases) == []
+    # Without a case at the boundary, moving it up goes unnoticed
+    cases[0] = ({"user": {"age": 20, "banned": False}}, True)
+    assert survivors(cases) == ["user.age > 18 && !user.banned", "user.age >= 19 && !user.banned"]
@@ -0,0 +1,94 @@
+import pytest
+
+import cel
+
+RULES = {
+    "adult": "user.age >= 18",
+    "named": "user.name != ''",
+    "allowed": "rule('adult') && rule('named')",
+}
+
+
+def make_context(**kwargs):
+    context = cel.Context({"user": {"age": 21, "name": "alice"}}, **kwargs)
+
+    def rule(name):
+        return cel.evaluate(RULES[name], context)
+
+    context.add_function("rule", rule)
+    return context
+
+
+def test_functions_can_evaluate_expressions():
+    context = make_context()
+    assert cel.evaluate("rule('allowed')", context) is True
+    assert cel.Program("rule('adult') && rule('named')", optimize=True).evaluate(context) is True
+
+
+def test_functions_can_evaluate_the_program_calling_them():
+    program = cel.Program("depth > 0 ? nested(depth - 1) + 1 : 0")
+
+    def nested(depth):
+        return program.evaluate(cel.Context({"depth": depth}, functions={"nested": nested}))
+
+    assert program.evaluate(cel.Context({"depth": 10}, functions={"nested": nested})) == 10
+
+
+def test_nested_evaluations_keep_their_own_memoized_calls():
+    calls = []
+
+    def lookup(x):
+        calls.append(x)
+        return x
+
+    context = make_context(memoize=True)
+    context.add_function("lookup", lookup)
+    RULES["lookup"] = "lookup(1) + lookup(1)"
+    try:
+        assert cel.evaluate("lookup(1) + rule('lookup') + lookup(1)", context) == 4
+    finally:
+        del RULES["lookup"]
+    assert calls == [1, 1]
+
+
+def test_errors_of_nested_evaluations_are_raised():
+    context = make_context()
+    with pytest.raises(ValueError, match="Failed to evaluate expression 'undefined'"):
+        cel.evaluate("rule_of(' undefined')", {"rule_of": lambda e: cel.evaluate(e.strip())})
+    error = cel.evaluate("rule('missing')", context, on_error="return")
+    assert error.kind == "execution"
+    assert "KeyError" in error.message
+
+
+def test_endless_nesting_fails():
+    def forever():
+        return cel.evaluate("forever()", {"forever": forever})
+
+    with pytest.raises(ValueError, match="nested more than 32 deep"):
+        cel.evaluate("forever()", {"forever": forever})
+    # Evaluating works as usual afterwards
+    assert cel.evaluate("forever != null || true", {"forever": 1}) is True
+
+
+def test_functions_on_an_evaluator_thread_can_submit_to_it():
+    with cel.Evaluator(workers=1) as evaluator:
+        def double(x):
+            return evaluator.submit("x * 2", {"x": x}).result()
+
+        def pair(x):
+            return evaluator.map("x + 1", [{"x": x}, {"x": x + 1}])
+
+        context = {"double": double, "pair": pair}
+        results = evaluator.map("double(x) + size(pair(x))", [dict(context, x=1), dict(context, x=2)])
+        assert results == [4, 6]
+
+
+def test_functions_on_an_evaluator_thread_can_nest_evaluations():
+    program = cel.Program("depth > 0 ? nested(depth - 1) + 1 : 0")
+
+    def nested(depth):
+        return program.evaluate(cel.Context({"depth": depth}, functions={"nested": nested}))
+
+    with cel.Evaluator(workers=1) as evaluator:
+        context = cel.Context({"depth": 30}, functions={"nested": nested})
+        assert evaluator.submit(program, context).result() == 30
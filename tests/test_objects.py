@@ -0,0 +1,207 @@
+import pytest
+
+import cel
+
+
+class Proxy:
+    """Resolves attributes on demand, like a lazily loaded ORM object"""
+
+    def __init__(self, value):
+        self.value = value
+        self.loads = []
+
+    def __getattr__(self, name):
+        self.loads.append(name)
+        if name == "child":
+            return Proxy(self.value + 1)
+        if name == "children":
+            return [Proxy(10), Proxy(20)]
+        if name == "details":
+            return {"proxy": Proxy(5), "count": 2}
+        if name == "broken":
+            raise RuntimeError("lost connection")
+        raise AttributeError(name)
+
+
+def context(**variables):
+    return cel.Context(variables, objects="attributes")
+
+
+def test_objects_are_rejected_by_default():
+    with pytest.raises(ValueError, match="can't be converted"):
+        cel.Context({"obj": Proxy(1)})
+    assert cel.Context().objects == "error"
+    assert context().objects == "attributes"
+
+
+def test_invalid_objects_setting():
+    with pytest.raises(ValueError, match="objects must be"):
+        cel.Context(objects="dicts")
+
+
+def test_attributes_are_read_while_evaluating():
+    obj = Proxy(1)
+    ctx = context(obj=obj)
+    assert obj.loads == []
+    assert cel.evaluate("obj.value", ctx) == 1
+    assert cel.evaluate("obj.child.child.value", ctx) == 3
+    assert set(obj.loads) == {"child"}
+
+
+def test_only_selected_attributes_are_loaded():
+    obj = Proxy(1)
+    assert cel.evaluate("obj.value > 0 || obj.child.value > 0", context(obj=obj)) is True
+    assert obj.loads == []
+
+
+def test_objects_within_attributes():
+    ctx = context(obj=Proxy(1))
+    assert cel.evaluate("obj.children.map(c, c.value)", ctx) == [10, 20]
+    assert cel.evaluate("obj.children[1].child.value", ctx) == 21
+    assert cel.evaluate("obj.details.proxy.value + obj.details.count", ctx) == 7
+
+
+def test_objects_within_variables():
+    ctx = context(items=[Proxy(3)], by_name={"a": Proxy(4)})
+    assert cel.evaluate("items[0].value + by_name.a.value", ctx) == 7
+    assert cel.evaluate("items.exists(i, i.value == 3)", ctx) is True
+
+
+def test_missing_attributes_are_missing_fields():
+    ctx = context(obj=Proxy(1))
+    with pytest.raises(ValueError, match="No such key: missing"):
+        cel.evaluate("obj.missing", ctx)
+    assert cel.evaluate("has(obj.missing)", ctx) is False
+    assert cel.evaluate("has(obj.value)", ctx) is True
+    assert cel.evaluate("obj.missing.deeper", ctx, safe_navigation=True) is None
+
+
+SECRET_TOKEN = "hunter2"
+
+
+class User:
+    def __init__(self):
+        self._password = "secret"
+        self.name = "ada"
+
+
+@pytest.mark.parametrize("expression, name", [
+    ("user.__init__.__globals__.SECRET_TOKEN", "__init__"),
+    ("user.__class__.__name__", "__class__"),
+    ("user.__dict__", "__dict__"),
+    ("user._password", "_password"),
+])
+def test_private_attributes_are_missing(expression, name):
+    ctx = context(user=User())
+    with pytest.raises(ValueError, match=f"No such key: {name}"):
+        cel.evaluate(expression, ctx)
+    assert cel.evaluate(f"has(user.{name})", ctx) is False
+    assert cel.evaluate("user.name", ctx) == "ada"
+
+
+def test_private_attributes_arent_looked_up():
+    proxy = Proxy(1)
+    with pytest.raises(ValueError, match="No such key: _session"):
+        cel.evaluate("obj.child._session", context(obj=proxy))
+    assert proxy.loads == ["child"]
+
+
+def test_errors_raised_by_attributes():
+    with pytest.raises(ValueError, match="lost connection"):
+        cel.evaluate("obj.broken", context(obj=Proxy(1)))
+
+
+def test_maps_still_work():
+    ctx = context(obj=Proxy(1), user={"name": "Ada"})
+    assert cel.evaluate("user.name", ctx) == "Ada"
+    assert cel.evaluate("has(user.email)", ctx) is False
+    assert cel.evaluate("user.email", ctx, safe_navigation=True) is None
+
+
+def test_objects_cant_be_returned():
+    with pytest.raises(TypeError, match="select their attributes"):
+        cel.evaluate("obj", context(obj=Proxy(1)))
+
+
+def test_replacing_and_removing_variables():
+    ctx = context(obj=Proxy(1))
+    ctx.update_variable("obj", Proxy(7))
+    assert cel.evaluate("obj.value", ctx) == 7
+    ctx.update({"obj": {"value": 8}})
+    assert cel.evaluate("obj.value", ctx) == 8
+
+
+def test_programs_and_compiled_plans():
+    ctx = context(obj=Proxy(2))
+    assert cel.Program("obj.child.value * 2").evaluate(ctx) == 6
+    assert cel.Program("obj.child.value * 2", optimize=True).evaluate(ctx) == 6
+
+
+class Money:
+    def __init__(self, cents):
+        self.cents = cents
+
+    def __add__(self, other):
+        return Money(self.cents + (other.cents if isinstance(other, Money) else other))
+
+    __radd__ = __add__
+
+    def __mul__(self, factor):
+        return Money(self.cents * factor)
+
+    __rmul__ = __mul__
+
+    def __eq__(self, other):
+        return isinstance(other, Money) and self.cents == other.cents
+
+    def __lt__(self, other):
+        return self.cents < other.cents
+
+    def __hash__(self):
+        return hash(self.cents)
+
+
+class Bag:
+    def __init__(self, items):
+        self.items = items
+
+    def __contains__(self, item):
+        return item in self.items
+
+    def __getitem__(self, key):
+        return self.items[key]
+
+
+def test_operators_use_the_python_operators():
+    ctx = context(price=Money(100), fee=Money(250), n=3)
+    assert cel.evaluate("price < fee", ctx) is True
+    assert cel.evaluate("price + fee > fee", ctx) is True
+    assert cel.evaluate("price == price && price != fee", ctx) is True
+    assert cel.evaluate("(price + fee).cents", ctx) == 350
+    assert cel.evaluate("(price + fee + 5).cents", ctx) == 355
+    assert cel.evaluate("(2 * price).cents + (price * n).cents", ctx) == 500
+
+
+def test_membership_and_indexes_use_the_python_operators():
+    ctx = context(bag=Bag(["x", {"a": Money(3)}]))
+    assert cel.evaluate("'x' in bag && !('z' in bag)", ctx) is True
+    assert cel.evaluate("bag[0]", ctx) == "x"
+    assert cel.evaluate("bag[1].a.cents", ctx) == 3
+    with pytest.raises(ValueError, match="No such key: 5"):
+        cel.evaluate("bag[5]", ctx)
+
+
+def test_operators_on_other_values_are_unchanged():
+    ctx = context(price=Money(1), items=[Money(1), Money(2)])
+    assert cel.evaluate("1 + 2 * 3", ctx) == 7
+    assert cel.evaluate("[1] + [2]", ctx) == [1, 2]
+    assert cel.evaluate("{'a': 1}['a'] < 2 && 'a' in ['a']", ctx) is True
+    assert cel.evaluate("items[0] < items[1]", ctx) is True
+    assert cel.evaluate("items.map(i, i.cents)", ctx) == [1, 2]
+
+
+def test_unsupported_operators():
+    with pytest.raises(ValueError, match="unsupported operand"):
+        cel.evaluate("price - price", context(price=Money(1)))
+    with pytest.raises(TypeError, match="select their attributes"):
+        cel.evaluate("price + price", context(price=Money(1)))
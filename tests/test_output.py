@@ -0,0 +1,134 @@
+import datetime
+import types
+
+import pytest
+
+import cel
+
+
+def test_default_output_is_python():
+    assert cel.evaluate("{'a': [1, 2]}") == {'a': [1, 2]}
+
+
+def test_cel_output_is_opaque():
+    result = cel.evaluate("{'a': [1, 2], 'b': 'text'}", output="cel")
+    assert isinstance(result, cel.Value)
+    assert result.type == "map"
+    assert result.to_python() == {'a': [1, 2], 'b': 'text'}
+
+
+def test_cel_output_indexing():
+    result = cel.evaluate("{'a': [1, {'x': true}], 1: 'one'}", output="cel")
+    assert isinstance(result['a'], cel.Value)
+    assert result['a'][0].to_python() == 1
+    assert result['a'][-1]['x'].to_python() == True
+    assert result[1] == 'one'
+    with pytest.raises(KeyError):
+        result['missing']
+    with pytest.raises(IndexError):
+        result['a'][5]
+    with pytest.raises(TypeError):
+        result['a'][0][0]
+
+
+def test_cel_output_iteration_and_len():
+    items = cel.evaluate("[1, 'two', [3]]", output="cel")
+    assert len(items) == 3
+    assert [item.to_python() for item in items] == [1, 'two', [3]]
+    assert sorted(cel.evaluate("{'b': 1, 'a': 2}", output="cel")) == ['a', 'b']
+    assert 'a' in cel.evaluate("{'a': 1}", output="cel")
+    assert 'two' in items
+    assert 4 not in items
+
+
+def test_cel_output_truthiness():
+    assert not cel.evaluate("[]", output="cel")
+    assert cel.evaluate("[0]", output="cel")
+    assert not cel.evaluate("0", output="cel")
+    assert cel.evaluate("'x'", output="cel")
+
+
+def test_cel_output_equality():
+    assert cel.evaluate("1 + 1", output="cel") == 2
+    assert cel.evaluate("[1]", output="cel") == cel.evaluate("[1]", output="cel")
+    assert cel.evaluate("[1]", output="cel") != [2]
+
+
+def test_cel_output_from_program():
+    program = cel.Program("data.items")
+    result = program.evaluate({'data': {'items': list(range(10))}}, output="cel")
+    assert result[9] == 9
+
+
+def test_invalid_output():
+    with pytest.raises(ValueError, match="output"):
+        cel.evaluate("1", output="json")
+
+
+def test_default_output_types():
+    result = cel.evaluate("[timestamp('2024-01-02T03:04:05Z'), duration('90s'), {'a': b'x'}]", cel.Context())
+    assert isinstance(result[0], datetime.datetime)
+    assert result[1] == datetime.timedelta(seconds=90)
+    assert result[2] == {'a': b'x'}
+
+
+def test_timestamp_and_duration_output_types():
+    context = cel.Context(output_types={'timestamp': 'iso', 'duration': 'seconds'})
+    assert cel.evaluate("timestamp('2024-01-02T03:04:05Z')", context) == "2024-01-02T03:04:05+00:00"
+    assert cel.evaluate("duration('1.5s')", context) == 1.5
+    assert cel.evaluate("[duration('-90s')]", context) == [-90.0]
+
+
+def test_map_and_bytes_output_types():
+    context = cel.Context({'data': {'inner': {'a': 1}}},
+                          output_types={'map': 'mappingproxy', 'bytes': 'bytearray'})
+    result = cel.evaluate("data", context)
+    assert isinstance(result, types.MappingProxyType)
+    assert isinstance(result['inner'], types.MappingProxyType)
+    assert result['inner']['a'] == 1
+    assert cel.evaluate("b'abc'", context) == bytearray(b'abc')
+    assert isinstance(cel.evaluate("b'abc'", context), bytearray)
+
+
+def test_output_types_apply_to_programs_and_cel_values():
+    context = cel.Context({'t': datetime.datetime(2024, 1, 1, tzinfo=datetime.timezone.utc)},
+                          output_types={'timestamp': 'iso'})
+    assert cel.Program("t").evaluate(context) == "2024-01-01T00:00:00+00:00"
+    assert cel.evaluate("[t]", context, output="cel")[0].to_python() == "2024-01-01T00:00:00+00:00"
+
+
+def test_python_functions_receive_default_types():
+    context = cel.Context({'d': datetime.timedelta(seconds=5)},
+                          functions={'seconds': lambda d: d.total_seconds()},
+                          output_types={'duration': 'seconds'})
+    assert cel.evaluate("seconds(d)", context) == 5.0
+
+
+def test_list_output_type_restores_tuples():
+    context = cel.Context({'points': [(1, 2), (3, 4)]}, output_types={'list': 'tuple'})
+    result = cel.evaluate("points", context)
+    assert result == ((1, 2), (3, 4))
+    assert cel.evaluate("points.map(p, p[0])", context) == (1, 3)
+    assert cel.evaluate("{'a': [1]}", context) == {'a': (1,)}
+
+
+def test_sorted_map_order():
+    context = cel.Context({'data': {f'k{i}': {'z': 1, 'a': 2} for i in range(50, 0, -1)}},
+                          output_types={'map_order': 'sorted'})
+    result = cel.evaluate("data", context)
+    assert list(result) == sorted(result)
+    assert all(list(inner) == ['a', 'z'] for inner in result.values())
+    assert list(cel.evaluate("{2: 'b', 1: 'a', 3: 'c'}", context)) == [1, 2, 3]
+    assert list(cel.evaluate("data", context, output="cel")) == sorted(result)
+
+
+def test_sorted_map_order_with_mixed_keys():
+    context = cel.Context(output_types={'map_order': 'sorted'})
+    result = cel.evaluate("{'b': 1, true: 2, 3: 3, 'a': 4, 2: 5}", context)
+    assert list(result) == [2, 3, True, 'a', 'b']
+
+
+@pytest.mark.parametrize("output_types", [{'timestamp': 'unix'}, {'colour': 'red'}, {'map_order': 'inserted'}])
+def test_invalid_output_types(output_types):
+    with pytest.raises(ValueError):
+        cel.Context(output_types=output_types)
@@ -0,0 +1,97 @@
+import pytest
+
+import cel
+
+
+def messages(diagnostics):
+    return [(d.message, d.start, d.end) for d in diagnostics]
+
+
+def test_valid_expression_has_no_diagnostics():
+    program, diagnostics = cel.parse_lenient("a + 2")
+    assert diagnostics == []
+    assert program.source == "a + 2"
+    assert program.evaluate({"a": 1}) == 3
+    assert program.fingerprint() == cel.Program("a + 2").fingerprint()
+
+
+def test_missing_operand():
+    program, diagnostics = cel.parse_lenient("1 +")
+    assert messages(diagnostics) == [("expected an expression", 3, 3)]
+    with pytest.raises(ValueError, match="@error"):
+        program.evaluate()
+
+
+def test_empty_expression():
+    program, diagnostics = cel.parse_lenient("")
+    assert program is not None
+    assert messages(diagnostics) == [("expected an expression", 0, 0)]
+
+
+def test_unclosed_brackets_are_closed():
+    program, diagnostics = cel.parse_lenient('{"a": [1, 2')
+    assert messages(diagnostics) == [("expected ']'", 11, 11), ("expected '}'", 11, 11)]
+    assert program.evaluate() == {"a": [1, 2]}
+
+    program, diagnostics = cel.parse_lenient("size(items")
+    assert messages(diagnostics) == [("expected ')'", 10, 10)]
+    assert program.evaluate({"items": [1, 2]}) == 2
+
+
+def test_every_error_is_reported():
+    program, diagnostics = cel.parse_lenient("a b + * 2")
+    assert messages(diagnostics) == [
+        ("unexpected 'b'", 2, 3),
+        ("expected an expression before '*'", 6, 6),
+    ]
+    assert program is not None
+
+
+def test_missing_parts_of_a_ternary_and_map():
+    assert messages(cel.parse_lenient("a ? b")[1]) == [
+        ("expected ':'", 5, 5),
+        ("expected an expression", 5, 5),
+    ]
+    assert [d.message for d in cel.parse_lenient('{"a"')[1]] == [
+        "expected ':'",
+        "expected an expression",
+        "expected '}'",
+    ]
+
+
+def test_missing_field_name():
+    program, diagnostics = cel.parse_lenient("user.")
+    assert messages(diagnostics) == [("expected a name", 5, 5)]
+    assert program is not None
+
+
+def test_invalid_characters_and_unterminated_strings():
+    _, diagnostics = cel.parse_lenient("x = 1")
+    assert messages(diagnostics)[0] == ("unexpected character '='", 2, 3)
+
+    _, diagnostics = cel.parse_lenient('name == "abc')
+    assert messages(diagnostics) == [("unterminated string", 8, 9)]
+
+
+def test_invalid_literals_are_diagnostics():
+    program, diagnostics = cel.parse_lenient("99999999999999999999 + 1")
+    assert len(diagnostics) == 1
+    assert diagnostics[0].message.startswith("invalid int literal 99999999999999999999")
+    assert (diagnostics[0].start, diagnostics[0].end) == (0, 20)
+    assert program is not None
+
+    _, diagnostics = cel.parse_lenient(r'"\q" == s')
+    assert messages(diagnostics) == [(r'invalid string literal "\q": invalid escape \q', 0, 4)]
+
+
+def test_offsets_are_characters_in_the_original_expression():
+    _, diagnostics = cel.parse_lenient("'é' + + x )")
+    assert messages(diagnostics) == [
+        ("expected an expression before '+'", 6, 6),
+        ("unexpected ')'", 10, 11),
+    ]
+
+
+def test_repr():
+    _, diagnostics = cel.parse_lenient("1 +")
+    assert repr(diagnostics[0]) == "Diagnostic(message='expected an expression', start=3, end=3)"
@@ -0,0 +1,218 @@
+import pytest
+
+import cel
+
+
+def test_compile_and_evaluate():
+    program = cel.Program("a + b")
+    assert program.evaluate({'a': 1, 'b': 2}) == 3
+    assert program.evaluate({'a': 10, 'b': 20}) == 30
+
+
+def test_program_source():
+    program = cel.Program("size(items) > 0")
+    assert program.source == "size(items) > 0"
+    assert "size(items) > 0" in repr(program)
+
+
+def test_invalid_program_raises_on_compile():
+    with pytest.raises(ValueError, match="Failed to compile"):
+        cel.Program("1 +")
+
+
+def test_program_with_context_object():
+    context = cel.Context({'a': 2}, functions={'double': lambda x: x * 2})
+    assert cel.Program("double(a)").evaluate(context) == 4
+
+
+def test_program_without_context():
+    assert cel.Program("[1, 2, 3].map(x, x * 2)").evaluate() == [2, 4, 6]
+
+
+def test_program_evaluation_options():
+    program = cel.Program("user.email")
+    assert program.evaluate({'user': {}}, safe_navigation=True) is None
+    assert isinstance(program.evaluate({'user': {}}, on_error="return"), cel.EvalError)
+    # Options from one evaluation don't leak into the next
+    with pytest.raises(ValueError):
+        program.evaluate({'user': {}})
+
+
+def test_program_with_unknowns():
+    program = cel.Program("request.time > 1 && allowed")
+    assert program.evaluate({'allowed': False}, unknowns=["request.time"]) == False
+    assert isinstance(program.evaluate({'allowed': True}, unknowns=["request.time"]), cel.Unknown)
+
+
+OPTIMIZE_CASES = [
+    ("1 + 2 * 3", {}),
+    ("a + b * 2 - 1", {'a': 1, 'b': 4}),
+    ("a > 1 && b < 10", {'a': 2, 'b': 3}),
+    ("a > 5 && b", {'a': 2, 'b': 3}),
+    ("a || b", {'a': 0, 'b': 'fallback'}),
+    ("a ? 'yes' : 'no'", {'a': ''}),
+    ("!a && -b < 0", {'a': False, 'b': 2}),
+    ("user.name + ' ' + user['surname']", {'user': {'name': 'Ada', 'surname': 'Lovelace'}}),
+    ("items[1] + items[5 - 5]", {'items': [10, 20]}),
+    ("{'a': x, 'b': [x, x]}", {'x': 1}),
+    ("'b' in {'a': 1, 'b': 2} && 2 in [1, 2]", {}),
+    ("items.map(x, x * 2)", {'items': [1, 2, 3]}),
+    ("items.filter(x, x > 1).map(y, y + n)", {'items': [1, 2, 3], 'n': 10}),
+    ("items.all(x, x > 0) && items.exists(x, x == 2) && items.exists_one(x, x > 2)", {'items': [1, 2, 3]}),
+    ("m.all(k, k != 'z')", {'m': {'a': 1, 'b': 2}}),
+    ("rows.map(r, r.exists(x, x > 1) && r.all(y, y > n))", {'rows': [[1, 2], [3], [0]], 'n': 0}),
+    ("rows.map(r, r.map(x, [r, x]))", {'rows': [[1, 2], [3]]}),
+    ("items.map(x, size(x) + x.map(x, x * 2)[0])", {'items': [[1], [1, 2]]}),
+    ("size(items.filter(x, x > 1)) == 2", {'items': [1, 2, 3]}),
+    ("has(user.email) ? user.email : 'none'", {'user': {}}),
+    ("get(m, 'missing', 0) + 1", {'m': {}}),
+]
+
+
+@pytest.mark.parametrize("expression,context", OPTIMIZE_CASES)
+def test_optimized_program_matches_interpreter(expression, context):
+    expected = cel.evaluate(expression, context)
+    assert cel.Program.compile(expression, optimize=True).evaluate(context) == expected
+
+
+@pytest.mark.parametrize("expression,context", [
+    ("user.email", {'user': {}}),
+    ("a - 'b'", {'a': 1}),
+    ("'x' * 2", {}),
+    ("items.map(x, x.missing)", {'items': [{}]}),
+    ("1 < 'a'", {}),
+    # Calls are left to the interpreter, with their arithmetic still checked
+    ("string(1 / n)", {'n': 0}),
+    ("string(n + 9223372036854775807)", {'n': 1}),
+])
+def test_optimized_program_errors_match_interpreter(expression, context):
+    expected = cel.evaluate(expression, context, on_error="return")
+    result = cel.Program(expression, optimize=True).evaluate(context, on_error="return")
+    assert isinstance(result, cel.EvalError)
+    assert result.message == expected.message
+
+
+def test_optimized_program_short_circuits():
+    program = cel.Program.compile("a && boom()", optimize=True)
+
+    def boom():
+        raise RuntimeError("should not be called")
+
+    assert program.evaluate({'a': False, 'boom': boom}) == False
+
+
+def test_optimized_program_reused_with_options():
+    program = cel.Program.compile("user.email", optimize=True)
+    assert program.optimized
+    assert program.evaluate({'user': {'email': 'a@b.c'}}) == 'a@b.c'
+    assert program.evaluate({'user': {}}, safe_navigation=True) is None
+    with pytest.raises(ValueError):
+        program.evaluate({'user': {}})
+
+
+def test_optimize_defaults_off():
+    assert not cel.Program("1 + 1").optimized
+    assert not cel.Program.compile("1 + 1").optimized
+
+
+@pytest.mark.parametrize("expression", [
+    "items.map(x, x * 2)",
+    "items.filter(x, x % 3 == 0).map(x, [x, n])",
+    "items.all(x, x >= 0) && !items.all(x, x < 500)",
+    "items.map(x, items.filter(y, y == x).size())",
+    "items.map(x, {'v': x}).filter(m, m.v > n).size()",
+])
+def test_parallel_comprehensions_match_sequential(expression):
+    context = {'items': list(range(1000)), 'n': 10}
+    expected = cel.evaluate(expression, context)
+    program = cel.Program(expression, optimize=True, parallel_threshold=100)
+    assert program.evaluate(context) == expected
+
+
+def test_parallel_comprehension_errors_match_sequential():
+    context = {'items': [1, 2, 'three', 4, 'five'] * 50}
+    expression = "items.map(x, x + 1)"
+    expected = cel.evaluate(expression, context, on_error="return")
+    program = cel.Program(expression, optimize=True, parallel_threshold=10)
+    assert program.evaluate(context, on_error="return").message == expected.message
+    # all() stops at the first false, before reaching the error
+    program = cel.Program("items.all(x, x < 2)", optimize=True, parallel_threshold=10)
+    assert program.evaluate(context) == False
+
+
+def test_parallel_comprehension_with_python_function():
+    program = cel.Program("items.map(x, double(x))", optimize=True, parallel_threshold=10)
+    assert program.evaluate({'items': list(range(100)), 'double': lambda x: x * 2}) == list(range(0, 200, 2))
+
+
+def test_parallel_threshold_requires_optimize():
+    with pytest.raises(ValueError, match="optimized"):
+        cel.Program("items.map(x, x)", parallel_threshold=10)
+
+
+@pytest.mark.parametrize(
+    "expression, value",
+    [
+        ("1 + 2 * 3", 7),
+        ("[1, 2.5, 'a'] + ['b']", [1, 2.5, "a", "b"]),
+        ("{'a': {'b': true}}.a.b", True),
+        ("b'a' + b'b'", b"ab"),
+        ("1 < 2 ? 'yes' : 'no'", "yes"),
+    ],
+)
+def test_constant_value(expression, value):
+    program = cel.Program(expression)
+    assert program.is_constant()
+    assert program.constant_value() == value
+    assert program.constant_value() == value
+
+
+@pytest.mark.parametrize("expression", ["x", "x + 1", "size('ab')", "[1, 2].map(x, x)", "has(a.b)"])
+def test_expressions_referring_to_variables_or_functions_are_not_constant(expression):
+    program = cel.Program(expression)
+    assert not program.is_constant()
+    with pytest.raises(ValueError, match="isn't constant"):
+        program.constant_value()
+
+
+def test_constant_value_errors_and_modes():
+    with pytest.raises(ValueError, match="No such key: b"):
+        cel.Program("{'a': 1}.b").constant_value()
+    program = cel.Program("1 + 1.0")
+    assert program.constant_value() == 2.0
+    with pytest.raises(ValueError, match="numeric promotion"):
+        program.constant_value(mode="strict")
+
+
+def test_name():
+    program = cel.Program("user.role == 'admin'", name="rbac.allow_admin")
+    assert program.name == "rbac.allow_admin"
+    assert program.source == "user.role == 'admin'"
+    assert repr(program) == "Program(\"user.role == 'admin'\", name='rbac.allow_admin')"
+    assert cel.Program("1").name is None
+    assert cel.Program.compile("1", name="one").name == "one"
+
+
+def test_name_is_in_errors():
+    program = cel.Program("user.role == 'admin'", name="rbac.allow_admin")
+    with pytest.raises(
+        ValueError,
+        match="Failed to evaluate program 'rbac.allow_admin', expression 'user.role == 'admin'': ",
+    ):
+        program.evaluate({"user": {}})
+
+    error = program.evaluate({"user": {}}, on_error="return")
+    assert error.name == "rbac.allow_admin"
+    assert "name='rbac.allow_admin'" in repr(error)
+    assert cel.Program("{}.a").evaluate(on_error="return").name is None
+
+    with pytest.raises(ValueError, match="Failed to compile program 'broken', expression '1 \\+': "):
+        cel.Program("1 +", name="broken")
+
+
+def test_name_is_in_errors_of_other_entry_points():
+    program = cel.Program("x.missing", name="policy")
+    with pytest.raises(ValueError, match="program 'policy'"):
+        program.evaluate({"x": {}}, coverage=cel.Coverage())
+    with pytest.raises(ValueError, match="program 'policy'"):
+        program.evaluate({"x": {}}, cache=True)
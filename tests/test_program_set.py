@@ -0,0 +1,127 @@
+import pytest
+
+import cel
+
+
+@pytest.fixture
+def rules():
+    return cel.compile_many(
+        {
+            "is_owner": "resource.owner == user.id",
+            "is_admin": "'admin' in user.roles",
+            "recent": "size(resource.tags.filter(t, startsWith(t, prefix))) > 0",
+        }
+    )
+
+
+def test_evaluate_all(rules):
+    context = {
+        "user": {"id": "u1", "roles": ["admin"]},
+        "resource": {"owner": "u2", "tags": ["new"]},
+        "prefix": "n",
+        "startsWith": lambda tag, prefix: tag.startswith(prefix),
+    }
+    assert rules.evaluate_all(context) == {"is_owner": False, "is_admin": True, "recent": True}
+
+
+def test_names_and_lookup(rules):
+    assert rules.names == ["is_owner", "is_admin", "recent"]
+    assert len(rules) == 3
+    assert "is_admin" in rules
+    assert "missing" not in rules
+    assert isinstance(rules["is_admin"], cel.Program)
+    assert rules["is_admin"].name == "is_admin"
+    with pytest.raises(KeyError):
+        rules["missing"]
+    assert repr(rules) == "ProgramSet(['is_owner', 'is_admin', 'recent'])"
+
+
+def test_combined_references(rules):
+    assert rules.variables == ["prefix", "resource", "user"]
+    assert rules.functions == ["size", "startsWith"]
+
+
+def test_pairs_and_programs():
+    program = cel.Program("x + 1")
+    rules = cel.compile_many([("b", "x * 2"), ("a", program)])
+    assert rules.names == ["b", "a"]
+    assert rules["a"] is program
+    assert rules.evaluate_all({"x": 2}) == {"b": 4, "a": 3}
+
+
+def test_errors_name_the_rule():
+    rules = cel.compile_many({"ok": "1 + 1", "broken": "1 / x"})
+    with pytest.raises(ValueError, match="broken"):
+        rules.evaluate_all({"x": 0})
+
+    results = rules.evaluate_all({"x": 0}, on_error="return")
+    assert results["ok"] == 2
+    assert isinstance(results["broken"], cel.EvalError)
+    assert results["broken"].name == "broken"
+
+
+def test_compile_errors_name_the_rule():
+    with pytest.raises(ValueError, match="bad"):
+        cel.compile_many({"good": "1", "bad": "1 +"})
+
+
+def test_duplicate_names():
+    with pytest.raises(ValueError, match="'a' is used by more than one expression"):
+        cel.compile_many([("a", "1"), ("a", "2")])
+
+
+def test_options():
+    rules = cel.compile_many({"sum": "1 + x"}, optimize=True)
+    assert rules["sum"].optimized
+    with pytest.raises(ValueError):
+        rules.evaluate_all({"x": 2.0}, mode="strict")
+    assert isinstance(rules.evaluate_all({"x": 2}, output="cel")["sum"], cel.Value)
+
+
+def test_project_context():
+    rules = cel.compile_many(
+        {
+            "owner": "resource.owner == user.id",
+            "tagged": "resource.tags.exists(t, t == tag)",
+            "named": "has(user.name) && upper(user.name) == 'ADA'",
+        }
+    )
+    upper = str.upper
+    context = {
+        "user": {"id": "u1", "name": "ada", "history": list(range(1000))},
+        "resource": {"owner": "u1", "tags": ["a"], "body": "x" * 10_000, "meta": {"size": 1}},
+        "tag": "a",
+        "unused": [{"big": True}] * 100,
+        "upper": upper,
+    }
+    projected = rules.project_context(context)
+    assert projected == {
+        "user": context["user"],
+        "resource": {"owner": "u1", "tags": ["a"]},
+        "tag": "a",
+        "upper": upper,
+    }
+    assert projected["resource"]["tags"] is context["resource"]["tags"]
+    assert rules.evaluate_all(projected) == rules.evaluate_all(context)
+
+
+def test_project_context_keeps_nested_fields_and_skips_missing_ones():
+    rules = cel.compile_many({"a": "request.auth.claims.sub == 'x'", "b": "request.auth.token != ''"})
+    context = {
+        "request": {"auth": {"claims": {"sub": "x", "iat": 1}, "token": "t", "other": 1}, "body": b""},
+    }
+    assert rules.project_context(context) == {
+        "request": {"auth": {"claims": {"sub": "x"}, "token": "t"}},
+    }
+    assert rules.project_context({"request": {"auth": object()}})["request"]["auth"] is not None
+    assert rules.project_context({}) == {}
+
+    whole = cel.compile_many({"a": "request.auth.token != ''", "b": "size(request) > 0"})
+    assert whole.project_context(context) == context
+
+
+def test_sets_can_be_created_directly():
+    rules = cel.ProgramSet([("double", "x * 2"), ("half", "x / 2.0")], optimize=True)
+    assert rules.names == ["double", "half"]
+    assert rules["double"].optimized
+    assert rules.evaluate_all({"x": 3}) == {"double": 6, "half": 1.5}
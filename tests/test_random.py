@@ -0,0 +1,81 @@
+import pytest
+
+import cel
+
+
+def test_rand_is_between_0_and_1():
+    values = [cel.evaluate("rand()") for _ in range(100)]
+    assert all(0.0 <= value < 1.0 for value in values)
+    assert len(set(values)) > 90
+
+
+def test_rand_draws_a_new_number_for_each_call():
+    assert cel.evaluate("[1, 2, 3].map(x, rand())", cel.Context(seed=1)) == cel.evaluate(
+        "[rand(), rand(), rand()]", cel.Context(seed=1)
+    )
+    assert len(set(cel.evaluate("[rand(), rand(), rand()]"))) == 3
+
+
+def test_a_seed_makes_evaluations_reproducible():
+    context = cel.Context(seed=42)
+    assert context.seed == 42
+    first = cel.evaluate("[rand(), rand(), sample(0.5)]", context)
+    assert cel.evaluate("[rand(), rand(), sample(0.5)]", context) == first
+    assert cel.evaluate("[rand(), rand(), sample(0.5)]", cel.Context(seed=42)) == first
+    assert cel.evaluate("[rand(), rand(), sample(0.5)]", cel.Context(seed=43)) != first
+    assert cel.Context().seed is None
+
+
+def test_seeded_draws_are_the_same_with_an_optimized_program():
+    items = list(range(1000))
+    context = cel.Context({"items": items}, seed=7)
+    expected = cel.evaluate("items.map(x, rand())", context)
+    program = cel.Program("items.map(x, rand())", optimize=True, parallel_threshold=10)
+    assert program.evaluate(context) == expected
+
+
+def test_sample():
+    assert cel.evaluate("sample(1)") is True
+    assert cel.evaluate("sample(0.0)") is False
+    hits = sum(cel.evaluate("sample(0.25)") for _ in range(2000))
+    assert 350 < hits < 650
+
+
+@pytest.mark.parametrize("probability", ["1.5", "-0.1"])
+def test_sample_needs_a_probability(probability):
+    with pytest.raises(ValueError, match="probability must be between 0 and 1"):
+        cel.evaluate(f"sample({probability})")
+    with pytest.raises(ValueError, match="expected a probability, got string"):
+        cel.evaluate("sample('half')")
+
+
+def test_bucket_is_stable():
+    # The first 8 bytes of the SHA-256 hash of the id, salted with the seed, mod n
+    assert cel.evaluate("bucket('user-1', 100)") == 94
+    assert cel.evaluate("bucket('user-1', 100)", cel.Context(seed=1)) == 47
+    assert cel.evaluate("bucket(42, 1000) == bucket('42', 1000)") is True
+    assert cel.evaluate("bucket(b'42', 1000) == bucket('42', 1000)") is True
+
+
+def test_buckets_are_spread_evenly():
+    ids = [f"user-{i}" for i in range(4000)]
+    buckets = cel.evaluate("ids.map(id, bucket(id, 4))", {"ids": ids})
+    assert all(800 < buckets.count(bucket) < 1200 for bucket in range(4))
+
+
+@pytest.mark.parametrize(
+    "expression,message",
+    [
+        ("bucket('a', 0)", "can't make 0 buckets"),
+        ("bucket('a', -2)", "can't make -2 buckets"),
+        ("bucket('a', 'ten')", "expected a number of buckets, got string"),
+        ("bucket(1.5, 10)", "can't bucket float"),
+    ],
+)
+def test_bucket_checks_its_arguments(expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression)
+
+
+def test_sandboxed_expressions_can_roll_out():
+    assert cel.sandbox.evaluate("bucket(id, 100) < 100", {"id": "u"}) is True
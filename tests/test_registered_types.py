@@ -0,0 +1,228 @@
+import collections
+import dataclasses
+
+import pytest
+
+import cel
+
+
+@dataclasses.dataclass
+class Money:
+    cents: int
+    currency: str = "NZD"
+
+
+class Version:
+    def __init__(self, major, minor=0):
+        self.major = major
+        self.minor = minor
+
+    def __eq__(self, other):
+        return (self.major, self.minor) == (other.major, other.minor)
+
+
+Point = collections.namedtuple("Point", ["x", "y"])
+
+cel.register_type("shop.Money", Money)
+cel.register_type("Version", Version, fields={"major", "minor"})
+cel.register_type("geo.plane.Point", Point)
+
+
+def test_values_have_the_registered_type():
+    context = {"price": Money(150), "v": Version(1, 2)}
+    assert cel.evaluate("type(price) == shop.Money", context) is True
+    assert cel.evaluate("type(price) == map", context) is False
+    assert cel.evaluate("type(v) == Version", context) is True
+    assert cel.evaluate("type(price)", context) == cel.CelType("shop.Money")
+
+
+def test_fields_can_be_selected():
+    context = {"price": Money(150), "prices": [Money(1), Money(2)]}
+    assert cel.evaluate("price.cents + 1", context) == 151
+    assert cel.evaluate("has(price.currency)", context) is True
+    assert cel.evaluate("prices.map(p, p.cents)", context) == [1, 2]
+
+
+def test_values_are_returned_as_instances():
+    assert cel.evaluate("price", {"price": Money(150)}) == Money(150)
+    assert cel.evaluate("[v]", {"v": Version(1, 2)}) == [Version(1, 2)]
+    assert cel.evaluate("p", {"p": Point(1, 2)}) == Point(1, 2)
+
+
+def test_construction():
+    assert cel.evaluate("shop.Money{cents: 5}") == Money(5)
+    assert cel.evaluate("Version{major: 3, minor: 1}") == Version(3, 1)
+    assert cel.evaluate("geo.plane.Point{x: 1, y: 2}.y") == 2
+    assert cel.evaluate("shop.Money{cents: 5} == shop.Money{cents: 5}") is True
+    assert cel.evaluate("type(shop.Money{cents: 5}) == shop.Money") is True
+
+
+def test_construction_errors():
+    with pytest.raises(ValueError, match="has no field bogus"):
+        cel.evaluate("shop.Money{bogus: 1}")
+    with pytest.raises(ValueError, match="want 'a registered type'"):
+        cel.evaluate("int{value: 1}")
+
+
+def test_types_are_distinct_from_maps():
+    context = {"price": Money(150, "NZD")}
+    assert cel.evaluate("price == {'cents': 150, 'currency': 'NZD'}", context) is False
+    assert cel.evaluate("price == shop.Money{cents: 150, currency: 'NZD'}", context) is True
+
+
+def test_functions_receive_instances():
+    context = cel.Context({"price": Money(150)}, functions={"cents": lambda m: m.cents})
+    assert cel.evaluate("cents(price)", context) == 150
+    context = cel.Context(functions={"make": lambda c: Money(c)})
+    assert cel.evaluate("make(3).cents", context) == 3
+
+
+@pytest.mark.parametrize("name, message", [
+    ("1x", "isn't a valid type name"),
+    ("a..b", "isn't a valid type name"),
+    ("int", "hide the builtin type"),
+    ("google.Thing", "hide the builtin type"),
+    ("shop", "both a type and a namespace"),
+    ("shop.Money.Cents", "both a type and a namespace"),
+])
+def test_invalid_names(name, message):
+    with pytest.raises(ValueError, match=message):
+        cel.register_type(name, Money)
+
+
+def test_fields_are_required_for_plain_classes():
+    with pytest.raises(ValueError, match="fields of Version must be given"):
+        cel.register_type("other.Version", Version)
+
+
+def test_type_names_are_declared():
+    assert cel.diagnose("type(p) == shop.Money && type(p) != int", {"p": Money(1)}) == []
+
+
+@dataclasses.dataclass
+class User:
+    first: str
+    last: str
+
+    def display_name(self, separator=" "):
+        return self.first + separator + self.last
+
+    def initials(self):
+        return [self.first[0], self.last[0]]
+
+    def fail(self):
+        raise RuntimeError("profile unavailable")
+
+
+cel.register_type("acme.User", User, methods=["display_name", "initials", "fail"])
+
+
+def test_registered_methods_can_be_called():
+    context = {"user": User("Ada", "Lovelace")}
+    assert cel.evaluate("user.display_name()", context) == "Ada Lovelace"
+    assert cel.evaluate("user.display_name('_')", context) == "Ada_Lovelace"
+    assert cel.evaluate("user.initials()", context) == ["A", "L"]
+    assert cel.evaluate("acme.User{first: 'G', last: 'H'}.display_name()") == "G H"
+    assert cel.evaluate("[user].map(u, u.display_name())", context) == ["Ada Lovelace"]
+
+
+def test_registered_methods_in_compiled_programs():
+    program = cel.Program("users.map(u, u.initials()[0])", optimize=True, parallel_threshold=10)
+    users = [User(str(i), "x") for i in range(200)]
+    assert program.evaluate({"users": users}) == [str(i)[0] for i in range(200)]
+
+
+def test_registered_methods_only_apply_to_their_type():
+    with pytest.raises(ValueError, match="map has no method display_name"):
+        cel.evaluate("m.display_name()", {"m": {"first": "Ada"}})
+    with pytest.raises(ValueError, match="must be called as a method"):
+        cel.evaluate("display_name(user)", {"user": User("Ada", "Lovelace")})
+
+
+def test_errors_raised_by_registered_methods():
+    with pytest.raises(ValueError, match="profile unavailable"):
+        cel.evaluate("user.fail()", {"user": User("Ada", "Lovelace")})
+
+
+@pytest.mark.parametrize("methods, message", [
+    (["size"], "would replace the builtin"),
+    (["missing"], "User has no method 'missing'"),
+])
+def test_invalid_methods(methods, message):
+    with pytest.raises(ValueError, match=message):
+        cel.register_type("acme.Other", User, methods=methods)
+
+
+def test_registered_methods_are_declared():
+    assert cel.diagnose("user.display_name()", {"user": User("Ada", "Lovelace")}) == []
+
+
+def test_values_only_show_their_fields():
+    context = {"price": Money(150), "key": "@type"}
+    for optimize in (False, True):
+        def evaluate(expression):
+            return cel.Program(expression, optimize=optimize).evaluate(context)
+        assert evaluate("size(price)") == 2
+        assert evaluate("price.size()") == 2
+        assert sorted(evaluate("price.map(k, k)")) == ["cents", "currency"]
+        assert evaluate("price.exists(k, k == '@type')") is False
+        assert evaluate("price['@type']") is None
+        assert evaluate("price[key]") is None
+        assert evaluate("price['cents']") == 150
+
+
+@dataclasses.dataclass(order=True)
+class Rank:
+    level: int
+
+    def __add__(self, other):
+        return Rank(self.level + other.level)
+
+
+cel.register_type("acme.Rank", Rank)
+
+
+@pytest.mark.parametrize("optimize", [False, True])
+def test_operators_are_the_classes_own(optimize):
+    context = {"a": Rank(1), "b": Rank(2)}
+    assert cel.Program("a + b", optimize=optimize).evaluate(context) == Rank(3)
+    assert cel.Program("a < b && !(b <= a)", optimize=optimize).evaluate(context) is True
+
+
+@pytest.mark.parametrize("expression, message", [
+    ("p + q", r"unsupported operand type\(s\) for \+: 'Money' and 'Money'"),
+    ("p + {'cents': 1}", r"unsupported operand type\(s\) for \+: 'Money' and 'dict'"),
+    ("q < p", "'<' not supported between instances of 'Money' and 'Money'"),
+])
+@pytest.mark.parametrize("optimize", [False, True])
+def test_map_operators_are_not_applied(expression, message, optimize):
+    program = cel.Program(expression, optimize=optimize)
+    with pytest.raises(ValueError, match=message):
+        program.evaluate({"p": Money(5), "q": Money(3)})
+
+
+def test_values_cant_be_forged_with_maps():
+    with pytest.raises(ValueError, match="'@type' can't be a map key"):
+        cel.evaluate("{'@type': shop.Money, 'cents': 1}")
+    forged = "{key: shop.Money, 'cents': 1}"
+    assert cel.evaluate(f"type({forged}) == map", {"key": "@type"}) is True
+    context = {"m": {"@type": cel.CelType("shop.Money"), "cents": 1}}
+    assert cel.evaluate("type(m) == map && size(m) == 2", context) is True
+
+
+def test_values_encode_as_json_without_their_type():
+    assert cel.evaluate("json.encode(m)", {"m": Money(150)}) == '{"cents":150,"currency":"NZD"}'
+
+
+@pytest.mark.parametrize("expression, expected", [
+    ("sum(p)", 3),
+    ("avg(p)", 1.5),
+    ("min(p)", 1),
+    ("p.max()", 2),
+    ("p.get('@type')", None),
+    ("p.get('@type', 0)", 0),
+    ("p.get('x')", 1),
+])
+def test_aggregates_and_get_ignore_the_type(expression, expected):
+    assert cel.evaluate(expression, {"p": Point(1, 2)}) == expected
+    assert cel.Program(expression, optimize=True).evaluate({"p": Point(1, 2)}) == expected
@@ -0,0 +1,79 @@
+import collections
+
+import pytest
+
+import cel
+
+
+@pytest.fixture
+def user():
+    return {"name": "Ada", "tags": ["admin"], "profile": {"country": "NZ"}}
+
+
+def test_off_by_default(user):
+    context = cel.Context({"user": user})
+    assert not context.round_trip
+    result = cel.evaluate("user", context)
+    assert result == user
+    assert result is not user
+
+
+def test_unchanged_values_are_returned_as_the_original_objects(user):
+    context = cel.Context({"user": user}, round_trip=True)
+    assert context.round_trip
+    assert cel.evaluate("user", context) is user
+    assert cel.evaluate("user.profile", context) is user["profile"]
+    assert cel.evaluate("user.tags", context) is user["tags"]
+
+
+def test_new_values_hold_the_original_objects(user):
+    context = cel.Context({"user": user}, round_trip=True)
+    result = cel.evaluate("[user, {'tags': user.tags}]", context)
+    assert result[0] is user
+    assert result[1]["tags"] is user["tags"]
+
+
+def test_changed_values_are_converted(user):
+    context = cel.Context({"user": user}, round_trip=True)
+    result = cel.evaluate("user.tags + ['staff']", context)
+    assert result == ["admin", "staff"]
+    assert cel.evaluate("user.tags.filter(t, true)", context) is not user["tags"]
+
+
+def test_types_that_would_not_round_trip_are_kept():
+    Point = collections.namedtuple("Point", ["x", "y"])
+    point, pair = Point(1, 2), (3, 4)
+    context = cel.Context({"point": point, "pair": pair}, round_trip=True)
+    assert cel.evaluate("point", context) is point
+    assert cel.evaluate("pair", context) is pair
+    assert cel.evaluate("[point, pair]", context) == [point, pair]
+
+
+def test_originals_win_over_output_types(user):
+    context = cel.Context({"user": user}, round_trip=True, output_types={"map": "mappingproxy"})
+    assert cel.evaluate("user", context) is user
+    assert type(cel.evaluate("{'a': 1}", context)).__name__ == "mappingproxy"
+
+
+def test_updated_and_removed_variables(user):
+    context = cel.Context({"user": user}, round_trip=True)
+    replacement = {"name": "Grace"}
+    context.update({"user": replacement})
+    assert cel.evaluate("user", context) is replacement
+    other = {"name": "Alan"}
+    context.update_variable("user", other)
+    assert cel.evaluate("user", context) is other
+    context.add_variable("count", 1)
+    context.remove_variable("user")
+    assert cel.evaluate("count", context) == 1
+
+
+def test_programs_and_evaluators(user):
+    context = cel.Context({"user": user}, round_trip=True)
+    assert cel.Program("user.profile").evaluate(context) is user["profile"]
+    with cel.Evaluator() as evaluator:
+        assert evaluator.submit("user", context).result(timeout=5) is user
+
+
+def test_dicts_passed_directly_are_converted(user):
+    assert cel.evaluate("user", {"user": user}) is not user
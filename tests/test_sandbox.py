@@ -0,0 +1,161 @@
+import time
+
+import pytest
+
+import cel
+import cel.sandbox
+
+
+def test_sandboxed_evaluation():
+    assert cel.sandbox.evaluate("user.age >= 18", {"user": {"age": 21}}) is True
+    assert cel.sandbox.evaluate("items.filter(x, x > 1).map(x, x * 2)", {"items": [1, 2, 3]}) == [4, 6]
+    assert cel.sandbox.evaluate("1 + 1") == 2
+
+
+def test_limit_exceeded_is_a_value_error():
+    assert issubclass(cel.sandbox.LimitExceeded, ValueError)
+
+
+def test_expression_length_limit():
+    with pytest.raises(cel.sandbox.LimitExceeded, match="1001 characters long, the limit is 1000"):
+        cel.sandbox.evaluate("1" + " " * 1000)
+    assert cel.sandbox.evaluate("x" + " " * 20, {"x": 1}, max_length=30) == 1
+
+
+def test_nesting_limit():
+    expression = "[" * 60 + "]" * 60
+    with pytest.raises(cel.sandbox.LimitExceeded, match="nested 60 deep, the limit is 50"):
+        cel.sandbox.evaluate(expression)
+    assert cel.sandbox.evaluate(expression, max_depth=60) is not None
+
+
+def test_cost_limit():
+    items = list(range(100))
+    with pytest.raises(cel.sandbox.LimitExceeded, match="cost limit"):
+        cel.sandbox.evaluate("size(items.map(x, items.map(y, x * y)))", {"items": items}, max_cost=1000)
+    assert cel.sandbox.evaluate("size(items.map(x, items.map(y, x * y)))", {"items": items},
+                                max_cost=100 * 100 + 100) == 100
+
+
+def test_timeout():
+    items = list(range(2000))
+    with pytest.raises(cel.sandbox.LimitExceeded, match="timed out"):
+        cel.sandbox.evaluate("items.all(x, items.all(y, x + y >= 0))", {"items": items},
+                             max_cost=10 ** 9, timeout=0.05)
+
+
+def test_result_size_limit():
+    with pytest.raises(cel.sandbox.LimitExceeded, match="more than 10 items and bytes"):
+        cel.sandbox.evaluate("'abcdefghijk'", max_result_size=10)
+    with pytest.raises(cel.sandbox.LimitExceeded, match="more than 100 items and bytes"):
+        cel.sandbox.evaluate("[l, l, l, l]", {"l": list(range(30))}, max_result_size=100)
+    assert cel.sandbox.evaluate("[l, l, l]", {"l": list(range(30))}, max_result_size=100) == [list(range(30))] * 3
+
+
+def test_restricted_functions():
+    with pytest.raises(ValueError, match="function 'matches' is not available in the sandbox"):
+        cel.sandbox.evaluate("name.matches('a+')", {"name": "aaa"})
+
+
+def test_python_functions_are_not_allowed():
+    with pytest.raises(ValueError, match="doesn't allow Python functions"):
+        cel.sandbox.evaluate("1", {"f": lambda: 1})
+    context = cel.Context({"x": 1})
+    context.add_function("f", lambda: 1)
+    with pytest.raises(ValueError, match="doesn't allow Python functions"):
+        cel.sandbox.evaluate("x", context)
+
+
+def test_errors_are_raised_as_usual():
+    with pytest.raises(ValueError, match="Failed to compile"):
+        cel.sandbox.evaluate("1 +")
+    with pytest.raises(ValueError, match="Failed to evaluate"):
+        cel.sandbox.evaluate("missing")
+
+
+def test_sandbox_uses_the_context_settings():
+    context = cel.Context({"t": "2024-01-02"}, mode="strict", output_types={"timestamp": "iso"})
+    with pytest.raises(ValueError, match="RFC 3339"):
+        cel.sandbox.evaluate("timestamp(t)", context)
+    assert cel.sandbox.evaluate("timestamp('2024-01-02T00:00:00Z')", context) == "2024-01-02T00:00:00+00:00"
+
+
+def test_functions_can_be_passed_separately():
+    def twice(x):
+        return x * 2
+
+    assert cel.sandbox.evaluate("items.map(i, twice(i))", {"items": [1, 2]},
+                                functions={"twice": twice}) == [2, 4]
+    context = cel.Context({"x": 3})
+    assert cel.sandbox.evaluate("twice(x)", context, functions={"twice": twice}) == 6
+    # The context itself is left without the function
+    assert "twice" not in context.functions_info()
+    with pytest.raises(ValueError, match="function 'triple' is not available in the sandbox"):
+        cel.sandbox.evaluate("triple(1)", functions={"twice": twice})
+
+
+def test_functions_can_read_the_remaining_budget():
+    budgets = []
+
+    def record(x):
+        budgets.append((cel.sandbox.time_remaining(), cel.sandbox.cost_remaining()))
+        return x
+
+    cel.sandbox.evaluate("[1, 2].map(x, record(x))", functions={"record": record},
+                         max_cost=10, timeout=5.0)
+    (time_1, cost_1), (time_2, cost_2) = budgets
+    assert 0 < time_2 <= time_1 <= 5.0
+    assert (cost_1, cost_2) == (9, 8)
+
+
+def test_remaining_budget_outside_of_the_sandbox():
+    assert cel.sandbox.time_remaining() is None
+    assert cel.sandbox.cost_remaining() is None
+    assert cel.evaluate("f()", {"f": cel.sandbox.time_remaining}) is None
+
+
+def test_functions_that_overrun_the_timeout_fail_the_evaluation():
+    def slow(x):
+        time.sleep(0.1)
+        return x
+
+    def timing_out(x):
+        time.sleep(0.1)
+        raise TimeoutError("request timed out")
+
+    with pytest.raises(cel.sandbox.LimitExceeded, match="timed out"):
+        cel.sandbox.evaluate("slow(1) == 1", functions={"slow": slow}, timeout=0.05)
+    # Even when the result doesn't depend on the call
+    with pytest.raises(cel.sandbox.LimitExceeded, match="timed out"):
+        cel.sandbox.evaluate("slow(1) == 1 || true", functions={"slow": slow}, timeout=0.05)
+    with pytest.raises(cel.sandbox.LimitExceeded, match="timed out"):
+        cel.sandbox.evaluate("timing_out(1)", functions={"timing_out": timing_out}, timeout=0.05)
+    assert cel.sandbox.evaluate("slow(1)", functions={"slow": slow}, timeout=1.0) == 1
+
+
+def test_nested_sandboxed_evaluations_keep_their_own_budget():
+    def nested(x):
+        cel.sandbox.evaluate("1", timeout=10.0)
+        return cel.sandbox.time_remaining()
+
+    assert cel.sandbox.evaluate("nested(1) <= 1.0", functions={"nested": nested}, timeout=1.0) is True
+
+
+SECRET_TOKEN = "hunter2"
+
+
+class User:
+    name = "ada"
+
+
+@pytest.mark.parametrize("expression", [
+    "user.__init__.__globals__.SECRET_TOKEN",
+    "user.__class__.__name__",
+    "user.name",
+])
+def test_objects_read_by_attribute_are_refused(expression):
+    context = cel.Context({"user": User()}, objects="attributes")
+    with pytest.raises(ValueError, match="doesn't allow contexts with objects=\"attributes\""):
+        cel.sandbox.evaluate(expression, context)
+    with pytest.raises(ValueError, match="doesn't allow contexts with objects"):
+        cel.sandbox.evaluate(expression, context, functions={"f": len})
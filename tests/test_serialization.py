@@ -0,0 +1,125 @@
+import concurrent.futures
+import multiprocessing
+import pickle
+
+import pytest
+
+import cel
+
+EXPRESSIONS = [
+    "1 + 2 * 3 - 4 / 2 % 3",
+    "-9223372036854775807 - 1",
+    "18446744073709551615u",
+    "-2.5e10 < 1.0",
+    "'héllo' + \"\\n\" == b'bytes'",
+    "[1, 'a', null, true, false, [2.0]]",
+    "{'a': 1, 2: [x]}.a",
+    "x in [1, 2] ? !y : --z",
+    "a.b[0].c || has(a.d) && a.e != 3u",
+    "items.filter(i, i > 1).map(i, i * 2).size() >= 0",
+    "Msg{field: 1, other: 'x'}",
+    "timestamp('2024-01-02T03:04:05Z').getFullYear()",
+]
+
+
+@pytest.mark.parametrize("source", EXPRESSIONS)
+def test_round_trip(source):
+    program = cel.Program(source)
+    data = program.dumps()
+    assert isinstance(data, bytes)
+    loaded = cel.Program.loads(data)
+    assert loaded.source == source
+    assert not loaded.optimized
+    assert loaded.dumps() == data
+
+
+def test_loaded_programs_evaluate():
+    program = cel.Program.loads(cel.Program("x * 2 + size(items)").dumps())
+    assert program.evaluate({"x": 3, "items": [1, 2]}) == 8
+
+
+def test_optimized_programs_stay_optimized():
+    program = cel.Program("items.map(i, i + 1)", optimize=True, parallel_threshold=100)
+    loaded = cel.Program.loads(program.dumps())
+    assert loaded.optimized
+    assert loaded.evaluate({"items": list(range(200))}) == list(range(1, 201))
+    assert loaded.dumps() == program.dumps()
+
+
+def test_pickle():
+    program = cel.Program("a < b", optimize=True)
+    loaded = pickle.loads(pickle.dumps(program))
+    assert isinstance(loaded, cel.Program)
+    assert loaded.optimized
+    assert loaded.evaluate({"a": 1, "b": 2}) is True
+
+
+def test_programs_can_be_sent_to_worker_processes():
+    program = cel.Program("x * x")
+    context = multiprocessing.get_context("spawn")
+    with concurrent.futures.ProcessPoolExecutor(max_workers=2, mp_context=context) as pool:
+        results = list(pool.map(cel.Program.evaluate, [program] * 4, [{"x": x} for x in range(4)]))
+    assert results == [0, 1, 4, 9]
+
+
+@pytest.mark.parametrize("data,message", [
+    (b"", "not a serialized program"),
+    (b"PK\x03\x04", "not a serialized program"),
+    (b"CEL\x63", "incompatible version"),
+    (cel.Program("1 + 2").dumps()[:-1], "truncated"),
+    (cel.Program("1").dumps() + b"\x00", "unexpected data"),
+    (cel.Program("1").dumps()[:-3] + b"\x0c", "unknown expression"),
+])
+def test_invalid_data(data, message):
+    with pytest.raises(ValueError, match=message):
+        cel.Program.loads(data)
+
+
+def negations(depth):
+    """A serialized program of `true` negated until it is nested `depth` deep"""
+    header = cel.Program("1").dumps()[:-3]
+    return header + b"\x05\x00" * (depth - 1) + b"\x0a\x06"
+
+
+def test_deeply_nested_data_is_rejected():
+    # Read recursively, this would run out of stack rather than fail
+    with pytest.raises(ValueError, match="nested more than 250 deep"):
+        cel.Program.loads(negations(200_000))
+    with pytest.raises(ValueError, match="nested more than 250 deep"):
+        cel.Program.loads(negations(251))
+    assert cel.Program.loads(negations(250)).evaluate() is False
+
+
+def test_fingerprint_ignores_layout():
+    fingerprint = cel.Program("a + b > 1").fingerprint()
+    assert len(fingerprint) == 64
+    assert cel.Program("(a+b)>1 // total\n").fingerprint() == fingerprint
+    assert cel.Program("a + b > 1", optimize=True).fingerprint() == fingerprint
+    assert cel.Program.loads(cel.Program("a  +  b > 1").dumps()).fingerprint() == fingerprint
+
+
+def test_fingerprint_distinguishes_expressions():
+    sources = ["a + b > 1", "a + b > 2", "a - b > 1", "b + a > 1", "a + b >= 1", "'1'", "1", "1u", "b'1'"]
+    fingerprints = {cel.Program(source).fingerprint() for source in sources}
+    assert len(fingerprints) == len(sources)
+
+
+def test_fingerprint_is_stable():
+    assert cel.Program("a+b").fingerprint() == "ad7f42d50f72a73fbb56110869f3356d3b471a6a519a9c34e9d5dc78abb16677"
+
+
+def test_names_round_trip():
+    program = cel.Program("x > 1", name="limits.above_one")
+    loaded = pickle.loads(pickle.dumps(program))
+    assert loaded.name == "limits.above_one"
+    assert cel.Program.loads(cel.Program("x").dumps()).name is None
+
+
+def test_loads_the_first_version_of_the_format():
+    data = cel.Program("x").dumps()
+    # Version 1 had no flag for the name after the source, b"\x01x"
+    assert data[4:7] == b"\x01x\x00"
+    program = cel.Program.loads(b"CEL\x01" + data[4:6] + data[7:])
+    assert program.source == "x"
+    assert program.name is None
+    assert program.evaluate({"x": 3}) == 3
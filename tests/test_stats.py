@@ -0,0 +1,82 @@
+import threading
+
+import pytest
+
+import cel
+
+
+def delta(before, after):
+    counts = {key: after[key] - before[key] for key in ["compiles", "evaluations", "cache_hits", "cache_misses"]}
+    counts["errors"] = {
+        error_type: count - before["errors"].get(error_type, 0)
+        for error_type, count in after["errors"].items()
+        if count != before["errors"].get(error_type, 0)
+    }
+    return counts
+
+
+def test_counters():
+    stats = cel.stats()
+    assert set(stats) == {"compiles", "evaluations", "evaluation_seconds", "cache_hits", "cache_misses", "errors"}
+    assert isinstance(stats["evaluation_seconds"], float)
+    assert all(isinstance(count, int) for count in stats["errors"].values())
+
+
+def test_evaluations_are_counted():
+    before = cel.stats()
+    cel.evaluate("1 + 1")
+    program = cel.Program("x * 2")
+    for x in range(3):
+        program.evaluate({"x": x})
+    after = cel.stats()
+    assert delta(before, after) == {
+        "compiles": 2,
+        "evaluations": 4,
+        "cache_hits": 0,
+        "cache_misses": 0,
+        "errors": {},
+    }
+    assert after["evaluation_seconds"] > before["evaluation_seconds"]
+
+
+def test_cache_hits_and_misses():
+    program = cel.Program("x + 1")
+    before = cel.stats()
+    for x in [1, 1, 2, 1]:
+        program.evaluate({"x": x}, cache=True)
+    counts = delta(before, cel.stats())
+    assert (counts["cache_hits"], counts["cache_misses"], counts["evaluations"]) == (2, 2, 2)
+
+
+def test_errors_by_type():
+    before = cel.stats()
+    cel.evaluate("1 +", on_error="return")
+    cel.evaluate("{'a': 1}.b", on_error="return")
+    cel.evaluate("1 / 0", on_error="return")
+    cel.evaluate("missing", on_error="return")
+    cel.evaluate("1 + 2.0", mode="strict", on_error="return")
+    assert delta(before, cel.stats())["errors"] == {
+        "compile": 1,
+        "no_such_key": 1,
+        "function_error": 1,
+        "undeclared_reference": 1,
+        "rejected": 1,
+    }
+
+
+def test_counted_across_threads():
+    program = cel.Program("x")
+    before = cel.stats()
+    threads = [threading.Thread(target=lambda: [program.evaluate({"x": i}) for i in range(100)]) for _ in range(4)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+    assert delta(before, cel.stats())["evaluations"] == 400
+
+
+def test_failed_evaluations_raise_as_usual():
+    before = cel.stats()
+    with pytest.raises(ValueError):
+        cel.evaluate("1 / 0")
+    assert delta(before, cel.stats())["evaluations"] == 1
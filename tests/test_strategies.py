@@ -0,0 +1,110 @@
+import datetime
+import sys
+import types
+
+import pytest
+
+import cel
+from cel.testing.strategies import contexts, values
+
+
+class Strategy:
+    """Stands in for a Hypothesis strategy, recording how it was made"""
+
+    def __init__(self, name, *args, **kwargs):
+        self.call = (name, args, kwargs)
+
+    def __eq__(self, other):
+        return isinstance(other, Strategy) and self.call == other.call
+
+    def __repr__(self):
+        return f"Strategy{self.call!r}"
+
+
+@pytest.fixture
+def st():
+    # A stand-in for hypothesis.strategies, so the strategies made can be checked
+    module = types.ModuleType("hypothesis.strategies")
+    module.SearchStrategy = Strategy
+    for name in ["integers", "floats", "booleans", "text", "binary", "none", "datetimes", "timedeltas", "just", "lists", "dictionaries", "fixed_dictionaries", "one_of"]:
+        setattr(module, name, lambda *args, _name=name, **kwargs: Strategy(_name, *args, **kwargs))
+    package = types.ModuleType("hypothesis")
+    package.strategies = module
+    saved = {name: sys.modules.get(name) for name in ["hypothesis", "hypothesis.strategies"]}
+    sys.modules.update({"hypothesis": package, "hypothesis.strategies": module})
+    try:
+        yield module
+    finally:
+        for name, saved_module in saved.items():
+            if saved_module is None:
+                del sys.modules[name]
+            else:
+                sys.modules[name] = saved_module
+
+
+def test_values(st):
+    assert values("int") == st.integers(min_value=-(2**63), max_value=2**63 - 1)
+    assert values("uint") == st.integers(min_value=0, max_value=2**63 - 1)
+    assert values("string") == st.text()
+    assert values("list(double)") == st.lists(st.floats())
+    assert values(" map( string , list(bool) ) ") == st.dictionaries(st.text(), st.lists(st.booleans()))
+    assert values("google.protobuf.Timestamp") == st.datetimes(timezones=st.just(datetime.timezone.utc))
+    assert values("duration") == st.timedeltas(
+        min_value=datetime.timedelta(seconds=-315576000000), max_value=datetime.timedelta(seconds=315576000000)
+    )
+
+
+def test_dyn(st):
+    dyn = values("dyn")
+    assert dyn.call[0] == "one_of"
+    scalars, lists, maps = dyn.call[1][0]
+    assert st.text() in scalars.call[1][0]
+    assert lists == st.lists(scalars)
+    assert maps == st.dictionaries(st.text(), scalars)
+    assert values("list") == st.lists(dyn)
+
+
+def test_contexts(st):
+    own = st.just(3)
+    strategy = contexts({"age": "int", "user": {"name": "string", "tags": "list(string)"}, "n": own})
+    assert strategy == st.fixed_dictionaries(
+        {
+            "age": values("int"),
+            "user": st.fixed_dictionaries({"name": st.text(), "tags": st.lists(st.text())}),
+            "n": own,
+        }
+    )
+
+
+@pytest.mark.parametrize(
+    "declarations, error, message",
+    [
+        ({"x": "float"}, ValueError, "unknown type 'float' in the declaration of 'x'"),
+        ({"user": {"age": "list(int"}}, ValueError, r"unbalanced parentheses in type 'list\(int' in the declaration of 'user.age'"),
+        ({"m": "map(double, int)"}, ValueError, "map keys must be int, uint, bool or string"),
+        ({"m": "map(int)"}, ValueError, "wrong number of type parameters"),
+        ({"x": 3}, TypeError, "'x' must be declared with a type name, a dict of fields or a strategy, got 3"),
+    ],
+)
+def test_invalid_declarations(st, declarations, error, message):
+    with pytest.raises(error, match=message):
+        contexts(declarations)
+
+
+def test_with_hypothesis():
+    hypothesis = pytest.importorskip("hypothesis")
+    declarations = {"user": {"age": "int", "name": "string", "joined": "timestamp"}, "tags": "list(string)", "extra": "dyn"}
+    seen = []
+
+    @hypothesis.settings(max_examples=50, deadline=None)
+    @hypothesis.given(contexts(declarations))
+    def check(context):
+        assert isinstance(context["user"]["age"], int)
+        assert isinstance(context["user"]["joined"], datetime.datetime)
+        assert all(isinstance(tag, str) for tag in context["tags"])
+        # Every generated context can be evaluated with
+        assert cel.evaluate("size(tags) >= 0 && user.name == user.name", context) is True
+        seen.append(context)
+
+    check()
+    assert seen
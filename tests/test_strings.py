@@ -0,0 +1,80 @@
+import pytest
+
+import cel
+
+
+@pytest.fixture
+def strings():
+    return cel.Context(extensions=["strings"])
+
+
+def test_strings_is_opt_in():
+    with pytest.raises(ValueError, match="Undeclared reference to 'soundex'"):
+        cel.evaluate("strings.soundex('Robert')")
+
+
+@pytest.mark.parametrize(
+    "a,b,distance",
+    [("kitten", "sitting", 3), ("", "abc", 3), ("same", "same", 0), ("café", "cafe", 1)],
+)
+def test_levenshtein(strings, a, b, distance):
+    strings.update({"a": a, "b": b})
+    assert cel.evaluate("strings.levenshtein(a, b)", strings) == distance
+
+
+@pytest.mark.parametrize(
+    "a,b,similarity",
+    [("", "", 1.0), ("abc", "abc", 1.0), ("abc", "xyz", 0.0), ("kitten", "sitting", 4 / 7)],
+)
+def test_similarity(strings, a, b, similarity):
+    strings.update({"a": a, "b": b})
+    assert cel.evaluate("strings.similarity(a, b)", strings) == pytest.approx(similarity)
+
+
+@pytest.mark.parametrize(
+    "name,code",
+    [
+        ("Robert", "R163"),
+        ("Rupert", "R163"),
+        ("Rubin", "R150"),
+        ("Ashcraft", "A261"),
+        ("Ashcroft", "A261"),
+        ("Tymczak", "T522"),
+        ("Pfister", "P236"),
+        ("Honeyman", "H555"),
+        ("Lee", "L000"),
+        ("o'hara", "O600"),
+        ("123", ""),
+    ],
+)
+def test_soundex(strings, name, code):
+    strings.update({"name": name})
+    assert cel.evaluate("strings.soundex(name)", strings) == code
+
+
+def test_data_quality_rule(strings):
+    rows = [
+        {"name": "Jon Smith", "billing": "John Smith"},
+        {"name": "Jane Doe", "billing": "Bob Stone"},
+    ]
+    strings.update({"rows": rows})
+    rule = "rows.filter(r, strings.similarity(r.name, r.billing) < 0.8).map(r, r.name)"
+    assert cel.evaluate(rule, strings) == ["Jane Doe"]
+
+
+@pytest.mark.parametrize(
+    "expression,message",
+    [
+        ("strings.soundex(1)", "expected a string, got int"),
+        ("strings.similarity('a')", "expected 2, got 1"),
+    ],
+)
+def test_errors(strings, expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression, strings)
+
+
+def test_strings_functions_are_described(strings):
+    info = strings.functions_info()
+    assert info["strings.similarity"]["builtin"] is True
+    assert cel.diagnose("strings.soundex('a') == 'A000'", strings) == []
@@ -0,0 +1,160 @@
+import ast
+import importlib
+import inspect
+import pathlib
+import types
+
+import pytest
+
+import cel
+
+STUBS = pathlib.Path(__file__).parent.parent / "cel-stubs"
+NO_DEFAULT = object()
+# Made when pytest first looks them up, so only their names are checked
+FIXTURES = {"cel.testing": {"cel_mode", "cel_golden"}}
+
+
+def submodules(module, name):
+    """The names of `module` and of the modules within it, which are named
+    without their package at runtime"""
+    names = [name]
+    for attribute, value in vars(module).items():
+        if isinstance(value, types.ModuleType):
+            names.extend(submodules(value, f"{name}.{attribute}"))
+    return names
+
+
+def stub_path(module):
+    """The stub file of a module, `cel.testing` as testing/__init__.pyi"""
+    path = STUBS.joinpath(*module.split(".")[1:])
+    if path.is_dir() or module == "cel":
+        return path / "__init__.pyi"
+    return path.with_suffix(".pyi")
+
+
+def stub_definitions(module):
+    """The public functions, classes and constants of a module's stubs, by name,
+    with the names it re-exports from other modules among the constants"""
+    tree = ast.parse(stub_path(module).read_text())
+    functions, classes, constants = {}, {}, set()
+    for node in tree.body:
+        if isinstance(node, ast.AnnAssign) and not node.target.id.startswith("_"):
+            constants.add(node.target.id)
+        elif isinstance(node, ast.FunctionDef):
+            functions.setdefault(node.name, []).append(node)
+        elif isinstance(node, ast.ClassDef) and not node.name.startswith("_"):
+            classes[node.name] = node
+        elif isinstance(node, ast.ImportFrom) and node.level == 0:
+            constants.update(alias.asname for alias in node.names if alias.asname == alias.name)
+    return functions, classes, constants
+
+
+MODULES = submodules(cel, "cel")
+DEFINITIONS = {module: stub_definitions(module) for module in MODULES}
+
+
+def members(kind):
+    """`(module, name)` of each function or class of the stubs"""
+    return [(module, name) for module in MODULES for name in sorted(DEFINITIONS[module][kind])]
+
+
+def stub_parameters(function):
+    """The names and defaults of the parameters of a stub function"""
+    args = function.args
+    positional = args.posonlyargs + args.args
+    defaults = [NO_DEFAULT] * (len(positional) - len(args.defaults)) + args.defaults
+    parameters = [(arg.arg, default) for arg, default in zip(positional, defaults)]
+    if args.vararg:
+        parameters.append(("*" + args.vararg.arg, NO_DEFAULT))
+    for arg, default in zip(args.kwonlyargs, args.kw_defaults):
+        parameters.append((arg.arg, NO_DEFAULT if default is None else default))
+    return [(name, default if default is NO_DEFAULT else ast.literal_eval(default)) for name, default in parameters]
+
+
+def runtime_parameters(signature):
+    parameters = []
+    for parameter in signature.parameters.values():
+        name = "*" + parameter.name if parameter.kind is parameter.VAR_POSITIONAL else parameter.name
+        default = NO_DEFAULT if parameter.default is parameter.empty else parameter.default
+        parameters.append((name, default))
+    return parameters
+
+
+def check_signature(definitions, signature, skip_self=False):
+    expected = runtime_parameters(signature)
+    for definition in definitions:
+        parameters = stub_parameters(definition)
+        if skip_self:
+            parameters = parameters[1:]
+        assert [name for name, _ in parameters] == [name for name, _ in expected], definition.name
+        # Overloads may require what the implementation defaults, to narrow the result
+        for (name, default), (_, runtime_default) in zip(parameters, expected):
+            if default is not NO_DEFAULT or len(definitions) == 1:
+                assert default == runtime_default, f"{definition.name}({name})"
+
+
+def test_every_submodule_has_stubs():
+    assert {"cel.sandbox", "cel.testing", "cel.testing.strategies", "cel.conformance", "cel.fuzz"} <= set(MODULES)
+    for module in MODULES:
+        assert stub_path(module).is_file(), module
+
+
+@pytest.mark.parametrize("module", MODULES)
+def test_module_names_match(module):
+    runtime = importlib.import_module(module)
+    names = {
+        name
+        for name in dir(runtime)
+        if not name.startswith("_") and not isinstance(vars(runtime).get(name), types.ModuleType)
+    }
+    functions, classes, constants = DEFINITIONS[module]
+    assert names == set(functions) | set(classes) | constants
+
+
+@pytest.mark.parametrize("module, name", members(0))
+def test_function_signatures(module, name):
+    if name in FIXTURES.get(module, ()):
+        return
+    function = getattr(importlib.import_module(module), name)
+    check_signature(DEFINITIONS[module][0][name], inspect.signature(function))
+
+
+@pytest.mark.parametrize("module, name", members(1))
+def test_classes(module, name):
+    cls = getattr(importlib.import_module(module), name)
+    stub = DEFINITIONS[module][1][name]
+    assert [ast.unparse(base) for base in stub.bases] == [
+        base.__name__ for base in cls.__bases__ if base is not object
+    ]
+    if issubclass(cls, BaseException):
+        return
+
+    members = {}
+    for node in stub.body:
+        if isinstance(node, ast.FunctionDef):
+            members.setdefault(node.name, []).append(node)
+        elif isinstance(node, ast.AnnAssign):
+            members[node.target.id] = None
+
+    runtime = {member for member in vars(cls) if not member.startswith("_")}
+    assert runtime == {member for member in members if not member.startswith("_")}
+
+    for member, definitions in members.items():
+        assert hasattr(cls, member), member
+        if definitions is None or member.startswith("__") and member != "__init__":
+            continue
+        decorators = {ast.unparse(d) for definition in definitions for d in definition.decorator_list}
+        if "property" in decorators:
+            assert inspect.isdatadescriptor(inspect.getattr_static(cls, member)), member
+        elif member == "__init__":
+            check_signature(definitions, inspect.signature(cls), skip_self=True)
+        else:
+            check_signature(definitions, inspect.signature(getattr(cls, member)))
+
+
+@pytest.mark.parametrize("module, name", members(1))
+def test_constructors(module, name):
+    cls = getattr(importlib.import_module(module), name)
+    stub_init = any(isinstance(node, ast.FunctionDef) and node.name == "__init__" for node in DEFINITIONS[module][1][name].body)
+    if not issubclass(cls, BaseException):
+        assert stub_init == (cls.__text_signature__ is not None)
@@ -0,0 +1,70 @@
+import pytest
+
+import cel
+
+
+@pytest.mark.parametrize(
+    "expression, suggestions",
+    [
+        ("user.age >= 18 && user.age < 65", [{"user": {"age": 18}}]),
+        ("18 <= age", [{"age": 18}]),
+        ("x > 5 && x < 8 && x != 6", [{"x": 7}]),
+        ("price > 1.5", [{"price": 2.5}]),
+        ("n > 5u", [{"n": 6}]),
+        ("name > 'm'", [{"name": "ma"}]),
+        ("!(a < 3 || b)", [{"a": 3, "b": False}]),
+        ("verified", [{"verified": True}]),
+        ("status in ['active', 'trial']", [{"status": "active"}]),
+        ("account.closed_at == null", [{"account": {"closed_at": None}}]),
+    ],
+)
+def test_suggestions(expression, suggestions):
+    assert cel.suggest_inputs(expression) == suggestions
+
+
+def test_each_way_of_satisfying_the_expression():
+    expression = "role == 'admin' || (role in ['editor', 'viewer'] && !blocked)"
+    suggestions = cel.suggest_inputs(expression)
+    assert suggestions == [
+        {"role": "admin", "blocked": False},
+        {"role": "editor", "blocked": False},
+    ]
+    assert all(cel.evaluate(expression, variables) is True for variables in suggestions)
+
+
+def test_ternary():
+    assert cel.suggest_inputs("internal ? level > 1 : level > 5") == [
+        {"internal": True, "level": 2},
+        {"internal": False, "level": 6},
+    ]
+
+
+def test_strings():
+    expression = "name.startsWith('ab') && name.endsWith('z') && name != 'abz' && !name.contains('q')"
+    assert cel.suggest_inputs(expression) == [{"name": "abaz"}]
+    assert cel.suggest_inputs("email != null && email.endsWith('@example.com')") == [{"email": "@example.com"}]
+
+
+def test_unsatisfiable_expressions():
+    assert cel.suggest_inputs("x > 1 && x < 1") == []
+    assert cel.suggest_inputs("x == 1 && x == 'one'") == []
+    assert cel.suggest_inputs("false && x == 1") == []
+
+
+def test_suggestions_are_checked():
+    # Nothing is known about `tags`, so no suggestion evaluates to true
+    assert cel.suggest_inputs("size(tags) > 0 && x > 1") == []
+    # The other clause holds whatever x is
+    assert cel.suggest_inputs("[1, 2].exists(i, i > 1) && x > 1") == [{"x": 2}]
+
+
+def test_mode():
+    assert cel.suggest_inputs("x > 1.5", mode="strict") == [{"x": 2.5}]
+    # Strict mode has no numeric promotion, so an int x can't be added to 1.5
+    assert cel.suggest_inputs("x > 0 && x + 1.5 > 2") == [{"x": 1}]
+    assert cel.suggest_inputs("x > 0 && x + 1.5 > 2", mode="strict") == []
+
+
+def test_invalid_expressions():
+    with pytest.raises(ValueError):
+        cel.suggest_inputs("x >")
@@ -0,0 +1,91 @@
+import pytest
+
+import cel
+
+
+class Recorder:
+    """A Python function that records the arguments it is called with."""
+
+    def __init__(self):
+        self.calls = []
+
+    def __call__(self, value):
+        self.calls.append(value)
+        return value
+
+
+@pytest.mark.parametrize("mode", ["python", "strict"])
+@pytest.mark.parametrize("expression, expected, calls", [
+    ("true ? f('then') : f('else')", "then", ["then", "then"]),
+    ("false ? f('then') : f('else')", "else", ["else", "else"]),
+    ("n > 0 ? f('then') : f('else')", "then", ["then", "then"]),
+    ("n > 1 ? f('then') : n > 0 ? f('nested') : f('else')", "nested", ["nested", "nested"]),
+    ("[0, 1].map(x, x > 0 ? f(x) : 0)", [0, 1], [1, 1]),
+])
+def test_only_the_selected_branch_is_evaluated(mode, expression, expected, calls, evaluate_both_ways):
+    recorder = Recorder()
+    context = cel.Context({"n": 1}, functions={"f": recorder})
+    assert evaluate_both_ways(expression, context, mode=mode) == expected
+    assert recorder.calls == calls
+
+
+@pytest.mark.parametrize("expression", [
+    "true ? 1 : 1 / 0",
+    "false ? missing.field : 2",
+    "n == 1 ? 'one' : n / 0 == 1",
+])
+def test_errors_in_the_untaken_branch_are_not_raised(expression, evaluate_both_ways):
+    assert evaluate_both_ways(expression, {"n": 1}) in (1, 2, "one")
+
+
+def test_errors_in_the_condition_are_raised():
+    recorder = Recorder()
+    context = cel.Context(functions={"f": recorder})
+    with pytest.raises(ValueError, match="division by zero"):
+        cel.evaluate("1 / 0 == 0 ? f(1) : f(2)", context)
+    with pytest.raises(ValueError, match="division by zero"):
+        cel.Program("1 / 0 == 0 ? f(1) : f(2)", optimize=True).evaluate(context)
+    assert recorder.calls == []
+
+
+@pytest.mark.parametrize("condition, expected", [
+    ("1", "yes"),
+    ("0", "no"),
+    ("'text'", "yes"),
+    ("''", "no"),
+    ("[1]", "yes"),
+    ("[]", "no"),
+    ("{}", "no"),
+    ("null", "no"),
+])
+def test_python_mode_uses_truthiness(condition, expected, evaluate_both_ways):
+    assert evaluate_both_ways(f"{condition} ? 'yes' : 'no'", {}) == expected
+    assert evaluate_both_ways("c ? 'yes' : 'no'", {"c": cel.evaluate(condition)}) == expected
+
+
+@pytest.mark.parametrize("condition", ["1", "'text'", "[]", "null"])
+def test_strict_mode_rejects_literal_non_bool_conditions(condition):
+    with pytest.raises(ValueError, match="the condition of '\\?:' must be a bool"):
+        cel.evaluate(f"{condition} ? 'yes' : 'no'", mode="strict")
+
+
+@pytest.mark.parametrize("value", [1, "text", [], {"a": 1}])
+def test_strict_mode_rejects_non_bool_conditions_while_evaluating(value):
+    recorder = Recorder()
+    context = cel.Context({"c": value}, functions={"f": recorder})
+    for program in (cel.Program("c ? f(1) : f(2)"), cel.Program("c ? f(1) : f(2)", optimize=True)):
+        with pytest.raises(ValueError, match="want 'bool condition of '\\?:''"):
+            program.evaluate(context, mode="strict")
+    assert recorder.calls == []
+
+
+def test_strict_mode_allows_branches_of_different_types():
+    assert cel.evaluate("c ? 1 : 'one'", {"c": False}, mode="strict") == "one"
+
+
+def test_unknown_condition_evaluates_neither_branch():
+    recorder = Recorder()
+    context = cel.Context(functions={"f": recorder})
+    result = cel.evaluate("u ? f(1) : f(2)", context, unknowns=["u"])
+    assert isinstance(result, cel.Unknown)
+    assert recorder.calls == []
@@ -0,0 +1,144 @@
+import os
+import subprocess
+import sys
+import textwrap
+
+import pytest
+
+import cel
+from cel.testing import Golden, assert_evaluates, assert_trace, run_cases
+
+
+def test_assert_evaluates():
+    assert_evaluates("a + 1", {"a": 1}, 2)
+    assert_evaluates("[1, 2.5, true]", None, [1, 2.5, True])
+    assert_evaluates("1 / 0", None, ValueError)
+    assert_evaluates("1 + 1.5", None, ValueError, mode="strict")
+
+
+@pytest.mark.parametrize(
+    "expression, expected, message",
+    [
+        ("1 + 1", 3, "'1 + 1' evaluated to 2, expected 3"),
+        ("1 == 1", 1, "'1 == 1' evaluated to True, expected 1"),
+        ("2", 2.0, "'2' evaluated to 2, expected 2.0"),
+        ("{'a': [1]}", {"a": [True]}, "'{'a': [1]}' evaluated to {'a': [1]}, expected {'a': [True]}"),
+        ("1", ValueError, "expected '1' to raise ValueError, it evaluated to 1"),
+        ("x", 1, "'x' raised ValueError: Failed to evaluate expression 'x': Undeclared reference to 'x', expected 1"),
+        ("1 / 0", TypeError, "expected '1 / 0' to raise TypeError, it raised ValueError: "),
+    ],
+)
+def test_assert_evaluates_failures(expression, expected, message):
+    with pytest.raises(AssertionError) as error:
+        assert_evaluates(expression, None, expected)
+    assert str(error.value).startswith(message)
+
+
+def test_run_cases():
+    run_cases(
+        [
+            ("age >= 18", {"age": 21}, True),
+            ("age >= 18", True),
+            {"name": "minor", "expression": "age < 18", "context": None, "expected": False},
+        ],
+        context={"age": 21},
+    )
+
+
+def test_run_cases_reports_every_failure():
+    cases = [
+        {"name": "adult", "expression": "age >= 18", "context": {"age": 15}, "expected": True},
+        ("age > 1", {"age": 2}, True),
+        ("age + 1", {"age": 2}, 4),
+    ]
+    with pytest.raises(AssertionError) as error:
+        run_cases(iter(cases))
+    assert str(error.value) == "\n".join(
+        [
+            "2 of 3 cases failed:",
+            "  adult: 'age >= 18' evaluated to False, expected True",
+            "  age + 1: 'age + 1' evaluated to 3, expected 4",
+        ]
+    )
+
+
+def test_run_cases_rejects_malformed_cases():
+    with pytest.raises(TypeError, match="case 2 has no 'expected'"):
+        run_cases([("1", 1), {"expression": "1"}])
+    with pytest.raises(TypeError, match="case 1 must be a dict, or a tuple"):
+        run_cases(["1 + 1"])
+
+
+POLICY = "user.age >= 18 && !user.banned"
+
+
+def test_assert_trace(tmp_path):
+    path = tmp_path / "traces" / "minor.txt"
+    context = {"user": {"age": 15, "banned": False}}
+    assert_trace(path, POLICY, context, update=True)
+    assert path.read_text() == "user.age >= 18 && !user.banned is False\n  user.age >= 18 is False (user.age = 15)\n"
+    assert_trace(path, POLICY, context)
+    assert_trace(str(path), POLICY, context)
+
+    with pytest.raises(AssertionError) as error:
+        assert_trace(path, POLICY, {"user": {"age": 20, "banned": True}})
+    message = str(error.value)
+    assert message.startswith(f"the trace of '{POLICY}' differs from {path}:")
+    assert "-  user.age >= 18 is False (user.age = 15)" in message
+    assert "+  !user.banned is False" in message
+
+
+def test_assert_trace_needs_the_golden_file(tmp_path):
+    with pytest.raises(AssertionError, match="doesn't exist, set CEL_UPDATE_GOLDEN=1 to create it"):
+        assert_trace(tmp_path / "missing.txt", "true")
+
+
+def test_assert_trace_updates_from_the_environment(tmp_path):
+    path = tmp_path / "trace.txt"
+    os.environ["CEL_UPDATE_GOLDEN"] = "1"
+    try:
+        assert_trace(path, "true")
+    finally:
+        del os.environ["CEL_UPDATE_GOLDEN"]
+    assert path.read_text() == "true is True\n"
+
+
+def test_golden(tmp_path):
+    golden = Golden(tmp_path, "test_policy[admin-1 2]", mode="strict")
+    assert golden.name == "test_policy_admin-1_2_"
+    assert_trace(tmp_path / "test_policy_admin-1_2_-big.txt", "x > 1", {"x": 2}, update=True)
+    golden("x > 1", {"x": 2}, name="big")
+    with pytest.raises(AssertionError, match="test_policy_admin-1_2_.txt doesn't exist"):
+        golden("x > 1", {"x": 2})
+    # Traces are made in the golden's mode
+    with pytest.raises(ValueError):
+        golden("x + 0.5 > 1", {"x": 2}, name="big")
+
+
+def test_pytest_fixtures(tmp_path):
+    # The fixtures are made with pytest.fixture when first looked up, checked
+    # here with a stand-in for pytest
+    (tmp_path / "pytest.py").write_text("def fixture(function):\n    return ('fixture', function)\n")
+    script = textwrap.dedent(
+        """
+        import pathlib, types
+        import cel.testing
+
+        assert {"cel_mode", "cel_golden"} <= set(dir(cel.testing))
+        kind, cel_mode = cel.testing.cel_mode
+        assert kind == "fixture" and cel_mode() is None
+        kind, cel_golden = cel.testing.cel_golden
+        request = types.SimpleNamespace(
+            path=pathlib.Path("/project/tests/test_policy.py"),
+            node=types.SimpleNamespace(name="test_admin[1]"),
+        )
+        golden = cel_golden(request=request, cel_mode="strict")
+        assert pathlib.Path(golden.directory) == pathlib.Path("/project/tests/golden")
+        assert golden.name == "test_admin_1_"
+        assert cel.testing.cel_golden is cel.testing.cel_golden
+        """
+    )
+    path = os.pathsep.join([str(tmp_path), *sys.path])
+    subprocess.run([sys.executable, "-c", script], check=True, cwd=tmp_path, env={**os.environ, "PYTHONPATH": path})
+    with pytest.raises(AttributeError):
+        cel.testing.missing
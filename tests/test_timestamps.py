@@ -0,0 +1,95 @@
+import datetime
+
+import pytest
+
+import cel
+
+UTC = datetime.timezone.utc
+
+
+@pytest.mark.parametrize("text,expected", [
+    ("2024-01-02T03:04:05Z", datetime.datetime(2024, 1, 2, 3, 4, 5, tzinfo=UTC)),
+    ("2024-01-02T03:04:05.5+01:00",
+     datetime.datetime(2024, 1, 2, 3, 4, 5, 500000, tzinfo=datetime.timezone(datetime.timedelta(hours=1)))),
+    ("2024-01-02T03:04:05.123456789-05:30",
+     datetime.datetime(2024, 1, 2, 3, 4, 5, 123456,
+                       tzinfo=datetime.timezone(-datetime.timedelta(hours=5, minutes=30)))),
+])
+def test_rfc3339_timestamps(text, expected):
+    assert cel.evaluate(f"timestamp('{text}')") == expected
+    assert cel.evaluate(f"timestamp('{text}')", mode="strict") == expected
+
+
+@pytest.mark.parametrize("text,expected", [
+    ("2024-01-02 03:04:05Z", datetime.datetime(2024, 1, 2, 3, 4, 5, tzinfo=UTC)),
+    ("2024-01-02T03:04:05+0100",
+     datetime.datetime(2024, 1, 2, 3, 4, 5, tzinfo=datetime.timezone(datetime.timedelta(hours=1)))),
+    ("2024-01-02T03:04:05+01",
+     datetime.datetime(2024, 1, 2, 3, 4, 5, tzinfo=datetime.timezone(datetime.timedelta(hours=1)))),
+    ("2024-01-02T03:04:05,25Z", datetime.datetime(2024, 1, 2, 3, 4, 5, 250000, tzinfo=UTC)),
+    ("2024-01-02T03:04:05", datetime.datetime(2024, 1, 2, 3, 4, 5, tzinfo=UTC)),
+    ("2024-01-02 03:04", datetime.datetime(2024, 1, 2, 3, 4, tzinfo=UTC)),
+    ("2024-01-02", datetime.datetime(2024, 1, 2, tzinfo=UTC)),
+])
+def test_lenient_timestamps(text, expected):
+    assert cel.evaluate(f"timestamp('{text}')") == expected
+    with pytest.raises(ValueError, match="RFC 3339"):
+        cel.evaluate(f"timestamp('{text}')", mode="strict")
+
+
+def test_invalid_timestamp():
+    with pytest.raises(ValueError, match="cannot parse"):
+        cel.evaluate("timestamp('yesterday')")
+
+
+@pytest.mark.parametrize("expression", [
+    "timestamp(1717000000)",
+    "timestamp(1717000000000, 'ms')",
+    "timestamp(1717000000000000, 'us')",
+    "timestamp(1717000000000000000, 'ns')",
+    "timestamp(1717000000u)",
+    "timestamp(1717000000.0)",
+])
+def test_epoch_timestamps(expression):
+    assert cel.evaluate(expression) == datetime.datetime(2024, 5, 29, 16, 26, 40, tzinfo=UTC)
+
+
+def test_fractional_epoch_seconds():
+    assert cel.evaluate("timestamp(1717000000.25)") == datetime.datetime(2024, 5, 29, 16, 26, 40, 250000, tzinfo=UTC)
+
+
+def test_epoch_seconds_in_strict_mode():
+    assert cel.evaluate("timestamp(0)", mode="strict") == datetime.datetime(1970, 1, 1, tzinfo=UTC)
+    with pytest.raises(ValueError, match="cannot convert float"):
+        cel.evaluate("timestamp(0.5)", mode="strict")
+    with pytest.raises(ValueError, match="argument count"):
+        cel.evaluate("timestamp(0, 'ms')", mode="strict")
+
+
+@pytest.mark.parametrize("expression,message", [
+    ("timestamp(1, 'h')", "unknown unit"),
+    ("timestamp(1e300)", "out of range"),
+    ("timestamp('2024-01-02', 's')", "unit can only be given with a number"),
+])
+def test_invalid_epoch_timestamps(expression, message):
+    with pytest.raises(ValueError, match=message):
+        cel.evaluate(expression)
+
+
+def test_timestamps_from_context():
+    assert cel.evaluate("timestamp(created) < timestamp(updated)",
+                        {"created": "2024-01-02 03:04:05", "updated": 1717000000})
+
+
+def test_mode_applies_to_programs_and_predicates():
+    program = cel.Program("timestamp('2024-01-02') < timestamp(1717000000)")
+    assert program.evaluate() is True
+    with pytest.raises(ValueError, match="RFC 3339"):
+        program.evaluate(mode="strict")
+    with pytest.raises(ValueError, match="RFC 3339"):
+        cel.evaluate_predicate("timestamp('2024-01-02') < now", {"now": 0}, mode="strict")
+
+
+def test_invalid_mode():
+    with pytest.raises(ValueError, match="mode must be"):
+        cel.evaluate("1", mode="lenient")
@@ -0,0 +1,98 @@
+import pytest
+
+import cel
+
+
+def kinds(expression):
+    return [(token["kind"], token["text"]) for token in cel.tokenize(expression)]
+
+
+def test_tokens_and_spans():
+    assert cel.tokenize("size(name) > 3") == [
+        {"kind": "identifier", "text": "size", "start": 0, "end": 4},
+        {"kind": "punctuation", "text": "(", "start": 4, "end": 5},
+        {"kind": "identifier", "text": "name", "start": 5, "end": 9},
+        {"kind": "punctuation", "text": ")", "start": 9, "end": 10},
+        {"kind": "operator", "text": ">", "start": 11, "end": 12},
+        {"kind": "int", "text": "3", "start": 13, "end": 14},
+    ]
+
+
+def test_empty_and_whitespace():
+    assert cel.tokenize("") == []
+    assert cel.tokenize("  \n\t") == []
+
+
+@pytest.mark.parametrize(
+    "expression,expected",
+    [
+        ("42", [("int", "42")]),
+        ("0x1F", [("int", "0x1F")]),
+        ("42u", [("uint", "42u")]),
+        ("0xffU", [("uint", "0xffU")]),
+        ("1.5", [("float", "1.5")]),
+        (".5e-3", [("float", ".5e-3")]),
+        ("2e10", [("float", "2e10")]),
+        ('"a \\" b"', [("string", '"a \\" b"')]),
+        ("'single'", [("string", "'single'")]),
+        ('r"raw\\d"', [("string", 'r"raw\\d"')]),
+        ("b'\\x00'", [("bytes", "b'\\x00'")]),
+        ("true", [("keyword", "true")]),
+        ("null", [("keyword", "null")]),
+        ("nullable", [("identifier", "nullable")]),
+        ("_x1", [("identifier", "_x1")]),
+    ],
+)
+def test_literals(expression, expected):
+    assert kinds(expression) == expected
+
+
+def test_operators_take_the_longest_match():
+    assert kinds("a <= b && !!c != d || e in f") == [
+        ("identifier", "a"),
+        ("operator", "<="),
+        ("identifier", "b"),
+        ("operator", "&&"),
+        ("operator", "!!"),
+        ("identifier", "c"),
+        ("operator", "!="),
+        ("identifier", "d"),
+        ("operator", "||"),
+        ("identifier", "e"),
+        ("keyword", "in"),
+        ("identifier", "f"),
+    ]
+    assert kinds("a ? b : c")[1::2] == [("operator", "?"), ("operator", ":")]
+
+
+def test_signs_are_part_of_numbers_like_in_the_parser():
+    assert kinds("x-1") == [("identifier", "x"), ("int", "-1")]
+    assert kinds("x - y") == [("identifier", "x"), ("operator", "-"), ("identifier", "y")]
+    with pytest.raises(ValueError):
+        cel.evaluate("x-1", {"x": 2})
+
+
+def test_comments_are_kept():
+    assert kinds("a // the answer\n+ b") == [
+        ("identifier", "a"),
+        ("comment", "// the answer"),
+        ("operator", "+"),
+        ("identifier", "b"),
+    ]
+
+
+def test_unrecognised_characters_are_errors():
+    assert kinds('name == "unterminated') == [
+        ("identifier", "name"),
+        ("operator", "=="),
+        ("error", '"'),
+        ("identifier", "unterminated"),
+    ]
+    assert kinds("a = b")[1] == ("error", "=")
+
+
+def test_offsets_count_characters():
+    tokens = cel.tokenize("'héllo' + x")
+    assert tokens[0]["end"] == 7
+    assert tokens[-1] == {"kind": "identifier", "text": "x", "start": 10, "end": 11}
+    assert "'héllo' + x"[tokens[-1]["start"]:tokens[-1]["end"]] == "x"
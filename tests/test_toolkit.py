@@ -0,0 +1,103 @@
+import pytest
+
+import cel
+
+
+@pytest.fixture
+def toolkit():
+    return cel.Context(extensions=["toolkit"])
+
+
+def test_toolkit_is_opt_in():
+    with pytest.raises(ValueError, match="Undeclared reference to 'slugify'"):
+        cel.evaluate("slugify('a b')")
+
+
+def test_toolkit_is_listed_as_an_extension(toolkit):
+    assert toolkit.extensions == ["toolkit"]
+
+
+@pytest.mark.parametrize(
+    "text,slug",
+    [
+        ("Hello, World!", "hello-world"),
+        ("  --Already-a-slug--  ", "already-a-slug"),
+        ("Café au lait 2", "café-au-lait-2"),
+        ("!!!", ""),
+    ],
+)
+def test_slugify(toolkit, text, slug):
+    toolkit.update({"text": text})
+    assert cel.evaluate("slugify(text)", toolkit) == slug
+    assert cel.evaluate("text.slugify()", toolkit) == slug
+
+
+@pytest.mark.parametrize(
+    "a,b,distance",
+    [("kitten", "sitting", 3), ("", "abc", 3), ("same", "same", 0), ("naïve", "naive", 1)],
+)
+def test_levenshtein(toolkit, a, b, distance):
+    toolkit.update({"a": a, "b": b})
+    assert cel.evaluate("levenshtein(a, b)", toolkit) == distance
+    assert cel.evaluate("a.levenshtein(b)", toolkit) == distance
+
+
+@pytest.mark.parametrize(
+    "text,pattern,matches",
+    [
+        ("reports/2024.csv", "reports/*.csv", True),
+        ("reports/2024.csv", "*.txt", False),
+        ("a/b/c", "a*c", True),
+        ("file1", "file?", True),
+        ("file10", "file?", False),
+        ("b", "[abc]", True),
+        ("d", "[a-c]", False),
+        ("d", "[!a-c]", True),
+        ("]", "[]]", True),
+        ("[x", "[x", True),
+        ("", "*", True),
+        ("aaab", "*a*b", True),
+    ],
+)
+def test_glob_match(toolkit, text, pattern, matches):
+    toolkit.update({"text": text, "pattern": pattern})
+    assert cel.evaluate("globMatch(text, pattern)", toolkit) is matches
+    assert cel.evaluate("text.globMatch(pattern)", toolkit) is matches
+
+
+@pytest.mark.parametrize(
+    "line,fields",
+    [
+        ("a,b,c", ["a", "b", "c"]),
+        ('a,"b, c",""""', ["a", "b, c", '"']),
+        ('x"y,z', ['x"y', "z"]),
+        ("a,,", ["a", "", ""]),
+        ("", [""]),
+    ],
+)
+def test_split_csv(toolkit, line, fields):
+    toolkit.update({"line": line})
+    assert cel.evaluate("splitCsv(line)", toolkit) == fields
+    assert cel.evaluate("line.splitCsv()", toolkit) == fields
+
+
+def test_toolkit_functions_check_their_arguments(toolkit):
+    with pytest.raises(ValueError, match="expected a string, got int"):
+        cel.evaluate("slugify(1)", toolkit)
+    with pytest.raises(ValueError):
+        cel.evaluate("levenshtein('a')", toolkit)
+
+
+def test_python_functions_replace_toolkit_functions():
+    context = cel.Context(functions={"slugify": str.upper}, extensions=["toolkit"])
+    assert cel.evaluate("slugify('a b')", context) == "A B"
+
+
+def test_toolkit_functions_are_described(toolkit):
+    info = toolkit.functions_info()
+    assert info["levenshtein"]["builtin"] is True
+    assert "levenshtein" not in cel.Context().functions_info()
+    [problem] = cel.diagnose("globMatch(path, '*.py')", {"path": "a.py"})
+    assert problem["code"] == "undeclared-function"
+    context = cel.Context({"path": "a.py"}, extensions=["toolkit"])
+    assert cel.diagnose("globMatch(path, '*.py')", context) == []
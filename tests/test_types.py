@@ -0,0 +1,66 @@
+import datetime
+
+import pytest
+
+import cel
+
+
+@pytest.mark.parametrize("expression", [
+    "type(1) == int",
+    "type(1u) == uint",
+    "type(1.5) == double",
+    "type('a') == string",
+    "type(b'a') == bytes",
+    "type(true) == bool",
+    "type(null) == null_type",
+    "type([1]) == list",
+    "type({'a': 1}) == map",
+    "type(int) == type",
+    "type(type(1)) == type",
+    "type(timestamp('2024-01-02T03:04:05Z')) == google.protobuf.Timestamp",
+    "type(duration('1s')) == google.protobuf.Duration",
+    "type(1) == type(2)",
+    "type(1) != uint",
+])
+def test_type_comparisons(expression):
+    assert cel.Program(expression).evaluate() is True
+    assert cel.Program(expression, optimize=True).evaluate() is True
+
+
+def test_type_of_context_values():
+    context = {
+        "name": "alice",
+        "created": datetime.datetime(2024, 1, 2, tzinfo=datetime.timezone.utc),
+        "tags": ["a"],
+    }
+    assert cel.evaluate("type(name) == string && type(tags) == list", context)
+    assert cel.evaluate("type(created) == google.protobuf.Timestamp", context)
+
+
+def test_type_converts_to_cel_type():
+    result = cel.evaluate("type(1)")
+    assert isinstance(result, cel.CelType)
+    assert result.name == "int"
+    assert str(result) == "int"
+    assert repr(result) == "CelType('int')"
+    assert result == cel.CelType("int")
+    assert result != cel.CelType("uint")
+    assert cel.evaluate("[1, 'a'].map(v, type(v))") == [cel.CelType("int"), cel.CelType("string")]
+
+
+def test_cel_types_are_hashable():
+    assert {cel.CelType("int"), cel.evaluate("int")} == {cel.CelType("int")}
+
+
+def test_cel_type_as_variable():
+    assert cel.evaluate("type(x) == t", {"x": 1.5, "t": cel.CelType("double")})
+    assert cel.evaluate("t", {"t": cel.CelType("map")}) == cel.CelType("map")
+
+
+def test_context_variables_shadow_type_names():
+    assert cel.evaluate("int + 1", {"int": 1}) == 2
+
+
+def test_predicate_error_names_cel_type():
+    with pytest.raises(TypeError, match="got double"):
+        cel.evaluate_predicate("1.5")
@@ -0,0 +1,63 @@
+import pytest
+
+import cel
+
+
+def test_unknown_attribute_prevents_decision():
+    result = cel.evaluate(
+        "request.time > start",
+        {'request': {}, 'start': 1},
+        unknowns=["request.time"],
+    )
+    assert isinstance(result, cel.Unknown)
+    assert result.attributes == ["request.time"]
+    assert repr(result) == "Unknown(attributes=['request.time'])"
+
+
+def test_known_attributes_still_evaluate():
+    result = cel.evaluate(
+        "request.user == 'alice'",
+        {'request': {'user': 'alice'}},
+        unknowns=["request.time"],
+    )
+    assert result == True
+
+
+def test_index_syntax_matches_unknown_attribute():
+    result = cel.evaluate("request['time'] > 1", {'request': {}}, unknowns=["request.time"])
+    assert result.attributes == ["request.time"]
+
+
+def test_whole_variable_unknown():
+    result = cel.evaluate("resource.owner == user", {'user': 'alice'}, unknowns=["resource"])
+    assert result.attributes == ["resource"]
+
+
+def test_and_absorbs_unknown_when_other_side_false():
+    context = {'user': {'admin': False}}
+    assert cel.evaluate("request.time > 1 && user.admin", context, unknowns=["request.time"]) == False
+    assert cel.evaluate("user.admin && request.time > 1", context, unknowns=["request.time"]) == False
+
+
+def test_or_absorbs_unknown_when_other_side_true():
+    context = {'user': {'admin': True}}
+    assert cel.evaluate("request.time > 1 || user.admin", context, unknowns=["request.time"]) == True
+
+
+def test_unknowns_from_both_sides_are_merged():
+    result = cel.evaluate(
+        "request.time > 1 && resource.owner == 'alice'",
+        {},
+        unknowns=["request.time", "resource.owner"],
+    )
+    assert result.attributes == ["request.time", "resource.owner"]
+
+
+def test_unknown_takes_priority_over_error():
+    result = cel.evaluate("missing > 1 || request.time > 1", {}, unknowns=["request.time"])
+    assert result.attributes == ["request.time"]
+
+
+def test_predicate_rejects_unknown():
+    with pytest.raises(TypeError, match="unknown"):
+        cel.evaluate_predicate("request.time > 1", {}, unknowns=["request.time"])
@@ -0,0 +1,69 @@
+import pytest
+
+import cel
+
+RULES = [
+    {"id": "replicas", "rule": "self.replicas <= 10", "message": "{name} has {replicas} replicas, at most 10 are allowed", "path": "spec.replicas"},
+    {"id": "name", "rule": "size(self.name) > 0", "message": "name is required", "path": "metadata.name"},
+    {"rule": "self.replicas >= self.min"},
+]
+
+
+def test_valid_object():
+    validator = cel.Validator(RULES)
+    assert validator.validate({"name": "web", "replicas": 3, "min": 1}) == []
+    assert validator.is_valid({"name": "web", "replicas": 3, "min": 1})
+
+
+def test_violations():
+    validator = cel.Validator(RULES)
+    violations = validator.validate({"name": "web", "replicas": 12, "min": 20})
+    assert [v.id for v in violations] == ["replicas", "self.replicas >= self.min"]
+    replicas, minimum = violations
+    assert replicas.message == "web has 12 replicas, at most 10 are allowed"
+    assert replicas.path == "spec.replicas"
+    assert replicas.reason == "invalid"
+    assert minimum.message == "failed rule: self.replicas >= self.min"
+    assert minimum.path is None
+    assert not validator.is_valid({"name": "", "replicas": 1, "min": 1})
+
+
+def test_messages_can_use_self():
+    validator = cel.Validator([{"rule": "self > 0", "message": "{self} is not positive"}])
+    assert validator.validate(-1)[0].message == "-1 is not positive"
+
+
+def test_rules_that_fail_to_evaluate_are_errors():
+    validator = cel.Validator([{"id": "size", "rule": "self.missing > 1"}, {"id": "type", "rule": "self.name"}])
+    violations = validator.validate({"name": "x"})
+    assert [(v.id, v.reason) for v in violations] == [("size", "error"), ("type", "error")]
+    assert "missing" in violations[0].message
+    assert violations[1].message == "expected the rule to evaluate to a bool, got string"
+    assert "reason='error'" in repr(violations[1])
+
+
+def test_repr():
+    validator = cel.Validator([
+        {"id": "replicas", "rule": "self.replicas <= 10", "message": "can't be \"more\" than 10", "path": "spec.replicas"},
+        {"rule": "self.name != ''"},
+    ])
+    replicas, name = validator.validate({"replicas": 11, "name": ""})
+    assert repr(replicas) == (
+        "Violation(id='replicas', message='can\\'t be \"more\" than 10', path='spec.replicas', reason='invalid')"
+    )
+    assert repr(name) == "Violation(id=\"self.name != ''\", message=\"failed rule: self.name != ''\", path=None, reason='invalid')"
+
+
+def test_mode():
+    rules = [{"rule": "self.count * 1.5 > 2.0"}]
+    assert cel.Validator(rules).is_valid({"count": 2})
+    assert cel.Validator(rules, mode="strict").validate({"count": 2})[0].reason == "error"
+
+
+def test_invalid_rules():
+    with pytest.raises(ValueError, match="must have a 'rule'"):
+        cel.Validator([{"message": "no rule"}])
+    with pytest.raises(ValueError, match="must be a dict"):
+        cel.Validator(["self > 1"])
+    with pytest.raises(ValueError, match="Failed to compile"):
+        cel.Validator([{"rule": "self >"}])
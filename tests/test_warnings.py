@@ -0,0 +1,71 @@
+import warnings
+
+import pytest
+
+import cel
+
+
+def test_numeric_promotion_warns():
+    with pytest.warns(cel.CELPromotionWarning, match="relies on numeric promotion"):
+        assert cel.evaluate("1 + 2.0") == 3.0
+
+
+def test_truthiness_warns():
+    with pytest.warns(cel.CELTruthinessWarning, match="the condition of '\\?:' must be a bool"):
+        assert cel.evaluate("1 ? 'yes' : 'no'") == "yes"
+
+
+def test_duplicate_map_keys_warn():
+    with pytest.warns(cel.CELDuplicateKeyWarning, match="duplicate map key 'a'"):
+        assert cel.evaluate("{'a': 1, 'a': 2}") == {"a": 2}
+
+
+def test_categories():
+    assert issubclass(cel.CELWarning, UserWarning)
+    for category in [cel.CELPromotionWarning, cel.CELTruthinessWarning, cel.CELDuplicateKeyWarning]:
+        assert issubclass(category, cel.CELWarning)
+
+
+@pytest.mark.parametrize(
+    "expression",
+    ["1 + 2", "1.0 + 2.0", "x + 2.0", "1 < 2.0", "size('ab') > 0 && true", "{'a': 1, 'b': 2}"],
+)
+def test_expressions_that_follow_the_specification_dont_warn(expression):
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        cel.evaluate(expression, {"x": 1.5})
+
+
+def test_no_warning_for_a_disabled_convenience():
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        with pytest.raises(ValueError, match="without numeric promotion"):
+            cel.evaluate("1 + 2.0", mode="strict")
+
+
+def test_escalating_to_errors():
+    with warnings.catch_warnings():
+        warnings.simplefilter("error", cel.CELWarning)
+        with pytest.raises(cel.CELPromotionWarning, match="'1 \\+ 2.0' relies on numeric promotion"):
+            cel.evaluate("1 + 2.0")
+        with pytest.raises(cel.CELTruthinessWarning):
+            cel.evaluate_predicate("!1")
+
+
+def test_programs_warn_on_each_evaluation():
+    program = cel.Program("[1, 2.5].map(x, x * 2) == [2, 5.0] && 2 * 1.5 == 3.0")
+    for _ in range(2):
+        with pytest.warns(cel.CELPromotionWarning):
+            assert program.evaluate() is True
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        with pytest.raises(ValueError):
+            program.evaluate(mode="strict")
+
+
+def test_context_mode():
+    context = cel.Context(mode="strict")
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        with pytest.raises(ValueError):
+            cel.evaluate("1 + 2.0", context)